@@ -0,0 +1,143 @@
+//! Borrowed query inputs: `&str` against the `String` taking shims of 6.1 signatures,
+//! heap allocations per call counted and printed next to criterion times
+
+#![allow(deprecated)]
+
+mod common;
+
+use common::{options, runtime, temp_dir};
+use criterion::{criterion_group, criterion_main, Criterion};
+use darkbird::{
+    document::{Document, FullText, Indexer, MaterializedView, Range, RangeField, Tags},
+    workload::text,
+    Storage, StorageType,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const DOCS: usize = 10_000;
+const CALLS: usize = 10_000;
+
+type Case<'a> = (&'static str, Box<dyn Fn() + 'a>);
+
+
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Note {
+    code: String,
+    body: String,
+}
+
+impl Document for Note {}
+
+impl Indexer for Note {
+    fn extract(&self) -> Vec<String> {
+        vec![format!("code:{}", self.code)]
+    }
+}
+
+impl Tags for Note {
+    fn get_tags(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Range for Note {
+    fn get_fields(&self) -> Vec<RangeField> {
+        vec![RangeField { name: "code".to_owned(), value: self.code.clone() }]
+    }
+}
+
+impl MaterializedView for Note {
+    fn filter(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FullText for Note {
+    fn get_content(&self) -> Option<String> {
+        Some(self.body.clone())
+    }
+}
+
+
+
+// heap allocations per call of f, averaged over CALLS
+fn allocations(mut f: impl FnMut()) -> f64 {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..CALLS {
+        f();
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / CALLS as f64
+}
+
+fn borrowed(c: &mut Criterion) {
+    let rt = runtime();
+    let dir = temp_dir("borrowed");
+
+    let storage = rt.block_on(async {
+        let storage = Storage::<usize, Note>::open(options(&dir, "notes", StorageType::RamCopies)).await.unwrap();
+        for i in 0..DOCS {
+            let note = Note { code: format!("{:05}", i), body: text(64, i as u64) };
+            storage.insert(i, note).await.unwrap();
+        }
+        storage
+    });
+
+    let cases: [Case; 6] = [
+        ("lookup_by_index/str", Box::new(|| drop(black_box(storage.lookup_by_index("code:00042").unwrap())))),
+        ("lookup_by_index/string", Box::new(|| drop(black_box(storage.lookup_by_index(&format!("code:{:05}", 42)).unwrap())))),
+        ("search/str", Box::new(|| drop(black_box(storage.search("zebra"))))),
+        ("search/string", Box::new(|| drop(black_box(storage.search_string("zebra".to_owned()))))),
+        ("range/str", Box::new(|| drop(black_box(storage.range("code", "00100", "00102"))))),
+        ("range/string", Box::new(|| {
+            drop(black_box(storage.range_string("code", "00100".to_owned(), "00102".to_owned())))
+        })),
+    ];
+
+    for (name, f) in cases.iter() {
+        println!("{}: {:.2} allocations per call", name, allocations(f));
+    }
+
+    let mut group = c.benchmark_group("borrowed_inputs");
+    for (name, f) in cases.iter() {
+        group.bench_function(*name, |b| b.iter(f));
+    }
+    group.finish();
+
+    drop(cases);
+    rt.block_on(storage.close()).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+criterion_group!(benches, borrowed);
+criterion_main!(benches);
@@ -0,0 +1,83 @@
+//! REMOVED of STORE documents taken out of a DiskCopies store by remove_keys, remove_where
+//! and remove_prefix, timed until the store is closed and records are written and fsynced.
+//! keys below 2^16 are removed, so the three remove the same keys: listed, matched by
+//! predicate, and sharing six zero bytes of their big-endian encoding
+
+mod common;
+
+use common::{options, runtime, temp_dir, Blob};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use darkbird::{Storage, StorageType};
+use std::time::{Duration, Instant};
+
+const STORE: u64 = 100_000;
+const REMOVED: u64 = 1 << 16;
+
+
+
+#[derive(Clone, Copy, Debug)]
+enum Mode {
+    Keys,
+    Where,
+    Prefix,
+}
+
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Keys => "remove_keys",
+            Mode::Where => "remove_where",
+            Mode::Prefix => "remove_prefix",
+        }
+    }
+}
+
+fn blob(key: u64) -> Blob {
+    Blob { data: key.to_le_bytes().repeat(8) }
+}
+
+async fn bulk_remove(mode: Mode) -> Duration {
+    let dir = temp_dir("bulk-remove");
+    let storage = Storage::<u64, Blob>::open(options(&dir, "bulk_bench", StorageType::DiskCopies)).await.unwrap();
+    storage.insert_batch((0..STORE).map(|key| (key, blob(key))).collect()).await.unwrap();
+
+    let started = Instant::now();
+    let progress = match mode {
+        Mode::Keys => storage.remove_keys((0..REMOVED).collect()).await.unwrap(),
+        Mode::Where => storage.remove_where(|key, _| *key < REMOVED).await.unwrap(),
+        Mode::Prefix => storage.remove_prefix(&[0; 6]).await.unwrap(),
+    };
+    storage.close().await.unwrap();
+    let took = started.elapsed();
+
+    assert_eq!(progress.removed as u64, REMOVED, "{}", mode.name());
+    let _ = std::fs::remove_dir_all(&dir);
+    took
+}
+
+fn bulk_removes(c: &mut Criterion) {
+    let rt = runtime();
+
+    let mut group = c.benchmark_group("bulk_remove");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(REMOVED));
+
+    for mode in [Mode::Keys, Mode::Where, Mode::Prefix] {
+        group.bench_function(mode.name(), |b| {
+            b.iter_custom(|iters| {
+                rt.block_on(async {
+                    let mut took = Duration::ZERO;
+                    for _ in 0..iters {
+                        took += bulk_remove(mode).await;
+                    }
+                    took
+                })
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bulk_removes);
+criterion_main!(benches);
@@ -0,0 +1,137 @@
+//! 100KB already serialized payloads: BytesStorage write them to WAL as is,
+//! Storage serialize them with bincode on every write
+
+mod common;
+
+use bytes::Bytes;
+use common::{options, runtime, temp_dir, Blob};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use darkbird::{storage_bytes::{BytesStorage, Extractors}, Storage, StorageType};
+use std::time::{Duration, Instant};
+
+const BLOB: usize = 100 * 1024;
+
+// writes to one store before it's dropped with its log, bound disk used by a run
+const ROUND: u64 = 256;
+
+
+
+fn blob() -> Vec<u8> {
+    (0..BLOB).map(|i| (i % 251) as u8).collect()
+}
+
+fn insert(c: &mut Criterion) {
+    let rt = runtime();
+    let payload = blob();
+
+    let mut group = c.benchmark_group("blob_100kb_insert");
+    group.throughput(Throughput::Bytes(BLOB as u64));
+    group.sample_size(10);
+
+    group.bench_function("bytes_store", |b| {
+        let doc = Bytes::from(payload.clone());
+        b.iter_custom(|iters| {
+            rt.block_on(async {
+                let mut took = Duration::ZERO;
+                let mut done = 0;
+                while done < iters {
+                    let dir = temp_dir("bytes");
+                    let storage = BytesStorage::<u64>::open(options(&dir, "blobs", StorageType::DiskCopies), Extractors::new())
+                        .await
+                        .unwrap();
+
+                    let round = ROUND.min(iters - done);
+                    let started = Instant::now();
+                    for i in 0..round {
+                        storage.insert(i, doc.clone()).await.unwrap();
+                    }
+                    took += started.elapsed();
+                    done += round;
+
+                    drop(storage);
+                    let _ = std::fs::remove_dir_all(&dir);
+                }
+                took
+            })
+        })
+    });
+
+    group.bench_function("serde_store", |b| {
+        let doc = Blob { data: payload.clone() };
+        b.iter_custom(|iters| {
+            rt.block_on(async {
+                let mut took = Duration::ZERO;
+                let mut done = 0;
+                while done < iters {
+                    let dir = temp_dir("serde");
+                    let storage = Storage::<u64, Blob>::open(options(&dir, "blobs", StorageType::DiskCopies))
+                        .await
+                        .unwrap();
+
+                    let round = ROUND.min(iters - done);
+                    let started = Instant::now();
+                    for i in 0..round {
+                        storage.insert(i, doc.clone()).await.unwrap();
+                    }
+                    took += started.elapsed();
+                    done += round;
+
+                    storage.close().await.unwrap();
+                    let _ = std::fs::remove_dir_all(&dir);
+                }
+                took
+            })
+        })
+    });
+
+    group.finish();
+}
+
+fn lookup(c: &mut Criterion) {
+    let rt = runtime();
+    let payload = blob();
+    let dir = temp_dir("lookup");
+
+    let (bytes_store, serde_store) = rt.block_on(async {
+        let bytes_store = BytesStorage::<u64>::open(options(&dir, "bytes", StorageType::RamCopies), Extractors::new())
+            .await
+            .unwrap();
+        let serde_store = Storage::<u64, Blob>::open(options(&dir, "serde", StorageType::RamCopies))
+            .await
+            .unwrap();
+
+        for i in 0..16 {
+            bytes_store.insert(i, Bytes::from(payload.clone())).await.unwrap();
+            serde_store.insert(i, Blob { data: payload.clone() }).await.unwrap();
+        }
+        (bytes_store, serde_store)
+    });
+
+    let mut group = c.benchmark_group("blob_100kb_lookup");
+    group.throughput(Throughput::Bytes(BLOB as u64));
+
+    let mut i = 0u64;
+    group.bench_function("bytes_store", |b| {
+        b.iter(|| {
+            i = (i + 1) % 16;
+            bytes_store.lookup(&i).unwrap()
+        })
+    });
+
+    // owned copy, what a caller handing the payload on would need
+    group.bench_function("serde_store_owned", |b| {
+        b.iter(|| {
+            i = (i + 1) % 16;
+            serde_store.lookup_owned(&i).unwrap()
+        })
+    });
+
+    group.finish();
+
+    drop(bytes_store);
+    rt.block_on(serde_store.close()).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+criterion_group!(benches, insert, lookup);
+criterion_main!(benches);
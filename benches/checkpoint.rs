@@ -0,0 +1,150 @@
+//! Reopen of a store from its newest checkpoint plus a WAL tail, against full WAL replay.
+//! ENTRIES documents (BENCH_ENTRIES, 200k by default, 10M for request scale) with
+//! an index key, a tag and text, so replay rebuild every derived structure
+
+mod common;
+
+use common::{runtime, temp_dir};
+use criterion::{criterion_group, criterion_main, Criterion};
+use darkbird::{
+    document::{Document, FullText, Indexer, MaterializedView, Range, Tags},
+    workload::text,
+    Options, Storage, StorageType,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+// writes after checkpoint, replayed by both
+const TAIL: u64 = 1000;
+
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Record {
+    id: u64,
+    group: u64,
+    text: String,
+}
+
+impl Document for Record {}
+
+impl Indexer for Record {
+    fn extract(&self) -> Vec<String> {
+        vec![format!("id:{}", self.id)]
+    }
+}
+
+impl Tags for Record {
+    fn get_tags(&self) -> Vec<String> {
+        vec![format!("group:{}", self.group)]
+    }
+}
+
+impl Range for Record {}
+
+impl MaterializedView for Record {
+    fn filter(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FullText for Record {
+    fn get_content(&self) -> Option<String> {
+        Some(self.text.clone())
+    }
+}
+
+
+
+fn entries() -> u64 {
+    std::env::var("BENCH_ENTRIES").ok().and_then(|n| n.parse().ok()).unwrap_or(200_000)
+}
+
+fn options(dir: &Path) -> Options {
+    Options::new(dir.to_str().unwrap(), "records", 100_000, StorageType::DiskCopies, true)
+}
+
+fn record(id: u64) -> Record {
+    Record { id, group: id % 100, text: text(48, id) }
+}
+
+// store of entries documents, checkpointed before tail if asked, closed
+async fn fill(dir: &Path, entries: u64, checkpoint: bool) {
+    let storage = Storage::<u64, Record>::open(options(dir)).await.unwrap();
+    for id in 0..entries {
+        storage.insert(id, record(id)).await.unwrap();
+    }
+    if checkpoint {
+        storage.checkpoint().await.unwrap();
+    }
+    for id in entries..entries + TAIL {
+        storage.insert(id, record(id)).await.unwrap();
+    }
+    storage.close().await.unwrap();
+}
+
+// time of open alone, store closed after
+async fn reopen(dir: &Path, entries: u64) -> Duration {
+    let started = Instant::now();
+    let storage = Storage::<u64, Record>::open(options(dir)).await.unwrap();
+    let took = started.elapsed();
+
+    assert_eq!(storage.len() as u64, entries + TAIL);
+    storage.close().await.unwrap();
+    took
+}
+
+fn reopen_bench(c: &mut Criterion) {
+    let rt = runtime();
+    let entries = entries();
+    let (checkpointed, replayed) = (temp_dir("checkpoint"), temp_dir("replay"));
+
+    rt.block_on(async {
+        fill(&checkpointed, entries, true).await;
+        fill(&replayed, entries, false).await;
+    });
+
+    let (from_checkpoint, full) = rt.block_on(async { (reopen(&checkpointed, entries).await, reopen(&replayed, entries).await) });
+    println!(
+        "reopen of {} documents + {} tail: checkpoint {:?}, full replay {:?}",
+        entries, TAIL, from_checkpoint, full
+    );
+
+    let mut group = c.benchmark_group("reopen");
+    group.sample_size(10);
+
+    group.bench_function("checkpoint_and_tail", |b| {
+        b.iter_custom(|iters| {
+            rt.block_on(async {
+                let mut took = Duration::ZERO;
+                for _ in 0..iters {
+                    took += reopen(&checkpointed, entries).await;
+                }
+                took
+            })
+        })
+    });
+
+    group.bench_function("full_replay", |b| {
+        b.iter_custom(|iters| {
+            rt.block_on(async {
+                let mut took = Duration::ZERO;
+                for _ in 0..iters {
+                    took += reopen(&replayed, entries).await;
+                }
+                took
+            })
+        })
+    });
+
+    group.finish();
+
+    let _ = std::fs::remove_dir_all(&checkpointed);
+    let _ = std::fs::remove_dir_all(&replayed);
+}
+
+criterion_group!(benches, reopen_bench);
+criterion_main!(benches);
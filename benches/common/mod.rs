@@ -0,0 +1,72 @@
+#![allow(dead_code)]
+
+use darkbird::{
+    document::{Document, FullText, Indexer, MaterializedView, Range, Tags},
+    Options, StorageType,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+
+
+/// Opaque payload stored through serde
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Blob {
+    pub data: Vec<u8>,
+}
+
+impl Document for Blob {}
+
+impl Indexer for Blob {
+    fn extract(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Tags for Blob {
+    fn get_tags(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Range for Blob {}
+
+impl MaterializedView for Blob {
+    fn filter(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FullText for Blob {
+    fn get_content(&self) -> Option<String> {
+        None
+    }
+}
+
+
+
+/// empty directory under system temp dir, unique per call
+pub fn temp_dir(name: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = std::env::temp_dir().join(format!(
+        "darkbird-bench-{}-{}-{}",
+        name,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::SeqCst)
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub fn options(dir: &std::path::Path, name: &str, stype: StorageType) -> Options {
+    Options::new(dir.to_str().unwrap(), name, 1000, stype, true)
+}
+
+pub fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap()
+}
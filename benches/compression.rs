@@ -0,0 +1,115 @@
+//! Value compression of 64KB text documents: memory held by store and lookup latency,
+//! memory is measured by counting live heap bytes
+
+mod common;
+
+use common::{options, runtime, temp_dir};
+use criterion::{criterion_group, criterion_main, Criterion};
+use darkbird::{
+    workload::{self, WorkloadDoc},
+    Compression, Options, Storage, StorageType,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const DOCS: usize = 1000;
+const DOC_SIZE: usize = 64 * 1024;
+const SEED: u64 = 0x5eed;
+
+
+
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        LIVE.fetch_add(new_size, Ordering::Relaxed);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOC: Counting = Counting;
+
+
+
+// store filled with DOCS workload documents and heap bytes it took
+fn filled(rt: &tokio::runtime::Runtime, ops: Options) -> (Storage<u64, WorkloadDoc>, usize) {
+    rt.block_on(async {
+        let before = LIVE.load(Ordering::Relaxed);
+        let storage = Storage::<u64, WorkloadDoc>::open(ops).await.unwrap();
+        for (key, doc) in workload::documents(DOCS, DOC_SIZE, SEED) {
+            storage.insert(key, doc).await.unwrap();
+        }
+        let held = LIVE.load(Ordering::Relaxed).saturating_sub(before);
+        (storage, held)
+    })
+}
+
+fn compression(c: &mut Criterion) {
+    let rt = runtime();
+    let dir = temp_dir("compression");
+
+    let (plain, plain_bytes) = filled(&rt, options(&dir, "plain", StorageType::RamCopies));
+    let (lz4, lz4_bytes) = filled(&rt, options(&dir, "lz4", StorageType::RamCopies).with_value_compression(Compression::Lz4));
+
+    println!(
+        "memory of {} documents of {}KB: plain {:.1} MiB, lz4 {:.1} MiB ({:.1}% of plain)",
+        DOCS,
+        DOC_SIZE / 1024,
+        plain_bytes as f64 / (1024.0 * 1024.0),
+        lz4_bytes as f64 / (1024.0 * 1024.0),
+        lz4_bytes as f64 * 100.0 / plain_bytes as f64
+    );
+
+    let mut group = c.benchmark_group("compression_64kb_lookup");
+    let mut i = 0u64;
+
+    // Ref of plain store, no copy
+    group.bench_function("plain_lookup", |b| {
+        b.iter(|| {
+            i = (i + 1) % DOCS as u64;
+            black_box(plain.lookup(&i).is_some())
+        })
+    });
+
+    group.bench_function("plain_lookup_owned", |b| {
+        b.iter(|| {
+            i = (i + 1) % DOCS as u64;
+            plain.lookup_owned(&i).unwrap()
+        })
+    });
+
+    group.bench_function("lz4_lookup_owned", |b| {
+        b.iter(|| {
+            i = (i + 1) % DOCS as u64;
+            lz4.lookup_owned(&i).unwrap()
+        })
+    });
+
+    group.finish();
+
+    rt.block_on(async {
+        plain.close().await.unwrap();
+        lz4.close().await.unwrap();
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+criterion_group!(benches, compression);
+criterion_main!(benches);
@@ -0,0 +1,91 @@
+//! BATCH documents into a new DiskCopies store, by one insert_batch against a loop of inserts,
+//! timed until the store is closed and records are written and fsynced.
+//! speedup of batch over loop is measured once and printed next to criterion times
+
+mod common;
+
+use common::{options, runtime, temp_dir, Blob};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use darkbird::{Storage, StorageType};
+use std::time::{Duration, Instant};
+
+const BATCH: u64 = 10_000;
+
+
+
+#[derive(Clone, Copy, Debug)]
+enum Mode {
+    Loop,
+    Batch,
+}
+
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Loop => "insert_loop",
+            Mode::Batch => "insert_batch",
+        }
+    }
+}
+
+fn blob(key: u64) -> Blob {
+    Blob { data: key.to_le_bytes().repeat(8) }
+}
+
+async fn durable_inserts(mode: Mode) -> Duration {
+    let dir = temp_dir("insert-batch");
+    let storage = Storage::<u64, Blob>::open(options(&dir, "batch_bench", StorageType::DiskCopies)).await.unwrap();
+
+    let started = Instant::now();
+    match mode {
+        Mode::Loop => {
+            for key in 0..BATCH {
+                storage.insert(key, blob(key)).await.unwrap();
+            }
+        }
+        Mode::Batch => {
+            storage.insert_batch((0..BATCH).map(|key| (key, blob(key))).collect()).await.unwrap();
+        }
+    }
+    storage.close().await.unwrap();
+    let took = started.elapsed();
+
+    let _ = std::fs::remove_dir_all(&dir);
+    took
+}
+
+fn insert_batch(c: &mut Criterion) {
+    let rt = runtime();
+
+    let (looped, batched) = rt.block_on(async { (durable_inserts(Mode::Loop).await, durable_inserts(Mode::Batch).await) });
+    println!(
+        "{} documents: loop {:?}, batch {:?}, speedup {:.1}x",
+        BATCH,
+        looped,
+        batched,
+        looped.as_secs_f64() / batched.as_secs_f64()
+    );
+
+    let mut group = c.benchmark_group("durable_inserts");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(BATCH));
+
+    for mode in [Mode::Loop, Mode::Batch] {
+        group.bench_function(mode.name(), |b| {
+            b.iter_custom(|iters| {
+                rt.block_on(async {
+                    let mut took = Duration::ZERO;
+                    for _ in 0..iters {
+                        took += durable_inserts(mode).await;
+                    }
+                    took
+                })
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, insert_batch);
+criterion_main!(benches);
@@ -0,0 +1,108 @@
+//! 99% of writes to one hot key: latency of writes to other keys, with one WAL
+//! and reporter lane and with four. each round spawn HOT writes of hot key
+//! and one of a new key, timed from its spawn; p50 and p99 are printed per lane count
+
+mod common;
+
+use common::{runtime, temp_dir, Blob};
+use criterion::{criterion_group, criterion_main, Criterion};
+use darkbird::{Options, Storage, StorageType};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc;
+
+const HOT: usize = 99;
+
+// rounds written to one store before it's dropped with its log
+const ROUND: u64 = 500;
+
+const REPORT_ROUNDS: u64 = 2000;
+
+
+
+async fn open(lanes: usize) -> (Arc<Storage<String, Blob>>, std::path::PathBuf) {
+    let dir = temp_dir("lanes");
+    let ops = Options::new(dir.to_str().unwrap(), "counters", 1000, StorageType::DiskCopies, false).with_lanes(lanes);
+    let storage = Arc::new(Storage::<String, Blob>::open(ops).await.unwrap());
+
+    // subscriber draining events, so dispatch take part in contention
+    let (sender, mut receiver) = mpsc::channel(1024);
+    storage.subscribe(sender).await.unwrap();
+    tokio::spawn(async move { while receiver.recv().await.is_some() {} });
+
+    (storage, dir)
+}
+
+// latency of write of cold key, among HOT writes of hot key
+async fn round(storage: &Arc<Storage<String, Blob>>, n: u64) -> Duration {
+    let mut hot = Vec::with_capacity(HOT);
+    for _ in 0..HOT {
+        let storage = storage.clone();
+        hot.push(tokio::spawn(async move {
+            storage.insert("hot".to_owned(), Blob { data: vec![0; 64] }).await.unwrap()
+        }));
+    }
+
+    let storage = storage.clone();
+    let started = Instant::now();
+    let cold = tokio::spawn(async move {
+        storage.insert(format!("cold{}", n), Blob { data: vec![0; 64] }).await.unwrap();
+        started.elapsed()
+    });
+
+    for write in hot {
+        write.await.unwrap();
+    }
+    cold.await.unwrap()
+}
+
+async fn cold_latencies(lanes: usize, rounds: u64) -> Vec<Duration> {
+    let mut latencies = Vec::with_capacity(rounds as usize);
+    while (latencies.len() as u64) < rounds {
+        let (storage, dir) = open(lanes).await;
+        for n in 0..ROUND.min(rounds - latencies.len() as u64) {
+            latencies.push(round(&storage, n).await);
+        }
+        // writes of rounds are joined, their clones dropped
+        if let Ok(storage) = Arc::try_unwrap(storage) {
+            storage.close().await.unwrap();
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    latencies
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    sorted[((sorted.len() - 1) as f64 * p) as usize]
+}
+
+fn hot_key(c: &mut Criterion) {
+    let rt = runtime();
+
+    for lanes in [1, 4] {
+        let mut latencies = rt.block_on(cold_latencies(lanes, REPORT_ROUNDS));
+        latencies.sort();
+        println!(
+            "other keys with 99% of writes to one key, {} lane(s): p50 {:?}, p99 {:?}",
+            lanes,
+            percentile(&latencies, 0.50),
+            percentile(&latencies, 0.99)
+        );
+    }
+
+    let mut group = c.benchmark_group("hot_key_cold_write");
+    group.sample_size(10);
+
+    for lanes in [1, 4] {
+        group.bench_function(format!("lanes_{}", lanes), |b| {
+            b.iter_custom(|iters| rt.block_on(async { cold_latencies(lanes, iters).await.into_iter().sum() }))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, hot_key);
+criterion_main!(benches);
@@ -0,0 +1,68 @@
+//! Overhead of latency recorders: same operations built without and with `metrics`,
+//! ids are tagged by feature so criterion compare two runs
+//!
+//! ```text
+//! cargo bench --bench latency
+//! cargo bench --bench latency --features metrics
+//! ```
+
+mod common;
+
+use common::{options, runtime, temp_dir, Blob};
+use criterion::{criterion_group, criterion_main, Criterion};
+use darkbird::{Operation, Storage, StorageType};
+use std::hint::black_box;
+
+const DOCS: u64 = 10_000;
+
+const BUILD: &str = if cfg!(feature = "metrics") { "metrics" } else { "no_metrics" };
+
+
+
+fn overhead(c: &mut Criterion) {
+    let rt = runtime();
+    let dir = temp_dir("latency");
+
+    let storage = rt.block_on(async {
+        let storage = Storage::<u64, Blob>::open(options(&dir, "blobs", StorageType::RamCopies)).await.unwrap();
+        for i in 0..DOCS {
+            storage.insert(i, Blob { data: vec![1; 32] }).await.unwrap();
+        }
+        storage
+    });
+
+    let mut group = c.benchmark_group("latency_recorder");
+    let mut i = 0;
+
+    // cheapest recorded reads, where recorder weigh most
+    group.bench_function(format!("lookup_owned/{}", BUILD), |b| {
+        b.iter(|| {
+            i = (i + 1) % DOCS;
+            black_box(storage.lookup_owned(&i))
+        })
+    });
+
+    group.bench_function(format!("lookup_miss/{}", BUILD), |b| {
+        b.iter(|| black_box(storage.lookup_owned(&(DOCS + 1))))
+    });
+
+    group.bench_function(format!("insert/{}", BUILD), |b| {
+        b.iter(|| {
+            i = (i + 1) % DOCS;
+            rt.block_on(storage.insert(i, Blob { data: vec![2; 32] })).unwrap()
+        })
+    });
+
+    group.finish();
+
+    // recorders saw benched operations, empty report without metrics
+    if let Some(lookup) = storage.latency_report().get(Operation::Lookup) {
+        println!("recorded {} lookups, p50 {:?}, p99 {:?}", lookup.count, lookup.p50, lookup.p99);
+    }
+
+    rt.block_on(storage.close()).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+criterion_group!(benches, overhead);
+criterion_main!(benches);
@@ -0,0 +1,126 @@
+//! Replay of a WAL holding DOCS workload documents updated ROUNDS times each, opened with and
+//! without `Options::with_low_memory_replay`: peak heap bytes while opening are printed once,
+//! open time is measured by criterion. heap is measured by counting live bytes
+
+mod common;
+
+use common::{options, runtime, temp_dir};
+use criterion::{criterion_group, criterion_main, Criterion};
+use darkbird::{
+    workload::{self, WorkloadDoc},
+    Options, Storage, StorageType,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const DOCS: usize = 5000;
+const DOC_SIZE: usize = 2048;
+const ROUNDS: u64 = 4;
+const SEED: u64 = 0x5eed;
+
+
+
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+fn grown(size: usize) {
+    let live = LIVE.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(live, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        grown(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        grown(new_size);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOC: Counting = Counting;
+
+
+
+// every document inserted, then its body rewritten ROUNDS times, all left on WAL
+async fn write_wal(dir: &Path) {
+    let storage = Storage::<u64, WorkloadDoc>::open(options(dir, "replay", StorageType::DiskCopies)).await.unwrap();
+    for (key, doc) in workload::documents(DOCS, DOC_SIZE, SEED) {
+        storage.insert(key, doc).await.unwrap();
+    }
+    for round in 1..=ROUNDS {
+        for (key, doc) in workload::documents(DOCS, DOC_SIZE, SEED + round) {
+            storage.update(&key, |held| held.body = doc.body).await.unwrap();
+        }
+    }
+    storage.close().await.unwrap();
+}
+
+fn replay_options(dir: &Path, low_memory: bool) -> Options {
+    let ops = options(dir, "replay", StorageType::DiskCopies);
+    if low_memory { ops.with_low_memory_replay() } else { ops }
+}
+
+// heap bytes above those held before open, at their peak while opening
+async fn open_peak(dir: &Path, low_memory: bool) -> usize {
+    let before = LIVE.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+
+    let storage = Storage::<u64, WorkloadDoc>::open(replay_options(dir, low_memory)).await.unwrap();
+    let peak = PEAK.load(Ordering::Relaxed) - before;
+
+    assert_eq!(storage.len(), DOCS);
+    storage.close().await.unwrap();
+    peak
+}
+
+fn low_memory_replay(c: &mut Criterion) {
+    let rt = runtime();
+    let dir = temp_dir("low-memory-replay");
+    rt.block_on(write_wal(&dir));
+
+    let (plain, low) = rt.block_on(async { (open_peak(&dir, false).await, open_peak(&dir, true).await) });
+    let mib = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+    println!(
+        "peak heap replaying {} documents x {} versions: plain {:.1} MiB, low memory {:.1} MiB ({:.1}% of plain)",
+        DOCS,
+        ROUNDS + 1,
+        mib(plain),
+        mib(low),
+        low as f64 * 100.0 / plain as f64
+    );
+
+    let mut group = c.benchmark_group("low_memory_replay_open");
+    group.sample_size(10);
+
+    for (name, low_memory) in [("plain", false), ("low_memory", true)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                rt.block_on(async {
+                    let storage = Storage::<u64, WorkloadDoc>::open(replay_options(&dir, low_memory)).await.unwrap();
+                    storage.close().await.unwrap();
+                })
+            })
+        });
+    }
+
+    group.finish();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+criterion_group!(benches, low_memory_replay);
+criterion_main!(benches);
@@ -0,0 +1,95 @@
+//! lookup_with latency of each ReadPreference while a writer keep overwriting the same keys.
+//! p50 and p99 of READS timed reads are printed per preference next to criterion times
+
+mod common;
+
+use common::{options, runtime, temp_dir, Blob};
+use criterion::{criterion_group, criterion_main, Criterion};
+use darkbird::{ReadPreference, Storage, StorageType};
+use std::{
+    hint::black_box,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+const DOCS: u64 = 10_000;
+const READS: usize = 100_000;
+
+
+
+fn name(pref: ReadPreference) -> &'static str {
+    match pref {
+        ReadPreference::Fresh => "fresh",
+        ReadPreference::Snapshot => "snapshot",
+        ReadPreference::SnapshotMaxStaleness(_) => "snapshot_max_staleness",
+    }
+}
+
+async fn percentiles(storage: &Storage<u64, Blob>, pref: ReadPreference) -> (Duration, Duration) {
+    let mut took = Vec::with_capacity(READS);
+    for i in 0..READS {
+        let started = Instant::now();
+        black_box(storage.lookup_with(&(i as u64 % DOCS), pref).await);
+        took.push(started.elapsed());
+    }
+
+    took.sort();
+    (took[READS / 2], took[READS * 99 / 100])
+}
+
+fn read_preference(c: &mut Criterion) {
+    let rt = runtime();
+    let dir = temp_dir("read-preference");
+
+    let ops = options(&dir, "blobs", StorageType::RamCopies).with_read_snapshot(Duration::from_millis(100));
+    let storage = Arc::new(rt.block_on(async {
+        let storage = Storage::<u64, Blob>::open(ops).await.unwrap();
+        for i in 0..DOCS {
+            storage.insert(i, Blob { data: vec![1; 32] }).await.unwrap();
+        }
+        storage
+    }));
+
+    // overwrite keys read, so Fresh reads contend with a writer
+    let stop = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let (storage, stop) = (storage.clone(), stop.clone());
+        rt.spawn(async move {
+            let mut i = 0;
+            while !stop.load(Ordering::Relaxed) {
+                i = (i + 1) % DOCS;
+                storage.insert(i, Blob { data: vec![2; 32] }).await.unwrap();
+            }
+        })
+    };
+
+    let prefs = [ReadPreference::Fresh, ReadPreference::Snapshot, ReadPreference::SnapshotMaxStaleness(Duration::from_millis(50))];
+    for pref in prefs {
+        let (p50, p99) = rt.block_on(percentiles(&storage, pref));
+        println!("{} reads {}: p50 {:?}, p99 {:?}", READS, name(pref), p50, p99);
+    }
+
+    let mut group = c.benchmark_group("read_preference");
+    for pref in prefs {
+        let mut i = 0;
+        group.bench_function(name(pref), |b| {
+            b.iter(|| {
+                i = (i + 1) % DOCS;
+                black_box(rt.block_on(storage.lookup_with(&i, pref)))
+            })
+        });
+    }
+    group.finish();
+
+    stop.store(true, Ordering::Relaxed);
+    rt.block_on(writer).unwrap();
+    let storage = Arc::try_unwrap(storage).ok().expect("writer joined");
+    rt.block_on(storage.close()).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+criterion_group!(benches, read_preference);
+criterion_main!(benches);
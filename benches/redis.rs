@@ -0,0 +1,83 @@
+//! RedisStorage reads: per-op overhead of a `CacheHandle` resolved once against
+//! `Database` resolving the store on each call, and 1000 `get` calls, each taking
+//! the lock, against one `mget` taking it once
+
+mod common;
+
+use common::runtime;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use darkbird::{storage_redis::RedisStorage, Schema};
+use std::hint::black_box;
+
+const KEYS: u64 = 1000;
+
+
+
+fn handle_vs_database(c: &mut Criterion) {
+    let rt = runtime();
+
+    let db = rt.block_on(async { Schema::new().with_redisstore::<u64, String>("cache").await.unwrap().build() });
+    let handle = db.cache_handle::<u64, String>().unwrap();
+    handle.mset((0..KEYS).map(|i| (i, format!("value {}", i), None)).collect());
+
+    let mut group = c.benchmark_group("redis_handle");
+    let mut i = 0;
+
+    group.bench_function("database_get", |b| {
+        b.iter(|| {
+            i = (i + 1) % KEYS;
+            black_box(db.get::<u64, String>(&i).unwrap())
+        })
+    });
+
+    group.bench_function("handle_get", |b| {
+        b.iter(|| {
+            i = (i + 1) % KEYS;
+            black_box(handle.get(&i))
+        })
+    });
+
+    group.bench_function("database_set", |b| {
+        b.iter(|| {
+            i = (i + 1) % KEYS;
+            db.set::<u64, String>(i, String::new(), None).unwrap()
+        })
+    });
+
+    group.bench_function("handle_set", |b| {
+        b.iter(|| {
+            i = (i + 1) % KEYS;
+            handle.set(i, String::new(), None)
+        })
+    });
+
+    group.finish();
+}
+
+fn get_vs_mget(c: &mut Criterion) {
+    let rt = runtime();
+
+    // purge task of store is spawned on runtime
+    let _entered = rt.enter();
+    let storage = RedisStorage::<u64, String>::new();
+    storage.mset((0..KEYS).map(|i| (i, format!("value {}", i), None)).collect());
+    let keys: Vec<u64> = (0..KEYS).collect();
+
+    let mut group = c.benchmark_group("redis_1000_keys");
+    group.throughput(Throughput::Elements(KEYS));
+
+    group.bench_function("get_x1000", |b| {
+        b.iter(|| {
+            for key in keys.iter() {
+                black_box(storage.get(key));
+            }
+        })
+    });
+
+    group.bench_function("mget", |b| b.iter(|| black_box(storage.mget(&keys))));
+
+    group.finish();
+}
+
+criterion_group!(benches, handle_vs_database, get_vs_mget);
+criterion_main!(benches);
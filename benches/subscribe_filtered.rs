@@ -0,0 +1,168 @@
+//! Writes with 100 subscribers: every event broadcast to each of them, against the same
+//! subscribers filtering 1% of documents by closure and by tag expression.
+//! each iteration is timed until its events are delivered
+
+mod common;
+
+use common::{runtime, temp_dir};
+use criterion::{criterion_group, criterion_main, Criterion};
+use darkbird::{
+    document::{Document, FullText, Indexer, MaterializedView, Range, Tags},
+    Event, EventFilter, Options, RQuery, Storage, StorageType, SubscribeFilter, TagExpr,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc;
+
+const SUBSCRIBERS: usize = 100;
+
+// one document of HOT_EVERY is tagged "hot"
+const HOT_EVERY: u64 = 100;
+
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Item {
+    hot: bool,
+}
+
+impl Document for Item {}
+
+impl Indexer for Item {
+    fn extract(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Tags for Item {
+    fn get_tags(&self) -> Vec<String> {
+        if self.hot {
+            vec!["hot".to_owned()]
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl Range for Item {}
+
+impl MaterializedView for Item {
+    fn filter(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FullText for Item {
+    fn get_content(&self) -> Option<String> {
+        None
+    }
+}
+
+
+
+#[derive(Clone, Copy, Debug)]
+enum Mode {
+    // subscribe, every event to every subscriber
+    Broadcast,
+
+    // subscribe_filtered with a closure over event
+    Predicate,
+
+    // subscribe_matching with a tag expression, evaluated on metadata built once per event
+    Matching,
+}
+
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Broadcast => "broadcast",
+            Mode::Predicate => "predicate",
+            Mode::Matching => "matching",
+        }
+    }
+
+    // events subscribers receive for writes of keys from..to
+    fn expected(self, from: u64, to: u64) -> u64 {
+        let docs = match self {
+            Mode::Broadcast => to - from,
+            _ => (from..to).filter(|key| key % HOT_EVERY == 0).count() as u64,
+        };
+        docs * SUBSCRIBERS as u64
+    }
+}
+
+async fn open(mode: Mode, dir: &std::path::Path) -> (Storage<u64, Item>, Arc<AtomicU64>) {
+    let ops = Options::new(dir.to_str().unwrap(), "subscribe_bench", 1000, StorageType::RamCopies, false);
+    let storage = Storage::<u64, Item>::open(ops).await.unwrap();
+    let received = Arc::new(AtomicU64::new(0));
+
+    for _ in 0..SUBSCRIBERS {
+        let (sender, mut receiver) = mpsc::channel::<Event<u64, Item>>(1024);
+
+        let received = received.clone();
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                if let Event::Query(RQuery::Insert(..)) = event {
+                    received.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        });
+
+        match mode {
+            Mode::Broadcast => storage.subscribe(sender).await.unwrap(),
+            Mode::Predicate => {
+                let filter = SubscribeFilter::Custom(Arc::new(|event: &Event<u64, Item>| {
+                    matches!(event, Event::Query(RQuery::Insert(_, doc)) if doc.hot)
+                }));
+                storage.subscribe_filtered(sender, filter).await.unwrap()
+            }
+            Mode::Matching => storage.subscribe_matching(sender, EventFilter::Tags(TagExpr::tag("hot"))).await.unwrap(),
+        };
+    }
+
+    (storage, received)
+}
+
+fn dispatch(c: &mut Criterion) {
+    let rt = runtime();
+    let mut group = c.benchmark_group("subscribers_100");
+
+    for mode in [Mode::Broadcast, Mode::Predicate, Mode::Matching] {
+        let dir = temp_dir("subscribe");
+        let (storage, received) = rt.block_on(open(mode, &dir));
+        let mut next = 0;
+
+        group.bench_function(mode.name(), |b| {
+            b.iter_custom(|iters| {
+                rt.block_on(async {
+                    let (from, to) = (next, next + iters);
+                    next = to;
+
+                    let expected = received.load(Ordering::Relaxed) + mode.expected(from, to);
+                    let started = Instant::now();
+                    for key in from..to {
+                        storage.insert(key, Item { hot: key % HOT_EVERY == 0 }).await.unwrap();
+                    }
+                    while received.load(Ordering::Relaxed) < expected {
+                        tokio::time::sleep(Duration::from_micros(100)).await;
+                    }
+                    started.elapsed()
+                })
+            })
+        });
+
+        rt.block_on(storage.close()).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
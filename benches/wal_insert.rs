@@ -0,0 +1,123 @@
+//! Concurrent inserts into a DiskCopies store, once per WAL writer, timed until
+//! the store is closed and queued records are written and fsynced.
+//! `WalWriter::Uring` run on linux with `--features uring`
+
+mod common;
+
+use common::{runtime, temp_dir};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use darkbird::{
+    document::{Document, FullText, Indexer, MaterializedView, Range, Tags},
+    Options, Storage, StorageType, WalWriter,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+const TASKS: u64 = 64;
+const WRITES_PER_TASK: u64 = 500;
+
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Item {
+    payload: String,
+}
+
+impl Document for Item {}
+
+impl Indexer for Item {
+    fn extract(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Tags for Item {
+    fn get_tags(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Range for Item {}
+
+impl MaterializedView for Item {
+    fn filter(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FullText for Item {
+    fn get_content(&self) -> Option<String> {
+        None
+    }
+}
+
+
+
+// TASKS tasks inserting WRITES_PER_TASK documents each into a new store, until it's closed
+async fn durable_inserts(writer: WalWriter) -> Duration {
+    let dir = temp_dir("wal-insert");
+    let ops = Options::new(dir.to_str().unwrap(), "wal_bench", 100_000, StorageType::DiskCopies, true)
+        .with_wal_writer(writer);
+    let storage = Arc::new(Storage::<u64, Item>::open(ops).await.unwrap());
+
+    let started = Instant::now();
+    let mut handles = vec![];
+    for task in 0..TASKS {
+        let storage = storage.clone();
+        handles.push(tokio::spawn(async move {
+            for n in 0..WRITES_PER_TASK {
+                let key = task * WRITES_PER_TASK + n;
+                storage.insert(key, Item { payload: format!("document {}", key) }).await.unwrap();
+            }
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    let storage = Arc::try_unwrap(storage).ok().unwrap();
+    storage.close().await.unwrap();
+    let took = started.elapsed();
+
+    let _ = std::fs::remove_dir_all(&dir);
+    took
+}
+
+fn wal_writers(c: &mut Criterion) {
+    let rt = runtime();
+
+    #[allow(unused_mut)]
+    let mut writers = vec![("thread", WalWriter::Thread)];
+
+    #[cfg(all(target_os = "linux", feature = "uring"))]
+    {
+        writers.push(("uring", WalWriter::Uring { direct_io: false, preallocate: false }));
+        writers.push(("uring_direct_preallocate", WalWriter::Uring { direct_io: true, preallocate: true }));
+    }
+
+    let mut group = c.benchmark_group("concurrent_inserts");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(TASKS * WRITES_PER_TASK));
+
+    for (name, writer) in writers {
+        group.bench_function(name, |b| {
+            b.iter_custom(|iters| {
+                rt.block_on(async {
+                    let mut took = Duration::ZERO;
+                    for _ in 0..iters {
+                        took += durable_inserts(writer).await;
+                    }
+                    took
+                })
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, wal_writers);
+criterion_main!(benches);
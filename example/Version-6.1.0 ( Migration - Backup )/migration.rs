@@ -35,7 +35,14 @@ async fn main() {
                     RQuery::Insert(k, p)  
                       
                 }
-                RQuery::Remove(k) => RQuery::Remove(k)
+                RQuery::Update(k, u) => RQuery::Update(k, Profile { fullname: u.fullname, age: 25 }),
+                RQuery::Remove(k) => RQuery::Remove(k),
+                RQuery::Clear => RQuery::Clear,
+                RQuery::TagAdd(k, tag) => RQuery::TagAdd(k, tag),
+                RQuery::TagRemove(k, tag) => RQuery::TagRemove(k, tag),
+
+                // RQuery is non_exhaustive, patch records are refused by migration
+                _ => unreachable!()
             }
         }
     ).unwrap();
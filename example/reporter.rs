@@ -49,6 +49,7 @@ async fn main() {
             Event::Subscribed(_key) => {
                 unimplemented!()
             }
+            _ => {}
         }
     });
 
@@ -1,15 +1,60 @@
 use simple_wal::LogError;
-use std::{io::Error, time::Duration};
+use serde::{Deserialize, Serialize};
+use std::{io::Error, sync::Arc, time::Duration};
+
+use self::{compression::Compression, wal::{archive::ArchiveHook, codec::WalCodec, writer::WalWriter}};
 
 mod index;
+pub mod access;
 pub mod document;
+pub mod filter;
+mod fingerprint;
 mod router;
 pub mod database;
+pub mod derive;
+mod disk_space;
+pub mod config;
+mod coalesce;
+mod lanes;
+mod coop;
+mod expiry;
+pub mod admin;
+pub mod capabilities;
+pub mod clock;
+pub mod compression;
+pub mod key_codec;
+pub mod latency;
+pub mod metrics;
+pub mod patch;
+pub mod query;
+pub mod search;
+pub mod reference;
 pub mod schema;
+pub mod snapshot;
 pub mod storage_redis;
+pub mod storage_bytes;
 pub mod wal;
 pub mod persistent_worker;
+pub mod plugin;
+pub mod read_snapshot;
+pub mod repair;
+pub mod startup;
+pub mod registry;
+pub mod retention;
+pub mod recovery;
+pub mod capacity;
+pub mod query_cache;
+mod watch;
+pub mod views;
+pub mod transaction;
+mod timer;
 pub mod storage;
+pub mod stream;
+pub mod wire;
+pub mod workload;
+
+#[cfg(feature = "test-util")]
+pub mod testing;
 
 pub use async_trait::async_trait;
 
@@ -19,6 +64,7 @@ pub use async_trait::async_trait;
 pub static TIMEOUT: Duration = Duration::from_secs(5);
 
 pub use storage::{Event, RQuery};
+pub use router::{SubscriberInfo, SubscriptionId};
 
 
 
@@ -35,6 +81,7 @@ pub enum StatusResult {
     IoError(Error),
     End,
     ReporterIsOff,
+    TimersIsOff,
     Err(String),
     Duplicate,
 }
@@ -46,6 +93,7 @@ impl ToString for StatusResult {
             StatusResult::IoError(e) => e.to_string(),
             StatusResult::End => "End".to_string(),
             StatusResult::ReporterIsOff => "ReporterIsOff".to_string(),
+            StatusResult::TimersIsOff => "TimersIsOff".to_string(),
             StatusResult::Err(e) => e.to_string(),
             StatusResult::Duplicate => "Duplicate".to_string()
         }
@@ -61,6 +109,35 @@ pub enum SessionResult {
     NoResponse,
     DataStoreNotFound,
     UnImplement,
+    CompressedValue,
+    ReferencedBy { store: String, count: usize },
+    RebuildInProgress,
+    PluginRejected { plugin: String, reason: String },
+    SchemaMismatch { stored: String, requested: String },
+    FieldTypeMismatch { field: String, stored: String, requested: String },
+    WouldBlock,
+
+    // insert of a new key into a store at Options::with_capacity with EvictionPolicy::RejectNew
+    CapacityExceeded,
+
+    // events after sequence asked were evicted from retention, or it's ahead of store
+    GapTooLarge { oldest_available: u64 },
+
+    // Storage::import with ConflictPolicy::Fail met a key already held after importing some documents
+    ImportConflict { imported: usize },
+
+    // Storage::create_view with a name of a view already held, Storage::drop_view of a view not created
+    ViewExists { name: String },
+    ViewNotFound { name: String },
+
+    // Storage::add_tag and remove_tag of a key not held
+    KeyNotFound,
+
+    // RedisStorage::incr and decr past bounds of value type, value left as is
+    Overflow,
+
+    // write of a store in read-only mode, see `Storage::set_read_only`
+    ReadOnly,
     Err(StatusResult),
 }
 
@@ -73,6 +150,21 @@ impl ToString for SessionResult {
             SessionResult::NoResponse => "NoResponse".to_string(),
             SessionResult::DataStoreNotFound => "DataStoreNotFound".to_string(),
             SessionResult::UnImplement => "UnImplement".to_string(),
+            SessionResult::CompressedValue => "CompressedValue: use owned reads (lookup_owned, ...)".to_string(),
+            SessionResult::ReferencedBy { store, count } => format!("ReferencedBy {} ({})", store, count),
+            SessionResult::RebuildInProgress => "RebuildInProgress".to_string(),
+            SessionResult::PluginRejected { plugin, reason } => format!("PluginRejected {}: {}", plugin, reason),
+            SessionResult::SchemaMismatch { stored, requested } => format!("SchemaMismatch stored {} requested {}", stored, requested),
+            SessionResult::FieldTypeMismatch { field, stored, requested } => format!("FieldTypeMismatch {} holds {} requested {}", field, stored, requested),
+            SessionResult::WouldBlock => "WouldBlock".to_string(),
+            SessionResult::CapacityExceeded => "CapacityExceeded".to_string(),
+            SessionResult::GapTooLarge { oldest_available } => format!("GapTooLarge oldest available {}", oldest_available),
+            SessionResult::ImportConflict { imported } => format!("ImportConflict after {} imported", imported),
+            SessionResult::ViewExists { name } => format!("ViewExists {}", name),
+            SessionResult::ViewNotFound { name } => format!("ViewNotFound {}", name),
+            SessionResult::KeyNotFound => "KeyNotFound".to_string(),
+            SessionResult::Overflow => "Overflow".to_string(),
+            SessionResult::ReadOnly => "ReadOnly".to_string(),
             SessionResult::Err(e) => e.to_string()
         }
     }
@@ -86,7 +178,7 @@ pub enum WorkerState {
     Empty,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum StorageType {
     // Store to memory
     RamCopies,
@@ -97,28 +189,302 @@ pub enum StorageType {
 
 
 #[derive(Clone)]
-pub struct Options<'a> {
-    path: &'a str,
-    storage_name: &'a str,
+pub struct Options {
+    path: String,
+    storage_name: String,
     total_page_size: usize,
     stype: StorageType,
     off_reporter: bool,
+    coalesce: Option<Duration>,
+    lag_threshold: Option<usize>,
+    value_compression: Option<Compression>,
+    lanes: usize,
+    admin_log: Option<String>,
+    clock: Arc<dyn clock::Clock>,
+    timers: bool,
+    plugins: Vec<plugin::ErasedPlugin>,
+    read_repair: Option<repair::ErasedRepair>,
+    read_snapshot: Option<Duration>,
+    schema_override: bool,
+    track_access: bool,
+    ordered_keys: bool,
+    low_memory_replay: bool,
+    previous_values: bool,
+    event_retention: Option<retention::EventRetention>,
+    wal_codec: WalCodec,
+    wal_writer: WalWriter,
+    archive_hook: Option<ArchiveHook>,
+    recovery: recovery::RecoveryMode,
+    clock_skew: Option<(Duration, recovery::ClockSkewPolicy)>,
+    capacity: Option<usize>,
+    eviction: capacity::EvictionPolicy,
+    query_cache: Option<(usize, usize)>,
+    metrics_observer: Option<Arc<dyn metrics::MetricsObserver>>,
+    bloom_filter: Option<(usize, f64)>,
+    disk_space_watch: Option<(u64, Option<u64>)>,
+
+    // set by Schema::open_all
+    io_budget: Option<Arc<startup::IoBudget>>,
+    load_progress: Option<Arc<startup::LoadProgress>>,
+
+    #[cfg(feature = "test-util")]
+    wal_faults: Option<testing::FaultyWal>,
 }
 
-impl<'a> Options<'a> {
+impl Options {
     pub fn new(
-        path: &'a str,
-        storage_name: &'a str,
+        path: &str,
+        storage_name: &str,
         total_page_size: usize,
         stype: StorageType,
         off_reporter: bool
     ) -> Self {
         Options {
-            path,
-            storage_name,
+            path: path.to_owned(),
+            storage_name: storage_name.to_owned(),
             total_page_size,
             stype,
-            off_reporter
+            off_reporter,
+            coalesce: None,
+            lag_threshold: None,
+            value_compression: None,
+            lanes: 1,
+            admin_log: None,
+            clock: clock::default_clock(),
+            timers: false,
+            plugins: vec![],
+            read_repair: None,
+            read_snapshot: None,
+            schema_override: false,
+            track_access: false,
+            ordered_keys: false,
+            low_memory_replay: false,
+            previous_values: false,
+            event_retention: None,
+            wal_codec: WalCodec::Bincode,
+            wal_writer: WalWriter::Thread,
+            archive_hook: None,
+            recovery: recovery::RecoveryMode::Strict,
+            clock_skew: None,
+            capacity: None,
+            eviction: capacity::EvictionPolicy::RejectNew,
+            query_cache: None,
+            metrics_observer: None,
+            bloom_filter: None,
+            disk_space_watch: None,
+            io_budget: None,
+            load_progress: None,
+
+            #[cfg(feature = "test-util")]
+            wal_faults: None,
         }
     }
+
+    /// collapse writes to the same key within window to the last one
+    /// for WAL logging and event dispatch, memory always reflect newest value
+    pub fn with_coalesce(mut self, window: Duration) -> Self {
+        self.coalesce = Some(window);
+        self
+    }
+
+    /// subscribers receive `Event::Lagging` when a subscriber
+    /// has more than threshold events waiting in its channel
+    pub fn with_lag_threshold(mut self, threshold: usize) -> Self {
+        self.lag_threshold = Some(threshold);
+        self
+    }
+
+    /// hold documents compressed in memory, decompressed on `lookup_owned`,
+    /// Ref based queries (lookup, lookup_by_tag, ...) don't see compressed documents
+    pub fn with_value_compression(mut self, compression: Compression) -> Self {
+        self.value_compression = Some(compression);
+        self
+    }
+
+    /// distribute WAL records and events over lanes by key hash,
+    /// so a hot key can't starve others, order per key is preserved
+    pub fn with_lanes(mut self, lanes: usize) -> Self {
+        self.lanes = lanes.max(1);
+        self
+    }
+
+    /// append admin events (audit, rebuild, close, ...) to file at path,
+    /// independent from data WAL
+    pub fn with_admin_log(mut self, path: &str) -> Self {
+        self.admin_log = Some(path.to_owned());
+        self
+    }
+
+    /// source of wall time for admin events and other wall time reads,
+    /// default is a process wide `ClampedClock` over system clock
+    pub fn with_clock(mut self, clock: Arc<dyn clock::Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// enable `Storage::notify_at` timers, require reporter,
+    /// durable in their own WAL with DiskCopies
+    pub fn with_timers(mut self) -> Self {
+        self.timers = true;
+        self
+    }
+
+    /// append a plugin to write chain, key and document type must match store
+    /// or open fail
+    pub fn with_plugin<K: 'static, Doc: 'static>(mut self, plugin: impl plugin::WritePlugin<K, Doc> + 'static) -> Self {
+        self.plugins.push(plugin::ErasedPlugin::new(Arc::new(plugin)));
+        self
+    }
+
+    /// check documents as they are read with hook, see `ReadRepair`,
+    /// key and document type must match store or open fail
+    pub fn with_read_repair<K: 'static, Doc: 'static>(mut self, hook: impl repair::ReadRepair<K, Doc> + 'static) -> Self {
+        self.read_repair = Some(repair::ErasedRepair::new(Arc::new(hook)));
+        self
+    }
+
+    /// keep a read snapshot refreshed every interval,
+    /// for `ReadPreference::Snapshot` reads that don't contend with writers
+    pub fn with_read_snapshot(mut self, interval: Duration) -> Self {
+        self.read_snapshot = Some(interval);
+        self
+    }
+
+    /// open even if WAL was written with other key or document type,
+    /// and record new types, for intentional migrations
+    pub fn with_schema_override(mut self) -> Self {
+        self.schema_override = true;
+        self
+    }
+
+    /// count reads of every key in an approximate counter, for `Storage::top_accessed`
+    /// and `Storage::access_count`. counters live in memory only, reset on open
+    pub fn with_access_tracking(mut self) -> Self {
+        self.track_access = true;
+        self
+    }
+
+    /// keep keys in order beside map, so `Storage::iter_prefix` and `Storage::iter_page`
+    /// seek their keys instead of walking every key, at cost of an ordered insert per new key
+    pub fn with_ordered_keys(mut self) -> Self {
+        self.ordered_keys = true;
+        self
+    }
+
+    /// replay WAL into memory and hash index only, then build tags, views, ranges and text
+    /// in one pass each over documents, so open peak at documents and one structure growing
+    /// instead of every structure churning with each replayed version. slower open, same end state
+    pub fn with_low_memory_replay(mut self) -> Self {
+        self.low_memory_replay = true;
+        self
+    }
+
+    /// dispatch `Event::Removed` and `Event::Replaced`, carrying document a remove or an insert
+    /// over a held key replaced, in place of their `Event::Query` (and of `Event::BulkRemove`).
+    /// cost a copy of previous document per write, WAL records are unchanged.
+    /// events of coalesced writes stay `Query`, required by `Database::derive`
+    pub fn with_previous_values(mut self) -> Self {
+        self.previous_values = true;
+        self
+    }
+
+    /// keep last max_events document changes dispatched, none older than max_age by store clock,
+    /// numbered from 1 when store is first opened, so a subscriber back from a short disconnection
+    /// resume from last sequence it saw with `Storage::subscribe_from_seq`.
+    /// a disk store reopened after `Storage::close` continue numbering, required by `Database::derive`.
+    /// one copy of each event is kept, shared by replays, require reporter
+    pub fn with_event_retention(mut self, max_events: usize, max_age: Duration) -> Self {
+        self.event_retention = Some(retention::EventRetention { max_events, max_age });
+        self
+    }
+
+    /// encoding of WAL records, Bincode by default. each page begin with a header record
+    /// naming format version and codec, Msgpack require msgpack feature.
+    /// a WAL is opened only with codec it was written with.
+    /// WAL of timers and checkpoint files stay Bincode
+    pub fn with_wal_codec(mut self, codec: WalCodec) -> Self {
+        self.wal_codec = codec;
+        self
+    }
+
+    /// how WAL pages are written, `WalWriter::Thread` by default.
+    /// `WalWriter::Uring` (uring feature, linux) batch records written together
+    /// into one io_uring submission, optionally with O_DIRECT and preallocated pages.
+    /// pages are same either way, WAL of timers is written by Thread
+    pub fn with_wal_writer(mut self, writer: WalWriter) -> Self {
+        self.wal_writer = writer;
+        self
+    }
+
+    /// send each WAL page to hook once sealed and fsynced, compaction delete
+    /// a page only once hook acknowledged it, see `ArchiveHook`. ignored by RamCopies
+    pub fn with_archive_hook(mut self, hook: ArchiveHook) -> Self {
+        self.archive_hook = Some(hook);
+        self
+    }
+
+    /// what open does with a corrupted WAL record, `RecoveryMode::Strict` by default,
+    /// see `Storage::recovery` for what was replayed. a torn record at end of WAL
+    /// is dropped whatever the mode
+    pub fn with_recovery(mut self, mode: recovery::RecoveryMode) -> Self {
+        self.recovery = mode;
+        self
+    }
+
+    /// what open does when newest WAL page was written more than threshold away from store clock,
+    /// see `ClockSkewPolicy`. a WAL behind clock is skewed too, as a store left closed look the same:
+    /// set threshold above longest time store stay closed. without it a WAL more than
+    /// five minutes ahead of clock is only reported in `Storage::recovery`. ignored by RamCopies
+    pub fn with_clock_skew(mut self, threshold: Duration, policy: recovery::ClockSkewPolicy) -> Self {
+        self.clock_skew = Some((threshold, policy));
+        self
+    }
+
+    /// hold at most max documents, an insert of a new key into a full store follow eviction,
+    /// see `EvictionPolicy`. evicted documents are removed like a remove, logged and dispatched.
+    /// inserts, batches and transactions are bounded, replay isn't: a WAL holding more than max,
+    /// capacity lowered, is evicted down on open
+    pub fn with_capacity(mut self, max: usize, eviction: capacity::EvictionPolicy) -> Self {
+        self.capacity = Some(max);
+        self.eviction = eviction;
+        self
+    }
+
+    /// cache results of `QueryBuilder::cached` queries, at most max_entries
+    /// and about max_bytes of keys, least recently used dropped first.
+    /// writes drop results they change, see `Storage::query_cache_stats`
+    pub fn with_query_cache(mut self, max_entries: usize, max_bytes: usize) -> Self {
+        self.query_cache = Some((max_entries, max_bytes));
+        self
+    }
+
+    /// report reads and WAL enqueues to observer as they complete, see `Storage::metrics`
+    pub fn with_metrics_observer(mut self, observer: impl metrics::MetricsObserver + 'static) -> Self {
+        self.metrics_observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// answer lookups of keys never inserted without locking a shard, for miss heavy reads.
+    /// sized on open for the larger of expected_keys and keys loaded; while store hold fewer,
+    /// about false_positive_rate of misses (0.01 cost ~10 bits a key) still reach the map.
+    /// removed keys stay in filter until next open rebuild it
+    pub fn with_bloom_filter(mut self, expected_keys: usize, false_positive_rate: f64) -> Self {
+        self.bloom_filter = Some((expected_keys, false_positive_rate));
+        self
+    }
+
+    /// check free space of WAL filesystem every second (linux, DiskCopies), `disk_space_low`
+    /// admin event when it drop below threshold bytes. below read_only_floor store turn read-only,
+    /// see `Storage::set_read_only`, so a full disk refuse writes instead of tearing a page
+    pub fn with_disk_space_watch(mut self, threshold: u64, read_only_floor: Option<u64>) -> Self {
+        self.disk_space_watch = Some((threshold, read_only_floor));
+        self
+    }
+
+    /// inject faults and latency into WAL appends
+    #[cfg(feature = "test-util")]
+    pub fn with_faulty_wal(mut self, faults: testing::FaultyWal) -> Self {
+        self.wal_faults = Some(faults);
+        self
+    }
 }
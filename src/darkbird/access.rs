@@ -0,0 +1,119 @@
+use std::{
+    cell::Cell,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    time::Instant,
+};
+
+
+
+// count-min sketch rows and cells per row, 4MB with stripes
+const DEPTH: usize = 4;
+const WIDTH: usize = 1 << 16;
+
+// sketches reads are spread over, summed on query
+const STRIPES: usize = 4;
+
+
+/// result of `Storage::top_accessed`.
+///
+/// counters are in memory only: not in WAL, reset when store is opened, so counts
+/// cover reads since `since`. counts are approximate, never under real count, and may be
+/// over it for keys sharing sketch cells with hotter ones
+#[derive(Clone, Debug)]
+pub struct AccessReport<K> {
+    // most read keys, most read first
+    pub top: Vec<(K, u64)>,
+
+    // reads counted since open
+    pub reads: u64,
+
+    pub since: Instant,
+}
+
+
+thread_local! {
+    static STRIPE: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+static NEXT_STRIPE: AtomicUsize = AtomicUsize::new(0);
+
+
+/// approximate read counter of keys, enabled by `Options::with_access_tracking`.
+///
+/// a thread always count into same stripe, so hot keys read from many threads
+/// don't contend on one cell, stripes merged when counts are asked
+pub(crate) struct AccessCounter {
+    stripes: Vec<Stripe>,
+    since: Instant,
+}
+
+struct Stripe {
+    cells: Vec<AtomicU32>,
+    reads: AtomicU64,
+}
+
+impl AccessCounter {
+    pub fn new() -> Self {
+        AccessCounter {
+            stripes: (0..STRIPES)
+                .map(|_| Stripe {
+                    cells: (0..DEPTH * WIDTH).map(|_| AtomicU32::new(0)).collect(),
+                    reads: AtomicU64::new(0),
+                })
+                .collect(),
+            since: Instant::now(),
+        }
+    }
+
+    #[inline]
+    pub fn record<K: Hash>(&self, key: &K) {
+        let stripe = &self.stripes[stripe()];
+        for cell in cells(key) {
+            stripe.cells[cell].fetch_add(1, Ordering::Relaxed);
+        }
+        stripe.reads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// smallest of key's cells, summed over stripes
+    pub fn count<K: Hash>(&self, key: &K) -> u64 {
+        cells(key)
+            .map(|cell| self.stripes.iter().map(|stripe| stripe.cells[cell].load(Ordering::Relaxed) as u64).sum())
+            .min()
+            .unwrap_or(0)
+    }
+
+    pub fn reads(&self) -> u64 {
+        self.stripes.iter().map(|stripe| stripe.reads.load(Ordering::Relaxed)).sum()
+    }
+
+    pub fn since(&self) -> Instant {
+        self.since
+    }
+}
+
+
+// stripe of current thread, assigned round robin on its first read
+#[inline]
+fn stripe() -> usize {
+    STRIPE.with(|stripe| match stripe.get() {
+        Some(index) => index,
+        None => {
+            let index = NEXT_STRIPE.fetch_add(1, Ordering::Relaxed) % STRIPES;
+            stripe.set(Some(index));
+            index
+        }
+    })
+}
+
+// one cell per row, rows indexed by double hashing of one hash
+#[inline]
+fn cells<K: Hash>(key: &K) -> impl Iterator<Item = usize> {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    let hash = hasher.finish();
+    let (h1, h2) = (hash as u32 as usize, (hash >> 32) as usize | 1);
+
+    (0..DEPTH).map(move |row| row * WIDTH + h1.wrapping_add(row.wrapping_mul(h2)) % WIDTH)
+}
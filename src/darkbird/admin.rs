@@ -0,0 +1,193 @@
+use parking_lot::Mutex;
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::broadcast;
+
+use super::clock::Clock;
+
+// events kept for slow admin subscribers
+const ADMIN_CHANNEL_SIZE: usize = 64;
+
+
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AdminOutcome {
+    Started,
+    Succeeded,
+    Failed(String),
+
+    // single event of a store condition, see `AdminLog::notice`
+    Notice,
+}
+
+
+/// Structured record of an administrative operation (audit, rebuild, close, ...),
+/// every operation emit one `Started` and one terminal event with same id.
+/// conditions noticed by store (page_rotated, disk_space_low, read_only) emit one `Notice`
+#[derive(Clone, Debug)]
+pub struct AdminEvent {
+    pub id: u64,
+    pub store: String,
+    pub operation: &'static str,
+    pub parameters: String,
+    pub started: SystemTime,
+
+    // None for Started
+    pub finished: Option<SystemTime>,
+
+    pub outcome: AdminOutcome,
+
+    // bytes affected by operation, 0 when not applicable
+    pub bytes: u64,
+}
+
+
+/// operation started but not finished,
+/// consumed by `AdminLog::finish` so terminal event emitted once
+pub(crate) struct AdminOp {
+    id: u64,
+    operation: &'static str,
+    parameters: String,
+    started: SystemTime,
+}
+
+
+/// per store admin channel and optional on-disk audit log,
+/// audit log is independent from data WAL, one tab separated line per event
+pub(crate) struct AdminLog {
+    store: String,
+    sender: broadcast::Sender<AdminEvent>,
+    file: Option<Mutex<File>>,
+    next_id: AtomicU64,
+    clock: Arc<dyn Clock>,
+}
+
+impl AdminLog {
+    pub fn open(store: &str, path: Option<&str>, clock: Arc<dyn Clock>) -> Result<Self, String> {
+        let file = match path {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("admin log {}: {}", path, e))?;
+                Some(Mutex::new(file))
+            }
+            None => None,
+        };
+
+        let (sender, _) = broadcast::channel(ADMIN_CHANNEL_SIZE);
+
+        Ok(AdminLog {
+            store: store.to_owned(),
+            sender,
+            file,
+            next_id: AtomicU64::new(1),
+            clock,
+        })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AdminEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn start(&self, operation: &'static str, parameters: String) -> AdminOp {
+        let op = AdminOp {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            operation,
+            parameters,
+            started: self.clock.now(),
+        };
+
+        self.emit(AdminEvent {
+            id: op.id,
+            store: self.store.clone(),
+            operation: op.operation,
+            parameters: op.parameters.clone(),
+            started: op.started,
+            finished: None,
+            outcome: AdminOutcome::Started,
+            bytes: 0,
+        });
+
+        op
+    }
+
+    /// Ok(bytes affected) or Err(reason)
+    pub fn finish(&self, op: AdminOp, result: Result<u64, String>) {
+        let (outcome, bytes) = match result {
+            Ok(bytes) => (AdminOutcome::Succeeded, bytes),
+            Err(reason) => (AdminOutcome::Failed(reason), 0),
+        };
+
+        self.emit(AdminEvent {
+            id: op.id,
+            store: self.store.clone(),
+            operation: op.operation,
+            parameters: op.parameters,
+            started: op.started,
+            finished: Some(self.clock.now()),
+            outcome,
+            bytes,
+        });
+    }
+
+    /// condition of store, started and finished now
+    pub fn notice(&self, operation: &'static str, parameters: String, bytes: u64) {
+        let now = self.clock.now();
+
+        self.emit(AdminEvent {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            store: self.store.clone(),
+            operation,
+            parameters,
+            started: now,
+            finished: Some(now),
+            outcome: AdminOutcome::Notice,
+            bytes,
+        });
+    }
+
+    fn emit(&self, event: AdminEvent) {
+        if let Some(file) = &self.file {
+            if let Err(e) = writeln!(file.lock(), "{}", to_line(&event)) {
+                eprintln!("admin log: {}", e);
+            }
+        }
+
+        // no subscriber is not an error
+        let _ = self.sender.send(event);
+    }
+}
+
+
+// id | store | operation | outcome | started_ms | finished_ms | bytes | parameters
+fn to_line(event: &AdminEvent) -> String {
+    let millis = |t: SystemTime| t.duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+
+    let outcome = match &event.outcome {
+        AdminOutcome::Started => "started".to_owned(),
+        AdminOutcome::Succeeded => "succeeded".to_owned(),
+        AdminOutcome::Failed(reason) => format!("failed: {}", reason.replace(['\t', '\n'], " ")),
+        AdminOutcome::Notice => "notice".to_owned(),
+    };
+
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        event.id,
+        event.store,
+        event.operation,
+        outcome,
+        millis(event.started),
+        event.finished.map(millis).map(|m| m.to_string()).unwrap_or_default(),
+        event.bytes,
+        event.parameters.replace(['\t', '\n'], " "),
+    )
+}
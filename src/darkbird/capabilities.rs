@@ -0,0 +1,196 @@
+use serde::Serialize;
+
+use super::{capacity::EvictionPolicy, compression::Compression, recovery::{ClockSkewPolicy, RecoveryMode}, wal::{codec::WalCodec, writer::WalWriter}, Options, StorageType};
+
+
+
+// tokenizer of InvertedIndex
+const SEARCH_TOKENIZER: &str = "alphanumeric-lowercase";
+
+
+/// Features enabled on a store and their parameters,
+/// derived from Options so every knob is reported
+#[derive(Clone, Debug, Serialize)]
+pub struct Capabilities {
+    pub store: String,
+    pub storage_type: StorageType,
+    pub total_page_size: usize,
+
+    // Reporter events
+    pub reporter: bool,
+
+    pub coalesce_ms: Option<u64>,
+    pub lag_threshold: Option<usize>,
+    pub value_compression: Option<Compression>,
+    pub lanes: usize,
+
+    // on-disk admin audit log path
+    pub admin_log: Option<String>,
+
+    pub search_tokenizer: &'static str,
+
+    // Storage::notify_at
+    pub timers: bool,
+
+    // write plugins in chain order
+    pub plugins: Vec<String>,
+
+    // ReadRepair hook on reads
+    pub read_repair: bool,
+
+    // refresh interval of read snapshot
+    pub read_snapshot_ms: Option<u64>,
+
+    // open accepted types other than recorded ones
+    pub schema_override: bool,
+
+    // read counters of Storage::top_accessed
+    pub track_access: bool,
+
+    // ordered keys of Storage::iter_prefix
+    pub ordered_keys: bool,
+
+    // derived structures built after replay
+    pub low_memory_replay: bool,
+
+    // Event::Removed and Event::Replaced
+    pub previous_values: bool,
+
+    // bounds of events kept for Storage::subscribe_from_seq
+    pub event_retention: Option<usize>,
+    pub event_retention_ms: Option<u64>,
+
+    // encoding of WAL records
+    pub wal_codec: WalCodec,
+
+    // how WAL pages are written
+    pub wal_writer: WalWriter,
+
+    // sealed pages sent to an ArchiveHook
+    pub archive_hook: bool,
+
+    // handling of corrupted WAL records on open
+    pub recovery: RecoveryMode,
+
+    // WAL written away from store clock on open, threshold of Options::with_clock_skew
+    pub clock_skew_ms: Option<u64>,
+    pub clock_skew_policy: ClockSkewPolicy,
+
+    // bound of documents held and what a full store do with new keys
+    pub capacity: Option<usize>,
+    pub eviction: EvictionPolicy,
+
+    // bounds of QueryBuilder::cached results
+    pub query_cache: Option<usize>,
+    pub query_cache_bytes: Option<usize>,
+
+    // WAL fault injection (test-util)
+    pub faulty_wal: bool,
+
+    // Storage::latency_report recorders (metrics feature)
+    pub latency_metrics: bool,
+
+    // MetricsObserver called on operations
+    pub metrics_observer: bool,
+
+    // expected keys and false positive rate of bloom filter on lookups
+    pub bloom_filter: Option<usize>,
+    pub bloom_false_positive_rate: Option<f64>,
+
+    // free space of Options::with_disk_space_watch noticed below, and turning store read-only below
+    pub disk_space_threshold: Option<u64>,
+    pub read_only_floor: Option<u64>,
+}
+
+
+impl Options {
+    pub fn capabilities(&self) -> Capabilities {
+        // exhaustive on purpose, a new knob must be reported here
+        let Options {
+            path: _,
+            storage_name,
+            total_page_size,
+            stype,
+            off_reporter,
+            coalesce,
+            lag_threshold,
+            value_compression,
+            lanes,
+            admin_log,
+            clock: _,
+            timers,
+            plugins,
+            read_repair,
+            read_snapshot,
+            schema_override,
+            track_access,
+            ordered_keys,
+            low_memory_replay,
+            previous_values,
+            event_retention,
+            wal_codec,
+            wal_writer,
+            archive_hook,
+            recovery,
+            clock_skew,
+            capacity,
+            eviction,
+            query_cache,
+            metrics_observer,
+            bloom_filter,
+            disk_space_watch,
+            io_budget: _,
+            load_progress: _,
+
+            #[cfg(feature = "test-util")]
+            wal_faults,
+        } = self;
+
+        #[cfg(feature = "test-util")]
+        let faulty_wal = wal_faults.is_some();
+
+        #[cfg(not(feature = "test-util"))]
+        let faulty_wal = false;
+
+        Capabilities {
+            store: storage_name.clone(),
+            storage_type: stype.clone(),
+            total_page_size: *total_page_size,
+            reporter: !off_reporter,
+            coalesce_ms: coalesce.map(|window| window.as_millis() as u64),
+            lag_threshold: *lag_threshold,
+            value_compression: *value_compression,
+            lanes: *lanes,
+            admin_log: admin_log.clone(),
+            search_tokenizer: SEARCH_TOKENIZER,
+            timers: *timers,
+            plugins: plugins.iter().map(|p| p.name.clone()).collect(),
+            read_repair: read_repair.is_some(),
+            read_snapshot_ms: read_snapshot.map(|interval| interval.as_millis() as u64),
+            schema_override: *schema_override,
+            track_access: *track_access,
+            ordered_keys: *ordered_keys,
+            low_memory_replay: *low_memory_replay,
+            previous_values: *previous_values,
+            event_retention: event_retention.map(|bounds| bounds.max_events),
+            event_retention_ms: event_retention.map(|bounds| bounds.max_age.as_millis() as u64),
+            wal_codec: *wal_codec,
+            wal_writer: *wal_writer,
+            archive_hook: archive_hook.is_some(),
+            recovery: *recovery,
+            clock_skew_ms: clock_skew.map(|(threshold, _)| threshold.as_millis() as u64),
+            clock_skew_policy: clock_skew.map_or(ClockSkewPolicy::default(), |(_, policy)| policy),
+            capacity: *capacity,
+            eviction: *eviction,
+            query_cache: query_cache.map(|(max_entries, _)| max_entries),
+            query_cache_bytes: query_cache.map(|(_, max_bytes)| max_bytes),
+            faulty_wal,
+            latency_metrics: cfg!(feature = "metrics"),
+            metrics_observer: metrics_observer.is_some(),
+            bloom_filter: bloom_filter.map(|(expected, _)| expected),
+            bloom_false_positive_rate: bloom_filter.map(|(_, rate)| rate),
+            disk_space_threshold: disk_space_watch.map(|(threshold, _)| threshold),
+            read_only_floor: disk_space_watch.and_then(|(_, floor)| floor),
+        }
+    }
+}
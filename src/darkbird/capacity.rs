@@ -0,0 +1,113 @@
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    hash::Hash,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+
+
+/// What an insert of a new key does once a store hold its capacity,
+/// set by `Options::with_capacity`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvictionPolicy {
+    // insert fails with SessionResult::CapacityExceeded
+    #[default]
+    RejectNew,
+
+    // least recently read or written key is removed first
+    EvictLru,
+
+    // key inserted first is removed first, reads don't count
+    EvictOldest,
+}
+
+
+/// stamps of keys eviction pick victims by, in memory only:
+/// rebuilt in WAL order by replay, so after open reads before it are forgotten
+pub(crate) struct Capacity<K> {
+    limit: usize,
+    policy: EvictionPolicy,
+
+    // logical clock of stamps, ordered not timed
+    tick: AtomicU64,
+
+    // key -> last access with EvictLru, first insert otherwise
+    stamps: DashMap<K, u64>,
+
+    // inserts of new keys, checked and applied one at a time so capacity isn't overshot
+    admit: tokio::sync::Mutex<()>,
+}
+
+impl<K> Capacity<K>
+where
+    K: Eq + Hash + Clone,
+{
+    pub fn new(limit: usize, policy: EvictionPolicy) -> Self {
+        Capacity {
+            limit,
+            policy,
+            tick: AtomicU64::new(0),
+            stamps: DashMap::new(),
+            admit: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    #[inline]
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    #[inline]
+    pub fn evicts(&self) -> bool {
+        self.policy != EvictionPolicy::RejectNew
+    }
+
+    pub async fn admit(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        self.admit.lock().await
+    }
+
+    /// key stored, an overwrite keep insert stamp of EvictOldest
+    #[inline]
+    pub fn written(&self, key: &K) {
+        match self.policy {
+            EvictionPolicy::EvictLru => {
+                self.stamps.insert(key.clone(), self.next());
+            }
+            _ => {
+                self.stamps.entry(key.clone()).or_insert_with(|| self.next());
+            }
+        }
+    }
+
+    /// key read, only EvictLru care
+    #[inline]
+    pub fn touch(&self, key: &K) {
+        if self.policy != EvictionPolicy::EvictLru {
+            return;
+        }
+        if let Some(mut stamp) = self.stamps.get_mut(key) {
+            *stamp = self.next();
+        }
+    }
+
+    #[inline]
+    pub fn forget(&self, key: &K) {
+        self.stamps.remove(key);
+    }
+
+    /// key of smallest stamp not in spared, walk every stamp
+    pub fn victim(&self, spared: &HashSet<&K>) -> Option<K> {
+        self.stamps
+            .iter()
+            .filter(|rf| !spared.contains(rf.key()))
+            .min_by_key(|rf| *rf.value())
+            .map(|rf| rf.key().clone())
+    }
+
+    #[inline]
+    fn next(&self) -> u64 {
+        self.tick.fetch_add(1, Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,112 @@
+use parking_lot::Mutex;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+// backward step of source reported as a jump
+const JUMP_THRESHOLD: Duration = Duration::from_secs(1);
+
+// shared by stores opened without `Options::with_clock`
+static DEFAULT_CLOCK: OnceLock<Arc<dyn Clock>> = OnceLock::new();
+
+
+
+/// Source of wall time for a store, every wall time read of store go through it
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+
+/// operating system wall clock, may go backwards on NTP step
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+
+
+struct Clamp {
+    // last time returned and monotonic instant it was read
+    last: SystemTime,
+    at: Instant,
+
+    // source is behind, jump already reported
+    behind: bool,
+}
+
+
+/// Wall clock anchored to monotonic clock, never go backwards.
+///
+/// when source step backwards, time keep advancing from last returned value
+/// at monotonic rate until source catch up, a step over `JUMP_THRESHOLD` is logged once.
+/// forward steps are followed
+pub struct ClampedClock {
+    source: Arc<dyn Clock>,
+    clamp: Mutex<Clamp>,
+    jumps: AtomicU64,
+}
+
+impl ClampedClock {
+    pub fn new(source: Arc<dyn Clock>) -> Self {
+        ClampedClock {
+            clamp: Mutex::new(Clamp {
+                last: source.now(),
+                at: Instant::now(),
+                behind: false,
+            }),
+            source,
+            jumps: AtomicU64::new(0),
+        }
+    }
+
+    /// backward jumps of source detected
+    pub fn backward_jumps(&self) -> u64 {
+        self.jumps.load(Ordering::SeqCst)
+    }
+}
+
+impl Clock for ClampedClock {
+    fn now(&self) -> SystemTime {
+        let mut clamp = self.clamp.lock();
+
+        let wall = self.source.now();
+        let at = Instant::now();
+        let floor = clamp.last + at.duration_since(clamp.at);
+
+        let now = match floor.duration_since(wall) {
+            // source behind floor
+            Ok(back) if !back.is_zero() => {
+                if back >= JUMP_THRESHOLD && !clamp.behind {
+                    clamp.behind = true;
+                    self.jumps.fetch_add(1, Ordering::SeqCst);
+                    eprintln!("clock: wall clock went backwards by {:?}, clamped", back);
+                }
+                floor
+            }
+            _ => {
+                clamp.behind = false;
+                wall
+            }
+        };
+
+        clamp.last = now;
+        clamp.at = at;
+        now
+    }
+}
+
+
+/// clamped system clock shared by whole process
+pub(crate) fn default_clock() -> Arc<dyn Clock> {
+    DEFAULT_CLOCK
+        .get_or_init(|| Arc::new(ClampedClock::new(Arc::new(SystemClock))))
+        .clone()
+}
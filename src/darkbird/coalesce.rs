@@ -0,0 +1,138 @@
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{collections::HashMap, hash::Hash, sync::Arc, time::Duration};
+use tokio::time;
+
+use super::{
+    router, SessionResult,
+    storage::{Event, RQuery},
+    wal::{codec::WalCodec, disk_log::Session},
+};
+
+
+
+/// Collapse writes to the same key within `window` to the last one,
+/// only WAL logging and event dispatch are delayed, memory always is newest.
+///
+/// Durability: at most `window` of writes lost on crash,
+/// last write always logged when window elapsed or Storage dropped.
+/// a query WAL refused stay pending and is retried on next flush,
+/// unless a newer write of its key replaced it, `close` return the error
+pub struct Coalescer<K, Doc> {
+    pending: Arc<Mutex<HashMap<K, RQuery<K, Doc>>>>,
+
+    // held while a flush in progress, so close wait for a running flush
+    flushing: Arc<tokio::sync::Mutex<()>>,
+
+    wal_session: Option<Session>,
+    codec: WalCodec,
+    reporter_session: Option<router::Session<Event<K, Doc>>>,
+}
+
+impl<K, Doc> Coalescer<K, Doc>
+where
+    Doc: Serialize + Clone + Send + 'static,
+    K: Serialize + Eq + Hash + Clone + Send + Sync + 'static,
+{
+    pub fn run_service(
+        window: Duration,
+        wal_session: Option<Session>,
+        codec: WalCodec,
+        reporter_session: Option<router::Session<Event<K, Doc>>>,
+    ) -> Self {
+        let pending = Arc::new(Mutex::new(HashMap::new()));
+        let flushing = Arc::new(tokio::sync::Mutex::new(()));
+
+        let shared = pending.clone();
+        let lock = flushing.clone();
+        let (wal, reporter) = (wal_session.clone(), reporter_session.clone());
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(window);
+            loop {
+                interval.tick().await;
+
+                // Storage dropped, flush remaining and terminate
+                let last_round = Arc::strong_count(&shared) == 1;
+
+                let guard = lock.lock().await;
+                if let Err(e) = flush(&shared, &wal, codec, &reporter).await {
+                    eprintln!("coalescer: {}, retried on next flush", e.to_string());
+                }
+                drop(guard);
+
+                if last_round {
+                    return;
+                }
+            }
+        });
+
+        Coalescer { pending, flushing, wal_session, codec, reporter_session }
+    }
+
+    /// flush pending queries now, used before closing storage, return queries flushed,
+    /// Err if WAL refused one of them
+    pub async fn close(self) -> Result<usize, SessionResult> {
+        let _guard = self.flushing.lock().await;
+        flush(&self.pending, &self.wal_session, self.codec, &self.reporter_session).await
+    }
+
+    /// pending queries as WAL records, once a running flush is done. without a runtime,
+    /// for a store dropped without close: subscribers don't receive them
+    pub fn drain_blocking(self) -> Vec<Vec<u8>> {
+        let _guard = self.flushing.blocking_lock();
+        let queries = std::mem::take(&mut *self.pending.lock());
+
+        match self.wal_session {
+            Some(_) => queries.into_values().map(|query| query.to_record(self.codec)).collect(),
+            None => vec![],
+        }
+    }
+
+    /// stash query, replace previous query of same key.
+    /// a patch is kept as document it produced, queries it applied to may be replaced
+    #[inline]
+    pub fn stash(&self, key: K, query: RQuery<K, Doc>) {
+        let query = match query {
+            RQuery::Patch(key, _, doc) => RQuery::Update(key, doc),
+            query => query,
+        };
+        self.pending.lock().insert(key, query);
+    }
+}
+
+async fn flush<K, Doc>(
+    pending: &Mutex<HashMap<K, RQuery<K, Doc>>>,
+    wal_session: &Option<Session>,
+    codec: WalCodec,
+    reporter_session: &Option<router::Session<Event<K, Doc>>>,
+) -> Result<usize, SessionResult>
+where
+    Doc: Serialize + Clone + Send + 'static,
+    K: Serialize + Eq + Hash + Clone + Send + 'static,
+{
+    let queries = std::mem::take(&mut *pending.lock());
+    let mut flushed = 0;
+    let mut failed = None;
+
+    for (key, query) in queries {
+        if let Some(wal) = wal_session {
+            if let Err(e) = wal.log(query.to_record(codec)).await {
+                // put back for next flush, a write stashed meanwhile is newer
+                pending.lock().entry(key).or_insert(query);
+                failed = Some(e);
+                continue;
+            }
+        }
+
+        if let Some(reporter) = reporter_session {
+            let _ = reporter.dispatch(Event::Query(query)).await;
+        }
+        flushed += 1;
+    }
+
+    match failed {
+        Some(e) => Err(e),
+        None => Ok(flushed),
+    }
+}
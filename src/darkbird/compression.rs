@@ -0,0 +1,43 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+
+
+const HEADER_LZ4: u8 = 1;
+
+
+/// value compression for documents held in memory
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Compression {
+    Lz4,
+}
+
+impl Compression {
+    /// serialize and compress document,
+    /// first byte is header refer to algorithm
+    #[inline]
+    pub fn compress<Doc: Serialize>(&self, doc: &Doc) -> Vec<u8> {
+        let raw = bincode::serialize(doc).unwrap();
+
+        match self {
+            Compression::Lz4 => {
+                let compressed = lz4_flex::compress_prepend_size(&raw);
+                let mut buf = Vec::with_capacity(1 + compressed.len());
+                buf.push(HEADER_LZ4);
+                buf.extend_from_slice(&compressed);
+                buf
+            }
+        }
+    }
+
+    /// decompress and deserialize document
+    #[inline]
+    pub fn decompress<Doc: DeserializeOwned>(bytes: &[u8]) -> Result<Doc, String> {
+        match bytes.first() {
+            Some(&HEADER_LZ4) => {
+                let raw = lz4_flex::decompress_size_prepended(&bytes[1..]).map_err(|e| e.to_string())?;
+                bincode::deserialize(&raw).map_err(|e| e.to_string())
+            }
+            _ => Err("unknown compression header".to_owned()),
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use serde::Deserialize;
+use std::{fmt, time::Duration};
+
+use super::{
+    capacity::EvictionPolicy,
+    compression::Compression,
+    recovery::{ClockSkewPolicy, RecoveryMode},
+    wal::{codec::WalCodec, writer::WalWriter},
+    Options, StorageType,
+};
+
+
+
+/// Owned store configuration, deserializable from TOML/JSON/...
+///
+/// every serializable `Options` knob, named like its `Capabilities` field.
+/// knobs taking two values are two fields, set together
+///
+/// ```ignore
+/// path = "/var/lib/app"
+/// storage_name = "users"
+/// storage_type = "DiskCopies"
+/// total_page_size = 10000
+/// coalesce_ms = 50
+/// wal_codec = "Json"
+/// recovery = "SkipCorrupted"
+/// capacity = 100000
+/// eviction = "EvictLru"
+/// bloom_filter = 100000
+/// bloom_false_positive_rate = 0.01
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct StoreConfig {
+    pub path: String,
+    pub storage_name: String,
+    pub storage_type: StorageType,
+
+    #[serde(default = "default_page_size")]
+    pub total_page_size: usize,
+
+    #[serde(default)]
+    pub off_reporter: bool,
+
+    #[serde(default)]
+    pub coalesce_ms: Option<u64>,
+
+    #[serde(default)]
+    pub lag_threshold: Option<usize>,
+
+    #[serde(default)]
+    pub value_compression: Option<Compression>,
+
+    #[serde(default)]
+    pub lanes: Option<usize>,
+
+    #[serde(default)]
+    pub admin_log: Option<String>,
+
+    #[serde(default)]
+    pub timers: bool,
+
+    #[serde(default)]
+    pub read_snapshot_ms: Option<u64>,
+
+    #[serde(default)]
+    pub schema_override: bool,
+
+    #[serde(default)]
+    pub track_access: bool,
+
+    #[serde(default)]
+    pub ordered_keys: bool,
+
+    #[serde(default)]
+    pub low_memory_replay: bool,
+
+    #[serde(default)]
+    pub previous_values: bool,
+
+    // max events and max age, with_event_retention
+    #[serde(default)]
+    pub event_retention: Option<usize>,
+
+    #[serde(default)]
+    pub event_retention_ms: Option<u64>,
+
+    #[serde(default)]
+    pub wal_codec: WalCodec,
+
+    #[serde(default)]
+    pub wal_writer: WalWriter,
+
+    #[serde(default)]
+    pub recovery: RecoveryMode,
+
+    // threshold and policy, with_clock_skew. policy default to TrustClock
+    #[serde(default)]
+    pub clock_skew_ms: Option<u64>,
+
+    #[serde(default)]
+    pub clock_skew_policy: Option<ClockSkewPolicy>,
+
+    // max documents and policy, with_capacity. policy default to RejectNew
+    #[serde(default)]
+    pub capacity: Option<usize>,
+
+    #[serde(default)]
+    pub eviction: Option<EvictionPolicy>,
+
+    // max entries and max bytes, with_query_cache
+    #[serde(default)]
+    pub query_cache: Option<usize>,
+
+    #[serde(default)]
+    pub query_cache_bytes: Option<usize>,
+
+    // expected keys and false positive rate, with_bloom_filter
+    #[serde(default)]
+    pub bloom_filter: Option<usize>,
+
+    #[serde(default)]
+    pub bloom_false_positive_rate: Option<f64>,
+
+    // threshold and read-only floor in bytes, with_disk_space_watch
+    #[serde(default)]
+    pub disk_space_threshold: Option<u64>,
+
+    #[serde(default)]
+    pub read_only_floor: Option<u64>,
+}
+
+
+#[derive(Debug)]
+pub struct ConfigError {
+    // offending field
+    pub field: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.reason)
+    }
+}
+
+
+impl Options {
+    pub fn from_config(cfg: &StoreConfig) -> Result<Options, ConfigError> {
+        if cfg.path.is_empty() {
+            return Err(config_error("path", "must not be empty"));
+        }
+
+        if cfg.storage_name.is_empty() {
+            return Err(config_error("storage_name", "must not be empty"));
+        }
+
+        if cfg.storage_name.contains(['/', '\\']) {
+            return Err(config_error("storage_name", "must not contain path separator"));
+        }
+
+        if cfg.total_page_size == 0 {
+            return Err(config_error("total_page_size", "must be greater than zero"));
+        }
+
+        if cfg.lanes == Some(0) {
+            return Err(config_error("lanes", "must be greater than zero"));
+        }
+
+        if cfg.coalesce_ms == Some(0) {
+            return Err(config_error("coalesce_ms", "must be greater than zero"));
+        }
+
+        if cfg.timers && cfg.off_reporter {
+            return Err(config_error("timers", "require reporter, off_reporter is set"));
+        }
+
+        if cfg.read_snapshot_ms == Some(0) {
+            return Err(config_error("read_snapshot_ms", "must be greater than zero"));
+        }
+
+        let event_retention = paired(("event_retention", cfg.event_retention), ("event_retention_ms", cfg.event_retention_ms))?;
+        if event_retention.is_some() && cfg.off_reporter {
+            return Err(config_error("event_retention", "require reporter, off_reporter is set"));
+        }
+
+        if cfg.clock_skew_ms == Some(0) {
+            return Err(config_error("clock_skew_ms", "must be greater than zero"));
+        }
+        if cfg.clock_skew_policy.is_some() && cfg.clock_skew_ms.is_none() {
+            return Err(config_error("clock_skew_policy", "require clock_skew_ms"));
+        }
+
+        if cfg.capacity == Some(0) {
+            return Err(config_error("capacity", "must be greater than zero"));
+        }
+        if cfg.eviction.is_some() && cfg.capacity.is_none() {
+            return Err(config_error("eviction", "require capacity"));
+        }
+
+        let query_cache = paired(("query_cache", cfg.query_cache), ("query_cache_bytes", cfg.query_cache_bytes))?;
+
+        if cfg.bloom_filter == Some(0) {
+            return Err(config_error("bloom_filter", "must be greater than zero"));
+        }
+        if let Some(rate) = cfg.bloom_false_positive_rate {
+            if !(rate > 0.0 && rate <= 0.5) {
+                return Err(config_error("bloom_false_positive_rate", "must be in (0, 0.5]"));
+            }
+        }
+        let bloom_filter = match (cfg.bloom_filter, cfg.bloom_false_positive_rate) {
+            (Some(expected), Some(rate)) => Some((expected, rate)),
+            (Some(_), None) => return Err(config_error("bloom_filter", "require bloom_false_positive_rate")),
+            (None, Some(_)) => return Err(config_error("bloom_false_positive_rate", "require bloom_filter")),
+            (None, None) => None,
+        };
+
+        if cfg.disk_space_threshold == Some(0) {
+            return Err(config_error("disk_space_threshold", "must be greater than zero"));
+        }
+        match (cfg.disk_space_threshold, cfg.read_only_floor) {
+            (None, Some(_)) => return Err(config_error("read_only_floor", "require disk_space_threshold")),
+            (Some(threshold), Some(floor)) if floor > threshold => {
+                return Err(config_error("read_only_floor", "must not exceed disk_space_threshold"));
+            }
+            _ => {}
+        }
+
+        let mut opts = Options::new(
+            &cfg.path,
+            &cfg.storage_name,
+            cfg.total_page_size,
+            cfg.storage_type.clone(),
+            cfg.off_reporter,
+        );
+
+        if let Some(ms) = cfg.coalesce_ms {
+            opts = opts.with_coalesce(Duration::from_millis(ms));
+        }
+
+        if let Some(threshold) = cfg.lag_threshold {
+            opts = opts.with_lag_threshold(threshold);
+        }
+
+        if let Some(compression) = cfg.value_compression {
+            opts = opts.with_value_compression(compression);
+        }
+
+        if let Some(lanes) = cfg.lanes {
+            opts = opts.with_lanes(lanes);
+        }
+
+        if let Some(path) = &cfg.admin_log {
+            if path.is_empty() {
+                return Err(config_error("admin_log", "must not be empty"));
+            }
+            opts = opts.with_admin_log(path);
+        }
+
+        if cfg.timers {
+            opts = opts.with_timers();
+        }
+
+        if let Some(ms) = cfg.read_snapshot_ms {
+            opts = opts.with_read_snapshot(Duration::from_millis(ms));
+        }
+
+        if cfg.schema_override {
+            opts = opts.with_schema_override();
+        }
+
+        if cfg.track_access {
+            opts = opts.with_access_tracking();
+        }
+
+        if cfg.ordered_keys {
+            opts = opts.with_ordered_keys();
+        }
+
+        if cfg.low_memory_replay {
+            opts = opts.with_low_memory_replay();
+        }
+
+        if cfg.previous_values {
+            opts = opts.with_previous_values();
+        }
+
+        if let Some((max_events, max_age_ms)) = event_retention {
+            opts = opts.with_event_retention(max_events, Duration::from_millis(max_age_ms));
+        }
+
+        opts = opts
+            .with_wal_codec(cfg.wal_codec)
+            .with_wal_writer(cfg.wal_writer)
+            .with_recovery(cfg.recovery);
+
+        if let Some(ms) = cfg.clock_skew_ms {
+            opts = opts.with_clock_skew(Duration::from_millis(ms), cfg.clock_skew_policy.unwrap_or_default());
+        }
+
+        if let Some(max) = cfg.capacity {
+            opts = opts.with_capacity(max, cfg.eviction.unwrap_or_default());
+        }
+
+        if let Some((max_entries, max_bytes)) = query_cache {
+            opts = opts.with_query_cache(max_entries, max_bytes);
+        }
+
+        if let Some((expected_keys, rate)) = bloom_filter {
+            opts = opts.with_bloom_filter(expected_keys, rate);
+        }
+
+        if let Some(threshold) = cfg.disk_space_threshold {
+            opts = opts.with_disk_space_watch(threshold, cfg.read_only_floor);
+        }
+
+        Ok(opts)
+    }
+}
+
+
+fn default_page_size() -> usize {
+    super::wal::disk_log::DEFAULT_PAGE_SIZE
+}
+
+// two fields set together, both greater than zero
+fn paired<A, B>(a: (&'static str, Option<A>), b: (&'static str, Option<B>)) -> Result<Option<(A, B)>, ConfigError>
+where
+    A: Default + PartialEq,
+    B: Default + PartialEq,
+{
+    match (a.1, b.1) {
+        (Some(x), _) if x == A::default() => Err(config_error(a.0, "must be greater than zero")),
+        (_, Some(y)) if y == B::default() => Err(config_error(b.0, "must be greater than zero")),
+        (Some(x), Some(y)) => Ok(Some((x, y))),
+        (Some(_), None) => Err(ConfigError { field: a.0, reason: format!("require {}", b.0) }),
+        (None, Some(_)) => Err(ConfigError { field: b.0, reason: format!("require {}", a.0) }),
+        (None, None) => Ok(None),
+    }
+}
+
+fn config_error(field: &'static str, reason: &str) -> ConfigError {
+    ConfigError {
+        field,
+        reason: reason.to_owned(),
+    }
+}
@@ -0,0 +1,28 @@
+// entries walked between yields to runtime in long scans
+pub(crate) const SCAN_YIELD: usize = 256;
+
+
+
+/// counts entries of a long scan and yield to runtime every `SCAN_YIELD`,
+/// so a full-store scan on a runtime worker don't starve other tasks
+/// (DiskLog and Router services of every store run on same runtime)
+pub(crate) struct Budget {
+    left: usize,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget { left: SCAN_YIELD }
+    }
+}
+
+impl Budget {
+    #[inline]
+    pub async fn tick(&mut self) {
+        self.left -= 1;
+        if self.left == 0 {
+            self.left = SCAN_YIELD;
+            tokio::task::yield_now().await;
+        }
+    }
+}
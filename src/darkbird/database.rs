@@ -1,12 +1,13 @@
 use anymap::AnyMap;
-use dashmap::{mapref::one::Ref, iter::Iter, DashSet};
-use tokio::sync::mpsc::Sender;
-use std::{hash::Hash, sync::Arc, time::Duration};
+use bytes::Bytes;
+use dashmap::{mapref::{multiple::RefMulti, one::Ref}, iter::Iter, DashSet};
+use tokio::sync::{broadcast, mpsc::Sender, watch};
+use std::{borrow::Borrow, collections::BTreeMap, hash::Hash, io::{Read, Write}, sync::Arc, time::Duration};
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::{Storage, document::Document, Event};
+use crate::{Storage, Derivation, StorageStats, MetricsSnapshot, KeyPage, AccessReport, QueryCacheStats, EventFilter, SubscribeFilter, KeyCodec, CheckpointReport, ExportOptions, ExportFormat, ConflictPolicy, ImportReport, CloseReport, CompactReport, RepairStats, AdminEvent, document::{Document, FieldValue}, PageOrder, PinnedDoc, Event, RQuery, SubscriberInfo, SubscriptionId};
 
-use super::{SessionResult, storage_redis::RedisStorage};
+use super::{SessionResult, storage_redis::{CacheHandle, Numeric, RedisStorage}, storage_bytes::BytesStorage, reference::{Plan, References}, registry::Registry, capabilities::Capabilities, snapshot::ConsistentView, transaction::Transaction, views::ViewFn, search::SearchQuery, patch::DocPatch};
 
 
 
@@ -17,13 +18,75 @@ pub struct Database {
 impl Database {
     
 
+    /// datastores as given, unchecked: a store inserted twice in AnyMap replaced the first.
+    /// prefer `Schema`, which reject duplicate stores, and `Schema::open`
     pub fn open(datastores: AnyMap) -> Database {
         Database { datastores }
     }
 
 
+    /// close every datastore in registration order, see `Storage::close`,
+    /// a failed close doesn't stop the others.
+    /// AnyMap can't be walked, stores are reached through registry of `Schema`:
+    /// a Database built by `open` from a plain AnyMap close nothing, close its stores before
+    pub async fn close_all(mut self) -> Vec<(String, Result<CloseReport, SessionResult>)> {
+        match self.datastores.remove::<Registry>() {
+            None => vec![],
+            Some(registry) => registry.close_all(&mut self.datastores).await,
+        }
+    }
+
+
+    /// capabilities of every datastore keyed by store type name
+    pub fn capabilities_all(&self) -> BTreeMap<String, Capabilities> {
+        match self.datastores.get::<Registry>() {
+            None => BTreeMap::new(),
+            Some(registry) => registry.capabilities_all(&self.datastores),
+        }
+    }
+
+
+    /// Run `f` against every datastore as of one barrier,
+    /// for reads that must be mutually consistent across stores (reports, ...).
+    ///
+    /// writes of all stores are paused only to take barrier, bounded and measured
+    /// by `ConsistentView::pause`, `f` run right after without yielding.
+    /// Timeout if barrier couldn't be taken in bound, e.g. during long index rebuild
+    pub async fn consistent_read<R>(&self, f: impl FnOnce(&ConsistentView) -> R) -> Result<R, SessionResult> {
+        let pause = match self.datastores.get::<Registry>() {
+            None => Duration::ZERO,
+            Some(registry) => registry.barrier(&self.datastores).await?,
+        };
+
+        let view = ConsistentView::new(&self.datastores, pause);
+        Ok(f(&view))
+    }
+
+
+    /// Apply writes to several stores atomically, see `Transaction`.
+    ///
+    /// `f` buffer writes in transaction, nothing is applied if it return an error.
+    /// then every store involved log its writes to its WAL before any change is visible,
+    /// waiting for its WAL worker to write them, and if one fail to log,
+    /// writes logged by the others are logged back and error returned.
+    /// `consistent_read` never see part of it applied.
+    /// `Storage::transaction` apply queries of one store
+    pub async fn cross_transaction<F>(&self, f: F) -> Result<(), SessionResult>
+    where
+        F: AsyncFnOnce(&mut Transaction) -> Result<(), SessionResult>,
+    {
+        let mut tx = Transaction::new();
+        f(&mut tx).await?;
+
+        if tx.is_empty() {
+            return Ok(());
+        }
+        tx.commit(&self.datastores).await
+    }
+
+
     #[inline]        
-    pub async fn subscribe<K, Doc>(&self, sender: Sender<Event<K, Doc>>) -> Result<(), SessionResult> 
+    pub async fn subscribe<K, Doc>(&self, sender: Sender<Event<K, Doc>>) -> Result<SubscriptionId, SessionResult> 
     where
         Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
         K:  Serialize
@@ -46,8 +109,9 @@ impl Database {
         }
     }
 
+    /// see `Storage::subscribe_filtered`
     #[inline]        
-    pub async fn insert<K, Doc>(&self, key: K, doc: Doc) -> Result<(), SessionResult>
+    pub async fn subscribe_filtered<K, Doc>(&self, sender: Sender<Event<K, Doc>>, filter: SubscribeFilter<K, Doc>) -> Result<SubscriptionId, SessionResult> 
     where
         Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
         K:  Serialize
@@ -65,13 +129,14 @@ impl Database {
         match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                datastore.insert(key, doc).await
+                datastore.subscribe_filtered(sender, filter).await
             }
         }
     }
 
+    /// see `Storage::subscribe_from_seq`
     #[inline]        
-    pub async fn remove<K, Doc>(&self, key: K) -> Result<(), SessionResult>
+    pub async fn subscribe_from_seq<K, Doc>(&self, sender: Sender<(u64, Event<K, Doc>)>, last_seen_seq: u64) -> Result<SubscriptionId, SessionResult> 
     where
         Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
         K:  Serialize
@@ -89,15 +154,14 @@ impl Database {
         match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                datastore.remove(key).await
+                datastore.subscribe_from_seq(sender, last_seen_seq).await
             }
         }
     }
 
-
-    
+    /// see `Storage::unsubscribe`
     #[inline]        
-    pub fn gets<'a, K, Doc>(&self, list: Vec<&K>) -> Result<Vec<Ref<K, Doc>>, SessionResult>
+    pub async fn unsubscribe<K, Doc>(&self, id: SubscriptionId) -> Result<bool, SessionResult> 
     where
         Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
         K:  Serialize
@@ -115,15 +179,14 @@ impl Database {
         match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                let res = datastore.gets(list);
-                Ok(res)
+                datastore.unsubscribe(id).await
             }
         }
     }
 
-
+    /// see `Storage::subscribe_matching`
     #[inline]        
-    pub fn range<K, Doc>(&self, field_name: &str, from: String, to: String) -> Result<Vec<Ref<K, Doc>>, SessionResult>
+    pub async fn subscribe_matching<K, Doc>(&self, sender: Sender<Event<K, Doc>>, filter: EventFilter) -> Result<SubscriptionId, SessionResult> 
     where
         Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
         K:  Serialize
@@ -137,20 +200,18 @@ impl Database {
             + Send
             + Sync
             + 'static
+            + KeyCodec
     {
         match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                let res = datastore.range(field_name, from, to);
-                Ok(res)
+                datastore.subscribe_matching(sender, filter).await
             }
         }
     }
 
-
-
     #[inline]        
-    pub fn lookup<K, Doc>(&self, key: &K) -> Result<Option<Ref<K, Doc>>, SessionResult> 
+    pub async fn subscriber_report<K, Doc>(&self) -> Result<Vec<SubscriberInfo>, SessionResult> 
     where
         Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
         K:  Serialize
@@ -168,15 +229,35 @@ impl Database {
         match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                let res = datastore.lookup(key);
-                Ok(res)
+                datastore.subscriber_report().await
             }
         }
     }
 
+    #[inline]        
+    pub fn admin_events<K, Doc>(&self) -> Result<broadcast::Receiver<AdminEvent>, SessionResult> 
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => Ok(datastore.admin_events())
+        }
+    }
 
     #[inline]        
-    pub fn lookup_by_index<K, Doc>(&self, index_key: &str) -> Result<Option<Ref<K, Doc>>, SessionResult>
+    pub async fn insert<K, Doc>(&self, key: K, doc: Doc) -> Result<(), SessionResult>
     where
         Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
         K:  Serialize
@@ -194,16 +275,13 @@ impl Database {
         match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                let res = datastore.lookup_by_index(index_key);
-                Ok(res)
+                datastore.insert(key, doc).await
             }
         }
     }
 
-
-
     #[inline]        
-    pub fn lookup_by_tag<K, Doc>(&self, tag: &str) -> Result<Vec<Ref<K, Doc>>, SessionResult>
+    pub async fn remove<K, Doc>(&self, key: K) -> Result<(), SessionResult>
     where
         Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
         K:  Serialize
@@ -221,16 +299,65 @@ impl Database {
         match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                let res = datastore.lookup_by_tag(tag);
-                Ok(res)
+                datastore.remove(key).await
             }
         }
     }
 
+    /// see `Storage::update`
+    #[inline]        
+    pub async fn update<K, Doc, F>(&self, key: &K, f: F) -> Result<bool, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        F: FnOnce(&mut Doc),
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.update(key, f).await
+            }
+        }
+    }
 
+    /// see `Storage::patch`
+    #[inline]
+    pub async fn patch<K, Doc>(&self, key: &K, patch: DocPatch) -> Result<bool, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.patch(key, patch).await
+            }
+        }
+    }
 
-    #[inline]        
-    pub fn fetch_view<K, Doc>(&self, view_name: &str) -> Result<Vec<Ref<K, Doc>>, SessionResult>
+    /// see `Storage::clear`
+    #[inline]
+    pub async fn clear<K, Doc>(&self) -> Result<usize, SessionResult>
     where
         Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
         K:  Serialize
@@ -243,23 +370,71 @@ impl Database {
             + Clone
             + Send
             + Sync
-            + 'static
+            + 'static,
     {
         match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                let res = datastore.fetch_view(view_name);
-                Ok(res)
+                datastore.clear().await
             }
         }
     }
 
+    /// see `Storage::add_tag`
+    #[inline]
+    pub async fn add_tag<K, Doc>(&self, key: &K, tag: &str) -> Result<(), SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.add_tag(key, tag).await
+            }
+        }
+    }
 
+    /// see `Storage::remove_tag`
+    #[inline]
+    pub async fn remove_tag<K, Doc>(&self, key: &K, tag: &str) -> Result<(), SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.remove_tag(key, tag).await
+            }
+        }
+    }
 
+    /// see `Storage::compare_and_swap`
     #[inline]        
-    pub fn search<K, Doc>(&self, text: String) -> Result<Vec<Ref<K, Doc>>, SessionResult>
+    pub async fn compare_and_swap<K, Doc>(&self, key: K, expected: Doc, new: Doc) -> Result<bool, SessionResult>
     where
-        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document + PartialEq,
         K:  Serialize
             + DeserializeOwned
             + PartialOrd
@@ -275,16 +450,40 @@ impl Database {
         match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                let res = datastore.search(text);
-                Ok(res)
+                datastore.compare_and_swap(key, expected, new).await
             }
         }
     }
 
+    /// see `Storage::remove_if`
+    #[inline]        
+    pub async fn remove_if<K, Doc, F>(&self, key: K, predicate: F) -> Result<bool, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        F: FnOnce(&Doc) -> bool
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.remove_if(key, predicate).await
+            }
+        }
+    }
 
-
+    /// see `Storage::insert_if_absent`
     #[inline]        
-    pub fn iter<K, Doc>(&self) -> Result<Iter<'_, K, Doc>, SessionResult>
+    pub async fn insert_if_absent<K, Doc>(&self, key: K, doc: Doc) -> Result<bool, SessionResult>
     where
         Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
         K:  Serialize
@@ -302,15 +501,15 @@ impl Database {
         match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                let res = datastore.iter();
-                Ok(res)
+                datastore.insert_if_absent(key, doc).await
             }
         }
     }
 
 
+    /// see `Storage::get_or_insert`
     #[inline]        
-    pub fn iter_index<K, Doc>(&self) -> Result<Iter<String, K>, SessionResult>
+    pub async fn get_or_insert<K, Doc>(&self, key: K, default: impl FnOnce() -> Doc) -> Result<Ref<'_, K, Doc>, SessionResult>
     where
         Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
         K:  Serialize
@@ -328,15 +527,15 @@ impl Database {
         match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                let res = datastore.iter_index();
-                Ok(res)
+                datastore.get_or_insert(key, default).await
             }
         }
     }
 
 
+    /// see `Storage::compact`
     #[inline]        
-    pub fn iter_tags<K, Doc>(&self) -> Result<Iter<String, DashSet<K>>, SessionResult>
+    pub async fn compact<K, Doc>(&self) -> Result<CompactReport, SessionResult>
     where
         Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
         K:  Serialize
@@ -354,112 +553,2050 @@ impl Database {
         match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                let res = datastore.iter_tags();
-                Ok(res)
+                datastore.compact().await
             }
         }
     }
 
-
-
-
-    /// Just for redisstore engine
-    #[inline]
-    pub fn set<K, Doc>(&self, key: K, value: Doc, expire: Option<Duration>) -> Result<(), SessionResult>
+    /// see `Storage::checkpoint`
+    #[inline]        
+    pub async fn checkpoint<K, Doc>(&self) -> Result<CheckpointReport, SessionResult>
     where
-        Doc: Clone + Send + Sync + 'static,
-        K:  Clone
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
             + PartialOrd
             + Ord
             + PartialEq
             + Eq
             + Hash
+            + Clone
             + Send
+            + Sync
             + 'static
     {
-        match self.datastores.get::<RedisStorage<K, Doc>>() {
+        match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                datastore.set(key, value, expire);
-                Ok(())
+                datastore.checkpoint().await
             }
         }
     }
 
-
-    /// Just for redisstore engine
-    #[inline]
-    pub fn get<K, Doc>(&self, key: &K) -> Result<Option<Arc<Doc>>, SessionResult>
+    /// see `Storage::export`
+    #[inline]        
+    pub async fn export<K, Doc, W: Write>(&self, writer: W, opts: &ExportOptions) -> Result<usize, SessionResult>
     where
-        Doc: Clone + Send + Sync + 'static,
-        K:  Clone
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
             + PartialOrd
             + Ord
             + PartialEq
             + Eq
             + Hash
+            + Clone
             + Send
+            + Sync
             + 'static
+            + KeyCodec
     {
-        match self.datastores.get::<RedisStorage<K, Doc>>() {
+        match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                Ok(datastore.get(key))
+                datastore.export(writer, opts).await
             }
         }
     }
 
-
-    /// Just for redisstore engine
-    #[inline]
-    pub fn del<K, Doc>(&self, key: &K) -> Result<(), SessionResult>
+    /// see `Storage::import`
+    #[inline]        
+    pub async fn import<K, Doc, R: Read>(&self, reader: R, format: ExportFormat, policy: ConflictPolicy) -> Result<ImportReport, SessionResult>
     where
-        Doc: Clone + Send + Sync + 'static,
-        K:  Clone
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
             + PartialOrd
             + Ord
             + PartialEq
             + Eq
             + Hash
+            + Clone
             + Send
+            + Sync
             + 'static
     {
-        match self.datastores.get::<RedisStorage<K, Doc>>() {
+        match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                Ok(datastore.del(key))
+                datastore.import(reader, format, policy).await
             }
         }
     }
 
-
-
-    /// Just for redisstore engine
-    #[inline]
-    pub fn set_nx<K, Doc>(&self, key: K, value: Doc, expire: Option<Duration>) -> Result<bool, SessionResult>
+    /// see `Storage::create_view`
+    #[inline]        
+    pub async fn create_view<K, Doc>(&self, name: &str, view: ViewFn<K, Doc>) -> Result<usize, SessionResult>
     where
-        Doc: Clone + Send + Sync + 'static,
-        K:  Clone
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
             + PartialOrd
             + Ord
             + PartialEq
             + Eq
             + Hash
+            + Clone
             + Send
+            + Sync
             + 'static
     {
-        match self.datastores.get::<RedisStorage<K, Doc>>() {
+        match self.datastores.get::<Storage<K, Doc>>() {
             None => Err(SessionResult::DataStoreNotFound),
             Some(datastore) => {
-                Ok(datastore.set_nx(key, value, expire))
+                datastore.create_view(name, view).await
             }
         }
     }
 
-
-
-    
-
-
+    /// see `Storage::drop_view`
+    #[inline]        
+    pub async fn drop_view<K, Doc>(&self, name: &str) -> Result<(), SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.drop_view(name).await
+            }
+        }
+    }
+
+    /// see `Storage::rebuild_view`
+    #[inline]        
+    pub async fn rebuild_view<K, Doc>(&self, name: &str) -> Result<usize, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.rebuild_view(name).await
+            }
+        }
+    }
+
+    /// see `Storage::repair_stats`
+    #[inline]        
+    pub fn repair_stats<K, Doc>(&self) -> Result<RepairStats, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.repair_stats())
+            }
+        }
+    }
+
+    /// see `Storage::insert_with_ttl`
+    #[inline]        
+    pub async fn insert_with_ttl<K, Doc>(&self, key: K, doc: Doc, ttl: Duration) -> Result<(), SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.insert_with_ttl(key, doc, ttl).await
+            }
+        }
+    }
+
+    /// see `Storage::insert_batch`
+    #[inline]        
+    pub async fn insert_batch<K, Doc>(&self, batch: Vec<(K, Doc)>) -> Result<(), SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.insert_batch(batch).await
+            }
+        }
+    }
+
+    /// see `Storage::remove_batch`
+    #[inline]        
+    pub async fn remove_batch<K, Doc>(&self, keys: Vec<K>) -> Result<(), SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.remove_batch(keys).await
+            }
+        }
+    }
+
+    /// see `Storage::transaction`
+    #[inline]        
+    pub async fn transaction<K, Doc>(&self, queries: Vec<RQuery<K, Doc>>) -> Result<(), SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.transaction(queries).await
+            }
+        }
+    }
+
+    /// see `Storage::retain`
+    #[inline]        
+    pub async fn retain<K, Doc>(&self, f: impl Fn(&K, &Doc) -> bool) -> Result<usize, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.retain(f).await
+            }
+        }
+    }
+
+    /// Remove after checking references declared by `Schema::with_reference`,
+    /// return `ReferencedBy` if a Restrict dependent exist, remove Cascade dependents
+    /// and what depend on them.
+    ///
+    /// dependents and key are removed by one `cross_transaction`, stores of dependents
+    /// held with writes paused meanwhile, and plan made again once held: if a dependent
+    /// changed since planned, it is planned again. `UnImplement` if a store coalesce writes
+    #[inline]        
+    pub async fn remove_checked<K, Doc>(&self, key: K) -> Result<(), SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                let refs = match self.datastores.get::<References<K, Doc>>() {
+                    Some(refs) => refs,
+                    None => return datastore.remove(key).await,
+                };
+
+                loop {
+                    let mut plan = Plan::new();
+                    refs.plan(&self.datastores, &key, &mut plan)?;
+
+                    let mut tx = std::mem::replace(&mut plan.tx, Transaction::new());
+                    refs.pause(&self.datastores, &mut tx);
+                    tx.remove::<K, Doc>(key.clone());
+
+                    let unchanged = || {
+                        let mut again = Plan::new();
+                        refs.plan(&self.datastores, &key, &mut again)?;
+                        Ok(again.same(&plan))
+                    };
+                    if tx.commit_checked(&self.datastores, unchanged).await? {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+
+    /// Derive store of `Storage<KDst, DocDst>` from store of `Storage<KSrc, DocSrc>`,
+    /// documents transform map to None are left out. both must be registered, source opened
+    /// with `Options::with_previous_values` and `Options::with_event_retention`,
+    /// without coalescing, reporter on.
+    ///
+    /// - backfill: destination emptied and every source document transformed into it,
+    ///   unless it can resume from a recorded sequence, see restart.
+    /// - changes: `Derivation::follow` apply source changes as they come. an insert over a held key,
+    ///   a remove or a quarantine first remove what previous document transformed into, unless
+    ///   new one transform into same key, which is updated. a clear clear destination,
+    ///   a `BulkRemove` (documents unknown) backfill again. transform must map distinct
+    ///   source keys to distinct destination keys.
+    /// - version: bump it when transform change, a version other than recorded one backfill again.
+    /// - restart: destination is persisted by its own WAL, beside sequence of last source change
+    ///   applied, recorded after backfill and by `finish` (or `follow` seeing source closed).
+    ///   next `derive` replay source changes after it, those written while derivation was down too,
+    ///   or backfill when source no longer retain them: evicted by retention bounds, or source
+    ///   reopened without `close`, which restart its sequence
+    pub async fn derive<KSrc, DocSrc, KDst, DocDst, F>(&self, version: u32, transform: F) -> Result<Derivation<'_, KSrc, DocSrc, KDst, DocDst, F>, SessionResult>
+    where
+        DocSrc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        KSrc:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        DocDst: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        KDst:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        F: Fn(&KSrc, &DocSrc) -> Option<(KDst, DocDst)>,
+    {
+        match (self.datastores.get::<Storage<KSrc, DocSrc>>(), self.datastores.get::<Storage<KDst, DocDst>>()) {
+            (Some(source), Some(destination)) => Derivation::start(source, destination, version, transform).await,
+            _ => Err(SessionResult::DataStoreNotFound),
+        }
+    }
+
+
+    
+    #[inline]        
+    pub fn gets<'a, K, Doc>(&self, list: Vec<&K>) -> Result<Vec<Ref<K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.gets(list);
+                Ok(res)
+            }
+        }
+    }
+
+
+    /// see `Storage::try_range`
+    #[inline]        
+    pub fn range<K, Doc>(&self, field_name: &str, from: impl Into<FieldValue>, to: impl Into<FieldValue>) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                datastore.try_range(field_name, from, to)
+            }
+        }
+    }
+
+
+
+    /// see `Storage::lookup`, returned `Ref` can deadlock, see `lookup_owned`.
+    /// this and the other `Ref` reads fail with `CompressedValue` on a store with value compression
+    #[inline]        
+    pub fn lookup<K, Doc>(&self, key: &K) -> Result<Option<Ref<K, Doc>>, SessionResult> 
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.lookup(key);
+                Ok(res)
+            }
+        }
+    }
+
+
+    /// see `Storage::lookup_ref`
+    #[inline]
+    pub fn lookup_ref<K, Doc>(&self, key: &K) -> Result<Option<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => datastore.lookup_ref(key)
+        }
+    }
+
+
+    #[inline]        
+    pub fn lookup_by_index<K, Doc>(&self, index_key: &str) -> Result<Option<Ref<K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.lookup_by_index(index_key);
+                Ok(res)
+            }
+        }
+    }
+
+
+
+    /// see `Storage::lookup_by_tag`, returned `Ref`s can deadlock, see `lookup_by_tag_owned`
+    #[inline]        
+    pub fn lookup_by_tag<K, Doc>(&self, tag: &str) -> Result<Vec<Ref<K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.lookup_by_tag(tag);
+                Ok(res)
+            }
+        }
+    }
+
+    /// see `Storage::lookup_by_index_prefix`
+    #[inline]        
+    pub fn lookup_by_index_prefix<K, Doc>(&self, prefix: &str) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.lookup_by_index_prefix(prefix);
+                Ok(res)
+            }
+        }
+    }
+
+    /// see `Storage::lookup_owned`, decompress documents of a store with value compression
+    #[inline]        
+    pub fn lookup_owned<K, Doc>(&self, key: &K) -> Result<Option<Doc>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                let res = datastore.lookup_owned(key);
+                Ok(res)
+            }
+        }
+    }
+
+    /// see `Storage::watch`
+    #[inline]        
+    pub fn watch<K, Doc>(&self, key: K) -> Result<watch::Receiver<Option<Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + Sync + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.watch(key))
+            }
+        }
+    }
+
+    /// see `Storage::lookup_pinned`
+    #[inline]        
+    pub fn lookup_pinned<K, Doc>(&self, key: &K) -> Result<Option<PinnedDoc<Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                let res = datastore.lookup_pinned(key);
+                Ok(res)
+            }
+        }
+    }
+
+    /// see `Storage::iter_keys_owned`
+    #[inline]        
+    pub fn iter_keys_owned<K, Doc>(&self) -> Result<std::vec::IntoIter<K>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                let res = datastore.iter_keys_owned();
+                Ok(res)
+            }
+        }
+    }
+
+    /// see `Storage::try_lookup`
+    #[inline]        
+    pub fn try_lookup<K, Doc>(&self, key: &K) -> Result<Option<Doc>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => datastore.try_lookup(key)
+        }
+    }
+
+    /// see `Storage::lookup_by_index_owned`
+    #[inline]        
+    pub fn lookup_by_index_owned<K, Doc>(&self, index_key: &str) -> Result<Option<(K, Doc)>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                let res = datastore.lookup_by_index_owned(index_key);
+                Ok(res)
+            }
+        }
+    }
+
+    /// see `Storage::range_owned`
+    #[inline]        
+    pub fn range_owned<K, Doc>(&self, field_name: &str, from: impl Into<FieldValue>, to: impl Into<FieldValue>) -> Result<Vec<(K, Doc)>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => datastore.range_owned(field_name, from, to)
+        }
+    }
+
+    /// see `Storage::search_owned`
+    #[inline]        
+    pub fn search_owned<K, Doc>(&self, text: impl AsRef<str>) -> Result<Vec<(K, Doc)>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                let res = datastore.search_owned(text);
+                Ok(res)
+            }
+        }
+    }
+
+    /// Send documents contain any word of text to sender while postings are walked,
+    /// see `Storage::search_to`. run it beside receiver, e.g. with `tokio::join!`
+    pub async fn search_async<K, Doc>(&self, text: impl AsRef<str>, sender: Sender<(K, Doc)>) -> Result<usize, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + Sync + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.search_to(text.as_ref(), &sender).await)
+            }
+        }
+    }
+
+    /// see `Storage::lookup_by_tag_owned`
+    #[inline]        
+    pub fn lookup_by_tag_owned<K, Doc>(&self, tag: &str) -> Result<Vec<(K, Doc)>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                let res = datastore.lookup_by_tag_owned(tag);
+                Ok(res)
+            }
+        }
+    }
+
+    /// see `Storage::fetch_view_owned`
+    #[inline]        
+    pub fn fetch_view_owned<K, Doc>(&self, view_name: &str) -> Result<Vec<(K, Doc)>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                let res = datastore.fetch_view_owned(view_name);
+                Ok(res)
+            }
+        }
+    }
+
+    /// see `Storage::iter_page`
+    #[inline]        
+    pub fn iter_page<K, Doc>(&self, cursor: Option<K>, page_size: usize) -> Result<KeyPage<'_, K, Doc>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.iter_page(cursor, page_size);
+                Ok(res)
+            }
+        }
+    }
+
+    /// see `Storage::lookup_by_tag_page`
+    #[inline]        
+    pub fn lookup_by_tag_page<K, Doc>(&self, tag: &str, offset: usize, limit: usize, order: &PageOrder) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.lookup_by_tag_page(tag, offset, limit, order);
+                Ok(res)
+            }
+        }
+    }
+
+    /// see `Storage::fetch_view_page`
+    #[inline]        
+    pub fn fetch_view_page<K, Doc>(&self, view_name: &str, offset: usize, limit: usize, order: &PageOrder) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.fetch_view_page(view_name, offset, limit, order);
+                Ok(res)
+            }
+        }
+    }
+
+    /// see `Storage::search_page`
+    #[inline]        
+    pub fn search_page<K, Doc>(&self, text: &str, offset: usize, limit: usize, order: &PageOrder) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.search_page(text, offset, limit, order);
+                Ok(res)
+            }
+        }
+    }
+
+    /// see `Storage::range_page`
+    #[inline]        
+    pub fn range_page<K, Doc>(
+        &self,
+        field_name: &str,
+        from: impl Into<FieldValue>,
+        to: impl Into<FieldValue>,
+        offset: usize,
+        limit: usize,
+        order: &PageOrder,
+    ) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                datastore.range_page(field_name, from, to, offset, limit, order)
+            }
+        }
+    }
+
+    /// see `Storage::iter_prefix`
+    #[inline]        
+    pub fn iter_prefix<K, Doc>(&self, prefix: &str) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+            + AsRef<str>
+            + Borrow<str>
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                Ok(datastore.iter_prefix(prefix))
+            }
+        }
+    }
+
+
+    /// see `Storage::lookup_by_tags_all`
+    #[inline]        
+    pub fn lookup_by_tags_all<K, Doc>(&self, tags: &[&str]) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.lookup_by_tags_all(tags);
+                Ok(res)
+            }
+        }
+    }
+
+
+    /// see `Storage::lookup_by_tags_any`
+    #[inline]        
+    pub fn lookup_by_tags_any<K, Doc>(&self, tags: &[&str]) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.lookup_by_tags_any(tags);
+                Ok(res)
+            }
+        }
+    }
+
+
+
+
+    /// see `Storage::fetch_view`, returned `Ref`s can deadlock, see `fetch_view_owned`
+    #[inline]        
+    pub fn fetch_view<K, Doc>(&self, view_name: &str) -> Result<Vec<Ref<K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.fetch_view(view_name);
+                Ok(res)
+            }
+        }
+    }
+
+
+    /// see `Storage::stats`
+    #[inline]        
+    pub fn stats<K, Doc>(&self) -> Result<StorageStats, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.stats())
+            }
+        }
+    }
+
+
+    /// see `Storage::metrics`
+    #[inline]        
+    pub fn metrics<K, Doc>(&self) -> Result<MetricsSnapshot, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.metrics())
+            }
+        }
+    }
+
+
+    /// see `Storage::top_accessed`
+    #[inline]        
+    pub fn top_accessed<K, Doc>(&self, n: usize) -> Result<AccessReport<K>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.top_accessed(n))
+            }
+        }
+    }
+
+
+    /// see `Storage::query_cache_stats`
+    #[inline]        
+    pub fn query_cache_stats<K, Doc>(&self) -> Result<QueryCacheStats, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.query_cache_stats())
+            }
+        }
+    }
+
+
+    /// see `Storage::access_count`
+    #[inline]        
+    pub fn access_count<K, Doc>(&self, key: &K) -> Result<u64, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.access_count(key))
+            }
+        }
+    }
+
+
+    /// see `Storage::len`
+    #[inline]        
+    pub fn len<K, Doc>(&self) -> Result<usize, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.len())
+            }
+        }
+    }
+
+
+    /// see `Storage::is_empty`
+    #[inline]        
+    pub fn is_empty<K, Doc>(&self) -> Result<bool, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.is_empty())
+            }
+        }
+    }
+
+
+    /// see `Storage::index_len`
+    #[inline]        
+    pub fn index_len<K, Doc>(&self) -> Result<usize, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.index_len())
+            }
+        }
+    }
+
+
+    /// see `Storage::tags_len`
+    #[inline]        
+    pub fn tags_len<K, Doc>(&self) -> Result<usize, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.tags_len())
+            }
+        }
+    }
+
+
+    /// see `Storage::view_len`
+    #[inline]        
+    pub fn view_len<K, Doc>(&self, view_name: &str) -> Result<usize, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.view_len(view_name))
+            }
+        }
+    }
+
+
+    #[inline]        
+    pub fn lookup_by_variant<K, Doc>(&self, variant: &str) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.lookup_by_variant(variant);
+                Ok(res)
+            }
+        }
+    }
+
+
+    #[inline]        
+    pub fn count_by_variant<K, Doc>(&self) -> Result<Vec<(&'static str, usize)>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                let res = datastore.count_by_variant();
+                Ok(res)
+            }
+        }
+    }
+
+
+
+    /// see `Storage::search`
+    #[inline]        
+    pub fn search<K, Doc>(&self, text: impl AsRef<str>) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                let res = datastore.search(text);
+                Ok(res)
+            }
+        }
+    }
+
+    /// see `Storage::search_string`
+    #[deprecated(since = "6.2.0", note = "use `search`, text is borrowed")]
+    #[inline]
+    pub fn search_string<K, Doc>(&self, text: String) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        self.search(text)
+    }
+
+    /// see `Storage::range_string`
+    #[deprecated(since = "6.2.0", note = "use `range`, bounds take `&str` and numbers")]
+    #[inline]
+    pub fn range_string<K, Doc>(&self, field_name: &str, from: String, to: String) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        self.range(field_name, from, to)
+    }
+
+
+    /// see `Storage::search_query`
+    #[inline]
+    pub fn search_query<K, Doc>(&self, query: &SearchQuery) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                Ok(datastore.search_query(query))
+            }
+        }
+    }
+
+
+
+    #[inline]        
+    pub fn iter<K, Doc>(&self) -> Result<impl Iterator<Item = RefMulti<'_, K, Doc>> + '_, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.borrowable()?;
+                Ok(datastore.iter())
+            }
+        }
+    }
+
+
+    #[inline]        
+    pub fn iter_index<K, Doc>(&self) -> Result<Iter<String, K>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                let res = datastore.iter_index();
+                Ok(res)
+            }
+        }
+    }
+
+
+    #[inline]        
+    pub fn iter_tags<K, Doc>(&self) -> Result<Iter<String, DashSet<K>>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                let res = datastore.iter_tags();
+                Ok(res)
+            }
+        }
+    }
+
+
+
+
+    /// Just for redisstore engine,
+    /// resolve store once, handle avoid AnyMap lookup and turbofish per operation
+    pub fn cache_handle<K, Doc>(&self) -> Result<CacheHandle<K, Doc>, SessionResult>
+    where
+        Doc: Clone + Send + Sync + 'static,
+        K:  Clone
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Send
+            + 'static
+    {
+        match self.datastores.get::<RedisStorage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => Ok(CacheHandle::new(datastore.clone())),
+        }
+    }
+
+
+    /// Just for redisstore engine
+    #[inline]
+    pub fn set<K, Doc>(&self, key: K, value: Doc, expire: Option<Duration>) -> Result<(), SessionResult>
+    where
+        Doc: Clone + Send + Sync + 'static,
+        K:  Clone
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Send
+            + 'static
+    {
+        match self.datastores.get::<RedisStorage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.set(key, value, expire);
+                Ok(())
+            }
+        }
+    }
+
+
+    /// Just for redisstore engine
+    #[inline]
+    pub fn get<K, Doc>(&self, key: &K) -> Result<Option<Arc<Doc>>, SessionResult>
+    where
+        Doc: Clone + Send + Sync + 'static,
+        K:  Clone
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Send
+            + 'static
+    {
+        match self.datastores.get::<RedisStorage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.get(key))
+            }
+        }
+    }
+
+
+    /// Just for redisstore engine
+    #[inline]
+    pub fn del<K, Doc>(&self, key: &K) -> Result<(), SessionResult>
+    where
+        Doc: Clone + Send + Sync + 'static,
+        K:  Clone
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Send
+            + 'static
+    {
+        match self.datastores.get::<RedisStorage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.del(key))
+            }
+        }
+    }
+
+
+
+    /// Just for redisstore engine
+    #[inline]
+    pub fn set_nx<K, Doc>(&self, key: K, value: Doc, expire: Option<Duration>) -> Result<bool, SessionResult>
+    where
+        Doc: Clone + Send + Sync + 'static,
+        K:  Clone
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Send
+            + 'static
+    {
+        match self.datastores.get::<RedisStorage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.set_nx(key, value, expire))
+            }
+        }
+    }
+
+
+    /// Just for redisstore engine, see `RedisStorage::mget`
+    #[inline]
+    pub fn mget<K, Doc>(&self, keys: &[K]) -> Result<Vec<Option<Arc<Doc>>>, SessionResult>
+    where
+        Doc: Clone + Send + Sync + 'static,
+        K:  Clone
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Send
+            + 'static
+    {
+        match self.datastores.get::<RedisStorage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => Ok(datastore.mget(keys)),
+        }
+    }
+
+
+    /// Just for redisstore engine, see `RedisStorage::mset`
+    #[inline]
+    pub fn mset<K, Doc>(&self, entries: Vec<(K, Doc, Option<Duration>)>) -> Result<(), SessionResult>
+    where
+        Doc: Clone + Send + Sync + 'static,
+        K:  Clone
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Send
+            + 'static
+    {
+        match self.datastores.get::<RedisStorage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.mset(entries);
+                Ok(())
+            }
+        }
+    }
+
+
+    /// Just for redisstore engine, see `RedisStorage::keys`
+    #[inline]
+    pub fn keys<K, Doc>(&self) -> Result<Vec<K>, SessionResult>
+    where
+        Doc: Clone + Send + Sync + 'static,
+        K:  Clone
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Send
+            + 'static
+    {
+        match self.datastores.get::<RedisStorage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => Ok(datastore.keys()),
+        }
+    }
+
+
+    /// Just for redisstore engine, see `RedisStorage::scan`
+    #[inline]
+    pub fn scan<K, Doc>(&self, cursor: Option<K>, count: usize) -> Result<(Vec<K>, Option<K>), SessionResult>
+    where
+        Doc: Clone + Send + Sync + 'static,
+        K:  Clone
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Send
+            + 'static
+    {
+        match self.datastores.get::<RedisStorage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => Ok(datastore.scan(cursor, count)),
+        }
+    }
+
+
+    /// Just for redisstore engine, see `RedisStorage::ttl`
+    #[inline]
+    pub fn ttl<K, Doc>(&self, key: &K) -> Result<Option<Duration>, SessionResult>
+    where
+        Doc: Clone + Send + Sync + 'static,
+        K:  Clone
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Send
+            + 'static
+    {
+        match self.datastores.get::<RedisStorage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => datastore.ttl(key),
+        }
+    }
+
+
+    /// Just for redisstore engine, see `RedisStorage::expire`
+    #[inline]
+    pub fn expire<K, Doc>(&self, key: &K, expire: Duration) -> Result<bool, SessionResult>
+    where
+        Doc: Clone + Send + Sync + 'static,
+        K:  Clone
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Send
+            + 'static
+    {
+        match self.datastores.get::<RedisStorage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => Ok(datastore.expire(key, expire)),
+        }
+    }
+
+
+    /// Just for redisstore engine, see `RedisStorage::incr`
+    #[inline]
+    pub fn incr<K, Doc>(&self, key: K, delta: i64, expire: Option<Duration>) -> Result<Doc, SessionResult>
+    where
+        Doc: Numeric + Clone + Send + Sync + 'static,
+        K:  Clone
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Send
+            + 'static
+    {
+        match self.datastores.get::<RedisStorage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => datastore.incr(key, delta, expire),
+        }
+    }
+
+
+    /// Just for redisstore engine, see `RedisStorage::decr`
+    #[inline]
+    pub fn decr<K, Doc>(&self, key: K, delta: i64, expire: Option<Duration>) -> Result<Doc, SessionResult>
+    where
+        Doc: Numeric + Clone + Send + Sync + 'static,
+        K:  Clone
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Send
+            + 'static
+    {
+        match self.datastores.get::<RedisStorage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => datastore.decr(key, delta, expire),
+        }
+    }
+
+
+
+    
+
+
+
+
+    /// Just for bytesstore engine
+    #[inline]
+    pub async fn insert_bytes<K>(&self, key: K, doc: Bytes) -> Result<(), SessionResult>
+    where
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<BytesStorage<K>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.insert(key, doc).await
+            }
+        }
+    }
+
+
+    /// Just for bytesstore engine
+    #[inline]
+    pub async fn remove_bytes<K>(&self, key: K) -> Result<(), SessionResult>
+    where
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<BytesStorage<K>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                datastore.remove(key).await
+            }
+        }
+    }
+
+
+    /// Just for bytesstore engine
+    #[inline]
+    pub fn lookup_bytes<K>(&self, key: &K) -> Result<Option<Bytes>, SessionResult>
+    where
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<BytesStorage<K>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.lookup(key))
+            }
+        }
+    }
+
+
+    /// Just for bytesstore engine, see `BytesStorage::lookup_pinned`
+    #[inline]
+    pub fn lookup_bytes_pinned<K>(&self, key: &K) -> Result<Option<PinnedDoc<Bytes>>, SessionResult>
+    where
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<BytesStorage<K>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.lookup_pinned(key))
+            }
+        }
+    }
+
+
+    /// Just for bytesstore engine, see `BytesStorage::metrics`
+    #[inline]
+    pub fn metrics_bytes<K>(&self) -> Result<MetricsSnapshot, SessionResult>
+    where
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match self.datastores.get::<BytesStorage<K>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                Ok(datastore.metrics())
+            }
+        }
+    }
 
 }
\ No newline at end of file
@@ -0,0 +1,333 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{fs, hash::Hash, io::ErrorKind};
+use tokio::sync::mpsc::{self, Receiver};
+
+use crate::{document::Document, Event, RQuery, Storage};
+
+use super::{fingerprint::Fingerprint, SessionResult, StatusResult};
+
+
+
+// source documents read, and destination documents written, per backfill batch
+const BACKFILL_BATCH: usize = 1024;
+
+// source events waiting for transform
+const BUFFER: usize = 1024;
+
+
+/// progress of a derived store, kept in `<path>/<name>.derive` beside its WAL
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct DeriveState {
+    // fingerprint of source store
+    source: String,
+
+    version: u32,
+
+    // run of source event sequence, see `EventSeq`
+    run: u64,
+
+    // sequence of last source change applied to destination, None while backfill run
+    seq: Option<u64>,
+}
+
+impl DeriveState {
+    fn read(file: &str) -> Result<Option<DeriveState>, SessionResult> {
+        match fs::read(file) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes).ok()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(SessionResult::Err(StatusResult::IoError(e))),
+        }
+    }
+
+    fn write(&self, file: &str) -> Result<(), SessionResult> {
+        fs::write(file, serde_json::to_vec(self).unwrap()).map_err(|e| SessionResult::Err(StatusResult::IoError(e)))
+    }
+}
+
+
+/// Destination store kept up to date from a source store, built by `Database::derive`.
+///
+/// source changes queue until `follow` or `finish` apply them,
+/// destination must not be written by anything else
+pub struct Derivation<'a, KSrc, DocSrc: Document, KDst, DocDst: Document, F> {
+    source: &'a Storage<KSrc, DocSrc>,
+    destination: &'a Storage<KDst, DocDst>,
+    transform: F,
+    receiver: Receiver<(u64, Event<KSrc, DocSrc>)>,
+    state: DeriveState,
+    file: String,
+
+    // documents written by backfill, None when it was skipped
+    backfilled: Option<usize>,
+
+    // an event was being applied when follow was dropped
+    torn: bool,
+
+    // a change gave no document to transform, destination is derived again
+    stale: bool,
+}
+
+impl<'a, KSrc, DocSrc, KDst, DocDst, F> Derivation<'a, KSrc, DocSrc, KDst, DocDst, F>
+where
+    DocSrc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    KSrc: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    DocDst: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    KDst: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    F: Fn(&KSrc, &DocSrc) -> Option<(KDst, DocDst)>,
+{
+    pub(crate) async fn start(
+        source: &'a Storage<KSrc, DocSrc>,
+        destination: &'a Storage<KDst, DocDst>,
+        version: u32,
+        transform: F,
+    ) -> Result<Self, SessionResult> {
+        let capabilities = source.capabilities();
+        let position = source.event_position();
+        let (run, current) = match position {
+            Some(position) if capabilities.previous_values && capabilities.coalesce_ms.is_none() => position,
+            _ => {
+                return Err(SessionResult::Err(StatusResult::Err(
+                    "derive: source must be opened with Options::with_previous_values and with_event_retention, without coalescing".to_owned(),
+                )))
+            }
+        };
+
+        let file = format!("{}.derive", destination.wal_dir());
+        let state = DeriveState {
+            source: Fingerprint::of::<KSrc, DocSrc>().to_string(),
+            version,
+            run,
+            seq: None,
+        };
+
+        // changes applied since recorded sequence are replayed, if source still retain them
+        let resume_at = DeriveState::read(&file)?
+            .filter(|stored| DeriveState { seq: stored.seq, ..state.clone() } == *stored)
+            .and_then(|stored| stored.seq);
+
+        let (sender, receiver) = mpsc::channel(BUFFER);
+        let resumed = match resume_at {
+            Some(seq) => match source.subscribe_from_seq(sender.clone(), seq).await {
+                Ok(_) => Some(seq),
+                Err(SessionResult::GapTooLarge { .. }) => None,
+                Err(e) => return Err(e),
+            },
+            None => None,
+        };
+
+        // before backfill, so changes written while it run are queued
+        if resumed.is_none() {
+            source.subscribe_from_seq(sender, current).await?;
+        }
+
+        let mut derivation = Derivation {
+            source,
+            destination,
+            transform,
+            receiver,
+            state,
+            file,
+            backfilled: None,
+            torn: false,
+            stale: false,
+        };
+
+        match resumed {
+            Some(seq) => {
+                derivation.state.seq = Some(seq);
+                derivation.state.write(&derivation.file)?;
+            }
+            None => {
+                derivation.state.seq = Some(current);
+                derivation.backfill().await?;
+            }
+        }
+
+        Ok(derivation)
+    }
+
+    /// documents written to destination by backfill of `Database::derive`,
+    /// None when destination was resumed
+    pub fn backfilled(&self) -> Option<usize> {
+        self.backfilled
+    }
+
+    /// Apply source changes as they come, return once source is closed,
+    /// after applying every change of it and recording its sequence.
+    ///
+    /// may be dropped, e.g. in `tokio::select!`, then call `finish` before closing stores
+    pub async fn follow(&mut self) -> Result<(), SessionResult> {
+        while let Some((seq, event)) = self.receiver.recv().await {
+            self.torn = true;
+            self.apply(seq, event).await?;
+            self.torn = false;
+        }
+
+        self.record()
+    }
+
+    /// Apply every source change dispatched before call and record sequence of last one,
+    /// so next `Database::derive` with same version replay changes after it without backfill
+    pub async fn finish(mut self) -> Result<(), SessionResult> {
+        {
+            let source = self.source;
+            let flushed = source.flush_events();
+            tokio::pin!(flushed);
+
+            // router block on a full channel, so drained while waiting
+            loop {
+                tokio::select! {
+                    res = &mut flushed => {
+                        res?;
+                        break;
+                    }
+                    Some((seq, event)) = self.receiver.recv() => {
+                        self.apply(seq, event).await?;
+                    }
+                }
+            }
+        }
+
+        while let Ok((seq, event)) = self.receiver.try_recv() {
+            self.apply(seq, event).await?;
+        }
+
+        self.record()
+    }
+
+    fn record(&mut self) -> Result<(), SessionResult> {
+        // a change dropped half applied, leave sequence recorded before it to replay it
+        if self.torn {
+            return Ok(());
+        }
+
+        self.state.write(&self.file)
+    }
+
+    // empty destination, then transform every source document into it,
+    // again while a change read meanwhile left it unknown
+    async fn backfill(&mut self) -> Result<(), SessionResult> {
+        // a crash before backfill end make next start backfill
+        let seq = self.state.seq.take();
+        self.state.write(&self.file)?;
+        self.state.seq = seq;
+
+        loop {
+            self.stale = false;
+            self.destination.retain(|_, _| false).await?;
+
+            let mut written = 0;
+            for keys in self.source.keys().chunks(BACKFILL_BATCH) {
+                let batch: Vec<(KDst, DocDst)> = keys
+                    .iter()
+                    .filter_map(|key| self.source.visible_owned(key).and_then(|doc| (self.transform)(key, &doc)))
+                    .collect();
+
+                written += batch.len();
+
+                // keys a change already wrote to are updated
+                let (held, batch): (Vec<_>, Vec<_>) = batch.into_iter().partition(|(key, _)| self.destination.contains_key(key));
+                self.destination.insert_batch(batch).await?;
+                for (key, doc) in held {
+                    self.put(key, doc).await?;
+                }
+
+                // changes of documents already read are applied again, to same end state
+                while let Ok((seq, event)) = self.receiver.try_recv() {
+                    self.apply_one(seq, event).await?;
+                }
+            }
+
+            if !self.stale {
+                self.backfilled = Some(written);
+                return self.state.write(&self.file);
+            }
+        }
+    }
+
+    // change of source, then backfill again if it left destination unknown
+    async fn apply(&mut self, seq: u64, event: Event<KSrc, DocSrc>) -> Result<(), SessionResult> {
+        self.apply_one(seq, event).await?;
+
+        if self.stale {
+            self.backfill().await?;
+        }
+        Ok(())
+    }
+
+    // remove what previous version transformed into, unless new version replace same key
+    async fn apply_one(&mut self, seq: u64, event: Event<KSrc, DocSrc>) -> Result<(), SessionResult> {
+        let change = match event {
+            Event::Query(RQuery::Insert(key, doc) | RQuery::Update(key, doc) | RQuery::Patch(key, _, doc)) => Some((key, None, Some(doc))),
+            Event::Replaced(key, old, doc) => Some((key, Some(old), Some(doc))),
+            Event::Removed(key, old) => Some((key, Some(old), None)),
+
+            // hidden by read repair, its document is still held aside
+            Event::Quarantined(key) => self.source.lookup_quarantined(&key).map(|old| (key, Some(old), None)),
+
+            // documents of keys are gone, what they transformed into is unknown.
+            // with previous values bulk removals come as Removed
+            Event::BulkRemove(_) => {
+                self.stale = true;
+                None
+            }
+
+            // destination hold only what source produced
+            Event::Query(RQuery::Clear) => {
+                self.destination.clear().await?;
+                None
+            }
+
+            // with previous values a Query(Remove) removed nothing, others aren't document changes
+            _ => None,
+        };
+
+        if let Some((key, old, new)) = change {
+            let old = old.and_then(|doc| (self.transform)(&key, &doc)).map(|(key, _)| key);
+            let new = new.and_then(|doc| (self.transform)(&key, &doc));
+
+            if let Some(old) = old {
+                if new.as_ref().is_none_or(|(key, _)| *key != old) {
+                    self.destination.remove(old).await?;
+                }
+            }
+
+            if let Some((key, doc)) = new {
+                self.put(key, doc).await?;
+            }
+        }
+
+        self.state.seq = Some(seq);
+        Ok(())
+    }
+
+    // insert, or update held key so its index keys aren't seen as duplicate
+    async fn put(&self, key: KDst, doc: DocDst) -> Result<(), SessionResult> {
+        let mut doc = Some(doc);
+        if self.destination.update(&key, |held| *held = doc.take().unwrap()).await? {
+            return Ok(());
+        }
+
+        self.destination.insert(key, doc.unwrap()).await
+    }
+}
@@ -0,0 +1,78 @@
+use std::{sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+
+use super::{admin::AdminLog, wal::disk_log::Session};
+
+// period of free space check
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+
+
+/// free bytes of filesystem holding path for unprivileged writers,
+/// None if it can't be read or on platforms other than linux
+#[cfg(target_os = "linux")]
+pub(crate) fn available(path: &str) -> Option<u64> {
+    let path = std::ffi::CString::new(path).ok()?;
+
+    // SAFETY: path is nul terminated, stat is plain data written by statvfs
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn available(_path: &str) -> Option<u64> {
+    None
+}
+
+
+
+/// Check of free space under WAL of a store, `Options::with_disk_space_watch`.
+///
+/// `disk_space_low` noticed once each time free space drop below threshold.
+/// below floor WAL session is turned read-only and `read_only` noticed,
+/// store stay read-only until `Storage::set_read_only(false)`.
+/// stopped when dropped
+pub(crate) struct DiskWatch {
+    task: JoinHandle<()>,
+}
+
+impl DiskWatch {
+    pub fn spawn(dir: String, threshold: u64, floor: Option<u64>, admin: Arc<AdminLog>, wal: Session) -> Self {
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CHECK_INTERVAL);
+            let mut low = false;
+
+            loop {
+                interval.tick().await;
+
+                let available = match available(&dir) {
+                    Some(available) => available,
+                    None => continue,
+                };
+
+                if available < threshold && !low {
+                    admin.notice("disk_space_low", format!("available_bytes={} threshold={}", available, threshold), available);
+                }
+                low = available < threshold;
+
+                if let Some(floor) = floor {
+                    if available < floor && wal.set_read_only(true) {
+                        admin.notice("read_only", format!("available_bytes={} floor={}", available, floor), available);
+                    }
+                }
+            }
+        });
+
+        DiskWatch { task }
+    }
+}
+
+impl Drop for DiskWatch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -1,5 +1,23 @@
+use std::{cmp::Ordering, fmt};
 
-pub trait Document: Indexer + Tags + Range + MaterializedView + FullText {}
+
+
+pub trait Document: Indexer + Tags + Range + MaterializedView + FullText {
+    /// variant names of an enum document, recorded in schema fingerprint
+    /// so renaming a variant fail open instead of emptying `lookup_by_variant`
+    const VARIANTS: &'static [&'static str] = &[];
+
+    /// variant of document, one of `VARIANTS`, grouped by store for `Storage::lookup_by_variant`
+    fn variant(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// apply a `DocPatch::Custom` of `Storage::patch`, in encoding caller gave it.
+    /// Err reject patch with document unchanged, as every patch is by default
+    fn apply_patch(&mut self, _patch: &[u8]) -> Result<(), String> {
+        Err("document doesn't apply custom patches".to_owned())
+    }
+}
 
 pub trait Indexer {
     fn extract(&self) -> Vec<String>;
@@ -10,7 +28,16 @@ pub trait Tags {
 }
 
 pub trait Range {
-    fn get_fields(&self) -> Vec<RangeField>;
+    /// string fields, compared lexicographically
+    fn get_fields(&self) -> Vec<RangeField> {
+        vec![]
+    }
+
+    /// fields indexed by range index, default is `get_fields` as `FieldValue::Str`,
+    /// override to index integer or float fields compared as numbers
+    fn get_typed_fields(&self) -> Vec<TypedField> {
+        self.get_fields().into_iter().map(TypedField::from).collect()
+    }
 }
 
 pub trait MaterializedView {
@@ -26,4 +53,120 @@ pub trait FullText {
 pub struct RangeField {
     pub name: String,
     pub value: String
-}
\ No newline at end of file
+}
+
+
+/// range field with typed value, see `Range::get_typed_fields`
+pub struct TypedField {
+    pub name: String,
+    pub value: FieldValue
+}
+
+impl From<RangeField> for TypedField {
+    fn from(rf: RangeField) -> Self {
+        TypedField { name: rf.name, value: FieldValue::Str(rf.value) }
+    }
+}
+
+
+/// Value of a range field.
+///
+/// values of one field compare by their type, a range query must use type of field,
+/// querying a field with another type, or a field holding several types, is an error
+#[derive(Clone, Debug)]
+pub enum FieldValue {
+    Str(String),
+    Int(i64),
+    Float(f64),
+}
+
+impl FieldValue {
+    /// name of type, used in `SessionResult::FieldTypeMismatch`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FieldValue::Str(_) => "Str",
+            FieldValue::Int(_) => "Int",
+            FieldValue::Float(_) => "Float",
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            FieldValue::Str(_) => 0,
+            FieldValue::Int(_) => 1,
+            FieldValue::Float(_) => 2,
+        }
+    }
+}
+
+// values of a type together, ordered by type: Str, Int, Float
+impl Ord for FieldValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (FieldValue::Str(a), FieldValue::Str(b)) => a.cmp(b),
+            (FieldValue::Int(a), FieldValue::Int(b)) => a.cmp(b),
+            (FieldValue::Float(a), FieldValue::Float(b)) => a.total_cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for FieldValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for FieldValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FieldValue {}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Str(value) => f.write_str(value),
+            FieldValue::Int(value) => write!(f, "{}", value),
+            FieldValue::Float(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        FieldValue::Str(value)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        FieldValue::Str(value.to_owned())
+    }
+}
+
+impl From<i64> for FieldValue {
+    fn from(value: i64) -> Self {
+        FieldValue::Int(value)
+    }
+}
+
+impl From<i32> for FieldValue {
+    fn from(value: i32) -> Self {
+        FieldValue::Int(value as i64)
+    }
+}
+
+impl From<u32> for FieldValue {
+    fn from(value: u32) -> Self {
+        FieldValue::Int(value as i64)
+    }
+}
+
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        FieldValue::Float(value)
+    }
+}
@@ -0,0 +1,335 @@
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::Hash,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    sync::{Notify, RwLock},
+    task::JoinHandle,
+    time,
+};
+
+use super::{
+    clock::Clock,
+    filter::{Change, Filtering},
+    index::tags::TagIndex,
+    router,
+    storage::{bucket, Event, RQuery},
+    wal::{codec::{Codec, WalCodec}, disk_log::Session},
+};
+use crate::document::Document;
+
+// longest sleep of expiry service, so steps of wall clock are noticed
+const MAX_TICK: Duration = Duration::from_secs(1);
+
+// no deadline armed
+const NONE: u64 = u64::MAX;
+
+
+
+/// wall time in millis since UNIX_EPOCH, as persisted in WAL
+#[inline]
+pub(crate) fn unix_ms(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+
+struct Deadlines<K, Doc> {
+    // key -> deadline and document, for events of expiry service
+    by_key: HashMap<K, (u64, Doc)>,
+
+    // (deadline, key) of by_key, earliest first
+    queue: BTreeSet<(u64, K)>,
+
+    // key -> deadline, removal logged and dispatched by expiry service,
+    // document left in memory until Storage::expire_due
+    logged: HashMap<K, u64>,
+}
+
+
+/// Deadlines of documents inserted by `Storage::insert_with_ttl`,
+/// a plain insert or remove of key disarm it
+pub(crate) struct Expiry<K, Doc> {
+    deadlines: Mutex<Deadlines<K, Doc>>,
+
+    // earliest deadline, logged ones included, checked without lock on every write
+    next: AtomicU64,
+
+    // an earlier deadline armed or service closed
+    wake: Notify,
+    closed: AtomicBool,
+}
+
+impl<K, Doc> Expiry<K, Doc>
+where
+    K: Eq + Hash + Ord + Clone,
+    Doc: Clone,
+{
+    pub fn new() -> Self {
+        Expiry {
+            deadlines: Mutex::new(Deadlines { by_key: HashMap::new(), queue: BTreeSet::new(), logged: HashMap::new() }),
+            next: AtomicU64::new(NONE),
+            wake: Notify::new(),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn arm(&self, key: K, at: u64, doc: Doc) {
+        let mut deadlines = self.deadlines.lock();
+        deadlines.logged.remove(&key);
+        if let Some((old, _)) = deadlines.by_key.insert(key.clone(), (at, doc)) {
+            deadlines.queue.remove(&(old, key.clone()));
+        }
+        deadlines.queue.insert((at, key));
+        self.publish(&deadlines);
+        self.wake.notify_one();
+    }
+
+    #[inline]
+    pub fn disarm(&self, key: &K) {
+        if self.next.load(Ordering::Relaxed) == NONE {
+            return;
+        }
+
+        let mut deadlines = self.deadlines.lock();
+        let armed = match deadlines.by_key.remove(key) {
+            Some((at, _)) => deadlines.queue.remove(&(at, key.clone())),
+            None => false,
+        };
+        if armed || deadlines.logged.remove(key).is_some() {
+            self.publish(&deadlines);
+        }
+    }
+
+    /// true if some deadline passed at `now`
+    #[inline]
+    pub fn due(&self, now: u64) -> bool {
+        self.next.load(Ordering::Relaxed) <= now
+    }
+
+    /// earliest deadline
+    pub fn next(&self) -> Option<u64> {
+        match self.next.load(Ordering::Relaxed) {
+            NONE => None,
+            at => Some(at),
+        }
+    }
+
+    pub fn deadline(&self, key: &K) -> Option<u64> {
+        let deadlines = self.deadlines.lock();
+        match deadlines.by_key.get(key) {
+            Some((at, _)) => Some(*at),
+            None => deadlines.logged.get(key).copied(),
+        }
+    }
+
+    /// true if deadline of key passed, `now` read only when some deadline is armed
+    #[inline]
+    pub fn expired(&self, key: &K, now: impl FnOnce() -> u64) -> bool {
+        let next = self.next.load(Ordering::Relaxed);
+        if next == NONE {
+            return false;
+        }
+
+        let now = now();
+        next <= now && self.deadline(key).is_some_and(|at| at <= now)
+    }
+
+    /// keys whose deadline passed at `now`, not removed yet
+    pub fn due_count(&self, now: u64) -> usize {
+        if !self.due(now) {
+            return 0;
+        }
+        let deadlines = self.deadlines.lock();
+        deadlines.queue.iter().take_while(|(at, _)| *at <= now).count() + deadlines.logged.len()
+    }
+
+    /// keys whose deadline passed at `now` with their deadline, left armed:
+    /// a write of key may disarm it before caller hold its lock
+    pub fn due_keys(&self, now: u64) -> Vec<(K, u64)> {
+        if !self.due(now) {
+            return vec![];
+        }
+        let deadlines = self.deadlines.lock();
+        deadlines
+            .logged
+            .iter()
+            .map(|(key, at)| (key.clone(), *at))
+            .chain(deadlines.queue.iter().take_while(|(at, _)| *at <= now).map(|(at, key)| (key.clone(), *at)))
+            .collect()
+    }
+
+    /// true if removal of key at deadline was logged by expiry service
+    pub fn is_logged(&self, key: &K, at: u64) -> bool {
+        self.deadlines.lock().logged.get(key) == Some(&at)
+    }
+
+    /// every deadline armed
+    pub fn armed(&self) -> Vec<(K, u64)> {
+        let deadlines = self.deadlines.lock();
+        deadlines
+            .by_key
+            .iter()
+            .map(|(key, (at, _))| (key.clone(), *at))
+            .chain(deadlines.logged.iter().map(|(key, at)| (key.clone(), *at)))
+            .collect()
+    }
+
+    // document of key still armed at deadline and not logged
+    fn pending(&self, key: &K, at: u64) -> Option<Doc> {
+        match self.deadlines.lock().by_key.get(key) {
+            Some((armed, doc)) if *armed == at => Some(doc.clone()),
+            _ => None,
+        }
+    }
+
+    fn set_logged(&self, key: K, at: u64) {
+        let mut deadlines = self.deadlines.lock();
+        deadlines.by_key.remove(&key);
+        deadlines.queue.remove(&(at, key.clone()));
+        deadlines.logged.insert(key, at);
+        self.publish(&deadlines);
+    }
+
+    // not logged keys whose deadline passed at `now`, and earliest deadline after them
+    fn armed_due(&self, now: u64) -> (Vec<(K, u64)>, Option<u64>) {
+        let deadlines = self.deadlines.lock();
+        let due = deadlines.queue.iter().take_while(|(at, _)| *at <= now).map(|(at, key)| (key.clone(), *at)).collect();
+        let next = deadlines.queue.iter().map(|(at, _)| *at).find(|at| *at > now);
+        (due, next)
+    }
+
+    fn publish(&self, deadlines: &Deadlines<K, Doc>) {
+        let armed = deadlines.queue.first().map(|(at, _)| *at).unwrap_or(NONE);
+        let logged = deadlines.logged.values().min().copied().unwrap_or(NONE);
+        self.next.store(armed.min(logged), Ordering::Relaxed);
+    }
+}
+
+
+
+/// what expiry service share with its store, to remove a key as `Storage::remove` log and dispatch it
+pub(crate) struct Store<K, Doc> {
+    pub update_locks: Arc<Vec<tokio::sync::Mutex<()>>>,
+    pub rebuild_gate: Arc<RwLock<()>>,
+    pub wal: Session,
+    pub durable: bool,
+    pub codec: WalCodec,
+    pub reporter: Option<router::Session<Event<K, Doc>>>,
+    pub lanes: usize,
+    pub previous_values: bool,
+    pub filtering: Arc<Filtering<K>>,
+    pub tag_index: Arc<TagIndex<K>>,
+    pub clock: Arc<dyn Clock>,
+}
+
+
+/// Task removing documents as their deadline come, spawned by `Storage::open`.
+///
+/// it can't touch memory of store, so it log `RQuery::Remove` and dispatch events
+/// under lock of key, and leave document hidden by its deadline until `Storage::expire_due`
+/// drop it from memory on next write. stopped by close or when dropped
+pub(crate) struct ExpiryService<K, Doc> {
+    expiry: Arc<Expiry<K, Doc>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl<K, Doc> ExpiryService<K, Doc>
+where
+    K: Serialize + DeserializeOwned + Eq + Hash + Ord + Clone + Send + Sync + 'static,
+    Doc: Serialize + Clone + Send + 'static + Document,
+{
+    pub fn spawn(expiry: Arc<Expiry<K, Doc>>, store: Store<K, Doc>) -> Self {
+        let task = tokio::spawn(run_service(expiry.clone(), store));
+        ExpiryService { expiry, task: Some(task) }
+    }
+
+    /// stop service, a removal being logged is finished first
+    pub async fn close(mut self) {
+        self.expiry.closed.store(true, Ordering::SeqCst);
+        self.expiry.wake.notify_one();
+
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl<K, Doc> Drop for ExpiryService<K, Doc> {
+    fn drop(&mut self) {
+        self.expiry.closed.store(true, Ordering::SeqCst);
+        self.expiry.wake.notify_one();
+    }
+}
+
+
+async fn run_service<K, Doc>(expiry: Arc<Expiry<K, Doc>>, store: Store<K, Doc>)
+where
+    K: Serialize + DeserializeOwned + Eq + Hash + Ord + Clone + Send + Sync + 'static,
+    Doc: Serialize + Clone + Send + 'static + Document,
+{
+    loop {
+        if expiry.closed.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let now = unix_ms(store.clock.now());
+        let (due, next) = expiry.armed_due(now);
+
+        for (key, at) in due {
+            if expiry.closed.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let _update = store.update_locks[bucket(&key, store.update_locks.len())].lock().await;
+            let _gate = store.rebuild_gate.read().await;
+
+            // written since, or removed by expire_due
+            let Some(doc) = expiry.pending(&key, at) else { continue };
+
+            // left armed, tried again on next tick or by expire_due
+            if store.wal.is_read_only() {
+                continue;
+            }
+
+            let lane = bucket(&key, store.lanes);
+            if store.durable {
+                let record = store.codec.encode(&RQuery::<K, Doc>::Remove(key.clone()));
+                if let Err(e) = store.wal.log_keyed(lane, record).await {
+                    eprintln!("expiry: {}", e.to_string());
+                    continue;
+                }
+            }
+
+            expiry.set_logged(key.clone(), at);
+
+            if let Some(reporter) = &store.reporter {
+                // as Storage::remove then expire_due dispatch them, document is no longer readable
+                let removed_meta = store.filtering.meta(Change::Remove, &key, || store.tag_index.tags_of(&key, &doc));
+                let expired_meta = store.filtering.meta(Change::Expired, &key, || store.tag_index.tags_of(&key, &doc));
+                let removed = match store.previous_values {
+                    true => Event::Removed(key.clone(), doc),
+                    false => Event::Query(RQuery::Remove(key.clone())),
+                };
+                let _ = reporter.dispatch_meta(lane, removed, removed_meta).await;
+                let _ = reporter.dispatch_meta(lane, Event::Expired(key), expired_meta).await;
+            }
+        }
+
+        let wait = match next {
+            Some(at) => Duration::from_millis(at.saturating_sub(now)).min(MAX_TICK),
+            None => MAX_TICK,
+        };
+
+        tokio::select! {
+            _ = time::sleep(wait.max(Duration::from_millis(1))) => {}
+            _ = expiry.wake.notified() => {}
+        }
+    }
+}
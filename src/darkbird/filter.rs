@@ -0,0 +1,216 @@
+use std::{
+    ops::BitOr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+use super::{router::Filter, storage::{Event, RQuery}};
+
+
+
+/// predicate of `SubscribeFilter::Custom`
+pub type EventPredicate<K, Doc> = Arc<dyn Fn(&Event<K, Doc>) -> bool + Send + Sync>;
+
+
+/// event types sent to a subscriber registered by `Storage::subscribe_filtered`,
+/// evaluated by router before sending
+pub enum SubscribeFilter<K, Doc> {
+    All,
+
+    // Query(Insert), Query(Update) and Replaced
+    InsertsOnly,
+
+    // Query(Remove), Removed and BulkRemove
+    RemovesOnly,
+
+    Custom(EventPredicate<K, Doc>),
+}
+
+impl<K, Doc> SubscribeFilter<K, Doc> {
+    pub(crate) fn into_filter(self) -> Option<Filter<Event<K, Doc>>> {
+        match self {
+            SubscribeFilter::All => None,
+            SubscribeFilter::InsertsOnly => Some(Filter::Predicate(
+                "InsertsOnly",
+                Arc::new(|event: &Event<K, Doc>| matches!(event, Event::Query(RQuery::Insert(..) | RQuery::Update(..) | RQuery::Patch(..)) | Event::Replaced(..))),
+            )),
+            SubscribeFilter::RemovesOnly => Some(Filter::Predicate(
+                "RemovesOnly",
+                Arc::new(|event: &Event<K, Doc>| matches!(event, Event::Query(RQuery::Remove(_) | RQuery::Clear) | Event::Removed(..) | Event::BulkRemove(_))),
+            )),
+            SubscribeFilter::Custom(predicate) => Some(Filter::Predicate("Custom", predicate)),
+        }
+    }
+}
+
+
+/// Filter of a subscriber registered by `Storage::subscribe_matching`,
+/// evaluated by router before an event is sent, so events not matching never reach channel.
+///
+/// document changes (`Query`, `Removed`, `Replaced`, `Expired`) are filtered, other events
+/// (`Subscribed`, `Lagging`, `SubscriberDropped`, `Timer`, `BulkRemove`, `Quarantined`, `Compacting`)
+/// are always sent
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EventFilter {
+    // tags of inserted or removed document
+    Tags(TagExpr),
+
+    // KeyCodec encoding of key start with prefix
+    KeyPrefix(Vec<u8>),
+
+    Changes(ChangeMask),
+
+    // every filter match
+    All(Vec<EventFilter>),
+}
+
+impl EventFilter {
+    pub(crate) fn matches(&self, meta: &EventMeta) -> bool {
+        match self {
+            EventFilter::Tags(expr) => expr.matches(&meta.tags),
+            EventFilter::KeyPrefix(prefix) => meta.key.starts_with(prefix),
+            EventFilter::Changes(mask) => mask.contains(meta.change),
+            EventFilter::All(filters) => filters.iter().all(|filter| filter.matches(meta)),
+        }
+    }
+
+    fn uses_tags(&self) -> bool {
+        match self {
+            EventFilter::Tags(_) => true,
+            EventFilter::All(filters) => filters.iter().any(|filter| filter.uses_tags()),
+            _ => false,
+        }
+    }
+
+    fn uses_key(&self) -> bool {
+        match self {
+            EventFilter::KeyPrefix(_) => true,
+            EventFilter::All(filters) => filters.iter().any(|filter| filter.uses_key()),
+            _ => false,
+        }
+    }
+}
+
+
+/// boolean expression over tags of a document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TagExpr {
+    Tag(String),
+    All(Vec<TagExpr>),
+    Any(Vec<TagExpr>),
+    Not(Box<TagExpr>),
+}
+
+impl TagExpr {
+    pub fn tag(tag: &str) -> Self {
+        TagExpr::Tag(tag.to_owned())
+    }
+
+    fn matches(&self, tags: &[String]) -> bool {
+        match self {
+            TagExpr::Tag(tag) => tags.iter().any(|t| t == tag),
+            TagExpr::All(exprs) => exprs.iter().all(|expr| expr.matches(tags)),
+            TagExpr::Any(exprs) => exprs.iter().any(|expr| expr.matches(tags)),
+            TagExpr::Not(expr) => !expr.matches(tags),
+        }
+    }
+}
+
+
+/// kind of document change
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Insert,
+    Remove,
+    Expired,
+}
+
+
+/// set of `Change`, e.g. `ChangeMask::INSERT | ChangeMask::REMOVE`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChangeMask(u8);
+
+impl ChangeMask {
+    pub const INSERT: ChangeMask = ChangeMask(1);
+    pub const REMOVE: ChangeMask = ChangeMask(1 << 1);
+    pub const EXPIRED: ChangeMask = ChangeMask(1 << 2);
+
+    pub fn contains(&self, change: Change) -> bool {
+        let bit = match change {
+            Change::Insert => ChangeMask::INSERT,
+            Change::Remove => ChangeMask::REMOVE,
+            Change::Expired => ChangeMask::EXPIRED,
+        };
+        self.0 & bit.0 != 0
+    }
+}
+
+impl BitOr for ChangeMask {
+    type Output = ChangeMask;
+
+    fn bitor(self, rhs: ChangeMask) -> ChangeMask {
+        ChangeMask(self.0 | rhs.0)
+    }
+}
+
+
+/// metadata of a document change, built once on write path and shared by every filter
+pub(crate) struct EventMeta {
+    pub change: Change,
+
+    // empty unless a filter use key prefix
+    pub key: Vec<u8>,
+
+    // empty unless a filter use tags
+    pub tags: Vec<String>,
+}
+
+
+/// what registered filters need from write path, metadata built just when a filter exist
+pub(crate) struct Filtering<K> {
+    active: AtomicBool,
+    tags: AtomicBool,
+
+    // KeyCodec encode of K, set by first filter using key prefix
+    encode: OnceLock<fn(&K) -> Vec<u8>>,
+}
+
+impl<K> Filtering<K> {
+    pub fn new() -> Self {
+        Filtering {
+            active: AtomicBool::new(false),
+            tags: AtomicBool::new(false),
+            encode: OnceLock::new(),
+        }
+    }
+
+    pub fn register(&self, filter: &EventFilter, encode: fn(&K) -> Vec<u8>) {
+        if filter.uses_tags() {
+            self.tags.store(true, Ordering::Relaxed);
+        }
+        if filter.uses_key() {
+            let _ = self.encode.set(encode);
+        }
+        self.active.store(true, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// None without filters, tags asked only if a filter use them
+    pub fn meta(&self, change: Change, key: &K, tags: impl FnOnce() -> Vec<String>) -> Option<EventMeta> {
+        if !self.is_active() {
+            return None;
+        }
+
+        Some(EventMeta {
+            change,
+            key: self.encode.get().map_or_else(Vec::new, |encode| encode(key)),
+            tags: if self.tags.load(Ordering::Relaxed) { tags() } else { vec![] },
+        })
+    }
+}
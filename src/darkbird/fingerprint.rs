@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io::ErrorKind};
+
+use super::{document::Document, SessionResult, StatusResult};
+
+
+
+/// Types a store was created with, kept in `<path>/<name>.schema` beside WAL
+/// so opening a WAL with other key or document type fail early instead of misdecoding.
+/// type names and declared variants only: no shape reflection is available,
+/// a changed field of same type isn't caught
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Fingerprint {
+    pub key: String,
+    pub doc: String,
+
+    // Document::VARIANTS, absent in fingerprints recorded before them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<String>,
+}
+
+impl Fingerprint {
+    pub fn of<K, Doc: Document>() -> Self {
+        Fingerprint {
+            key: std::any::type_name::<K>().to_owned(),
+            doc: std::any::type_name::<Doc>().to_owned(),
+            variants: Doc::VARIANTS.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    /// Compare with fingerprint stored for store, true when it must be recorded
+    /// (new store, one created before fingerprints, or `overwrite`).
+    /// `overwrite` accept a mismatch, for intentional migrations
+    pub fn verify(&self, path: &str, name: &str, overwrite: bool) -> Result<bool, SessionResult> {
+        let stored: Option<Fingerprint> = match fs::read(file(path, name)) {
+            Ok(bytes) => Some(serde_json::from_slice(&bytes).map_err(|e| SessionResult::Err(StatusResult::Err(e.to_string())))?),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(SessionResult::Err(StatusResult::IoError(e))),
+        };
+
+        match stored {
+            Some(stored) if stored == *self => Ok(false),
+            // variants declared since fingerprint was recorded
+            Some(stored) if stored.variants.is_empty() && stored.key == self.key && stored.doc == self.doc => Ok(true),
+            Some(stored) if !overwrite => Err(SessionResult::SchemaMismatch {
+                stored: stored.to_string(),
+                requested: self.to_string(),
+            }),
+            _ => Ok(true),
+        }
+    }
+
+    /// called once WAL replayed, so a failed open doesn't record types
+    pub fn record(&self, path: &str, name: &str) -> Result<(), SessionResult> {
+        fs::write(file(path, name), serde_json::to_vec(self).unwrap()).map_err(|e| SessionResult::Err(StatusResult::IoError(e)))
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Storage<{}, {}>", self.key, self.doc)?;
+        if !self.variants.is_empty() {
+            write!(f, " variants [{}]", self.variants.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+
+
+#[inline]
+fn file(path: &str, name: &str) -> String {
+    format!("{}/{}.schema", path, name)
+}
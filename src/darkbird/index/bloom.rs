@@ -0,0 +1,75 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+
+
+// bounds of hash functions, 1% rate need 7
+const MIN_HASHES: u32 = 1;
+const MAX_HASHES: u32 = 16;
+
+
+/// Keys ever inserted to store, `Options::with_bloom_filter`.
+///
+/// a miss is definite, a hit may be false with about the configured rate
+/// while no more than expected keys were inserted, more past it. removed keys stay in,
+/// filter is rebuilt from keys in memory on every open
+pub struct Bloom {
+    bits: Vec<AtomicU64>,
+
+    // total bits, multiple of 64
+    len: u64,
+    hashes: u32,
+}
+
+impl Bloom {
+    /// sized for expected keys at false_positive_rate, clamped to (0, 0.5]
+    pub fn new(expected: usize, false_positive_rate: f64) -> Self {
+        let expected = expected.max(1);
+        let rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+
+        // m = -n ln(p) / ln(2)^2, k = m/n ln(2)
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-(expected as f64) * rate.ln() / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let words = bits.div_ceil(64);
+        let hashes = ((words * 64) as f64 / expected as f64 * ln2).round() as u32;
+
+        Bloom {
+            bits: (0..words).map(|_| AtomicU64::new(0)).collect(),
+            len: words * 64,
+            hashes: hashes.clamp(MIN_HASHES, MAX_HASHES),
+        }
+    }
+
+    #[inline]
+    pub fn insert<K: Hash>(&self, key: &K) {
+        let (h1, h2) = hashes(key);
+        for i in 0..self.hashes as u64 {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % self.len;
+            self.bits[(bit / 64) as usize].fetch_or(1 << (bit % 64), Ordering::Relaxed);
+        }
+    }
+
+    /// false if key was never inserted
+    #[inline]
+    pub fn may_contain<K: Hash>(&self, key: &K) -> bool {
+        let (h1, h2) = hashes(key);
+        (0..self.hashes as u64).all(|i| {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % self.len;
+            self.bits[(bit / 64) as usize].load(Ordering::Relaxed) & (1 << (bit % 64)) != 0
+        })
+    }
+}
+
+
+// double hashing, second hash odd so every probe differ
+#[inline]
+fn hashes<K: Hash>(key: &K) -> (u64, u64) {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    let h1 = hasher.finish();
+    let h2 = h1.wrapping_mul(0x9E37_79B9_7F4A_7C15).rotate_left(31) | 1;
+    (h1, h2)
+}
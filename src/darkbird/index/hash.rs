@@ -1,12 +1,16 @@
 use dashmap::{iter::Iter, mapref::one::Ref, DashMap};
+use parking_lot::RwLock;
 use serde::{de::DeserializeOwned, Serialize};
 
 use crate::{document::Document, darkbird::StatusResult};
-use std::hash::Hash;
+use std::{collections::BTreeSet, hash::Hash, ops::Bound};
 
 
 pub struct HashIndex<K> {
     hash: DashMap<String, K>,
+
+    // index keys ordered for prefix scans, written under its lock together with hash
+    ordered: RwLock<BTreeSet<String>>,
 }
 
 impl<K> HashIndex<K>
@@ -25,6 +29,7 @@ where
     pub fn new() -> Self {
         HashIndex {
             hash: DashMap::new(),
+            ordered: RwLock::new(BTreeSet::new()),
         }
     }
 
@@ -34,14 +39,21 @@ where
     where
         Doc: Document,
     {
-        let index_keys = doc.extract();
+        self.insert_keys(key, doc.extract())
+    }
+
+    /// insert entry with extracted index_keys
+    #[inline]
+    pub fn insert_keys(&self, key: &K, index_keys: Vec<String>) -> Result<(), StatusResult> {
+        let mut ordered = self.ordered.write();
         for ik in index_keys.iter() {
             if let Some(_) = self.hash.get(ik) {
                 return Err(StatusResult::Duplicate)
             }
         }
 
-        doc.extract().into_iter().for_each(|index_key| {
+        index_keys.into_iter().for_each(|index_key| {
+            ordered.insert(index_key.clone());
             self.hash.insert(index_key, key.clone());
         });
 
@@ -54,11 +66,44 @@ where
     where
         Doc: Document,
     {
-        doc.extract().iter().for_each(|index_key| {
+        self.remove_keys(&doc.extract());
+    }
+
+    /// remove entry with extracted index_keys
+    #[inline]
+    pub fn remove_keys(&self, index_keys: &[String]) {
+        let mut ordered = self.ordered.write();
+        index_keys.iter().for_each(|index_key| {
+            ordered.remove(index_key);
             self.hash.remove(index_key);
         });
     }
 
+    /// number of index keys
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.hash.len()
+    }
+
+    /// insert single entry, overwrite previous
+    #[inline]
+    pub fn insert_entry(&self, key: &K, index_key: String) {
+        let mut ordered = self.ordered.write();
+        ordered.insert(index_key.clone());
+        self.hash.insert(index_key, key.clone());
+    }
+
+    /// keys of index keys starting with prefix, in index key order,
+    /// every key for empty prefix
+    pub fn lookup_prefix(&self, prefix: &str) -> Vec<K> {
+        let ordered = self.ordered.read();
+        ordered
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|index_key| index_key.starts_with(prefix))
+            .filter_map(|index_key| self.hash.get(index_key).map(|rf| rf.value().clone()))
+            .collect()
+    }
+
     /// lookup by index_key
     #[inline]
     pub fn lookup(&self, index_key: &str) -> Option<Ref<String, K>>{
@@ -1,13 +1,16 @@
-use dashmap::{DashMap, DashSet};
+use dashmap::DashMap;
 use tokio::{spawn, task::JoinHandle};
 
-use std::{hash::Hash, sync::Arc, collections::HashSet};
+use std::{hash::Hash, sync::Arc, collections::{HashMap, HashSet}};
 
+use crate::darkbird::search::{tokenize, SearchQuery};
 
 
 
+
+// word -> key -> times word is in content of key
 pub struct InvertedIndex<K> {
-    index: Arc<DashMap<String, DashSet<K>>>,
+    index: Arc<DashMap<String, DashMap<K, u32>>>,
 }
 
 impl<K> InvertedIndex<K>
@@ -16,94 +19,185 @@ where
     +  Ord
     +  PartialEq
     +  Eq
-    +  Hash 
+    +  Hash
     +  Clone
     +  Send
     +  Sync
     + 'static
 {
     pub fn new() -> Self {
-        InvertedIndex { 
+        InvertedIndex {
             index: Arc::new(DashMap::new()),
         }
     }
 
 
+    /// index content of key, leaving words only old content (of document replaced) had
     #[inline]
-    pub fn insert(&self, key: K, content: String) -> JoinHandle<()> {
+    pub fn insert(&self, key: K, content: Option<String>, old: Option<String>) -> JoinHandle<()> {
         let index = self.index.clone();
         spawn(async move {
-            for word in content.split_whitespace() {
-                let word = word.to_lowercase() ;
-                let key = &key;
-                match index.get_mut(&word) {
-                    Some(list) => {
-                        if let None = list.value().get(&key) {
-                            list.value().insert(key.to_owned());
-                        }
-                    }
-                    None => {
-                        let list = DashSet::new();
-                        list.insert(key.to_owned());
-                        index.insert(word, list);
+            let counts = count_words(content.as_deref().unwrap_or_default());
+
+            if let Some(old) = old {
+                for word in tokenize(&old).collect::<HashSet<_>>() {
+                    if !counts.contains_key(&word) {
+                        remove_posting(&index, &key, &word);
                     }
                 }
             }
+
+            for (word, count) in counts {
+                index.entry(word).or_default().insert(key.clone(), count);
+            }
         })
     }
+
     #[inline]
     pub fn remove(&self, key: K, content: String) -> JoinHandle<()> {
         let index = self.index.clone();
         spawn(async move {
-            for word in content.split_whitespace() {
-                let word = word.to_lowercase() ;
-                if let Some(list) = index.get_mut(&word) {
-                    list.value().remove(&key);
-                }
+            for word in tokenize(&content).collect::<HashSet<_>>() {
+                remove_posting(&index, &key, &word);
             }
         })
     }
-   
-   
+
+
+    /// keys containing any word of text, most occurrences first
+    #[inline]
+    pub fn search(&self, text: &str) -> Vec<K> {
+        self.query(&SearchQuery::Any(tokenize(text).map(SearchQuery::Term).collect()))
+    }
+
+    /// keys of word as indexed now, copied out so caller hold no lock of index
+    #[inline]
+    pub fn posting_keys(&self, word: &str) -> Vec<K> {
+        match self.index.get(word) {
+            Some(list) => list.iter().map(|k| k.key().clone()).collect(),
+            None => vec![],
+        }
+    }
+
+    /// keys containing word, keys aren't copied
     #[inline]
-    pub fn search(&self, words: Vec<&str>) -> Vec<K> {
-        let mut collector = HashSet::new();
-        for w in words {
-            let keys = self.inner_search(w);
-            self.intersect(keys, &mut collector);
+    pub fn posting_len(&self, word: &str) -> usize {
+        self.index.get(word).map(|list| list.len()).unwrap_or(0)
+    }
+
+    /// keep keys containing any of words, one posting locked at a time
+    pub fn retain_any(&self, words: &[String], keys: &mut Vec<K>) {
+        let mut found = HashSet::with_capacity(keys.len());
+        for word in words {
+            if let Some(list) = self.index.get(word) {
+                found.extend(keys.iter().filter(|key| list.contains_key(*key)).cloned());
+            }
         }
+        keys.retain(|key| found.contains(key));
+    }
 
-        collector.into_iter().collect()
+    /// keys matching query, ordered by occurrences of words matched then by key
+    pub fn query(&self, query: &SearchQuery) -> Vec<K> {
+        let mut scored: Vec<(K, u32)> = self.score(query).into_iter().collect();
+        scored.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        scored.into_iter().map(|(key, _)| key).collect()
     }
 
-    #[inline] 
-    fn intersect(&self, keys: Vec<K>, collector: &mut HashSet<K>) {
-        for key in keys {
-            if let None = collector.get(&key) {
-                collector.insert(key);
+    /// all (word, key) entries
+    #[inline]
+    pub fn entries(&self) -> Vec<(String, K)> {
+        let mut result = Vec::new();
+        for list in self.index.iter() {
+            for k in list.value().iter() {
+                result.push((list.key().clone(), k.key().clone()));
             }
         }
+        result
     }
 
+    /// insert single word, once per occurrence
+    #[inline]
+    pub fn insert_word(&self, key: &K, word: String) {
+        *self.index.entry(word).or_default().entry(key.clone()).or_insert(0) += 1;
+    }
+
+    /// remove single word
+    #[inline]
+    pub fn remove_word(&self, key: &K, word: &str) {
+        remove_posting(&self.index, key, word);
+    }
 
-    #[inline] 
-    fn inner_search(&self, word: &str) -> Vec<K> {
-        let word = word.to_lowercase();
-        match self.index.get(&word) {
-            Some(list) => {
-                list
-                    .value()
-                    .iter()
-                    .map(|entry| entry.key().to_owned() )
-                    .collect()
+    // key -> occurrences of words matched
+    fn score(&self, query: &SearchQuery) -> HashMap<K, u32> {
+        match query {
+            SearchQuery::Term(term) => {
+                let mut words = tokenize(term);
+                let first = match words.next() {
+                    Some(word) => self.postings(&word),
+                    None => return HashMap::new(),
+                };
+                words.fold(first, |scores, word| intersect(scores, self.postings(&word)))
+            }
+            SearchQuery::All(queries) => {
+                let mut queries = queries.iter();
+                let first = match queries.next() {
+                    Some(query) => self.score(query),
+                    None => return HashMap::new(),
+                };
+                queries.fold(first, |scores, query| {
+                    if scores.is_empty() {
+                        return scores;
+                    }
+                    intersect(scores, self.score(query))
+                })
+            }
+            SearchQuery::Any(queries) => {
+                let mut scores = HashMap::new();
+                for query in queries {
+                    for (key, score) in self.score(query) {
+                        *scores.entry(key).or_insert(0) += score;
+                    }
+                }
+                scores
             }
-            None => {
-                vec![]
-            }        
         }
     }
 
+    fn postings(&self, word: &str) -> HashMap<K, u32> {
+        match self.index.get(word) {
+            Some(list) => list.value().iter().map(|entry| (entry.key().clone(), *entry.value())).collect(),
+            None => HashMap::new(),
+        }
+    }
+}
+
 
+fn count_words(content: &str) -> HashMap<String, u32> {
+    let mut counts = HashMap::new();
+    for word in tokenize(content) {
+        *counts.entry(word).or_insert(0) += 1;
+    }
+    counts
+}
 
+// word left without keys is dropped, so removed content doesn't grow index
+fn remove_posting<K: Eq + Hash>(index: &DashMap<String, DashMap<K, u32>>, key: &K, word: &str) {
+    let empty = match index.get(word) {
+        Some(list) => {
+            list.value().remove(key);
+            list.value().is_empty()
+        }
+        None => false,
+    };
+    if empty {
+        index.remove_if(word, |_, list| list.is_empty());
+    }
 }
 
+fn intersect<K: Eq + Hash>(scores: HashMap<K, u32>, other: HashMap<K, u32>) -> HashMap<K, u32> {
+    let (small, large) = if scores.len() <= other.len() { (scores, other) } else { (other, scores) };
+    small
+        .into_iter()
+        .filter_map(|(key, score)| large.get(&key).map(|other| (key, score + other)))
+        .collect()
+}
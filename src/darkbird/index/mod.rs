@@ -5,6 +5,9 @@ pub mod tags;
 pub mod hash;
 pub mod range;
 pub mod inverted_index;
+pub mod shadow;
+pub mod ordered;
 
 
 
+pub mod bloom;
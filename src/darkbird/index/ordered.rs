@@ -0,0 +1,133 @@
+use parking_lot::RwLock;
+
+use std::{borrow::Borrow, collections::{BTreeMap, BTreeSet}, ops::Bound};
+
+
+/// keys of store in order, `Options::with_ordered_keys`
+pub struct KeyOrder<K> {
+    keys: RwLock<BTreeSet<K>>,
+}
+
+impl<K> KeyOrder<K>
+where
+    K: Ord + Clone,
+{
+    pub fn new() -> Self {
+        KeyOrder {
+            keys: RwLock::new(BTreeSet::new()),
+        }
+    }
+
+    #[inline]
+    pub fn insert(&self, key: &K) {
+        let mut keys = self.keys.write();
+        if !keys.contains(key) {
+            keys.insert(key.clone());
+        }
+    }
+
+    #[inline]
+    pub fn remove(&self, key: &K) {
+        self.keys.write().remove(key);
+    }
+
+    /// up to n keys after cursor in order, first keys without cursor
+    pub fn after(&self, cursor: Option<&K>, n: usize) -> Vec<K> {
+        let keys = self.keys.read();
+        let from = cursor.map_or(Bound::Unbounded, Bound::Excluded);
+        keys.range((from, Bound::Unbounded)).take(n).cloned().collect()
+    }
+
+    /// keys starting with prefix in order, Borrow keep order of K same as of str
+    pub fn prefix(&self, prefix: &str) -> Vec<K>
+    where
+        K: Borrow<str>,
+    {
+        let keys = self.keys.read();
+        keys.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|key| (*key).borrow().starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+}
+
+
+
+/// keys of store in byte order of `KeyCodec` encoding, for `Storage::scan_prefix`
+/// and `Storage::key_range`. empty until first scan fill it, then kept by writes
+pub struct EncodedOrder<K> {
+    keys: RwLock<Option<Encoded<K>>>,
+}
+
+type Encode<K> = fn(&K) -> Vec<u8>;
+
+// encode used, and keys by their encoding
+type Encoded<K> = (Encode<K>, BTreeMap<Vec<u8>, K>);
+
+impl<K> EncodedOrder<K>
+where
+    K: Clone,
+{
+    pub fn new() -> Self {
+        EncodedOrder {
+            keys: RwLock::new(None),
+        }
+    }
+
+    /// caller put key in memory before, so a fill running meanwhile see it or key is added after
+    #[inline]
+    pub fn insert(&self, key: &K) {
+        if self.keys.read().is_none() {
+            return;
+        }
+
+        if let Some((encode, keys)) = self.keys.write().as_mut() {
+            keys.insert(encode(key), key.clone());
+        }
+    }
+
+    /// caller took key out of memory before
+    #[inline]
+    pub fn remove(&self, key: &K) {
+        if self.keys.read().is_none() {
+            return;
+        }
+
+        if let Some((encode, keys)) = self.keys.write().as_mut() {
+            keys.remove(&encode(key));
+        }
+    }
+
+    /// order keys held, on first call only
+    pub fn fill(&self, encode: Encode<K>, held: impl FnOnce() -> Vec<K>) {
+        if self.keys.read().is_some() {
+            return;
+        }
+
+        let mut keys = self.keys.write();
+        if keys.is_none() {
+            let filled = held().into_iter().map(|key| (encode(&key), key)).collect();
+            *keys = Some((encode, filled));
+        }
+    }
+
+    /// keys with encoding in [from, to) in order, none before fill
+    pub fn range(&self, from: &[u8], to: &[u8]) -> Vec<K> {
+        match self.keys.read().as_ref() {
+            Some((_, keys)) if from < to => keys.range::<[u8], _>((Bound::Included(from), Bound::Excluded(to))).map(|(_, key)| key.clone()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// keys with encoding starting with prefix in order, none before fill
+    pub fn prefix(&self, prefix: &[u8]) -> Vec<K> {
+        match self.keys.read().as_ref() {
+            Some((_, keys)) => keys
+                .range::<[u8], _>((Bound::Included(prefix), Bound::Unbounded))
+                .take_while(|(encoded, _)| encoded.starts_with(prefix))
+                .map(|(_, key)| key.clone())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
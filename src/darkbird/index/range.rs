@@ -1,12 +1,12 @@
 use std::{collections::{BTreeMap, BTreeSet}, ops::Bound};
 
-use crate::document::Document;
+use crate::{darkbird::SessionResult, document::{Document, FieldValue}};
 use dashmap::{DashMap, DashSet};
 use serde::{de::DeserializeOwned, Serialize};
 use std::hash::Hash;
 
 pub struct RangeIndex<K> {
-    multi_btree: DashMap<String, BTreeMap<String, DashSet<K>>>,
+    multi_btree: DashMap<String, BTreeMap<FieldValue, DashSet<K>>>,
 }
 
 impl<K> RangeIndex<K>
@@ -34,7 +34,7 @@ where
     where
         Doc: Document,
     {
-        doc.get_fields()
+        doc.get_typed_fields()
             .into_iter()
             .for_each(|rf| {
                 let val = rf.value;
@@ -60,19 +60,14 @@ where
             });
     }
 
-    /// remove entry from tree
+    /// remove entry from tree, value dropped with its last key
+    /// so type of field follow values still stored
     #[inline]
     pub fn remove<Doc>(&self, key: &K, doc: &Doc)
     where
         Doc: Document,
     {
-        doc.get_fields().into_iter().for_each(|rf| {
-            if let Some(mut tree) = self.multi_btree.get_mut(&rf.name) {
-                if let Some(set) = tree.value_mut().get_mut(&rf.value) {
-                    set.insert(key.clone());
-                }
-            }
-        });
+        doc.get_typed_fields().into_iter().for_each(|rf| self.remove_entry(key, &rf.name, &rf.value));
     }
 
     /// remove tree from multi-tree
@@ -82,29 +77,145 @@ where
     }
 
 
-    /// fetch document by range hash_index
+    /// fetch document by range hash_index,
+    /// from and to must have type of values stored for field
     #[inline]
-    pub fn range(&self, field_name: &str, from: String, to: String) -> Vec<K> {
-        let set = match self.multi_btree.get(field_name) {
-            Some(tree) => {
-                let mut set_result = BTreeSet::new();
+    pub fn range(&self, field_name: &str, from: FieldValue, to: FieldValue) -> Result<Vec<K>, SessionResult> {
+        self.keys_within(field_name, Bound::Included(from), Bound::Excluded(to))
+    }
 
-                // collect and distinct keys
-                for (_, set) in tree.range((Bound::Included(from), Bound::Excluded(to))) {
-                    for k in set.iter() {
-                        set_result.insert(k.key().clone());
-                    }
+    /// keys with field value within bounds, distinct and ordered
+    pub fn keys_within(&self, field_name: &str, from: Bound<FieldValue>, to: Bound<FieldValue>) -> Result<Vec<K>, SessionResult> {
+        let set = self.within(field_name, from, to, |values| {
+            let mut set_result = BTreeSet::new();
+            for (_, set) in values {
+                for k in set.iter() {
+                    set_result.insert(k.key().clone());
                 }
+            }
+            set_result
+        })?;
 
-                set_result
+        Ok(set.unwrap_or_default().into_iter().collect())
+    }
+
+    /// number of keys with field value within bounds, keys aren't copied
+    pub fn count_within(&self, field_name: &str, from: Bound<FieldValue>, to: Bound<FieldValue>) -> Result<usize, SessionResult> {
+        let count = self.within(field_name, from, to, |values| values.map(|(_, set)| set.len()).sum())?;
+        Ok(count.unwrap_or_default())
+    }
+
+    // f walk values within bounds, None when field has no tree or bounds are reversed
+    fn within<R>(
+        &self,
+        field_name: &str,
+        from: Bound<FieldValue>,
+        to: Bound<FieldValue>,
+        f: impl FnOnce(&mut dyn Iterator<Item = (&FieldValue, &DashSet<K>)>) -> R,
+    ) -> Result<Option<R>, SessionResult> {
+        let tree = match self.multi_btree.get(field_name) {
+            Some(tree) => tree,
+            None => return Ok(None),
+        };
+
+        let mismatch = |stored: String, requested: &FieldValue| SessionResult::FieldTypeMismatch {
+            field: field_name.to_owned(),
+            stored,
+            requested: requested.kind().to_owned(),
+        };
+
+        // values ordered by type first, so first and last differ if field hold several types
+        if let (Some((first, _)), Some((last, _))) = (tree.first_key_value(), tree.last_key_value()) {
+            for requested in [&from, &to] {
+                let requested = match requested {
+                    Bound::Included(value) | Bound::Excluded(value) => value,
+                    Bound::Unbounded => continue,
+                };
+                if first.kind() != last.kind() {
+                    return Err(mismatch(format!("{}|{}", first.kind(), last.kind()), requested));
+                }
+                if requested.kind() != first.kind() {
+                    return Err(mismatch(first.kind().to_owned(), requested));
+                }
             }
-            None => BTreeSet::new()
+        }
+
+        // BTreeMap::range panic on reversed bounds, or equal ones both excluded
+        let valid = match (&from, &to) {
+            (Bound::Included(a), Bound::Included(b)) => a <= b,
+            (Bound::Included(a) | Bound::Excluded(a), Bound::Included(b) | Bound::Excluded(b)) => a < b,
+            _ => true,
         };
+        if !valid {
+            return Ok(None);
+        }
+
+        Ok(Some(f(&mut tree.range((from, to)))))
+    }
 
-        set
-        .into_iter()
-        .collect::<Vec<K>>()
+        /// keys ordered by field value
+    #[inline]
+    pub fn ordered(&self, field_name: &str, desc: bool) -> Vec<K> {
+        match self.multi_btree.get(field_name) {
+            Some(tree) => {
+                let mut result = Vec::new();
+                let mut push = |set: &DashSet<K>| {
+                    let mut keys: Vec<K> = set.iter().map(|k| k.key().clone()).collect();
+                    keys.sort();
+                    result.extend(keys);
+                };
+
+                if desc {
+                    tree.values().rev().for_each(&mut push);
+                } else {
+                    tree.values().for_each(&mut push);
+                }
+
+                result
+            }
+            None => vec![]
+        }
     }
 
-    
+    /// all (field_name, value, key) entries
+    #[inline]
+    pub fn entries(&self) -> Vec<(String, FieldValue, K)> {
+        let mut result = Vec::new();
+        for tree in self.multi_btree.iter() {
+            for (value, set) in tree.value().iter() {
+                for k in set.iter() {
+                    result.push((tree.key().clone(), value.clone(), k.key().clone()));
+                }
+            }
+        }
+        result
+    }
+
+    /// insert single entry
+    #[inline]
+    pub fn insert_entry(&self, key: &K, field_name: String, value: FieldValue) {
+        self.multi_btree
+            .entry(field_name)
+            .or_default()
+            .entry(value)
+            .or_default()
+            .insert(key.clone());
+    }
+
+    /// remove single entry
+    #[inline]
+    pub fn remove_entry(&self, key: &K, field_name: &str, value: &FieldValue) {
+        if let Some(mut tree) = self.multi_btree.get_mut(field_name) {
+            let emptied = match tree.value().get(value) {
+                Some(set) => {
+                    set.remove(key);
+                    set.is_empty()
+                }
+                None => false,
+            };
+            if emptied {
+                tree.value_mut().remove(value);
+            }
+        }
+    }
 }
@@ -0,0 +1,55 @@
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+
+
+/// Index with an optional shadow under rebuild,
+/// writes applied to live and shadow, reads served by live until swap
+pub struct Shadowed<I> {
+    live: RwLock<Arc<I>>,
+    shadow: RwLock<Option<Arc<I>>>,
+}
+
+impl<I> Shadowed<I> {
+    pub fn new(index: I) -> Self {
+        Shadowed {
+            live: RwLock::new(Arc::new(index)),
+            shadow: RwLock::new(None),
+        }
+    }
+
+    /// index serving reads
+    #[inline]
+    pub fn live(&self) -> Arc<I> {
+        self.live.read().clone()
+    }
+
+    /// live and shadow (if any), every write must go to all of them
+    #[inline]
+    pub fn targets(&self) -> Vec<Arc<I>> {
+        let mut result = vec![self.live()];
+        if let Some(shadow) = self.shadow.read().as_ref() {
+            result.push(shadow.clone());
+        }
+        result
+    }
+
+    /// install empty shadow, return it for filling
+    pub fn begin(&self, index: I) -> Arc<I> {
+        let shadow = Arc::new(index);
+        *self.shadow.write() = Some(shadow.clone());
+        shadow
+    }
+
+    /// replace live by shadow, old live dropped when last reader release it
+    pub fn swap(&self) {
+        if let Some(shadow) = self.shadow.write().take() {
+            *self.live.write() = shadow;
+        }
+    }
+
+    /// drop shadow, live untouched
+    pub fn abort(&self) {
+        self.shadow.write().take();
+    }
+}
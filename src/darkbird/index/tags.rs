@@ -2,10 +2,19 @@ use dashmap::{iter::Iter, mapref::one::Ref, DashMap, DashSet};
 use serde::{de::DeserializeOwned, Serialize};
 
 use crate::document::Document;
-use std::hash::Hash;
+use std::{collections::HashSet, hash::Hash};
 
 pub struct TagIndex<K> {
     pub tags: DashMap<String, DashSet<K>>,
+
+    // tags of Storage::add_tag and remove_tag, over those of document
+    edits: DashMap<K, TagEdits>,
+}
+
+#[derive(Default)]
+struct TagEdits {
+    added: HashSet<String>,
+    removed: HashSet<String>,
 }
 
 impl<K> TagIndex<K>
@@ -24,17 +33,23 @@ where
     pub fn new() -> Self {
         TagIndex {
             tags: DashMap::new(),
+            edits: DashMap::new(),
         }
     }
 
-    /// insert entry with tags
+    /// insert entry with tags, edits of key applied
     #[inline]
     pub fn insert<Doc>(&self, key: &K, doc: &Doc)
     where
         Doc: Document,
     {
-        doc.get_tags()
-            .into_iter()
+        self.insert_tags(key, self.tags_of(key, doc));
+    }
+
+    /// insert entry with extracted tags
+    #[inline]
+    pub fn insert_tags(&self, key: &K, tags: Vec<String>) {
+        tags.into_iter()
             .for_each(|index_key| match self.tags.get_mut(&index_key) {
                 Some(set) => {
                     set.value().insert(key.clone());
@@ -64,19 +79,95 @@ where
         }
     }
 
-    /// remove entry from tags
+    /// remove entry from tags, edits of key applied and kept
     #[inline]
     pub fn remove<Doc>(&self, key: &K, doc: &Doc)
     where
         Doc: Document,
     {
-        doc.get_tags().into_iter().for_each(|index_key| {
-            if let Some(set) = self.tags.get_mut(&index_key) {
-                set.value().remove(&key);
+        self.remove_tags(key, self.tags_of(key, doc));
+    }
+
+    /// remove entry from extracted tags, a tag left without keys is dropped
+    #[inline]
+    pub fn remove_tags(&self, key: &K, tags: Vec<String>) {
+        tags.into_iter().for_each(|index_key| {
+            let empty = match self.tags.get(&index_key) {
+                Some(set) => {
+                    set.value().remove(key);
+                    set.value().is_empty()
+                }
+                None => false,
+            };
+            if empty {
+                self.tags.remove_if(&index_key, |_, set| set.is_empty());
             }
         });
     }
 
+    /// tags of document with edits of key applied
+    pub fn tags_of<Doc>(&self, key: &K, doc: &Doc) -> Vec<String>
+    where
+        Doc: Document,
+    {
+        let mut tags = doc.get_tags();
+        if let Some(edits) = self.edits.get(key) {
+            tags.retain(|tag| !edits.removed.contains(tag));
+            for tag in edits.added.iter() {
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+        }
+        tags
+    }
+
+    /// record tag added to key, whatever tags its document has
+    pub fn add_edit(&self, key: &K, tag: &str) {
+        let mut edits = self.edits.entry(key.clone()).or_default();
+        edits.removed.remove(tag);
+        edits.added.insert(tag.to_owned());
+    }
+
+    /// record tag removed from key, whatever tags its document has
+    pub fn remove_edit(&self, key: &K, tag: &str) {
+        let mut edits = self.edits.entry(key.clone()).or_default();
+        edits.added.remove(tag);
+        edits.removed.insert(tag.to_owned());
+    }
+
+    /// tags added and removed of key, sorted
+    pub fn edits_of(&self, key: &K) -> (Vec<String>, Vec<String>) {
+        match self.edits.get(key) {
+            Some(edits) => {
+                let mut added: Vec<String> = edits.added.iter().cloned().collect();
+                let mut removed: Vec<String> = edits.removed.iter().cloned().collect();
+                added.sort();
+                removed.sort();
+                (added, removed)
+            }
+            None => (vec![], vec![]),
+        }
+    }
+
+    /// drop edits of key removed
+    #[inline]
+    pub fn forget_edits(&self, key: &K) {
+        self.edits.remove(key);
+    }
+
+    /// insert entry to variant
+    #[inline]
+    pub fn insert_variant(&self, variant: &str, key: &K) {
+        self.insert_tags(key, vec![self.variant_key_maker(variant)]);
+    }
+
+    /// remove entry from variant
+    #[inline]
+    pub fn remove_from_variant(&self, variant: &str, key: &K) {
+        self.remove_tags(key, vec![self.variant_key_maker(variant)]);
+    }
+
     /// remove entry from view
     #[inline]
     pub fn remove_from_view(&self, view_name: &str, key: &K) {
@@ -87,13 +178,6 @@ where
     }
 
 
-    /// remove tag
-    #[inline]
-    pub fn remove_tag(&self, tag: &str) {
-        self.tags.remove(tag);
-    }
-
-
     /// remove view
     #[inline]
     pub fn remove_view(&self, view_name: &str) {
@@ -117,6 +201,40 @@ where
     }
     
     
+    /// lookup by variant
+    #[inline]
+    pub fn lookup_variant(&self, variant: &str) -> Option<Ref<'_, String, DashSet<K>>> {
+        self.tags.get(&self.variant_key_maker(variant))
+    }
+    
+    
+    /// insert single entry
+    #[inline]
+    pub fn insert_entry(&self, key: &K, tag: String) {
+        self.insert_tags(key, vec![tag]);
+    }
+
+    /// remove single entry
+    #[inline]
+    pub fn remove_entry(&self, key: &K, tag: String) {
+        self.remove_tags(key, vec![tag]);
+    }
+
+    /// number of tags, without views and variants
+    #[inline]
+    pub fn tags_len(&self) -> usize {
+        self.tags
+            .iter()
+            .filter(|rf| !rf.key().starts_with("__View__") && !rf.key().starts_with("__Variant__"))
+            .count()
+    }
+
+    /// number of keys in view
+    #[inline]
+    pub fn view_len(&self, view_name: &str) -> usize {
+        self.lookup_view(view_name).map_or(0, |rf| rf.value().len())
+    }
+
     /// get iter
     #[inline]
     pub fn iter(&self) -> Iter<String, DashSet<K>> {
@@ -125,9 +243,14 @@ where
 
 
     #[inline]
-    fn view_key_maker(&self, name: &str) -> String {
+    pub fn view_key_maker(&self, name: &str) -> String {
         format!("__View__{}", name)
     }
+
+    #[inline]
+    pub fn variant_key_maker(&self, name: &str) -> String {
+        format!("__Variant__{}", name)
+    }
         
 
 }
@@ -0,0 +1,143 @@
+/// Order-preserving key encoding,
+/// byte order of encoded keys equal to Ord of keys,
+/// so encoded keys can be used for prefix scans and ordered key ranges
+///
+/// - unsigned integers: big-endian
+/// - signed integers: big-endian with sign bit flipped
+/// - strings: 0x00 escaped as 0x00 0xFF, terminated by 0x00 0x00
+/// - tuples: concatenation of components, ordered component by component
+///
+/// ```ignore
+/// // all keys of user 7
+/// let sessions = storage.scan_prefix(&7u64.encode())?;
+/// ```
+pub trait KeyCodec: Sized {
+    /// append encoding to buf
+    fn encode_to(&self, buf: &mut Vec<u8>);
+
+    /// decode from head of buf and advance it
+    fn decode_from(buf: &mut &[u8]) -> Option<Self>;
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode_to(&mut buf);
+        buf
+    }
+
+    /// None if bytes is not exactly one encoded key
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut buf = bytes;
+        let key = Self::decode_from(&mut buf)?;
+        if buf.is_empty() {
+            Some(key)
+        } else {
+            None
+        }
+    }
+}
+
+
+
+#[inline]
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if buf.len() < len {
+        return None;
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Some(head)
+}
+
+
+macro_rules! unsigned_codec {
+    ($($t:ty),*) => {
+        $(
+            impl KeyCodec for $t {
+                fn encode_to(&self, buf: &mut Vec<u8>) {
+                    buf.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn decode_from(buf: &mut &[u8]) -> Option<Self> {
+                    let bytes = take(buf, std::mem::size_of::<$t>())?;
+                    Some(<$t>::from_be_bytes(bytes.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! signed_codec {
+    ($($t:ty => $u:ty),*) => {
+        $(
+            impl KeyCodec for $t {
+                fn encode_to(&self, buf: &mut Vec<u8>) {
+                    // flip sign bit, so negatives sort before positives
+                    let flipped = (*self as $u) ^ (1 << (<$u>::BITS - 1));
+                    flipped.encode_to(buf);
+                }
+
+                fn decode_from(buf: &mut &[u8]) -> Option<Self> {
+                    let flipped = <$u>::decode_from(buf)?;
+                    Some((flipped ^ (1 << (<$u>::BITS - 1))) as $t)
+                }
+            }
+        )*
+    };
+}
+
+unsigned_codec!(u8, u16, u32, u64, u128);
+signed_codec!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128);
+
+
+impl KeyCodec for String {
+    fn encode_to(&self, buf: &mut Vec<u8>) {
+        for &b in self.as_bytes() {
+            buf.push(b);
+            if b == 0x00 {
+                buf.push(0xFF);
+            }
+        }
+        buf.extend_from_slice(&[0x00, 0x00]);
+    }
+
+    fn decode_from(buf: &mut &[u8]) -> Option<Self> {
+        let mut bytes = Vec::new();
+        loop {
+            let b = take(buf, 1)?[0];
+            if b != 0x00 {
+                bytes.push(b);
+                continue;
+            }
+            match take(buf, 1)?[0] {
+                0x00 => break,
+                0xFF => bytes.push(0x00),
+                _ => return None,
+            }
+        }
+        String::from_utf8(bytes).ok()
+    }
+}
+
+
+macro_rules! tuple_codec {
+    ($($name:ident),+) => {
+        impl<$($name: KeyCodec),+> KeyCodec for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn encode_to(&self, buf: &mut Vec<u8>) {
+                let ($($name,)+) = self;
+                $($name.encode_to(buf);)+
+            }
+
+            fn decode_from(buf: &mut &[u8]) -> Option<Self> {
+                Some(($($name::decode_from(buf)?,)+))
+            }
+        }
+    };
+}
+
+tuple_codec!(A);
+tuple_codec!(A, B);
+tuple_codec!(A, B, C);
+tuple_codec!(A, B, C, D);
+tuple_codec!(A, B, C, D, E);
+tuple_codec!(A, B, C, D, E, F);
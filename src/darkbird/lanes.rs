@@ -0,0 +1,162 @@
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{
+    self,
+    error::{SendTimeoutError, TryRecvError, TrySendError},
+};
+
+
+
+/// Multiple bounded queues drained round robin by a single consumer,
+/// so a burst on one lane can't starve the others.
+/// order inside a lane is preserved, order across lanes is consumer order:
+/// a value that must follow every value sent before it, on any lane, is
+/// handled after consumer `drain`ed lanes
+pub fn channel<T>(lanes: usize, buffer: usize) -> (LaneSender<T>, LaneReceiver<T>) {
+    let lanes = lanes.max(1);
+
+    // doorbell wake up consumer when any lane received
+    let (bell_sx, bell_rx) = mpsc::channel(1);
+
+    let (senders, receivers) = (0..lanes).map(|_| mpsc::channel(buffer)).unzip();
+
+    (
+        LaneSender { lanes: senders, doorbell: bell_sx, buffer },
+        LaneReceiver { lanes: receivers, doorbell: bell_rx, next: 0 },
+    )
+}
+
+
+
+pub struct LaneSender<T> {
+    lanes: Vec<mpsc::Sender<T>>,
+    doorbell: mpsc::Sender<()>,
+
+    // capacity of each lane
+    buffer: usize,
+}
+
+impl<T> Clone for LaneSender<T> {
+    fn clone(&self) -> Self {
+        LaneSender {
+            lanes: self.lanes.clone(),
+            doorbell: self.doorbell.clone(),
+            buffer: self.buffer,
+        }
+    }
+}
+
+impl<T> LaneSender<T> {
+    pub fn lanes(&self) -> usize {
+        self.lanes.len()
+    }
+
+    /// values waiting in every lane
+    pub fn queued(&self) -> usize {
+        self.lanes.iter().map(|lane| self.buffer - lane.capacity()).sum()
+    }
+
+    /// send to lane (modulo total lanes)
+    pub async fn send_timeout(&self, lane: usize, value: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+        self.lanes[lane % self.lanes.len()].send_timeout(value, timeout).await?;
+
+        // if full, consumer already have a pending wake up
+        let _ = self.doorbell.try_send(());
+        Ok(())
+    }
+
+    /// send to lane without a runtime, retried while lane is full until deadline
+    pub fn send_deadline(&self, lane: usize, mut value: T, deadline: Instant) -> Result<(), SendTimeoutError<T>> {
+        loop {
+            match self.lanes[lane % self.lanes.len()].try_send(value) {
+                Ok(()) => {
+                    let _ = self.doorbell.try_send(());
+                    return Ok(());
+                }
+                Err(TrySendError::Closed(v)) => return Err(SendTimeoutError::Closed(v)),
+                Err(TrySendError::Full(v)) => {
+                    if Instant::now() >= deadline {
+                        return Err(SendTimeoutError::Timeout(v));
+                    }
+                    value = v;
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }
+    }
+}
+
+
+
+pub struct LaneReceiver<T> {
+    lanes: Vec<mpsc::Receiver<T>>,
+    doorbell: mpsc::Receiver<()>,
+    next: usize,
+}
+
+impl<T> LaneReceiver<T> {
+    /// take from next non-empty lane, round robin
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let total = self.lanes.len();
+        let mut disconnected = 0;
+
+        for _ in 0..total {
+            let index = self.next;
+            self.next = (self.next + 1) % total;
+
+            match self.lanes[index].try_recv() {
+                Ok(value) => return Ok(value),
+                Err(TryRecvError::Disconnected) => disconnected += 1,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+
+        if disconnected == total {
+            Err(TryRecvError::Disconnected)
+        } else {
+            Err(TryRecvError::Empty)
+        }
+    }
+
+    /// values waiting in every lane, at most limit, so senders can't keep it draining.
+    /// each value sent before call is returned if limit isn't reached
+    pub fn drain(&mut self, limit: usize) -> Vec<T> {
+        let mut values = vec![];
+        while values.len() < limit {
+            match self.try_recv() {
+                Ok(value) => values.push(value),
+                Err(_) => break,
+            }
+        }
+        values
+    }
+
+    /// block until a value received, None if all senders dropped
+    pub fn blocking_recv(&mut self) -> Option<T> {
+        loop {
+            match self.try_recv() {
+                Ok(value) => return Some(value),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => {
+                    if self.doorbell.blocking_recv().is_none() {
+                        return self.try_recv().ok();
+                    }
+                }
+            }
+        }
+    }
+
+    /// wait until a value received, None if all senders dropped
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            match self.try_recv() {
+                Ok(value) => return Some(value),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => {
+                    if self.doorbell.recv().await.is_none() {
+                        return self.try_recv().ok();
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use serde::Serialize;
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Instant,
+};
+
+
+
+/// Operations measured by `Storage::latency_report`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum Operation {
+    Insert,
+    Remove,
+    Lookup,
+    Range,
+    Search,
+
+    // handing an event to Reporter
+    Dispatch,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 6] = [
+        Operation::Insert,
+        Operation::Remove,
+        Operation::Lookup,
+        Operation::Range,
+        Operation::Search,
+        Operation::Dispatch,
+    ];
+}
+
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct OpLatency {
+    pub count: u64,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub p999: Duration,
+    pub max: Duration,
+}
+
+
+/// Latency per operation since open or last `Storage::reset_latency`,
+/// percentiles within ~12% (8 sub-buckets per power of two).
+/// empty unless built with `metrics` feature
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct LatencyReport {
+    pub operations: Vec<(Operation, OpLatency)>,
+}
+
+impl LatencyReport {
+    pub fn get(&self, op: Operation) -> Option<&OpLatency> {
+        self.operations.iter().find(|(o, _)| *o == op).map(|(_, l)| l)
+    }
+}
+
+
+
+// stripes of recorders, a thread record to its own stripe, merged on read
+#[cfg(feature = "metrics")]
+const STRIPES: usize = 4;
+
+// 8 sub-buckets per power of two, values up to 2^40 ns (~18 min)
+#[cfg(feature = "metrics")]
+const SUB_BITS: u32 = 3;
+
+#[cfg(feature = "metrics")]
+const MAX_BIT: u32 = 40;
+
+#[cfg(feature = "metrics")]
+const BUCKETS: usize = ((MAX_BIT - SUB_BITS + 2) << SUB_BITS) as usize;
+
+
+#[cfg(feature = "metrics")]
+struct Histogram {
+    buckets: Vec<AtomicU64>,
+    max: AtomicU64,
+}
+
+#[cfg(feature = "metrics")]
+impl Histogram {
+    fn new() -> Self {
+        Histogram {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    fn record(&self, nanos: u64) {
+        self.buckets[bucket(nanos)].fetch_add(1, Ordering::Relaxed);
+
+        // plain load first, max rarely change
+        if nanos > self.max.load(Ordering::Relaxed) {
+            self.max.fetch_max(nanos, Ordering::Relaxed);
+        }
+    }
+
+    fn reset(&self) {
+        self.buckets.iter().for_each(|b| b.store(0, Ordering::Relaxed));
+        self.max.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "metrics")]
+#[inline]
+fn bucket(nanos: u64) -> usize {
+    let nanos = nanos.min((1 << (MAX_BIT + 1)) - 1);
+    if nanos < (1 << SUB_BITS) {
+        return nanos as usize;
+    }
+
+    let msb = 63 - nanos.leading_zeros();
+    let shift = msb - SUB_BITS;
+    let sub = (nanos >> shift) & ((1 << SUB_BITS) - 1);
+    (((shift + 1) << SUB_BITS) as u64 + sub) as usize
+}
+
+// upper bound of bucket
+#[cfg(feature = "metrics")]
+fn bucket_value(index: usize) -> u64 {
+    let index = index as u64;
+    let sub_count = 1u64 << SUB_BITS;
+    if index < sub_count {
+        return index;
+    }
+
+    let shift = (index >> SUB_BITS) - 1;
+    let sub = index & (sub_count - 1);
+    ((sub_count + sub + 1) << shift) - 1
+}
+
+#[cfg(feature = "metrics")]
+fn stripe() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static STRIPE: Cell<Option<usize>> = const { Cell::new(None) };
+    }
+
+    STRIPE.with(|stripe| match stripe.get() {
+        Some(s) => s,
+        None => {
+            let s = NEXT.fetch_add(1, Ordering::Relaxed) % STRIPES;
+            stripe.set(Some(s));
+            s
+        }
+    })
+}
+
+
+
+/// Latency recorders of a store, compiled to nothing without `metrics` feature
+pub(crate) struct Latency {
+    // [stripe][operation]
+    #[cfg(feature = "metrics")]
+    recorders: Vec<Vec<Histogram>>,
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) type Started = Instant;
+
+#[cfg(not(feature = "metrics"))]
+#[derive(Clone, Copy)]
+pub(crate) struct Started;
+
+impl Latency {
+    pub fn new() -> Self {
+        Latency {
+            #[cfg(feature = "metrics")]
+            recorders: (0..STRIPES)
+                .map(|_| Operation::ALL.iter().map(|_| Histogram::new()).collect())
+                .collect(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn start(&self) -> Started {
+        #[cfg(feature = "metrics")]
+        return Instant::now();
+
+        #[cfg(not(feature = "metrics"))]
+        Started
+    }
+
+    #[inline(always)]
+    pub fn record(&self, _op: Operation, _started: Started) {
+        #[cfg(feature = "metrics")]
+        self.recorders[stripe()][_op as usize].record(_started.elapsed().as_nanos() as u64);
+    }
+
+    pub fn reset(&self) {
+        #[cfg(feature = "metrics")]
+        self.recorders.iter().flatten().for_each(Histogram::reset);
+    }
+
+    pub fn report(&self) -> LatencyReport {
+        #[cfg(feature = "metrics")]
+        {
+            let operations = Operation::ALL
+                .iter()
+                .map(|op| (*op, self.merge(*op as usize)))
+                .collect();
+            LatencyReport { operations }
+        }
+
+        #[cfg(not(feature = "metrics"))]
+        LatencyReport::default()
+    }
+
+    #[cfg(feature = "metrics")]
+    fn merge(&self, op: usize) -> OpLatency {
+        let mut counts = vec![0u64; BUCKETS];
+        let mut max = 0;
+        for stripe in self.recorders.iter() {
+            let histogram = &stripe[op];
+            for (count, bucket) in counts.iter_mut().zip(histogram.buckets.iter()) {
+                *count += bucket.load(Ordering::Relaxed);
+            }
+            max = max.max(histogram.max.load(Ordering::Relaxed));
+        }
+
+        let total: u64 = counts.iter().sum();
+        let percentile = |p: f64| {
+            let rank = ((total as f64) * p).ceil().max(1.0) as u64;
+            let mut seen = 0;
+            for (index, count) in counts.iter().enumerate() {
+                seen += count;
+                if seen >= rank {
+                    return Duration::from_nanos(bucket_value(index).min(max));
+                }
+            }
+            Duration::from_nanos(max)
+        };
+
+        if total == 0 {
+            return OpLatency::default();
+        }
+
+        OpLatency {
+            count: total,
+            p50: percentile(0.50),
+            p90: percentile(0.90),
+            p99: percentile(0.99),
+            p999: percentile(0.999),
+            max: Duration::from_nanos(max),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use serde::Serialize;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+
+
+/// Operation of a store, reported to `MetricsObserver` as it complete
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricEvent {
+    // hit when a document was found
+    Lookup { hit: bool },
+    LookupByIndex { hit: bool },
+
+    // hit when at least one document was found
+    LookupByTag { hit: bool },
+    Range { hit: bool },
+    View { hit: bool },
+    Search { hit: bool },
+
+    // time a write waited to queue its records to a WAL lane,
+    // records are written to disk after, by WAL thread
+    WalEnqueue(Duration),
+}
+
+
+/// Bridge of store operations into a metrics system (prometheus, statsd, ...),
+/// called on thread of operation, so it should only bump counters
+pub trait MetricsObserver: Send + Sync {
+    fn observe(&self, event: MetricEvent);
+}
+
+
+/// counters of `Storage::metrics` since open
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MetricsSnapshot {
+    pub lookup_hits: u64,
+    pub lookup_misses: u64,
+
+    pub index_hits: u64,
+    pub index_misses: u64,
+
+    pub tag_hits: u64,
+    pub tag_misses: u64,
+
+    pub range_hits: u64,
+    pub range_misses: u64,
+
+    pub view_hits: u64,
+    pub view_misses: u64,
+
+    pub search_hits: u64,
+    pub search_misses: u64,
+
+    // writes queued to WAL and their cumulative wait
+    pub wal_enqueues: u64,
+    pub wal_enqueue_time: Duration,
+
+    // events waiting to be dispatched by reporter, 0 without reporter
+    pub reporter_queue: usize,
+
+    pub subscribers: usize,
+}
+
+
+
+/// Counters of a store, shared with its WAL session
+pub(crate) struct Metrics {
+    lookup_hits: AtomicU64,
+    lookup_misses: AtomicU64,
+    index_hits: AtomicU64,
+    index_misses: AtomicU64,
+    tag_hits: AtomicU64,
+    tag_misses: AtomicU64,
+    range_hits: AtomicU64,
+    range_misses: AtomicU64,
+    view_hits: AtomicU64,
+    view_misses: AtomicU64,
+    search_hits: AtomicU64,
+    search_misses: AtomicU64,
+    wal_enqueues: AtomicU64,
+    wal_enqueue_nanos: AtomicU64,
+    observer: Option<Arc<dyn MetricsObserver>>,
+}
+
+impl Metrics {
+    pub fn new(observer: Option<Arc<dyn MetricsObserver>>) -> Self {
+        Metrics {
+            lookup_hits: AtomicU64::new(0),
+            lookup_misses: AtomicU64::new(0),
+            index_hits: AtomicU64::new(0),
+            index_misses: AtomicU64::new(0),
+            tag_hits: AtomicU64::new(0),
+            tag_misses: AtomicU64::new(0),
+            range_hits: AtomicU64::new(0),
+            range_misses: AtomicU64::new(0),
+            view_hits: AtomicU64::new(0),
+            view_misses: AtomicU64::new(0),
+            search_hits: AtomicU64::new(0),
+            search_misses: AtomicU64::new(0),
+            wal_enqueues: AtomicU64::new(0),
+            wal_enqueue_nanos: AtomicU64::new(0),
+            observer,
+        }
+    }
+
+    #[inline]
+    pub fn record(&self, event: MetricEvent) {
+        let counter = match event {
+            MetricEvent::Lookup { hit: true } => &self.lookup_hits,
+            MetricEvent::Lookup { hit: false } => &self.lookup_misses,
+            MetricEvent::LookupByIndex { hit: true } => &self.index_hits,
+            MetricEvent::LookupByIndex { hit: false } => &self.index_misses,
+            MetricEvent::LookupByTag { hit: true } => &self.tag_hits,
+            MetricEvent::LookupByTag { hit: false } => &self.tag_misses,
+            MetricEvent::Range { hit: true } => &self.range_hits,
+            MetricEvent::Range { hit: false } => &self.range_misses,
+            MetricEvent::View { hit: true } => &self.view_hits,
+            MetricEvent::View { hit: false } => &self.view_misses,
+            MetricEvent::Search { hit: true } => &self.search_hits,
+            MetricEvent::Search { hit: false } => &self.search_misses,
+            MetricEvent::WalEnqueue(took) => {
+                self.wal_enqueue_nanos.fetch_add(took.as_nanos() as u64, Ordering::Relaxed);
+                &self.wal_enqueues
+            }
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(observer) = &self.observer {
+            observer.observe(event);
+        }
+    }
+
+    /// record a WAL enqueue started at
+    #[inline]
+    pub fn enqueued(&self, started: Instant) {
+        self.record(MetricEvent::WalEnqueue(started.elapsed()));
+    }
+
+    pub fn snapshot(&self, reporter_queue: usize, subscribers: usize) -> MetricsSnapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+        MetricsSnapshot {
+            lookup_hits: load(&self.lookup_hits),
+            lookup_misses: load(&self.lookup_misses),
+            index_hits: load(&self.index_hits),
+            index_misses: load(&self.index_misses),
+            tag_hits: load(&self.tag_hits),
+            tag_misses: load(&self.tag_misses),
+            range_hits: load(&self.range_hits),
+            range_misses: load(&self.range_misses),
+            view_hits: load(&self.view_hits),
+            view_misses: load(&self.view_misses),
+            search_hits: load(&self.search_hits),
+            search_misses: load(&self.search_misses),
+            wal_enqueues: load(&self.wal_enqueues),
+            wal_enqueue_time: Duration::from_nanos(load(&self.wal_enqueue_nanos)),
+            reporter_queue,
+            subscribers,
+        }
+    }
+}
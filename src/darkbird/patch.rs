@@ -0,0 +1,81 @@
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::document::Document;
+
+
+
+/// Change to part of a document, applied by `Storage::patch` and logged to WAL alone,
+/// so updating one field of a large document doesn't write it whole.
+///
+/// replay apply patches in order on top of last full version of key,
+/// `compact` write documents whole so patch chains don't outlive it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DocPatch {
+    // RFC 7386 merge patch of document serialized as json: null remove a field,
+    // an object merge into field, anything else replace it.
+    // logged as json text, so WAL codecs without self-describing values hold it
+    Merge(#[serde(serialize_with = "json_text", deserialize_with = "from_json_text")] Value),
+
+    // applied by `Document::apply_patch`, encoded as document chose
+    Custom(Vec<u8>),
+}
+
+impl DocPatch {
+    pub fn merge(patch: Value) -> Self {
+        DocPatch::Merge(patch)
+    }
+
+    pub fn custom(patch: Vec<u8>) -> Self {
+        DocPatch::Custom(patch)
+    }
+
+    /// document left unchanged on Err
+    pub(crate) fn apply<Doc>(&self, doc: &mut Doc) -> Result<(), String>
+    where
+        Doc: Serialize + DeserializeOwned + Document,
+    {
+        match self {
+            DocPatch::Merge(patch) => {
+                let mut value = serde_json::to_value(&*doc).map_err(|e| e.to_string())?;
+                merge(&mut value, patch);
+                *doc = serde_json::from_value(value).map_err(|e| e.to_string())?;
+                Ok(())
+            }
+            DocPatch::Custom(patch) => doc.apply_patch(patch),
+        }
+    }
+}
+
+
+fn merge(target: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        patch => {
+            *target = patch.clone();
+            return;
+        }
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    if let Value::Object(target) = target {
+        for (name, value) in patch {
+            if value.is_null() {
+                target.remove(name);
+            } else {
+                merge(target.entry(name.as_str()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+fn json_text<S: Serializer>(value: &Value, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string())
+}
+
+fn from_json_text<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    serde_json::from_str(&text).map_err(serde::de::Error::custom)
+}
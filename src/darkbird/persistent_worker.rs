@@ -121,11 +121,14 @@ impl Persistent {
             + Sync
             + 'static,
     {
-        for refi in storage.iter() {
-            let key = refi.key();
-            let document = refi.value();
-
-            if let Err(Stop) = handler.handle_setter(&self.db_session, key, document).await {
+        // documents cloned out, so no shard is held across handler and value compression work
+        for key in storage.iter_keys_owned() {
+            let document = match storage.visible_owned(&key) {
+                Some(document) => document,
+                None => continue,
+            };
+
+            if let Err(Stop) = handler.handle_setter(&self.db_session, &key, &document).await {
                 println!("Stop copying ....");
                 return;
             }
@@ -0,0 +1,85 @@
+use serde::Serialize;
+use std::{any::Any, collections::HashMap, marker::PhantomData, sync::Arc};
+
+
+
+/// Write under way through the plugin chain, plugins may replace key or document,
+/// final key and document are what get logged and stored.
+/// metadata pass annotations to later plugins of chain, it is not stored
+pub struct WriteContext<K, Doc> {
+    pub key: K,
+    pub doc: Doc,
+    pub metadata: HashMap<String, String>,
+}
+
+
+/// Intercept writes of a store (validation, size limits, normalization, ...),
+/// registered by `Options::with_plugin` and run in registration order.
+///
+/// an Err reject the write with `SessionResult::PluginRejected`,
+/// chain isn't run while replaying WAL
+pub trait WritePlugin<K, Doc>: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// called by insert
+    fn on_insert(&self, ctx: &mut WriteContext<K, Doc>) -> Result<(), String>;
+
+    /// called by remove
+    fn on_remove(&self, _key: &K) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+
+// plugin erased of K and Doc, so Options don't need to be generic
+#[derive(Clone)]
+pub(crate) struct ErasedPlugin {
+    pub name: String,
+
+    // Arc<dyn WritePlugin<K, Doc>>
+    plugin: Arc<dyn Any + Send + Sync>,
+}
+
+impl ErasedPlugin {
+    pub fn new<K: 'static, Doc: 'static>(plugin: Arc<dyn WritePlugin<K, Doc>>) -> Self {
+        ErasedPlugin {
+            name: plugin.name().to_owned(),
+            plugin: Arc::new(plugin),
+        }
+    }
+
+    /// None if plugin was registered for other key or document type
+    pub fn typed<K: 'static, Doc: 'static>(&self) -> Option<Arc<dyn WritePlugin<K, Doc>>> {
+        self.plugin.downcast_ref::<Arc<dyn WritePlugin<K, Doc>>>().cloned()
+    }
+}
+
+
+
+/// reject documents larger than `max_bytes` once serialized (bincode, as in WAL)
+pub struct SizeLimit<K, Doc> {
+    max_bytes: usize,
+    _types: PhantomData<fn(K, Doc)>,
+}
+
+impl<K, Doc> SizeLimit<K, Doc> {
+    pub fn new(max_bytes: usize) -> Self {
+        SizeLimit { max_bytes, _types: PhantomData }
+    }
+}
+
+impl<K, Doc: Serialize> WritePlugin<K, Doc> for SizeLimit<K, Doc> {
+    fn name(&self) -> &str {
+        "size_limit"
+    }
+
+    fn on_insert(&self, ctx: &mut WriteContext<K, Doc>) -> Result<(), String> {
+        let size = bincode::serialized_size(&ctx.doc).map_err(|e| e.to_string())? as usize;
+        if size > self.max_bytes {
+            return Err(format!("document is {} bytes, limit {}", size, self.max_bytes));
+        }
+
+        ctx.metadata.insert("size".to_owned(), size.to_string());
+        Ok(())
+    }
+}
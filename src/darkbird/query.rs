@@ -0,0 +1,305 @@
+use dashmap::mapref::one::Ref;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fmt::Display,
+    hash::Hash,
+    ops::{Bound, RangeBounds},
+    time::Duration,
+};
+
+use crate::{document::{Document, FieldValue}, Storage};
+
+use super::{query_cache::Deps, storage::Probe};
+
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+
+/// order of pages of `Storage::lookup_by_tag_page` and others, stable across calls
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PageOrder {
+    Key(Order),
+
+    // by range field, documents without field come last ordered by key
+    Field(String, Order),
+}
+
+
+/// Chainable query over tags, index, range and search, planned as `Storage::lookup_by_tags_all`:
+/// keys of most selective filter are read and checked against the others
+///
+/// ```ignore
+/// let docs = storage.find()
+///     .tag("status:open")
+///     .index_eq("country", "DE")
+///     .range("age", 18..=30)
+///     .order_by("created_at", Order::Desc)
+///     .limit(50)
+///     .fetch_owned();
+///
+/// // next page
+/// let next = storage.find().tag("status:open").after(last_key).limit(50).keys();
+/// ```
+pub struct QueryBuilder<'a, K, Doc: Document> {
+    storage: &'a Storage<K, Doc>,
+    filters: Vec<Probe>,
+    order_by: Option<(String, Order)>,
+    after: Option<K>,
+    offset: usize,
+    limit: Option<usize>,
+    cached: Option<Duration>,
+}
+
+impl<'a, K, Doc> QueryBuilder<'a, K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    pub(crate) fn new(storage: &'a Storage<K, Doc>) -> Self {
+        QueryBuilder {
+            storage,
+            filters: vec![],
+            order_by: None,
+            after: None,
+            offset: 0,
+            limit: None,
+            cached: None,
+        }
+    }
+
+    /// documents have tag
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.filters.push(Probe::Tag(tag.to_owned()));
+        self
+    }
+
+    /// document refer to index_key
+    pub fn index(mut self, index_key: &str) -> Self {
+        self.filters.push(Probe::Index(index_key.to_owned()));
+        self
+    }
+
+    /// document refer to index key `field:value`, as `Indexer::extract` build them by convention
+    pub fn index_eq(self, field: &str, value: impl Display) -> Self {
+        self.index(&format!("{}:{}", field, value))
+    }
+
+    /// documents with field in range, e.g. `18..=30`, `..30` or `18..`,
+    /// none if a bound doesn't have type of field
+    pub fn range<V>(mut self, field_name: &str, range: impl RangeBounds<V>) -> Self
+    where
+        V: Clone + Into<FieldValue>,
+    {
+        let bound = |bound: Bound<&V>| match bound {
+            Bound::Included(value) => Bound::Included(value.clone().into()),
+            Bound::Excluded(value) => Bound::Excluded(value.clone().into()),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        self.filters.push(Probe::Range(field_name.to_owned(), bound(range.start_bound()), bound(range.end_bound())));
+        self
+    }
+
+    /// documents contain any word of text
+    pub fn text(mut self, text: &str) -> Self {
+        self.filters.push(Probe::Text(text.to_owned()));
+        self
+    }
+
+    /// order by range field, documents without field come last,
+    /// default order is by key
+    pub fn order_by(mut self, field_name: &str, order: Order) -> Self {
+        self.order_by = Some((field_name.to_owned(), order));
+        self
+    }
+
+    /// Results after cursor, key of last result of previous page, offset applied after.
+    ///
+    /// in key order, keys greater than cursor. with `order_by`, results after place of cursor,
+    /// found from its current document if it doesn't match any more, cursor removed since
+    /// continue from documents without field
+    pub fn after(mut self, cursor: K) -> Self {
+        self.after = Some(cursor);
+        self
+    }
+
+    /// skip first n results, used for paging
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// serve keys from query cache if cached less than ttl ago, else cache them.
+    ///
+    /// a write changing result of query drop it, a result read while a write is applied
+    /// isn't cached, so a cached result is never stale but by ttl for what writes don't drive:
+    /// documents past their ttl and quarantined ones. documents are read from store
+    /// by `fetch`, so they are current. no-op without `Options::with_query_cache`
+    pub fn cached(mut self, ttl: Duration) -> Self {
+        self.cached = Some(ttl);
+        self
+    }
+
+    /// fetch documents, none with value compression as `Storage::lookup`, see `fetch_owned`
+    pub fn fetch(self) -> Vec<Ref<'a, K, Doc>> {
+        let storage = self.storage;
+        self.keys()
+            .iter()
+            .filter_map(|key| storage.get_visible(key))
+            .collect()
+    }
+
+    /// fetch documents cloned out, no lock held once returned, work with value compression
+    pub fn fetch_owned(self) -> Vec<(K, Doc)> {
+        let storage = self.storage;
+        self.keys()
+            .into_iter()
+            .filter_map(|key| storage.get_owned(&key).map(|doc| (key, doc)))
+            .collect()
+    }
+
+    /// count matched documents, offset and limit applied
+    pub fn count(self) -> usize {
+        self.keys().len()
+    }
+
+    /// keys of matched documents
+    pub fn keys(self) -> Vec<K> {
+        let (cache, ttl) = match (self.storage.query_cache(), self.cached) {
+            (Some(cache), Some(ttl)) => (cache, ttl),
+            _ => return self.matched(),
+        };
+
+        let fingerprint = self.fingerprint();
+        match cache.get(&fingerprint, ttl) {
+            Ok(keys) => keys,
+            Err(generation) => {
+                let keys = self.matched();
+                cache.put(fingerprint, self.deps(), &keys, generation);
+                keys
+            }
+        }
+    }
+
+    fn matched(&self) -> Vec<K> {
+        let candidates: Vec<K> = if self.filters.is_empty() {
+            self.storage.iter_keys_owned().collect()
+        } else {
+            self.storage.select(&self.filters)
+        };
+
+        let ordered = match &self.order_by {
+            None => {
+                let mut keys = candidates;
+                keys.sort();
+                keys.dedup();
+                if let Some(cursor) = &self.after {
+                    let past = keys.partition_point(|k| k <= cursor);
+                    keys.drain(..past);
+                }
+                keys
+            }
+            Some((field_name, order)) => {
+                let mut rest: HashSet<K> = candidates.into_iter().collect();
+                let mut keys = Vec::with_capacity(rest.len());
+
+                for k in self.storage.range_order(field_name, matches!(order, Order::Desc)) {
+                    if rest.remove(&k) {
+                        keys.push(k);
+                    }
+                }
+
+                let mut tail: Vec<K> = rest.into_iter().collect();
+                tail.sort();
+                keys.extend(tail);
+
+                if let Some(cursor) = &self.after {
+                    let past = match keys.iter().position(|k| k == cursor) {
+                        Some(at) => at + 1,
+                        None => {
+                            let place = |k: &K| (self.storage.field_value(k, field_name), k.clone());
+                            let cursor = place(cursor);
+                            keys.iter()
+                                .take_while(|k| compare(&place(k), &cursor, *order) != Ordering::Greater)
+                                .count()
+                        }
+                    };
+                    keys.drain(..past);
+                }
+                keys
+            }
+        };
+
+        ordered
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    // same for queries with same filters in any order
+    fn fingerprint(&self) -> String {
+        let mut filters: Vec<String> = self.filters.iter().map(|filter| format!("{:?}", filter)).collect();
+        filters.sort();
+        filters.dedup();
+
+        let after = self.after.as_ref().map(|cursor| bincode::serialize(cursor).unwrap_or_default());
+        format!("{:?} {:?} {:?} {} {:?}", filters, self.order_by, after, self.offset, self.limit)
+    }
+
+    fn deps(&self) -> Deps {
+        let mut deps = Deps { all: self.filters.is_empty(), ..Deps::default() };
+        for filter in self.filters.iter() {
+            match filter {
+                Probe::Tag(tag) => deps.tags.push(tag.clone()),
+                Probe::Index(index_key) => deps.index_keys.push(index_key.clone()),
+                Probe::Range(field_name, ..) => deps.fields.push(field_name.clone()),
+                Probe::Text(_) => deps.text = true,
+            }
+        }
+        if let Some((field_name, _)) = &self.order_by {
+            deps.fields.push(field_name.clone());
+        }
+
+        deps
+    }
+}
+
+
+/// order of places (value of order_by field, key) of two results,
+/// documents without field come last ordered by key
+fn compare<K: Ord>(a: &(Option<FieldValue>, K), b: &(Option<FieldValue>, K), order: Order) -> Ordering {
+    match (&a.0, &b.0) {
+        (Some(x), Some(y)) => {
+            let by_value = match order {
+                Order::Asc => x.cmp(y),
+                Order::Desc => y.cmp(x),
+            };
+            by_value.then_with(|| a.1.cmp(&b.1))
+        }
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.1.cmp(&b.1),
+    }
+}
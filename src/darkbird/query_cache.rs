@@ -0,0 +1,288 @@
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    time::Duration,
+};
+use tokio::time::Instant;
+
+use super::document::{Document, FieldValue};
+
+
+
+/// statistics of query cache since open, see `Storage::query_cache_stats`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QueryCacheStats {
+    pub hits: u64,
+
+    // not cached, older than ttl of call, or cached while a write was applied
+    pub misses: u64,
+
+    // entries dropped by a write changing their result
+    pub invalidations: u64,
+
+    // entries dropped to stay under bounds
+    pub evictions: u64,
+
+    pub entries: usize,
+
+    // approximate, fingerprints and serialized keys
+    pub bytes: usize,
+}
+
+
+/// what a cached result depend on, from filters and order of query
+#[derive(Default)]
+pub(crate) struct Deps {
+    pub tags: Vec<String>,
+    pub index_keys: Vec<String>,
+
+    // range and order_by fields
+    pub fields: Vec<String>,
+
+    pub text: bool,
+
+    // no filter, every key is a candidate
+    pub all: bool,
+}
+
+
+// what a write changed in derived structures, from document before and after
+#[derive(Default)]
+pub(crate) struct Change {
+    tags: HashSet<String>,
+    index_keys: HashSet<String>,
+    fields: HashSet<String>,
+    content: bool,
+
+    // key created or removed
+    membership: bool,
+}
+
+impl Change {
+    fn between<Doc: Document>(old: Option<&Doc>, new: Option<&Doc>) -> Self {
+        if old.is_none() && new.is_none() {
+            return Change::default();
+        }
+
+        let fields = |doc: Option<&Doc>| {
+            let mut fields: HashMap<String, Vec<FieldValue>> = HashMap::new();
+            for field in doc.map(|doc| doc.get_typed_fields()).unwrap_or_default() {
+                fields.entry(field.name).or_default().push(field.value);
+            }
+            fields.values_mut().for_each(|values| values.sort());
+            fields
+        };
+        let (old_fields, new_fields) = (fields(old), fields(new));
+        let changed_fields = old_fields
+            .keys()
+            .chain(new_fields.keys())
+            .filter(|name| old_fields.get(*name) != new_fields.get(*name))
+            .cloned()
+            .collect();
+
+        Change {
+            tags: changed(old.map(|doc| doc.get_tags()), new.map(|doc| doc.get_tags())),
+            index_keys: changed(old.map(|doc| doc.extract()), new.map(|doc| doc.extract())),
+            fields: changed_fields,
+            content: old.and_then(|doc| doc.get_content()) != new.and_then(|doc| doc.get_content()),
+            membership: old.is_some() != new.is_some(),
+        }
+    }
+
+    fn hits(&self, deps: &Deps) -> bool {
+        (deps.all && self.membership)
+            || (deps.text && self.content)
+            || deps.tags.iter().any(|tag| self.tags.contains(tag))
+            || deps.index_keys.iter().any(|index_key| self.index_keys.contains(index_key))
+            || deps.fields.iter().any(|field| self.fields.contains(field))
+    }
+}
+
+// values in one of old and new only
+fn changed(old: Option<Vec<String>>, new: Option<Vec<String>>) -> HashSet<String> {
+    let old: HashSet<String> = old.unwrap_or_default().into_iter().collect();
+    let new: HashSet<String> = new.unwrap_or_default().into_iter().collect();
+    old.symmetric_difference(&new).cloned().collect()
+}
+
+
+struct Entry<K> {
+    keys: Vec<K>,
+    deps: Deps,
+    cached_at: Instant,
+    bytes: usize,
+
+    // tick of last hit, least recently used evicted first
+    used: u64,
+}
+
+struct State<K> {
+    entries: HashMap<String, Entry<K>>,
+
+    // bumped when a write start and end, a result computed across a bump isn't cached
+    generation: u64,
+
+    // writes between begin and end
+    writing: usize,
+
+    tick: u64,
+    stats: QueryCacheStats,
+}
+
+
+/// Results of `QueryBuilder::cached` queries, enabled by `Options::with_query_cache`.
+///
+/// invalidated by write path once derived structures are updated, from documents before and after:
+/// an entry is dropped only when a write change membership of a tag or index key it filter by,
+/// a range or order field it read, content with text filters, or creates or removes a key
+/// for queries without filter
+pub(crate) struct QueryCache<K> {
+    max_entries: usize,
+    max_bytes: usize,
+    state: Mutex<State<K>>,
+}
+
+impl<K> QueryCache<K>
+where
+    K: Serialize + Clone + Eq + Hash,
+{
+    pub fn new(max_entries: usize, max_bytes: usize) -> Self {
+        QueryCache {
+            max_entries,
+            max_bytes,
+            state: Mutex::new(State {
+                entries: HashMap::new(),
+                generation: 0,
+                writing: 0,
+                tick: 0,
+                stats: QueryCacheStats::default(),
+            }),
+        }
+    }
+
+    /// keys cached for fingerprint within ttl, else generation to `put` result with
+    pub fn get(&self, fingerprint: &str, ttl: Duration) -> Result<Vec<K>, u64> {
+        let mut state = self.state.lock();
+        state.tick += 1;
+        let tick = state.tick;
+
+        match state.entries.get_mut(fingerprint) {
+            Some(entry) if entry.cached_at.elapsed() <= ttl => {
+                entry.used = tick;
+                let keys = entry.keys.clone();
+                state.stats.hits += 1;
+                Ok(keys)
+            }
+            _ => {
+                state.stats.misses += 1;
+                Err(state.generation)
+            }
+        }
+    }
+
+    /// cache result computed since `get` returned generation, unless a write ran meanwhile
+    pub fn put(&self, fingerprint: String, deps: Deps, keys: &[K], generation: u64) {
+        let bytes = fingerprint.len() + bincode::serialized_size(keys).map_or(usize::MAX, |size| size as usize);
+        if bytes > self.max_bytes || self.max_entries == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock();
+        if state.writing > 0 || state.generation != generation {
+            return;
+        }
+
+        state.tick += 1;
+        let entry = Entry { keys: keys.to_vec(), deps, cached_at: Instant::now(), bytes, used: state.tick };
+        if let Some(old) = state.entries.insert(fingerprint, entry) {
+            state.stats.bytes -= old.bytes;
+        }
+        state.stats.bytes += bytes;
+
+        while state.entries.len() > self.max_entries || state.stats.bytes > self.max_bytes {
+            let lru = state.entries.iter().min_by_key(|(_, entry)| entry.used).map(|(fingerprint, _)| fingerprint.clone());
+            if let Some(entry) = lru.and_then(|fingerprint| state.entries.remove(&fingerprint)) {
+                state.stats.bytes -= entry.bytes;
+                state.stats.evictions += 1;
+            }
+        }
+        state.stats.entries = state.entries.len();
+    }
+
+    /// a write start changing derived structures from old to new document,
+    /// entries it changed are dropped when returned guard is, once write is applied.
+    /// None for both when an enclosing write report change itself
+    pub fn write<Doc: Document>(&self, old: Option<&Doc>, new: Option<&Doc>) -> Writing<'_, K> {
+        let change = Change::between(old, new);
+
+        let mut state = self.state.lock();
+        state.writing += 1;
+        state.generation += 1;
+        drop(state);
+
+        Writing { cache: self, change }
+    }
+
+    /// write changing membership of tag alone, by `Storage::add_tag` and `remove_tag`
+    pub fn write_tag(&self, tag: &str) -> Writing<'_, K> {
+        let mut state = self.state.lock();
+        state.writing += 1;
+        state.generation += 1;
+        drop(state);
+
+        let change = Change { tags: HashSet::from([tag.to_owned()]), ..Change::default() };
+        Writing { cache: self, change }
+    }
+
+    fn end(&self, change: &Change) {
+        let mut state = self.state.lock();
+        state.writing -= 1;
+        state.generation += 1;
+
+        let before = state.entries.len();
+        let mut freed = 0;
+        state.entries.retain(|_, entry| {
+            let keep = !change.hits(&entry.deps);
+            if !keep {
+                freed += entry.bytes;
+            }
+            keep
+        });
+
+        state.stats.invalidations += (before - state.entries.len()) as u64;
+        state.stats.bytes -= freed;
+        state.stats.entries = state.entries.len();
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state.lock();
+        state.entries.clear();
+        state.stats.bytes = 0;
+        state.stats.entries = 0;
+    }
+
+    pub fn stats(&self) -> QueryCacheStats {
+        self.state.lock().stats
+    }
+}
+
+
+/// write in progress, see `QueryCache::write`, ended on early returns too
+pub(crate) struct Writing<'a, K>
+where
+    K: Serialize + Clone + Eq + Hash,
+{
+    cache: &'a QueryCache<K>,
+    change: Change,
+}
+
+impl<K> Drop for Writing<'_, K>
+where
+    K: Serialize + Clone + Eq + Hash,
+{
+    fn drop(&mut self) {
+        self.cache.end(&self.change);
+    }
+}
@@ -0,0 +1,135 @@
+use parking_lot::RwLock;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::time::Instant;
+
+
+
+/// Where a read is served from, see `Storage::lookup_with`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ReadPreference {
+    /// live store, always current
+    #[default]
+    Fresh,
+
+    /// frozen read snapshot, no shard lock taken
+    Snapshot,
+
+    /// read snapshot if not older than bound, else Fresh
+    SnapshotMaxStaleness(Duration),
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadSource {
+    Live,
+    Snapshot,
+}
+
+
+/// Result of a read with its freshness, for callers to log
+#[derive(Clone, Debug)]
+pub struct ReadResult<T> {
+    pub value: T,
+    pub source: ReadSource,
+
+    // store clock when snapshot refresh started, None when Live
+    pub taken_at: Option<SystemTime>,
+
+    // zero when Live
+    pub age: Duration,
+}
+
+impl<T> ReadResult<T> {
+    pub(crate) fn live(value: T) -> Self {
+        ReadResult {
+            value,
+            source: ReadSource::Live,
+            taken_at: None,
+            age: Duration::ZERO,
+        }
+    }
+}
+
+
+
+/// copy of documents, immutable once published
+pub(crate) struct Frozen<K, Doc> {
+    pub docs: HashMap<K, Doc>,
+    pub taken_at: SystemTime,
+    pub taken: Instant,
+}
+
+impl<K, Doc> Frozen<K, Doc> {
+    pub fn result<T>(&self, value: T) -> ReadResult<T> {
+        ReadResult {
+            value,
+            source: ReadSource::Snapshot,
+            taken_at: Some(self.taken_at),
+            age: self.taken.elapsed(),
+        }
+    }
+}
+
+
+/// Read snapshot of a store, enabled by `Options::with_read_snapshot`.
+///
+/// refreshed once older than interval by the Snapshot read noticing it,
+/// or by `Storage::refresh_snapshot`, readers keep the previous one meanwhile
+pub(crate) struct ReadSnapshot<K, Doc> {
+    pub interval: Duration,
+    current: RwLock<Option<Arc<Frozen<K, Doc>>>>,
+    refreshing: AtomicBool,
+}
+
+impl<K, Doc> ReadSnapshot<K, Doc> {
+    pub fn new(interval: Duration) -> Self {
+        ReadSnapshot {
+            interval,
+            current: RwLock::new(None),
+            refreshing: AtomicBool::new(false),
+        }
+    }
+
+    pub fn current(&self) -> Option<Arc<Frozen<K, Doc>>> {
+        self.current.read().clone()
+    }
+
+    /// Some when caller must refresh, only one refresh run at a time
+    pub fn begin_refresh(&self, force: bool) -> Option<Refresh<'_, K, Doc>> {
+        let expired = match self.current() {
+            None => true,
+            Some(frozen) => frozen.taken.elapsed() >= self.interval,
+        };
+
+        if (force || expired) && !self.refreshing.swap(true, Ordering::SeqCst) {
+            Some(Refresh { snapshot: self })
+        } else {
+            None
+        }
+    }
+}
+
+
+// running refresh, a dropped (cancelled) refresh let next reader retry
+pub(crate) struct Refresh<'a, K, Doc> {
+    snapshot: &'a ReadSnapshot<K, Doc>,
+}
+
+impl<K, Doc> Refresh<'_, K, Doc> {
+    pub fn publish(self, frozen: Frozen<K, Doc>) {
+        *self.snapshot.current.write() = Some(Arc::new(frozen));
+    }
+}
+
+impl<K, Doc> Drop for Refresh<'_, K, Doc> {
+    fn drop(&mut self) {
+        self.snapshot.refreshing.store(false, Ordering::SeqCst);
+    }
+}
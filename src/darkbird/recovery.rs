@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, time::{Duration, SystemTime}};
+
+// skew of a WAL from the future reported without Options::with_clock_skew
+pub(crate) const DEFAULT_SKEW_THRESHOLD: Duration = Duration::from_secs(5 * 60);
+
+
+
+/// What `Storage::open` does with a corrupted WAL record, set by `Options::with_recovery`.
+///
+/// a record is corrupted when it doesn't match its checksum, can't be decoded,
+/// or its length run past end of its page. a record torn by a crash while appended,
+/// at end of last page, is dropped in every mode and reported as `RecoveryReport::torn_tail`:
+/// `LogFile::open` trim it off last page before loader read WAL, so a WAL whose last bytes
+/// are cut open the same in Strict, SkipCorrupted and TruncateAtCorruption.
+/// modes differ only for a corrupted record inside a page or in a sealed page
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecoveryMode {
+    // open fails with page and offset of record
+    #[default]
+    Strict,
+
+    // record is logged and skipped, rest of page is read
+    SkipCorrupted,
+
+    // WAL is truncated at record, pages after its page removed, so appends follow last good record
+    TruncateAtCorruption,
+}
+
+
+/// What `Storage::open` does when newest WAL page was written at a wall time away from
+/// store clock by more than threshold, set by `Options::with_clock_skew`.
+///
+/// a WAL restored on another host keep wall times of clock that wrote it, read by ttl deadlines
+/// and timers: ahead of store clock they never fire, behind it they all fire at open.
+/// event retention age is measured on monotonic clock, skew doesn't reach it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClockSkewPolicy {
+    // deadlines compared with store clock as they are, skew only reported
+    #[default]
+    TrustClock,
+
+    // deadlines read from WAL, checkpoint and timers WAL moved by skew, as if store clock
+    // were the one that wrote them, then logged again so next open read them moved
+    TrustLog,
+
+    // open fails
+    Fail,
+}
+
+
+/// distance of newest WAL page from store clock at open, `RecoveryReport::clock_skew`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockSkew {
+    // WAL written after store clock's now, from the future
+    Ahead(Duration),
+
+    // WAL written before store clock's now, checked with `Options::with_clock_skew` only
+    Behind(Duration),
+}
+
+impl ClockSkew {
+    /// millis to subtract from a wall time of WAL to read it on store clock
+    pub(crate) fn offset_ms(&self) -> i64 {
+        match self {
+            ClockSkew::Ahead(skew) => skew.as_millis() as i64,
+            ClockSkew::Behind(skew) => -(skew.as_millis() as i64),
+        }
+    }
+}
+
+
+/// WAL replayed by `Storage::open`, read with `Storage::recovery`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    pub mode: RecoveryMode,
+
+    // records replayed
+    pub recovered: u64,
+
+    // corrupted records skipped, SkipCorrupted
+    pub skipped: u64,
+
+    // page and byte offset WAL was truncated at, TruncateAtCorruption
+    pub truncated_at: Option<(usize, u64)>,
+
+    // pages removed after truncated page
+    pub pages_removed: usize,
+
+    // bytes of a record torn at end of WAL, dropped
+    pub torn_tail: u64,
+
+    // newest WAL page away from store clock beyond threshold, see `ClockSkewPolicy`
+    pub clock_skew: Option<ClockSkew>,
+
+    // ttl deadlines and timers moved by ClockSkewPolicy::TrustLog
+    pub rebased: u64,
+}
+
+impl RecoveryReport {
+    pub(crate) fn new(mode: RecoveryMode) -> Self {
+        RecoveryReport { mode, ..Default::default() }
+    }
+}
+
+
+/// skew of newest page of WAL directory from now beyond threshold, None without pages.
+/// a WAL behind now is skewed only if `behind`, a store left closed look the same
+pub(crate) fn clock_skew(wal_dir: &str, now: SystemTime, threshold: Duration, behind: bool) -> Option<ClockSkew> {
+    let newest = fs::read_dir(wal_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_str().is_some_and(|name| name.starts_with("page-") && name.ends_with(".LOG")))
+        .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+        .max()?;
+
+    match newest.duration_since(now) {
+        Ok(ahead) if ahead > threshold => Some(ClockSkew::Ahead(ahead)),
+        Ok(_) => None,
+        Err(e) if behind && e.duration() > threshold => Some(ClockSkew::Behind(e.duration())),
+        Err(_) => None,
+    }
+}
+
+/// wall time in millis of WAL read on store clock
+#[inline]
+pub(crate) fn rebase(at: u64, offset_ms: i64) -> u64 {
+    match offset_ms >= 0 {
+        true => at.saturating_sub(offset_ms as u64),
+        false => at.saturating_add(offset_ms.unsigned_abs()),
+    }
+}
@@ -0,0 +1,213 @@
+use anymap::AnyMap;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    any::type_name,
+    collections::{hash_map::DefaultHasher, BTreeSet},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+use crate::{document::Document, Storage};
+
+use super::{transaction::Transaction, SessionResult};
+
+
+
+/// what happen to dependents when a referenced document removed by `remove_checked`,
+/// dependents of a Cascade dependent are checked too
+#[derive(Clone, Copy)]
+pub enum ReferenceAction {
+    // refuse remove while dependents exist
+    Restrict,
+
+    // remove dependents too
+    Cascade,
+}
+
+
+// (tag_of, dependent store)
+type Dependency<K> = (fn(&K) -> String, Box<dyn Referrer>);
+
+
+/// Foreign-key mapping from a dependent store to a referenced store,
+/// dependents are found by tag, so a dependent document must
+/// return the tag produced by `tag_of(referenced_key)` from `get_tags()`
+pub struct References<K, Doc> {
+    list: Vec<Dependency<K>>,
+    phantom: PhantomData<Doc>,
+}
+
+impl<K, Doc> References<K, Doc> {
+    pub(crate) fn new() -> Self {
+        References {
+            list: vec![],
+            phantom: PhantomData,
+        }
+    }
+
+    pub(crate) fn push(&mut self, tag_of: fn(&K) -> String, referrer: Box<dyn Referrer>) {
+        self.list.push((tag_of, referrer));
+    }
+
+    /// Plan removal of key: Restrict dependents return Err, Cascade dependents
+    /// are planned removed, checked through their own references first
+    pub(crate) fn plan(&self, datastores: &AnyMap, key: &K, plan: &mut Plan) -> Result<(), SessionResult> {
+        // first check all restricts, so nothing planned if one of them failed
+        for (tag_of, referrer) in self.list.iter() {
+            if let ReferenceAction::Restrict = referrer.action() {
+                let count = referrer.count(datastores, &tag_of(key), plan);
+                if count > 0 {
+                    return Err(SessionResult::ReferencedBy {
+                        store: referrer.store().to_owned(),
+                        count,
+                    });
+                }
+            }
+        }
+
+        for (tag_of, referrer) in self.list.iter() {
+            if let ReferenceAction::Cascade = referrer.action() {
+                referrer.cascade(datastores, &tag_of(key), plan)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// pause stores of dependents, and of their dependents, while transaction commit
+    pub(crate) fn pause(&self, datastores: &AnyMap, tx: &mut Transaction) {
+        for (_, referrer) in self.list.iter() {
+            referrer.pause(datastores, tx);
+        }
+    }
+}
+
+
+
+/// removals planned by `References::plan`, as transaction and as (store, key hash)
+/// to compare a plan with one made again
+pub(crate) struct Plan {
+    pub(crate) tx: Transaction,
+    planned: BTreeSet<(&'static str, u64)>,
+}
+
+impl Plan {
+    pub(crate) fn new() -> Self {
+        Plan {
+            tx: Transaction::new(),
+            planned: BTreeSet::new(),
+        }
+    }
+
+    /// same keys planned removed
+    pub(crate) fn same(&self, other: &Plan) -> bool {
+        self.planned == other.planned
+    }
+
+    fn entry<K: Hash>(store: &'static str, key: &K) -> (&'static str, u64) {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (store, hasher.finish())
+    }
+
+    fn is_planned<K: Hash>(&self, store: &'static str, key: &K) -> bool {
+        self.planned.contains(&Plan::entry(store, key))
+    }
+}
+
+
+
+pub(crate) trait Referrer {
+    fn store(&self) -> &str;
+
+    fn action(&self) -> ReferenceAction;
+
+    /// count dependents refer to tag, not already planned removed
+    fn count(&self, datastores: &AnyMap, tag: &str, plan: &Plan) -> usize;
+
+    /// plan removal of dependents refer to tag, and of what depend on them
+    fn cascade(&self, datastores: &AnyMap, tag: &str, plan: &mut Plan) -> Result<(), SessionResult>;
+
+    /// pause store, and stores of its own dependents
+    fn pause(&self, datastores: &AnyMap, tx: &mut Transaction);
+}
+
+
+
+pub(crate) struct Dependent<K, Doc> {
+    store: String,
+    action: ReferenceAction,
+    phantom: PhantomData<(K, Doc)>,
+}
+
+impl<K, Doc> Dependent<K, Doc> {
+    pub(crate) fn new(store: &str, action: ReferenceAction) -> Self {
+        Dependent {
+            store: store.to_owned(),
+            action,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<K, Doc> Referrer for Dependent<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    fn store(&self) -> &str {
+        &self.store
+    }
+
+    fn action(&self) -> ReferenceAction {
+        self.action
+    }
+
+    fn count(&self, datastores: &AnyMap, tag: &str, plan: &Plan) -> usize {
+        let store = type_name::<Storage<K, Doc>>();
+        match datastores.get::<Storage<K, Doc>>() {
+            Some(datastore) => datastore.tag_keys(tag).iter().filter(|key| !plan.is_planned(store, *key)).count(),
+            None => 0,
+        }
+    }
+
+    fn cascade(&self, datastores: &AnyMap, tag: &str, plan: &mut Plan) -> Result<(), SessionResult> {
+        let store = type_name::<Storage<K, Doc>>();
+        match datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => {
+                for key in datastore.tag_keys(tag) {
+                    // a cycle of references reach a key planned already
+                    if !plan.planned.insert(Plan::entry(store, &key)) {
+                        continue;
+                    }
+                    if let Some(refs) = datastores.get::<References<K, Doc>>() {
+                        refs.plan(datastores, &key, plan)?;
+                    }
+                    plan.tx.remove::<K, Doc>(key);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn pause(&self, datastores: &AnyMap, tx: &mut Transaction) {
+        if tx.is_paused::<K, Doc>() {
+            return;
+        }
+        tx.pause::<K, Doc>();
+        if let Some(refs) = datastores.get::<References<K, Doc>>() {
+            refs.pause(datastores, tx);
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use anymap::AnyMap;
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, hash::Hash, marker::PhantomData};
+use tokio::{sync::RwLockWriteGuard, time::{self, Duration, Instant}};
+
+use crate::{document::Document, Storage};
+
+use super::{
+    capabilities::Capabilities,
+    database::Database,
+    snapshot::{BARRIER_ATTEMPTS, MAX_PAUSE},
+    storage::CloseReport,
+    SessionResult,
+};
+
+
+
+/// datastores registered by Schema,
+/// AnyMap can't be iterated so each store registers how to reach itself
+pub(crate) struct Registry {
+    list: Vec<Box<dyn Registered>>,
+}
+
+impl Registry {
+    pub(crate) fn new() -> Self {
+        Registry { list: vec![] }
+    }
+
+    pub(crate) fn push(&mut self, entry: Box<dyn Registered>) {
+        self.list.push(entry);
+    }
+
+    /// close stores in registration order
+    pub(crate) async fn close_all(self, datastores: &mut AnyMap) -> Vec<(String, Result<CloseReport, SessionResult>)> {
+        let mut result = Vec::with_capacity(self.list.len());
+        for entry in self.list.iter() {
+            result.push((entry.store().to_owned(), entry.close(datastores).await));
+        }
+        result
+    }
+
+    /// (store name, type name) in registration order
+    pub(crate) fn roster(&self) -> Vec<(String, &'static str)> {
+        self.list
+            .iter()
+            .map(|entry| (entry.store().to_owned(), entry.type_name()))
+            .collect()
+    }
+
+    /// ping every store, first failure returned with its store name
+    pub(crate) async fn ping_all(&self, datastores: &AnyMap) -> Result<(), (String, SessionResult)> {
+        for entry in self.list.iter() {
+            if let Err(e) = entry.ping(datastores).await {
+                return Err((entry.store().to_owned(), e));
+            }
+        }
+        Ok(())
+    }
+
+    /// capabilities keyed by store type name
+    pub(crate) fn capabilities_all(&self, datastores: &AnyMap) -> BTreeMap<String, Capabilities> {
+        self.list
+            .iter()
+            .filter_map(|entry| Some((entry.type_name().to_owned(), entry.capabilities(datastores)?)))
+            .collect()
+    }
+
+    /// Pause writes of every store at once and resume them, return how long they stayed paused.
+    ///
+    /// stores are paused in registration order, if all aren't paused within `MAX_PAUSE`
+    /// the ones paused are resumed and barrier retried, Timeout after `BARRIER_ATTEMPTS`
+    pub(crate) async fn barrier(&self, datastores: &AnyMap) -> Result<Duration, SessionResult> {
+        for attempt in 0..BARRIER_ATTEMPTS {
+            let started = Instant::now();
+
+            let mut paused = Vec::with_capacity(self.list.len());
+            for entry in self.list.iter() {
+                let left = MAX_PAUSE.saturating_sub(started.elapsed());
+                match time::timeout(left, entry.pause_writes(datastores)).await {
+                    Ok(Some(guard)) => paused.push(guard),
+                    Ok(None) => {}
+                    Err(_) => break,
+                }
+            }
+
+            let complete = paused.len() == self.list.len();
+            drop(paused);
+
+            if complete {
+                return Ok(started.elapsed());
+            }
+
+            time::sleep(MAX_PAUSE * (attempt as u32 + 1)).await;
+        }
+
+        Err(SessionResult::Timeout)
+    }
+}
+
+
+
+#[async_trait(?Send)]
+pub(crate) trait Registered {
+    fn store(&self) -> &str;
+
+    fn type_name(&self) -> &'static str;
+
+    fn capabilities(&self, datastores: &AnyMap) -> Option<Capabilities>;
+
+    async fn ping(&self, datastores: &AnyMap) -> Result<(), SessionResult>;
+
+    /// None if store was taken out of datastores
+    async fn pause_writes<'a>(&self, datastores: &'a AnyMap) -> Option<RwLockWriteGuard<'a, ()>>;
+
+    /// take store out of datastores and close it
+    async fn close(&self, datastores: &mut AnyMap) -> Result<CloseReport, SessionResult>;
+}
+
+
+
+pub(crate) struct StoreEntry<K, Doc> {
+    store: String,
+    phantom: PhantomData<(K, Doc)>,
+}
+
+impl<K, Doc> StoreEntry<K, Doc> {
+    pub(crate) fn new(store: &str) -> Self {
+        StoreEntry {
+            store: store.to_owned(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<K, Doc> Registered for StoreEntry<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    fn store(&self) -> &str {
+        &self.store
+    }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Storage<K, Doc>>()
+    }
+
+    fn capabilities(&self, datastores: &AnyMap) -> Option<Capabilities> {
+        datastores.get::<Storage<K, Doc>>().map(|datastore| datastore.capabilities())
+    }
+
+    async fn ping(&self, datastores: &AnyMap) -> Result<(), SessionResult> {
+        match datastores.get::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => datastore.ping().await,
+        }
+    }
+
+    async fn pause_writes<'a>(&self, datastores: &'a AnyMap) -> Option<RwLockWriteGuard<'a, ()>> {
+        match datastores.get::<Storage<K, Doc>>() {
+            None => None,
+            Some(datastore) => Some(datastore.pause_writes().await),
+        }
+    }
+
+    async fn close(&self, datastores: &mut AnyMap) -> Result<CloseReport, SessionResult> {
+        match datastores.remove::<Storage<K, Doc>>() {
+            None => Err(SessionResult::DataStoreNotFound),
+            Some(datastore) => datastore.close().await,
+        }
+    }
+}
+
+
+
+
+/// Error of a `TypeRegistry` call
+#[derive(Debug)]
+pub enum TypeRegistryError {
+    // no store registered under name
+    UnknownStore(String),
+
+    // name registered twice
+    AlreadyRegistered(String),
+
+    // payload isn't a key or document of store, with serde_json reason
+    MalformedKey(String),
+    MalformedDoc(String),
+
+    // document of store can't be written as json (e.g. map with non string keys)
+    Encode(String),
+
+    Session(SessionResult),
+}
+
+impl From<SessionResult> for TypeRegistryError {
+    fn from(e: SessionResult) -> Self {
+        TypeRegistryError::Session(e)
+    }
+}
+
+
+/// Stores reachable by name with json keys and documents,
+/// for layers that can't be generic over user types (network, console, CLI).
+///
+/// each `register` capture how to decode and encode its types,
+/// calls then run the typed `Database` methods of store
+#[derive(Default)]
+pub struct TypeRegistry {
+    stores: BTreeMap<String, Box<dyn JsonStore>>,
+}
+
+impl TypeRegistry {
+    pub fn new() -> Self {
+        TypeRegistry::default()
+    }
+
+    /// reach `Storage<K, Doc>` by name
+    pub fn register<K, Doc>(&mut self, name: &str) -> Result<&mut Self, TypeRegistryError>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K: Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        if self.stores.contains_key(name) {
+            return Err(TypeRegistryError::AlreadyRegistered(name.to_owned()));
+        }
+
+        self.stores.insert(name.to_owned(), Box::new(JsonEntry::<K, Doc>(PhantomData)));
+        Ok(self)
+    }
+
+    /// names registered, in order
+    pub fn names(&self) -> Vec<&str> {
+        self.stores.keys().map(String::as_str).collect()
+    }
+
+    /// see `Database::insert`
+    pub async fn insert_json(&self, db: &Database, name: &str, key: &Value, doc: &Value) -> Result<(), TypeRegistryError> {
+        self.store(name)?.insert(db, key, doc).await
+    }
+
+    /// see `Database::remove`
+    pub async fn remove_json(&self, db: &Database, name: &str, key: &Value) -> Result<(), TypeRegistryError> {
+        self.store(name)?.remove(db, key).await
+    }
+
+    /// see `Database::lookup_owned`
+    pub fn lookup_json(&self, db: &Database, name: &str, key: &Value) -> Result<Option<Value>, TypeRegistryError> {
+        self.store(name)?.lookup(db, key)
+    }
+
+    /// see `Database::len`
+    pub fn len_of(&self, db: &Database, name: &str) -> Result<usize, TypeRegistryError> {
+        self.store(name)?.len(db)
+    }
+
+    fn store(&self, name: &str) -> Result<&dyn JsonStore, TypeRegistryError> {
+        match self.stores.get(name) {
+            Some(store) => Ok(store.as_ref()),
+            None => Err(TypeRegistryError::UnknownStore(name.to_owned())),
+        }
+    }
+}
+
+
+
+#[async_trait(?Send)]
+trait JsonStore {
+    async fn insert(&self, db: &Database, key: &Value, doc: &Value) -> Result<(), TypeRegistryError>;
+
+    async fn remove(&self, db: &Database, key: &Value) -> Result<(), TypeRegistryError>;
+
+    fn lookup(&self, db: &Database, key: &Value) -> Result<Option<Value>, TypeRegistryError>;
+
+    fn len(&self, db: &Database) -> Result<usize, TypeRegistryError>;
+}
+
+
+struct JsonEntry<K, Doc>(PhantomData<(K, Doc)>);
+
+impl<K: DeserializeOwned, Doc: DeserializeOwned> JsonEntry<K, Doc> {
+    fn key(key: &Value) -> Result<K, TypeRegistryError> {
+        K::deserialize(key).map_err(|e| TypeRegistryError::MalformedKey(e.to_string()))
+    }
+
+    fn doc(doc: &Value) -> Result<Doc, TypeRegistryError> {
+        Doc::deserialize(doc).map_err(|e| TypeRegistryError::MalformedDoc(e.to_string()))
+    }
+}
+
+#[async_trait(?Send)]
+impl<K, Doc> JsonStore for JsonEntry<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    async fn insert(&self, db: &Database, key: &Value, doc: &Value) -> Result<(), TypeRegistryError> {
+        let (key, doc) = (Self::key(key)?, Self::doc(doc)?);
+        Ok(db.insert::<K, Doc>(key, doc).await?)
+    }
+
+    async fn remove(&self, db: &Database, key: &Value) -> Result<(), TypeRegistryError> {
+        let key = Self::key(key)?;
+        Ok(db.remove::<K, Doc>(key).await?)
+    }
+
+    fn lookup(&self, db: &Database, key: &Value) -> Result<Option<Value>, TypeRegistryError> {
+        let key = Self::key(key)?;
+        match db.lookup_owned::<K, Doc>(&key)? {
+            Some(doc) => serde_json::to_value(doc).map(Some).map_err(|e| TypeRegistryError::Encode(e.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    fn len(&self, db: &Database) -> Result<usize, TypeRegistryError> {
+        Ok(db.len::<K, Doc>()?)
+    }
+}
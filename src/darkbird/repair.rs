@@ -0,0 +1,191 @@
+use dashmap::DashSet;
+use parking_lot::Mutex;
+use std::{
+    any::Any,
+    collections::HashMap,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+
+
+/// verdict of a `ReadRepair` hook on a document read
+pub enum Repair<Doc> {
+    Valid,
+
+    // written back in place of read document
+    Repaired(Doc),
+
+    // hidden from reads until key is written again
+    Quarantine,
+}
+
+
+/// Check documents as they are read (lookup, iter, ...) and fix or hide broken ones,
+/// registered by `Options::with_read_repair`.
+///
+/// never called while replaying WAL, nor by internal walks (compact, audit, export, snapshots).
+/// called with shard of key locked, must not touch the store
+pub trait ReadRepair<K, Doc>: Send + Sync {
+    fn check(&self, key: &K, doc: &Doc) -> Repair<Doc>;
+}
+
+impl<K, Doc, F> ReadRepair<K, Doc> for F
+where
+    F: Fn(&K, &Doc) -> Repair<Doc> + Send + Sync,
+{
+    fn check(&self, key: &K, doc: &Doc) -> Repair<Doc> {
+        self(key, doc)
+    }
+}
+
+
+// hook erased of K and Doc, so Options don't need to be generic
+#[derive(Clone)]
+pub(crate) struct ErasedRepair {
+    // Arc<dyn ReadRepair<K, Doc>>
+    hook: Arc<dyn Any + Send + Sync>,
+}
+
+impl ErasedRepair {
+    pub fn new<K: 'static, Doc: 'static>(hook: Arc<dyn ReadRepair<K, Doc>>) -> Self {
+        ErasedRepair { hook: Arc::new(hook) }
+    }
+
+    /// None if hook was registered for other key or document type
+    pub fn typed<K: 'static, Doc: 'static>(&self) -> Option<Arc<dyn ReadRepair<K, Doc>>> {
+        self.hook.downcast_ref::<Arc<dyn ReadRepair<K, Doc>>>().cloned()
+    }
+}
+
+
+/// counters of `Storage::repair_stats`, since open
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RepairStats {
+    // repaired documents written back
+    pub repaired: u64,
+
+    pub quarantined: u64,
+
+    // keys quarantined now
+    pub in_quarantine: usize,
+
+    // repairs and quarantine events waiting for `Storage::apply_repairs`
+    pub pending: usize,
+}
+
+
+pub(crate) enum Pending<Doc> {
+    // bincode of document read, write back skipped if key was written meanwhile
+    Repaired { read: Vec<u8>, doc: Doc },
+    Quarantined,
+}
+
+
+/// read repair state of a store
+pub(crate) struct Repairs<K, Doc> {
+    pub hook: Arc<dyn ReadRepair<K, Doc>>,
+
+    // one entry per key, reads of a key already pending don't queue again
+    pending: Mutex<HashMap<K, Pending<Doc>>>,
+
+    // checked without lock on every write
+    pending_len: AtomicUsize,
+
+    quarantine: DashSet<K>,
+
+    repaired: AtomicU64,
+    quarantined: AtomicU64,
+}
+
+impl<K, Doc> Repairs<K, Doc>
+where
+    K: Eq + Hash + Clone,
+{
+    pub fn new(hook: Arc<dyn ReadRepair<K, Doc>>) -> Self {
+        Repairs {
+            hook,
+            pending: Mutex::new(HashMap::new()),
+            pending_len: AtomicUsize::new(0),
+            quarantine: DashSet::new(),
+            repaired: AtomicU64::new(0),
+            quarantined: AtomicU64::new(0),
+        }
+    }
+
+    /// queue write back of key, false if key already pending
+    pub fn queue(&self, key: &K, pending: Pending<Doc>) -> bool {
+        let mut queue = self.pending.lock();
+        if queue.contains_key(key) {
+            return false;
+        }
+
+        queue.insert(key.clone(), pending);
+        self.pending_len.store(queue.len(), Ordering::Relaxed);
+        true
+    }
+
+    pub fn take(&self) -> Vec<(K, Pending<Doc>)> {
+        let mut queue = self.pending.lock();
+        self.pending_len.store(0, Ordering::Relaxed);
+        queue.drain().collect()
+    }
+
+    #[inline]
+    pub fn has_pending(&self) -> bool {
+        self.pending_len.load(Ordering::Relaxed) > 0
+    }
+
+    /// hide key and queue its event
+    pub fn quarantine(&self, key: &K) {
+        if self.hide(key) {
+            self.queue(key, Pending::Quarantined);
+        }
+    }
+
+    /// hide key, false if already hidden
+    pub fn hide(&self, key: &K) -> bool {
+        let hidden = self.quarantine.insert(key.clone());
+        if hidden {
+            self.quarantined.fetch_add(1, Ordering::Relaxed);
+        }
+        hidden
+    }
+
+    #[inline]
+    pub fn is_quarantined(&self, key: &K) -> bool {
+        !self.quarantine.is_empty() && self.quarantine.contains(key)
+    }
+
+    /// key written, its new document is checked on next read
+    #[inline]
+    pub fn release(&self, key: &K) {
+        if !self.quarantine.is_empty() {
+            self.quarantine.remove(key);
+        }
+    }
+
+    pub fn quarantined_keys(&self) -> Vec<K> {
+        self.quarantine.iter().map(|k| k.key().clone()).collect()
+    }
+
+    pub fn in_quarantine(&self) -> usize {
+        self.quarantine.len()
+    }
+
+    pub fn repaired(&self) {
+        self.repaired.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> RepairStats {
+        RepairStats {
+            repaired: self.repaired.load(Ordering::Relaxed),
+            quarantined: self.quarantined.load(Ordering::Relaxed),
+            in_quarantine: self.quarantine.len(),
+            pending: self.pending_len.load(Ordering::Relaxed),
+        }
+    }
+}
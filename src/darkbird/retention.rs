@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::clock::Clock;
+
+
+
+/// bounds of events kept by reporter for `Storage::subscribe_from_seq`,
+/// set by `Options::with_event_retention`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EventRetention {
+    pub max_events: usize,
+    pub max_age: Duration,
+}
+
+
+/// recent msgs with their sequence, one copy of each shared by every replay.
+///
+/// read by position rather than borrowed, so router future hold no reference
+/// to a msg across a send and msgs needn't be Sync
+pub(crate) struct RetentionBuffer<Msg> {
+    bounds: EventRetention,
+
+    // kinds of msg given a sequence and kept
+    retains: fn(&Msg) -> bool,
+
+    // sequence of last retained msg, 0 before first of run, shared with `EventSeq`
+    seq: Arc<AtomicU64>,
+
+    // store clock, msgs are aged by it
+    clock: Arc<dyn Clock>,
+
+    msgs: VecDeque<(u64, SystemTime, Msg)>,
+}
+
+impl<Msg> RetentionBuffer<Msg> {
+    /// first msg retained take sequence after `seq`
+    pub fn new(bounds: EventRetention, retains: fn(&Msg) -> bool, seq: Arc<AtomicU64>, clock: Arc<dyn Clock>) -> Self {
+        RetentionBuffer {
+            bounds,
+            retains,
+            seq,
+            clock,
+            msgs: VecDeque::new(),
+        }
+    }
+
+    /// keep msg under next sequence, None when its kind isn't retained
+    pub fn push(&mut self, msg: &Msg) -> Option<u64>
+    where
+        Msg: Clone,
+    {
+        if !(self.retains)(msg) {
+            return None;
+        }
+
+        let seq = self.seq.fetch_add(1, Ordering::SeqCst) + 1;
+        self.msgs.push_back((seq, self.clock.now(), msg.clone()));
+        self.evict();
+
+        Some(seq)
+    }
+
+    /// sequence of first msg after last_seen, or Err(oldest sequence still kept)
+    /// when some were evicted or last_seen is ahead of buffer
+    pub fn since(&mut self, last_seen: u64) -> Result<u64, u64> {
+        self.evict();
+
+        let seq = self.seq.load(Ordering::SeqCst);
+        let oldest = self.msgs.front().map_or(seq + 1, |(seq, _, _)| *seq);
+        if last_seen > seq || last_seen + 1 < oldest {
+            return Err(oldest);
+        }
+
+        Ok(last_seen + 1)
+    }
+
+    /// copy of msg of seq, None once evicted or not yet dispatched
+    pub fn get(&self, seq: u64) -> Option<Msg>
+    where
+        Msg: Clone,
+    {
+        let oldest = self.msgs.front()?.0;
+        let (_, _, msg) = self.msgs.get(seq.checked_sub(oldest)? as usize)?;
+        Some(msg.clone())
+    }
+
+    // oldest first, past max_events or older than max_age.
+    // a msg stamped after now, clock stepped back, is of age zero
+    fn evict(&mut self) {
+        while self.msgs.len() > self.bounds.max_events {
+            self.msgs.pop_front();
+        }
+
+        let now = self.clock.now();
+        while self.msgs.front().is_some_and(|(_, at, _)| now.duration_since(*at).unwrap_or_default() > self.bounds.max_age) {
+            self.msgs.pop_front();
+        }
+    }
+}
+
+
+
+#[derive(Serialize, Deserialize)]
+struct SeqFile {
+    run: u64,
+    seq: u64,
+
+    // written by close, every msg of run got its sequence before
+    closed: bool,
+}
+
+/// Sequence of retained events, kept across reopen of a disk store closed by `Storage::close`
+/// in `<path>/<name>.events`.
+///
+/// a run is numbering of one store from a fresh start: on first open, after a drop without close
+/// or a crash, whose last sequences are unknown, a new run start at 0.
+/// RamCopies store start a new run on each open
+pub(crate) struct EventSeq {
+    run: u64,
+    seq: Arc<AtomicU64>,
+    file: Option<String>,
+}
+
+impl EventSeq {
+    pub fn open(file: Option<String>) -> Self {
+        let closed = file
+            .as_ref()
+            .and_then(|file| fs::read(file).ok())
+            .and_then(|bytes| serde_json::from_slice::<SeqFile>(&bytes).ok())
+            .filter(|stored| stored.closed);
+
+        let (run, seq) = match closed {
+            Some(stored) => (stored.run, stored.seq),
+            None => (new_run(), 0),
+        };
+
+        let event_seq = EventSeq { run, seq: Arc::new(AtomicU64::new(seq)), file };
+
+        // not closed until close say so
+        event_seq.write(false);
+        event_seq
+    }
+
+    /// counter given to reporter retention
+    pub fn counter(&self) -> Arc<AtomicU64> {
+        self.seq.clone()
+    }
+
+    /// run and sequence of last retained event
+    pub fn position(&self) -> (u64, u64) {
+        (self.run, self.seq.load(Ordering::SeqCst))
+    }
+
+    /// record sequence once reporter is stopped, so next open continue run
+    pub fn close(&self) {
+        self.write(true);
+    }
+
+    fn write(&self, closed: bool) {
+        if let Some(file) = &self.file {
+            let stored = SeqFile { run: self.run, seq: self.seq.load(Ordering::SeqCst), closed };
+            if let Err(e) = fs::write(file, serde_json::to_vec(&stored).unwrap()) {
+                eprintln!("darkbird: {}: {}", file, e);
+            }
+        }
+    }
+}
+
+// distinct from runs of same store before, wall time isn't trusted alone
+fn new_run() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+    nanos ^ (u64::from(std::process::id()) << 32) ^ NEXT.fetch_add(1, Ordering::Relaxed)
+}
@@ -1,9 +1,16 @@
 use crate::darkbird::{SessionResult, TIMEOUT, Status};
+use std::time::Duration;
 use tokio::sync::mpsc::Sender;
-use tokio::sync::mpsc;
+use tokio::sync::oneshot;
 use tokio::sync::mpsc::error::SendTimeoutError;
+use tokio::time::Instant;
 
 use crate::darkbird::WorkerState;
+use crate::darkbird::lanes::{self, LaneSender};
+use crate::darkbird::filter::{EventFilter, EventMeta};
+use crate::darkbird::retention::{EventRetention, RetentionBuffer};
+use crate::darkbird::clock::Clock;
+use std::sync::{atomic::{AtomicU64, AtomicUsize, Ordering}, Arc};
 
 
 /// In some cases it is useful to distribute messages of the same type over a set of channels, 
@@ -77,8 +84,121 @@ pub struct DestinationDown<Msg>(Msg);
 
 
 pub enum Request<Msg> {
-    Register(Sender<Msg>),
-    Dispatch(Msg)
+    Register(Sender<Msg>, Option<Filter<Msg>>, oneshot::Sender<SubscriptionId>),
+
+    // answered true if subscriber was registered
+    Unregister(SubscriptionId, oneshot::Sender<bool>),
+
+    // metadata evaluated by filters, None for events filters don't apply to
+    Dispatch(Msg, Option<EventMeta>),
+    Report(oneshot::Sender<Vec<SubscriberInfo>>),
+
+    // sequenced subscriber, sent retained msgs after last seen sequence then live ones
+    Resume(Sender<(u64, Msg)>, u64, oneshot::Sender<Result<SubscriptionId, SessionResult>>),
+    Stop(oneshot::Sender<()>)
+}
+
+
+/// filter of a subscriber, msgs not passing aren't sent to its channel
+pub enum Filter<Msg> {
+    // evaluated on metadata of msg, msgs without metadata pass
+    Meta(EventFilter),
+
+    // evaluated on msg, named in SubscriberInfo
+    Predicate(&'static str, Arc<dyn Fn(&Msg) -> bool + Send + Sync>),
+}
+
+impl<Msg> Filter<Msg> {
+    fn pass(&self, msg: &Msg, meta: Option<&EventMeta>) -> bool {
+        match self {
+            Filter::Meta(filter) => meta.is_none_or(|meta| filter.matches(meta)),
+            Filter::Predicate(_, predicate) => predicate(msg),
+        }
+    }
+}
+
+
+/// id assigned to subscriber when registered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(pub usize);
+
+
+/// delivery metrics of a subscriber
+#[derive(Clone, Debug)]
+pub struct SubscriberInfo {
+    pub id: SubscriptionId,
+
+    // total events sent to subscriber
+    pub sent: u64,
+
+    // events waiting in subscriber channel
+    pub occupancy: usize,
+
+    // channel capacity observed when registered
+    pub capacity: usize,
+
+    // last successful send
+    pub last_send: Option<Instant>,
+
+    // cumulative time dispatch waited on subscriber channel
+    pub blocked: Duration,
+
+    // events filtered out before send
+    pub skipped: u64,
+
+    // filter registered by `subscribe_matching`
+    pub filter: Option<EventFilter>,
+
+    // filter registered by `subscribe_filtered`, InsertsOnly, RemovesOnly or Custom
+    pub predicate: Option<&'static str>,
+}
+
+
+struct Metrics<Msg> {
+    id: SubscriptionId,
+    sent: u64,
+    capacity: usize,
+    last_send: Option<Instant>,
+    blocked: Duration,
+    lagging: bool,
+    skipped: u64,
+    filter: Option<Filter<Msg>>,
+}
+
+impl<Msg> Metrics<Msg> {
+    fn new(id: usize, capacity: usize, filter: Option<Filter<Msg>>) -> Self {
+        Metrics {
+            id: SubscriptionId(id),
+            sent: 0,
+            capacity,
+            last_send: None,
+            blocked: Duration::ZERO,
+            lagging: false,
+            skipped: 0,
+            filter,
+        }
+    }
+
+    // available: free slots of subscriber channel
+    fn info(&self, available: usize) -> SubscriberInfo {
+        SubscriberInfo {
+            id: self.id,
+            sent: self.sent,
+            occupancy: self.capacity.saturating_sub(available),
+            capacity: self.capacity,
+            last_send: self.last_send,
+            blocked: self.blocked,
+            skipped: self.skipped,
+            filter: match &self.filter {
+                Some(Filter::Meta(filter)) => Some(filter.clone()),
+                _ => None,
+            },
+            predicate: match &self.filter {
+                Some(Filter::Predicate(name, _)) => Some(name),
+                _ => None,
+            },
+        }
+    }
 }
 
 
@@ -89,10 +209,35 @@ pub enum RouterType {
 }
 
 
+type LagAlert<Msg> = fn(SubscriberInfo) -> Msg;
+
+type DropAlert<Msg> = fn(SubscriptionId) -> Msg;
+
+// subscriber sent msgs with their sequence, and its metrics
+type Sequenced<Msg> = (Sender<(u64, Msg)>, Metrics<Msg>);
+
+
 pub struct Router<Msg> {
     c: usize,
     channels: Vec<Sender<Msg>>,
-    router_type: RouterType
+    metrics: Vec<Metrics<Msg>>,
+    next_id: usize,
+    router_type: RouterType,
+
+    // total dispatch lanes
+    lanes: usize,
+
+    // (threshold, alert) when a subscriber occupancy exceed threshold, alert broadcasted
+    on_lag: Option<(usize, LagAlert<Msg>)>,
+
+    // broadcasted when a subscriber leave, unsubscribed or its receiver dropped
+    on_drop: Option<DropAlert<Msg>>,
+
+    // recent msgs replayed to sequenced subscribers, see `with_retention`
+    retention: Option<RetentionBuffer<Msg>>,
+
+    // subscribers registered by `Session::resume`, sent retained msgs only
+    sequenced: Vec<Sequenced<Msg>>,
 }
 
 impl<Msg> Router<Msg> 
@@ -108,25 +253,86 @@ where
             return Err(Status::SendersRepetive);
         }
 
+        let metrics = channels
+            .iter()
+            .enumerate()
+            .map(|(id, sender)| Metrics::new(id, sender.capacity(), None))
+            .collect();
+
         Ok(Router { 
             c: 0, 
+            next_id: channels.len(),
             channels,
-            router_type: RouterType::Broadcast
+            metrics,
+            router_type: RouterType::Broadcast,
+            lanes: 1,
+            on_lag: None,
+            on_drop: None,
+            retention: None,
+            sequenced: vec![],
         })
     }
 
 
+    /// msgs sent by `dispatch_keyed` distributed over lanes,
+    /// lanes drained round robin so a burst on one lane can't monopolize router
+    pub fn with_lanes(mut self, lanes: usize) -> Self {
+        self.lanes = lanes;
+        self
+    }
+
+
+    /// broadcast `alert(info)` when a subscriber occupancy exceed threshold
+    pub fn with_lag_alert(mut self, threshold: usize, alert: LagAlert<Msg>) -> Self {
+        self.on_lag = Some((threshold, alert));
+        self
+    }
+
+
+    /// broadcast `alert(id)` when a subscriber leave: unregistered,
+    /// or found closed while dispatching, then removed
+    pub fn with_drop_alert(mut self, alert: DropAlert<Msg>) -> Self {
+        self.on_drop = Some(alert);
+        self
+    }
+
+
+    /// number msgs retains let through after seq and keep recent ones within bounds, aged by clock,
+    /// so `Session::resume` can replay what a subscriber missed
+    pub fn with_retention(mut self, bounds: EventRetention, retains: fn(&Msg) -> bool, seq: Arc<AtomicU64>, clock: Arc<dyn Clock>) -> Self {
+        self.retention = Some(RetentionBuffer::new(bounds, retains, seq, clock));
+        self
+    }
+
+
     pub fn run_service(mut self) -> Session<Msg> {
 
-        let (sx, mut rx) = mpsc::channel(30);
-        
-        let session = Session::new(sx);
+        let (sx, mut rx) = lanes::channel(self.lanes, 30);
+
+        let subscribers = Arc::new(AtomicUsize::new(self.subscribers()));
+        let session = Session::new(sx, subscribers.clone());
 
         tokio::spawn(async move {
             loop {
-                let res = rx.recv().await;
-                if let WorkerState::Disconnected = self.handle_recv(res).await {
-                    return ();
+                match rx.recv().await {
+                    Some(Request::Stop(dst)) => {
+                        // msgs queued before stop still dispatched
+                        while let Ok(req) = rx.try_recv() {
+                            let _ = self.handle_recv(Some(req)).await;
+                        }
+
+                        // drop subscribers senders, then reply
+                        subscribers.store(0, Ordering::Relaxed);
+                        drop(self);
+                        let _ = dst.send(());
+                        return;
+                    }
+                    res => {
+                        if let WorkerState::Disconnected = self.handle_recv(res).await {
+                            return;
+                        }
+                        subscribers.store(self.subscribers(), Ordering::Relaxed);
+                    }
                 }
             }
         });
@@ -134,24 +340,51 @@ where
         return session
     }
 
+    fn subscribers(&self) -> usize {
+        self.channels.len() + self.sequenced.len()
+    }
+
+
     async fn handle_recv(&mut self, res: Option<Request<Msg>>) -> WorkerState {
         match res {
             Some(req) => {
                 match req  {
-                    Request::Register(sender) => {
+                    Request::Register(sender, filter, dst) => {
                         match self.check(&sender) {
                             Ok(_) => {
+                                let id = SubscriptionId(self.next_id);
+                                self.metrics.push(Metrics::new(self.next_id, sender.capacity(), filter));
+                                self.next_id += 1;
                                 self.channels.push(sender);
+                                let _ = dst.send(id);
                                 WorkerState::Continue
                             }
-                            Err(_) => {
+                            Err(index) => {
+                                // already registered
+                                let _ = dst.send(self.metrics[index].id);
                                 return WorkerState::Continue
                             }
                         }
                         
                     }
-                    Request::Dispatch(msg) => {
-                        let _ = self.dispatch(msg).await;
+                    Request::Unregister(id, dst) => {
+                        let _ = dst.send(self.unregister(id));
+                        WorkerState::Continue
+                    }
+                    Request::Dispatch(msg, meta) => {
+                        let _ = self.dispatch(msg, meta).await;
+                        WorkerState::Continue
+                    }
+                    Request::Report(dst) => {
+                        let _ = dst.send(self.report());
+                        WorkerState::Continue
+                    }
+                    Request::Resume(sender, last_seen, dst) => {
+                        self.resume(sender, last_seen, dst).await;
+                        WorkerState::Continue
+                    }
+                    Request::Stop(_) => {
+                        // router already stopping
                         WorkerState::Continue
                     }
                 }
@@ -162,26 +395,180 @@ where
     
 
     #[inline]
-    async fn dispatch(&mut self, msg: Msg) -> Result<(), DestinationDown<Msg>> {
+    async fn dispatch(&mut self, msg: Msg, meta: Option<EventMeta>) -> Result<(), DestinationDown<Msg>> {
+
+        // retained without subscribers too, for those coming back
+        if let Some(seq) = self.retention.as_mut().and_then(|retention| retention.push(&msg)) {
+            self.send_sequenced(seq, msg.clone()).await;
+        }
 
         if self.channels.len() == 0 {
             return Ok(())
         }
 
-        self.broadcast(msg).await;
+        self.broadcast(msg, meta).await;
         Ok(()) 
     }
 
+
+    // replay retained msgs after last_seen, then register sender for live ones.
+    // answered before replay, so caller can read its receiver meanwhile
+    async fn resume(&mut self, sender: Sender<(u64, Msg)>, last_seen: u64, dst: oneshot::Sender<Result<SubscriptionId, SessionResult>>) {
+        let since = match self.retention.as_mut() {
+            None => Err(SessionResult::UnImplement),
+            Some(retention) => retention
+                .since(last_seen)
+                .map_err(|oldest_available| SessionResult::GapTooLarge { oldest_available }),
+        };
+
+        let mut seq = match since {
+            Ok(seq) => seq,
+            Err(e) => {
+                let _ = dst.send(Err(e));
+                return;
+            }
+        };
+        let mut metrics = Metrics::new(self.next_id, sender.capacity(), None);
+        self.next_id += 1;
+        let _ = dst.send(Ok(metrics.id));
+
+        // nothing is dispatched meanwhile, so no msg of replay is evicted
+        while let Some(msg) = self.retention.as_ref().and_then(|retention| retention.get(seq)) {
+            let started = Instant::now();
+            if sender.send((seq, msg)).await.is_err() {
+                self.alert_drop(metrics.id);
+                return;
+            }
+            metrics.blocked += started.elapsed();
+            metrics.sent += 1;
+            metrics.last_send = Some(Instant::now());
+            seq += 1;
+        }
+
+        self.sequenced.push((sender, metrics));
+    }
+
+
+    // closed sequenced subscribers are removed, a resumed consumer register a new one
+    async fn send_sequenced(&mut self, seq: u64, msg: Msg) {
+        let mut dead = vec![];
+
+        for (index, (sender, metrics)) in self.sequenced.iter_mut().enumerate() {
+            let started = Instant::now();
+            let res = sender.send((seq, msg.clone())).await;
+
+            metrics.blocked += started.elapsed();
+            match res {
+                Ok(_) => {
+                    metrics.sent += 1;
+                    metrics.last_send = Some(Instant::now());
+                }
+                Err(_) => dead.push(index),
+            }
+        }
+
+        for index in dead.into_iter().rev() {
+            let (_, metrics) = self.sequenced.remove(index);
+            self.alert_drop(metrics.id);
+        }
+    }
+
     
     #[inline]
-    async fn broadcast(&mut self, msg: Msg) {        
-        for index in 0..=(self.channels.len() - 2) {
-            let msg = msg.clone();
-            let _ = self.channels[index].send(msg).await;
+    async fn broadcast(&mut self, msg: Msg, meta: Option<EventMeta>) {        
+        let mut lagging = vec![];
+
+        // subscribers whose receiver is dropped, removed after dispatch
+        let mut dead = vec![];
+
+        for index in 0..self.channels.len() {
+            if let Some(filter) = &self.metrics[index].filter {
+                if !filter.pass(&msg, meta.as_ref()) {
+                    if self.channels[index].is_closed() {
+                        dead.push(index);
+                    }
+                    self.metrics[index].skipped += 1;
+                    continue;
+                }
+            }
+
+            let started = Instant::now();
+            let res = self.channels[index].send(msg.clone()).await;
+
+            let metrics = &mut self.metrics[index];
+            metrics.blocked += started.elapsed();
+            if res.is_err() {
+                dead.push(index);
+                continue;
+            }
+            metrics.sent += 1;
+            metrics.last_send = Some(Instant::now());
+
+            if let Some((threshold, _)) = self.on_lag {
+                let info = metrics.info(self.channels[index].capacity());
+                let is_lagging = info.occupancy > threshold;
+
+                // alert just when crossing threshold
+                if is_lagging && !metrics.lagging {
+                    lagging.push(info);
+                }
+                metrics.lagging = is_lagging;
+            }
+        }
+
+        for index in dead.into_iter().rev() {
+            self.channels.remove(index);
+            let metrics = self.metrics.remove(index);
+            self.alert_drop(metrics.id);
         }
 
-        let _ = self.channels[self.channels.len() - 1].send(msg).await;
+        if let Some((_, alert)) = self.on_lag {
+            for info in lagging {
+                let msg = alert(info);
 
+                // don't block on the lagging subscriber
+                for chan in self.channels.iter() {
+                    let _ = chan.try_send(msg.clone());
+                }
+            }
+        }
+    }
+
+
+    // remove subscriber of id, true if it was registered
+    fn unregister(&mut self, id: SubscriptionId) -> bool {
+        if let Some(index) = self.metrics.iter().position(|metrics| metrics.id == id) {
+            self.channels.remove(index);
+            self.metrics.remove(index);
+        } else if let Some(index) = self.sequenced.iter().position(|(_, metrics)| metrics.id == id) {
+            self.sequenced.remove(index);
+        } else {
+            return false;
+        }
+
+        self.alert_drop(id);
+        true
+    }
+
+
+    // sent without waiting, like lag alerts
+    fn alert_drop(&self, id: SubscriptionId) {
+        if let Some(alert) = self.on_drop {
+            let msg = alert(id);
+            for chan in self.channels.iter() {
+                let _ = chan.try_send(msg.clone());
+            }
+        }
+    }
+
+
+    fn report(&self) -> Vec<SubscriberInfo> {
+        self.channels
+            .iter()
+            .zip(self.metrics.iter())
+            .map(|(sender, metrics)| metrics.info(sender.capacity()))
+            .chain(self.sequenced.iter().map(|(sender, metrics)| metrics.info(sender.capacity())))
+            .collect()
     }
 
 
@@ -217,13 +604,13 @@ where
         Ok(())
     }
 
-    /// Check channel to registered before
-    fn check(&self, chan: &Sender<Msg>) -> Result<(), ()> {
-        for (_index, dst) in self.channels.iter().enumerate() {
+    /// Check channel to registered before, Err(index) of it if so
+    fn check(&self, chan: &Sender<Msg>) -> Result<(), usize> {
+        for (index, dst) in self.channels.iter().enumerate() {
             
             // if channel was same
             if chan.same_channel(dst) {
-                return Err(())
+                return Err(index)
             }
 
         }
@@ -238,25 +625,59 @@ where
 
 
 pub struct Session<Msg> {
-    sender: mpsc::Sender<Request<Msg>>
+    sender: LaneSender<Request<Msg>>,
+
+    // registered subscribers, updated by router after each request
+    subscribers: Arc<AtomicUsize>,
+}
+
+impl<Msg> Clone for Session<Msg> {
+    fn clone(&self) -> Self {
+        Session {
+            sender: self.sender.clone(),
+            subscribers: self.subscribers.clone(),
+        }
+    }
 }
 
 impl<Msg> Session<Msg> 
 where
     Msg: Send + 'static
 {
-    fn new(sender: mpsc::Sender<Request<Msg>>) -> Self {
+    fn new(sender: LaneSender<Request<Msg>>, subscribers: Arc<AtomicUsize>) -> Self {
         Session { 
-            sender 
+            sender,
+            subscribers,
         }
     }
 
 
-    /// register new channel to router
-    pub async fn register(&self, sender: Sender<Msg>) -> Result<(), SessionResult> {
-        let res = self.sender.send_timeout(Request::Register(sender), TIMEOUT).await;
+    /// requests waiting for router, dispatches included
+    pub fn queued(&self) -> usize {
+        self.sender.queued()
+    }
+
+
+    /// subscribers registered when router handled its last request
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.load(Ordering::Relaxed)
+    }
+
+
+    /// register new channel to router, id of its subscription
+    pub async fn register(&self, sender: Sender<Msg>) -> Result<SubscriptionId, SessionResult> {
+        self.register_filtered(sender, None).await
+    }
+
+
+    /// register new channel to router, sent just msgs passing filter.
+    /// a channel already registered keep its filter and id
+    pub async fn register_filtered(&self, sender: Sender<Msg>, filter: Option<Filter<Msg>>) -> Result<SubscriptionId, SessionResult> {
+        let (ask, resp) = oneshot::channel();
+
+        let res = self.sender.send_timeout(0, Request::Register(sender, filter, ask), TIMEOUT).await;
         match res {
-            Ok(_) => Ok(()),
+            Ok(_) => resp.await.map_err(|_| SessionResult::NoResponse),
             Err(e) => {
                 match e {
                     SendTimeoutError::Timeout(_) => Err(SessionResult::Timeout),
@@ -267,9 +688,107 @@ where
     }   
 
 
+    /// remove subscriber of id, false if it wasn't registered
+    /// (unregistered before, or removed once its receiver was dropped)
+    pub async fn unregister(&self, id: SubscriptionId) -> Result<bool, SessionResult> {
+        let (ask, resp) = oneshot::channel();
+
+        if let Err(e) = self.sender.send_timeout(0, Request::Unregister(id, ask), TIMEOUT).await {
+            return match e {
+                SendTimeoutError::Timeout(_) => Err(SessionResult::Timeout),
+                SendTimeoutError::Closed(_) => Err(SessionResult::Closed),
+            }
+        }
+
+        resp.await.map_err(|_| SessionResult::NoResponse)
+    }
+
+
+    /// delivery metrics of registered subscribers
+    pub async fn report(&self) -> Result<Vec<SubscriberInfo>, SessionResult> {
+        let (ask, resp) = oneshot::channel();
+
+        if let Err(e) = self.sender.send_timeout(0, Request::Report(ask), TIMEOUT).await {
+            return match e {
+                SendTimeoutError::Timeout(_) => Err(SessionResult::Timeout),
+                SendTimeoutError::Closed(_) => Err(SessionResult::Closed),
+            }
+        }
+
+        resp.await.map_err(|_| SessionResult::NoResponse)
+    }
+
+
+    /// register sender for msgs kept by `Router::with_retention`, each with its sequence:
+    /// retained msgs after last_seen first, then new ones as dispatched.
+    /// `SessionResult::GapTooLarge` when some after last_seen were already evicted.
+    ///
+    /// returned before replay, router then wait on sender like on any subscriber
+    pub async fn resume(&self, sender: Sender<(u64, Msg)>, last_seen: u64) -> Result<SubscriptionId, SessionResult> {
+        let (ask, resp) = oneshot::channel();
+
+        if let Err(e) = self.sender.send_timeout(0, Request::Resume(sender, last_seen, ask), TIMEOUT).await {
+            return match e {
+                SendTimeoutError::Timeout(_) => Err(SessionResult::Timeout),
+                SendTimeoutError::Closed(_) => Err(SessionResult::Closed),
+            }
+        }
+
+        resp.await.map_err(|_| SessionResult::NoResponse)?
+    }
+
+
+    /// wait until msgs queued before call, in every lane, are dispatched.
+    /// a report request per lane, answered once lane's earlier msgs are sent to subscribers
+    pub(crate) async fn flush(&self) -> Result<(), SessionResult> {
+        for lane in 0..self.sender.lanes() {
+            let (ask, resp) = oneshot::channel();
+
+            if let Err(e) = self.sender.send_timeout(lane, Request::Report(ask), TIMEOUT).await {
+                return match e {
+                    SendTimeoutError::Timeout(_) => Err(SessionResult::Timeout),
+                    SendTimeoutError::Closed(_) => Err(SessionResult::Closed),
+                }
+            }
+
+            resp.await.map_err(|_| SessionResult::NoResponse)?;
+        }
+
+        Ok(())
+    }
+
+
+    /// dispatch queued msgs and stop router,
+    /// subscribers channels closed when returned
+    pub async fn stop(&self) -> Result<(), SessionResult> {
+        let (ask, resp) = oneshot::channel();
+
+        if let Err(e) = self.sender.send_timeout(0, Request::Stop(ask), TIMEOUT).await {
+            return match e {
+                SendTimeoutError::Timeout(_) => Err(SessionResult::Timeout),
+                SendTimeoutError::Closed(_) => Err(SessionResult::Closed),
+            }
+        }
+
+        resp.await.map_err(|_| SessionResult::NoResponse)
+    }
+
+
     /// dispatch msg by router
     pub async fn dispatch(&self, msg: Msg) -> Result<(), SessionResult> {
-        let res = self.sender.send_timeout(Request::Dispatch(msg), TIMEOUT).await;
+        self.dispatch_keyed(0, msg).await
+    }
+
+
+    /// dispatch msg by router through lane, msgs of same lane keep order
+    pub async fn dispatch_keyed(&self, lane: usize, msg: Msg) -> Result<(), SessionResult> {
+        self.dispatch_meta(lane, msg, None).await
+    }
+
+
+    /// dispatch msg through lane with metadata evaluated by subscribers filters
+    pub(crate) async fn dispatch_meta(&self, lane: usize, msg: Msg, meta: Option<EventMeta>) -> Result<(), SessionResult> {
+        let res = self.sender.send_timeout(lane, Request::Dispatch(msg, meta), TIMEOUT).await;
         match res {
             Ok(_) => Ok(()),
             Err(e) => {
@@ -1,16 +1,32 @@
 use anymap::AnyMap;
-use std::{hash::Hash, collections::HashSet};
+use std::{any::{type_name, TypeId}, hash::Hash, collections::HashSet, sync::Arc};
 use serde::{Serialize, de::DeserializeOwned};
+use tokio::{sync::Semaphore, task::JoinHandle};
 
 use crate::{Options, document::Document, Storage};
 
-use super::{database::Database, storage_redis::RedisStorage};
+use super::{config::{StoreConfig, ConfigError}, database::Database, storage_redis::RedisStorage, storage_bytes::{BytesStorage, Extractors}, reference::{References, Dependent, ReferenceAction}, registry::{Registry, StoreEntry}, startup::{IoBudget, OpenProgress}};
 
 
 
+// insert opened datastore into AnyMap
+type Inserter = Box<dyn FnOnce(&mut AnyMap) + Send>;
+
+// spawn open task, limited by semaphore
+type PendingOpen = Box<dyn FnOnce(Arc<Semaphore>) -> JoinHandle<Result<Inserter, String>>>;
+
+
 pub struct Schema {
     datastores: AnyMap,
-    names: HashSet<String>
+    names: HashSet<String>,
+
+    // datastores added by add_datastore, opened by open_all
+    pending: Vec<PendingOpen>,
+    pending_types: HashSet<TypeId>,
+
+    open_concurrency: usize,
+    io_budget: Option<Arc<IoBudget>>,
+    progress: OpenProgress
 }
 
 impl Schema {
@@ -18,12 +34,37 @@ impl Schema {
     pub fn new() -> Schema {
         Schema { 
             datastores: AnyMap::new(),
-            names: HashSet::new()
+            names: HashSet::new(),
+            pending: vec![],
+            pending_types: HashSet::new(),
+            open_concurrency: 1,
+            io_budget: None,
+            progress: OpenProgress::default()
         }
     }
 
 
-    pub async fn with_datastore<'a, K, Doc>(mut self, opts: Options<'a>) -> Result<Schema, SchemaError> 
+    /// max datastores opened (replaying WAL) at same time by `open_all`
+    pub fn with_open_concurrency(mut self, open_concurrency: usize) -> Schema {
+        self.open_concurrency = open_concurrency.max(1);
+        self
+    }
+
+    /// WAL pages per second shared by all loaders of `open_all`
+    pub fn with_io_budget(mut self, pages_per_sec: u32) -> Schema {
+        self.io_budget = Some(Arc::new(IoBudget::new(pages_per_sec)));
+        self
+    }
+
+    /// progress of datastores added by `add_datastore`
+    pub fn open_progress(&self) -> OpenProgress {
+        self.progress.clone()
+    }
+
+
+    /// same as `with_datastore` but opened later by `open_all`,
+    /// concurrently with other added datastores
+    pub fn add_datastore<K, Doc>(mut self, mut opts: Options) -> Result<Schema, SchemaError> 
     where
         Doc: Serialize + DeserializeOwned + Clone + Sync + Send + 'static + Document,
         K:  Serialize
@@ -38,19 +79,102 @@ impl Schema {
             + Sync
             + 'static
     {
+        let type_id = TypeId::of::<Storage<K, Doc>>();
 
-        if self.names.contains(opts.storage_name) {
+        if self.names.contains(&opts.storage_name) {
             return Err(SchemaError::DatastoreAlreadyExist(opts.storage_name.to_owned()))
         }
 
-        if let Some(_) = self.datastores.get::<Storage<K, Doc>>() {
+        if self.datastores.contains::<Storage<K, Doc>>() || self.pending_types.contains(&type_id) {
+            return Err(SchemaError::DuplicateStore(type_name::<Storage<K, Doc>>()))
+        }
+
+        self.pending_types.insert(type_id);
+        self.names.insert(opts.storage_name.clone());
+
+        opts.io_budget = self.io_budget.clone();
+        opts.load_progress = Some(self.progress.register(&opts.storage_name));
+
+        self.pending.push(Box::new(move |permits: Arc<Semaphore>| {
+            tokio::spawn(async move {
+                let _permit = permits.acquire_owned().await.map_err(|e| e.to_string())?;
+
+                let name = opts.storage_name.clone();
+                let ds = Storage::<K, Doc>::open(opts).await?;
+
+                let inserter: Inserter = Box::new(move |datastores: &mut AnyMap| {
+                    insert_datastore(datastores, &name, ds)
+                });
+                Ok(inserter)
+            })
+        }));
+
+        Ok(self)
+    }
+
+    /// open datastores added by `add_datastore`, at most `open_concurrency` at once,
+    /// datastores inserted in add order whatever completion order
+    pub async fn open_all(mut self) -> Result<Schema, SchemaError> {
+        let permits = Arc::new(Semaphore::new(self.open_concurrency));
+
+        let handles: Vec<_> = self.pending
+            .drain(..)
+            .map(|spawn| spawn(permits.clone()))
+            .collect();
+        self.pending_types.clear();
+
+        let mut inserters = Vec::with_capacity(handles.len());
+        for handle in handles {
+            match handle.await {
+                Ok(Ok(inserter)) => inserters.push(inserter),
+                Ok(Err(e)) => return Err(SchemaError::Err(e)),
+                Err(e) => return Err(SchemaError::Err(e.to_string())),
+            }
+        }
+
+        for inserter in inserters {
+            inserter(&mut self.datastores);
+        }
+
+        Ok(self)
+    }
+
+
+    pub async fn with_datastore<K, Doc>(mut self, opts: Options) -> Result<Schema, SchemaError> 
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Sync + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+
+        if self.names.contains(&opts.storage_name) {
             return Err(SchemaError::DatastoreAlreadyExist(opts.storage_name.to_owned()))
         }
 
+        if let Some(_) = self.datastores.get::<Storage<K, Doc>>() {
+            return Err(SchemaError::DuplicateStore(type_name::<Storage<K, Doc>>()))
+        }
+
+        if self.pending_types.contains(&TypeId::of::<Storage<K, Doc>>()) {
+            return Err(SchemaError::DuplicateStore(type_name::<Storage<K, Doc>>()))
+        }
+
+        let name = opts.storage_name.clone();
+
         match Storage::<K, Doc>::open(opts).await {
             Err(e) => Err(SchemaError::Err(e)),
             Ok(ds) => {
-                self.datastores.insert(ds);
+                insert_datastore(&mut self.datastores, &name, ds);
+                self.names.insert(name);
                 Ok(self)
             }
         }
@@ -59,6 +183,30 @@ impl Schema {
 
 
 
+    /// same as `with_datastore` but build Options from StoreConfig
+    pub async fn with_store_config<K, Doc>(self, cfg: &StoreConfig) -> Result<Schema, SchemaError> 
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Sync + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        match Options::from_config(cfg) {
+            Ok(opts) => self.with_datastore::<K, Doc>(opts).await,
+            Err(e) => Err(SchemaError::Config(e)),
+        }
+    }
+
+
+
     pub async fn with_redisstore<K, Doc>(mut self, storage_name: &str) -> Result<Schema, SchemaError> 
     where
         Doc: Clone + Sync + Send + 'static,
@@ -79,26 +227,164 @@ impl Schema {
         }
 
         if let Some(_) = self.datastores.get::<RedisStorage<K, Doc>>() {
-            return Err(SchemaError::DatastoreAlreadyExist(storage_name.to_owned()))
+            return Err(SchemaError::DuplicateStore(type_name::<RedisStorage<K, Doc>>()))
         }
 
         self.datastores.insert(RedisStorage::<K, Doc>::new());
+        self.names.insert(storage_name.to_owned());
         Ok(self)
         
     }
 
 
 
+    pub async fn with_bytesstore<K>(mut self, opts: Options, extractors: Extractors) -> Result<Schema, SchemaError> 
+    where
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+
+        if self.names.contains(&opts.storage_name) {
+            return Err(SchemaError::DatastoreAlreadyExist(opts.storage_name.to_owned()))
+        }
+
+        if self.datastores.contains::<BytesStorage<K>>() {
+            return Err(SchemaError::DuplicateStore(type_name::<BytesStorage<K>>()))
+        }
+
+        let name = opts.storage_name.clone();
+
+        match BytesStorage::<K>::open(opts, extractors).await {
+            Err(e) => Err(SchemaError::Err(e)),
+            Ok(ds) => {
+                self.datastores.insert(ds);
+                self.names.insert(name);
+                Ok(self)
+            }
+        }
+        
+    }
+
+
+
+    /// declare store (DepK, DepDoc) refer to (K, Doc),
+    /// dependent documents must have tag `tag_of(key)`, used by `Database::remove_checked`
+    pub fn with_reference<K, Doc, DepK, DepDoc>(mut self, 
+                                                dependent_name: &str, 
+                                                tag_of: fn(&K) -> String, 
+                                                action: ReferenceAction) -> Schema
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Sync + Send + 'static + Document,
+        K:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        DepDoc: Serialize + DeserializeOwned + Clone + Sync + Send + 'static + Document,
+        DepK:  Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static
+    {
+        let dependent = Box::new(Dependent::<DepK, DepDoc>::new(dependent_name, action));
+
+        match self.datastores.get_mut::<References<K, Doc>>() {
+            Some(refs) => refs.push(tag_of, dependent),
+            None => {
+                let mut refs = References::<K, Doc>::new();
+                refs.push(tag_of, dependent);
+                self.datastores.insert(refs);
+            }
+        }
+
+        self
+    }
+
+
+
     pub fn build(self) -> Database {
         Database::open(self.datastores)
     }
 
+    /// same as `build` but ping every datastore first and log registered stores to stderr,
+    /// Err with store name if one doesn't answer
+    pub async fn open(self) -> Result<Database, SchemaError> {
+        if let Some(registry) = self.datastores.get::<Registry>() {
+            if let Err((store, e)) = registry.ping_all(&self.datastores).await {
+                return Err(SchemaError::Err(format!("{}: {}", store, e.to_string())));
+            }
+
+            for (store, type_name) in registry.roster() {
+                eprintln!("schema: {} ({})", store, type_name);
+            }
+        }
+
+        Ok(Database::open(self.datastores))
+    }
+
+}
+
+
+
+fn insert_datastore<K, Doc>(datastores: &mut AnyMap, name: &str, ds: Storage<K, Doc>)
+where
+    Doc: Serialize + DeserializeOwned + Clone + Sync + Send + 'static + Document,
+    K:  Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static
+{
+    datastores.insert(ds);
+
+    let entry = Box::new(StoreEntry::<K, Doc>::new(name));
+    match datastores.get_mut::<Registry>() {
+        Some(registry) => registry.push(entry),
+        None => {
+            let mut registry = Registry::new();
+            registry.push(entry);
+            datastores.insert(registry);
+        }
+    }
 }
 
 
 
 #[derive(Debug)]
 pub enum SchemaError {
+    // store name registered twice
     DatastoreAlreadyExist(String),
+
+    // store type registered twice, a second store would replace the first in AnyMap
+    DuplicateStore(&'static str),
+    Config(ConfigError),
     Err(String)
 }
\ No newline at end of file
@@ -0,0 +1,149 @@
+use std::str::FromStr;
+
+use super::{SessionResult, StatusResult};
+
+
+
+/// Split content into words of inverted index: runs of letters and digits, lowercased.
+///
+/// documents and queries go through it, so `Rust,` and `rust` are one word
+pub fn tokenize(content: &str) -> impl Iterator<Item = String> + '_ {
+    content
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+}
+
+
+/// Boolean expression over words of `FullText::get_content`, run by `Storage::search_query`.
+///
+/// built as `SearchQuery::All(vec![SearchQuery::term("rust"), SearchQuery::Any(..)])`
+/// or parsed from `rust AND (storage OR database)`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SearchQuery {
+    // tokenized as content, every word of it must match
+    Term(String),
+
+    // every query match
+    All(Vec<SearchQuery>),
+
+    // any query match
+    Any(Vec<SearchQuery>),
+}
+
+impl SearchQuery {
+    pub fn term(term: &str) -> Self {
+        SearchQuery::Term(term.to_owned())
+    }
+
+    /// Parse `AND`, `OR` and parentheses, `AND` binding tighter.
+    ///
+    /// words next to each other without operator are `AND`, operators are uppercase only
+    /// so `and` is a word. `Err` on unbalanced parentheses or an operator without operand
+    pub fn parse(query: &str) -> Result<SearchQuery, SessionResult> {
+        let tokens = lex(query);
+        let mut parser = Parser { tokens: &tokens, pos: 0 };
+
+        let parsed = parser.any()?;
+        match parser.peek() {
+            None => Ok(parsed),
+            Some(token) => Err(search_error(&format!("unexpected {}", token))),
+        }
+    }
+}
+
+impl FromStr for SearchQuery {
+    type Err = SessionResult;
+
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        SearchQuery::parse(query)
+    }
+}
+
+
+
+fn lex(query: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    for chunk in query.split_whitespace() {
+        let mut start = 0;
+        for (i, c) in chunk.char_indices() {
+            if c == '(' || c == ')' {
+                if start < i {
+                    tokens.push(&chunk[start..i]);
+                }
+                tokens.push(&chunk[i..i + 1]);
+                start = i + 1;
+            }
+        }
+        if start < chunk.len() {
+            tokens.push(&chunk[start..]);
+        }
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: &'a [&'a str],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn any(&mut self) -> Result<SearchQuery, SessionResult> {
+        let mut queries = vec![self.all()?];
+        while self.peek() == Some("OR") {
+            self.pos += 1;
+            queries.push(self.all()?);
+        }
+        Ok(flatten(queries, SearchQuery::Any))
+    }
+
+    fn all(&mut self) -> Result<SearchQuery, SessionResult> {
+        let mut queries = vec![self.operand()?];
+        loop {
+            match self.peek() {
+                Some("AND") => self.pos += 1,
+                Some("OR") | Some(")") | None => break,
+                Some(_) => {}
+            }
+            queries.push(self.operand()?);
+        }
+        Ok(flatten(queries, SearchQuery::All))
+    }
+
+    fn operand(&mut self) -> Result<SearchQuery, SessionResult> {
+        let token = match self.peek() {
+            Some(token) => token,
+            None => return Err(search_error("missing word at end")),
+        };
+        self.pos += 1;
+
+        match token {
+            "(" => {
+                let query = self.any()?;
+                if self.peek() != Some(")") {
+                    return Err(search_error("missing )"));
+                }
+                self.pos += 1;
+                Ok(query)
+            }
+            ")" | "AND" | "OR" => Err(search_error(&format!("unexpected {}", token))),
+            word if tokenize(word).next().is_none() => Err(search_error(&format!("{} has no word", word))),
+            word => Ok(SearchQuery::term(word)),
+        }
+    }
+}
+
+fn flatten(mut queries: Vec<SearchQuery>, f: fn(Vec<SearchQuery>) -> SearchQuery) -> SearchQuery {
+    if queries.len() == 1 {
+        return queries.pop().unwrap();
+    }
+    f(queries)
+}
+
+fn search_error(reason: &str) -> SessionResult {
+    SessionResult::Err(StatusResult::Err(format!("search: {}", reason)))
+}
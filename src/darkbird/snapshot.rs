@@ -0,0 +1,88 @@
+use anymap::AnyMap;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{hash::Hash, time::Duration};
+
+use crate::{document::Document, Storage};
+
+use super::SessionResult;
+
+// longest time writes of all stores may stay paused by a consistent read
+pub(crate) const MAX_PAUSE: Duration = Duration::from_millis(50);
+
+// barrier attempts before a consistent read give up with Timeout
+pub(crate) const BARRIER_ATTEMPTS: usize = 5;
+
+
+
+/// Every store of a `Database` as it was at one barrier,
+/// passed to closure of `Database::consistent_read`.
+///
+/// barrier is taken while no write is half applied in any store,
+/// closure run right after it without yielding, and `Database` can't be shared across threads,
+/// so no write is applied until closure return and view read stores themselves
+pub struct ConsistentView<'a> {
+    datastores: &'a AnyMap,
+    pause: Duration,
+}
+
+impl<'a> ConsistentView<'a> {
+    pub(crate) fn new(datastores: &'a AnyMap, pause: Duration) -> Self {
+        ConsistentView { datastores, pause }
+    }
+
+    /// how long writes of all stores were paused to take barrier
+    pub fn pause(&self) -> Duration {
+        self.pause
+    }
+
+    pub fn lookup<K, Doc>(&self, key: &K) -> Result<Option<Doc>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K: Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        Ok(self.datastore::<K, Doc>()?.lookup_owned(key))
+    }
+
+    /// every document of store, in no particular order
+    pub fn documents<K, Doc>(&self) -> Result<Vec<(K, Doc)>, SessionResult>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K: Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        let datastore = self.datastore::<K, Doc>()?;
+        Ok(datastore
+            .keys()
+            .into_iter()
+            .filter_map(|key| {
+                let doc = datastore.get_owned(&key)?;
+                Some((key, doc))
+            })
+            .collect())
+    }
+
+    fn datastore<K: 'static, Doc: Document + 'static>(&self) -> Result<&Storage<K, Doc>, SessionResult> {
+        self.datastores
+            .get::<Storage<K, Doc>>()
+            .ok_or(SessionResult::DataStoreNotFound)
+    }
+}
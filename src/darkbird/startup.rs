@@ -0,0 +1,124 @@
+use parking_lot::Mutex;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::time::{self, Instant};
+
+
+
+/// Token bucket shared by loaders of stores opened by `Schema::open_all`,
+/// one token per WAL page read, so total replay IO stay within budget
+pub struct IoBudget {
+    // pages per second
+    rate: f64,
+
+    // max tokens accumulated while idle
+    burst: f64,
+
+    // (tokens, last refill)
+    state: Mutex<(f64, Instant)>,
+}
+
+impl IoBudget {
+    pub fn new(pages_per_sec: u32) -> Self {
+        let rate = pages_per_sec.max(1) as f64;
+        IoBudget {
+            rate,
+            burst: rate,
+            state: Mutex::new((rate, Instant::now())),
+        }
+    }
+
+    /// wait for a page token
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock();
+                let now = Instant::now();
+                let refill = now.duration_since(state.1).as_secs_f64() * self.rate;
+                *state = ((state.0 + refill).min(self.burst), now);
+
+                if state.0 >= 1.0 {
+                    state.0 -= 1.0;
+                    return;
+                }
+
+                Duration::from_secs_f64((1.0 - state.0) / self.rate)
+            };
+
+            time::sleep(wait).await;
+        }
+    }
+}
+
+
+
+/// loader progress of a single store
+#[derive(Default)]
+pub(crate) struct LoadProgress {
+    pages_done: AtomicUsize,
+    pages_total: AtomicUsize,
+    finished: AtomicBool,
+}
+
+impl LoadProgress {
+    pub fn set_total(&self, pages: usize) {
+        self.pages_total.store(pages, Ordering::SeqCst);
+    }
+
+    pub fn page_done(&self) {
+        self.pages_done.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn finish(&self) {
+        self.finished.store(true, Ordering::SeqCst);
+    }
+}
+
+
+
+#[derive(Clone, Debug)]
+pub struct StoreProgress {
+    pub name: String,
+    pub pages_done: usize,
+    pub pages_total: usize,
+
+    // store opened (or failed)
+    pub finished: bool,
+}
+
+
+type Stores = Vec<(String, Arc<LoadProgress>)>;
+
+
+/// Observe open progress of stores added by `Schema::add_datastore`,
+/// clone it before `open_all` and poll `snapshot` from another task
+#[derive(Clone, Default)]
+pub struct OpenProgress {
+    stores: Arc<Mutex<Stores>>,
+}
+
+impl OpenProgress {
+    pub(crate) fn register(&self, name: &str) -> Arc<LoadProgress> {
+        let progress = Arc::new(LoadProgress::default());
+        self.stores.lock().push((name.to_owned(), progress.clone()));
+        progress
+    }
+
+    pub fn snapshot(&self) -> Vec<StoreProgress> {
+        self.stores
+            .lock()
+            .iter()
+            .map(|(name, p)| StoreProgress {
+                name: name.clone(),
+                pages_done: p.pages_done.load(Ordering::SeqCst),
+                pages_total: p.pages_total.load(Ordering::SeqCst),
+                finished: p.finished.load(Ordering::SeqCst),
+            })
+            .collect()
+    }
+}
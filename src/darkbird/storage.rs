@@ -1,18 +1,99 @@
 use serde::{Serialize, Deserialize, de::DeserializeOwned};
-use std::hash::Hash;
+use std::{cmp::Reverse, collections::{hash_map::DefaultHasher, BinaryHeap, HashMap, HashSet}, hash::{Hash, Hasher}, sync::Arc, time::{Duration, SystemTime}};
+use tokio::time::Instant;
+use parking_lot::Mutex;
 use tokio::sync::mpsc::Sender;
 
-use dashmap::{iter::Iter, mapref::one::Ref, DashMap, DashSet};
+use dashmap::{iter::Iter, mapref::{multiple::RefMulti, one::Ref}, DashMap, DashSet};
 
 
 use super::{
-    wal::disk_log::{DiskLog, Session},
-    index::{hash::HashIndex, range::RangeIndex, tags::TagIndex, inverted_index::InvertedIndex},
-    router::{self, Router},
+    wal::{codec::{Codec, WalCodec}, disk_log::{DiskLog, Session, WalStats}, frames::{frames, Frame}},
+    index::{bloom::Bloom, hash::HashIndex, ordered::{EncodedOrder, KeyOrder}, range::RangeIndex, tags::TagIndex, inverted_index::InvertedIndex, shadow::Shadowed},
+    router::{self, Router, SubscriberInfo, SubscriptionId},
+    coalesce::Coalescer,
+    query::QueryBuilder,
+    search::SearchQuery,
+    stream::ScanStream,
+    compression::Compression,
+    admin::{AdminLog, AdminEvent},
+    disk_space::DiskWatch,
+    startup::{IoBudget, LoadProgress},
+    capabilities::Capabilities,
+    clock::Clock,
+    fingerprint::Fingerprint,
+    latency::{Latency, LatencyReport, Operation},
+    metrics::{MetricEvent, Metrics, MetricsSnapshot},
+    coop::Budget,
+    timer::Timers,
+    plugin::{WritePlugin, WriteContext},
+    read_snapshot::ReadSnapshot,
+    expiry::{self, Expiry, ExpiryService},
+    repair::Repairs,
+    filter::{Change, EventMeta, Filtering, SubscribeFilter},
+    access::{AccessCounter, AccessReport},
+    recovery::{self, ClockSkewPolicy, RecoveryMode, RecoveryReport},
+    retention::EventSeq,
+    capacity::Capacity,
+    query_cache::{QueryCache, QueryCacheStats},
+    watch::Watchers,
+    views::Views,
+    patch::DocPatch,
     Options, StatusResult, StorageType,
 };
 
-use crate::{darkbird::SessionResult, document::Document};
+use crate::{darkbird::SessionResult, document::{Document, FieldValue}};
+
+mod audit;
+mod batch;
+mod bulk;
+mod capacity;
+mod checkpoint;
+mod clear;
+mod compact;
+mod export;
+mod keys;
+mod owned;
+mod page;
+mod plan;
+mod prefix;
+mod read;
+mod rebuild;
+mod repair;
+mod select;
+mod self_test;
+mod stats;
+mod tag;
+mod transaction;
+mod ttl;
+mod update;
+mod view;
+
+pub use audit::{AuditReport, AuditEntry, Structure};
+pub use bulk::BulkProgress;
+pub use checkpoint::CheckpointReport;
+pub use compact::{CompactPhase, CompactReport};
+pub use export::{ConflictPolicy, ExportFormat, ExportOptions, ImportReport};
+pub use owned::PinnedDoc;
+pub use page::KeyPage;
+pub use plan::{CompactPlan, PlanReport, RemovalPlan};
+pub use rebuild::{RebuildProgress, RebuildState};
+pub(crate) use select::Probe;
+pub use self_test::{PhaseReport, SelfTestProfile, SelfTestReport};
+pub use stats::StorageStats;
+pub(crate) use transaction::{Before, Held};
+
+// stripes of update_locks
+const UPDATE_STRIPES: usize = 64;
+
+/// one of n lanes or stripes of key, by its hash
+#[inline]
+pub(crate) fn bucket<K: Hash>(key: &K, n: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish() as usize % n
+}
+
 
 
 
@@ -24,13 +105,14 @@ pub struct Storage<K, Doc: Document> {
     hash_index: HashIndex<K>,
 
     // TagIndex
-    tag_index: TagIndex<K>,
+    // shared with expiry service
+    tag_index: Arc<TagIndex<K>>,
 
     // RangeIndex
-    range_index: RangeIndex<K>,
+    range_index: Shadowed<RangeIndex<K>>,
 
     // InvertedIndex
-    inverted_index: InvertedIndex<K>,
+    inverted_index: Shadowed<InvertedIndex<K>>,
 
     // Wal session
     wal_session: Session,
@@ -40,7 +122,166 @@ pub struct Storage<K, Doc: Document> {
 
     off_reporter: bool,
 
-    off_disk: bool
+    off_disk: bool,
+
+    // Write coalescing
+    coalescer: Option<Coalescer<K, Doc>>,
+
+    // Value compression, documents held in `compressed` instead of `collection`
+    compression: Option<Compression>,
+    compressed: DashMap<K, Vec<u8>>,
+
+    // WAL and Reporter lanes
+    lanes: usize,
+
+    opened_at: Instant,
+
+    // Online index rebuild, Database consistent reads and WAL compaction,
+    // writes hold gate shared from WAL log to memory, expiry service too
+    rebuild_gate: Arc<tokio::sync::RwLock<()>>,
+    rebuild: Mutex<Option<RebuildProgress>>,
+
+    admin: Arc<AdminLog>,
+    clock: Arc<dyn Clock>,
+
+    // directory of WAL, self_test scratch store opened beside
+    path: String,
+
+    // <path>/<name>, pages of WAL
+    wal_dir: String,
+
+    // records per WAL page
+    page_size: usize,
+
+    // <path>/<name>.checkpoints, see Storage::checkpoint
+    checkpoints: String,
+
+    timers: Option<Timers<K>>,
+
+    // deadlines of insert_with_ttl
+    expiry: Arc<Expiry<K, Doc>>,
+
+    // logs and dispatch removal of documents past their ttl, set once open replayed WAL
+    expiry_service: Option<ExpiryService<K, Doc>>,
+
+    // writes of a key through insert, remove, update and compare_and_swap
+    // run one at a time, held across WAL write. expiry service take them too
+    update_locks: Arc<Vec<tokio::sync::Mutex<()>>>,
+
+    // one Storage::compact at a time
+    compaction: tokio::sync::Mutex<()>,
+
+    // write chain, skipped by loader
+    plugins: Vec<Arc<dyn WritePlugin<K, Doc>>>,
+
+    // read repair hook, set after loader
+    repair: Option<Repairs<K, Doc>>,
+
+    // what subscribers filters read from events, see subscribe_filtered
+    filtering: Arc<Filtering<K>>,
+
+    // read counters, Options::with_access_tracking
+    access: Option<AccessCounter>,
+
+    // keys in order, Options::with_ordered_keys
+    key_order: Option<KeyOrder<K>>,
+
+    // keys in KeyCodec byte order, filled by first scan_prefix or key_range
+    encoded_order: EncodedOrder<K>,
+
+    // while loader run with Options::with_low_memory_replay, derived structures
+    // but hash index are left to build_derived
+    defer_derived: bool,
+
+    // Event::Removed and Event::Replaced, Options::with_previous_values
+    previous_values: bool,
+
+    // sequence of events kept by Options::with_event_retention
+    event_seq: Option<EventSeq>,
+
+    // encoding of WAL records, Options::with_wal_codec
+    codec: WalCodec,
+
+    bulk: Mutex<Option<BulkProgress>>,
+
+    // ReadPreference::Snapshot reads
+    read_snapshot: Option<ReadSnapshot<K, Doc>>,
+
+    // no-op without metrics feature
+    latency: Latency,
+
+    // counters of Storage::metrics, shared with wal_session
+    metrics: Arc<Metrics>,
+
+    // keys ever inserted, Options::with_bloom_filter. set once open replayed WAL
+    bloom: Option<Bloom>,
+
+    // free space check under WAL, Options::with_disk_space_watch
+    disk_watch: Option<DiskWatch>,
+
+    // WAL replayed by open, Options::with_recovery
+    recovery: RecoveryReport,
+
+    // millis wall times read by open are moved by, ClockSkewPolicy::TrustLog. 0 once open
+    rebase_ms: i64,
+
+    // bound of keys held, Options::with_capacity
+    capacity: Option<Capacity<K>>,
+
+    // results of QueryBuilder::cached, Options::with_query_cache
+    query_cache: Option<QueryCache<K>>,
+
+    // channels of Storage::watch
+    watchers: Watchers<K, Doc>,
+
+    // views of Storage::create_view
+    views: Views<K, Doc>,
+
+    // run by drop of a store not closed, set once open succeeded, see close_dropped
+    close_on_drop: Option<fn(&mut Storage<K, Doc>)>,
+
+    capabilities: Capabilities
+}
+
+
+impl<K, Doc: Document> Drop for Storage<K, Doc> {
+    fn drop(&mut self) {
+        if let Some(close) = self.close_on_drop.take() {
+            close(self);
+        }
+    }
+}
+
+
+// bound of close of a store dropped without close, outside a runtime
+const DROP_DEADLINE: Duration = Duration::from_secs(2);
+
+fn warn_dropped(store: &str, flushed: usize, closed: Result<WalStats, SessionResult>) {
+    match closed {
+        Ok(stats) => eprintln!(
+            "darkbird: store {} dropped without close(), {} coalesced writes logged and WAL synced \
+             ({} records since open). call close() to know writes are on disk",
+            store, flushed, stats.records
+        ),
+        Err(e) => eprintln!(
+            "darkbird: store {} dropped without close(), {} coalesced writes flushed but WAL not synced: {}. \
+             call close() to know writes are on disk",
+            store, flushed, e.to_string()
+        ),
+    }
+}
+
+
+/// final statistics returned by `Storage::close`
+#[derive(Clone, Copy, Debug)]
+pub struct CloseReport {
+    // records written to disk_log since open
+    pub records: u64,
+
+    // bytes of records written to disk_log since open
+    pub bytes: u64,
+
+    pub uptime: Duration,
 }
 
 impl<K, Doc> Storage<K, Doc>
@@ -58,227 +299,1350 @@ where
         + Sync
         + 'static,
 {
-    pub async fn open<'a>(ops: Options<'a>) -> Result<Self, String> {
+    pub async fn open(ops: Options) -> Result<Self, String> {
         
-        match DiskLog::open(ops.path, ops.storage_name, ops.total_page_size) {
+        let admin = Arc::new(AdminLog::open(&ops.storage_name, ops.admin_log.as_deref(), ops.clock.clone())?);
+        let capabilities = ops.capabilities();
+
+        let plugins = ops
+            .plugins
+            .iter()
+            .map(|p| p.typed::<K, Doc>().ok_or_else(|| format!("plugin {} registered for other key or document type", p.name)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let read_repair = match &ops.read_repair {
+            Some(hook) => Some(hook.typed::<K, Doc>().ok_or("read repair registered for other key or document type")?),
+            None => None,
+        };
+
+        // before WAL is touched, so a mismatch leave it as is
+        let fingerprint = Fingerprint::of::<K, Doc>();
+        let record_fingerprint = fingerprint
+            .verify(&ops.path, &ops.storage_name, ops.schema_override)
+            .map_err(|e| e.to_string())?;
+
+        // before disk_log open, which may write newest page
+        let (skew_threshold, skew_policy) = ops.clock_skew.unwrap_or((recovery::DEFAULT_SKEW_THRESHOLD, ClockSkewPolicy::TrustClock));
+        let clock_skew = match ops.stype {
+            StorageType::RamCopies => None,
+            _ => recovery::clock_skew(&format!("{}/{}", ops.path, ops.storage_name), ops.clock.now(), skew_threshold, ops.clock_skew.is_some()),
+        };
+        if let Some(skew) = clock_skew {
+            eprintln!("{}: newest WAL page {:?} of clock, {:?}", ops.storage_name, skew, skew_policy);
+            if skew_policy == ClockSkewPolicy::Fail {
+                return Err(format!("ClockSkew {:?}", skew));
+            }
+        }
+
+        match DiskLog::open(&ops.path, &ops.storage_name, ops.total_page_size, ops.wal_codec, ops.wal_writer) {
             Err(e) => return Err(e.to_string()),
             Ok(disklog) => {
                 // Run DiskLog
                 let off_disk = if let StorageType::RamCopies = ops.stype { true } else { false };
 
                 // Run Reporter
-                let reporter = Router::<Event<K, Doc>>::new(vec![]).unwrap().run_service();
+                let mut router = Router::<Event<K, Doc>>::new(vec![]).unwrap().with_lanes(ops.lanes);
+                if let Some(threshold) = ops.lag_threshold {
+                    router = router.with_lag_alert(threshold, Event::Lagging);
+                }
+                router = router.with_drop_alert(Event::SubscriberDropped);
+                let event_seq = ops.event_retention.map(|_| {
+                    EventSeq::open((!off_disk).then(|| format!("{}/{}.events", ops.path, ops.storage_name)))
+                });
+                if let (Some(bounds), Some(event_seq)) = (ops.event_retention, &event_seq) {
+                    router = router.with_retention(bounds, Event::is_change, event_seq.counter(), ops.clock.clone());
+                }
+                let reporter = router.run_service();
+
+                if let Some(progress) = &ops.load_progress {
+                    progress.set_total(disklog.pages());
+                }
+
+                let disklog = match ops.archive_hook.clone() {
+                    Some(hook) if !off_disk => disklog.with_archive(hook),
+                    _ => disklog,
+                };
+
+                let rotated = admin.clone();
+                let disklog = disklog.with_rotate_hook(Arc::new(move |page| {
+                    rotated.notice("page_rotated", format!("index={} records={}", page.index, page.records), page.bytes);
+                }));
+
+                let torn_tail = disklog.torn_tail();
 
                 // Run disk_log
-                let wal_session = disklog.run_service();
+                let wal_session = disklog.run_service_with_lanes(ops.lanes);
+
+                #[cfg(feature = "test-util")]
+                let wal_session = wal_session.with_faults(ops.wal_faults.clone());
+
+                let metrics = Arc::new(Metrics::new(ops.metrics_observer.clone()));
+                let wal_session = wal_session.with_metrics(metrics.clone());
 
 
                 // Create Storage
                 let mut st = Storage {
                     collection: DashMap::new(),
                     hash_index: HashIndex::new(),
-                    tag_index: TagIndex::new(),
-                    range_index: RangeIndex::new(),
-                    inverted_index: InvertedIndex::new(),
+                    tag_index: Arc::new(TagIndex::new()),
+                    range_index: Shadowed::new(RangeIndex::new()),
+                    inverted_index: Shadowed::new(InvertedIndex::new()),
                     wal_session: wal_session,
                     reporter_session: reporter,
                     off_reporter: ops.off_reporter,
-                    off_disk: true
+                    off_disk: true,
+                    coalescer: None,
+                    compression: ops.value_compression,
+                    compressed: DashMap::new(),
+                    lanes: ops.lanes,
+                    opened_at: Instant::now(),
+                    rebuild_gate: Arc::new(tokio::sync::RwLock::new(())),
+                    rebuild: Mutex::new(None),
+                    admin,
+                    clock: ops.clock.clone(),
+                    path: ops.path.clone(),
+                    wal_dir: format!("{}/{}", ops.path, ops.storage_name),
+                    page_size: ops.total_page_size,
+                    checkpoints: format!("{}/{}.checkpoints", ops.path, ops.storage_name),
+                    timers: None,
+                    expiry: Arc::new(Expiry::new()),
+                    expiry_service: None,
+                    update_locks: Arc::new((0..UPDATE_STRIPES).map(|_| tokio::sync::Mutex::new(())).collect()),
+                    compaction: tokio::sync::Mutex::new(()),
+                    plugins,
+                    repair: None,
+                    filtering: Arc::new(Filtering::new()),
+                    access: ops.track_access.then(AccessCounter::new),
+                    key_order: ops.ordered_keys.then(KeyOrder::new),
+                    encoded_order: EncodedOrder::new(),
+                    defer_derived: ops.low_memory_replay,
+                    previous_values: ops.previous_values,
+                    event_seq,
+                    codec: ops.wal_codec,
+                    bulk: Mutex::new(None),
+                    read_snapshot: ops.read_snapshot.map(ReadSnapshot::new),
+                    latency: Latency::new(),
+                    metrics,
+                    bloom: None,
+                    disk_watch: None,
+                    recovery: RecoveryReport::new(ops.recovery),
+                    rebase_ms: 0,
+                    capacity: ops.capacity.map(|limit| Capacity::new(limit, ops.eviction)),
+                    query_cache: ops.query_cache.map(|(max_entries, max_bytes)| QueryCache::new(max_entries, max_bytes)),
+                    watchers: Watchers::new(),
+                    views: Views::new(),
+                    close_on_drop: None,
+                    capabilities
                 };
 
 
-                // load from disk
-                if let Err(x) = st.loader().await {
-                    if x != "End" {
-                        return Err(x);
-                    } 
+                if skew_policy == ClockSkewPolicy::TrustLog {
+                    st.rebase_ms = clock_skew.map_or(0, |skew| skew.offset_ms());
+                }
+
+                // load from disk, WAL before newest checkpoint isn't replayed
+                let from_page = if off_disk {
+                    1
+                } else {
+                    st.restore_checkpoint(&st.wal_dir).await
+                };
+                let loaded = st.loader(from_page, ops.io_budget.as_deref(), ops.load_progress.as_deref()).await;
+
+                if let Some(progress) = &ops.load_progress {
+                    progress.finish();
+                }
+
+                st.recovery = loaded?;
+                st.recovery.torn_tail = torn_tail;
+                st.recovery.clock_skew = clock_skew;
+
+                if st.defer_derived {
+                    st.build_derived();
+                    st.defer_derived = false;
+                }
+
+                if let Some((expected, rate)) = ops.bloom_filter {
+                    st.bloom = Some(st.build_bloom(expected, rate));
+                }
+
+                if record_fingerprint {
+                    fingerprint.record(&ops.path, &ops.storage_name).map_err(|e| e.to_string())?;
                 }
 
 
                 // because we want loader dont write to disk_log
                 st.off_disk = off_disk;
 
-                return Ok(st);
-            }
+                let rebase_ms = std::mem::take(&mut st.rebase_ms);
+                if rebase_ms != 0 {
+                    st.recovery.rebased = st.log_rebased().await.map_err(|e| e.to_string())?;
+                }
+
+                // replay never repair
+                st.repair = read_repair.map(Repairs::new);
+
+                if ops.timers {
+                    if ops.off_reporter {
+                        return Err(StatusResult::ReporterIsOff.to_string());
+                    }
+                    let timers = Timers::open(
+                        &ops.path,
+                        &ops.storage_name,
+                        ops.total_page_size,
+                        !off_disk,
+                        ops.clock.clone(),
+                        rebase_ms,
+                        st.reporter_session.clone(),
+                    );
+                    let (timers, rebased) = timers.await?;
+                    st.recovery.rebased += rebased;
+                    st.timers = Some(timers);
+                }
+
+                // coalescing start after loader 
+                if let Some(window) = ops.coalesce {
+                    let wal_session = if off_disk { None } else { Some(st.wal_session.clone()) };
+                    let reporter_session = if ops.off_reporter { None } else { Some(st.reporter_session.clone()) };
+                    st.coalescer = Some(Coalescer::run_service(window, wal_session, st.codec, reporter_session));
+                }
+
+                st.fit_capacity().await.map_err(|e| e.to_string())?;
+
+                // deadlines replayed are armed, nothing to log or dispatch without disk and reporter
+                if !off_disk || !ops.off_reporter {
+                    let store = expiry::Store {
+                        update_locks: st.update_locks.clone(),
+                        rebuild_gate: st.rebuild_gate.clone(),
+                        wal: st.wal_session.clone(),
+                        durable: !off_disk,
+                        codec: st.codec,
+                        reporter: (!ops.off_reporter).then(|| st.reporter_session.clone()),
+                        lanes: st.lanes,
+                        previous_values: st.previous_values,
+                        filtering: st.filtering.clone(),
+                        tag_index: st.tag_index.clone(),
+                        clock: st.clock.clone(),
+                    };
+                    st.expiry_service = Some(ExpiryService::spawn(st.expiry.clone(), store));
+                }
+
+                if let Some((threshold, floor)) = ops.disk_space_watch.filter(|_| !off_disk) {
+                    st.disk_watch = Some(DiskWatch::spawn(st.wal_dir.clone(), threshold, floor, st.admin.clone(), st.wal_session.clone()));
+                }
+
+                st.close_on_drop = Some(Self::close_dropped);
+                return Ok(st);
+            }
+        }
+
+    }
+
+    /// flush pending writes, fsync and stop disk_log and reporter,
+    /// page file released and subscribers channels closed when returned.
+    ///
+    /// once returned Ok, every write that returned Ok (coalesced ones included) is on disk
+    /// and seen by next open. every step is run even if one before failed,
+    /// so WAL is synced and reporter stopped whatever happen to timers, first error returned
+    pub async fn close(mut self) -> Result<CloseReport, SessionResult> {
+        self.close_on_drop = None;
+        let op = self.admin.start("close", String::new());
+
+        if let Some(service) = self.expiry_service.take() {
+            service.close().await;
+        }
+
+        let coalesced = match self.coalescer.take() {
+            Some(coalescer) => coalescer.close().await.map(|_| ()),
+            None => Ok(()),
+        };
+
+        let timers = match self.timers.take() {
+            Some(timers) => timers.close().await,
+            None => Ok(()),
+        };
+
+        let wal = self.wal_session.close().await;
+        let reporter = self.reporter_session.stop().await;
+
+        // every event got its sequence, next open continue them
+        if let (Ok(_), Some(event_seq)) = (&reporter, &self.event_seq) {
+            event_seq.close();
+        }
+
+        let res = coalesced.and(timers).and(wal).and_then(|stats| reporter.map(|_| stats));
+
+        match res {
+            Ok(stats) => {
+                self.admin.finish(op, Ok(stats.bytes));
+                Ok(CloseReport {
+                    records: stats.records,
+                    bytes: stats.bytes,
+                    uptime: self.opened_at.elapsed(),
+                })
+            }
+            Err(e) => {
+                self.admin.finish(op, Err(e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    /// Safety net of a store dropped without `close`: coalesced writes logged and WAL synced
+    /// as close do, with a warning on stderr.
+    ///
+    /// inside a runtime it's spawned, blocking there could stall tasks a flush wait on,
+    /// so it may not be done when drop return. else it's handed to disk_log thread
+    /// and waited for at most DROP_DEADLINE
+    fn close_dropped(&mut self) {
+        self.expiry_service = None;
+
+        let store = self.capabilities.store.clone();
+        let coalescer = self.coalescer.take();
+        let wal = self.wal_session.clone();
+
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    let flushed = match coalescer {
+                        Some(coalescer) => coalescer.close().await.unwrap_or_else(|e| {
+                            eprintln!("darkbird: store {} coalesced writes not logged: {}", store, e.to_string());
+                            0
+                        }),
+                        None => 0,
+                    };
+                    warn_dropped(&store, flushed, wal.close().await);
+                });
+            }
+            Err(_) => {
+                let records = coalescer.map_or(vec![], |coalescer| coalescer.drain_blocking());
+                let flushed = records.len();
+                warn_dropped(&store, flushed, wal.close_blocking(records, DROP_DEADLINE));
+            }
+        }
+    }
+
+    /// wall time of store clock, never go backwards with default clock
+    #[inline]
+    pub fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+
+    /// fire `Event::Timer(key)` to subscribers at `at` without touching document,
+    /// replace pending timer of key, durable with DiskCopies
+    pub async fn notify_at(&self, key: K, at: SystemTime) -> Result<(), SessionResult> {
+        match &self.timers {
+            Some(timers) => timers.arm(key, at).await,
+            None => Err(SessionResult::Err(StatusResult::TimersIsOff)),
+        }
+    }
+
+    /// return false if key had no pending timer
+    pub async fn cancel_notify(&self, key: &K) -> Result<bool, SessionResult> {
+        match &self.timers {
+            Some(timers) => timers.cancel(key).await,
+            None => Err(SessionResult::Err(StatusResult::TimersIsOff)),
+        }
+    }
+
+    /// deadline of pending timer of key
+    pub fn pending_notify(&self, key: &K) -> Option<SystemTime> {
+        self.timers.as_ref()?.deadline(key)
+    }
+
+    /// no-op round trip to disk_log and reporter of store, Err if one of them stopped
+    pub async fn ping(&self) -> Result<(), SessionResult> {
+        self.wal_session.ping().await?;
+
+        if !self.off_reporter {
+            match tokio::time::timeout(super::TIMEOUT, self.reporter_session.report()).await {
+                Ok(res) => { res?; }
+                Err(_) => return Err(SessionResult::Timeout),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// features enabled on this store
+    #[inline]
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities.clone()
+    }
+
+    /// records replayed by open and what was done with corrupted ones, see `RecoveryMode`
+    pub fn recovery(&self) -> &RecoveryReport {
+        &self.recovery
+    }
+
+    /// writes refused with `SessionResult::ReadOnly`
+    pub fn read_only(&self) -> bool {
+        self.wal_session.is_read_only()
+    }
+
+    /// refuse writes with `SessionResult::ReadOnly` before they change anything, or accept them again,
+    /// e.g. once space is freed after `Options::with_disk_space_watch` turned store read-only.
+    /// a change is noticed as `read_only` or `writable` admin event, reads are unaffected
+    pub fn set_read_only(&self, read_only: bool) {
+        if self.wal_session.set_read_only(read_only) {
+            let operation = if read_only { "read_only" } else { "writable" };
+            self.admin.notice(operation, String::new(), 0);
+        }
+    }
+
+    // also checked by WAL session, here for writes not logged (coalesced, RamCopies)
+    #[inline]
+    fn writable(&self) -> Result<(), SessionResult> {
+        match self.wal_session.is_read_only() {
+            true => Err(SessionResult::ReadOnly),
+            false => Ok(()),
+        }
+    }
+
+    /// structured events of admin operations (audit, rebuild_index, close)
+    #[inline]
+    pub fn admin_events(&self) -> tokio::sync::broadcast::Receiver<AdminEvent> {
+        self.admin.subscribe()
+    }
+
+    /// subscribe to Reporter, see `subscribe_filtered`
+    #[inline]
+    pub async fn subscribe(&self, sender: Sender<Event<K, Doc>>) -> Result<SubscriptionId, SessionResult> {
+        self.subscribe_filtered(sender, SubscribeFilter::All).await
+    }
+
+    /// subscribe to Reporter with events of types filter let through,
+    /// router skip sender for others so its task isn't woken up.
+    /// to filter by tag or key prefix see `subscribe_matching`.
+    ///
+    /// subscriber is removed once its receiver is dropped, or by `unsubscribe` with id returned,
+    /// others then receive `Event::SubscriberDropped`
+    #[inline]
+    pub async fn subscribe_filtered(&self, sender: Sender<Event<K, Doc>>, filter: SubscribeFilter<K, Doc>) -> Result<SubscriptionId, SessionResult> {
+        if self.off_reporter {
+            return Err(SessionResult::Err(StatusResult::ReporterIsOff));
+        }
+
+        self.register_subscriber(sender, filter.into_filter()).await
+    }
+
+    /// Subscribe to document changes kept by `Options::with_event_retention`, each sent with
+    /// its sequence: those after last_seen_seq first, then new ones as dispatched.
+    /// last_seen_seq 0 asks every change since store was first opened.
+    ///
+    /// `SessionResult::GapTooLarge` when some changes after last_seen_seq were evicted
+    /// (none are kept across reopen), or it's ahead of store (sequence restart when store
+    /// is reopened without `close`, or is RamCopies),
+    /// consumer must then resync from documents of store.
+    /// replay is sent once returned, router wait on sender like on any subscriber
+    pub async fn subscribe_from_seq(&self, sender: Sender<(u64, Event<K, Doc>)>, last_seen_seq: u64) -> Result<SubscriptionId, SessionResult> {
+        if self.off_reporter {
+            return Err(SessionResult::Err(StatusResult::ReporterIsOff));
+        }
+
+        self.reporter_session.resume(sender, last_seen_seq).await
+    }
+
+    /// remove subscriber of id, false if it wasn't subscribed
+    /// (unsubscribed before, or removed once its receiver was dropped)
+    pub async fn unsubscribe(&self, id: SubscriptionId) -> Result<bool, SessionResult> {
+        if self.off_reporter {
+            return Err(SessionResult::Err(StatusResult::ReporterIsOff));
+        }
+
+        self.reporter_session.unregister(id).await
+    }
+
+    pub(crate) async fn register_subscriber(&self, sender: Sender<Event<K, Doc>>, filter: Option<router::Filter<Event<K, Doc>>>) -> Result<SubscriptionId, SessionResult> {
+        // Send to Reporter
+        let _ = self
+            .reporter_session
+            .dispatch(Event::Subscribed(sender.clone()))
+            .await;
+
+        let id = self.reporter_session.register_filtered(sender, filter).await?;
+
+        if let Some(timers) = &self.timers {
+            timers.start();
+        }
+
+        Ok(id)
+    }
+
+    /// delivery metrics of subscribers
+    #[inline]
+    pub async fn subscriber_report(&self) -> Result<Vec<SubscriberInfo>, SessionResult> {
+        if self.off_reporter {
+            return Err(SessionResult::Err(StatusResult::ReporterIsOff));
+        }
+
+        self.reporter_session.report().await
+    }
+
+    /// insert to storage and persist to disk, through write plugins.
+    ///
+    /// overwriting a held key with insert is deprecated: it is logged and dispatched
+    /// as an Insert, not told apart from a creation. modify documents with `update`
+    /// or `compare_and_swap`, logged as `RQuery::Update`
+    #[inline]
+    pub async fn insert(&self, key: K, doc: Doc) -> Result<(), SessionResult> {
+        self.expire_if_due().await;
+        self.repair_if_pending().await;
+
+        let started = self.latency.start();
+        let res = self.insert_chain(key, doc).await;
+        self.latency.record(Operation::Insert, started);
+        res
+    }
+
+    async fn insert_chain(&self, key: K, doc: Doc) -> Result<(), SessionResult> {
+        if self.plugins.is_empty() {
+            let _admit = self.make_room(&[&key]).await?;
+            return self.apply_insert(key, doc).await;
+        }
+
+        let mut ctx = WriteContext { key, doc, metadata: HashMap::new() };
+        for plugin in self.plugins.iter() {
+            if let Err(reason) = plugin.on_insert(&mut ctx) {
+                return Err(SessionResult::PluginRejected { plugin: plugin.name().to_owned(), reason });
+            }
+        }
+
+        let _admit = self.make_room(&[&ctx.key]).await?;
+        self.apply_insert(ctx.key, ctx.doc).await
+    }
+
+    async fn apply_insert(&self, key: K, doc: Doc) -> Result<(), SessionResult> {
+
+        let _update = self.update_lock(&key).lock().await;
+        let _gate = self.rebuild_gate.read().await;
+
+        let previous = self.previous(&key);
+        self.log_insert(&key, &doc, previous.as_ref()).await?;
+        self.store_doc(key, doc).await
+    }
+
+    /// previous is document replaced by insert, read with Options::with_previous_values
+    #[inline]
+    async fn log_insert(&self, key: &K, doc: &Doc, previous: Option<&Doc>) -> Result<(), SessionResult> {
+        self.log_write(key, || RQuery::Insert(key.clone(), doc.clone()), previous).await
+    }
+
+    /// modification of held key, logged and dispatched as `RQuery::Update`
+    #[inline]
+    pub(crate) async fn log_update(&self, key: &K, doc: &Doc, previous: &Doc) -> Result<(), SessionResult> {
+        self.log_write(key, || RQuery::Update(key.clone(), doc.clone()), Some(previous)).await
+    }
+
+    /// doc is previous with patch applied
+    pub(crate) async fn log_patch(&self, key: &K, patch: DocPatch, doc: &Doc, previous: &Doc) -> Result<(), SessionResult> {
+        self.log_write(key, || RQuery::Patch(key.clone(), patch, doc.clone()), Some(previous)).await
+    }
+
+    // query built only if logged or dispatched
+    async fn log_write(&self, key: &K, query: impl FnOnce() -> RQuery<K, Doc>, previous: Option<&Doc>) -> Result<(), SessionResult> {
+        self.writable()?;
+
+        if let Some(coalescer) = &self.coalescer {
+            coalescer.stash(key.clone(), query());
+        }
+        else if !self.off_disk || !self.off_reporter {
+            let query = query();
+            let lane = self.lane_of(key);
+
+            if !self.off_disk {
+                self.wal_session.log_keyed(lane, query.to_record(self.codec)).await?;
+            }
+
+            if !self.off_reporter {
+                self.dispatch(lane, self.change_event(query, previous)).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// insert to derived structures and memory, caller hold rebuild_gate.
+    /// a ttl of key is disarmed, insert_with_ttl arm it again after
+    async fn store_doc(&self, key: K, doc: Doc) -> Result<(), SessionResult> {
+        let _writing = self.query_cache.as_ref().map(|cache| cache.write(self.stored(&key).as_ref(), Some(&doc)));
+
+        self.expiry.disarm(&key);
+        self.release(&key);
+
+        // Insert to indexes
+        if let Err(e) = self.hash_index.insert(&key, &doc) {
+            return Err(SessionResult::Err(e))
+        }
+
+        if !self.defer_derived {
+            self.insert_derived(&key, &doc).await;
+        }
+
+        if let Some(order) = &self.key_order {
+            order.insert(&key);
+        }
+
+        if let Some(capacity) = &self.capacity {
+            capacity.written(&key);
+        }
+
+        // before memory, so a reader never miss a stored key
+        if let Some(bloom) = &self.bloom {
+            bloom.insert(&key);
+        }
+
+        // held until document is in memory, so a watch of key start after write
+        let watcher = self.watchers.sender(&key).map(|sender| (sender, doc.clone()));
+
+        // Insert to memory
+        match &self.compression {
+            Some(compression) => {
+                self.compressed.insert(key, compression.compress(&doc));
+            }
+            None => {
+                self.collection.insert(key.clone(), doc);
+                // after memory, see EncodedOrder::insert
+                self.encoded_order.insert(&key);
+            }
+        }
+
+        if let Some((sender, doc)) = watcher {
+            sender.send_replace(Some(doc));
+        }
+
+        Ok(())
+    }
+
+    /// derived structures but hash index, before document is stored
+    async fn insert_derived(&self, key: &K, doc: &Doc) {
+        // Insert to view
+        if let Some(view_name) = doc.filter() {
+            self.tag_index.insert_view(&view_name, key)
+        }
+
+        // Insert to views of create_view, leaving those replaced document was in
+        self.views.each(|name, view| {
+            if view(key, doc) {
+                self.tag_index.insert_view(name, key)
+            } else {
+                self.tag_index.remove_from_view(name, key)
+            }
+        });
+
+
+        // Insert to variant, leaving variant of replaced document
+        if let Some(variant) = doc.variant() {
+            if let Some(old) = self.stored(key).and_then(|old| old.variant()).filter(|old| *old != variant) {
+                self.tag_index.remove_from_variant(old, key);
+            }
+            self.tag_index.insert_variant(variant, key)
+        }
+
+
+        // Insert to InvertedIndex, leaving words of replaced document
+        let content = doc.get_content();
+        let old = self.stored(key).and_then(|old| old.get_content());
+        if content.is_some() || old.is_some() {
+            for index in self.inverted_index.targets() {
+                let _ = index.insert(key.clone(), content.clone(), old.clone()).await;
+            }
+        }
+
+
+        // Insert to tag_index
+        self.tag_index.insert(key, doc);
+
+
+        // Insert to range
+        for index in self.range_index.targets() {
+            index.insert(key, doc);
+        }
+    }
+
+    /// remove from storage and persist to disk, through write plugins
+    #[inline]
+    pub async fn remove(&self, key: K) -> Result<(), SessionResult> {
+        self.expire_if_due().await;
+        self.repair_if_pending().await;
+
+        let started = self.latency.start();
+        let res = self.remove_chain(key).await;
+        self.latency.record(Operation::Remove, started);
+        res
+    }
+
+    async fn remove_chain(&self, key: K) -> Result<(), SessionResult> {
+        for plugin in self.plugins.iter() {
+            if let Err(reason) = plugin.on_remove(&key) {
+                return Err(SessionResult::PluginRejected { plugin: plugin.name().to_owned(), reason });
+            }
+        }
+
+        self.apply_remove(key).await
+    }
+
+    async fn apply_remove(&self, key: K) -> Result<(), SessionResult> {
+        let _update = self.update_lock(&key).lock().await;
+        self.remove_locked(&key).await.map(|_| ())
+    }
+
+    /// caller hold update_lock of key, false if key is missing
+    pub(crate) async fn remove_locked(&self, key: &K) -> Result<bool, SessionResult> {
+        let _gate = self.rebuild_gate.read().await;
+
+        // owned copy, a Ref must not be held across await
+        let doc = match self.stored(key) {
+            Some(doc) => doc,
+            None => return Ok(false),
+        };
+
+        self.log_remove(key, &doc).await?;
+        self.remove_derived(key, &doc).await;
+        self.forget(key);
+
+        Ok(true)
+    }
+
+    /// remove from memory, caller hold rebuild_gate
+    #[inline]
+    fn forget(&self, key: &K) {
+        let _writing = self.query_cache.as_ref().map(|cache| cache.write(self.stored(key).as_ref(), None));
+
+        self.expiry.disarm(key);
+        self.release(key);
+        self.tag_index.forget_edits(key);
+
+        let watcher = self.watchers.sender(key);
+        match &self.compression {
+            Some(_) => { self.compressed.remove(key); }
+            None => {
+                self.collection.remove(key);
+                self.encoded_order.remove(key);
+            }
+        }
+        if let Some(sender) = watcher {
+            sender.send_replace(None);
+        }
+        if let Some(order) = &self.key_order {
+            order.remove(key);
+        }
+        if let Some(capacity) = &self.capacity {
+            capacity.forget(key);
+        }
+    }
+
+    #[inline]
+    async fn log_remove(&self, key: &K, doc: &Doc) -> Result<(), SessionResult> {
+        self.writable()?;
+
+        if let Some(coalescer) = &self.coalescer {
+            coalescer.stash(key.clone(), RQuery::Remove(key.clone()));
+        }
+        else if !self.off_disk || !self.off_reporter {
+            let query = RQuery::<K, Doc>::Remove(key.clone());
+            let lane = self.lane_of(key);
+
+            if !self.off_disk {
+                self.wal_session.log_keyed(lane, self.codec.encode(&query)).await?;
+            }
+
+            if !self.off_reporter {
+                self.dispatch(lane, self.change_event(query, Some(doc))).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// document held by key when Options::with_previous_values, read before a write replace it
+    #[inline]
+    pub(crate) fn previous(&self, key: &K) -> Option<Doc> {
+        if self.previous_values && !self.off_reporter && self.coalescer.is_none() {
+            self.stored(key)
+        } else {
+            None
+        }
+    }
+
+    /// event of a logged query, `Removed` or `Replaced` carrying previous document
+    /// with Options::with_previous_values
+    pub(crate) fn change_event(&self, query: RQuery<K, Doc>, previous: Option<&Doc>) -> Event<K, Doc> {
+        match (query, previous) {
+            (RQuery::Remove(key), Some(old)) if self.previous_values => Event::Removed(key, old.clone()),
+            (RQuery::Insert(key, doc) | RQuery::Update(key, doc) | RQuery::Patch(key, _, doc), Some(old)) if self.previous_values => Event::Replaced(key, old.clone(), doc),
+            (query, _) => Event::Query(query),
+        }
+    }
+
+    /// hand event to Reporter, measured as Dispatch
+    #[inline]
+    pub(crate) async fn dispatch(&self, lane: usize, event: Event<K, Doc>) {
+        self.dispatch_removed(lane, event, None).await
+    }
+
+    /// dispatch with document a Remove or Expired event took away, read by tag filters
+    /// when it's no longer in memory
+    pub(crate) async fn dispatch_removed(&self, lane: usize, event: Event<K, Doc>, removed: Option<&Doc>) {
+        let started = self.latency.start();
+        let meta = self.event_meta(&event, removed);
+        let _ = self.reporter_session.dispatch_meta(lane, event, meta).await;
+        self.latency.record(Operation::Dispatch, started);
+    }
+
+    // metadata of document changes for subscribers filters, None without filters
+    fn event_meta(&self, event: &Event<K, Doc>, removed: Option<&Doc>) -> Option<EventMeta> {
+        let tags_of = |key: &K| match removed {
+            Some(doc) => self.tag_index.tags_of(key, doc),
+            None => self.tags_of(key).unwrap_or_default(),
+        };
+
+        match event {
+            Event::Query(RQuery::Insert(key, doc) | RQuery::Update(key, doc) | RQuery::Patch(key, _, doc)) => self.filtering.meta(Change::Insert, key, || self.tag_index.tags_of(key, doc)),
+            Event::Query(RQuery::Remove(key)) => self.filtering.meta(Change::Remove, key, || tags_of(key)),
+            Event::Replaced(key, _, doc) => self.filtering.meta(Change::Insert, key, || self.tag_index.tags_of(key, doc)),
+            Event::Removed(key, doc) => self.filtering.meta(Change::Remove, key, || self.tag_index.tags_of(key, doc)),
+            Event::Expired(key) => self.filtering.meta(Change::Expired, key, || tags_of(key)),
+
+            // document changed by its tags: tags of key and one edited
+            Event::Query(RQuery::TagAdd(key, tag) | RQuery::TagRemove(key, tag)) => self.filtering.meta(Change::Insert, key, || {
+                let mut tags = self.tags_of(key).unwrap_or_default();
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+                tags
+            }),
+            _ => None,
+        }
+    }
+
+    /// latency percentiles per operation since open or `reset_latency`,
+    /// empty unless built with `metrics` feature
+    pub fn latency_report(&self) -> LatencyReport {
+        self.latency.report()
+    }
+
+    pub fn reset_latency(&self) {
+        self.latency.reset();
+    }
+
+    /// read hits and misses, WAL enqueues since open, and reporter queue and subscribers now.
+    /// counters are relaxed, a snapshot taken during writes may be a few operations behind
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot(self.reporter_session.queued(), self.reporter_session.subscribers())
+    }
+
+    /// lane of key for WAL and Reporter
+    #[inline]
+    fn lane_of(&self, key: &K) -> usize {
+        if self.lanes == 1 {
+            return 0;
+        }
+
+        bucket(key, self.lanes)
+    }
+
+    /// stripe of update_locks serializing writes of key
+    #[inline]
+    fn update_lock(&self, key: &K) -> &tokio::sync::Mutex<()> {
+        &self.update_locks[self.update_stripe(key)]
+    }
+
+    #[inline]
+    fn update_stripe(&self, key: &K) -> usize {
+        bucket(key, UPDATE_STRIPES)
+    }
+
+    #[inline]
+    async fn remove_derived(&self, key: &K, doc: &Doc) {
+        // remove from invertedIndex
+        if let Some(content) = doc.get_content().filter(|_| !self.defer_derived) {
+            for index in self.inverted_index.targets() {
+                let _ = index.remove(key.clone(), content.clone()).await;
+            }
+        }
+
+        self.remove_derived_sync(key, doc);
+    }
+
+    /// derived structures but invertedIndex (removed by a spawned task)
+    fn remove_derived_sync(&self, key: &K, doc: &Doc) {
+        // remove from hash_index
+        self.hash_index.remove(doc);
+
+        if self.defer_derived {
+            return;
+        }
+
+        // remove from view
+        if let Some(view_name) = doc.filter() {
+            self.tag_index.remove_from_view(&view_name, key)
+        }
+        self.views.each(|name, _| self.tag_index.remove_from_view(name, key));
+
+        // remove from variant
+        if let Some(variant) = doc.variant() {
+            self.tag_index.remove_from_variant(variant, key)
+        }
+
+        // remove from tag_index
+        self.tag_index.remove(key, doc);
+
+        // remove to range
+        for index in self.range_index.targets() {
+            index.remove(key, doc);
+        }
+    }
+
+    /// gets documents
+    #[inline]
+    pub fn gets(&self, list: Vec<&K>) -> Vec<Ref<'_, K, Doc>> {
+        let mut result = Vec::with_capacity(list.len());
+
+        list.iter().for_each(|key| {
+            let rf = self.get_visible(key);
+            self.metrics.record(MetricEvent::Lookup { hit: rf.is_some() });
+            if let Some(r) = rf {
+                result.push(r);
+            }
+        });
+
+        result
+    }
+
+    /// fetch document by range hash_index, `String` values keep lexicographic compare,
+    /// numbers compare as numbers. empty when type of from or to differ from field,
+    /// see `try_range`
+    #[inline]
+    pub fn range(&self, field_name: &str, from: impl Into<FieldValue>, to: impl Into<FieldValue>) -> Vec<Ref<'_, K, Doc>> {
+        self.try_range(field_name, from, to).unwrap_or_default()
+    }
+
+    /// as `range`, `SessionResult::FieldTypeMismatch` when from or to don't have type
+    /// of values stored for field or field holds several types
+    #[inline]
+    pub fn try_range(&self, field_name: &str, from: impl Into<FieldValue>, to: impl Into<FieldValue>) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult> {
+        let started = self.latency.start();
+        let mut result = Vec::new();
+
+        // collect and distinct keys
+        for k in self.range_index.live().range(field_name, from.into(), to.into())? {
+            if let Some(r) = self.get_visible(&k) {
+                result.push(r);
+            }
+        }
+
+        self.latency.record(Operation::Range, started);
+        self.metrics.record(MetricEvent::Range { hit: !result.is_empty() });
+        Ok(result)
+    }
+
+    /// lookup by key.
+    ///
+    /// returned `Ref` hold a read lock on shard of key: holding it across an `.await`
+    /// or while writing to this store can deadlock, see `lookup_owned`.
+    /// with value compression documents aren't held to borrow, so `Ref` reads find none:
+    /// use `lookup_owned` and other owned reads, or `lookup_ref` to be told.
+    /// `Database` `Ref` reads tell with `CompressedValue`
+    #[inline]
+    pub fn lookup(&self, key: &K) -> Option<Ref<'_, K, Doc>> {
+        let started = self.latency.start();
+        let rf = self.get_visible(key);
+        self.latency.record(Operation::Lookup, started);
+        self.metrics.record(MetricEvent::Lookup { hit: rf.is_some() });
+        rf
+    }
+
+    /// `lookup`, `CompressedValue` with value compression instead of no document
+    #[inline]
+    pub fn lookup_ref(&self, key: &K) -> Result<Option<Ref<'_, K, Doc>>, SessionResult> {
+        self.borrowable()?;
+        Ok(self.lookup(key))
+    }
+
+    /// `CompressedValue` with value compression, documents aren't held for `Ref` reads to borrow
+    #[inline]
+    pub(crate) fn borrowable(&self) -> Result<(), SessionResult> {
+        match self.compression {
+            Some(_) => Err(SessionResult::CompressedValue),
+            None => Ok(()),
         }
+    }
+
+    /// lookup by key and return owned document, work with value compression,
+    /// no lock held once returned
+    #[inline]
+    pub fn lookup_owned(&self, key: &K) -> Option<Doc> {
+        let started = self.latency.start();
+        let doc = self.get_owned(key);
+        self.latency.record(Operation::Lookup, started);
+        self.metrics.record(MetricEvent::Lookup { hit: doc.is_some() });
+        doc
+    }
 
+    /// `lookup_owned` recording no metric, for reads made on behalf of other operations
+    #[inline]
+    pub(crate) fn get_owned(&self, key: &K) -> Option<Doc> {
+        let doc = self.visible_owned(key)?;
+        self.record_access(key);
+        Some(doc)
     }
 
-    /// subscribe to Reporter
+    /// `get_owned` not counted as a read of key, for writes and walks
     #[inline]
-    pub async fn subscribe(&self, sender: Sender<Event<K, Doc>>) -> Result<(), SessionResult> {
-        if self.off_reporter {
-            return Err(SessionResult::Err(StatusResult::ReporterIsOff));
+    pub(crate) fn visible_owned(&self, key: &K) -> Option<Doc> {
+        if self.never_inserted(key) || self.expired(key) || self.is_quarantined(key) {
+            return None;
         }
 
-        // Send to Reporter
-        let _ = self
-            .reporter_session
-            .dispatch(Event::Subscribed(sender.clone()))
-            .await;
+        let doc = self.stored(key)?;
+        self.check_read(key, &doc).then_some(doc)
+    }
 
-        self.reporter_session.register(sender).await
+    /// Changes of one key, receiver hold document after each insert, update or remove of it
+    /// (None once removed or expired) and start with current one.
+    ///
+    /// receivers of a key share a channel, dropped with its last receiver.
+    /// don't hold a `lookup` Ref while calling, a write of key can wait on watch of it.
+    /// must be called within a tokio runtime
+    pub fn watch(&self, key: K) -> tokio::sync::watch::Receiver<Option<Doc>>
+    where
+        Doc: Sync
+    {
+        self.watchers.watch(key.clone(), || self.get_owned(&key))
     }
 
-    /// insert to storage and persist to disk
     #[inline]
-    pub async fn insert(&self, key: K, doc: Doc) -> Result<(), SessionResult> {
+    fn record_access(&self, key: &K) {
+        if let Some(access) = &self.access {
+            access.record(key);
+        }
+        if let Some(capacity) = &self.capacity {
+            capacity.touch(key);
+        }
+    }
 
-        if !self.off_disk || !self.off_reporter {
-            let query = RQuery::Insert(key.clone(), doc.clone());
+    /// approximate reads of key since open, 0 without access tracking.
+    /// reads returning document by key count: lookups and documents of index, tag,
+    /// view, range and search results. walks (`iter`, exports) and writes reading
+    /// document they change (`update`, `compare_and_swap`, `remove_if`) don't
+    pub fn access_count(&self, key: &K) -> u64 {
+        self.access.as_ref().map_or(0, |access| access.count(key))
+    }
 
-            if !self.off_disk {
-                if let Err(e) = self.wal_session.log(bincode::serialize(&query).unwrap()).await {
-                    return Err(e);
-                }
+    /// n most read documents since open, see `AccessReport`.
+    /// walk keys in memory, empty report without access tracking
+    pub fn top_accessed(&self, n: usize) -> AccessReport<K> {
+        let access = match &self.access {
+            Some(access) => access,
+            None => return AccessReport { top: vec![], reads: 0, since: self.opened_at.into_std() },
+        };
+
+        // n largest counts, smallest on top to be evicted
+        let mut top: BinaryHeap<Reverse<(u64, K)>> = BinaryHeap::with_capacity(n + 1);
+        let mut offer = |key: &K| {
+            let count = access.count(key);
+            if count == 0 || n == 0 {
+                return;
             }
-
-            if !self.off_reporter {
-                let _ = self.reporter_session.dispatch(Event::Query(query)).await;
+            if top.len() < n {
+                top.push(Reverse((count, key.clone())));
+            } else if top.peek().is_some_and(|Reverse((least, _))| count > *least) {
+                top.pop();
+                top.push(Reverse((count, key.clone())));
             }
+        };
 
+        match &self.compression {
+            Some(_) => self.compressed.iter().for_each(|rf| offer(rf.key())),
+            None => self.collection.iter().for_each(|rf| offer(rf.key())),
         }
 
-    
-        // Insert to indexes
-        if let Err(e) = self.hash_index.insert(&key, &doc) {
-            return Err(SessionResult::Err(e))
-        }
-        
+        let mut top: Vec<(K, u64)> = top.into_iter().map(|Reverse((count, key))| (key, count)).collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
 
-        // Insert to view
-        if let Some(view_name) = doc.filter() {
-            self.tag_index.insert_view(&view_name, &key)
+        AccessReport { top, reads: access.reads(), since: access.since() }
+    }
+
+    /// document in memory, expired or not
+    #[inline]
+    pub(crate) fn stored(&self, key: &K) -> Option<Doc> {
+        match &self.compression {
+            Some(_) => {
+                let rf = self.compressed.get(key)?;
+                Compression::decompress(rf.value()).ok()
+            }
+            None => self.collection.get(key).map(|rf| rf.value().clone())
         }
+    }
+
+    /// check key exist, work with value compression
+    #[inline]
+    pub fn contains_key(&self, key: &K) -> bool {
+        !self.expired(key) && !self.is_quarantined(key) && self.holds(key)
+    }
 
+    /// definite miss of bloom filter, false without one
+    #[inline]
+    fn never_inserted(&self, key: &K) -> bool {
+        self.bloom.as_ref().is_some_and(|bloom| !bloom.may_contain(key))
+    }
+
+    // sized for keys loaded when more than expected
+    fn build_bloom(&self, expected: usize, rate: f64) -> Bloom {
+        let bloom = Bloom::new(expected.max(self.collection.len() + self.compressed.len()), rate);
+        self.collection.iter().for_each(|rf| bloom.insert(rf.key()));
+        self.compressed.iter().for_each(|rf| bloom.insert(rf.key()));
+        bloom
+    }
 
-        // Insert to InvertedIndex
-        if let Some(content) = doc.get_content() {
-            let _ = self.inverted_index.insert(key.clone(), content).await;
+    /// key in memory, expired or not
+    #[inline]
+    pub(crate) fn holds(&self, key: &K) -> bool {
+        if self.never_inserted(key) {
+            return false;
         }
 
+        match &self.compression {
+            Some(_) => self.compressed.contains_key(key),
+            None => self.collection.contains_key(key)
+        }
+    }
 
-        // Insert to tag_index
-        self.tag_index.insert(&key, &doc);
+    /// walk all documents, work with value compression.
+    /// keys snapshotted first so no shard guard held across yields,
+    /// documents removed meanwhile are skipped, quarantined ones walked and read repair not run
+    pub(crate) async fn for_each_doc(&self, mut f: impl FnMut(&K, &Doc)) {
+        let mut budget = Budget::default();
+        for key in self.keys() {
+            if self.expired(&key) {
+                continue;
+            }
+            if let Some(doc) = self.stored(&key) {
+                f(&key, &doc);
+            }
+            budget.tick().await;
+        }
+    }
 
+    /// `<path>/<name>`, files of store are kept beside it
+    pub(crate) fn wal_dir(&self) -> &str {
+        &self.wal_dir
+    }
 
-        // Insert to range
-        self.range_index.insert(&key, &doc);
+    /// run and sequence of last event kept by `Options::with_event_retention`
+    pub(crate) fn event_position(&self) -> Option<(u64, u64)> {
+        self.event_seq.as_ref().map(EventSeq::position)
+    }
 
+    /// wait until events dispatched before call reached subscribers channels
+    pub(crate) async fn flush_events(&self) -> Result<(), SessionResult> {
+        if self.off_reporter {
+            return Err(SessionResult::Err(StatusResult::ReporterIsOff));
+        }
 
-        // Insert to memory
-        self.collection.insert(key, doc);
+        self.reporter_session.flush().await
+    }
 
-        Ok(())
+    /// snapshot of all keys, work with value compression
+    pub(crate) fn keys(&self) -> Vec<K> {
+        match &self.compression {
+            Some(_) => self.compressed.iter().map(|rf| rf.key().clone()).collect(),
+            None => self.collection.iter().map(|rf| rf.key().clone()).collect(),
+        }
+    }
 
+    /// document of key unless past its ttl or quarantined, checked by read repair
+    #[inline]
+    pub(crate) fn get_visible(&self, key: &K) -> Option<Ref<'_, K, Doc>> {
+        if self.never_inserted(key) || self.expired(key) || self.is_quarantined(key) {
+            return None;
+        }
 
+        let rf = self.collection.get(key)?;
+        let visible = self.check_read(key, rf.value());
+        if visible {
+            self.record_access(key);
+        }
+        visible.then_some(rf)
     }
 
-    /// remove from storage and persist to disk
     #[inline]
-    pub async fn remove(&self, key: K) -> Result<(), SessionResult> {
-        match self.collection.get(&key) {
-            Some(doc) => {
+    fn expired(&self, key: &K) -> bool {
+        self.expiry.expired(key, || expiry::unix_ms(self.now()))
+    }
 
-                if !self.off_disk || !self.off_reporter {
-                    let query = RQuery::<K, Doc>::Remove(key.clone());
-        
-                    if !self.off_disk {
-                        if let Err(e) = self.wal_session.log(bincode::serialize(&query).unwrap()).await {
-                            return Err(e);
-                        }
-                    }
-        
-                    if !self.off_reporter {
-                        let _ = self.reporter_session.dispatch(Event::Query(query)).await;
-                    }
-                    
-                }
+    /// pause application of writes, writes already applying finish first
+    pub(crate) async fn pause_writes(&self) -> tokio::sync::RwLockWriteGuard<'_, ()> {
+        self.rebuild_gate.write().await
+    }
 
-                // remove from hash_index
-                self.hash_index.remove(doc.value());
+    /// lookup by hash_index
+    #[inline]
+    pub fn lookup_by_index(&self, index_key: &str) -> Option<Ref<'_, K, Doc>> {
+        let rf = match self.hash_index.lookup(index_key) {
+            Some(rf) => {
+                self.get_visible(rf.value())
+            }
+            None => None
+        };
+        self.metrics.record(MetricEvent::LookupByIndex { hit: rf.is_some() });
+        rf
+    }
 
-                // remove from view
-                if let Some(view_name) = doc.filter() {
-                    self.tag_index.remove_from_view(&view_name, &key)
-                }
+    /// documents with an index key starting with prefix, in index key order,
+    /// each once. every indexed document for empty prefix
+    pub fn lookup_by_index_prefix(&self, prefix: &str) -> Vec<Ref<'_, K, Doc>> {
+        let mut seen = HashSet::new();
+        let result: Vec<_> = self.hash_index
+            .lookup_prefix(prefix)
+            .into_iter()
+            .filter(|key| seen.insert(key.clone()))
+            .filter_map(|key| self.get_visible(&key))
+            .collect();
+        self.metrics.record(MetricEvent::LookupByIndex { hit: !result.is_empty() });
+        result
+    }
 
-                // remove from invertedIndex
-                if let Some(content) = doc.value().get_content() {
-                    let _ = self.inverted_index.remove(key.clone(), content).await;
+    /// lookup by tag, `Ref`s lock shards as `lookup` does, see `lookup_by_tag_owned`
+    #[inline]
+    pub fn lookup_by_tag(&self, tag: &str) -> Vec<Ref<'_, K, Doc>> {
+        let result = match self.tag_index.lookup(tag) {
+            Some(rf) => {
+                let mut result = Vec::with_capacity(rf.value().len());
+                for k in rf.value().iter() {
+                    if let Some(kd) = self.get_visible(&k) {
+                        result.push(kd);
+                    }  
                 }
+                result
+            }
+            None => vec![]
+        };
+        self.metrics.record(MetricEvent::LookupByTag { hit: !result.is_empty() });
+        result
+    }
 
-                // remove from tag_index
-                self.tag_index.remove(&key, doc.value());
-
-                // remove to range
-                self.range_index.remove(&key, doc.value());
+    /// documents carrying every tag, empty if tags is empty.
+    ///
+    /// starts from smallest tag set and probe others one at a time,
+    /// a single tag set is locked at once, see `select`
+    pub fn lookup_by_tags_all(&self, tags: &[&str]) -> Vec<Ref<'_, K, Doc>> {
+        let probes: Vec<Probe> = tags.iter().map(|tag| Probe::Tag((*tag).to_owned())).collect();
+        let result: Vec<_> = self.select(&probes).iter().filter_map(|k| self.get_visible(k)).collect();
+        self.metrics.record(MetricEvent::LookupByTag { hit: !result.is_empty() });
+        result
+    }
 
+    /// documents carrying any of tags, each once
+    pub fn lookup_by_tags_any(&self, tags: &[&str]) -> Vec<Ref<'_, K, Doc>> {
+        let mut seen = HashSet::new();
+        let mut result = vec![];
+        for tag in tags {
+            for k in self.tag_keys(tag) {
+                if seen.insert(k.clone()) {
+                    if let Some(kd) = self.get_visible(&k) {
+                        result.push(kd);
+                    }
+                }
             }
-            None => return Ok(()),
         }
+        self.metrics.record(MetricEvent::LookupByTag { hit: !result.is_empty() });
+        result
+    }
 
-        self.collection.remove(&key);
+    /// keys refer to tag
+    #[inline]
+    pub(crate) fn tag_keys(&self, tag: &str) -> Vec<K> {
+        match self.tag_index.lookup(tag) {
+            Some(rf) => rf.value().iter().map(|k| k.key().clone()).collect(),
+            None => vec![]
+        }
+    }
 
-        Ok(())
+    /// key refer to index_key
+    #[inline]
+    pub(crate) fn index_keys(&self, index_key: &str) -> Vec<K> {
+        match self.hash_index.lookup(index_key) {
+            Some(rf) => vec![rf.value().clone()],
+            None => vec![]
+        }
     }
 
-    /// gets documents  
+    /// keys in range
     #[inline]
-    pub fn gets(&self, list: Vec<&K>) -> Vec<Ref<K, Doc>> {
-        let mut result = Vec::with_capacity(list.len());
+    pub(crate) fn range_keys(&self, field_name: &str, from: FieldValue, to: FieldValue) -> Result<Vec<K>, SessionResult> {
+        self.range_index.live().range(field_name, from, to)
+    }
 
-        list.iter().for_each(|key| {
-            if let Some(r) = self.collection.get(key) {
-                result.push(r);
-            }
-        });
+    /// keys ordered by range field
+    #[inline]
+    pub(crate) fn range_order(&self, field_name: &str, desc: bool) -> Vec<K> {
+        self.range_index.live().ordered(field_name, desc)
+    }
 
-        result
+    /// keys contain any word of text, most occurrences first
+    #[inline]
+    pub(crate) fn search_keys(&self, text: &str) -> Vec<K> {
+        self.inverted_index.live().search(text)
     }
 
-    /// fetch document by range hash_index
+    /// query builder over tags, index, range and search
     #[inline]
-    pub fn range(&self, field_name: &str, from: String, to: String) -> Vec<Ref<K, Doc>> {
-        let mut result = Vec::new();
+    pub fn find(&self) -> QueryBuilder<'_, K, Doc> {
+        QueryBuilder::new(self)
+    }
 
-        // collect and distinct keys
-        for k in self.range_index.range(field_name, from, to) {
-            if let Some(r) = self.collection.get(&k) {
-                result.push(r);
-            }
+    #[inline]
+    pub(crate) fn query_cache(&self) -> Option<&QueryCache<K>> {
+        self.query_cache.as_ref()
+    }
+
+    /// hits and misses of `QueryBuilder::cached` since open, zero without query cache
+    pub fn query_cache_stats(&self) -> QueryCacheStats {
+        self.query_cache.as_ref().map(|cache| cache.stats()).unwrap_or_default()
+    }
+
+    /// drop every cached query result, statistics are kept
+    pub fn clear_query_cache(&self) {
+        if let Some(cache) = &self.query_cache {
+            cache.clear();
         }
+    }
 
-        result
+    /// stream documents contain text, see `ScanStream`
+    #[inline]
+    pub fn search_stream(&self, text: &str) -> ScanStream<'_, K, Doc> {
+        ScanStream::new(self, self.search_keys(text))
     }
 
-    /// lookup by key
+    /// stream documents have tag
     #[inline]
-    pub fn lookup(&self, key: &K) -> Option<Ref<K, Doc>> {
-        return self.collection.get(key);
+    pub fn tag_stream(&self, tag: &str) -> ScanStream<'_, K, Doc> {
+        ScanStream::new(self, self.tag_keys(tag))
     }
 
-    /// lookup by hash_index
+    /// stream documents of view
     #[inline]
-    pub fn lookup_by_index(&self, index_key: &str) -> Option<Ref<K, Doc>> {
-        match self.hash_index.lookup(index_key) {
-            Some(rf) => {
-                self.collection.get(rf.value())
-            }
-            None => None
-        }
+    pub fn view_stream(&self, view_name: &str) -> ScanStream<'_, K, Doc> {
+        ScanStream::new(self, self.tag_keys(&self.tag_index.view_key_maker(view_name)))
     }
 
-    /// lookup by tag
+    /// stream documents with field in [from, to), ordered by key, empty on type mismatch as `range`
     #[inline]
-    pub fn lookup_by_tag(&self, tag: &str) -> Vec<Ref<K, Doc>> {
-        match self.tag_index.lookup(tag) {
+    pub fn range_stream(&self, field_name: &str, from: impl Into<FieldValue>, to: impl Into<FieldValue>) -> ScanStream<'_, K, Doc> {
+        ScanStream::new(self, self.range_keys(field_name, from.into(), to.into()).unwrap_or_default())
+    }
+
+    /// documents of variant, see `Document::variant`
+    #[inline]
+    pub fn lookup_by_variant(&self, variant: &str) -> Vec<Ref<'_, K, Doc>> {
+        match self.tag_index.lookup_variant(variant) {
             Some(rf) => {
                 let mut result = Vec::with_capacity(rf.value().len());
                 for k in rf.value().iter() {
-                    if let Some(kd) = self.collection.get(&k) {
+                    if let Some(kd) = self.get_visible(&k) {
                         result.push(kd);
-                    }  
+                    }
                 }
                 result
             }
@@ -286,44 +1650,99 @@ where
         }
     }
 
-    /// fetch view
+    /// documents per variant of `Document::VARIANTS`, in declaration order
+    pub fn count_by_variant(&self) -> Vec<(&'static str, usize)> {
+        Doc::VARIANTS
+            .iter()
+            .map(|variant| {
+                let count = match self.tag_index.lookup_variant(variant) {
+                    Some(rf) => rf.value().iter().filter(|k| self.contains_key(k.key())).count(),
+                    None => 0,
+                };
+                (*variant, count)
+            })
+            .collect()
+    }
+
+    /// fetch view, `Ref`s lock shards as `lookup` does, see `fetch_view_owned`
     #[inline]
-    pub fn fetch_view(&self, view_name: &str) -> Vec<Ref<K, Doc>> {
-        match self.tag_index.lookup_view(view_name) {
+    pub fn fetch_view(&self, view_name: &str) -> Vec<Ref<'_, K, Doc>> {
+        let result = match self.tag_index.lookup_view(view_name) {
             Some(rf) => {
                 let mut result = Vec::with_capacity(rf.value().len());
                 for k in rf.value().iter() {
-                    if let Some(kd) = self.collection.get(&k) {
+                    if let Some(kd) = self.get_visible(&k) {
                         result.push(kd);
                     }  
                 }
                 result
             }
             None => vec![]
-        }
+        };
+        self.metrics.record(MetricEvent::View { hit: !result.is_empty() });
+        result
+    }
+
+
+    /// search by text, documents containing any word of it, most occurrences first.
+    /// words are split on whitespace and punctuation and matched case-insensitively,
+    /// text is borrowed, a `&str` need no allocation
+    #[inline]
+    pub fn search(&self, text: impl AsRef<str>) -> Vec<Ref<'_, K, Doc>> {
+        let started = self.latency.start();
+        let keys = self.search_keys(text.as_ref());
+        let result = self.search_result(keys);
+
+        self.latency.record(Operation::Search, started);
+        self.metrics.record(MetricEvent::Search { hit: !result.is_empty() });
+        result
+    }
+
+    /// `search` with text typed `String`, for callers passing `.into()` that no longer infer
+    #[deprecated(since = "6.2.0", note = "use `search`, text is borrowed")]
+    #[inline]
+    pub fn search_string(&self, text: String) -> Vec<Ref<'_, K, Doc>> {
+        self.search(text)
+    }
+
+    /// `range` with bounds typed `String`, for callers passing `.into()` that no longer infer
+    #[deprecated(since = "6.2.0", note = "use `range`, bounds take `&str` and numbers")]
+    #[inline]
+    pub fn range_string(&self, field_name: &str, from: String, to: String) -> Vec<Ref<'_, K, Doc>> {
+        self.range(field_name, from, to)
     }
 
+    /// search by `SearchQuery`, e.g. `"rust AND (storage OR database)".parse()?`,
+    /// documents with most occurrences of words matched first
+    #[inline]
+    pub fn search_query(&self, query: &SearchQuery) -> Vec<Ref<'_, K, Doc>> {
+        let started = self.latency.start();
+        let keys = self.inverted_index.live().query(query);
+        let result = self.search_result(keys);
+
+        self.latency.record(Operation::Search, started);
+        self.metrics.record(MetricEvent::Search { hit: !result.is_empty() });
+        result
+    }
 
-    /// search by text
     #[inline]
-    pub fn search(&self, text: String) -> Vec<Ref<K, Doc>> {
-        let words: Vec<&str> = text.split_whitespace().collect();
-        let keys = self.inverted_index.search(words);
+    fn search_result(&self, keys: Vec<K>) -> Vec<Ref<'_, K, Doc>> {
         let mut result = Vec::with_capacity(keys.len());
-        
         for key in keys {
-            if let Some(rd) = self.collection.get(&key) {
+            if let Some(rd) = self.get_visible(&key) {
                 result.push(rd);
             }
         }
-
         result
     }
 
-    /// return Iter (Safe for mutation)
+    /// return Iter (Safe for mutation), without documents past their ttl or quarantined.
+    /// empty with value compression, see `iter_keys_owned`
     #[inline]
-    pub fn iter(&self) -> Iter<'_, K, Doc> {
-        self.collection.iter()
+    pub fn iter(&self) -> impl Iterator<Item = RefMulti<'_, K, Doc>> + '_ {
+        self.collection
+            .iter()
+            .filter(|rf| !self.expired(rf.key()) && !self.is_quarantined(rf.key()) && self.check_read(rf.key(), rf.value()))
     }
 
     /// return Iter (Safe for mutation)
@@ -339,9 +1758,44 @@ where
     }
 
     
+    /// documents in store, see `len`
     #[inline]
     pub fn collection_len(self) -> usize {
-        self.collection.len()
+        self.len()
+    }
+
+    /// documents in store, without documents past their ttl or quarantined
+    #[inline]
+    pub fn len(&self) -> usize {
+        let len = match &self.compression {
+            Some(_) => self.compressed.len(),
+            None => self.collection.len(),
+        };
+        let quarantined = self.repair.as_ref().map_or(0, |repair| repair.in_quarantine());
+        len.saturating_sub(self.expiry.due_count(expiry::unix_ms(self.now())) + quarantined)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// index keys in hash index, one per indexed key of every stored document
+    #[inline]
+    pub fn index_len(&self) -> usize {
+        self.hash_index.len()
+    }
+
+    /// distinct tags, without views and variants. walk tag names, not their keys
+    #[inline]
+    pub fn tags_len(&self) -> usize {
+        self.tag_index.tags_len()
+    }
+
+    /// keys in view, may count documents past their ttl or quarantined
+    #[inline]
+    pub fn view_len(&self, view_name: &str) -> usize {
+        self.tag_index.view_len(view_name)
     }
 
 
@@ -354,16 +1808,25 @@ where
 
     /// load storage from disk
     #[inline]
-    async fn loader(&self) -> Result<(), String> {
+    async fn loader(&self, from_page: usize, budget: Option<&IoBudget>, progress: Option<&LoadProgress>) -> Result<RecoveryReport, String> {
         // when storage just open with Disc Copies option it call loader, else it don't call
         let wal = &self.wal_session;
+        let mut report = RecoveryReport::new(self.recovery.mode);
 
-        let mut page_index = 1;
+        let mut page_index = from_page;
+        if let Some(progress) = progress {
+            (1..from_page).for_each(|_| progress.page_done());
+        }
 
         loop {
-            // Get Page
-            let mut logfile = match wal.get_page(page_index).await {
-                Ok(lf) => lf,
+            if let Some(budget) = budget {
+                budget.acquire().await;
+            }
+
+            // Get Page, read raw so a torn record is seen
+            let bytes = match wal.read_page(page_index).await {
+                Ok(bytes) => bytes,
+                Err(SessionResult::Err(StatusResult::End)) => return Ok(report),
                 Err(sess_res) => {
                     if let SessionResult::Err(e) = sess_res {
                         return Err(e.to_string())
@@ -373,72 +1836,231 @@ where
                 }
             };
 
+            let page = page_index;
             page_index += 1;
 
-            // Must Call Recover if return Err, remove unwrap()
-            let iter = match logfile.iter(..) {
-                Ok(iter) => iter,
-                Err(e) => {
-                    eprintln!("==> {:?}", e);
-                    return Err(e.to_string());
-                }
-            };
-
-            for qline in iter {
-                let bytes = match qline {
-                    Ok(ql)  => ql,
-                    Err(e) => return Err(e.to_string()),
+            for (offset, frame) in frames(&bytes) {
+                let record = match frame {
+                    Frame::Record(data) => match WalCodec::of_header(data) {
+                        // codec header of page
+                        Some(Ok(codec)) if codec == self.codec => continue,
+                        Some(Ok(stored)) => {
+                            return Err(format!("WalCodecMismatch page {} stored {} requested {}", page, stored.name(), self.codec.name()));
+                        }
+                        Some(Err(e)) => return Err(format!("page {}: {}", page, e)),
+                        None => self.codec.decode::<LogRecord<K, Doc>>(data),
+                    },
+                    Frame::BadChecksum => Err("bad checksum".to_owned()),
+                    Frame::Torn => Err("torn record".to_owned()),
                 };
 
-                let query: RQuery<K, Doc> = match bincode::deserialize(&bytes) {
-                    Ok(rq) => rq,
-                    Err(e) => {
-                        return Err(e.to_string());
-                    }
+                let record = match record {
+                    Ok(record) => record,
+                    Err(e) => match report.mode {
+                        RecoveryMode::Strict => {
+                            return Err(format!("corrupted WAL record, page {} offset {}: {}", page, offset, e));
+                        }
+                        RecoveryMode::SkipCorrupted => {
+                            eprintln!("loader: skipped corrupted WAL record, page {} offset {}: {}", page, offset, e);
+                            report.skipped += 1;
+                            continue;
+                        }
+                        RecoveryMode::TruncateAtCorruption => {
+                            eprintln!("loader: WAL truncated at corrupted record, page {} offset {}: {}", page, offset, e);
+                            report.pages_removed = wal.truncate(page, offset).await.map_err(|e| e.to_string())?;
+                            report.truncated_at = Some((page, offset));
+                            return Ok(report);
+                        }
+                    },
                 };
+                report.recovered += 1;
+
+                // expired while down: removed, so an older version of key isn't left
+                if let LogRecord::InsertWithExpiry(key, doc, at) = record {
+                    let at = recovery::rebase(at, self.rebase_ms);
+                    if at <= expiry::unix_ms(self.now()) {
+                        let _ = self.apply_remove(key).await;
+                    } else {
+                        if let Some(old) = self.stored(&key) {
+                            self.remove_derived(&key, &old).await;
+                        }
+                        let _ = self.apply_insert(key.clone(), doc.clone()).await;
+                        self.expiry.arm(key, at, doc);
+                    }
+                    continue;
+                }
 
-                match query {
-                    RQuery::Insert(key, doc) => {                        
-                        let _ = self.insert(key, doc).await;
+                // on top of last version of key, a patch of a missing key or one failing is skipped
+                if let LogRecord::Patch(key, patch) = record {
+                    if let Some(old) = self.stored(&key) {
+                        let mut doc = old.clone();
+                        match patch.apply(&mut doc) {
+                            Ok(_) => {
+                                self.remove_derived(&key, &old).await;
+                                let _ = self.apply_insert(key, doc).await;
+                            }
+                            Err(e) => eprintln!("loader: skipped patch, page {} offset {}: {}", page, offset, e),
+                        }
                     }
-                    RQuery::Remove(key) => {
-                        let _ = self.remove(key).await;
+                    continue;
+                }
+
+                for query in record.into_queries() {
+                    match query {
+                        RQuery::Insert(key, doc) | RQuery::Update(key, doc) | RQuery::Patch(key, _, doc) => {
+                            // an Insert may overwrite key too, replace what it held
+                            if let Some(old) = self.stored(&key) {
+                                self.remove_derived(&key, &old).await;
+                            }
+                            let _ = self.apply_insert(key, doc).await;
+                        }
+                        RQuery::Remove(key) => {
+                            let _ = self.apply_remove(key).await;
+                        }
+                        RQuery::Clear => {
+                            self.forget_all().await;
+                        }
+                        RQuery::TagAdd(key, tag) => {
+                            self.edit_tag(&key, &tag, true);
+                        }
+                        RQuery::TagRemove(key, tag) => {
+                            self.edit_tag(&key, &tag, false);
+                        }
                     }
                 }
             }
+
+            if let Some(progress) = progress {
+                progress.page_done();
+            }
         }
     }
 }
 
 // used for log to disk
 #[derive(Serialize, Deserialize, Clone)]
+#[non_exhaustive]
 pub enum RQuery<K, Doc> {
     Insert(K, Doc),
     Remove(K),
+
+    // new version of a held document, by Storage::update and compare_and_swap,
+    // applied like Insert
+    Update(K, Doc),
+
+    // Storage::patch: patch and document it produced, applied like Update.
+    // logged as patch alone (LogRecord::Patch), in a transaction as Update
+    Patch(K, DocPatch, Doc),
+
+    // Storage::clear, every document logged before it removed
+    Clear,
+
+    // Storage::add_tag and remove_tag, tag of a held document
+    TagAdd(K, String),
+    TagRemove(K, String),
 }
 
 impl<K, Doc> RQuery<K, Doc> {
     
-    pub fn from_raw(type_id: &'static str, key: K, doc: Option<Doc>) -> RQuery<K, Doc> {
+    /// query of `into_raw` parts, for memory pages only: panic on unknown type_id
+    pub(crate) fn from_raw(type_id: &'static str, key: K, doc: Option<Doc>) -> RQuery<K, Doc> {
         match type_id {
             RQUERY_INSERT_TYPE => RQuery::Insert(key, doc.unwrap()),
             RQUERY_REMOVE_TYPE => RQuery::Remove(key),
+            RQUERY_UPDATE_TYPE => RQuery::Update(key, doc.unwrap()),
             _ => panic!("failed")
         }
     }
 
-    pub fn into_raw(self) -> (&'static str, K, Option<Doc>) {
+    /// parts of Insert, Remove, Update or Patch, panic on other variants
+    pub(crate) fn into_raw(self) -> (&'static str, K, Option<Doc>) {
         match self {
             RQuery::Insert(k, d) => (RQUERY_INSERT_TYPE, k, Some(d)),
             RQuery::Remove(k) => (RQUERY_REMOVE_TYPE, k, None),
+            RQuery::Update(k, d) | RQuery::Patch(k, _, d) => (RQUERY_UPDATE_TYPE, k, Some(d)),
+            RQuery::Clear => unreachable!("RQuery::Clear has no key"),
+            RQuery::TagAdd(..) | RQuery::TagRemove(..) => unreachable!("tag edits have no document"),
+        }
+    }
+
+}
+
+impl<K: Serialize, Doc: Serialize> RQuery<K, Doc> {
+    /// WAL record of query alone, an Update is written as `LogRecord::Update`:
+    /// its own variant index is the one of `LogRecord::Transaction`, a Patch without its document
+    pub(crate) fn to_record(&self, codec: WalCodec) -> Vec<u8> {
+        match self {
+            RQuery::Update(key, doc) => codec.encode(&LogRecord::Update(key, doc)),
+            RQuery::Patch(key, patch, _) => codec.encode(&LogRecord::<&K, &Doc>::Patch(key, patch.clone())),
+            RQuery::Clear => codec.encode(&LogRecord::<K, Doc>::Clear),
+            RQuery::TagAdd(key, tag) => codec.encode(&LogRecord::<&K, Doc>::TagAdd(key, tag.clone())),
+            RQuery::TagRemove(key, tag) => codec.encode(&LogRecord::<&K, Doc>::TagRemove(key, tag.clone())),
+            query => codec.encode(query),
+        }
+    }
+}
+
+
+// WAL record read by loader, Insert and Remove encode exactly like RQuery's,
+// so a WAL written before transactions existed replay unchanged
+#[derive(Serialize, Deserialize)]
+pub(crate) enum LogRecord<K, Doc> {
+    Insert(K, Doc),
+    Remove(K),
+
+    // Storage::transaction, applied whole or not at all
+    Transaction(Vec<RQuery<K, Doc>>),
+
+    // Storage::insert_with_ttl, deadline in millis since UNIX_EPOCH
+    InsertWithExpiry(K, Doc, u64),
+
+    // Storage::insert_batch, every document of batch
+    InsertBatch(Vec<(K, Doc)>),
+
+    // RQuery::Update
+    Update(K, Doc),
+
+    // RQuery::Patch, applied by replay to document of key as replayed so far
+    Patch(K, DocPatch),
+
+    // RQuery::Clear
+    Clear,
+
+    // RQuery::TagAdd and TagRemove
+    TagAdd(K, String),
+    TagRemove(K, String),
+}
+
+impl<K, Doc> LogRecord<K, Doc> {
+    pub fn into_queries(self) -> Vec<RQuery<K, Doc>> {
+        match self {
+            LogRecord::Insert(key, doc) => vec![RQuery::Insert(key, doc)],
+            LogRecord::Remove(key) => vec![RQuery::Remove(key)],
+            LogRecord::Transaction(queries) => queries,
+            LogRecord::InsertWithExpiry(key, doc, _) => vec![RQuery::Insert(key, doc)],
+            LogRecord::InsertBatch(docs) => docs.into_iter().map(|(key, doc)| RQuery::Insert(key, doc)).collect(),
+            LogRecord::Update(key, doc) => vec![RQuery::Update(key, doc)],
+
+            // no document to carry, caller apply it to stored one
+            LogRecord::Patch(..) => vec![],
+            LogRecord::Clear => vec![RQuery::Clear],
+            LogRecord::TagAdd(key, tag) => vec![RQuery::TagAdd(key, tag)],
+            LogRecord::TagRemove(key, tag) => vec![RQuery::TagRemove(key, tag)],
         }
     }
 
+    pub fn expires_at(&self) -> Option<u64> {
+        match self {
+            LogRecord::InsertWithExpiry(_, _, at) => Some(*at),
+            _ => None,
+        }
+    }
 }
 
 
 pub const RQUERY_INSERT_TYPE: &'static str = "Insert";
 pub const RQUERY_REMOVE_TYPE: &'static str = "Remove";
+pub const RQUERY_UPDATE_TYPE: &str = "Update";
 
 
 
@@ -446,9 +2068,44 @@ pub const RQUERY_REMOVE_TYPE: &'static str = "Remove";
 
 // used for reporting
 #[derive(Clone)]
+#[non_exhaustive]
 pub enum Event<K, Doc> {
     Query(RQuery<K, Doc>),
     Subscribed(Sender<Event<K, Doc>>), 
+    Lagging(SubscriberInfo),
+
+    // subscriber left: unsubscribed, or removed once its receiver was dropped
+    SubscriberDropped(SubscriptionId),
+
+    // timer of key armed by Storage::notify_at reached deadline
+    Timer(K),
+
+    // with Options::with_previous_values, in place of Query(Remove) and BulkRemove:
+    // key and document it held
+    Removed(K, Doc),
+
+    // with Options::with_previous_values, in place of Query(Insert) of a held key:
+    // key, previous document, new document
+    Replaced(K, Doc, Doc),
+
+    // keys removed by a batch of a bulk removal (remove_keys, remove_where, remove_prefix)
+    BulkRemove(Vec<K>),
+
+    // key removed because its ttl passed, after its Query(Remove)
+    Expired(K),
+
+    // document of key hidden by read repair hook
+    Quarantined(K),
+
+    // progress of Storage::compact
+    Compacting(CompactPhase),
+}
+
+impl<K, Doc> Event<K, Doc> {
+    // document changes, what Options::with_event_retention keep
+    fn is_change(&self) -> bool {
+        matches!(self, Event::Query(_) | Event::Removed(..) | Event::Replaced(..) | Event::BulkRemove(_) | Event::Expired(_) | Event::Quarantined(_))
+    }
 }
 
 
@@ -0,0 +1,260 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::HashSet, hash::Hash};
+
+use crate::{darkbird::{coop::Budget, search::tokenize}, document::{Document, FieldValue}};
+
+use super::Storage;
+
+
+
+/// derived structure an audit entry belong to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Structure {
+    // hash_index (index_key)
+    Index,
+
+    // tag_index (tag or view)
+    Tags,
+
+    // range_index (field=value, field#Int=value for numbers)
+    Range,
+
+    // inverted_index (word)
+    Text,
+}
+
+
+#[derive(Clone, Debug)]
+pub struct AuditEntry<K> {
+    pub structure: Structure,
+    pub name: String,
+    pub key: K,
+}
+
+
+/// result of `Storage::audit`
+#[derive(Debug)]
+pub struct AuditReport<K> {
+    // total documents walked
+    pub documents: usize,
+
+    // entries document must have but derived structure don't
+    pub missing: Vec<AuditEntry<K>>,
+
+    // entries refer to a document that don't produce them anymore
+    pub stale: Vec<AuditEntry<K>>,
+
+    // entries refer to an absent document
+    pub orphaned: Vec<AuditEntry<K>>,
+
+    // discrepancies fixed
+    pub repaired: bool,
+}
+
+impl<K> AuditReport<K> {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.stale.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+
+pub(super) type Entry<K> = (Structure, String, K);
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// recompute derived entries of every document and compare with live structures,
+    /// safe on a live store but concurrent writes may appear as discrepancies,
+    /// yield to runtime periodically while walking documents
+    pub async fn audit(&self, repair: bool) -> AuditReport<K> {
+        let op = self.admin.start("audit", format!("repair={}", repair));
+        let report = self.reconcile(None, repair).await;
+        self.admin.finish(op, Ok(0));
+        report
+    }
+
+    /// audit restricted to one structure when `only` set
+    pub(super) async fn reconcile(&self, only: Option<Structure>, repair: bool) -> AuditReport<K> {
+        let selected = |entry: &Entry<K>| only.is_none_or(|s| s == entry.0);
+
+        let mut documents = 0;
+        let mut expected: HashSet<Entry<K>> = HashSet::new();
+
+        self.for_each_doc(|key, doc| {
+            documents += 1;
+            expected.extend(self.derived_entries(key, doc).into_iter().filter(selected));
+        })
+        .await;
+
+        let mut live = self.live_entries();
+        live.retain(selected);
+
+        let missing: Vec<Entry<K>> = expected.iter().filter(|e| !live.contains(*e)).cloned().collect();
+
+        let mut stale = vec![];
+        let mut orphaned = vec![];
+        for entry in live.into_iter() {
+            if expected.contains(&entry) {
+                continue;
+            }
+            if self.holds(&entry.2) {
+                stale.push(entry);
+            } else {
+                orphaned.push(entry);
+            }
+        }
+
+        if repair {
+            let mut budget = Budget::default();
+            for entry in missing.iter() {
+                self.insert_entry(entry);
+                budget.tick().await;
+            }
+            for entry in stale.iter().chain(orphaned.iter()) {
+                self.remove_entry(entry);
+                budget.tick().await;
+            }
+        }
+
+        AuditReport {
+            documents,
+            missing: into_audit_entries(missing),
+            stale: into_audit_entries(stale),
+            orphaned: into_audit_entries(orphaned),
+            repaired: repair,
+        }
+    }
+
+    /// entries document produce via Document trait
+    fn derived_entries(&self, key: &K, doc: &Doc) -> Vec<Entry<K>> {
+        let mut result = vec![];
+
+        for ik in doc.extract() {
+            result.push((Structure::Index, ik, key.clone()));
+        }
+
+        for tag in self.tag_index.tags_of(key, doc) {
+            result.push((Structure::Tags, tag, key.clone()));
+        }
+
+        if let Some(view_name) = doc.filter() {
+            result.push((Structure::Tags, self.tag_index.view_key_maker(&view_name), key.clone()));
+        }
+
+        self.views.each(|name, view| {
+            if view(key, doc) {
+                result.push((Structure::Tags, self.tag_index.view_key_maker(name), key.clone()));
+            }
+        });
+
+        if let Some(variant) = doc.variant() {
+            result.push((Structure::Tags, self.tag_index.variant_key_maker(variant), key.clone()));
+        }
+
+        for rf in doc.get_typed_fields() {
+            result.push((Structure::Range, range_name(&rf.name, &rf.value), key.clone()));
+        }
+
+        if let Some(content) = doc.get_content() {
+            for word in tokenize(&content) {
+                result.push((Structure::Text, word, key.clone()));
+            }
+        }
+
+        result
+    }
+
+    pub(super) fn live_entries(&self) -> HashSet<Entry<K>> {
+        let mut result = HashSet::new();
+
+        for rf in self.hash_index.iter() {
+            result.insert((Structure::Index, rf.key().clone(), rf.value().clone()));
+        }
+
+        for rf in self.tag_index.iter() {
+            for k in rf.value().iter() {
+                result.insert((Structure::Tags, rf.key().clone(), k.key().clone()));
+            }
+        }
+
+        for (field_name, value, k) in self.range_index.live().entries() {
+            result.insert((Structure::Range, range_name(&field_name, &value), k));
+        }
+
+        for (word, k) in self.inverted_index.live().entries() {
+            result.insert((Structure::Text, word, k));
+        }
+
+        result
+    }
+
+    pub(super) fn insert_entry(&self, (structure, name, key): &Entry<K>) {
+        match structure {
+            Structure::Index => self.hash_index.insert_entry(key, name.clone()),
+            Structure::Tags => self.tag_index.insert_entry(key, name.clone()),
+            Structure::Range => {
+                let (field_name, value) = split_range_name(name);
+                self.range_index.live().insert_entry(key, field_name.to_owned(), value)
+            }
+            Structure::Text => self.inverted_index.live().insert_word(key, name.clone()),
+        }
+    }
+
+    fn remove_entry(&self, (structure, name, key): &Entry<K>) {
+        match structure {
+            Structure::Index => self.hash_index.remove_keys(std::slice::from_ref(name)),
+            Structure::Tags => self.tag_index.remove_entry(key, name.clone()),
+            Structure::Range => {
+                let (field_name, value) = split_range_name(name);
+                self.range_index.live().remove_entry(key, field_name, &value)
+            }
+            Structure::Text => self.inverted_index.live().remove_word(key, name),
+        }
+    }
+}
+
+
+// field=value for strings, field#Int=value and field#Float=value for numbers
+#[inline]
+fn range_name(field_name: &str, value: &FieldValue) -> String {
+    match value {
+        FieldValue::Str(_) => format!("{}={}", field_name, value),
+        _ => format!("{}#{}={}", field_name, value.kind(), value),
+    }
+}
+
+#[inline]
+fn split_range_name(name: &str) -> (&str, FieldValue) {
+    let (field, value) = name.split_once('=').unwrap_or((name, ""));
+    match field.rsplit_once('#') {
+        Some((field_name, "Int")) => match value.parse() {
+            Ok(value) => (field_name, FieldValue::Int(value)),
+            Err(_) => (field, FieldValue::from(value)),
+        },
+        Some((field_name, "Float")) => match value.parse() {
+            Ok(value) => (field_name, FieldValue::Float(value)),
+            Err(_) => (field, FieldValue::from(value)),
+        },
+        _ => (field, FieldValue::from(value)),
+    }
+}
+
+fn into_audit_entries<K>(entries: Vec<Entry<K>>) -> Vec<AuditEntry<K>> {
+    entries
+        .into_iter()
+        .map(|(structure, name, key)| AuditEntry { structure, name, key })
+        .collect()
+}
@@ -0,0 +1,208 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, hash::Hash};
+
+use crate::{
+    darkbird::{
+        plugin::WriteContext,
+        storage::{LogRecord, RQuery},
+        wal::codec::Codec,
+        SessionResult,
+    },
+    document::Document,
+};
+
+use super::Storage;
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Insert many documents with one WAL record instead of one per document,
+    /// through write plugins.
+    ///
+    /// one `Event::Query(RQuery::Insert)` per document is dispatched,
+    /// `Event::Replaced` for held keys with `Options::with_previous_values`.
+    ///
+    /// keys are held from log to memory, like insert hold its key.
+    /// a plugin rejection or WAL failure return error before memory is touched,
+    /// a write error may still leave record to replay on restart, like `transaction`.
+    /// memory updated after disk_log wrote record, then events dispatched
+    pub async fn insert_batch(&self, batch: Vec<(K, Doc)>) -> Result<(), SessionResult> {
+        self.writable()?;
+
+        let mut queries = Vec::with_capacity(batch.len());
+        for (key, doc) in batch {
+            let mut ctx = WriteContext { key, doc, metadata: HashMap::new() };
+            for plugin in self.plugins.iter() {
+                if let Err(reason) = plugin.on_insert(&mut ctx) {
+                    return Err(SessionResult::PluginRejected { plugin: plugin.name().to_owned(), reason });
+                }
+            }
+            queries.push(RQuery::Insert(ctx.key, ctx.doc));
+        }
+
+        let keys: Vec<&K> = queries.iter().map(query_key).collect();
+        let held = self.lock_keys(&keys, &keys, false).await?;
+        self.log_inserts(&queries).await?;
+
+        // an index conflict doesn't stop the rest, first one returned
+        let mut res = Ok(());
+        let mut previous = vec![];
+        for query in queries.iter() {
+            if let RQuery::Insert(key, doc) = query {
+                if self.previous_values {
+                    previous.push(self.previous(key));
+                }
+                if let Err(e) = self.store_doc(key.clone(), doc.clone()).await {
+                    res = res.and(Err(e));
+                }
+            }
+        }
+        drop(held);
+
+        self.dispatch_queries(queries, previous).await;
+        res
+    }
+
+    /// Remove many keys with one WAL record, see `insert_batch`.
+    /// missing keys are skipped
+    pub async fn remove_batch(&self, keys: Vec<K>) -> Result<(), SessionResult> {
+        self.writable()?;
+
+        self.remove_held(keys, |_, _| true).await.map(|_| ())
+    }
+
+    /// Remove every document for which predicate return false, see `remove_batch`.
+    ///
+    /// one `Event::Query(RQuery::Remove)` per removed key is dispatched.
+    /// predicate run again on documents of its keys once they are held,
+    /// so a document written in between is kept if predicate now keep it.
+    /// return number of documents removed
+    pub async fn retain(&self, f: impl Fn(&K, &Doc) -> bool) -> Result<usize, SessionResult> {
+        self.writable()?;
+
+        let mut keys = vec![];
+        self.for_each_doc(|key, doc| {
+            if !f(key, doc) {
+                keys.push(key.clone());
+            }
+        })
+        .await;
+
+        self.remove_held(keys, |key, doc| !f(key, doc)).await
+    }
+
+    // hold keys, then remove those whose document is stored and picked by remove.
+    // return number of keys removed
+    async fn remove_held(&self, keys: Vec<K>, remove: impl Fn(&K, &Doc) -> bool) -> Result<usize, SessionResult> {
+        for key in keys.iter() {
+            for plugin in self.plugins.iter() {
+                if let Err(reason) = plugin.on_remove(key) {
+                    return Err(SessionResult::PluginRejected { plugin: plugin.name().to_owned(), reason });
+                }
+            }
+        }
+
+        let held_keys: Vec<&K> = keys.iter().collect();
+        let held = self.lock_keys(&held_keys, &[], false).await?;
+
+        let mut docs = Vec::with_capacity(keys.len());
+        for key in keys.iter() {
+            if let Some(doc) = self.stored(key).filter(|doc| remove(key, doc)) {
+                docs.push((key.clone(), doc));
+            }
+        }
+
+        let mut queries: Vec<RQuery<K, Doc>> = docs.iter().map(|(key, _)| RQuery::Remove(key.clone())).collect();
+        self.log_queries(&mut queries).await?;
+
+        for (key, doc) in docs.iter() {
+            self.remove_derived(key, doc).await;
+            self.forget(key);
+        }
+        drop(held);
+
+        let removed = docs.len();
+        self.dispatch_queries(queries, docs.into_iter().map(|(_, doc)| Some(doc)).collect()).await;
+        Ok(removed)
+    }
+
+    /// log queries as one `LogRecord::Transaction`, see `log_transaction`, caller hold keys.
+    /// stashed in coalescer if enabled. return bytes logged
+    pub(super) async fn log_queries(&self, queries: &mut Vec<RQuery<K, Doc>>) -> Result<u64, SessionResult> {
+        if let Some(coalescer) = &self.coalescer {
+            for query in queries.iter() {
+                coalescer.stash(query_key(query).clone(), query.clone());
+            }
+            return Ok(0);
+        }
+
+        self.log_transaction(queries).await
+    }
+
+    /// log inserts as one `LogRecord::InsertBatch` on lane of first key, return once disk_log wrote it,
+    /// see `log_transaction`. stashed in coalescer if enabled
+    async fn log_inserts(&self, queries: &[RQuery<K, Doc>]) -> Result<(), SessionResult> {
+        if let Some(coalescer) = &self.coalescer {
+            for query in queries {
+                coalescer.stash(query_key(query).clone(), query.clone());
+            }
+            return Ok(());
+        }
+
+        if self.off_disk || queries.is_empty() {
+            return Ok(());
+        }
+
+        // borrowed, encode same as owned
+        let docs: Vec<(&K, &Doc)> = queries.iter().filter_map(|query| match query {
+            RQuery::Insert(key, doc) => Some((key, doc)),
+            _ => None,
+        }).collect();
+
+        let lane = self.lane_of(query_key(&queries[0]));
+        let record = self.codec.encode(&LogRecord::InsertBatch(docs));
+        self.wal_session.log_batch_acked(lane, vec![record]).await
+    }
+
+    // coalescer dispatch when flushed. previous is empty, or for each query document
+    // its key held before: removed ones, already gone from memory, are read by tag filters,
+    // all of them by Event::Removed and Event::Replaced
+    pub(crate) async fn dispatch_queries(&self, queries: Vec<RQuery<K, Doc>>, previous: Vec<Option<Doc>>) {
+        if self.off_reporter || self.coalescer.is_some() {
+            return;
+        }
+
+        let mut previous = previous.into_iter();
+        for query in queries {
+            let lane = self.lane_of(query_key(&query));
+            let doc = previous.next().flatten();
+            let removed = doc.as_ref().filter(|_| matches!(query, RQuery::Remove(_)));
+            let event = self.change_event(query, doc.as_ref());
+            self.dispatch_removed(lane, event, removed).await;
+        }
+    }
+}
+
+
+#[inline]
+pub(super) fn query_key<K, Doc>(query: &RQuery<K, Doc>) -> &K {
+    match query {
+        RQuery::Insert(key, _) | RQuery::Update(key, _) | RQuery::Patch(key, ..) => key,
+        RQuery::Remove(key) | RQuery::TagAdd(key, _) | RQuery::TagRemove(key, _) => key,
+        RQuery::Clear => unreachable!("RQuery::Clear has no key"),
+    }
+}
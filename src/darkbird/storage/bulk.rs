@@ -0,0 +1,249 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::hash::Hash;
+use tokio::time::Instant;
+
+use crate::{
+    darkbird::{
+        coop::Budget,
+        key_codec::KeyCodec,
+        storage::{Event, RQuery},
+        SessionResult,
+    },
+    document::Document,
+};
+
+use super::Storage;
+
+
+
+// keys per WAL batch and per removal round
+const BULK_BATCH: usize = 10_000;
+
+
+/// progress of running or last bulk removal (`remove_keys`, `remove_where`, `remove_prefix`)
+#[derive(Clone, Debug)]
+pub struct BulkProgress {
+    pub operation: &'static str,
+
+    // keys selected for removal
+    pub candidates: usize,
+
+    pub removed: usize,
+
+    // vetoed by a write plugin
+    pub rejected: usize,
+
+    pub batches: usize,
+
+    // tombstone bytes logged to WAL
+    pub bytes: u64,
+
+    // serialized size of documents removed, see `PlanReport::bytes`
+    pub reclaimed: u64,
+
+    pub started: Instant,
+    pub finished: Option<Instant>,
+}
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// remove many keys, see `bulk_remove`
+    pub async fn remove_keys(&self, keys: Vec<K>) -> Result<BulkProgress, SessionResult> {
+        self.bulk_remove("remove_keys", keys).await
+    }
+
+    /// remove every document matching predicate (retention sweeps, ...),
+    /// `plan_remove_where` report it without removing
+    pub async fn remove_where(&self, predicate: impl Fn(&K, &Doc) -> bool) -> Result<BulkProgress, SessionResult> {
+        self.plan_remove_where(predicate).execute().await
+    }
+
+    /// progress of running or last bulk removal
+    pub fn bulk_progress(&self) -> Option<BulkProgress> {
+        self.bulk.lock().clone()
+    }
+
+    /// Common engine of bulk removals.
+    ///
+    /// per batch of `BULK_BATCH` keys, held while removed: tombstones logged as one WAL record,
+    /// removals applied grouped by shard, and one `Event::BulkRemove` dispatched per lane
+    /// (an `Event::Removed` per key with `Options::with_previous_values`),
+    /// progress updated after every batch and one admin event summarize whole operation.
+    /// tombstones are Remove queries of a `LogRecord::Transaction`, so replay reach same end state
+    pub(super) async fn bulk_remove(&self, operation: &'static str, mut keys: Vec<K>) -> Result<BulkProgress, SessionResult> {
+        let op = self.admin.start("bulk_remove", format!("operation={} candidates={}", operation, keys.len()));
+
+        *self.bulk.lock() = Some(BulkProgress {
+            operation,
+            candidates: keys.len(),
+            removed: 0,
+            rejected: 0,
+            batches: 0,
+            bytes: 0,
+            reclaimed: 0,
+            started: Instant::now(),
+            finished: None,
+        });
+
+        // neighbours in same shard, so a batch touch few shard locks
+        keys.sort_by_cached_key(|key| self.shard_order(key));
+
+        let mut res = Ok(());
+        for batch in keys.chunks(BULK_BATCH) {
+            if let Err(e) = self.remove_chunk(batch).await {
+                res = Err(e);
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+
+        let progress = {
+            let mut bulk = self.bulk.lock();
+            let progress = bulk.as_mut().unwrap();
+            progress.finished = Some(Instant::now());
+            progress.clone()
+        };
+
+        match res {
+            Ok(_) => {
+                self.admin.finish(op, Ok(progress.bytes));
+                Ok(progress)
+            }
+            Err(e) => {
+                self.admin.finish(op, Err(e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    async fn remove_chunk(&self, batch: &[K]) -> Result<(), SessionResult> {
+        // documents read once keys are held, so a write in between isn't removed by its old derived
+        let keys: Vec<&K> = batch.iter().collect();
+        let held = self.lock_keys(&keys, &[], false).await?;
+
+        let mut rejected = 0;
+        let mut docs = Vec::with_capacity(batch.len());
+
+        'keys: for key in batch {
+            for plugin in self.plugins.iter() {
+                if plugin.on_remove(key).is_err() {
+                    rejected += 1;
+                    continue 'keys;
+                }
+            }
+
+            if let Some(doc) = self.stored(key) {
+                docs.push((key.clone(), doc));
+            }
+        }
+
+        let mut tombstones: Vec<RQuery<K, Doc>> = docs.iter().map(|(key, _)| RQuery::Remove(key.clone())).collect();
+        let bytes = {
+            let bytes = self.log_queries(&mut tombstones).await?;
+
+            // invertedIndex removed after keys are forgotten, a query in between isn't cached
+            let _writing = self.query_cache.as_ref().map(|cache| cache.write::<Doc>(None, None));
+
+            // invertedIndex removed by spawned tasks, run while others are removed
+            let mut handles = vec![];
+            for (key, doc) in docs.iter() {
+                if let Some(content) = doc.get_content() {
+                    for index in self.inverted_index.targets() {
+                        handles.push(index.remove(key.clone(), content.clone()));
+                    }
+                }
+            }
+
+            let mut budget = Budget::default();
+            for (key, doc) in docs.iter() {
+                self.remove_derived_sync(key, doc);
+                self.forget(key);
+                budget.tick().await;
+            }
+
+            for handle in handles {
+                let _ = handle.await;
+            }
+
+            bytes
+        };
+        drop(held);
+
+        // coalescer dispatch a Remove per key when flushed
+        if !self.off_reporter && self.coalescer.is_none() && self.previous_values {
+            for (key, doc) in docs.iter() {
+                let event = Event::Removed(key.clone(), doc.clone());
+                self.dispatch_removed(self.lane_of(key), event, Some(doc)).await;
+            }
+        }
+        else if !self.off_reporter && self.coalescer.is_none() {
+            let mut lanes: Vec<Vec<K>> = vec![vec![]; self.lanes];
+            for (key, _) in docs.iter() {
+                lanes[self.lane_of(key)].push(key.clone());
+            }
+
+            for (lane, keys) in lanes.into_iter().enumerate() {
+                if !keys.is_empty() {
+                    self.dispatch(lane, Event::BulkRemove(keys)).await;
+                }
+            }
+        }
+
+        let reclaimed: u64 = docs.iter().map(|(_, doc)| bincode::serialized_size(doc).unwrap_or(0)).sum();
+        if let Some(progress) = self.bulk.lock().as_mut() {
+            progress.removed += docs.len();
+            progress.reclaimed += reclaimed;
+            progress.rejected += rejected;
+            progress.batches += 1;
+            progress.bytes += bytes;
+        }
+
+        Ok(())
+    }
+
+    // dashmap pick shard by hash bits below top 7, ordering by them group shards
+    fn shard_order(&self, key: &K) -> usize {
+        match &self.compression {
+            Some(_) => self.compressed.hash_usize(key) << 7,
+            None => self.collection.hash_usize(key) << 7,
+        }
+    }
+}
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static
+        + KeyCodec,
+{
+    /// remove every key whose encoding start with prefix (tenant purge, ...),
+    /// see `KeyCodec` for prefixes of composite keys, `plan_remove_prefix` report it without removing
+    pub async fn remove_prefix(&self, prefix: &[u8]) -> Result<BulkProgress, SessionResult> {
+        self.plan_remove_prefix(prefix).execute().await
+    }
+}
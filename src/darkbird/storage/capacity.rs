@@ -0,0 +1,91 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashSet, hash::Hash};
+
+use crate::{
+    darkbird::{capacity::Capacity, SessionResult},
+    document::Document,
+};
+
+use super::Storage;
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Make room for keys under `Options::with_capacity`, before caller take any update lock.
+    ///
+    /// keys not held are new: with EvictLru or EvictOldest other keys are evicted
+    /// through remove path (logged and dispatched as a remove), with RejectNew
+    /// or more new keys than capacity `CapacityExceeded` is returned with nothing evicted.
+    /// caller write keys holding returned guard, None when capacity is off or keys are all held
+    pub(super) async fn make_room(&self, keys: &[&K]) -> Result<Option<tokio::sync::MutexGuard<'_, ()>>, SessionResult> {
+        let capacity = match &self.capacity {
+            Some(capacity) => capacity,
+            None => return Ok(None),
+        };
+
+        if keys.iter().all(|key| self.holds(key)) {
+            return Ok(None);
+        }
+
+        let admit = capacity.admit().await;
+
+        let spared: HashSet<&K> = keys.iter().copied().collect();
+        let new = spared.iter().filter(|key| !self.holds(key)).count();
+        if new > capacity.limit() || (!capacity.evicts() && self.held_len() + new > capacity.limit()) {
+            return Err(SessionResult::CapacityExceeded);
+        }
+
+        self.evict_down(capacity, capacity.limit() - new, &spared).await?;
+        Ok(Some(admit))
+    }
+
+    /// evict down to capacity keys loaded over it, when capacity was lowered between opens.
+    /// RejectNew keep them and reject new keys until removes bring store under capacity
+    pub(super) async fn fit_capacity(&self) -> Result<(), SessionResult> {
+        match &self.capacity {
+            Some(capacity) if capacity.evicts() => {
+                let _admit = capacity.admit().await;
+                self.evict_down(capacity, capacity.limit(), &HashSet::new()).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // caller hold admit guard
+    async fn evict_down(&self, capacity: &Capacity<K>, target: usize, spared: &HashSet<&K>) -> Result<(), SessionResult> {
+        while self.held_len() > target {
+            let victim = capacity.victim(spared).ok_or(SessionResult::CapacityExceeded)?;
+
+            let _update = self.update_lock(&victim).lock().await;
+            if !self.remove_locked(&victim).await? {
+                // removed meanwhile
+                capacity.forget(&victim);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// documents in memory, past their ttl or quarantined included
+    #[inline]
+    fn held_len(&self) -> usize {
+        match &self.compression {
+            Some(_) => self.compressed.len(),
+            None => self.collection.len(),
+        }
+    }
+}
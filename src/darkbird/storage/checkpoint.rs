@@ -0,0 +1,361 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs,
+    hash::Hash,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+use crate::{
+    darkbird::{expiry::unix_ms, recovery, SessionResult, StatusResult},
+    document::Document,
+};
+
+use super::{audit::Entry, Storage, Structure};
+
+// checkpoints kept, newest and previous one as fallback
+const KEEP: usize = 2;
+
+const MANIFEST: &str = "manifest.json";
+const DATA: &str = "data.bin";
+const DERIVED: &str = "derived.bin";
+const TAGS: &str = "tags.bin";
+
+// document with its ttl deadline
+type Saved<K, Doc> = (K, Doc, Option<u64>);
+
+// key with tags added and removed by add_tag and remove_tag
+type Edits<K> = (K, Vec<String>, Vec<String>);
+
+// first WAL page to replay, documents, derived entries and tag edits
+type Unit<K, Doc> = (usize, Vec<Saved<K, Doc>>, Vec<Entry<K>>, Vec<Edits<K>>);
+
+
+
+/// result of `Storage::checkpoint`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CheckpointReport {
+    pub seq: u64,
+    pub documents: usize,
+
+    // derived entries (indexes, tags, views, ranges, words)
+    pub entries: usize,
+
+    // first WAL page replayed by open after restoring checkpoint
+    pub wal_page: usize,
+
+    pub bytes: u64,
+
+    // how long writes were paused to copy memory
+    pub pause: Duration,
+
+    pub duration: Duration,
+}
+
+
+// written last, a unit without valid manifest is skipped
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    seq: u64,
+    wal_page: usize,
+
+    // WAL page before wal_page, a compacted or migrated WAL don't match it
+    covered: Option<FileSum>,
+
+    documents: usize,
+    entries: usize,
+    files: Vec<FileSum>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct FileSum {
+    name: String,
+    len: u64,
+    crc: u32,
+}
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Write documents and derived structures with the WAL position they correspond to,
+    /// so open restore them and replay only WAL written after.
+    ///
+    /// writes are paused while memory is copied and WAL cut, then checkpoint is written
+    /// to `<path>/<name>.checkpoints` while writes continue.
+    /// every file is checksummed in a manifest written last, open skip a partial or corrupt
+    /// checkpoint for the previous one or a full replay. newest two are kept.
+    /// WAL isn't truncated, see `compact`, which make older checkpoints unusable.
+    /// no-op for RamCopies, not available with write coalescing
+    pub async fn checkpoint(&self) -> Result<CheckpointReport, SessionResult> {
+        if self.coalescer.is_some() {
+            return Err(SessionResult::UnImplement);
+        }
+
+        if self.off_disk {
+            return Ok(CheckpointReport::default());
+        }
+
+        // compaction renumber pages
+        let _compaction = self.compaction.lock().await;
+
+        let op = self.admin.start("checkpoint", String::new());
+        match self.write_checkpoint().await {
+            Ok(report) => {
+                self.admin.finish(op, Ok(report.bytes));
+                Ok(report)
+            }
+            Err(e) => {
+                self.admin.finish(op, Err(e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    async fn write_checkpoint(&self) -> Result<CheckpointReport, SessionResult> {
+        let started = Instant::now();
+
+        // writes hold gate shared from WAL log to memory, see compact
+        let (docs, entries, edits, cut, pause) = {
+            let _gate = self.rebuild_gate.write().await;
+            let paused = Instant::now();
+
+            let mut docs = vec![];
+            let mut edits = vec![];
+            self.for_each_doc(|key, doc| {
+                docs.push((key.clone(), doc.clone(), self.expiry.deadline(key)));
+                let (added, removed) = self.tag_index.edits_of(key);
+                if !added.is_empty() || !removed.is_empty() {
+                    edits.push((key.clone(), added, removed));
+                }
+            })
+            .await;
+            let entries: Vec<Entry<K>> = self.live_entries().into_iter().collect();
+
+            let cut = self.wal_session.checkpoint().await?;
+            (docs, entries, edits, cut, paused.elapsed())
+        };
+
+        let mut report = CheckpointReport {
+            documents: docs.len(),
+            entries: entries.len(),
+            wal_page: cut.tail,
+            pause,
+            ..Default::default()
+        };
+
+        let dir = self.checkpoints.clone();
+        let (seq, bytes) = tokio::task::spawn_blocking(move || write_unit(&dir, &docs, &entries, &edits, cut.tail, cut.covered.as_deref()))
+            .await
+            .map_err(|_| SessionResult::NoResponse)?
+            .map_err(|e| SessionResult::Err(StatusResult::IoError(e)))?;
+
+        report.seq = seq;
+        report.bytes = bytes;
+        report.duration = started.elapsed();
+        Ok(report)
+    }
+
+    /// restore newest valid checkpoint, return first WAL page to replay (1 without checkpoint).
+    /// called by open before loader
+    pub(super) async fn restore_checkpoint(&self, wal_dir: &str) -> usize {
+        for (seq, unit) in units(&self.checkpoints) {
+            match read_unit::<K, Doc>(&unit, wal_dir) {
+                Ok((wal_page, docs, entries, edits)) => {
+                    self.restore(docs, entries, edits).await;
+                    return wal_page;
+                }
+                Err(e) => eprintln!("checkpoint {}: {}, skipped", seq, e),
+            }
+        }
+
+        1
+    }
+
+    async fn restore(&self, docs: Vec<Saved<K, Doc>>, entries: Vec<Entry<K>>, edits: Vec<Edits<K>>) {
+        for (key, doc, expires_at) in docs {
+            if let Some(at) = expires_at {
+                self.expiry.arm(key.clone(), recovery::rebase(at, self.rebase_ms), doc.clone());
+            }
+            if let Some(order) = &self.key_order {
+                order.insert(&key);
+            }
+            if let Some(capacity) = &self.capacity {
+                capacity.written(&key);
+            }
+            match &self.compression {
+                Some(compression) => {
+                    self.compressed.insert(key, compression.compress(&doc));
+                }
+                None => {
+                    self.collection.insert(key, doc);
+                }
+            }
+        }
+
+        // tag sets are in entries, edits kept so later writes of key apply them
+        for (key, added, removed) in edits {
+            added.iter().for_each(|tag| self.tag_index.add_edit(&key, tag));
+            removed.iter().for_each(|tag| self.tag_index.remove_edit(&key, tag));
+        }
+
+        // low memory replay build other structures from documents once WAL is replayed
+        for entry in entries.iter().filter(|(structure, ..)| !self.defer_derived || *structure == Structure::Index) {
+            self.insert_entry(entry);
+        }
+
+        // expired while down, loader isn't logging so only memory is touched
+        for (key, _) in self.expiry.due_keys(unix_ms(self.now())) {
+            let _ = self.apply_remove(key).await;
+        }
+    }
+}
+
+
+/// checkpoints in dir, newest first
+fn units(dir: &str) -> Vec<(u64, PathBuf)> {
+    let mut units: Vec<(u64, PathBuf)> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let seq = entry.file_name().to_str()?.parse::<u64>().ok()?;
+                Some((seq, entry.path()))
+            })
+            .collect(),
+        Err(_) => vec![],
+    };
+
+    units.sort_by_key(|(seq, _)| std::cmp::Reverse(*seq));
+    units
+}
+
+fn write_unit<K: Serialize, Doc: Serialize>(
+    dir: &str,
+    docs: &[Saved<K, Doc>],
+    entries: &[Entry<K>],
+    edits: &[Edits<K>],
+    wal_page: usize,
+    covered: Option<&str>,
+) -> io::Result<(u64, u64)> {
+    fs::create_dir_all(dir)?;
+    let seq = units(dir).first().map_or(1, |(seq, _)| seq + 1);
+
+    let tmp = format!("{}/{:020}.tmp", dir, seq);
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir(&tmp)?;
+
+    let files = vec![
+        write_file(&tmp, DATA, &bincode::serialize(docs).unwrap())?,
+        write_file(&tmp, DERIVED, &bincode::serialize(entries).unwrap())?,
+        write_file(&tmp, TAGS, &bincode::serialize(edits).unwrap())?,
+    ];
+    let covered = match covered {
+        Some(page) => Some(sum(page)?),
+        None => None,
+    };
+
+    let manifest = Manifest {
+        seq,
+        wal_page,
+        covered,
+        documents: docs.len(),
+        entries: entries.len(),
+        files,
+    };
+    let bytes = manifest.files.iter().map(|f| f.len).sum();
+    write_file(&tmp, MANIFEST, &serde_json::to_vec(&manifest).unwrap())?;
+    sync_dir(&tmp)?;
+
+    fs::rename(&tmp, format!("{}/{:020}", dir, seq))?;
+    sync_dir(dir)?;
+
+    for (_, old) in units(dir).into_iter().skip(KEEP) {
+        let _ = fs::remove_dir_all(old);
+    }
+
+    // left by a crash while writing
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            if entry.file_name().to_string_lossy().ends_with(".tmp") {
+                let _ = fs::remove_dir_all(entry.path());
+            }
+        }
+    }
+
+    Ok((seq, bytes))
+}
+
+fn read_unit<K: DeserializeOwned, Doc: DeserializeOwned>(
+    unit: &Path,
+    wal_dir: &str,
+) -> Result<Unit<K, Doc>, String> {
+    let manifest: Manifest = serde_json::from_slice(&fs::read(unit.join(MANIFEST)).map_err(|e| e.to_string())?)
+        .map_err(|e| format!("manifest {}", e))?;
+
+    if let Some(covered) = &manifest.covered {
+        let page = format!("{}/{}", wal_dir, covered.name);
+        if sum(&page).ok().as_ref() != Some(covered) {
+            return Err("WAL rewritten since checkpoint".to_owned());
+        }
+    }
+
+    let read = |name: &str| -> Result<Vec<u8>, String> {
+        let expected = manifest.files.iter().find(|f| f.name == name).ok_or(format!("{} missing from manifest", name))?;
+        let bytes = fs::read(unit.join(name)).map_err(|e| format!("{} {}", name, e))?;
+        if bytes.len() as u64 != expected.len || crc32fast::hash(&bytes) != expected.crc {
+            return Err(format!("{} checksum mismatch", name));
+        }
+        Ok(bytes)
+    };
+
+    let docs = bincode::deserialize(&read(DATA)?).map_err(|e| e.to_string())?;
+    let entries = bincode::deserialize(&read(DERIVED)?).map_err(|e| e.to_string())?;
+
+    // checkpoints written before tag edits have none
+    let edits = match manifest.files.iter().any(|f| f.name == TAGS) {
+        true => bincode::deserialize(&read(TAGS)?).map_err(|e| e.to_string())?,
+        false => vec![],
+    };
+    Ok((manifest.wal_page, docs, entries, edits))
+}
+
+fn write_file(dir: &str, name: &str, bytes: &[u8]) -> io::Result<FileSum> {
+    let path = format!("{}/{}", dir, name);
+    fs::write(&path, bytes)?;
+    fs::File::open(&path)?.sync_all()?;
+
+    Ok(FileSum {
+        name: name.to_owned(),
+        len: bytes.len() as u64,
+        crc: crc32fast::hash(bytes),
+    })
+}
+
+fn sum(path: &str) -> io::Result<FileSum> {
+    let bytes = fs::read(path)?;
+    let name = Path::new(path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+
+    Ok(FileSum {
+        name,
+        len: bytes.len() as u64,
+        crc: crc32fast::hash(&bytes),
+    })
+}
+
+fn sync_dir(dir: &str) -> io::Result<()> {
+    fs::File::open(dir)?.sync_all()
+}
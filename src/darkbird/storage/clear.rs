@@ -0,0 +1,114 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::hash::Hash;
+
+use crate::{
+    darkbird::{
+        coop::Budget,
+        storage::{Event, RQuery},
+        SessionResult,
+    },
+    document::Document,
+};
+
+use super::Storage;
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Remove every document in one operation, through write plugins, return documents removed.
+    ///
+    /// one `RQuery::Clear` WAL record, replayed as removal of everything logged before it,
+    /// and one `Event::Query(RQuery::Clear)` dispatched in place of a remove per key.
+    /// writes wait until documents are gone, reads see store emptied key by key.
+    /// a write plugin refusing removal of any key reject clear with nothing removed.
+    /// not available with write coalescing, which log per key
+    pub async fn clear(&self) -> Result<usize, SessionResult> {
+        self.writable()?;
+
+        if self.coalescer.is_some() {
+            return Err(SessionResult::UnImplement);
+        }
+
+        let op = self.admin.start("clear", String::new());
+        let res = self.clear_locked().await;
+
+        match &res {
+            Ok(_) => self.admin.finish(op, Ok(0)),
+            Err(e) => self.admin.finish(op, Err(e.to_string())),
+        }
+
+        res
+    }
+
+    async fn clear_locked(&self) -> Result<usize, SessionResult> {
+        let _gate = self.rebuild_gate.write().await;
+
+        let keys = self.held_keys();
+        for key in keys.iter() {
+            for plugin in self.plugins.iter() {
+                if let Err(reason) = plugin.on_remove(key) {
+                    return Err(SessionResult::PluginRejected { plugin: plugin.name().to_owned(), reason });
+                }
+            }
+        }
+
+        // gate held, so no write is logged after it before documents are gone.
+        // acked, so writes logged before on any lane are on WAL ahead of it
+        if !self.off_disk {
+            self.wal_session.log_batch_acked(0, vec![RQuery::<K, Doc>::Clear.to_record(self.codec)]).await?;
+        }
+
+        let removed = self.forget_keys(keys).await;
+
+        if !self.off_reporter {
+            self.dispatch(0, Event::Query(RQuery::Clear)).await;
+        }
+
+        Ok(removed)
+    }
+
+    /// remove every document from memory and derived structures, not logged.
+    /// by clear and by loader replaying `RQuery::Clear`
+    pub(super) async fn forget_all(&self) -> usize {
+        self.forget_keys(self.held_keys()).await
+    }
+
+    async fn forget_keys(&self, keys: Vec<K>) -> usize {
+        // keys leave derived structures one at a time, a query in between isn't cached
+        let _writing = self.query_cache.as_ref().map(|cache| cache.write::<Doc>(None, None));
+
+        let mut removed = 0;
+        let mut budget = Budget::default();
+        for key in keys {
+            if let Some(doc) = self.stored(&key) {
+                self.remove_derived(&key, &doc).await;
+                self.forget(&key);
+                removed += 1;
+            }
+            budget.tick().await;
+        }
+        removed
+    }
+
+    // past their ttl and quarantined too
+    fn held_keys(&self) -> Vec<K> {
+        match &self.compression {
+            Some(_) => self.compressed.iter().map(|rf| rf.key().clone()).collect(),
+            None => self.collection.iter().map(|rf| rf.key().clone()).collect(),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{hash::Hash, time::Duration};
+use tokio::time::Instant;
+
+use crate::{
+    darkbird::{
+        storage::{Event, LogRecord},
+        wal::{codec::Codec, disk_log::write_snapshot},
+        SessionResult,
+    },
+    document::Document,
+};
+
+use super::Storage;
+
+
+
+/// result of `Storage::compact`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CompactReport {
+    // documents written to compacted pages
+    pub records: usize,
+
+    // WAL pages before and after, pages written during compaction kept after compacted ones
+    pub pages_before: usize,
+    pub pages_after: usize,
+
+    // how long writes were paused to copy documents
+    pub pause: Duration,
+
+    pub duration: Duration,
+}
+
+
+/// progress of `Storage::compact`, dispatched as `Event::Compacting`
+#[derive(Clone, Copy, Debug)]
+pub enum CompactPhase {
+    Started,
+
+    // documents copied, writes resumed, compacted pages being written
+    Copied { records: usize },
+
+    // compacted pages swapped in
+    Finished(CompactReport),
+}
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Replace WAL pages by one insert per live document, so open replay current data only
+    /// and patches of `Storage::patch` are folded into documents they produced.
+    ///
+    /// writes are paused while documents are copied and WAL cut,
+    /// then compacted pages are written beside store directory while writes continue
+    /// and swapped in with pages written meanwhile kept after them.
+    /// a crash during compaction leave WAL as before or compacted, never partial.
+    /// one compaction run at a time, no-op for RamCopies, not available with write coalescing.
+    /// subscribers receive `Event::Compacting` as it progress, a failed run stop after `Copied`
+    pub async fn compact(&self) -> Result<CompactReport, SessionResult> {
+        if self.coalescer.is_some() {
+            return Err(SessionResult::UnImplement);
+        }
+
+        if self.off_disk {
+            return Ok(CompactReport::default());
+        }
+
+        let _compaction = self.compaction.lock().await;
+
+        let op = self.admin.start("compact", String::new());
+        self.report_compacting(CompactPhase::Started).await;
+
+        match self.compact_pages().await {
+            Ok(report) => {
+                self.admin.finish(op, Ok(report.records as u64));
+                self.report_compacting(CompactPhase::Finished(report)).await;
+                Ok(report)
+            }
+            Err(e) => {
+                self.admin.finish(op, Err(e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    async fn compact_pages(&self) -> Result<CompactReport, SessionResult> {
+        let started = Instant::now();
+
+        // writes hold gate shared from WAL log to memory,
+        // so with gate held memory is exactly what WAL before cut replay to
+        let (docs, checkpoint, pause) = {
+            let _gate = self.rebuild_gate.write().await;
+            let paused = Instant::now();
+
+            let mut docs = vec![];
+            self.for_each_doc(|key, doc| {
+                docs.push((key.clone(), doc.clone(), self.expiry.deadline(key), self.tag_index.edits_of(key)))
+            })
+            .await;
+
+            let checkpoint = self.wal_session.checkpoint().await?;
+            (docs, checkpoint, paused.elapsed())
+        };
+
+        let records_len = docs.len();
+        self.report_compacting(CompactPhase::Copied { records: records_len }).await;
+
+        // pages before cut are deleted by install, not before archive hook acknowledged them
+        self.wal_session.archived(checkpoint.sealed).await?;
+
+        // tag edits of add_tag and remove_tag follow document they apply to
+        let mut records = Vec::with_capacity(records_len);
+        for (key, doc, expires_at, (added, removed)) in docs {
+            let edits: Vec<Vec<u8>> = added
+                .into_iter()
+                .map(|tag| self.codec.encode(&LogRecord::<&K, Doc>::TagAdd(&key, tag)))
+                .chain(removed.into_iter().map(|tag| self.codec.encode(&LogRecord::<&K, Doc>::TagRemove(&key, tag))))
+                .collect();
+
+            records.push(match expires_at {
+                Some(at) => self.codec.encode(&LogRecord::InsertWithExpiry(key, doc, at)),
+                None => self.codec.encode(&LogRecord::Insert(key, doc)),
+            });
+            records.extend(edits);
+        }
+
+        let tail = checkpoint.tail;
+        let snapshot_pages = tokio::task::spawn_blocking(move || write_snapshot(&checkpoint, records))
+            .await
+            .map_err(|_| SessionResult::NoResponse)?
+            .map_err(SessionResult::Err)?;
+
+        let (pages_before, pages_after) = self.wal_session.install(snapshot_pages, tail).await?;
+
+        Ok(CompactReport {
+            records: records_len,
+            pages_before,
+            pages_after,
+            pause,
+            duration: started.elapsed(),
+        })
+    }
+
+    async fn report_compacting(&self, phase: CompactPhase) {
+        if !self.off_reporter {
+            self.dispatch(0, Event::Compacting(phase)).await;
+        }
+    }
+}
@@ -0,0 +1,544 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::{self, File},
+    hash::Hash,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    darkbird::{coop::Budget, key_codec::KeyCodec, SessionResult, StatusResult},
+    document::Document,
+};
+
+use super::Storage;
+
+
+// encoded keys sorted in memory before spilled as a run
+const DEFAULT_RUN_SIZE: usize = 100_000;
+
+// distinct run file names inside process
+static RUN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// first bytes of a bincode export, so it isn't imported as something else
+const BINCODE_HEADER: &[u8] = b"darkbird-export bincode 1\n";
+
+
+
+/// encoding of `Storage::export` and `Storage::import` files
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    // one `{"doc":...,"key":...}` per line
+    JsonLines,
+
+    // header then | len (u32 le) | bincode (key, doc) | per document
+    Bincode,
+
+    // one json array of `{"doc":...,"key":...}`, read whole by import
+    JsonArray,
+}
+
+
+/// what `Storage::import` do with a key the store already hold
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    Overwrite,
+    Skip,
+
+    // stop with `SessionResult::ImportConflict`, documents before it stay imported
+    Fail,
+}
+
+
+/// result of `Storage::import`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+
+    // held already, with ConflictPolicy::Skip
+    pub skipped: usize,
+}
+
+
+/// options of `Storage::export`
+#[derive(Clone, Debug)]
+pub struct ExportOptions {
+    ordered: bool,
+    run_size: usize,
+    spill_dir: Option<PathBuf>,
+    format: ExportFormat,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            ordered: false,
+            run_size: DEFAULT_RUN_SIZE,
+            spill_dir: None,
+            format: ExportFormat::JsonLines,
+        }
+    }
+}
+
+impl ExportOptions {
+    pub fn new() -> Self {
+        ExportOptions::default()
+    }
+
+    /// write documents ordered by encoded key, so exports of same data are byte-identical
+    pub fn with_ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// encoded keys kept in memory per sorted run
+    pub fn with_run_size(mut self, run_size: usize) -> Self {
+        self.run_size = run_size.max(1);
+        self
+    }
+
+    /// directory of sorted runs, default is OS temp dir
+    pub fn with_spill_dir(mut self, dir: &str) -> Self {
+        self.spill_dir = Some(PathBuf::from(dir));
+        self
+    }
+
+    /// default is JsonLines
+    pub fn with_format(mut self, format: ExportFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static
+        + KeyCodec,
+{
+    /// Export documents as JSON lines `{"doc":...,"key":...}`, or bincode or a json array with `ExportOptions::with_format`,
+    /// json maps serialized with sorted keys whatever map type document use.
+    ///
+    /// ordered export sort encoded keys in runs of `run_size` spilled to disk
+    /// and k-way merged, so memory stay bounded on huge stores,
+    /// return total documents written.
+    ///
+    /// documents are read one at a time while writes continue, each as it was when read.
+    /// yield to runtime periodically while writing documents,
+    /// writer is blocking so a slow writer still block current worker
+    pub async fn export<W: Write>(&self, writer: W, opts: &ExportOptions) -> Result<usize, SessionResult> {
+        let op = self.admin.start("export", format!("ordered={} format={:?}", opts.ordered, opts.format));
+
+        let mut writer = CountingWriter { inner: BufWriter::new(writer), bytes: 0 };
+
+        let res = match opts.format {
+            ExportFormat::Bincode => writer.write_all(BINCODE_HEADER).map_err(io_error),
+            ExportFormat::JsonArray => writer.write_all(b"[").map_err(io_error),
+            ExportFormat::JsonLines => Ok(()),
+        };
+
+        let res = match res {
+            Err(e) => Err(e),
+            Ok(_) if opts.ordered => self.export_ordered(&mut writer, opts).await,
+            Ok(_) => {
+                let mut written = 0;
+                let mut res = Ok(());
+                self.for_each_doc(|key, doc| {
+                    if res.is_ok() {
+                        res = write_record(&mut writer, opts.format, written, key, doc);
+                        written += 1;
+                    }
+                })
+                .await;
+                res.map(|_| written)
+            }
+        };
+
+        let res = match opts.format {
+            ExportFormat::JsonArray => res.and_then(|written| writer.write_all(b"\n]\n").map(|_| written).map_err(io_error)),
+            _ => res,
+        };
+        let res = res.and_then(|written| writer.flush().map(|_| written).map_err(io_error));
+
+        match &res {
+            Ok(_) => self.admin.finish(op, Ok(writer.bytes)),
+            Err(e) => self.admin.finish(op, Err(e.to_string())),
+        }
+
+        res
+    }
+
+    async fn export_ordered<W: Write>(&self, writer: &mut W, opts: &ExportOptions) -> Result<usize, SessionResult> {
+        let mut runs = Runs { files: vec![] };
+        let mut buffer: Vec<Vec<u8>> = Vec::with_capacity(opts.run_size.min(DEFAULT_RUN_SIZE));
+        let mut res = Ok(());
+
+        let mut push = |encoded: Vec<u8>| {
+            if res.is_err() {
+                return;
+            }
+            buffer.push(encoded);
+            if buffer.len() >= opts.run_size {
+                res = runs.spill(&mut buffer, opts);
+            }
+        };
+
+        match &self.compression {
+            Some(_) => self.compressed.iter().for_each(|rf| push(rf.key().encode())),
+            None => self.collection.iter().for_each(|rf| push(rf.key().encode())),
+        }
+        res?;
+
+        buffer.sort_unstable();
+
+        // single run, no need to merge
+        if runs.files.is_empty() {
+            return self.write_encoded(writer, opts.format, buffer.into_iter().map(Ok)).await;
+        }
+
+        runs.spill(&mut buffer, opts)?;
+        let merged = runs.merge()?;
+        self.write_encoded(writer, opts.format, merged).await
+    }
+
+    async fn write_encoded<W: Write>(&self, writer: &mut W, format: ExportFormat, keys: impl Iterator<Item = io::Result<Vec<u8>>>) -> Result<usize, SessionResult> {
+        let mut budget = Budget::default();
+        let mut written = 0;
+        for encoded in keys {
+            let encoded = encoded.map_err(io_error)?;
+            let key = match K::decode(&encoded) {
+                Some(key) => key,
+                None => return Err(SessionResult::Err(StatusResult::Err("export: undecodable key".to_owned()))),
+            };
+
+            // removed while exporting
+            if let Some(doc) = self.visible_owned(&key) {
+                write_record(writer, format, written, &key, &doc)?;
+                written += 1;
+            }
+            budget.tick().await;
+        }
+        Ok(written)
+    }
+}
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Import documents of an `export` file in format it was written with, through insert path
+    /// (write plugins, WAL, indexes and events), keys already held handled by policy.
+    ///
+    /// a document that fail to decode or insert stop import, documents before it stay imported.
+    /// documents held are kept, `retain(|_, _| false)` before to replace them.
+    /// reader is blocking, read one document at a time but a json array read whole
+    pub async fn import<R: Read>(&self, reader: R, format: ExportFormat, policy: ConflictPolicy) -> Result<ImportReport, SessionResult> {
+        let op = self.admin.start("import", format!("format={:?} policy={:?}", format, policy));
+
+        let mut records = Records { reader: BufReader::new(reader), format, line: 0, bytes: 0, array: None };
+        let res = self.import_records(&mut records, policy).await;
+
+        match &res {
+            Ok(_) => self.admin.finish(op, Ok(records.bytes)),
+            Err(e) => self.admin.finish(op, Err(e.to_string())),
+        }
+
+        res
+    }
+
+    async fn import_records<R: Read>(&self, records: &mut Records<R>, policy: ConflictPolicy) -> Result<ImportReport, SessionResult> {
+        if records.format == ExportFormat::Bincode {
+            records.header()?;
+        }
+
+        let mut report = ImportReport::default();
+        while let Some((key, doc)) = records.next::<K, Doc>()? {
+            match policy {
+                ConflictPolicy::Overwrite => self.insert(key, doc).await?,
+                ConflictPolicy::Skip => {
+                    if !self.insert_if_absent(key, doc).await? {
+                        report.skipped += 1;
+                        continue;
+                    }
+                }
+                ConflictPolicy::Fail => {
+                    if !self.insert_if_absent(key, doc).await? {
+                        return Err(SessionResult::ImportConflict { imported: report.imported });
+                    }
+                }
+            }
+            report.imported += 1;
+        }
+
+        Ok(report)
+    }
+}
+
+
+// written is documents before this one
+fn write_record<W: Write, K: Serialize, Doc: Serialize>(writer: &mut W, format: ExportFormat, written: usize, key: &K, doc: &Doc) -> Result<(), SessionResult> {
+    match format {
+        ExportFormat::JsonLines => write_line(writer, key, doc),
+        ExportFormat::JsonArray => {
+            let separator: &[u8] = if written == 0 { b"\n" } else { b",\n" };
+            writer.write_all(separator).map_err(io_error)?;
+            write_json(writer, key, doc)
+        }
+        ExportFormat::Bincode => {
+            let record = bincode::serialize(&(key, doc)).map_err(|e| SessionResult::Err(StatusResult::Err(e.to_string())))?;
+            writer.write_all(&(record.len() as u32).to_le_bytes()).map_err(io_error)?;
+            writer.write_all(&record).map_err(io_error)
+        }
+    }
+}
+
+fn write_line<W: Write, K: Serialize, Doc: Serialize>(writer: &mut W, key: &K, doc: &Doc) -> Result<(), SessionResult> {
+    write_json(writer, key, doc)?;
+    writer.write_all(b"\n").map_err(io_error)
+}
+
+fn write_json<W: Write, K: Serialize, Doc: Serialize>(writer: &mut W, key: &K, doc: &Doc) -> Result<(), SessionResult> {
+    // through Value, its Map is ordered, so HashMap fields get sorted keys
+    let record = serde_json::to_value(doc)
+        .and_then(|doc| Ok(serde_json::json!({ "key": serde_json::to_value(key)?, "doc": doc })))
+        .map_err(|e| SessionResult::Err(StatusResult::Err(e.to_string())))?;
+
+    serde_json::to_writer(&mut *writer, &record).map_err(|e| SessionResult::Err(StatusResult::Err(e.to_string())))
+}
+
+fn io_error(e: io::Error) -> SessionResult {
+    SessionResult::Err(StatusResult::IoError(e))
+}
+
+
+struct CountingWriter<W: Write> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+
+
+#[derive(Deserialize)]
+struct Line<K, Doc> {
+    key: K,
+    doc: Doc,
+}
+
+// documents of an export file, one read at a time
+struct Records<R: Read> {
+    reader: BufReader<R>,
+    format: ExportFormat,
+
+    // of JsonLines, for errors
+    line: usize,
+
+    bytes: u64,
+
+    // elements of JsonArray not yet imported, read at first document
+    array: Option<std::iter::Enumerate<std::vec::IntoIter<serde_json::Value>>>,
+}
+
+impl<R: Read> Records<R> {
+    fn header(&mut self) -> Result<(), SessionResult> {
+        let mut header = vec![0u8; BINCODE_HEADER.len()];
+        match self.reader.read_exact(&mut header) {
+            Ok(_) if header == BINCODE_HEADER => {
+                self.bytes += header.len() as u64;
+                Ok(())
+            }
+            Ok(_) => Err(import_error("not a bincode export")),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(import_error("not a bincode export")),
+            Err(e) => Err(io_error(e)),
+        }
+    }
+
+    fn next<K: DeserializeOwned, Doc: DeserializeOwned>(&mut self) -> Result<Option<(K, Doc)>, SessionResult> {
+        match self.format {
+            ExportFormat::JsonLines => loop {
+                let mut line = String::new();
+                let n = self.reader.read_line(&mut line).map_err(io_error)?;
+                if n == 0 {
+                    return Ok(None);
+                }
+                self.line += 1;
+                self.bytes += n as u64;
+
+                if line.trim().is_empty() {
+                    continue;
+                }
+                return match serde_json::from_str::<Line<K, Doc>>(&line) {
+                    Ok(Line { key, doc }) => Ok(Some((key, doc))),
+                    Err(e) => Err(import_error(&format!("line {}: {}", self.line, e))),
+                };
+            },
+            ExportFormat::JsonArray => {
+                if self.array.is_none() {
+                    let mut bytes = vec![];
+                    self.reader.read_to_end(&mut bytes).map_err(io_error)?;
+                    self.bytes += bytes.len() as u64;
+
+                    let array: Vec<serde_json::Value> = serde_json::from_slice(&bytes).map_err(|e| import_error(&e.to_string()))?;
+                    self.array = Some(array.into_iter().enumerate());
+                }
+
+                match self.array.as_mut().and_then(|array| array.next()) {
+                    Some((index, value)) => match serde_json::from_value::<Line<K, Doc>>(value) {
+                        Ok(Line { key, doc }) => Ok(Some((key, doc))),
+                        Err(e) => Err(import_error(&format!("element {}: {}", index, e))),
+                    },
+                    None => Ok(None),
+                }
+            }
+            ExportFormat::Bincode => {
+                // end of file only between documents
+                if self.reader.fill_buf().map_err(io_error)?.is_empty() {
+                    return Ok(None);
+                }
+
+                let mut len = [0u8; 4];
+                self.reader.read_exact(&mut len).map_err(io_error)?;
+                let mut record = vec![0u8; u32::from_le_bytes(len) as usize];
+                self.reader.read_exact(&mut record).map_err(io_error)?;
+                self.bytes += (len.len() + record.len()) as u64;
+
+                bincode::deserialize::<(K, Doc)>(&record)
+                    .map(Some)
+                    .map_err(|e| import_error(&e.to_string()))
+            }
+        }
+    }
+}
+
+fn import_error(reason: &str) -> SessionResult {
+    SessionResult::Err(StatusResult::Err(format!("import: {}", reason)))
+}
+
+
+// sorted runs on disk: | len (u32 le) | encoded key | ..., removed on drop
+struct Runs {
+    files: Vec<PathBuf>,
+}
+
+impl Runs {
+    fn spill(&mut self, buffer: &mut Vec<Vec<u8>>, opts: &ExportOptions) -> Result<(), SessionResult> {
+        buffer.sort_unstable();
+
+        let dir = opts.spill_dir.clone().unwrap_or_else(std::env::temp_dir);
+        let path = dir.join(format!(
+            "darkbird-export-{}-{}.run",
+            std::process::id(),
+            RUN_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        let mut file = BufWriter::new(File::create(&path).map_err(io_error)?);
+        self.files.push(path);
+
+        for encoded in buffer.drain(..) {
+            file.write_all(&(encoded.len() as u32).to_le_bytes()).map_err(io_error)?;
+            file.write_all(&encoded).map_err(io_error)?;
+        }
+
+        file.flush().map_err(io_error)
+    }
+
+    fn merge(self) -> Result<Merge, SessionResult> {
+        let mut readers = Vec::with_capacity(self.files.len());
+        for path in self.files.iter() {
+            readers.push(BufReader::new(File::open(path).map_err(io_error)?));
+        }
+
+        let mut merge = Merge { readers, heap: BinaryHeap::new(), _runs: self };
+        for index in 0..merge.readers.len() {
+            merge.advance(index).map_err(io_error)?;
+        }
+        Ok(merge)
+    }
+}
+
+impl Drop for Runs {
+    fn drop(&mut self) {
+        for path in self.files.iter() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+
+// k-way merge of sorted runs, smallest head first
+struct Merge {
+    readers: Vec<BufReader<File>>,
+    heap: BinaryHeap<Reverse<(Vec<u8>, usize)>>,
+
+    // keep run files until merge done
+    _runs: Runs,
+}
+
+impl Merge {
+    fn advance(&mut self, index: usize) -> io::Result<()> {
+        let reader = &mut self.readers[index];
+
+        let mut len = [0u8; 4];
+        match reader.read_exact(&mut len) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        let mut encoded = vec![0u8; u32::from_le_bytes(len) as usize];
+        reader.read_exact(&mut encoded)?;
+        self.heap.push(Reverse((encoded, index)));
+        Ok(())
+    }
+}
+
+impl Iterator for Merge {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((encoded, index)) = self.heap.pop()?;
+        match self.advance(index) {
+            Ok(_) => Some(Ok(encoded)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use dashmap::mapref::one::Ref;
+use serde::{de::DeserializeOwned, Serialize};
+use std::hash::Hash;
+
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    darkbird::{filter::EventFilter, key_codec::KeyCodec, router::{Filter, SubscriptionId}, SessionResult, StatusResult},
+    document::Document,
+};
+
+use super::{Event, Storage};
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static
+        + KeyCodec,
+{
+    /// Subscribe to Reporter with a filter on document changes evaluated by router before sending,
+    /// so document changes not matching never reach sender. see `EventFilter`.
+    ///
+    /// metadata read by filters (tags, encoded key) is built once per event on write path
+    /// and shared by every subscriber, just while a filtered subscriber exist.
+    /// not available with write coalescing, whose flushed events carry no metadata
+    pub async fn subscribe_matching(&self, sender: Sender<Event<K, Doc>>, filter: EventFilter) -> Result<SubscriptionId, SessionResult> {
+        if self.off_reporter {
+            return Err(SessionResult::Err(StatusResult::ReporterIsOff));
+        }
+
+        if self.coalescer.is_some() {
+            return Err(SessionResult::UnImplement);
+        }
+
+        // active before registered, so no event reach subscriber without metadata
+        self.filtering.register(&filter, |key| key.encode());
+        self.register_subscriber(sender, Some(Filter::Meta(filter))).await
+    }
+
+    /// documents whose encoded key start with prefix, ordered by encoded key,
+    /// e.g. `scan_prefix(&user_id.encode())` for `(user_id, session_id)` keys.
+    ///
+    /// first scan or `key_range` order keys held, then writes keep them ordered.
+    /// `CompressedValue` with value compression, see `lookup_ref`
+    pub fn scan_prefix(&self, prefix: &[u8]) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult> {
+        self.fill_encoded_order()?;
+        Ok(self.encoded_order.prefix(prefix).into_iter().filter_map(|key| self.get_visible(&key)).collect())
+    }
+
+    /// documents with key in [from, to), ordered by encoded key, see `scan_prefix`
+    pub fn key_range(&self, from: &K, to: &K) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult> {
+        self.fill_encoded_order()?;
+        let keys = self.encoded_order.range(&from.encode(), &to.encode());
+        Ok(keys.into_iter().filter_map(|key| self.get_visible(&key)).collect())
+    }
+
+    fn fill_encoded_order(&self) -> Result<(), SessionResult> {
+        self.borrowable()?;
+        self.encoded_order.fill(K::encode, || self.collection.iter().map(|rf| rf.key().clone()).collect());
+        Ok(())
+    }
+}
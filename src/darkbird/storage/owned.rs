@@ -0,0 +1,216 @@
+use bytes::Bytes;
+use dashmap::try_result::TryResult;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{borrow::Cow, collections::HashSet, hash::Hash, ops::Deref, sync::Arc};
+use tokio::{sync::mpsc::Sender, task::JoinHandle};
+
+use crate::{
+    darkbird::{compression::Compression, latency::Operation, metrics::MetricEvent, search::tokenize, SessionResult},
+    document::{Document, FieldValue},
+};
+
+use super::Storage;
+
+
+
+/// Document cloned out of store behind an Arc, returned by `lookup_pinned`.
+///
+/// hold no lock and no reference to store, so it stay valid after store is dropped,
+/// and its document keep same address until last clone is dropped, e.g. for FFI callers
+#[derive(Debug)]
+pub struct PinnedDoc<Doc>(Arc<Doc>);
+
+impl<Doc> PinnedDoc<Doc> {
+    pub(crate) fn new(doc: Doc) -> Self {
+        PinnedDoc(Arc::new(doc))
+    }
+
+    /// address of document, valid while this or a clone is alive
+    pub fn as_ptr(&self) -> *const Doc {
+        Arc::as_ptr(&self.0)
+    }
+
+    pub fn into_arc(self) -> Arc<Doc> {
+        self.0
+    }
+}
+
+impl PinnedDoc<Bytes> {
+    /// document of `BytesStorage::lookup_pinned`
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<Doc> Clone for PinnedDoc<Doc> {
+    fn clone(&self) -> Self {
+        PinnedDoc(self.0.clone())
+    }
+}
+
+impl<Doc> Deref for PinnedDoc<Doc> {
+    type Target = Doc;
+
+    fn deref(&self) -> &Doc {
+        &self.0
+    }
+}
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// `lookup_by_tag` with documents cloned out, no lock held once returned,
+    /// work with value compression
+    pub fn lookup_by_tag_owned(&self, tag: &str) -> Vec<(K, Doc)> {
+        let result = self.owned(self.tag_keys(tag));
+        self.metrics.record(MetricEvent::LookupByTag { hit: !result.is_empty() });
+        result
+    }
+
+    /// `fetch_view` with documents cloned out, see `lookup_by_tag_owned`
+    pub fn fetch_view_owned(&self, view_name: &str) -> Vec<(K, Doc)> {
+        let result = self.owned(self.tag_keys(&self.tag_index.view_key_maker(view_name)));
+        self.metrics.record(MetricEvent::View { hit: !result.is_empty() });
+        result
+    }
+
+    /// `lookup_by_index` with document cloned out, work with value compression
+    pub fn lookup_by_index_owned(&self, index_key: &str) -> Option<(K, Doc)> {
+        let key = self.index_keys(index_key).pop()?;
+        let doc = self.get_owned(&key);
+        self.metrics.record(MetricEvent::LookupByIndex { hit: doc.is_some() });
+        doc.map(|doc| (key, doc))
+    }
+
+    /// `try_range` with documents cloned out, work with value compression
+    pub fn range_owned(&self, field_name: &str, from: impl Into<FieldValue>, to: impl Into<FieldValue>) -> Result<Vec<(K, Doc)>, SessionResult> {
+        let started = self.latency.start();
+        let result = self.owned(self.range_keys(field_name, from.into(), to.into())?);
+        self.latency.record(Operation::Range, started);
+        self.metrics.record(MetricEvent::Range { hit: !result.is_empty() });
+        Ok(result)
+    }
+
+    /// `search` with documents cloned out, most occurrences first, work with value compression
+    pub fn search_owned(&self, text: impl AsRef<str>) -> Vec<(K, Doc)> {
+        let started = self.latency.start();
+        let result = self.owned(self.search_keys(text.as_ref()));
+        self.latency.record(Operation::Search, started);
+        self.metrics.record(MetricEvent::Search { hit: !result.is_empty() });
+        result
+    }
+
+    /// `lookup_owned` behind an Arc, see `PinnedDoc`
+    pub fn lookup_pinned(&self, key: &K) -> Option<PinnedDoc<Doc>> {
+        self.lookup_owned(key).map(PinnedDoc::new)
+    }
+
+    /// keys of documents visible when called, copied out so no lock is held
+    /// while iterated and they can outlive store, work with value compression
+    pub fn iter_keys_owned(&self) -> std::vec::IntoIter<K> {
+        let mut keys = self.keys();
+        keys.retain(|key| !self.expired(key) && !self.is_quarantined(key));
+        keys.into_iter()
+    }
+
+    /// `lookup_owned` that never wait: `SessionResult::WouldBlock` when shard of key is
+    /// locked for writing, e.g. by a write of same shard or a `RefMut` held by caller
+    pub fn try_lookup(&self, key: &K) -> Result<Option<Doc>, SessionResult> {
+        if self.never_inserted(key) || self.expired(key) || self.is_quarantined(key) {
+            return Ok(None);
+        }
+
+        let doc = match &self.compression {
+            Some(_) => match self.compressed.try_get(key) {
+                TryResult::Present(rf) => Compression::decompress(rf.value()).ok(),
+                TryResult::Absent => None,
+                TryResult::Locked => return Err(SessionResult::WouldBlock),
+            },
+            None => match self.collection.try_get(key) {
+                TryResult::Present(rf) => Some(rf.value().clone()),
+                TryResult::Absent => None,
+                TryResult::Locked => return Err(SessionResult::WouldBlock),
+            },
+        };
+
+        let doc = doc.filter(|doc| self.check_read(key, doc));
+        if doc.is_some() {
+            self.record_access(key);
+        }
+        self.metrics.record(MetricEvent::Lookup { hit: doc.is_some() });
+        Ok(doc)
+    }
+
+    // keys are copied out of index first, so no index lock is held while documents are cloned
+    fn owned(&self, keys: Vec<K>) -> Vec<(K, Doc)> {
+        keys.into_iter()
+            .filter_map(|key| self.get_owned(&key).map(|doc| (key, doc)))
+            .collect()
+    }
+}
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + Sync + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// `search_to` on a spawned task, handle resolve to number of documents sent.
+    /// task must own text: a `String` is moved, a `&str` copied
+    pub fn search_async<'t>(self: &Arc<Self>, text: impl Into<Cow<'t, str>>, sender: Sender<(K, Doc)>) -> JoinHandle<usize> {
+        let storage = self.clone();
+        let text = text.into().into_owned();
+        tokio::spawn(async move { storage.search_to(&text, &sender).await })
+    }
+
+    /// Send documents contain any word of text while postings are walked: words in order
+    /// of text, keys of a word in index order, each key once. a posting is copied out
+    /// before its documents are sent, so no index lock is held while sender wait.
+    /// stop early when receiver is dropped, return number of documents sent
+    pub async fn search_to(&self, text: &str, sender: &Sender<(K, Doc)>) -> usize {
+        let started = self.latency.start();
+        let mut seen = HashSet::new();
+        let mut sent = 0;
+
+        'walk: for word in tokenize(text) {
+            let posting = self.inverted_index.live().posting_keys(&word);
+            for key in posting {
+                if !seen.insert(key.clone()) {
+                    continue;
+                }
+                let Some(doc) = self.get_owned(&key) else { continue };
+                if sender.send((key, doc)).await.is_err() {
+                    break 'walk;
+                }
+                sent += 1;
+            }
+        }
+
+        self.latency.record(Operation::Search, started);
+        self.metrics.record(MetricEvent::Search { hit: sent > 0 });
+        sent
+    }
+}
@@ -0,0 +1,190 @@
+use dashmap::mapref::one::Ref;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::{BinaryHeap, HashSet},
+    hash::Hash,
+};
+
+use crate::{
+    darkbird::{
+        metrics::MetricEvent,
+        query::{Order, PageOrder},
+        SessionResult,
+    },
+    document::{Document, FieldValue},
+};
+
+use super::Storage;
+
+
+
+/// documents of a page of `Storage::iter_page` and cursor of next page
+pub type KeyPage<'a, K, Doc> = (Vec<Ref<'a, K, Doc>>, Option<K>);
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Page of `lookup_by_tag`: documents `offset..offset + limit` in `order`.
+    ///
+    /// keys of tag are copied and ordered, then just documents of page are looked up,
+    /// no index lock is held while they are, so holding `Ref`s of an earlier page is fine.
+    /// empty when offset is past the end or limit is 0
+    pub fn lookup_by_tag_page(&self, tag: &str, offset: usize, limit: usize, order: &PageOrder) -> Vec<Ref<'_, K, Doc>> {
+        let page = self.page(self.tag_keys(tag), offset, limit, order);
+        self.metrics.record(MetricEvent::LookupByTag { hit: !page.is_empty() });
+        page
+    }
+
+    /// page of `fetch_view`, see `lookup_by_tag_page`
+    pub fn fetch_view_page(&self, view_name: &str, offset: usize, limit: usize, order: &PageOrder) -> Vec<Ref<'_, K, Doc>> {
+        let page = self.page(self.tag_keys(&self.tag_index.view_key_maker(view_name)), offset, limit, order);
+        self.metrics.record(MetricEvent::View { hit: !page.is_empty() });
+        page
+    }
+
+    /// page of `search`, see `lookup_by_tag_page`
+    pub fn search_page(&self, text: &str, offset: usize, limit: usize, order: &PageOrder) -> Vec<Ref<'_, K, Doc>> {
+        let page = self.page(self.search_keys(text), offset, limit, order);
+        self.metrics.record(MetricEvent::Search { hit: !page.is_empty() });
+        page
+    }
+
+    /// page of `try_range`, see `lookup_by_tag_page`
+    pub fn range_page(
+        &self,
+        field_name: &str,
+        from: impl Into<FieldValue>,
+        to: impl Into<FieldValue>,
+        offset: usize,
+        limit: usize,
+        order: &PageOrder,
+    ) -> Result<Vec<Ref<'_, K, Doc>>, SessionResult> {
+        let keys = self.range_keys(field_name, from.into(), to.into())?;
+        let page = self.page(keys, offset, limit, order);
+        self.metrics.record(MetricEvent::Range { hit: !page.is_empty() });
+        Ok(page)
+    }
+
+    /// Documents in key order after `cursor` (from first key without), and cursor of next page,
+    /// None once last page is returned. cursor is key of last document of page,
+    /// it needn't still exist when next page is asked.
+    ///
+    /// with `Options::with_ordered_keys` keys of page are seeked, else every key is walked.
+    /// page_size 0 return no documents and cursor unchanged
+    pub fn iter_page(&self, cursor: Option<K>, page_size: usize) -> KeyPage<'_, K, Doc> {
+        if page_size == 0 {
+            return (vec![], cursor);
+        }
+
+        // one key past page tells whether a next page exist
+        let mut keys = match &self.key_order {
+            Some(order) => {
+                let mut keys = Vec::with_capacity(page_size + 1);
+                let mut from = cursor;
+                while keys.len() <= page_size {
+                    let batch = order.after(from.as_ref(), page_size + 1);
+                    let exhausted = batch.len() <= page_size;
+                    from = batch.last().cloned();
+                    keys.extend(batch.into_iter().filter(|key| self.contains_key(key)));
+                    if exhausted {
+                        break;
+                    }
+                }
+                keys
+            }
+            None => {
+                // page_size + 1 smallest keys after cursor
+                let mut heap = BinaryHeap::with_capacity(page_size + 2);
+                for rf in self.collection.iter() {
+                    let key = rf.key();
+                    // shard of key is held, so not looked up again as contains_key would
+                    if cursor.as_ref().is_some_and(|cursor| key <= cursor) || self.expired(key) || self.is_quarantined(key) {
+                        continue;
+                    }
+                    if heap.len() <= page_size {
+                        heap.push(key.clone());
+                    } else if heap.peek().is_some_and(|max| key < max) {
+                        heap.pop();
+                        heap.push(key.clone());
+                    }
+                }
+                heap.into_sorted_vec()
+            }
+        };
+
+        let more = keys.len() > page_size;
+        keys.truncate(page_size);
+
+        let next = if more { keys.last().cloned() } else { None };
+        let page = keys.iter().filter_map(|key| self.get_visible(key)).collect();
+
+        (page, next)
+    }
+
+    // documents past their ttl or quarantined are left out before offset is applied,
+    // so a page is short only at the end
+    fn page(&self, keys: Vec<K>, offset: usize, limit: usize, order: &PageOrder) -> Vec<Ref<'_, K, Doc>> {
+        if limit == 0 {
+            return vec![];
+        }
+
+        let mut result = Vec::with_capacity(limit.min(keys.len()));
+        let visible = self
+            .order_keys(keys, order)
+            .into_iter()
+            .filter(|key| self.contains_key(key))
+            .skip(offset);
+
+        for key in visible {
+            if let Some(rf) = self.get_visible(&key) {
+                result.push(rf);
+                if result.len() == limit {
+                    break;
+                }
+            }
+        }
+
+        result
+    }
+
+    fn order_keys(&self, mut keys: Vec<K>, order: &PageOrder) -> Vec<K> {
+        match order {
+            PageOrder::Key(order) => {
+                keys.sort();
+                keys.dedup();
+                if *order == Order::Desc {
+                    keys.reverse();
+                }
+                keys
+            }
+            PageOrder::Field(field_name, order) => {
+                let mut rest: HashSet<K> = keys.into_iter().collect();
+                let mut ordered = Vec::with_capacity(rest.len());
+
+                for key in self.range_order(field_name, *order == Order::Desc) {
+                    if rest.remove(&key) {
+                        ordered.push(key);
+                    }
+                }
+
+                let mut tail: Vec<K> = rest.into_iter().collect();
+                tail.sort();
+                ordered.extend(tail);
+                ordered
+            }
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::hash::Hash;
+
+use crate::{
+    darkbird::{compression::Compression, key_codec::KeyCodec, SessionResult},
+    document::Document,
+};
+
+use super::{bulk::BulkProgress, compact::CompactReport, stats::wal_usage, Storage};
+
+
+
+// keys kept in PlanReport::sample
+const SAMPLE: usize = 16;
+
+// simple_wal framing: header per page, length and crc per record
+const PAGE_HEADER: u64 = 8;
+const RECORD_FRAME: u64 = 8 + 4;
+
+// bincode variant tag of LogRecord, and deadline of InsertWithExpiry
+const RECORD_TAG: u64 = 4;
+const DEADLINE: u64 = 8;
+
+
+/// what a destructive operation would do, returned by `dry_run` of a plan
+#[derive(Clone, Debug)]
+pub struct PlanReport<K> {
+    pub operation: &'static str,
+
+    // documents removed, or rewritten by compaction
+    pub documents: usize,
+
+    // matching documents a write plugin would veto
+    pub rejected: usize,
+
+    // first matching keys, at most 16
+    pub sample: Vec<K>,
+
+    // removals: serialized size of documents removed (`BulkProgress::reclaimed` once executed),
+    // compaction: estimated WAL bytes freed
+    pub bytes: u64,
+}
+
+impl<K> PlanReport<K> {
+    fn new(operation: &'static str) -> Self {
+        PlanReport {
+            operation,
+            documents: 0,
+            rejected: 0,
+            sample: vec![],
+            bytes: 0,
+        }
+    }
+
+    fn push_sample(&mut self, key: &K)
+    where
+        K: Clone,
+    {
+        if self.sample.len() < SAMPLE {
+            self.sample.push(key.clone());
+        }
+    }
+}
+
+
+/// Removal built by `Storage::plan_remove_where` or `Storage::plan_remove_prefix`,
+/// `dry_run` report it without mutating anything, `execute` apply it.
+///
+/// documents are matched while walking store, never collecting all keys,
+/// so a dry run of a store wide sweep stay in constant memory.
+/// both walk store at their own time, writes between them can change what match
+pub struct RemovalPlan<'a, K, Doc: Document, F> {
+    storage: &'a Storage<K, Doc>,
+    operation: &'static str,
+    matcher: F,
+}
+
+impl<'a, K, Doc, F> RemovalPlan<'a, K, Doc, F>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    F: Fn(&K, &Doc) -> bool,
+{
+    /// counts, sample keys and bytes removal would reclaim, plugin vetoes included
+    pub fn dry_run(&self) -> PlanReport<K> {
+        let mut report = PlanReport::new(self.operation);
+
+        self.storage.scan_docs(|key, doc| {
+            if !(self.matcher)(key, doc) {
+                return;
+            }
+
+            if self.storage.plugins.iter().any(|plugin| plugin.on_remove(key).is_err()) {
+                report.rejected += 1;
+                return;
+            }
+
+            report.documents += 1;
+            report.bytes += bincode::serialized_size(doc).unwrap_or(0);
+            report.push_sample(key);
+        });
+
+        report
+    }
+
+    /// remove matching documents, see `Storage::remove_where`
+    pub async fn execute(self) -> Result<BulkProgress, SessionResult> {
+        let mut keys = vec![];
+        self.storage.scan_docs(|key, doc| {
+            if (self.matcher)(key, doc) {
+                keys.push(key.clone());
+            }
+        });
+
+        self.storage.bulk_remove(self.operation, keys).await
+    }
+}
+
+
+/// Compaction built by `Storage::plan_compact`, see `Storage::compact`
+pub struct CompactPlan<'a, K, Doc: Document> {
+    storage: &'a Storage<K, Doc>,
+}
+
+impl<'a, K, Doc> CompactPlan<'a, K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// documents compaction would write and estimate of WAL bytes it would free,
+    /// from size of WAL directory and of compacted pages documents encode to.
+    /// nothing is written, writes are not paused
+    pub fn dry_run(&self) -> Result<PlanReport<K>, SessionResult> {
+        let storage = self.storage;
+        if storage.coalescer.is_some() {
+            return Err(SessionResult::UnImplement);
+        }
+
+        let mut report = PlanReport::new("compact");
+        if storage.off_disk {
+            return Ok(report);
+        }
+
+        let mut records = 0;
+        storage.scan_docs(|key, doc| {
+            let deadline = storage.expiry.deadline(key).map_or(0, |_| DEADLINE);
+            let record = RECORD_TAG
+                + bincode::serialized_size(key).unwrap_or(0)
+                + bincode::serialized_size(doc).unwrap_or(0)
+                + deadline;
+
+            records += record + RECORD_FRAME;
+            report.documents += 1;
+            report.push_sample(key);
+        });
+
+        // compacted pages each start with header record, first page written after them is empty
+        let compacted = report.documents.div_ceil(storage.page_size - 1).max(1) as u64;
+        let headers = compacted * (storage.codec.header().len() as u64 + RECORD_FRAME);
+        let (_, before) = wal_usage(&storage.wal_dir);
+        report.bytes = before.saturating_sub(records + headers + (compacted + 1) * PAGE_HEADER);
+
+        Ok(report)
+    }
+
+    pub async fn execute(self) -> Result<CompactReport, SessionResult> {
+        self.storage.compact().await
+    }
+}
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// plan of `remove_where`
+    pub fn plan_remove_where<F>(&self, predicate: F) -> RemovalPlan<'_, K, Doc, F>
+    where
+        F: Fn(&K, &Doc) -> bool,
+    {
+        RemovalPlan {
+            storage: self,
+            operation: "remove_where",
+            matcher: predicate,
+        }
+    }
+
+    /// plan of `compact`
+    pub fn plan_compact(&self) -> CompactPlan<'_, K, Doc> {
+        CompactPlan { storage: self }
+    }
+
+    // every document not past its ttl, walked in place without snapshot of keys,
+    // f must not write to this store
+    fn scan_docs(&self, mut f: impl FnMut(&K, &Doc)) {
+        match &self.compression {
+            Some(_) => {
+                for rf in self.compressed.iter() {
+                    if self.expired(rf.key()) {
+                        continue;
+                    }
+                    if let Ok(doc) = Compression::decompress(rf.value()) {
+                        f(rf.key(), &doc);
+                    }
+                }
+            }
+            None => {
+                for rf in self.collection.iter() {
+                    if !self.expired(rf.key()) {
+                        f(rf.key(), rf.value());
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static
+        + KeyCodec,
+{
+    /// plan of `remove_prefix`
+    pub fn plan_remove_prefix(&self, prefix: &[u8]) -> RemovalPlan<'_, K, Doc, impl Fn(&K, &Doc) -> bool> {
+        let prefix = prefix.to_vec();
+        RemovalPlan {
+            storage: self,
+            operation: "remove_prefix",
+            matcher: move |key: &K, _: &Doc| key.encode().starts_with(&prefix),
+        }
+    }
+}
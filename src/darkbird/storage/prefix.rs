@@ -0,0 +1,52 @@
+use dashmap::mapref::one::Ref;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{borrow::Borrow, hash::Hash};
+
+use crate::document::Document;
+
+use super::Storage;
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static
+        + AsRef<str>
+        + Borrow<str>,
+{
+    /// Documents whose key start with prefix, in key order, every document for empty prefix.
+    ///
+    /// with `Options::with_ordered_keys` keys of prefix are seeked in ordered keys,
+    /// O(log n + result), else every key is walked. not available with value compression, as `lookup`
+    pub fn iter_prefix(&self, prefix: &str) -> Vec<Ref<'_, K, Doc>> {
+        match &self.key_order {
+            Some(order) => order
+                .prefix(prefix)
+                .iter()
+                .filter_map(|key| self.get_visible(key))
+                .collect(),
+            None => {
+                let mut keys: Vec<K> = self
+                    .collection
+                    .iter()
+                    .filter(|rf| rf.key().as_ref().starts_with(prefix))
+                    .map(|rf| rf.key().clone())
+                    .collect();
+                keys.sort();
+
+                keys.iter().filter_map(|key| self.get_visible(key)).collect()
+            }
+        }
+    }
+}
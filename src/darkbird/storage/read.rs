@@ -0,0 +1,131 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, hash::Hash, sync::Arc};
+use tokio::time::Instant;
+
+use crate::{
+    darkbird::{
+        metrics::MetricEvent,
+        read_snapshot::{Frozen, ReadPreference, ReadResult},
+    },
+    document::Document,
+};
+
+use super::Storage;
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// lookup served as `pref` ask, work with value compression
+    pub async fn lookup_with(&self, key: &K, pref: ReadPreference) -> ReadResult<Option<Doc>> {
+        match self.snapshot_for(pref).await {
+            Some(frozen) => frozen.result(frozen.docs.get(key).cloned()),
+            None => ReadResult::live(self.lookup_owned(key)),
+        }
+    }
+
+    /// documents of keys found, see `lookup_with`
+    pub async fn gets_with(&self, keys: Vec<&K>, pref: ReadPreference) -> ReadResult<Vec<Doc>> {
+        match self.snapshot_for(pref).await {
+            Some(frozen) => {
+                let docs = keys.into_iter().filter_map(|key| frozen.docs.get(key).cloned()).collect();
+                frozen.result(docs)
+            }
+            None => ReadResult::live(keys.into_iter().filter_map(|key| self.lookup_owned(key)).collect()),
+        }
+    }
+
+    /// every document, in no particular order
+    pub async fn iter_with(&self, pref: ReadPreference) -> ReadResult<Vec<(K, Doc)>> {
+        match self.snapshot_for(pref).await {
+            Some(frozen) => {
+                let docs = frozen.docs.iter().map(|(key, doc)| (key.clone(), doc.clone())).collect();
+                frozen.result(docs)
+            }
+            None => {
+                let mut docs = vec![];
+                self.for_each_doc(|key, doc| docs.push((key.clone(), doc.clone()))).await;
+                ReadResult::live(docs)
+            }
+        }
+    }
+
+    /// full-text search, matches come from live inverted index
+    /// and documents from snapshot, so a document inserted after snapshot isn't returned
+    pub async fn search_with(&self, text: &str, pref: ReadPreference) -> ReadResult<Vec<Doc>> {
+        let keys = self.search_keys(text);
+        match self.snapshot_for(pref).await {
+            Some(frozen) => {
+                let docs = keys.iter().filter_map(|key| frozen.docs.get(key).cloned()).collect();
+                frozen.result(docs)
+            }
+            None => {
+                let docs: Vec<Doc> = keys.iter().filter_map(|key| self.get_owned(key)).collect();
+                self.metrics.record(MetricEvent::Search { hit: !docs.is_empty() });
+                ReadResult::live(docs)
+            }
+        }
+    }
+
+    /// Refresh read snapshot now, for callers driving refresh from their own task
+    /// instead of letting a Snapshot read pay it. no-op if read snapshot isn't enabled
+    /// or a refresh is already running
+    pub async fn refresh_snapshot(&self) {
+        self.refresh(true).await;
+    }
+
+    /// age of read snapshot, None if not enabled or not taken yet
+    pub fn snapshot_age(&self) -> Option<std::time::Duration> {
+        let frozen = self.read_snapshot.as_ref()?.current()?;
+        Some(frozen.taken.elapsed())
+    }
+
+    // None: serve from live store
+    async fn snapshot_for(&self, pref: ReadPreference) -> Option<Arc<Frozen<K, Doc>>> {
+        let max_staleness = match pref {
+            ReadPreference::Fresh => return None,
+            ReadPreference::Snapshot => None,
+            ReadPreference::SnapshotMaxStaleness(bound) => Some(bound),
+        };
+
+        self.refresh(false).await;
+
+        let frozen = self.read_snapshot.as_ref()?.current()?;
+        match max_staleness {
+            Some(bound) if frozen.taken.elapsed() > bound => None,
+            _ => Some(frozen),
+        }
+    }
+
+    // copy documents cooperatively, snapshot age count from start of copy
+    async fn refresh(&self, force: bool) {
+        let refresh = match self.read_snapshot.as_ref().and_then(|snapshot| snapshot.begin_refresh(force)) {
+            Some(refresh) => refresh,
+            None => return,
+        };
+
+        let taken = Instant::now();
+        let taken_at = self.now();
+
+        let mut docs = HashMap::new();
+        self.for_each_doc(|key, doc| {
+            docs.insert(key.clone(), doc.clone());
+        })
+        .await;
+
+        refresh.publish(Frozen { docs, taken_at, taken });
+    }
+}
@@ -0,0 +1,267 @@
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+use std::hash::Hash;
+use tokio::time::Instant;
+
+use crate::{
+    darkbird::{
+        admin::{AdminLog, AdminOp},
+        compression::Compression,
+        index::{inverted_index::InvertedIndex, range::RangeIndex, shadow::Shadowed},
+        search::tokenize,
+        SessionResult,
+    },
+    document::Document,
+};
+
+use super::{Storage, Structure};
+
+
+
+// keys filled into shadow per gate acquisition
+const REBUILD_BATCH: usize = 1000;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RebuildState {
+    Running,
+
+    // shadow swapped in (or repaired in place)
+    Done,
+
+    // rebuild future dropped before swap, shadow discarded
+    Aborted,
+}
+
+
+/// progress of `Storage::rebuild_index`
+#[derive(Clone, Debug)]
+pub struct RebuildProgress {
+    pub structure: Structure,
+    pub state: RebuildState,
+
+    // documents filled into shadow
+    pub scanned: usize,
+
+    // documents at rebuild start
+    pub total: usize,
+
+    pub started: Instant,
+    pub finished: Option<Instant>,
+}
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Rebuild a derived structure while store stay online.
+    ///
+    /// Range and Text: a shadow filled from documents in batches while live writes
+    /// go to both, queries use old index until shadow atomically swapped in.
+    /// Index and Tags hand out references to their entries, so they repaired
+    /// in place (writes wait until done, reads continue).
+    ///
+    /// Derived structures are rebuilt from WAL on open, so a crash during rebuild
+    /// restart from scratch automatically, a dropped rebuild future discard its shadow
+    pub async fn rebuild_index(&self, structure: Structure) -> Result<RebuildProgress, SessionResult> {
+        let op = self.admin.start("rebuild_index", format!("structure={:?}", structure));
+
+        {
+            let mut progress = self.rebuild.lock();
+            if let Some(RebuildProgress { state: RebuildState::Running, .. }) = progress.as_ref() {
+                self.admin.finish(op, Err(SessionResult::RebuildInProgress.to_string()));
+                return Err(SessionResult::RebuildInProgress);
+            }
+
+            *progress = Some(RebuildProgress {
+                structure,
+                state: RebuildState::Running,
+                scanned: 0,
+                total: 0,
+                started: Instant::now(),
+                finished: None,
+            });
+        }
+
+        let mut guard = ProgressGuard { progress: &self.rebuild, admin: &self.admin, op: Some(op) };
+
+        match structure {
+            Structure::Index | Structure::Tags => {
+                let _gate = self.rebuild_gate.write().await;
+                let report = self.reconcile(Some(structure), true).await;
+                self.update_progress(|p| {
+                    p.total = report.documents;
+                    p.scanned = report.documents;
+                });
+            }
+            Structure::Range => {
+                self.rebuild_shadow(&self.range_index, RangeIndex::new(), |index, key, doc| {
+                    index.insert(key, doc)
+                })
+                .await
+            }
+            Structure::Text => {
+                self.rebuild_shadow(&self.inverted_index, InvertedIndex::new(), |index, key, doc| {
+                    if let Some(content) = doc.get_content() {
+                        for word in tokenize(&content) {
+                            index.insert_word(key, word);
+                        }
+                    }
+                })
+                .await
+            }
+        }
+
+        // results cached from structure before rebuild
+        self.clear_query_cache();
+
+        self.update_progress(|p| {
+            p.state = RebuildState::Done;
+            p.finished = Some(Instant::now());
+        });
+
+        if let Some(op) = guard.op.take() {
+            self.admin.finish(op, Ok(0));
+        }
+
+        Ok(self.rebuild_progress().unwrap())
+    }
+
+    /// Derived structures but hash index, after WAL replayed with `Options::with_low_memory_replay`.
+    ///
+    /// one pass over documents per structure (tags, views and variants, range, text),
+    /// so beside documents just structures already built and the one being built are held
+    pub(super) fn build_derived(&self) {
+        self.each_held(|key, doc| self.tag_index.insert(key, doc));
+
+        self.each_held(|key, doc| {
+            if let Some(view_name) = doc.filter() {
+                self.tag_index.insert_view(&view_name, key)
+            }
+            if let Some(variant) = doc.variant() {
+                self.tag_index.insert_variant(variant, key)
+            }
+        });
+
+        self.each_held(|key, doc| self.range_index.live().insert(key, doc));
+
+        self.each_held(|key, doc| {
+            if let Some(content) = doc.get_content() {
+                for word in tokenize(&content) {
+                    self.inverted_index.live().insert_word(key, word);
+                }
+            }
+        });
+    }
+
+    // every document in memory walked in place, compressed ones decompressed one at a time
+    pub(super) fn each_held(&self, mut f: impl FnMut(&K, &Doc)) {
+        match &self.compression {
+            Some(_) => {
+                for rf in self.compressed.iter() {
+                    if let Ok(doc) = Compression::decompress(rf.value()) {
+                        f(rf.key(), &doc);
+                    }
+                }
+            }
+            None => {
+                for rf in self.collection.iter() {
+                    f(rf.key(), rf.value());
+                }
+            }
+        }
+    }
+
+    /// progress of running or last rebuild
+    pub fn rebuild_progress(&self) -> Option<RebuildProgress> {
+        self.rebuild.lock().clone()
+    }
+
+    async fn rebuild_shadow<I>(&self, target: &Shadowed<I>, fresh: I, fill: impl Fn(&I, &K, &Doc)) {
+        // install shadow and snapshot keys with no write in flight,
+        // so every write after snapshot reach shadow too
+        let (shadow, keys) = {
+            let _gate = self.rebuild_gate.write().await;
+            (target.begin(fresh), self.keys())
+        };
+
+        let mut guard = AbortGuard { target, armed: true };
+
+        self.update_progress(|p| p.total = keys.len());
+
+        for batch in keys.chunks(REBUILD_BATCH) {
+            {
+                let _gate = self.rebuild_gate.write().await;
+                for key in batch {
+                    if let Some(doc) = self.stored(key) {
+                        fill(&shadow, key, &doc);
+                    }
+                }
+            }
+
+            self.update_progress(|p| p.scanned += batch.len());
+            tokio::task::yield_now().await;
+        }
+
+        let _gate = self.rebuild_gate.write().await;
+        target.swap();
+        guard.armed = false;
+    }
+
+    fn update_progress(&self, f: impl FnOnce(&mut RebuildProgress)) {
+        if let Some(progress) = self.rebuild.lock().as_mut() {
+            f(progress);
+        }
+    }
+}
+
+
+
+// mark rebuild aborted if future dropped while running
+struct ProgressGuard<'a> {
+    progress: &'a Mutex<Option<RebuildProgress>>,
+    admin: &'a AdminLog,
+    op: Option<AdminOp>,
+}
+
+impl<'a> Drop for ProgressGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(progress) = self.progress.lock().as_mut() {
+            if progress.state == RebuildState::Running {
+                progress.state = RebuildState::Aborted;
+                progress.finished = Some(Instant::now());
+            }
+        }
+
+        if let Some(op) = self.op.take() {
+            self.admin.finish(op, Err("aborted".to_owned()));
+        }
+    }
+}
+
+
+// discard shadow if rebuild future dropped before swap
+struct AbortGuard<'a, I> {
+    target: &'a Shadowed<I>,
+    armed: bool,
+}
+
+impl<'a, I> Drop for AbortGuard<'a, I> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.target.abort();
+        }
+    }
+}
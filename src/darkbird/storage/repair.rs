@@ -0,0 +1,148 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::hash::Hash;
+
+use crate::{
+    darkbird::{
+        repair::{Pending, Repair, RepairStats},
+        storage::Event,
+    },
+    document::Document,
+};
+
+use super::Storage;
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Write back repaired documents and dispatch `Event::Quarantined` of documents read since last call,
+    /// return how many documents were written back.
+    ///
+    /// a repair is written like an `update` of key, through write plugins,
+    /// and skipped if key was written since it was read.
+    /// a repaired version the hook doesn't find valid, or a write back rejected,
+    /// quarantine key instead of repairing again.
+    /// run by next insert or remove of store
+    pub async fn apply_repairs(&self) -> usize {
+        let repair = match &self.repair {
+            Some(repair) => repair,
+            None => return 0,
+        };
+
+        let mut applied = 0;
+        for (key, pending) in repair.take() {
+            let lane = self.lane_of(&key);
+            let (read, doc) = match pending {
+                Pending::Quarantined => {
+                    if !self.off_reporter {
+                        self.dispatch(lane, Event::Quarantined(key)).await;
+                    }
+                    continue;
+                }
+                Pending::Repaired { read, doc } => (read, doc),
+            };
+
+            let quarantine = {
+                let _update = self.update_lock(&key).lock().await;
+
+                let old = match self.stored(&key) {
+                    Some(old) if !self.expired(&key) && bincode::serialize(&old).unwrap() == read => old,
+                    // written since read, new document is checked on its next read
+                    _ => continue,
+                };
+
+                match repair.hook.check(&key, &doc) {
+                    Repair::Valid => match self.replace(&key, old, doc).await {
+                        Ok(()) => {
+                            repair.repaired();
+                            applied += 1;
+                            false
+                        }
+                        Err(_) => repair.hide(&key),
+                    },
+                    _ => repair.hide(&key),
+                }
+            };
+
+            if quarantine && !self.off_reporter {
+                self.dispatch(lane, Event::Quarantined(key)).await;
+            }
+        }
+
+        applied
+    }
+
+    /// read repair counters since open, zero without hook
+    pub fn repair_stats(&self) -> RepairStats {
+        self.repair.as_ref().map(|repair| repair.stats()).unwrap_or_default()
+    }
+
+    /// keys hidden by read repair, their documents still readable by `lookup_quarantined`
+    pub fn quarantined(&self) -> Vec<K> {
+        self.repair.as_ref().map(|repair| repair.quarantined_keys()).unwrap_or_default()
+    }
+
+    /// document of a quarantined key, for inspection
+    pub fn lookup_quarantined(&self, key: &K) -> Option<Doc> {
+        if !self.is_quarantined(key) {
+            return None;
+        }
+        self.stored(key)
+    }
+
+    /// run read repair on document read, false if it must be hidden
+    #[inline]
+    pub(super) fn check_read(&self, key: &K, doc: &Doc) -> bool {
+        let repair = match &self.repair {
+            Some(repair) => repair,
+            None => return true,
+        };
+
+        match repair.hook.check(key, doc) {
+            Repair::Valid => true,
+            Repair::Repaired(fixed) => {
+                // document read as is until written back
+                let read = bincode::serialize(doc).unwrap();
+                repair.queue(key, Pending::Repaired { read, doc: fixed });
+                true
+            }
+            Repair::Quarantine => {
+                repair.quarantine(key);
+                false
+            }
+        }
+    }
+
+    #[inline]
+    pub(super) fn is_quarantined(&self, key: &K) -> bool {
+        self.repair.as_ref().is_some_and(|repair| repair.is_quarantined(key))
+    }
+
+    /// key written, caller hold rebuild_gate
+    #[inline]
+    pub(super) fn release(&self, key: &K) {
+        if let Some(repair) = &self.repair {
+            repair.release(key);
+        }
+    }
+
+    #[inline]
+    pub(super) async fn repair_if_pending(&self) {
+        if self.repair.as_ref().is_some_and(|repair| repair.has_pending()) {
+            self.apply_repairs().await;
+        }
+    }
+}
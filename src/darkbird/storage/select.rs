@@ -0,0 +1,141 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    hash::Hash,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    darkbird::search::tokenize,
+    document::{Document, FieldValue},
+};
+
+use super::Storage;
+
+
+
+/// Structure keys of a query are read from or checked against, see `Storage::select`
+#[derive(Clone, Debug)]
+pub(crate) enum Probe {
+    Tag(String),
+    Index(String),
+
+    // a range whose bounds don't have type of field match nothing
+    Range(String, Bound<FieldValue>, Bound<FieldValue>),
+
+    // any word of text
+    Text(String),
+}
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Keys matching every probe, empty for no probe.
+    ///
+    /// probes are sized without copying keys, keys of smallest are copied
+    /// and checked against others one at a time, smallest first,
+    /// a single structure is locked at once
+    pub(crate) fn select(&self, probes: &[Probe]) -> Vec<K> {
+        let mut sized = Vec::with_capacity(probes.len());
+        for probe in probes {
+            let size = self.probe_size(probe);
+            if size == 0 {
+                return vec![];
+            }
+            sized.push((size, probe));
+        }
+        sized.sort_by_key(|(size, _)| *size);
+
+        let mut sized = sized.into_iter();
+        let mut keys = match sized.next() {
+            Some((_, smallest)) => self.probe_keys(smallest),
+            None => return vec![],
+        };
+
+        for (_, probe) in sized {
+            if keys.is_empty() {
+                break;
+            }
+            self.probe_retain(probe, &mut keys);
+        }
+
+        keys
+    }
+
+    // keys a probe match, an upper bound for text
+    fn probe_size(&self, probe: &Probe) -> usize {
+        match probe {
+            Probe::Tag(tag) => self.tag_index.lookup(tag).map(|rf| rf.value().len()).unwrap_or(0),
+            Probe::Index(index_key) => self.hash_index.lookup(index_key).is_some() as usize,
+            Probe::Range(field_name, from, to) => self
+                .range_index
+                .live()
+                .count_within(field_name, from.clone(), to.clone())
+                .unwrap_or(0),
+            Probe::Text(text) => {
+                let index = self.inverted_index.live();
+                words(text).iter().map(|word| index.posting_len(word)).sum()
+            }
+        }
+    }
+
+    fn probe_keys(&self, probe: &Probe) -> Vec<K> {
+        match probe {
+            Probe::Tag(tag) => self.tag_keys(tag),
+            Probe::Index(index_key) => self.index_keys(index_key),
+            Probe::Range(field_name, from, to) => self
+                .range_index
+                .live()
+                .keys_within(field_name, from.clone(), to.clone())
+                .unwrap_or_default(),
+            Probe::Text(text) => self.search_keys(text),
+        }
+    }
+
+    fn probe_retain(&self, probe: &Probe, keys: &mut Vec<K>) {
+        match probe {
+            Probe::Tag(tag) => match self.tag_index.lookup(tag) {
+                Some(rf) => keys.retain(|k| rf.value().contains(k)),
+                None => keys.clear(),
+            },
+            Probe::Index(index_key) => match self.hash_index.lookup(index_key) {
+                Some(rf) => keys.retain(|k| k == rf.value()),
+                None => keys.clear(),
+            },
+            // value of field read from document, range index has no key to value map
+            Probe::Range(field_name, from, to) => {
+                keys.retain(|k| self.field_value(k, field_name).is_some_and(|value| (from.as_ref(), to.as_ref()).contains(&value)))
+            }
+            Probe::Text(text) => self.inverted_index.live().retain_any(&words(text), keys),
+        }
+    }
+
+    /// value of typed field of stored document, work with value compression
+    pub(crate) fn field_value(&self, key: &K, field_name: &str) -> Option<FieldValue> {
+        let fields = match &self.compression {
+            Some(_) => self.stored(key)?.get_typed_fields(),
+            None => self.collection.get(key)?.value().get_typed_fields(),
+        };
+        fields.into_iter().find(|field| field.name == field_name).map(|field| field.value)
+    }
+}
+
+
+fn words(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = tokenize(text).collect();
+    words.sort();
+    words.dedup();
+    words
+}
@@ -0,0 +1,275 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    hash::Hash,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    darkbird::{
+        workload::{self, WorkloadDoc},
+        Options, SessionResult, StatusResult, StorageType,
+    },
+    document::Document,
+};
+
+use super::Storage;
+
+
+
+// fsync measured as mean of samples
+const FSYNC_SAMPLES: usize = 16;
+
+// bytes written before each fsync sample
+const FSYNC_BLOCK: usize = 4096;
+
+// generators seed, fixed so reports of different machines compare
+const SEED: u64 = 0x5eed;
+
+
+
+/// workload of `Storage::self_test`
+#[derive(Clone, Debug, Serialize)]
+pub struct SelfTestProfile {
+    pub documents: usize,
+    pub doc_size: usize,
+    pub lookups: usize,
+}
+
+impl Default for SelfTestProfile {
+    fn default() -> Self {
+        SelfTestProfile::quick()
+    }
+}
+
+impl SelfTestProfile {
+    /// a few seconds on common hardware
+    pub fn quick() -> Self {
+        SelfTestProfile {
+            documents: 10_000,
+            doc_size: 256,
+            lookups: 10_000,
+        }
+    }
+
+    pub fn standard() -> Self {
+        SelfTestProfile {
+            documents: 100_000,
+            doc_size: 1024,
+            lookups: 100_000,
+        }
+    }
+
+    pub fn with_documents(mut self, documents: usize) -> Self {
+        self.documents = documents.max(1);
+        self
+    }
+
+    /// approximate body bytes of each document
+    pub fn with_doc_size(mut self, doc_size: usize) -> Self {
+        self.doc_size = doc_size;
+        self
+    }
+
+    pub fn with_lookups(mut self, lookups: usize) -> Self {
+        self.lookups = lookups.max(1);
+        self
+    }
+}
+
+
+/// throughput and latencies of a phase, latencies in microseconds
+#[derive(Clone, Debug, Serialize)]
+pub struct PhaseReport {
+    pub ops: usize,
+    pub ops_per_sec: f64,
+    pub p50_us: u64,
+    pub p99_us: u64,
+    pub max_us: u64,
+}
+
+
+/// result of `Storage::self_test`, durations in microseconds
+#[derive(Clone, Debug, Serialize)]
+pub struct SelfTestReport {
+    pub profile: SelfTestProfile,
+
+    pub insert: PhaseReport,
+    pub lookup: PhaseReport,
+
+    pub range_us: u64,
+    pub range_matched: usize,
+
+    pub search_us: u64,
+    pub search_matched: usize,
+
+    // flush and fsync of WAL on close
+    pub close_us: u64,
+
+    // reopen replaying whole WAL
+    pub replay_us: u64,
+    pub replayed: usize,
+
+    // mean cost of a 4KiB write followed by fsync
+    pub fsync_us: u64,
+}
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Measure hardware with a scripted workload: inserts, lookups, a range query,
+    /// a search, close and reopen-and-replay.
+    ///
+    /// runs on a scratch store in a temporary subdirectory next to this store,
+    /// removed afterwards, this store data is never touched
+    pub async fn self_test(&self, profile: SelfTestProfile) -> Result<SelfTestReport, SessionResult> {
+        let op = self.admin.start("self_test", format!("{:?}", profile));
+
+        let name = format!("{}.self-test-{}", self.capabilities.store, std::process::id());
+        let dir = format!("{}/{}", self.path, name);
+
+        let res = run(&self.path, &name, &dir, profile).await;
+        let _ = fs::remove_dir_all(&dir);
+        let _ = fs::remove_file(format!("{}.schema", dir));
+
+        match &res {
+            Ok(_) => self.admin.finish(op, Ok(0)),
+            Err(e) => self.admin.finish(op, Err(e.to_string())),
+        }
+
+        res
+    }
+}
+
+
+
+async fn run(path: &str, name: &str, dir: &str, profile: SelfTestProfile) -> Result<SelfTestReport, SessionResult> {
+    // leftover of an interrupted run
+    let _ = fs::remove_dir_all(dir);
+    let _ = fs::remove_file(format!("{}.schema", dir));
+
+    let open = || Storage::<u64, WorkloadDoc>::open(Options::new(path, name, 0, StorageType::DiskCopies, true));
+
+    let store = open().await.map_err(to_error)?;
+
+    // insert
+    let mut latencies = Vec::with_capacity(profile.documents);
+    let started = Instant::now();
+    for (key, doc) in workload::documents(profile.documents, profile.doc_size, SEED) {
+        let t = Instant::now();
+        store.insert(key, doc).await?;
+        latencies.push(t.elapsed());
+    }
+    let insert = phase(latencies, started.elapsed());
+
+    // lookup
+    let mut latencies = Vec::with_capacity(profile.lookups);
+    let started = Instant::now();
+    for key in workload::lookup_keys(profile.lookups, profile.documents, SEED) {
+        let t = Instant::now();
+        let _ = store.lookup_owned(&key);
+        latencies.push(t.elapsed());
+    }
+    let lookup = phase(latencies, started.elapsed());
+
+    // range over first tenth of scores
+    let t = Instant::now();
+    let range_matched = store
+        .range("score", workload::score(0), workload::score((profile.documents / 10) as u64))
+        .len();
+    let range_us = micros(t.elapsed());
+
+    // search
+    let t = Instant::now();
+    let search_matched = store.search("alpha bravo").len();
+    let search_us = micros(t.elapsed());
+
+    // close
+    let t = Instant::now();
+    store.close().await?;
+    let close_us = micros(t.elapsed());
+
+    // reopen and replay
+    let t = Instant::now();
+    let store = open().await.map_err(to_error)?;
+    let replay_us = micros(t.elapsed());
+    let replayed = store.keys().len();
+    store.close().await?;
+
+    let fsync_us = fsync_cost(dir)?;
+
+    Ok(SelfTestReport {
+        profile,
+        insert,
+        lookup,
+        range_us,
+        range_matched,
+        search_us,
+        search_matched,
+        close_us,
+        replay_us,
+        replayed,
+        fsync_us,
+    })
+}
+
+
+fn fsync_cost(dir: &str) -> Result<u64, SessionResult> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(format!("{}/fsync.probe", dir))
+        .map_err(|e| SessionResult::Err(StatusResult::IoError(e)))?;
+
+    let block = [0u8; FSYNC_BLOCK];
+    let mut total = Duration::ZERO;
+
+    for _ in 0..FSYNC_SAMPLES {
+        let t = Instant::now();
+        file.write_all(&block)
+            .and_then(|_| file.sync_data())
+            .map_err(|e| SessionResult::Err(StatusResult::IoError(e)))?;
+        total += t.elapsed();
+    }
+
+    Ok(micros(total) / FSYNC_SAMPLES as u64)
+}
+
+
+fn phase(mut latencies: Vec<Duration>, elapsed: Duration) -> PhaseReport {
+    latencies.sort_unstable();
+
+    let at = |q: usize| latencies.get((latencies.len() * q / 100).min(latencies.len().saturating_sub(1)));
+
+    PhaseReport {
+        ops: latencies.len(),
+        ops_per_sec: latencies.len() as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        p50_us: at(50).copied().map(micros).unwrap_or(0),
+        p99_us: at(99).copied().map(micros).unwrap_or(0),
+        max_us: latencies.last().copied().map(micros).unwrap_or(0),
+    }
+}
+
+#[inline]
+fn micros(d: Duration) -> u64 {
+    d.as_micros() as u64
+}
+
+fn to_error(e: String) -> SessionResult {
+    SessionResult::Err(StatusResult::Err(e))
+}
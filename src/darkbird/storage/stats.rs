@@ -0,0 +1,87 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fs, hash::Hash};
+
+use crate::{darkbird::disk_space, document::Document};
+
+use super::Storage;
+
+
+
+/// counts of `Storage::stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    // documents, without documents past their ttl or quarantined
+    pub doc_count: usize,
+
+    // hash index keys
+    pub index_count: usize,
+
+    // distinct tags, without views and variants
+    pub tag_count: usize,
+
+    // WAL pages on disk and their size, 0 for RamCopies
+    pub wal_pages: usize,
+    pub wal_size_bytes: u64,
+
+    // free space of WAL filesystem, None for RamCopies or where not supported
+    pub disk_available_bytes: Option<u64>,
+
+    // writes refused, see Storage::set_read_only
+    pub read_only: bool,
+}
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// counts of documents, secondary structures and WAL, without walking documents.
+    /// WAL is measured from its directory, a page being written count its flushed size
+    pub fn stats(&self) -> StorageStats {
+        let (wal_pages, wal_size_bytes) = match self.off_disk {
+            true => (0, 0),
+            false => wal_usage(&self.wal_dir),
+        };
+
+        StorageStats {
+            doc_count: self.len(),
+            index_count: self.index_len(),
+            tag_count: self.tags_len(),
+            wal_pages,
+            wal_size_bytes,
+            disk_available_bytes: (!self.off_disk).then(|| disk_space::available(&self.wal_dir)).flatten(),
+            read_only: self.read_only(),
+        }
+    }
+}
+
+
+// page-*.LOG files in dir and their total size
+pub(super) fn wal_usage(dir: &str) -> (usize, u64) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return (0, 0),
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with("page-") && name.ends_with(".LOG")
+        })
+        .fold((0, 0), |(pages, bytes), entry| {
+            (pages + 1, bytes + entry.metadata().map_or(0, |meta| meta.len()))
+        })
+}
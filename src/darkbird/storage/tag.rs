@@ -0,0 +1,95 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::hash::Hash;
+
+use crate::{
+    darkbird::{storage::RQuery, SessionResult},
+    document::Document,
+};
+
+use super::Storage;
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Add tag to document of key without writing document again, `KeyNotFound` if key is missing.
+    ///
+    /// kept over tags of document by later inserts and updates of key, dropped when key is removed.
+    /// logged as `RQuery::TagAdd` and dispatched as `Event::Query(RQuery::TagAdd)`,
+    /// write plugins aren't run. not available with write coalescing, which log per key
+    pub async fn add_tag(&self, key: &K, tag: &str) -> Result<(), SessionResult> {
+        self.tag_write(key, tag, true).await
+    }
+
+    /// Remove tag from document of key without writing document again, `KeyNotFound` if key is missing.
+    ///
+    /// a tag document has stay removed over later inserts and updates of key, see `add_tag`.
+    /// a tag left without documents is dropped, so `iter_tags` doesn't list it
+    pub async fn remove_tag(&self, key: &K, tag: &str) -> Result<(), SessionResult> {
+        self.tag_write(key, tag, false).await
+    }
+
+    /// tags of document of key with `add_tag` and `remove_tag` applied, None if key is missing
+    pub fn tags_of(&self, key: &K) -> Option<Vec<String>> {
+        self.stored(key).map(|doc| self.tag_index.tags_of(key, &doc))
+    }
+
+    async fn tag_write(&self, key: &K, tag: &str, add: bool) -> Result<(), SessionResult> {
+        if self.coalescer.is_some() {
+            return Err(SessionResult::UnImplement);
+        }
+
+        self.expire_if_due().await;
+
+        let _update = self.update_lock(key).lock().await;
+        if !self.contains_key(key) {
+            return Err(SessionResult::KeyNotFound);
+        }
+
+        let query = || match add {
+            true => RQuery::TagAdd(key.clone(), tag.to_owned()),
+            false => RQuery::TagRemove(key.clone(), tag.to_owned()),
+        };
+
+        let _gate = self.rebuild_gate.read().await;
+        self.log_write(key, query, None).await?;
+        self.edit_tag(key, tag, add);
+        Ok(())
+    }
+
+    /// apply a tag edit of a held key, false if key is missing.
+    /// caller hold rebuild_gate, by tag_write and loader
+    pub(super) fn edit_tag(&self, key: &K, tag: &str, add: bool) -> bool {
+        if !self.holds(key) {
+            return false;
+        }
+
+        let _writing = self.query_cache.as_ref().map(|cache| cache.write_tag(tag));
+
+        if add {
+            self.tag_index.add_edit(key, tag);
+            if !self.defer_derived {
+                self.tag_index.insert_entry(key, tag.to_owned());
+            }
+        } else {
+            self.tag_index.remove_edit(key, tag);
+            if !self.defer_derived {
+                self.tag_index.remove_entry(key, tag.to_owned());
+            }
+        }
+        true
+    }
+}
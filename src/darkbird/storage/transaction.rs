@@ -0,0 +1,315 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use crate::{
+    darkbird::{
+        plugin::WriteContext,
+        storage::{LogRecord, RQuery},
+        wal::codec::Codec,
+        SessionResult, StatusResult,
+    },
+    document::Document,
+};
+
+use super::{batch::query_key, Storage};
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Apply inserts and removes atomically, through write plugins.
+    ///
+    /// whole transaction is one WAL record, so after a crash it is replayed whole or not at all,
+    /// memory updated only after disk_log wrote record, then one event dispatched per query.
+    /// a write error return Err with nothing applied, its record may still be replayed on restart.
+    /// write locks of keys are held from conflict check to memory, like insert, update and
+    /// compare_and_swap hold their key, so none of them run in between.
+    /// a transaction conflicting on an index key return `Duplicate` with nothing logged or applied.
+    /// not available with write coalescing, which log per key
+    pub async fn transaction(&self, queries: Vec<RQuery<K, Doc>>) -> Result<(), SessionResult> {
+        self.writable()?;
+
+        if self.coalescer.is_some() {
+            return Err(SessionResult::UnImplement);
+        }
+
+        if queries.is_empty() {
+            return Ok(());
+        }
+
+        let mut checked = Vec::with_capacity(queries.len());
+        for query in queries {
+            checked.push(self.run_plugins(query)?);
+        }
+
+        let keys: Vec<&K> = checked.iter().map(query_key).collect();
+        let held = self.hold(&keys, &inserted_keys(&checked), false).await?;
+
+        self.check_conflicts(&checked)?;
+        self.log_transaction(&mut checked).await?;
+        let previous = self.apply_transaction(&checked).await;
+        drop(held);
+
+        self.dispatch_queries(checked, previous).await;
+        Ok(())
+    }
+
+    /// queries through write plugins, `Duplicate` if they conflict on an index key.
+    /// caller hold keys, see `hold`
+    pub(crate) fn check_transaction(&self, queries: Vec<RQuery<K, Doc>>) -> Result<Vec<RQuery<K, Doc>>, SessionResult> {
+        let mut checked = Vec::with_capacity(queries.len());
+        for query in queries {
+            checked.push(self.run_plugins(query)?);
+        }
+
+        self.check_conflicts(&checked)?;
+        Ok(checked)
+    }
+
+    /// one WAL record for all queries, return once disk_log wrote it, caller hold keys.
+    /// lane of first key, written after records queued before on every lane, see `Session::log_batch_acked`.
+    /// queries are left in place whatever the result. return bytes logged
+    pub(crate) async fn log_transaction(&self, queries: &mut Vec<RQuery<K, Doc>>) -> Result<u64, SessionResult> {
+        if self.off_disk || queries.is_empty() {
+            return Ok(0);
+        }
+
+        let lane = self.lane_of(query_key(&queries[0]));
+        let record = LogRecord::Transaction(std::mem::take(queries));
+        let encoded = self.codec.encode(&record);
+        let bytes = encoded.len() as u64;
+        let res = self.wal_session.log_batch_acked(lane, vec![encoded]).await;
+        *queries = record.into_queries();
+        res.map(|_| bytes)
+    }
+
+    /// apply logged queries to memory, caller hold rebuild_gate.
+    /// return documents replaced by each query for dispatch, empty if no one read them
+    pub(crate) async fn apply_transaction(&self, queries: &[RQuery<K, Doc>]) -> Vec<Option<Doc>> {
+        let keep = self.filtering.is_active() || self.previous_values;
+        let mut previous = vec![];
+        for query in queries.iter() {
+            match query {
+                RQuery::Insert(key, doc) | RQuery::Update(key, doc) | RQuery::Patch(key, _, doc) => {
+                    if keep {
+                        previous.push(self.previous(key));
+                    }
+                    // like replace, derived of stored document leave first so it keep its index keys
+                    if let Some(old) = self.stored(key) {
+                        self.remove_derived(key, &old).await;
+                    }
+                    let _ = self.store_doc(key.clone(), doc.clone()).await;
+                }
+                RQuery::Remove(key) => {
+                    let doc = self.stored(key);
+                    if let Some(doc) = &doc {
+                        self.remove_derived(key, doc).await;
+                        self.forget(key);
+                    }
+                    if keep {
+                        previous.push(doc);
+                    }
+                }
+                RQuery::Clear | RQuery::TagAdd(..) | RQuery::TagRemove(..) => unreachable!("rejected by run_plugins"),
+            }
+        }
+
+        previous
+    }
+
+    /// Hold keys for a `Database::cross_transaction`: room for inserted ones,
+    /// write locks of all, and rebuild_gate, so they don't change until it's released.
+    /// paused hold rebuild_gate for write, so no key of store change.
+    /// `UnImplement` with write coalescing, like `transaction`
+    pub(crate) async fn hold(&self, keys: &[&K], inserted: &[&K], paused: bool) -> Result<Held<'_>, SessionResult> {
+        if self.coalescer.is_some() {
+            return Err(SessionResult::UnImplement);
+        }
+
+        self.lock_keys(keys, inserted, paused).await
+    }
+
+    /// `hold` without the coalescing check, for batch writes which stash in coalescer
+    pub(crate) async fn lock_keys(&self, keys: &[&K], inserted: &[&K], paused: bool) -> Result<Held<'_>, SessionResult> {
+        let admit = self.make_room(inserted).await?;
+
+        // stripes locked in order, so two transactions sharing stripes can't deadlock
+        let mut stripes: Vec<usize> = keys.iter().map(|key| self.update_stripe(key)).collect();
+        stripes.sort_unstable();
+        stripes.dedup();
+
+        let mut locks = Vec::with_capacity(stripes.len());
+        for stripe in stripes {
+            locks.push(self.update_locks[stripe].lock().await);
+        }
+
+        let (shared, paused) = match paused {
+            true => (None, Some(self.rebuild_gate.write().await)),
+            false => (Some(self.rebuild_gate.read().await), None),
+        };
+
+        Ok(Held {
+            _admit: admit,
+            _locks: locks,
+            _gate: shared,
+            _paused: paused,
+        })
+    }
+
+    /// documents of keys as stored, with their ttl, caller hold them
+    pub(crate) fn before(&self, keys: &[&K]) -> Vec<Before<K, Doc>> {
+        keys.iter()
+            .map(|key| Before {
+                key: (*key).clone(),
+                doc: self.stored(key),
+                expires_at: self.expiry.deadline(key),
+            })
+            .collect()
+    }
+
+    /// Log keys back as they were before a logged transaction, which is then never applied.
+    ///
+    /// restore record follow transaction in its lane, so replay undo it,
+    /// ttl of a restored key is logged after it in same lane
+    pub(crate) async fn log_before(&self, logged: &[RQuery<K, Doc>], before: Vec<Before<K, Doc>>) -> Result<(), SessionResult> {
+        if self.off_disk || logged.is_empty() {
+            return Ok(());
+        }
+
+        let lane = self.lane_of(query_key(&logged[0]));
+        let mut records = vec![];
+        let mut restores = vec![];
+        for Before { key, doc, expires_at } in before {
+            match (doc, expires_at) {
+                (Some(doc), Some(at)) => records.push(LogRecord::InsertWithExpiry(key, doc, at)),
+                (Some(doc), None) => restores.push(RQuery::Insert(key, doc)),
+                (None, _) => restores.push(RQuery::Remove(key)),
+            }
+        }
+        if !restores.is_empty() {
+            records.insert(0, LogRecord::Transaction(restores));
+        }
+
+        let records = records.iter().map(|record| self.codec.encode(record)).collect();
+        self.wal_session.log_batch_acked(lane, records).await?;
+        Ok(())
+    }
+
+    pub(super) fn run_plugins(&self, query: RQuery<K, Doc>) -> Result<RQuery<K, Doc>, SessionResult> {
+        match query {
+            RQuery::Insert(key, doc) => {
+                let ctx = self.run_insert_plugins(key, doc)?;
+                Ok(RQuery::Insert(ctx.key, ctx.doc))
+            }
+            RQuery::Update(key, doc) => {
+                let ctx = self.run_insert_plugins(key, doc)?;
+                Ok(RQuery::Update(ctx.key, ctx.doc))
+            }
+            // document whole, a record of transaction can't be applied to stored one
+            RQuery::Patch(key, _, doc) => {
+                let ctx = self.run_insert_plugins(key, doc)?;
+                Ok(RQuery::Update(ctx.key, ctx.doc))
+            }
+            RQuery::Remove(key) => {
+                for plugin in self.plugins.iter() {
+                    if let Err(reason) = plugin.on_remove(&key) {
+                        return Err(SessionResult::PluginRejected { plugin: plugin.name().to_owned(), reason });
+                    }
+                }
+                Ok(RQuery::Remove(key))
+            }
+            // one record of its own, see Storage::clear, add_tag and remove_tag
+            RQuery::Clear | RQuery::TagAdd(..) | RQuery::TagRemove(..) => Err(SessionResult::UnImplement),
+        }
+    }
+
+    // plugins see an update as an insert
+    fn run_insert_plugins(&self, key: K, doc: Doc) -> Result<WriteContext<K, Doc>, SessionResult> {
+        let mut ctx = WriteContext { key, doc, metadata: HashMap::new() };
+        for plugin in self.plugins.iter() {
+            if let Err(reason) = plugin.on_insert(&mut ctx) {
+                return Err(SessionResult::PluginRejected { plugin: plugin.name().to_owned(), reason });
+            }
+        }
+        Ok(ctx)
+    }
+
+    // replay transaction against hash_index, so apply can't fail half way
+    fn check_conflicts(&self, queries: &[RQuery<K, Doc>]) -> Result<(), SessionResult> {
+        // documents as transaction leave them, None: removed
+        let mut overlay: HashMap<&K, Option<&Doc>> = HashMap::new();
+        let mut released: HashSet<String> = HashSet::new();
+        let mut claimed: HashSet<String> = HashSet::new();
+
+        for query in queries {
+            match query {
+                RQuery::Insert(key, doc) | RQuery::Update(key, doc) | RQuery::Patch(key, _, doc) => {
+                    for index_key in doc.extract() {
+                        // held by another document, a document keep its own
+                        let held = self.hash_index.lookup(&index_key).is_some_and(|owner| owner.value() != key);
+                        let taken = claimed.contains(&index_key) || (held && !released.contains(&index_key));
+                        if taken {
+                            return Err(SessionResult::Err(StatusResult::Duplicate));
+                        }
+                        claimed.insert(index_key);
+                    }
+                    overlay.insert(key, Some(doc));
+                }
+                RQuery::Remove(key) => {
+                    let extracted = match overlay.get(key) {
+                        Some(doc) => doc.map(|doc| doc.extract()),
+                        None => self.stored(key).map(|doc| doc.extract()),
+                    };
+                    for index_key in extracted.unwrap_or_default() {
+                        claimed.remove(&index_key);
+                        released.insert(index_key);
+                    }
+                    overlay.insert(key, None);
+                }
+                RQuery::Clear | RQuery::TagAdd(..) | RQuery::TagRemove(..) => unreachable!("rejected by run_plugins"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+/// keys and locks held by `Storage::hold`, released when dropped
+pub(crate) struct Held<'a> {
+    _admit: Option<tokio::sync::MutexGuard<'a, ()>>,
+    _locks: Vec<tokio::sync::MutexGuard<'a, ()>>,
+    _gate: Option<tokio::sync::RwLockReadGuard<'a, ()>>,
+    _paused: Option<tokio::sync::RwLockWriteGuard<'a, ()>>,
+}
+
+/// key as stored before a cross-store transaction, see `Storage::log_before`
+pub(crate) struct Before<K, Doc> {
+    key: K,
+    doc: Option<Doc>,
+    expires_at: Option<u64>,
+}
+
+
+fn inserted_keys<K, Doc>(queries: &[RQuery<K, Doc>]) -> Vec<&K> {
+    queries.iter().filter_map(|query| match query {
+        RQuery::Insert(key, _) => Some(key),
+        _ => None,
+    }).collect()
+}
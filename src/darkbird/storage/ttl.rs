@@ -0,0 +1,150 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    hash::Hash,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    darkbird::{
+        expiry::unix_ms,
+        storage::{Event, LogRecord, RQuery},
+        wal::codec::Codec,
+        SessionResult,
+    },
+    document::Document,
+};
+
+use super::Storage;
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Insert document removed once `ttl` passed on store clock, through write plugins.
+    ///
+    /// deadline is persisted with document, a document expired while down isn't loaded on open.
+    /// once deadline passed, lookups, `iter` and `len` no longer see document.
+    /// expiry service spawned by open then remove it like a normal remove, `RQuery::Remove(key)`
+    /// logged to WAL and dispatched followed by `Event::Expired(key)`, and next write of store
+    /// or `expire_due` drop it from memory. a copy of document is held beside its deadline for those events.
+    /// a later insert or remove of key cancel ttl.
+    /// not available with write coalescing, which log per key
+    pub async fn insert_with_ttl(&self, key: K, doc: Doc, ttl: Duration) -> Result<(), SessionResult> {
+        if self.coalescer.is_some() {
+            return Err(SessionResult::UnImplement);
+        }
+
+        self.expire_if_due().await;
+
+        let (key, doc) = match self.run_plugins(RQuery::Insert(key, doc))? {
+            RQuery::Insert(key, doc) => (key, doc),
+            RQuery::Remove(_) | RQuery::Update(..) | RQuery::Patch(..) | RQuery::Clear | RQuery::TagAdd(..) | RQuery::TagRemove(..) => unreachable!(),
+        };
+
+        let _admit = self.make_room(&[&key]).await?;
+        let _update = self.update_lock(&key).lock().await;
+        let _gate = self.rebuild_gate.read().await;
+
+        let at = unix_ms(self.now() + ttl);
+        let lane = self.lane_of(&key);
+
+        if !self.off_disk {
+            let record = LogRecord::InsertWithExpiry(key.clone(), doc.clone(), at);
+            self.wal_session.log_keyed(lane, self.codec.encode(&record)).await?;
+        }
+
+        if !self.off_reporter {
+            let previous = self.previous(&key);
+            self.dispatch(lane, self.change_event(RQuery::Insert(key.clone(), doc.clone()), previous.as_ref())).await;
+        }
+
+        self.store_doc(key.clone(), doc.clone()).await?;
+        self.expiry.arm(key, at, doc);
+
+        Ok(())
+    }
+
+    /// deadline of key inserted by `insert_with_ttl`, None if it has no ttl
+    pub fn expires_at(&self, key: &K) -> Option<SystemTime> {
+        let at = self.expiry.deadline(key)?;
+        Some(UNIX_EPOCH + Duration::from_millis(at))
+    }
+
+    /// remove from memory documents whose ttl passed, logging removal of those expiry service
+    /// didn't log yet, return how many were removed
+    pub async fn expire_due(&self) -> usize {
+        let mut removed = 0;
+        for (key, at) in self.expiry.due_keys(unix_ms(self.now())) {
+            // a write of key since due_keys disarmed or moved deadline, it's not expired
+            let _update = self.update_lock(&key).lock().await;
+            if self.expiry.deadline(&key) != Some(at) {
+                continue;
+            }
+
+            if self.expiry.is_logged(&key, at) {
+                let _gate = self.rebuild_gate.read().await;
+                if let Some(doc) = self.stored(&key) {
+                    self.remove_derived(&key, &doc).await;
+                    removed += 1;
+                }
+                self.forget(&key);
+                continue;
+            }
+
+            let lane = self.lane_of(&key);
+
+            // gone from memory when Expired is dispatched, kept for tag filters.
+            // write plugins aren't asked, expiry was accepted at insert
+            let doc = self.filtering.is_active().then(|| self.stored(&key)).flatten();
+            match self.remove_locked(&key).await {
+                Ok(true) => {
+                    removed += 1;
+                    if !self.off_reporter {
+                        self.dispatch_removed(lane, Event::Expired(key), doc.as_ref()).await;
+                    }
+                }
+                Ok(false) => self.expiry.disarm(&key),
+
+                // left armed, tried again by next expire_due
+                Err(_) => {}
+            }
+        }
+
+        removed
+    }
+
+    /// log deadlines moved by `ClockSkewPolicy::TrustLog` on open, so next open read them moved,
+    /// return deadlines armed
+    pub(super) async fn log_rebased(&self) -> Result<u64, SessionResult> {
+        let armed = self.expiry.armed();
+        if !self.off_disk {
+            for (key, at) in armed.iter() {
+                if let Some(doc) = self.stored(key) {
+                    let record = LogRecord::InsertWithExpiry(key, &doc, *at);
+                    self.wal_session.log_keyed(self.lane_of(key), self.codec.encode(&record)).await?;
+                }
+            }
+        }
+        Ok(armed.len() as u64)
+    }
+
+    #[inline]
+    pub(super) async fn expire_if_due(&self) {
+        if self.expiry.next().is_some() && self.expiry.due(unix_ms(self.now())) {
+            self.expire_due().await;
+        }
+    }
+}
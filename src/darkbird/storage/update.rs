@@ -0,0 +1,221 @@
+use dashmap::mapref::one::Ref;
+use serde::{de::DeserializeOwned, Serialize};
+use std::hash::Hash;
+
+use crate::{
+    darkbird::{patch::DocPatch, storage::RQuery, SessionResult, StatusResult},
+    document::Document,
+};
+
+use super::Storage;
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Modify document of key with `f`, through write plugins.
+    ///
+    /// one WAL record and one `Event::Query(RQuery::Update)` for the new version
+    /// (`Event::Replaced` with `Options::with_previous_values`),
+    /// instead of a remove and an insert. writes of a key run one at a time,
+    /// so concurrent updates are never lost (batches and transactions aren't ordered with them).
+    /// `f` run on a copy of stored document, so no reader observe a partial change.
+    /// return false with `f` not run if key is missing,
+    /// nothing is logged or applied if new version claim an index key of another document (`Duplicate`).
+    /// like an insert it cancel ttl of key
+    pub async fn update<F>(&self, key: &K, f: F) -> Result<bool, SessionResult>
+    where
+        F: FnOnce(&mut Doc),
+    {
+        self.expire_if_due().await;
+
+        let _update = self.update_lock(key).lock().await;
+
+        let old = match self.visible_owned(key) {
+            Some(old) => old,
+            None => return Ok(false),
+        };
+        let mut doc = old.clone();
+        f(&mut doc);
+
+        self.replace(key, old, doc).await.map(|_| true)
+    }
+
+    /// Apply `patch` to document of key, through write plugins, like `update`.
+    ///
+    /// WAL record is patch alone, replayed on top of version of key before it, so a field
+    /// of a large document is logged without rest of it. with write plugins registered
+    /// document they return is logged whole, as they may change it beyond patch.
+    /// one `Event::Query(RQuery::Patch)` carry patch and new version
+    /// (`Event::Replaced` with `Options::with_previous_values`, `RQuery::Update` with write coalescing).
+    /// return false if key is missing, Err with nothing logged if patch doesn't apply
+    pub async fn patch(&self, key: &K, patch: DocPatch) -> Result<bool, SessionResult> {
+        self.expire_if_due().await;
+
+        let _update = self.update_lock(key).lock().await;
+
+        let old = match self.visible_owned(key) {
+            Some(old) => old,
+            None => return Ok(false),
+        };
+        let mut doc = old.clone();
+        if let Err(reason) = patch.apply(&mut doc) {
+            return Err(SessionResult::Err(StatusResult::Err(format!("patch: {}", reason))));
+        }
+
+        self.replace_patched(key, old, doc, Some(patch)).await.map(|_| true)
+    }
+
+    /// Replace document of key by `new` if it equals `expected`, through write plugins.
+    ///
+    /// return false with nothing logged or dispatched if key is missing or document differ.
+    /// check and write hold the write lock of key, so no insert, remove or update
+    /// of key come between them, see `update`
+    pub async fn compare_and_swap(&self, key: K, expected: Doc, new: Doc) -> Result<bool, SessionResult>
+    where
+        Doc: PartialEq,
+    {
+        self.expire_if_due().await;
+
+        let _update = self.update_lock(&key).lock().await;
+
+        let old = match self.visible_owned(&key) {
+            Some(old) if old == expected => old,
+            _ => return Ok(false),
+        };
+
+        self.replace(&key, old, new).await.map(|_| true)
+    }
+
+    /// Remove document of key only if `predicate` accept it, through write plugins,
+    /// return whether it was removed.
+    ///
+    /// check and remove hold the write lock of key, so document `predicate` saw is the one removed,
+    /// see `update`. false with nothing logged or dispatched if key is missing or `predicate` refuse,
+    /// a document past its ttl or quarantined count as missing
+    pub async fn remove_if<F>(&self, key: K, predicate: F) -> Result<bool, SessionResult>
+    where
+        F: FnOnce(&Doc) -> bool,
+    {
+        self.expire_if_due().await;
+        self.repair_if_pending().await;
+
+        let _update = self.update_lock(&key).lock().await;
+
+        match self.visible_owned(&key) {
+            Some(doc) if predicate(&doc) => {}
+            _ => return Ok(false),
+        }
+
+        for plugin in self.plugins.iter() {
+            if let Err(reason) = plugin.on_remove(&key) {
+                return Err(SessionResult::PluginRejected { plugin: plugin.name().to_owned(), reason });
+            }
+        }
+
+        self.remove_locked(&key).await
+    }
+
+    /// Insert document only if key is missing, through write plugins, return whether it was inserted.
+    ///
+    /// check and write hold the write lock of key, so of concurrent inserters of a missing key
+    /// exactly one succeed, see `update`. nothing is logged or dispatched when key exist,
+    /// a document past its ttl or quarantined count as missing
+    pub async fn insert_if_absent(&self, key: K, doc: Doc) -> Result<bool, SessionResult> {
+        self.expire_if_due().await;
+        self.repair_if_pending().await;
+
+        let (key, doc) = match self.run_plugins(RQuery::Insert(key, doc))? {
+            RQuery::Insert(key, doc) => (key, doc),
+            RQuery::Remove(_) | RQuery::Update(..) | RQuery::Patch(..) | RQuery::Clear | RQuery::TagAdd(..) | RQuery::TagRemove(..) => unreachable!(),
+        };
+
+        let _admit = self.make_room(&[&key]).await?;
+        let _update = self.update_lock(&key).lock().await;
+        if self.contains_key(&key) {
+            return Ok(false);
+        }
+
+        let _gate = self.rebuild_gate.read().await;
+        self.log_insert(&key, &doc, None).await?;
+        self.store_doc(key, doc).await.map(|_| true)
+    }
+
+    /// Document of key, or `default()` inserted through write plugins if key is missing.
+    ///
+    /// check and insert hold the write lock of key, so of concurrent callers for a missing key
+    /// one insert and all get its document, `default` is run only when key is missing.
+    /// an insert is logged and dispatched like `insert`, plugins may only change document.
+    /// returned Ref hold a read guard of key's shard, see `lookup`.
+    /// `CompressedValue` with value compression, `NoResponse` if document
+    /// was hidden by read repair or removed by a batch before it was read
+    pub async fn get_or_insert(&self, key: K, default: impl FnOnce() -> Doc) -> Result<Ref<'_, K, Doc>, SessionResult> {
+        if self.compression.is_some() {
+            return Err(SessionResult::CompressedValue);
+        }
+
+        self.expire_if_due().await;
+        self.repair_if_pending().await;
+
+        let _admit = self.make_room(&[&key]).await?;
+        let _update = self.update_lock(&key).lock().await;
+        if !self.contains_key(&key) {
+            let doc = match self.run_plugins(RQuery::Insert(key.clone(), default()))? {
+                RQuery::Insert(_, doc) => doc,
+                RQuery::Remove(_) | RQuery::Update(..) | RQuery::Patch(..) | RQuery::Clear | RQuery::TagAdd(..) | RQuery::TagRemove(..) => unreachable!(),
+            };
+
+            let _gate = self.rebuild_gate.read().await;
+            self.log_insert(&key, &doc, None).await?;
+            self.store_doc(key.clone(), doc).await?;
+        }
+
+        self.get_visible(&key).ok_or(SessionResult::NoResponse)
+    }
+
+    // caller hold update_lock of key
+    pub(super) async fn replace(&self, key: &K, old: Doc, doc: Doc) -> Result<(), SessionResult> {
+        self.replace_patched(key, old, doc, None).await
+    }
+
+    // doc is old with patch applied, if any
+    async fn replace_patched(&self, key: &K, old: Doc, doc: Doc, patch: Option<DocPatch>) -> Result<(), SessionResult> {
+        // key stay the one updated, plugins may only change document
+        let doc = match self.run_plugins(RQuery::Insert(key.clone(), doc))? {
+            RQuery::Insert(_, doc) => doc,
+            RQuery::Remove(_) | RQuery::Update(..) | RQuery::Patch(..) | RQuery::Clear | RQuery::TagAdd(..) | RQuery::TagRemove(..) => unreachable!(),
+        };
+
+        for index_key in doc.extract() {
+            if let Some(owner) = self.hash_index.lookup(&index_key) {
+                if owner.value() != key {
+                    return Err(SessionResult::Err(StatusResult::Duplicate));
+                }
+            }
+        }
+
+        let _gate = self.rebuild_gate.read().await;
+        match patch.filter(|_| self.plugins.is_empty()) {
+            Some(patch) => self.log_patch(key, patch, &doc, &old).await?,
+            None => self.log_update(key, &doc, &old).await?,
+        }
+
+        // key leave derived structures until store_doc, a query in between isn't cached
+        let _writing = self.query_cache.as_ref().map(|cache| cache.write::<Doc>(None, None));
+        self.remove_derived(key, &old).await;
+        self.store_doc(key.clone(), doc).await
+    }
+}
@@ -0,0 +1,97 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::hash::Hash;
+
+use crate::{
+    darkbird::{views::ViewFn, SessionResult},
+    document::Document,
+};
+
+use super::Storage;
+
+
+
+impl<K, Doc> Storage<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Create a view of documents view return true for, read by `fetch_view` and its variants
+    /// as views of `MaterializedView::filter`. filled from documents held, then kept by every
+    /// insert and remove. return documents in view.
+    ///
+    /// writes wait while view is filled, reads continue. `ViewExists` if a view of name is held,
+    /// created or filled by documents. views are in memory only, create them again after open
+    pub async fn create_view(&self, name: &str, view: ViewFn<K, Doc>) -> Result<usize, SessionResult> {
+        let op = self.admin.start("create_view", format!("name={}", name));
+        let _gate = self.rebuild_gate.write().await;
+
+        if self.tag_index.lookup_view(name).is_some() || !self.views.add(name, view.clone()) {
+            let e = SessionResult::ViewExists { name: name.to_owned() };
+            self.admin.finish(op, Err(e.to_string()));
+            return Err(e);
+        }
+
+        let len = self.fill_view(name, &view);
+        self.admin.finish(op, Ok(0));
+        Ok(len)
+    }
+
+    /// Remove a view created by `create_view`, `ViewNotFound` for any other name.
+    ///
+    /// a `fetch_view` in flight keep documents it read, its `Ref`s delay removal until dropped
+    pub async fn drop_view(&self, name: &str) -> Result<(), SessionResult> {
+        let op = self.admin.start("drop_view", format!("name={}", name));
+        let _gate = self.rebuild_gate.write().await;
+
+        if !self.views.remove(name) {
+            let e = SessionResult::ViewNotFound { name: name.to_owned() };
+            self.admin.finish(op, Err(e.to_string()));
+            return Err(e);
+        }
+
+        self.tag_index.remove_view(name);
+        self.admin.finish(op, Ok(0));
+        Ok(())
+    }
+
+    /// Fill a view created by `create_view` again from documents held, for a view
+    /// reading state beside document that changed. return documents in view
+    pub async fn rebuild_view(&self, name: &str) -> Result<usize, SessionResult> {
+        let op = self.admin.start("rebuild_view", format!("name={}", name));
+        let _gate = self.rebuild_gate.write().await;
+
+        let view = match self.views.get(name) {
+            Some(view) => view,
+            None => {
+                let e = SessionResult::ViewNotFound { name: name.to_owned() };
+                self.admin.finish(op, Err(e.to_string()));
+                return Err(e);
+            }
+        };
+
+        self.tag_index.remove_view(name);
+        let len = self.fill_view(name, &view);
+        self.admin.finish(op, Ok(0));
+        Ok(len)
+    }
+
+    // caller hold rebuild_gate
+    fn fill_view(&self, name: &str, view: &ViewFn<K, Doc>) -> usize {
+        self.each_held(|key, doc| {
+            if view(key, doc) {
+                self.tag_index.insert_view(name, key)
+            }
+        });
+        self.tag_index.view_len(name)
+    }
+}
@@ -0,0 +1,392 @@
+use bytes::Bytes;
+use dashmap::{iter::Iter, DashMap};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{hash::Hash, sync::Arc};
+use tokio::sync::mpsc::Sender;
+
+use super::{
+    index::{hash::HashIndex, tags::TagIndex},
+    metrics::{MetricEvent, Metrics, MetricsSnapshot},
+    router::{self, Router},
+    storage::{Event, PinnedDoc, RQuery},
+    wal::{codec::WalCodec, disk_log::{DiskLog, Session}},
+    Options, SessionResult, StatusResult, StorageType,
+};
+
+
+
+const RECORD_INSERT: u8 = 0;
+const RECORD_REMOVE: u8 = 1;
+
+
+type Extractor<T> = Box<dyn Fn(&[u8]) -> T + Send + Sync>;
+
+
+/// index/tag/view extraction for raw bytes documents,
+/// because Bytes can't implement Document trait
+#[derive(Default)]
+pub struct Extractors {
+    index: Option<Extractor<Vec<String>>>,
+    tags: Option<Extractor<Vec<String>>>,
+    view: Option<Extractor<Option<String>>>,
+}
+
+impl Extractors {
+    pub fn new() -> Self {
+        Extractors::default()
+    }
+
+    pub fn with_index(mut self, f: impl Fn(&[u8]) -> Vec<String> + Send + Sync + 'static) -> Self {
+        self.index = Some(Box::new(f));
+        self
+    }
+
+    pub fn with_tags(mut self, f: impl Fn(&[u8]) -> Vec<String> + Send + Sync + 'static) -> Self {
+        self.tags = Some(Box::new(f));
+        self
+    }
+
+    pub fn with_view(mut self, f: impl Fn(&[u8]) -> Option<String> + Send + Sync + 'static) -> Self {
+        self.view = Some(Box::new(f));
+        self
+    }
+
+    #[inline]
+    fn index(&self, doc: &[u8]) -> Vec<String> {
+        self.index.as_ref().map(|f| f(doc)).unwrap_or_default()
+    }
+
+    #[inline]
+    fn tags(&self, doc: &[u8]) -> Vec<String> {
+        self.tags.as_ref().map(|f| f(doc)).unwrap_or_default()
+    }
+
+    #[inline]
+    fn view(&self, doc: &[u8]) -> Option<String> {
+        self.view.as_ref().and_then(|f| f(doc))
+    }
+}
+
+
+
+/// Storage for already serialized payloads (protobuf, ...),
+/// documents written to WAL as is (length-prefixed) without bincode
+pub struct BytesStorage<K> {
+    // DashMap
+    collection: DashMap<K, Bytes>,
+
+    // HashIndex
+    hash_index: HashIndex<K>,
+
+    // TagIndex (tags and views)
+    tag_index: TagIndex<K>,
+
+    extractors: Extractors,
+
+    // Wal session
+    wal_session: Session,
+
+    // Reporter session
+    reporter_session: router::Session<Event<K, Bytes>>,
+
+    off_reporter: bool,
+
+    off_disk: bool,
+
+    // read counters, shared with WAL session
+    metrics: Arc<Metrics>,
+}
+
+impl<K> BytesStorage<K>
+where
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    pub async fn open(ops: Options, extractors: Extractors) -> Result<Self, String> {
+        let disklog = match DiskLog::open(&ops.path, &ops.storage_name, ops.total_page_size, WalCodec::Bincode, ops.wal_writer) {
+            Ok(disklog) => disklog,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let off_disk = matches!(ops.stype, StorageType::RamCopies);
+
+        let wal_session = disklog.run_service();
+
+        #[cfg(feature = "test-util")]
+        let wal_session = wal_session.with_faults(ops.wal_faults.clone());
+
+        let metrics = Arc::new(Metrics::new(ops.metrics_observer.clone()));
+        let wal_session = wal_session.with_metrics(metrics.clone());
+
+        let mut st = BytesStorage {
+            collection: DashMap::new(),
+            hash_index: HashIndex::new(),
+            tag_index: TagIndex::new(),
+            extractors,
+            wal_session,
+            reporter_session: Router::<Event<K, Bytes>>::new(vec![]).unwrap().run_service(),
+            off_reporter: ops.off_reporter,
+            off_disk: true,
+            metrics,
+        };
+
+        // load from disk
+        if let Err(x) = st.loader().await {
+            if x != "End" {
+                return Err(x);
+            }
+        }
+
+        // because we want loader dont write to disk_log
+        st.off_disk = off_disk;
+
+        Ok(st)
+    }
+
+    /// subscribe to Reporter
+    #[inline]
+    pub async fn subscribe(&self, sender: Sender<Event<K, Bytes>>) -> Result<(), SessionResult> {
+        if self.off_reporter {
+            return Err(SessionResult::Err(StatusResult::ReporterIsOff));
+        }
+
+        let _ = self
+            .reporter_session
+            .dispatch(Event::Subscribed(sender.clone()))
+            .await;
+
+        self.reporter_session.register(sender).await.map(|_| ())
+    }
+
+    /// insert to storage and persist to disk
+    #[inline]
+    pub async fn insert(&self, key: K, doc: Bytes) -> Result<(), SessionResult> {
+        if !self.off_disk {
+            self.wal_session.log(encode(RECORD_INSERT, &key, &doc)).await?;
+        }
+
+        if !self.off_reporter {
+            let query = RQuery::Insert(key.clone(), doc.clone());
+            let _ = self.reporter_session.dispatch(Event::Query(query)).await;
+        }
+
+        // overwrite, remove derived entries of previous document
+        if let Some(old) = self.collection.get(&key) {
+            self.remove_derived(&key, old.value());
+        }
+
+        if let Err(e) = self.hash_index.insert_keys(&key, self.extractors.index(&doc)) {
+            return Err(SessionResult::Err(e));
+        }
+
+        if let Some(view_name) = self.extractors.view(&doc) {
+            self.tag_index.insert_view(&view_name, &key);
+        }
+
+        self.tag_index.insert_tags(&key, self.extractors.tags(&doc));
+
+        self.collection.insert(key, doc);
+
+        Ok(())
+    }
+
+    /// remove from storage and persist to disk
+    #[inline]
+    pub async fn remove(&self, key: K) -> Result<(), SessionResult> {
+        let doc = match self.collection.get(&key) {
+            Some(doc) => doc.value().clone(),
+            None => return Ok(()),
+        };
+
+        if !self.off_disk {
+            self.wal_session.log(encode(RECORD_REMOVE, &key, &[])).await?;
+        }
+
+        if !self.off_reporter {
+            let query = RQuery::<K, Bytes>::Remove(key.clone());
+            let _ = self.reporter_session.dispatch(Event::Query(query)).await;
+        }
+
+        self.remove_derived(&key, &doc);
+        self.collection.remove(&key);
+
+        Ok(())
+    }
+
+    /// lookup by key, return cheap Bytes clone
+    #[inline]
+    pub fn lookup(&self, key: &K) -> Option<Bytes> {
+        let doc = self.collection.get(key).map(|rf| rf.value().clone());
+        self.metrics.record(MetricEvent::Lookup { hit: doc.is_some() });
+        doc
+    }
+
+    /// lookup by key, see `PinnedDoc::as_bytes`
+    #[inline]
+    pub fn lookup_pinned(&self, key: &K) -> Option<PinnedDoc<Bytes>> {
+        self.lookup(key).map(PinnedDoc::new)
+    }
+
+    /// lookup by index
+    #[inline]
+    pub fn lookup_by_index(&self, index_key: &str) -> Option<Bytes> {
+        let doc = self.hash_index
+            .lookup(index_key)
+            .map(|rf| rf.value().clone())
+            .and_then(|key| self.collection.get(&key).map(|rf| rf.value().clone()));
+        self.metrics.record(MetricEvent::LookupByIndex { hit: doc.is_some() });
+        doc
+    }
+
+    /// lookup by tag
+    #[inline]
+    pub fn lookup_by_tag(&self, tag: &str) -> Vec<(K, Bytes)> {
+        let result = match self.tag_index.lookup(tag) {
+            Some(rf) => self.collect(rf.value().iter().map(|k| k.key().clone())),
+            None => vec![]
+        };
+        self.metrics.record(MetricEvent::LookupByTag { hit: !result.is_empty() });
+        result
+    }
+
+    /// fetch view
+    #[inline]
+    pub fn fetch_view(&self, view_name: &str) -> Vec<(K, Bytes)> {
+        let result = match self.tag_index.lookup_view(view_name) {
+            Some(rf) => self.collect(rf.value().iter().map(|k| k.key().clone())),
+            None => vec![]
+        };
+        self.metrics.record(MetricEvent::View { hit: !result.is_empty() });
+        result
+    }
+
+    /// read hits and misses and WAL enqueues since open, see `Storage::metrics`
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot(self.reporter_session.queued(), self.reporter_session.subscribers())
+    }
+
+    /// return Iter (Safe for mutation)
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, K, Bytes> {
+        self.collection.iter()
+    }
+
+    /// documents in store
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.collection.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.collection.is_empty()
+    }
+
+    #[inline]
+    fn collect(&self, keys: impl Iterator<Item = K>) -> Vec<(K, Bytes)> {
+        keys.filter_map(|k| {
+            let doc = self.collection.get(&k)?.value().clone();
+            Some((k, doc))
+        })
+        .collect()
+    }
+
+    #[inline]
+    fn remove_derived(&self, key: &K, doc: &[u8]) {
+        self.hash_index.remove_keys(&self.extractors.index(doc));
+
+        if let Some(view_name) = self.extractors.view(doc) {
+            self.tag_index.remove_from_view(&view_name, key);
+        }
+
+        self.tag_index.remove_tags(key, self.extractors.tags(doc));
+    }
+
+    /// load storage from disk
+    async fn loader(&self) -> Result<(), String> {
+        let mut page_index = 1;
+
+        loop {
+            let mut logfile = match self.wal_session.get_page(page_index).await {
+                Ok(lf) => lf,
+                Err(SessionResult::Err(e)) => return Err(e.to_string()),
+                Err(_) => return Err("disk_log closed".to_string()),
+            };
+
+            page_index += 1;
+
+            let iter = match logfile.iter(..) {
+                Ok(iter) => iter,
+                Err(e) => return Err(e.to_string()),
+            };
+
+            for qline in iter {
+                let bytes = match qline {
+                    Ok(ql) => ql,
+                    Err(e) => return Err(e.to_string()),
+                };
+
+                // header of page
+                if let Some(codec) = WalCodec::of_header(&bytes) {
+                    codec?;
+                    continue;
+                }
+
+                let (op, key, doc) = decode::<K>(bytes)?;
+
+                match op {
+                    RECORD_INSERT => {
+                        let _ = self.insert(key, doc).await;
+                    }
+                    _ => {
+                        let _ = self.remove(key).await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+
+// record: | op (1) | key length (u32 le) | key (bincode) | document |
+#[inline]
+fn encode<K: Serialize>(op: u8, key: &K, doc: &[u8]) -> Vec<u8> {
+    let key = bincode::serialize(key).unwrap();
+    let mut buf = Vec::with_capacity(1 + 4 + key.len() + doc.len());
+
+    buf.push(op);
+    buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&key);
+    buf.extend_from_slice(doc);
+
+    buf
+}
+
+#[inline]
+fn decode<K: DeserializeOwned>(record: Vec<u8>) -> Result<(u8, K, Bytes), String> {
+    if record.len() < 5 {
+        return Err("bytes record is truncated".to_owned());
+    }
+
+    let op = record[0];
+    let key_len = u32::from_le_bytes([record[1], record[2], record[3], record[4]]) as usize;
+
+    if record.len() < 5 + key_len {
+        return Err("bytes record is truncated".to_owned());
+    }
+
+    let key = bincode::deserialize(&record[5..5 + key_len]).map_err(|e| e.to_string())?;
+    let mut doc = Bytes::from(record);
+
+    Ok((op, key, doc.split_off(5 + key_len)))
+}
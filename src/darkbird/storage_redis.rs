@@ -1,10 +1,13 @@
 use tokio::sync::Notify;
 use tokio::time::{self, Duration, Instant};
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
 use std::sync::{Arc, Mutex};
 use std::hash::Hash;
 
+use super::SessionResult;
+
 
 #[derive(Debug)]
 pub struct DbDropGuard<K, Doc> 
@@ -70,6 +73,8 @@ where
 {
     
     entries: HashMap<K, Entry<Doc>>,
+    // keys of entries in order, so scan start at its cursor
+    ordered: BTreeSet<K>,
     expirations: BTreeMap<(Instant, u64), K>,
     next_id: u64,
     shutdown: bool,
@@ -83,6 +88,14 @@ struct Entry<Doc> {
     expires_at: Option<Instant>,
 }
 
+impl<Doc> Entry<Doc> {
+    // not yet purged entries past expire are left out of keys, scan and ttl
+    #[inline]
+    fn live(&self, now: Instant) -> bool {
+        self.expires_at.is_none_or(|when| when > now)
+    }
+}
+
 impl<K, Doc> DbDropGuard<K, Doc> 
 where
     Doc: Clone + Send + Sync + 'static,
@@ -139,6 +152,7 @@ where
         let shared = Arc::new(Shared {
             state: Mutex::new(State {
                 entries: HashMap::new(),
+                ordered: BTreeSet::new(),
                 expirations: BTreeMap::new(),
                 next_id: 0,
                 shutdown: false,
@@ -179,7 +193,7 @@ where
             when
         });
 
-        let prev = state.entries.insert(
+        let prev = state.insert_entry(
             key,
             Entry {
                 id,
@@ -226,7 +240,7 @@ where
             when
         });
 
-        let prev = state.entries.insert(
+        let prev = state.insert_entry(
             key,
             Entry {
                 id,
@@ -253,7 +267,152 @@ where
 
     pub fn del(&self, key: &K) {
         let mut state = self.shared.state.lock().unwrap();
-        state.entries.remove(key);
+        if let Some(prev) = state.remove_entry(key) {
+            if let Some(when) = prev.expires_at {
+                state.expirations.remove(&(when, prev.id));
+            }
+        }
+    }
+
+    /// values of keys in order of keys, one lock for all of them
+    pub fn mget(&self, keys: &[K]) -> Vec<Option<Arc<Doc>>> {
+        let state = self.shared.state.lock().unwrap();
+        keys.iter()
+            .map(|key| state.entries.get(key).map(|entry| entry.data.clone()))
+            .collect()
+    }
+
+    /// `set` of every entry under one lock, purge task woken once if an expire is sooner than
+    /// every expire held. a key given twice keep its last entry
+    pub fn mset(&self, entries: Vec<(K, Doc, Option<Duration>)>) {
+        let mut state = self.shared.state.lock().unwrap();
+
+        let next = state.next_expiration();
+        let now = Instant::now();
+        let mut sooner = None;
+
+        for (key, value, expire) in entries {
+            let id = state.next_id;
+            state.next_id += 1;
+
+            let expires_at = expire.map(|duration| {
+                let when = now + duration;
+                state.expirations.insert((when, id), key.clone());
+                sooner = Some(sooner.map_or(when, |sooner: Instant| sooner.min(when)));
+                when
+            });
+
+            let prev = state.insert_entry(key, Entry { id, data: Arc::new(value), expires_at });
+
+            if let Some(prev) = prev {
+                if let Some(when) = prev.expires_at {
+                    state.expirations.remove(&(when, prev.id));
+                }
+            }
+        }
+
+        drop(state);
+
+        let notify = match (sooner, next) {
+            (Some(sooner), Some(next)) => next > sooner,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+    }
+
+    /// keys held, without keys past their expire, in no order
+    pub fn keys(&self) -> Vec<K> {
+        let state = self.shared.state.lock().unwrap();
+        let now = Instant::now();
+
+        state.entries
+            .iter()
+            .filter(|(_, entry)| entry.live(now))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Keys in order after `cursor` (from first key without), at most count, and cursor of next call,
+    /// None once last keys are returned. cursor needn't still exist when next call is made.
+    ///
+    /// walk start at cursor in ordered keys, so a page cost count keys and not every key held.
+    /// count 0 return no keys and cursor unchanged
+    pub fn scan(&self, cursor: Option<K>, count: usize) -> (Vec<K>, Option<K>) {
+        if count == 0 {
+            return (vec![], cursor);
+        }
+
+        let state = self.shared.state.lock().unwrap();
+        let now = Instant::now();
+
+        let from = match cursor.as_ref() {
+            Some(cursor) => Bound::Excluded(cursor),
+            None => Bound::Unbounded,
+        };
+
+        // count + 1 keys after cursor, one past tells whether a next call is needed
+        let mut keys: Vec<K> = state.ordered
+            .range((from, Bound::Unbounded))
+            .filter(|key| state.entries.get(*key).is_some_and(|entry| entry.live(now)))
+            .take(count + 1)
+            .cloned()
+            .collect();
+        drop(state);
+
+        let more = keys.len() > count;
+        keys.truncate(count);
+
+        let next = if more { keys.last().cloned() } else { None };
+        (keys, next)
+    }
+
+    /// time left before key expire, None for a key set without expire.
+    /// `SessionResult::KeyNotFound` if key isn't held or already expired
+    pub fn ttl(&self, key: &K) -> Result<Option<Duration>, SessionResult> {
+        let state = self.shared.state.lock().unwrap();
+        let now = Instant::now();
+
+        match state.entries.get(key) {
+            Some(entry) if entry.live(now) => Ok(entry.expires_at.map(|when| when - now)),
+            _ => Err(SessionResult::KeyNotFound),
+        }
+    }
+
+    /// set or replace expire of a held key, false if key isn't held or already expired
+    pub fn expire(&self, key: &K, expire: Duration) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        let now = Instant::now();
+
+        let (id, previous) = match state.entries.get(key) {
+            Some(entry) if entry.live(now) => (entry.id, entry.expires_at),
+            _ => return false,
+        };
+
+        if let Some(previous) = previous {
+            state.expirations.remove(&(previous, id));
+        }
+
+        let when = now + expire;
+        let notify = state
+            .next_expiration()
+            .map(|expiration| expiration > when)
+            .unwrap_or(true);
+
+        state.expirations.insert((when, id), key.clone());
+        if let Some(entry) = state.entries.get_mut(key) {
+            entry.expires_at = Some(when);
+        }
+
+        drop(state);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+
+        true
     }
 
  
@@ -267,6 +426,222 @@ where
     }
 }
 
+/// Value of a RedisStorage updated in place by `incr` and `decr`,
+/// implemented for integers, delta out of bounds is None
+pub trait Numeric: Sized {
+    fn zero() -> Self;
+    fn add_delta(&self, delta: i64) -> Option<Self>;
+}
+
+macro_rules! numeric {
+    ($($t:ty),*) => {
+        $(
+            impl Numeric for $t {
+                fn zero() -> Self {
+                    0
+                }
+
+                fn add_delta(&self, delta: i64) -> Option<Self> {
+                    (*self as i128).checked_add(delta as i128).and_then(|value| <$t>::try_from(value).ok())
+                }
+            }
+        )*
+    };
+}
+
+numeric!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+
+impl<K, Doc> RedisStorage<K, Doc> 
+where
+    Doc: Numeric + Clone + Send + Sync + 'static,
+    K:  Clone
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Send
+        + 'static
+{
+    /// add delta to value of key atomically, key created at zero when absent or expired (not yet purged),
+    /// new value returned. expire replace ttl of key, None keep it.
+    /// `SessionResult::Overflow` out of bounds of Doc, value and ttl left as is
+    pub fn incr(&self, key: K, delta: i64, expire: Option<Duration>) -> Result<Doc, SessionResult> {
+        let mut state = self.shared.state.lock().unwrap();
+
+        let now = Instant::now();
+        let current = state.entries.get(&key).filter(|entry| entry.live(now));
+        let value = match current {
+            Some(entry) => entry.data.add_delta(delta),
+            None => Doc::zero().add_delta(delta),
+        };
+        let value = value.ok_or(SessionResult::Overflow)?;
+
+        let mut notify = false;
+        let (id, expires_at) = match (current, expire) {
+            (Some(entry), None) => (entry.id, entry.expires_at),
+            (_, expire) => {
+                let id = state.next_id;
+                state.next_id += 1;
+
+                let expires_at = expire.map(|duration| {
+                    let when = now + duration;
+                    notify = state
+                        .next_expiration()
+                        .map(|expiration| expiration > when)
+                        .unwrap_or(true);
+
+                    state.expirations.insert((when, id), key.clone());
+                    when
+                });
+                (id, expires_at)
+            }
+        };
+
+        let prev = state.insert_entry(
+            key,
+            Entry {
+                id,
+                data: Arc::new(value.clone()),
+                expires_at,
+            },
+        );
+
+        if let Some(prev) = prev {
+            if let Some(when) = prev.expires_at.filter(|_| prev.id != id) {
+                state.expirations.remove(&(when, prev.id));
+            }
+        }
+
+        drop(state);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+
+        Ok(value)
+    }
+
+    /// `incr` by -delta
+    pub fn decr(&self, key: K, delta: i64, expire: Option<Duration>) -> Result<Doc, SessionResult> {
+        match delta.checked_neg() {
+            Some(delta) => self.incr(key, delta, expire),
+            None => Err(SessionResult::Overflow),
+        }
+    }
+}
+
+
+/// Typed handle of a RedisStorage resolved once by `Database::cache_handle`,
+/// cheap to clone, keep the storage alive even if Database dropped
+#[derive(Debug, Clone)]
+pub struct CacheHandle<K, Doc> 
+where
+    Doc: Clone + Send + Sync + 'static,
+    K:  Clone
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Send
+        + 'static
+{
+    storage: RedisStorage<K, Doc>,
+}
+
+impl<K, Doc> CacheHandle<K, Doc> 
+where
+    Doc: Clone + Send + Sync + 'static,
+    K:  Clone
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Send
+        + 'static
+{
+    pub(crate) fn new(storage: RedisStorage<K, Doc>) -> Self {
+        CacheHandle { storage }
+    }
+
+    #[inline]
+    pub fn get(&self, key: &K) -> Option<Arc<Doc>> {
+        self.storage.get(key)
+    }
+
+    #[inline]
+    pub fn set(&self, key: K, value: Doc, expire: Option<Duration>) {
+        self.storage.set(key, value, expire)
+    }
+
+    #[inline]
+    pub fn set_nx(&self, key: K, value: Doc, expire: Option<Duration>) -> bool {
+        self.storage.set_nx(key, value, expire)
+    }
+
+    #[inline]
+    pub fn del(&self, key: &K) {
+        self.storage.del(key)
+    }
+
+    #[inline]
+    pub fn mget(&self, keys: &[K]) -> Vec<Option<Arc<Doc>>> {
+        self.storage.mget(keys)
+    }
+
+    #[inline]
+    pub fn mset(&self, entries: Vec<(K, Doc, Option<Duration>)>) {
+        self.storage.mset(entries)
+    }
+
+    #[inline]
+    pub fn keys(&self) -> Vec<K> {
+        self.storage.keys()
+    }
+
+    #[inline]
+    pub fn scan(&self, cursor: Option<K>, count: usize) -> (Vec<K>, Option<K>) {
+        self.storage.scan(cursor, count)
+    }
+
+    #[inline]
+    pub fn ttl(&self, key: &K) -> Result<Option<Duration>, SessionResult> {
+        self.storage.ttl(key)
+    }
+
+    #[inline]
+    pub fn expire(&self, key: &K, expire: Duration) -> bool {
+        self.storage.expire(key, expire)
+    }
+}
+
+impl<K, Doc> CacheHandle<K, Doc> 
+where
+    Doc: Numeric + Clone + Send + Sync + 'static,
+    K:  Clone
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Send
+        + 'static
+{
+    #[inline]
+    pub fn incr(&self, key: K, delta: i64, expire: Option<Duration>) -> Result<Doc, SessionResult> {
+        self.storage.incr(key, delta, expire)
+    }
+
+    #[inline]
+    pub fn decr(&self, key: K, delta: i64, expire: Option<Duration>) -> Result<Doc, SessionResult> {
+        self.storage.decr(key, delta, expire)
+    }
+}
+
+
 impl<K, Doc> Shared<K, Doc> 
 where
     Doc: Clone + Send + Sync + 'static,
@@ -296,6 +671,7 @@ where
             }
 
             state.entries.remove(key);
+            state.ordered.remove(key);
             state.expirations.remove(&(when, id));
         }
 
@@ -319,6 +695,19 @@ where
         + Send
         + 'static
 {
+    // entries and ordered keys are only changed together
+    fn insert_entry(&mut self, key: K, entry: Entry<Doc>) -> Option<Entry<Doc>> {
+        if !self.entries.contains_key(&key) {
+            self.ordered.insert(key.clone());
+        }
+        self.entries.insert(key, entry)
+    }
+
+    fn remove_entry(&mut self, key: &K) -> Option<Entry<Doc>> {
+        self.ordered.remove(key);
+        self.entries.remove(key)
+    }
+
     fn next_expiration(&self) -> Option<Instant> {
         self.expirations
             .keys()
@@ -0,0 +1,109 @@
+use futures_core::Stream;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::VecDeque,
+    hash::Hash,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{document::Document, Storage};
+
+// documents copied out per refill
+const STREAM_BATCH: usize = 256;
+
+
+
+/// Stream of (key, document) over a matched key set,
+/// documents copied out in batches of `STREAM_BATCH`, no shard guard
+/// held between polls, so dropping the stream release nothing but memory.
+/// documents removed after match are skipped.
+/// stream yield to runtime before every refill but first,
+/// so a consumer that never await anything else don't starve other tasks
+pub struct ScanStream<'a, K, Doc: Document> {
+    storage: &'a Storage<K, Doc>,
+    keys: std::vec::IntoIter<K>,
+    buffer: VecDeque<(K, Doc)>,
+
+    // next refill must yield first
+    refilled: bool,
+}
+
+impl<'a, K, Doc> ScanStream<'a, K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    pub(crate) fn new(storage: &'a Storage<K, Doc>, keys: Vec<K>) -> Self {
+        ScanStream {
+            storage,
+            keys: keys.into_iter(),
+            buffer: VecDeque::with_capacity(STREAM_BATCH),
+            refilled: false,
+        }
+    }
+
+    fn refill(&mut self) {
+        while self.buffer.len() < STREAM_BATCH {
+            let key = match self.keys.next() {
+                Some(key) => key,
+                None => return,
+            };
+
+            if let Some(doc) = self.storage.get_owned(&key) {
+                self.buffer.push_back((key, doc));
+            }
+        }
+    }
+}
+
+impl<'a, K, Doc> Stream for ScanStream<'a, K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    type Item = (K, Doc);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if this.buffer.is_empty() && this.keys.len() > 0 {
+            if this.refilled {
+                this.refilled = false;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            this.refill();
+            this.refilled = true;
+        }
+
+        Poll::Ready(this.buffer.pop_front())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.buffer.len(), Some(self.buffer.len() + self.keys.len()))
+    }
+}
+
+impl<'a, K, Doc: Document> Unpin for ScanStream<'a, K, Doc> {}
@@ -0,0 +1,239 @@
+//! Fault, latency and clock injection for tests, enabled by `test-util` feature
+//!
+//! ```ignore
+//! testing::pause_clock();
+//!
+//! let wal = FaultyWal::new().fail_nth_append(3);
+//! let ops = Options::new(".", "users", 1000, StorageType::DiskCopies, false)
+//!     .with_faulty_wal(wal.clone());
+//!
+//! let storage = Storage::<String, User>::open(ops).await?;
+//! ```
+
+use parking_lot::Mutex;
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    sync::{mpsc::{self, Sender}, Notify},
+    task::JoinHandle,
+    time::{self, Instant},
+};
+
+use super::{clock::Clock, SessionResult, StatusResult};
+
+
+
+#[derive(Default)]
+struct Counters {
+    appends: AtomicU64,
+    bytes: AtomicU64,
+    failed: AtomicU64,
+    writes: AtomicU64,
+    closed: AtomicBool,
+    close: Notify,
+}
+
+
+/// Fault plan applied to WAL appends of a store opened with `Options::with_faulty_wal`,
+/// a failed append return IoError before record reach disk_log so write is rejected,
+/// a failed write once disk_log wrote it, clones share counters
+#[derive(Clone, Default)]
+pub struct FaultyWal {
+    fail_nth: Option<u64>,
+    fail_nth_write: Option<u64>,
+    fail_after_bytes: Option<u64>,
+    latency: Option<Duration>,
+    counters: Arc<Counters>,
+}
+
+impl FaultyWal {
+    pub fn new() -> Self {
+        FaultyWal::default()
+    }
+
+    /// nth append (starting from 1) fail
+    pub fn fail_nth_append(mut self, n: u64) -> Self {
+        self.fail_nth = Some(n);
+        self
+    }
+
+    /// nth write waiting for disk_log (starting from 1) fail after record is written,
+    /// like a flush error. only acked writes wait: transactions, batches and clear
+    pub fn fail_nth_write(mut self, n: u64) -> Self {
+        self.fail_nth_write = Some(n);
+        self
+    }
+
+    /// appends fail once accepted bytes would exceed limit, like a full disk
+    pub fn fail_after_bytes(mut self, limit: u64) -> Self {
+        self.fail_after_bytes = Some(limit);
+        self
+    }
+
+    /// delay every append, follow paused clock
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// total appends attempted
+    pub fn appends(&self) -> u64 {
+        self.counters.appends.load(Ordering::SeqCst)
+    }
+
+    /// bytes of accepted appends
+    pub fn bytes(&self) -> u64 {
+        self.counters.bytes.load(Ordering::SeqCst)
+    }
+
+    /// total appends failed by injection
+    pub fn failed(&self) -> u64 {
+        self.counters.failed.load(Ordering::SeqCst)
+    }
+
+    /// wait until WAL is closed and synced, by `Storage::close`
+    /// or in background by a store dropped without it
+    pub async fn wait_closed(&self) {
+        if !self.counters.closed.load(Ordering::SeqCst) {
+            self.counters.close.notified().await;
+        }
+    }
+
+    pub(crate) fn on_close(&self) {
+        self.counters.closed.store(true, Ordering::SeqCst);
+        self.counters.close.notify_one();
+    }
+
+    pub(crate) async fn before_append(&self, len: usize) -> Result<(), SessionResult> {
+        if let Some(latency) = self.latency {
+            time::sleep(latency).await;
+        }
+
+        let nth = self.counters.appends.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let over_limit = match self.fail_after_bytes {
+            Some(limit) => self.bytes() + len as u64 > limit,
+            None => false,
+        };
+
+        if self.fail_nth == Some(nth) || over_limit {
+            self.counters.failed.fetch_add(1, Ordering::SeqCst);
+            let err = io::Error::other(format!("injected fault on append {}", nth));
+            return Err(SessionResult::Err(StatusResult::IoError(err)));
+        }
+
+        self.counters.bytes.fetch_add(len as u64, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub(crate) fn after_write(&self) -> Result<(), SessionResult> {
+        let nth = self.counters.writes.fetch_add(1, Ordering::SeqCst) + 1;
+
+        if self.fail_nth_write == Some(nth) {
+            self.counters.failed.fetch_add(1, Ordering::SeqCst);
+            let err = io::Error::other(format!("injected fault on write {}", nth));
+            return Err(SessionResult::Err(StatusResult::IoError(err)));
+        }
+        Ok(())
+    }
+}
+
+
+
+/// Subscriber consume one msg per `delay`,
+/// used to fill router channels and trigger lag alerts
+pub struct SlowSubscriber {
+    received: Arc<AtomicU64>,
+    handle: JoinHandle<()>,
+}
+
+impl SlowSubscriber {
+    /// return sender to subscribe with and the consumer
+    pub fn spawn<Msg: Send + 'static>(capacity: usize, delay: Duration) -> (Sender<Msg>, SlowSubscriber) {
+        let (sx, mut rx) = mpsc::channel(capacity);
+        let received = Arc::new(AtomicU64::new(0));
+        let counter = received.clone();
+
+        let handle = tokio::spawn(async move {
+            while rx.recv().await.is_some() {
+                counter.fetch_add(1, Ordering::SeqCst);
+                time::sleep(delay).await;
+            }
+        });
+
+        (sx, SlowSubscriber { received, handle })
+    }
+
+    /// total msgs received so far
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::SeqCst)
+    }
+
+    /// wait until channel closed (e.g. store closed), return total received
+    pub async fn closed(self) -> u64 {
+        let _ = self.handle.await;
+        self.received.load(Ordering::SeqCst)
+    }
+}
+
+
+
+/// Freeze tokio clock, timers used by coalescing, TTL and timeouts
+/// only fire on `advance_clock` (or when runtime is idle),
+/// require a current_thread runtime
+pub fn pause_clock() {
+    time::pause();
+}
+
+/// move paused clock forward and fire due timers
+pub async fn advance_clock(duration: Duration) {
+    time::advance(duration).await;
+}
+
+pub fn resume_clock() {
+    time::resume();
+}
+
+/// current time of tokio clock
+pub fn now() -> Instant {
+    Instant::now()
+}
+
+
+
+/// Wall clock moved by hand, can step backwards to simulate NTP,
+/// wrap it in `ClampedClock` to observe clamping
+#[derive(Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl ManualClock {
+    pub fn new(start: SystemTime) -> Self {
+        ManualClock { now: Arc::new(Mutex::new(start)) }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock() += duration;
+    }
+
+    pub fn step_back(&self, duration: Duration) {
+        *self.now.lock() -= duration;
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock() = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock()
+    }
+}
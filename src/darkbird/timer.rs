@@ -0,0 +1,289 @@
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::Notify, task::JoinHandle, time};
+
+use super::{
+    clock::Clock,
+    recovery::rebase,
+    router,
+    storage::Event,
+    wal::{codec::WalCodec, disk_log::{DiskLog, Session}, writer::WalWriter},
+    SessionResult, StatusResult,
+};
+
+// longest sleep of timer service, so steps of wall clock are noticed
+const MAX_TICK: Duration = Duration::from_secs(1);
+
+
+
+// records of timer WAL, deadlines are wall time in millis since UNIX_EPOCH
+#[derive(Serialize, Deserialize)]
+enum TimerRecord<K> {
+    Arm(K, u64),
+    Cancel(K),
+
+    // logged after Event::Timer dispatched, so not re-armed on replay
+    Fired(K, u64),
+}
+
+
+struct Shared<K> {
+    // key -> deadline
+    pending: Mutex<HashMap<K, u64>>,
+    wake: Notify,
+    closed: AtomicBool,
+
+    // set by first subscribe, timers re-armed on open don't fire to nobody
+    started: AtomicBool,
+}
+
+
+/// Durable timers of a store, fire `Event::Timer(key)` to subscribers at deadline.
+///
+/// timers logged to their own WAL (`<name>.timers`) beside store WAL and re-armed on open,
+/// a deadline passed while down fire immediately.
+/// nothing fire until first subscriber registered, so events aren't lost between open and subscribe.
+/// delivery is at-least-once: a crash between dispatch and `Fired` record
+/// fire that timer again after restart, a fired timer never fire again otherwise
+pub(crate) struct Timers<K> {
+    shared: Arc<Shared<K>>,
+    wal: Option<Session>,
+    service: Option<JoinHandle<()>>,
+}
+
+impl<K> Timers<K>
+where
+    K: Serialize + DeserializeOwned + Eq + Hash + Clone + Send + Sync + 'static,
+{
+    /// `durable` false keep timers in memory only (RamCopies).
+    /// deadlines replayed are moved by rebase_ms and logged again (`ClockSkewPolicy::TrustLog`),
+    /// return timers and deadlines moved
+    pub async fn open<Doc>(
+        path: &str,
+        name: &str,
+        total_page_size: usize,
+        durable: bool,
+        clock: Arc<dyn Clock>,
+        rebase_ms: i64,
+        reporter: router::Session<Event<K, Doc>>,
+    ) -> Result<(Self, u64), String>
+    where
+        Doc: Send + 'static,
+    {
+        let (wal, pending) = if durable {
+            let disklog = DiskLog::open(path, &format!("{}.timers", name), total_page_size, WalCodec::Bincode, WalWriter::Thread)?;
+            let wal = disklog.run_service();
+            let mut pending = replay(&wal).await?;
+            if rebase_ms != 0 {
+                for (key, deadline) in pending.iter_mut() {
+                    *deadline = rebase(*deadline, rebase_ms);
+                    wal.log(bincode::serialize(&TimerRecord::Arm(key, *deadline)).unwrap()).await.map_err(|e| e.to_string())?;
+                }
+            }
+            (Some(wal), pending)
+        } else {
+            (None, HashMap::new())
+        };
+        let rebased = if rebase_ms != 0 { pending.len() as u64 } else { 0 };
+
+        let shared = Arc::new(Shared {
+            pending: Mutex::new(pending),
+            wake: Notify::new(),
+            closed: AtomicBool::new(false),
+            started: AtomicBool::new(false),
+        });
+
+        let service = tokio::spawn(run_service(shared.clone(), wal.clone(), clock, reporter));
+
+        Ok((Timers { shared, wal, service: Some(service) }, rebased))
+    }
+
+    /// start firing, called when a subscriber registered
+    pub fn start(&self) {
+        if !self.shared.started.swap(true, Ordering::SeqCst) {
+            self.shared.wake.notify_one();
+        }
+    }
+
+    /// arm or re-arm timer of key
+    pub async fn arm(&self, key: K, deadline: SystemTime) -> Result<(), SessionResult> {
+        let deadline = millis(deadline);
+        self.log(&TimerRecord::Arm(key.clone(), deadline)).await?;
+
+        self.shared.pending.lock().insert(key, deadline);
+        self.shared.wake.notify_one();
+        Ok(())
+    }
+
+    /// return false if key had no pending timer
+    pub async fn cancel(&self, key: &K) -> Result<bool, SessionResult> {
+        if !self.shared.pending.lock().contains_key(key) {
+            return Ok(false);
+        }
+
+        self.log(&TimerRecord::Cancel(key.clone())).await?;
+
+        let removed = self.shared.pending.lock().remove(key).is_some();
+        self.shared.wake.notify_one();
+        Ok(removed)
+    }
+
+    pub fn deadline(&self, key: &K) -> Option<SystemTime> {
+        self.shared
+            .pending
+            .lock()
+            .get(key)
+            .map(|deadline| UNIX_EPOCH + Duration::from_millis(*deadline))
+    }
+
+    /// stop service and close timer WAL
+    pub async fn close(mut self) -> Result<(), SessionResult> {
+        self.shared.closed.store(true, Ordering::SeqCst);
+        self.shared.wake.notify_one();
+
+        if let Some(service) = self.service.take() {
+            let _ = service.await;
+        }
+
+        match &self.wal {
+            Some(wal) => wal.close().await.map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    async fn log(&self, record: &TimerRecord<K>) -> Result<(), SessionResult> {
+        match &self.wal {
+            Some(wal) => wal.log(bincode::serialize(record).unwrap()).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl<K> Drop for Timers<K> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+        self.shared.wake.notify_one();
+    }
+}
+
+
+
+async fn run_service<K, Doc>(
+    shared: Arc<Shared<K>>,
+    wal: Option<Session>,
+    clock: Arc<dyn Clock>,
+    reporter: router::Session<Event<K, Doc>>,
+) where
+    K: Serialize + Eq + Hash + Clone + Send + 'static,
+    Doc: Send + 'static,
+{
+    loop {
+        if shared.closed.load(Ordering::SeqCst) {
+            return;
+        }
+
+        if !shared.started.load(Ordering::SeqCst) {
+            shared.wake.notified().await;
+            continue;
+        }
+
+        let now = millis(clock.now());
+
+        let due: Vec<(K, u64)> = shared
+            .pending
+            .lock()
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(key, deadline)| (key.clone(), *deadline))
+            .collect();
+
+        for (key, deadline) in due {
+            // cancelled or re-armed meanwhile
+            {
+                let mut pending = shared.pending.lock();
+                if pending.get(&key) != Some(&deadline) {
+                    continue;
+                }
+                pending.remove(&key);
+            }
+
+            let _ = reporter.dispatch(Event::Timer(key.clone())).await;
+
+            if let Some(wal) = &wal {
+                if let Err(e) = wal.log(bincode::serialize(&TimerRecord::Fired(key, deadline)).unwrap()).await {
+                    eprintln!("timers: {}", e.to_string());
+                }
+            }
+        }
+
+        let next = shared.pending.lock().values().min().copied();
+        let wait = match next {
+            Some(deadline) => Duration::from_millis(deadline.saturating_sub(now)).min(MAX_TICK),
+            None => MAX_TICK,
+        };
+
+        tokio::select! {
+            _ = time::sleep(wait) => {}
+            _ = shared.wake.notified() => {}
+        }
+    }
+}
+
+
+async fn replay<K>(wal: &Session) -> Result<HashMap<K, u64>, String>
+where
+    K: DeserializeOwned + Eq + Hash,
+{
+    let mut pending = HashMap::new();
+    let mut page_index = 1;
+
+    loop {
+        let mut logfile = match wal.get_page(page_index).await {
+            Ok(lf) => lf,
+            Err(SessionResult::Err(StatusResult::End)) => return Ok(pending),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        page_index += 1;
+
+        let iter = logfile.iter(..).map_err(|e| e.to_string())?;
+        for qline in iter {
+            let bytes = qline.map_err(|e| e.to_string())?;
+
+            // header of page
+            if let Some(codec) = WalCodec::of_header(&bytes) {
+                codec?;
+                continue;
+            }
+
+            match bincode::deserialize(&bytes).map_err(|e| e.to_string())? {
+                TimerRecord::Arm(key, deadline) => {
+                    pending.insert(key, deadline);
+                }
+                TimerRecord::Cancel(key) => {
+                    pending.remove(&key);
+                }
+                TimerRecord::Fired(key, deadline) => {
+                    if pending.get(&key) == Some(&deadline) {
+                        pending.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+#[inline]
+fn millis(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
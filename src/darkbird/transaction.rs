@@ -0,0 +1,431 @@
+use anymap::AnyMap;
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{any::Any, collections::HashMap, hash::Hash};
+
+use crate::{document::Document, RQuery, Storage};
+
+use super::{storage::{Before, Held}, SessionResult};
+
+
+
+/// Writes of a `Database::cross_transaction`, buffered per store until closure return.
+///
+/// nothing is read or written while buffering: stores are checked when committed,
+/// and an update closure run then on document as left by writes before it
+pub struct Transaction {
+    pending: Vec<Box<dyn PendingOp>>,
+}
+
+impl Transaction {
+    pub(crate) fn new() -> Self {
+        Transaction { pending: vec![] }
+    }
+
+    pub fn insert<K, Doc>(&mut self, key: K, doc: Doc)
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K: Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.ops::<K, Doc>().push(Op::Insert(key, doc));
+    }
+
+    pub fn remove<K, Doc>(&mut self, key: K)
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K: Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.ops::<K, Doc>().push(Op::Remove(key));
+    }
+
+    /// logged as `RQuery::Update` like `Storage::update`, skipped if key is missing when committed
+    pub fn update<K, Doc, F>(&mut self, key: K, f: F)
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K: Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        F: FnOnce(&mut Doc) + 'static,
+    {
+        self.ops::<K, Doc>().push(Op::Update(key, Box::new(f)));
+    }
+
+    /// hold store with its writes paused while committed, even if nothing is written to it
+    pub(crate) fn pause<K, Doc>(&mut self)
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K: Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.store::<K, Doc>().paused = true;
+    }
+
+    pub(crate) fn is_paused<K, Doc>(&mut self) -> bool
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K: Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.store::<K, Doc>().paused
+    }
+
+    /// writes buffered
+    pub fn len(&self) -> usize {
+        self.pending.iter().map(|pending| pending.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn ops<K, Doc>(&mut self) -> &mut Vec<Op<K, Doc>>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K: Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        &mut self.store::<K, Doc>().ops
+    }
+
+    fn store<K, Doc>(&mut self) -> &mut StoreOps<K, Doc>
+    where
+        Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+        K: Serialize
+            + DeserializeOwned
+            + PartialOrd
+            + Ord
+            + PartialEq
+            + Eq
+            + Hash
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        let index = match self.pending.iter_mut().position(|pending| pending.as_any().is::<StoreOps<K, Doc>>()) {
+            Some(index) => index,
+            None => {
+                self.pending.push(Box::new(StoreOps::<K, Doc> { ops: vec![], paused: false }));
+                self.pending.len() - 1
+            }
+        };
+
+        self.pending[index].as_any().downcast_mut::<StoreOps<K, Doc>>().unwrap()
+    }
+
+    /// Two-phase commit: every store is held and checked, then logged,
+    /// and only once all are logged is memory of any of them changed.
+    ///
+    /// stores are held in type name order, so two transactions can't deadlock.
+    /// a store is logged once disk_log wrote its record, not just queued it.
+    /// if a store fail to log, it and those logged before log their keys back as they were
+    /// and nothing is applied. stores log to their own WAL: a crash between two of them
+    /// leave on restart writes of stores logged before it
+    pub(crate) async fn commit(self, datastores: &AnyMap) -> Result<(), SessionResult> {
+        self.commit_checked(datastores, || Ok(true)).await.map(|_| ())
+    }
+
+    /// `commit`, with check run once all stores are held, before anything is logged.
+    /// check returning false abort commit with nothing written, and return false
+    pub(crate) async fn commit_checked(self, datastores: &AnyMap, check: impl FnOnce() -> Result<bool, SessionResult>) -> Result<bool, SessionResult> {
+        let mut pending = self.pending;
+        pending.sort_by_key(|pending| pending.store());
+
+        let mut participants = vec![];
+        for pending in pending {
+            participants.push(pending.bind(datastores)?);
+        }
+
+        // prepare: locks of all stores held until applied, dropped on early return
+        for participant in participants.iter_mut() {
+            participant.hold().await?;
+        }
+        if !check()? {
+            return Ok(false);
+        }
+        for participant in participants.iter_mut() {
+            participant.prepare()?;
+        }
+
+        for index in 0..participants.len() {
+            if let Err(e) = participants[index].log().await {
+                for participant in participants[..=index].iter_mut() {
+                    if let Err(rollback) = participant.roll_back().await {
+                        eprintln!("cross_transaction rollback {}: {}", participant.store(), rollback.to_string());
+                    }
+                }
+                return Err(e);
+            }
+        }
+
+        // commit
+        for participant in participants.iter_mut() {
+            participant.apply().await;
+        }
+        for participant in participants {
+            participant.dispatch().await;
+        }
+
+        Ok(true)
+    }
+}
+
+
+
+enum Op<K, Doc> {
+    Insert(K, Doc),
+    Remove(K),
+    Update(K, Box<dyn FnOnce(&mut Doc)>),
+}
+
+impl<K, Doc> Op<K, Doc> {
+    fn key(&self) -> &K {
+        match self {
+            Op::Insert(key, _) | Op::Remove(key) | Op::Update(key, _) => key,
+        }
+    }
+}
+
+
+/// buffered writes of one store, type erased so a transaction hold stores of any type
+trait PendingOp {
+    fn as_any(&mut self) -> &mut dyn Any;
+
+    fn store(&self) -> &'static str;
+
+    fn len(&self) -> usize;
+
+    /// store of writes, `DataStoreNotFound` if it isn't in datastores
+    fn bind<'a>(self: Box<Self>, datastores: &'a AnyMap) -> Result<Box<dyn Participant + 'a>, SessionResult>;
+}
+
+struct StoreOps<K, Doc> {
+    ops: Vec<Op<K, Doc>>,
+    paused: bool,
+}
+
+impl<K, Doc> PendingOp for StoreOps<K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn store(&self) -> &'static str {
+        std::any::type_name::<Storage<K, Doc>>()
+    }
+
+    fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    fn bind<'a>(self: Box<Self>, datastores: &'a AnyMap) -> Result<Box<dyn Participant + 'a>, SessionResult> {
+        let datastore = datastores
+            .get::<Storage<K, Doc>>()
+            .ok_or(SessionResult::DataStoreNotFound)?;
+
+        Ok(Box::new(Bound {
+            datastore,
+            ops: self.ops,
+            paused: self.paused,
+            held: None,
+            before: vec![],
+            queries: vec![],
+            previous: vec![],
+            logged: false,
+        }))
+    }
+}
+
+
+
+/// store taking part in a commit, steps run in order for all stores before next one
+#[async_trait(?Send)]
+trait Participant {
+    fn store(&self) -> &'static str;
+
+    /// room, write locks of keys and rebuild gate, held until dispatch
+    async fn hold(&mut self) -> Result<(), SessionResult>;
+
+    /// run update closures, write plugins and index conflict check
+    fn prepare(&mut self) -> Result<(), SessionResult>;
+
+    async fn log(&mut self) -> Result<(), SessionResult>;
+
+    /// undo log, if logged
+    async fn roll_back(&mut self) -> Result<(), SessionResult>;
+
+    async fn apply(&mut self);
+
+    /// release store and dispatch events
+    async fn dispatch(self: Box<Self>);
+}
+
+struct Bound<'a, K, Doc: Document> {
+    datastore: &'a Storage<K, Doc>,
+    ops: Vec<Op<K, Doc>>,
+    paused: bool,
+    held: Option<Held<'a>>,
+
+    // keys as stored when held, logged back by roll_back
+    before: Vec<Before<K, Doc>>,
+
+    queries: Vec<RQuery<K, Doc>>,
+    previous: Vec<Option<Doc>>,
+    logged: bool,
+}
+
+#[async_trait(?Send)]
+impl<K, Doc> Participant for Bound<'_, K, Doc>
+where
+    Doc: Serialize + DeserializeOwned + Clone + Send + 'static + Document,
+    K: Serialize
+        + DeserializeOwned
+        + PartialOrd
+        + Ord
+        + PartialEq
+        + Eq
+        + Hash
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    fn store(&self) -> &'static str {
+        std::any::type_name::<Storage<K, Doc>>()
+    }
+
+    async fn hold(&mut self) -> Result<(), SessionResult> {
+        let mut keys: Vec<&K> = self.ops.iter().map(|op| op.key()).collect();
+        keys.sort();
+        keys.dedup();
+        let inserted: Vec<&K> = self.ops.iter().filter(|op| matches!(op, Op::Insert(..))).map(|op| op.key()).collect();
+
+        self.held = Some(self.datastore.hold(&keys, &inserted, self.paused).await?);
+        self.before = self.datastore.before(&keys);
+        Ok(())
+    }
+
+    fn prepare(&mut self) -> Result<(), SessionResult> {
+        // documents as writes before leave them, None: removed
+        let mut current: HashMap<K, Option<Doc>> = HashMap::new();
+        let mut queries = Vec::with_capacity(self.ops.len());
+
+        for op in self.ops.drain(..) {
+            match op {
+                Op::Insert(key, doc) => {
+                    current.insert(key.clone(), Some(doc.clone()));
+                    queries.push(RQuery::Insert(key, doc));
+                }
+                Op::Remove(key) => {
+                    current.insert(key.clone(), None);
+                    queries.push(RQuery::Remove(key));
+                }
+                Op::Update(key, f) => {
+                    let doc = match current.get(&key) {
+                        Some(doc) => doc.clone(),
+                        None => self.datastore.get_owned(&key),
+                    };
+                    if let Some(mut doc) = doc {
+                        f(&mut doc);
+                        current.insert(key.clone(), Some(doc.clone()));
+                        queries.push(RQuery::Update(key, doc));
+                    }
+                }
+            }
+        }
+
+        self.queries = self.datastore.check_transaction(queries)?;
+        Ok(())
+    }
+
+    async fn log(&mut self) -> Result<(), SessionResult> {
+        // a record failing once queued may still be on disk, so logged back too
+        self.logged = true;
+        self.datastore.log_transaction(&mut self.queries).await.map(|_| ())
+    }
+
+    async fn roll_back(&mut self) -> Result<(), SessionResult> {
+        if !self.logged {
+            return Ok(());
+        }
+        let before = std::mem::take(&mut self.before);
+        self.datastore.log_before(&self.queries, before).await
+    }
+
+    async fn apply(&mut self) {
+        self.previous = self.datastore.apply_transaction(&self.queries).await;
+    }
+
+    async fn dispatch(mut self: Box<Self>) {
+        self.held = None;
+        self.datastore.dispatch_queries(self.queries, self.previous).await;
+    }
+}
@@ -0,0 +1,49 @@
+use parking_lot::RwLock;
+use std::{collections::HashMap, sync::Arc};
+
+
+
+/// Predicate of a view created by `Storage::create_view`, document is in view when true
+pub type ViewFn<K, Doc> = Arc<dyn Fn(&K, &Doc) -> bool + Send + Sync>;
+
+
+/// Views created by `Storage::create_view`, held in tag index beside views of
+/// `MaterializedView::filter` so `fetch_view` and its variants read both.
+///
+/// in memory only, a reopened store has none until created again
+pub(crate) struct Views<K, Doc> {
+    views: RwLock<HashMap<String, ViewFn<K, Doc>>>,
+}
+
+impl<K, Doc> Views<K, Doc> {
+    pub fn new() -> Self {
+        Views { views: RwLock::new(HashMap::new()) }
+    }
+
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<ViewFn<K, Doc>> {
+        self.views.read().get(name).cloned()
+    }
+
+    /// false if name is taken
+    pub fn add(&self, name: &str, view: ViewFn<K, Doc>) -> bool {
+        let mut views = self.views.write();
+        if views.contains_key(name) {
+            return false;
+        }
+        views.insert(name.to_owned(), view);
+        true
+    }
+
+    pub fn remove(&self, name: &str) -> bool {
+        self.views.write().remove(name).is_some()
+    }
+
+    /// run f for each view, a view must not create or drop views
+    #[inline]
+    pub fn each(&self, mut f: impl FnMut(&str, &ViewFn<K, Doc>)) {
+        for (name, view) in self.views.read().iter() {
+            f(name, view);
+        }
+    }
+}
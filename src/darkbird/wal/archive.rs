@@ -0,0 +1,276 @@
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, fs, path::Path, sync::Arc, time::Duration};
+use tokio::{
+    sync::{mpsc::UnboundedSender, Notify},
+    time::Instant,
+};
+
+use crate::darkbird::{SessionResult, StatusResult};
+
+use super::{disk_log::filename_factory, frames::{frames, Frame}};
+
+
+
+// longest wait of compaction for acknowledgements, see ArchiveHook::with_grace
+const DEFAULT_GRACE: Duration = Duration::from_secs(60);
+
+
+/// Ship sealed WAL pages off-box, set by `Options::with_archive_hook`.
+///
+/// a `PageSealed` is sent once a page is full, or cut by compaction, and fsynced.
+/// compaction doesn't delete a page before it's acknowledged by `PageSealed::ack`:
+/// it wait at most grace, then delete it with a warning, or fail with `fail_after_grace`.
+///
+/// sequence of pages and those not acknowledged are kept in `<path>/<storage_name>.archive`,
+/// so pages not acknowledged before a restart are sent again by open,
+/// and first open with a hook send every page already sealed
+#[derive(Clone)]
+pub struct ArchiveHook {
+    sender: UnboundedSender<PageSealed>,
+    grace: Duration,
+    force: bool,
+}
+
+impl ArchiveHook {
+    pub fn new(sender: UnboundedSender<PageSealed>) -> Self {
+        ArchiveHook {
+            sender,
+            grace: DEFAULT_GRACE,
+            force: true,
+        }
+    }
+
+    /// longest wait of compaction for acknowledgement of pages it delete, 60s by default
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// fail compaction when grace elapsed with pages not acknowledged, in place of deleting them
+    pub fn fail_after_grace(mut self) -> Self {
+        self.force = false;
+        self
+    }
+}
+
+
+/// WAL page sealed, to archive then acknowledge
+pub struct PageSealed {
+    // page file, kept until acknowledged or grace of compaction elapsed
+    pub path: String,
+
+    // index of page in WAL when sent, compaction renumber pages
+    pub index: usize,
+
+    // from 1, each page sealed in lifetime of store once, compaction included
+    pub seq: u64,
+
+    // crc32 of page file
+    pub checksum: u32,
+
+    pub records: usize,
+
+    total_page_size: usize,
+    archive: Arc<Archive>,
+}
+
+impl PageSealed {
+    /// page is archived, compaction may delete it
+    pub fn ack(&self) {
+        self.archive.ack(self.seq);
+    }
+
+    /// Name of page file in an archive that reopen as a store.
+    ///
+    /// pages copied under their restore name into `<dir>/<storage_name>/` replay
+    /// every page sealed, in order, by `Storage::open` of `Options::new(dir, storage_name, ..)`
+    /// with same page size and codec
+    pub fn restore_name(&self) -> String {
+        format!("page-{}.LOG", self.seq as usize * self.total_page_size)
+    }
+}
+
+impl fmt::Debug for PageSealed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PageSealed")
+            .field("path", &self.path)
+            .field("index", &self.index)
+            .field("seq", &self.seq)
+            .field("checksum", &self.checksum)
+            .field("records", &self.records)
+            .finish()
+    }
+}
+
+
+#[derive(Default, Serialize, Deserialize)]
+struct ArchiveState {
+    // seq of last page sealed
+    sealed: u64,
+
+    // seq to page index, of pages sent and not acknowledged
+    pending: BTreeMap<u64, usize>,
+}
+
+
+/// pages sent to hook and their acknowledgements, shared by disk_log and its sessions
+pub(crate) struct Archive {
+    hook: ArchiveHook,
+    dir: String,
+    total_page_size: usize,
+    file: String,
+    state: Mutex<ArchiveState>,
+
+    // one waiter at a time, compactions don't overlap
+    acked: Notify,
+}
+
+impl Archive {
+    /// state of WAL at dir whose pages 1 to sealed_pages are sealed,
+    /// pages not acknowledged are sent again
+    pub fn open(hook: ArchiveHook, dir: &str, total_page_size: usize, sealed_pages: usize) -> Arc<Self> {
+        let file = format!("{}.archive", dir);
+
+        let state = match fs::read(&file) {
+            Ok(bytes) => serde_json::from_slice(&bytes).ok(),
+            Err(_) => None,
+        };
+
+        let state = state.unwrap_or_else(|| ArchiveState {
+            sealed: sealed_pages as u64,
+            pending: (1..=sealed_pages).map(|index| (index as u64, index)).collect(),
+        });
+
+        let archive = Arc::new(Archive {
+            hook,
+            dir: dir.to_owned(),
+            total_page_size,
+            file,
+            state: Mutex::new(state),
+            acked: Notify::new(),
+        });
+
+        let pending: Vec<_> = archive.state.lock().pending.iter().map(|(seq, index)| (*seq, *index)).collect();
+        for (seq, index) in pending {
+            let filename = filename_factory(&archive.dir, index * total_page_size);
+            if !Path::new(&filename).is_file() {
+                eprintln!("archive: page {} of seq {} is gone, not sent again", filename, seq);
+                archive.state.lock().pending.remove(&seq);
+                continue;
+            }
+
+            archive.send(seq, index);
+        }
+        archive.persist();
+
+        archive
+    }
+
+    /// page of index is fsynced, give it next seq and send it
+    pub fn seal(self: &Arc<Self>, index: usize) {
+        let seq = {
+            let mut state = self.state.lock();
+            state.sealed += 1;
+            let seq = state.sealed;
+            state.pending.insert(seq, index);
+            seq
+        };
+
+        self.persist();
+        self.send(seq, index);
+    }
+
+    /// seq of last page sealed
+    pub fn sealed(&self) -> u64 {
+        self.state.lock().sealed
+    }
+
+    /// pages before tail are deleted and those after renumbered, see `Context::install`
+    pub fn install(&self, snapshot_pages: usize, tail: usize) {
+        {
+            let mut state = self.state.lock();
+            let pending = std::mem::take(&mut state.pending);
+            for (seq, index) in pending {
+                if index < tail {
+                    eprintln!("archive: page of seq {} deleted by compaction before acknowledged", seq);
+                    continue;
+                }
+                state.pending.insert(seq, snapshot_pages + index - tail + 1);
+            }
+        }
+
+        self.persist();
+    }
+
+    /// wait until pages sealed up to seq are acknowledged, at most grace of hook
+    pub async fn wait(&self, seq: u64) -> Result<(), SessionResult> {
+        let deadline = Instant::now() + self.hook.grace;
+        let mut warned = false;
+
+        loop {
+            let unacked = self.state.lock().pending.range(..=seq).count();
+            if unacked == 0 {
+                return Ok(());
+            }
+
+            if !warned {
+                eprintln!("archive: compaction waiting for {} sealed pages to be acknowledged", unacked);
+                warned = true;
+            }
+
+            if tokio::time::timeout_at(deadline, self.acked.notified()).await.is_err() {
+                if self.hook.force {
+                    eprintln!("archive: {} sealed pages not acknowledged after {:?}, compaction proceed", unacked, self.hook.grace);
+                    return Ok(());
+                }
+
+                return Err(SessionResult::Err(StatusResult::Err(format!(
+                    "archive: {} sealed pages not acknowledged after {:?}",
+                    unacked, self.hook.grace
+                ))));
+            }
+        }
+    }
+
+    fn ack(&self, seq: u64) {
+        if self.state.lock().pending.remove(&seq).is_some() {
+            self.persist();
+            self.acked.notify_one();
+        }
+    }
+
+    fn send(self: &Arc<Self>, seq: u64, index: usize) {
+        let path = filename_factory(&self.dir, index * self.total_page_size);
+        let bytes = fs::read(&path).unwrap_or_default();
+        let checksum = crc32fast::hash(&bytes);
+        let records = frames(&bytes).filter(|(_, frame)| matches!(frame, Frame::Record(_))).count();
+
+        let sealed = PageSealed {
+            path,
+            index,
+            seq,
+            checksum,
+            records,
+            total_page_size: self.total_page_size,
+            archive: self.clone(),
+        };
+
+        // hook dropped, page is sent again by next open
+        if self.hook.sender.send(sealed).is_err() {
+            eprintln!("archive: hook closed, page of seq {} not sent", seq);
+        }
+    }
+
+    // written aside then renamed, so a crash leave previous state,
+    // under lock so an older state can't be renamed over a newer one
+    fn persist(&self) {
+        let state = self.state.lock();
+        let tmp = format!("{}.tmp", self.file);
+
+        let res = fs::write(&tmp, serde_json::to_vec(&*state).unwrap()).and_then(|_| fs::rename(&tmp, &self.file));
+        if let Err(e) = res {
+            eprintln!("archive: {}", e);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+
+
+// header record of each page, followed by format version, ':' and codec name
+const HEADER: &[u8] = b"darkbird-wal-v";
+
+// header written before format versions, only by codecs other than bincode
+const LEGACY_HEADER: &[u8] = b"darkbird-wal-codec:";
+
+/// format version of WAL pages written by this release, in header record of each page.
+/// pages without header (bincode before codecs) and with codec header before versions
+/// are still read, any other version fail open naming it
+pub const WAL_FORMAT_VERSION: u32 = 2;
+
+
+/// Encoding of WAL records, implemented by `WalCodec`.
+///
+/// public so tools reading pages without darkbird's loader decode records
+/// with codec named by page header, see `WalCodec::of_header`
+pub trait Codec {
+    fn encode<T: Serialize>(&self, value: &T) -> Vec<u8>;
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String>;
+}
+
+
+/// Encoding of WAL records, set by `Options::with_wal_codec`.
+///
+/// each page of a WAL start with a header record naming format version and codec,
+/// a WAL without header is Bincode, so WALs written before codecs load unchanged.
+/// a WAL is opened only with codec it was written with, a mismatch fail open with
+/// `WalCodecMismatch`. Json and Msgpack records are readable without darkbird,
+/// at cost of size and speed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WalCodec {
+    #[default]
+    Bincode,
+    Json,
+
+    // fields by name, so records survive reordered fields
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+}
+
+impl WalCodec {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WalCodec::Bincode => "bincode",
+            WalCodec::Json => "json",
+            #[cfg(feature = "msgpack")]
+            WalCodec::Msgpack => "msgpack",
+        }
+    }
+
+    fn from_name(name: &[u8]) -> Result<WalCodec, String> {
+        match name {
+            b"bincode" => Ok(WalCodec::Bincode),
+            b"json" => Ok(WalCodec::Json),
+            #[cfg(feature = "msgpack")]
+            b"msgpack" => Ok(WalCodec::Msgpack),
+            #[cfg(not(feature = "msgpack"))]
+            b"msgpack" => Err("WAL codec msgpack require msgpack feature of darkbird".to_owned()),
+            _ => Err(format!("unknown WAL codec {}", String::from_utf8_lossy(name))),
+        }
+    }
+
+    /// first record of each page
+    pub(crate) fn header(&self) -> Vec<u8> {
+        [HEADER, format!("{}:{}", WAL_FORMAT_VERSION, self.name()).as_bytes()].concat()
+    }
+
+    /// codec named by record if it's a header, no record of any codec start like one.
+    /// a header of a format version this release doesn't read is an error naming it
+    pub fn of_header(record: &[u8]) -> Option<Result<WalCodec, String>> {
+        if let Some(name) = record.strip_prefix(LEGACY_HEADER) {
+            return Some(WalCodec::from_name(name));
+        }
+
+        let versioned = record.strip_prefix(HEADER)?;
+        let Some(colon) = versioned.iter().position(|b| *b == b':') else {
+            return Some(Err("WAL page header without format version".to_owned()));
+        };
+
+        let version = String::from_utf8_lossy(&versioned[..colon]);
+        match version.parse::<u32>() {
+            Ok(WAL_FORMAT_VERSION) => Some(WalCodec::from_name(&versioned[colon + 1..])),
+            Ok(v) if v > WAL_FORMAT_VERSION => Some(Err(format!(
+                "WAL page format version {} is newer than {} read by this release of darkbird",
+                v, WAL_FORMAT_VERSION
+            ))),
+            _ => Some(Err(format!(
+                "unsupported WAL page format version {}, this release of darkbird read version {}",
+                version, WAL_FORMAT_VERSION
+            ))),
+        }
+    }
+}
+
+impl Codec for WalCodec {
+    fn encode<T: Serialize>(&self, value: &T) -> Vec<u8> {
+        match self {
+            WalCodec::Bincode => bincode::serialize(value).unwrap(),
+            WalCodec::Json => serde_json::to_vec(value).unwrap(),
+            #[cfg(feature = "msgpack")]
+            WalCodec::Msgpack => rmp_serde::to_vec_named(value).unwrap(),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String> {
+        match self {
+            WalCodec::Bincode => bincode::deserialize(bytes).map_err(|e| e.to_string()),
+            WalCodec::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            #[cfg(feature = "msgpack")]
+            WalCodec::Msgpack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+        }
+    }
+}
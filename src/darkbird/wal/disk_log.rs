@@ -6,7 +6,10 @@ pub const DISKLOG_BUFFER_SIZE: usize = 1000;
 struct TmpLogStruct {
     path: String,
     log: LogFile,
-    current_page_index: usize
+    current_page_index: usize,
+
+    // bytes of a torn record LogFile::open dropped from end of last page
+    torn: u64
 }    
 
 
@@ -15,26 +18,115 @@ enum Request {
 
     Record(Vec<u8>),
 
+    // written in order, one request for many records
+    Records(Vec<Vec<u8>>),
+
+    // Records, answered with result of writing and flushing them
+    Acked(Vec<Vec<u8>>, oneshot::Sender<Result<(), StatusResult>>),
+
     GetPage {
         page_index: usize, 
         dst: oneshot::Sender<Result<LogFile, StatusResult>>,
     },
+
+    // bytes of page, read without LogFile so a torn record is left in place
+    ReadPage {
+        page_index: usize,
+        dst: oneshot::Sender<Result<Vec<u8>, StatusResult>>,
+    },
+
+    // cut page at offset and remove pages after it
+    Truncate {
+        page_index: usize,
+        offset: u64,
+        dst: oneshot::Sender<Result<usize, StatusResult>>,
+    },
+
+    Close(oneshot::Sender<Result<WalStats, StatusResult>>),
+
+    // answered once requests queued before it are handled
+    Ping(oneshot::Sender<()>),
+
+    // start a new page once queue drained, see Session::checkpoint
+    Checkpoint(oneshot::Sender<Result<Checkpoint, StatusResult>>),
+
+    // replace pages before checkpoint by compacted pages
+    Install {
+        snapshot_pages: usize,
+        tail: usize,
+        dst: oneshot::Sender<Result<(usize, usize), StatusResult>>,
+    },
+}
+
+
+/// cut of WAL taken by `Session::checkpoint`, every record queued before it is in pages before `tail`
+pub struct Checkpoint {
+    // directory compacted pages are written to
+    pub path: String,
+    pub total_page_size: usize,
+
+    // first page written after checkpoint
+    pub tail: usize,
+
+    // file of page before tail, unchanged until WAL is compacted or migrated
+    pub covered: Option<String>,
+
+    // codec header each compacted page start with, see `WalCodec::header`
+    pub header: Vec<u8>,
+
+    // archive seq of page before tail, 0 without archive hook
+    pub sealed: u64,
 }
 
 
+/// counters of a disk_log since open
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WalStats {
+    // records written
+    pub records: u64,
+
+    // bytes of records written
+    pub bytes: u64,
+}
+
+
+/// page of WAL filled and closed, next records go to a new page
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PageRotated {
+    pub index: usize,
+
+    // size of page file and records in it, codec header included
+    pub bytes: u64,
+    pub records: usize,
+}
+
+pub(crate) type RotateHook = Arc<dyn Fn(PageRotated) + Send + Sync>;
+
+
 pub struct DiskLog {
-    context: Context
+    context: Context,
+
+    // reply of close request, set when close requested
+    closing: Option<oneshot::Sender<Result<WalStats, StatusResult>>>,
+
+    // reply of checkpoint request, set until queue drained
+    checkpointing: Option<oneshot::Sender<Result<Checkpoint, StatusResult>>>
 }
 
 impl DiskLog {
+    /// open WAL of table, Err if it was written with another codec
     pub fn open (path: &str, 
                  table_name: &str, 
-                 total_page_size: usize) -> Result<Self, LogError>  
+                 total_page_size: usize,
+                 codec: WalCodec,
+                 writer: WalWriter) -> Result<Self, String>  
     {
-        match Context::open(path, table_name, total_page_size) {
+        match Context::open(path, table_name, total_page_size, codec, writer) {
             Ok(context) => {
                 Ok(DiskLog {
-                    context
+                    context,
+                    closing: None,
+                    checkpointing: None
                 })        
             }
             Err(e) => {
@@ -44,8 +136,41 @@ impl DiskLog {
         
     }
 
-    pub fn run_service(mut self) -> Session {
-        let (sx, mut rx) = mpsc::channel(DISKLOG_BUFFER_SIZE);
+    /// send pages to hook as they are sealed, and pages sealed before not yet acknowledged
+    pub fn with_archive(mut self, hook: ArchiveHook) -> Self {
+        let sealed_pages = self.context.current_page_index - 1;
+        self.context.archive = Some(Archive::open(hook, &self.context.path, self.context.total_page_size, sealed_pages));
+        self
+    }
+
+    /// call hook on disk_log thread each time a page is closed for a new one
+    pub(crate) fn with_rotate_hook(mut self, hook: RotateHook) -> Self {
+        self.context.on_rotate = Some(hook);
+        self
+    }
+
+    /// total pages on disk
+    pub fn pages(&self) -> usize {
+        self.context.current_page_index
+    }
+
+    /// bytes of a record torn by a crash while appended, dropped from end of WAL by open
+    pub fn torn_tail(&self) -> u64 {
+        self.context.torn_tail
+    }
+
+    pub fn run_service(self) -> Session {
+        self.run_service_with_lanes(1)
+    }
+
+    /// records sent by `log_keyed` distributed over lanes by key hash,
+    /// lanes drained round robin so a hot key can't starve others
+    pub fn run_service_with_lanes(mut self, lanes: usize) -> Session {
+        let (sx, mut rx) = lanes::channel(lanes, DISKLOG_BUFFER_SIZE);
+
+        // every request a fence can find queued
+        let queued = sx.lanes() * DISKLOG_BUFFER_SIZE;
+        let archive = self.context.archive.clone();
         std::thread::spawn(move || {
 
             let mut worker_state;
@@ -53,7 +178,8 @@ impl DiskLog {
             loop {
 
                 // Block until get request
-                match self.handle_recv(rx.blocking_recv()) {
+                let op = rx.blocking_recv();
+                match self.handle_recv(op, &mut rx, queued) {
                     Ok(w) => {
                         worker_state = w;
                     }
@@ -76,7 +202,7 @@ impl DiskLog {
                         let try_recv_result = rx.try_recv();
 
                         // handle_try_recv
-                        match self.handle_try_recv(try_recv_result) {
+                        match self.handle_try_recv(try_recv_result, &mut rx, queued) {
                             Ok(w) => {
                                 worker_state = w;
                             }
@@ -97,8 +223,24 @@ impl DiskLog {
                     
                 }
 
-                // Flush
-                let _= self.context.log.flush();
+                // Flush, records of WalWriter::Uring are written here
+                if let Err(e) = self.context.log.flush() {
+                    eprintln!("{:?}", e);
+                }
+
+
+                // checkpoint after queue drained, so records queued before it on any lane are before cut
+                if let Some(dst) = self.checkpointing.take() {
+                    let _ = dst.send(self.context.checkpoint());
+                }
+
+                // if close requested, sync and release page after queue drained
+                if let Some(dst) = self.closing.take() {
+                    let res = self.context.sync().map(|_| self.context.stats);
+                    drop(self);
+                    let _ = dst.send(res);
+                    return
+                }
 
 
                 // if worker_state was disconnect terminate
@@ -108,100 +250,137 @@ impl DiskLog {
             }
         });
 
-        Session::new(sx)
+        Session::new(sx, archive)
     }
     
-    fn handle_recv(&mut self, op: Option<Request>) -> Result<WorkerState, StatusResult> {
+    fn handle_recv(&mut self, op: Option<Request>, rx: &mut LaneReceiver<Request>, queued: usize) -> Result<WorkerState, StatusResult> {
         match op {
-            Some(req) => {
-                match req {
-                    Request::Record(mut bytes) => {
-                        // Log
-                        match self.context.write_to_disk(&mut bytes) {
-                            Ok(_) => Ok(WorkerState::Continue),
-                            Err(e) => Err(e),
-                        }
-                    }
-                    Request::GetPage { page_index, dst } => {
-                        
-                        let filename = self.context.find_filename(page_index);
-                        
-                        // Check file exist  
-                        if Path::new(&filename).is_file() {
-                            // open logFile
-                            match LogFile::open(filename) {
-                                Ok(log) => {
-                                    // send logfile
-                                    let _ = dst.send(Ok(log));
-                                    Ok(WorkerState::Continue)
-                                }
-                                Err(e) => {
-
-                                    // LogErr is => ("Bad checksum" || "Out of bounds" || "the log is locked"
-                                    let _ = dst.send(Err(StatusResult::LogErr(e)));
-                                    
-                                    return  Ok(WorkerState::Continue)
-                                }
-                            }
+            Some(req) => self.handle_fenced(req, rx, queued),
+            None => Ok(WorkerState::Disconnected)
+        }
+    }
 
-                        // file not found
-                        } else {
-                            let _ = dst.send(Err(StatusResult::End));
-                            Ok(WorkerState::Continue)
+    fn handle_try_recv(&mut self, res: Result<Request, TryRecvError>, rx: &mut LaneReceiver<Request>, queued: usize) -> Result<WorkerState, StatusResult> {
+        match res {
+            Ok(req) => self.handle_fenced(req, rx, queued),
+            Err(e) => {
+                match e {
+                    TryRecvError::Empty => Ok(WorkerState::Empty),
+                    TryRecvError::Disconnected => Ok(WorkerState::Disconnected)
+                }
+            }
+        }
+    }
+
+    /// an acked request is a fence: requests queued on any lane before it are handled first,
+    /// so a record of many keys is written after each record of those keys logged before it.
+    /// fences found queued are handled after other requests, keys of a fence are held
+    /// by its writer until it's acked, so no request of them is queued behind it
+    fn handle_fenced(&mut self, req: Request, rx: &mut LaneReceiver<Request>, queued: usize) -> Result<WorkerState, StatusResult> {
+        if let Request::Acked(..) = req {
+            let mut fences = vec![];
+            for before in rx.drain(queued) {
+                match before {
+                    Request::Acked(..) => fences.push(before),
+                    before => {
+                        if let Err(e) = self.handle_request(before) {
+                            eprintln!("{:?}", e);
                         }
                     }
                 }
             }
-            None => Ok(WorkerState::Disconnected)
+            for fence in fences {
+                self.handle_request(fence)?;
+            }
         }
+
+        self.handle_request(req)
     }
 
-    fn handle_try_recv(&mut self, res: Result<Request, TryRecvError>) -> Result<WorkerState, StatusResult> {
-        match res {
-            Ok(req) => {
-                match req {
-                    Request::Record(mut bytes) => {
-                        // Log
-                        match self.context.write_to_disk(&mut bytes) {
-                            Ok(_) => Ok(WorkerState::Continue),
-                            Err(e) => Err(e),
+    fn handle_request(&mut self, req: Request) -> Result<WorkerState, StatusResult> {
+        match req {
+            Request::Record(mut bytes) => {
+                // Log
+                match self.context.write_to_disk(&mut bytes) {
+                    Ok(_) => Ok(WorkerState::Continue),
+                    Err(e) => Err(e),
+                }
+            }
+            Request::Records(records) => {
+                for mut bytes in records {
+                    self.context.write_to_disk(&mut bytes)?;
+                }
+                Ok(WorkerState::Continue)
+            }
+            Request::Acked(records, dst) => {
+                let res = records
+                    .into_iter()
+                    .try_for_each(|mut bytes| self.context.write_to_disk(&mut bytes))
+                    .and_then(|_| self.context.log.flush().map_err(StatusResult::IoError));
+                let _ = dst.send(res);
+                Ok(WorkerState::Continue)
+            }
+            Request::GetPage { page_index, dst } => {
+                self.context.log.flush().map_err(StatusResult::IoError)?;
+
+                let filename = self.context.find_filename(page_index);
+                
+                // Check file exist  
+                if Path::new(&filename).is_file() {
+                    // open logFile
+                    match LogFile::open(filename) {
+                        Ok(log) => {
+                            // send logfile
+                            let _ = dst.send(Ok(log));
+                            Ok(WorkerState::Continue)
                         }
-                    }
-                    Request::GetPage { page_index, dst } => {
-                        
-                        let filename = self.context.find_filename(page_index);
-                        
-                        // Check file exist  
-                        if Path::new(&filename).is_file() {
-                            // open logFile
-                            match LogFile::open(filename) {
-                                Ok(log) => {
-                                    // send logfile
-                                    let _ = dst.send(Ok(log));
-                                    Ok(WorkerState::Continue)
-                                }
-                                Err(e) => {
-
-                                    // LogErr is => ("Bad checksum" || "Out of bounds" || "the log is locked"
-                                    let _ = dst.send(Err(StatusResult::LogErr(e)));
-                                    
-                                    return  Ok(WorkerState::Continue)
-                                }
-                            }
+                        Err(e) => {
+
+                            // LogErr is => ("Bad checksum" || "Out of bounds" || "the log is locked"
+                            let _ = dst.send(Err(StatusResult::LogErr(e)));
                             
-                        // file not found
-                        } else {
-                            let _ = dst.send(Err(StatusResult::End));
                             Ok(WorkerState::Continue)
                         }
                     }
+
+                // file not found
+                } else {
+                    let _ = dst.send(Err(StatusResult::End));
+                    Ok(WorkerState::Continue)
                 }
             }
-            Err(e) => {
-                match e {
-                    TryRecvError::Empty => Ok(WorkerState::Empty),
-                    TryRecvError::Disconnected => Ok(WorkerState::Disconnected)
-                }
+            Request::ReadPage { page_index, dst } => {
+                self.context.log.flush().map_err(StatusResult::IoError)?;
+
+                let filename = self.context.find_filename(page_index);
+                let res = match fs::read(filename) {
+                    Ok(bytes) => Ok(bytes),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(StatusResult::End),
+                    Err(e) => Err(StatusResult::IoError(e)),
+                };
+                let _ = dst.send(res);
+                Ok(WorkerState::Continue)
+            }
+            Request::Truncate { page_index, offset, dst } => {
+                let _ = dst.send(self.context.truncate(page_index, offset));
+                Ok(WorkerState::Continue)
+            }
+            Request::Close(dst) => {
+                // keep draining, requests queued before close still written
+                self.closing = Some(dst);
+                Ok(WorkerState::Continue)
+            }
+            Request::Ping(dst) => {
+                let _ = dst.send(());
+                Ok(WorkerState::Continue)
+            }
+            Request::Checkpoint(dst) => {
+                self.checkpointing = Some(dst);
+                Ok(WorkerState::Continue)
+            }
+            Request::Install { snapshot_pages, tail, dst } => {
+                let _ = dst.send(self.context.install(snapshot_pages, tail));
+                Ok(WorkerState::Continue)
             }
         }
     }
@@ -215,7 +394,7 @@ impl DiskLog {
 
 
 struct Context {
-    log: LogFile,
+    log: PageWriter,
     path: String,
 
     // total_page_size is total len of query list per file.LOG  
@@ -225,14 +404,34 @@ struct Context {
     used_page: usize,
 
     // current_page is pointer to current_page
-    current_page_index: usize
+    current_page_index: usize,
+
+    stats: WalStats,
+
+    codec: WalCodec,
+
+    // current page is empty, header of codec written before its first record
+    header_pending: bool,
+
+    // DiskLog::with_archive
+    archive: Option<Arc<Archive>>,
 
+    // bytes of a torn record dropped from end of last page by open
+    torn_tail: u64,
+
+    // writer of pages opened after first one
+    writer: WalWriter,
+
+    // DiskLog::with_rotate_hook
+    on_rotate: Option<RotateHook>,
 }
 impl Context {
 
     pub fn open(path: &str, 
                 table_name: &str, 
-                mut total_page_size: usize) -> Result<Self, LogError> 
+                mut total_page_size: usize,
+                codec: WalCodec,
+                writer: WalWriter) -> Result<Self, String> 
     {
 
         // at-least DEFAULT_PAGE_SIZE Record
@@ -240,11 +439,29 @@ impl Context {
         
 
         let mut slog = open_last_page(path, table_name, total_page_size);
+
+        // read raw, LogFile::open would drop a torn record loader must see
+        let first = fs::read(filename_factory(&slog.path, total_page_size)).map_err(|e| e.to_string())?;
+        let stored = if slog.current_page_index == 1 {
+            stored_codec(&first, codec)?
+        } else {
+            // pages after an empty first one, left by compacting an empty bincode store
+            stored_codec(&first, codec)?.or(Some(WalCodec::Bincode))
+        };
+
+        if let Some(stored) = stored {
+            if stored != codec {
+                return Err(format!("WalCodecMismatch stored {} requested {}", stored.name(), codec.name()));
+            }
+        }
         
         let used_page = used_page(&mut slog.log);
 
+        let filename = filename_factory(&slog.path, total_page_size * slog.current_page_index);
+        let log = PageWriter::new(slog.log, &filename, writer).map_err(|e| e.to_string())?;
+
         Ok(Context{
-            log: slog.log,
+            log,
 
             path: slog.path,
             
@@ -253,13 +470,50 @@ impl Context {
             used_page,
 
             // current_page is pointer to current_page and when move to new page change
-            current_page_index: 1
+            current_page_index: slog.current_page_index,
+
+            stats: WalStats::default(),
+
+            codec,
+
+            header_pending: used_page == 0,
+
+            archive: None,
+
+            torn_tail: slog.torn,
+
+            writer,
+
+            on_rotate: None,
         })
     }
  
 
     #[inline]
     fn write_to_disk(&mut self, bytes: &mut Vec<u8>) -> Result<(), StatusResult> {
+        self.write_header()?;
+
+        let len = bytes.len() as u64;
+        self.append(bytes)?;
+
+        self.stats.records += 1;
+        self.stats.bytes += len;
+        Ok(())
+    }
+
+    /// header of codec, first record of each page so a page alone tell its codec
+    #[inline]
+    fn write_header(&mut self) -> Result<(), StatusResult> {
+        if self.header_pending {
+            let mut header = self.codec.header();
+            self.append(&mut header)?;
+            self.header_pending = false;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn append(&mut self, bytes: &mut Vec<u8>) -> Result<(), StatusResult> {
         let sum = self.used_page + 1;
 
         // if page have free space 
@@ -290,6 +544,7 @@ impl Context {
             // flush to disk because move to next page
             match self.log.flush() {
                 Ok(_) => {
+                    self.seal(sum)?;
 
                     // ----- move to new page -----
                     self.current_page_index += 1;
@@ -298,15 +553,11 @@ impl Context {
                     let curr_filename = filename_factory(&self.path, self.current_page_index * self.total_page_size);
 
                     // create new page
-                    let log = match LogFile::open(&curr_filename) {
-                        Ok(lf) => lf,
-                        Err(err) => {
-                            return Err(StatusResult::LogErr(err))
-                        }
-                    };
+                    let log = self.open_page(&curr_filename)?;
 
                     self.used_page = 0;
                     self.log = log;
+                    self.header_pending = true;
 
                     Ok(())
                 }
@@ -323,6 +574,7 @@ impl Context {
                 // return error
                 return Err(StatusResult::IoError(e));
             }
+            self.seal(self.used_page)?;
 
             // ----- move to new page -----
             self.current_page_index += 1;
@@ -332,31 +584,147 @@ impl Context {
             
             
             // create new page
-            let log = match LogFile::open(&curr_filename) {
-                Ok(lf) => lf,
-                Err(err) => {
-                    return Err(StatusResult::LogErr(err))
-                }
-            };
+            let log = self.open_page(&curr_filename)?;
         
             self.log = log;       
-        
-            // write buffer to page
-            let res = self.log.write(bytes);
-            
-            match res {
-                Ok(_) => {
-                    self.used_page = 1;
-                    Ok(())
-                }
-                Err(err) => {
-                    return Err(StatusResult::IoError(err))
-                }
-            } 
+            self.used_page = 0;
+            self.header_pending = true;
+
+            // write header and buffer to page
+            self.write_header()?;
+            self.append(bytes)
+        }
+    }
+
+
+    /// flush and fsync current page,
+    /// LogFile don't expose its file so sync through a new handle
+    fn sync(&mut self) -> Result<(), StatusResult> {
+        if let Err(e) = self.log.flush() {
+            return Err(StatusResult::IoError(e));
+        }
 
+        let filename = self.find_filename(self.current_page_index);
+        match fs::OpenOptions::new().write(true).open(filename).and_then(|f| f.sync_all()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(StatusResult::IoError(e)),
         }
     }
 
+    /// fsync full page and send it to archive hook, before moving to next page
+    fn seal(&mut self, records: usize) -> Result<(), StatusResult> {
+        if let Some(archive) = &self.archive {
+            let filename = self.find_filename(self.current_page_index);
+            fs::OpenOptions::new().write(true).open(filename).and_then(|f| f.sync_all()).map_err(StatusResult::IoError)?;
+            archive.seal(self.current_page_index);
+        }
+        self.rotated(records);
+        Ok(())
+    }
+
+    // page flushed, before moving to next page
+    fn rotated(&self, records: usize) {
+        if let Some(hook) = &self.on_rotate {
+            let bytes = fs::metadata(self.find_filename(self.current_page_index)).map_or(0, |meta| meta.len());
+            hook(PageRotated { index: self.current_page_index, bytes, records });
+        }
+    }
+
+    /// start a new page unless current one is empty
+    fn checkpoint(&mut self) -> Result<Checkpoint, StatusResult> {
+        if self.used_page > 0 {
+            self.sync()?;
+            if let Some(archive) = &self.archive {
+                archive.seal(self.current_page_index);
+            }
+            self.rotated(self.used_page);
+            self.current_page_index += 1;
+            self.log = self.open_page(&self.find_filename(self.current_page_index))?;
+            self.used_page = 0;
+            self.header_pending = true;
+        }
+
+        Ok(Checkpoint {
+            path: compact_dir(&self.path),
+            total_page_size: self.total_page_size,
+            tail: self.current_page_index,
+            covered: (self.current_page_index > 1).then(|| self.find_filename(self.current_page_index - 1)),
+            header: self.codec.header(),
+            sealed: self.archive.as_ref().map_or(0, |archive| archive.sealed()),
+        })
+    }
+
+    /// Cut page at offset, the start of its first corrupted record, and remove pages after it,
+    /// so appends follow last good record. return pages removed
+    fn truncate(&mut self, page_index: usize, offset: u64) -> Result<usize, StatusResult> {
+        self.log.flush().map_err(StatusResult::IoError)?;
+
+        let mut removed = 0;
+        for index in (page_index + 1..=self.current_page_index).rev() {
+            fs::remove_file(self.find_filename(index)).map_err(StatusResult::IoError)?;
+            removed += 1;
+        }
+
+        let filename = self.find_filename(page_index);
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&filename)
+            .and_then(|f| f.set_len(offset).and_then(|_| f.sync_all()))
+            .map_err(StatusResult::IoError)?;
+        sync_parent(&filename);
+
+        self.current_page_index = page_index;
+        let mut log = LogFile::open(&filename).map_err(StatusResult::LogErr)?;
+        self.used_page = used_page(&mut log);
+        self.log = PageWriter::new(log, &filename, self.writer).map_err(StatusResult::IoError)?;
+
+        // header was first record
+        self.header_pending = self.used_page == 0;
+
+        Ok(removed)
+    }
+
+    /// Link pages from `tail` after the `snapshot_pages` compacted pages
+    /// and swap compacted directory in, return pages before and after.
+    ///
+    /// an interrupted swap is completed or discarded by next open, see `recover_compaction`
+    fn install(&mut self, snapshot_pages: usize, tail: usize) -> Result<(usize, usize), StatusResult> {
+        self.sync()?;
+
+        let compact = compact_dir(&self.path);
+        let before = self.current_page_index;
+        for index in tail..=before {
+            let target = filename_factory(&compact, (snapshot_pages + index - tail + 1) * self.total_page_size);
+            fs::hard_link(self.find_filename(index), target).map_err(StatusResult::IoError)?;
+        }
+
+        let old = old_dir(&self.path);
+        fs::rename(&self.path, &old).map_err(StatusResult::IoError)?;
+        if let Err(e) = fs::rename(&compact, &self.path) {
+            let _ = fs::rename(&old, &self.path);
+            return Err(StatusResult::IoError(e));
+        }
+        sync_parent(&self.path);
+        let _ = fs::remove_dir_all(&old);
+
+        self.current_page_index = snapshot_pages + before - tail + 1;
+        self.log = self.open_page(&self.find_filename(self.current_page_index))?;
+
+        // current page is a tail page, empty if cut by checkpoint
+        self.header_pending = self.used_page == 0;
+
+        if let Some(archive) = &self.archive {
+            archive.install(snapshot_pages, tail);
+        }
+
+        Ok((before, self.current_page_index))
+    }
+
+    /// open page, created with its header if new, for writer of DiskLog
+    fn open_page(&self, filename: &str) -> Result<PageWriter, StatusResult> {
+        let log = LogFile::open(filename).map_err(StatusResult::LogErr)?;
+        PageWriter::new(log, filename, self.writer).map_err(StatusResult::IoError)
+    }
 
     #[inline]
     fn find_filename(&self, page_index: usize) -> String {
@@ -371,14 +739,18 @@ impl Context {
 // -------------------------------------------------
 
 
-use crate::darkbird::{SessionResult, StatusResult};
+use crate::darkbird::{metrics::Metrics, SessionResult, StatusResult};
 
-use std::time::Duration;
-use std::{path::Path, fs};
+use std::time::{Duration, Instant};
+use std::{path::Path, fs, sync::{atomic::{AtomicBool, Ordering}, Arc}};
 
-use simple_wal::{LogFile, LogError};
+use simple_wal::LogFile;
 use tokio::sync::mpsc::error::{TryRecvError, SendTimeoutError};
-use tokio::sync::{oneshot, mpsc};
+use tokio::sync::oneshot;
+
+use crate::darkbird::lanes::{self, LaneReceiver, LaneSender};
+
+use super::{archive::{Archive, ArchiveHook}, codec::WalCodec, frames::{frames, Frame}, writer::{PageWriter, WalWriter}};
 
 
 pub enum WorkerState {
@@ -392,10 +764,99 @@ pub static TIMEOUT: Duration = Duration::from_secs(5);
 
 
 #[inline]
-fn filename_factory(path: &str, page_pointer: usize) -> String {
+pub(super) fn filename_factory(path: &str, page_pointer: usize) -> String {
     format!("{}/page-{}.LOG", &path, page_pointer)
 }
 
+// compacted pages are written beside store directory, then swapped in
+#[inline]
+fn compact_dir(path: &str) -> String {
+    format!("{}.compact", path)
+}
+
+#[inline]
+fn old_dir(path: &str) -> String {
+    format!("{}.old", path)
+}
+
+// make renames of directory durable
+fn sync_parent(path: &str) {
+    if let Some(parent) = Path::new(path).parent() {
+        let _ = fs::File::open(parent).and_then(|dir| dir.sync_all());
+    }
+}
+
+/// Finish or discard a compaction interrupted by a crash.
+///
+/// store directory is renamed to `.old` only once `.compact` is complete,
+/// so `.old` without store directory mean `.compact` must be swapped in,
+/// and `.compact` alone is an unfinished snapshot
+fn recover_compaction(path: &str) {
+    let (compact, old) = (compact_dir(path), old_dir(path));
+
+    if Path::new(&old).is_dir() {
+        if !Path::new(path).is_dir() {
+            if fs::rename(&compact, path).is_err() {
+                // swap couldn't complete, keep pages as they were
+                let _ = fs::rename(&old, path);
+                return;
+            }
+            sync_parent(path);
+        }
+        let _ = fs::remove_dir_all(&old);
+    }
+
+    if Path::new(&compact).is_dir() {
+        let _ = fs::remove_dir_all(&compact);
+    }
+}
+
+/// write compacted records to `checkpoint.path`, return pages written
+pub(crate) fn write_snapshot(checkpoint: &Checkpoint, records: Vec<Vec<u8>>) -> Result<usize, StatusResult> {
+    let _ = fs::remove_dir_all(&checkpoint.path);
+    fs::create_dir(&checkpoint.path).map_err(StatusResult::IoError)?;
+
+    // each page start with header
+    let per_page = checkpoint.total_page_size - 1;
+
+    // an empty store still has its first page
+    let pages = records.len().div_ceil(per_page).max(1);
+    let mut records = records.into_iter();
+
+    for page in 1..=pages {
+        let filename = filename_factory(&checkpoint.path, page * checkpoint.total_page_size);
+        let mut log = LogFile::open(&filename).map_err(StatusResult::LogErr)?;
+        let header = checkpoint.header.clone();
+        for mut record in std::iter::once(header).chain(records.by_ref().take(per_page)) {
+            log.write(&mut record).map_err(StatusResult::IoError)?;
+        }
+        log.flush().map_err(StatusResult::IoError)?;
+        fs::OpenOptions::new().write(true).open(&filename).and_then(|f| f.sync_all()).map_err(StatusResult::IoError)?;
+    }
+
+    Ok(pages)
+}
+
+// codec named by header record of first page, Bincode without header as written before
+// format versions, None if page is empty.
+// a corrupted first record can't tell, requested codec is assumed and left to recovery of loader
+fn stored_codec(first_page: &[u8], requested: WalCodec) -> Result<Option<WalCodec>, String> {
+    match frames(first_page).next() {
+        Some((_, Frame::Record(first))) => WalCodec::of_header(first).unwrap_or(Ok(WalCodec::Bincode)).map(Some),
+        Some(_) => Ok(Some(requested)),
+        None => Ok(None),
+    }
+}
+
+// LogFile::open drop a torn record at end of page, return bytes it dropped
+fn open_trimmed(filename: &str) -> (LogFile, u64) {
+    let before = fs::metadata(filename).map(|m| m.len()).unwrap_or(0);
+    let log = LogFile::open(filename).unwrap();
+    let after = fs::metadata(filename).map(|m| m.len()).unwrap_or(0);
+
+    (log, before.saturating_sub(after))
+}
+
 // if not exist directory then is first time run, create dir and a page-1 and open it
 // else open latest page exist
 fn used_page(log: &mut LogFile) -> usize {
@@ -409,6 +870,8 @@ fn used_page(log: &mut LogFile) -> usize {
 
 fn open_last_page(path: &str, table_name: &str, total_page_size: usize) -> TmpLogStruct {
      let path = format!("{}/{}", path, table_name);
+     recover_compaction(&path);
+
      // if not exist, (First times is started_service)
      if !Path::new(&path).is_dir() {
         let curr_filename = filename_factory(&path, total_page_size);
@@ -416,7 +879,8 @@ fn open_last_page(path: &str, table_name: &str, total_page_size: usize) -> TmpLo
         return TmpLogStruct {
             path: path,
             log: LogFile::open(&curr_filename).unwrap(),
-            current_page_index: 1
+            current_page_index: 1,
+            torn: 0
         }
      }
     else {
@@ -434,18 +898,21 @@ fn open_last_page(path: &str, table_name: &str, total_page_size: usize) -> TmpLo
             } 
             else {
                 // if not exist page_2
+                let (log, torn) = open_trimmed(&latest_page);
                 if page_index == 2 {
                     return TmpLogStruct {
                         path,
-                        log: LogFile::open(latest_page).unwrap(),
-                        current_page_index: 1
+                        log,
+                        current_page_index: 1,
+                        torn
                     }
                 } 
                 else {
                     return TmpLogStruct {
                         path,
-                        log: LogFile::open(latest_page).unwrap(),
-                        current_page_index: (page_index - 1)
+                        log,
+                        current_page_index: (page_index - 1),
+                        torn
                     }
                 }
             }                
@@ -460,23 +927,80 @@ fn open_last_page(path: &str, table_name: &str, total_page_size: usize) -> TmpLo
 // --------------------- Client Code --------------------------
 
 
+#[derive(Clone)]
 pub struct Session {
-    sender: mpsc::Sender<Request>
+    sender: LaneSender<Request>,
+    archive: Option<Arc<Archive>>,
+
+    // time of enqueues, see Storage::metrics
+    metrics: Option<Arc<Metrics>>,
+
+    // appends refused, shared by clones, see Storage::set_read_only
+    read_only: Arc<AtomicBool>,
+
+    #[cfg(feature = "test-util")]
+    faults: Option<crate::darkbird::testing::FaultyWal>
 }
 
 impl Session {
-    fn new(sender: mpsc::Sender<Request>) -> Self {
+    fn new(sender: LaneSender<Request>, archive: Option<Arc<Archive>>) -> Self {
         Session { 
-            sender 
+            sender,
+            archive,
+            metrics: None,
+            read_only: Arc::new(AtomicBool::new(false)),
+
+            #[cfg(feature = "test-util")]
+            faults: None
         }
     }
 
+    /// refuse appends with `SessionResult::ReadOnly`, or accept them again.
+    /// false if already so
+    pub(crate) fn set_read_only(&self, read_only: bool) -> bool {
+        self.read_only.swap(read_only, Ordering::SeqCst) != read_only
+    }
+
+    #[inline]
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    #[cfg(feature = "test-util")]
+    pub(crate) fn with_faults(mut self, faults: Option<crate::darkbird::testing::FaultyWal>) -> Self {
+        self.faults = faults;
+        self
+    }
+
 
 
     /// checkin a resource
     pub async fn log(&self, record: Vec<u8>) -> Result<(), SessionResult> {
+        self.log_keyed(0, record).await
+    }
+
+    /// checkin a resource to lane, records of same lane keep order
+    pub async fn log_keyed(&self, lane: usize, record: Vec<u8>) -> Result<(), SessionResult> {
+        if self.is_read_only() {
+            return Err(SessionResult::ReadOnly);
+        }
+
+        #[cfg(feature = "test-util")]
+        if let Some(faults) = &self.faults {
+            faults.before_append(record.len()).await?;
+        }
+
+        let started = Instant::now();
+        let res = self.sender.send_timeout(lane, Request::Record(record), TIMEOUT).await;
+        if let Some(metrics) = &self.metrics {
+            metrics.enqueued(started);
+        }
 
-        let res = self.sender.send_timeout(Request::Record(record), TIMEOUT).await;
         match res {
             Ok(_) => Ok(()),
             Err(e) => {
@@ -488,6 +1012,199 @@ impl Session {
         }
     }   
 
+    /// checkin many records to lane with a single request, in order,
+    /// return once disk_log wrote and flushed them, with its result.
+    /// records are flushed, not synced: durable like others by `Sync` of page processor.
+    ///
+    /// records are written after every record queued before on any lane, so a record of keys
+    /// of several lanes doesn't pass a write of one of them. caller hold its keys until return
+    pub async fn log_batch_acked(&self, lane: usize, records: Vec<Vec<u8>>) -> Result<(), SessionResult> {
+        if self.is_read_only() {
+            return Err(SessionResult::ReadOnly);
+        }
+
+        #[cfg(feature = "test-util")]
+        if let Some(faults) = &self.faults {
+            faults.before_append(records.iter().map(|r| r.len()).sum()).await?;
+        }
+
+        let (ask, resp) = oneshot::channel();
+        let started = Instant::now();
+        let res = self.sender.send_timeout(lane, Request::Acked(records, ask), TIMEOUT).await;
+        if let Some(metrics) = &self.metrics {
+            metrics.enqueued(started);
+        }
+
+        match res {
+            Ok(_) => {}
+            Err(SendTimeoutError::Timeout(_)) => return Err(SessionResult::Timeout),
+            Err(SendTimeoutError::Closed(_)) => return Err(SessionResult::Closed),
+        }
+
+        let written = match resp.await {
+            Ok(res) => res.map_err(SessionResult::Err),
+            Err(_) => Err(SessionResult::NoResponse),
+        };
+
+        #[cfg(feature = "test-util")]
+        if let (Ok(_), Some(faults)) = (&written, &self.faults) {
+            faults.after_write()?;
+        }
+
+        written
+    }
+
+    /// drain queued records, fsync and stop disk_log,
+    /// page file released before reply
+    pub async fn close(&self) -> Result<WalStats, SessionResult> {
+        let (ask, resp) = oneshot::channel();
+
+        match self.sender.send_timeout(0, Request::Close(ask), TIMEOUT).await {
+            Err(SendTimeoutError::Closed(_)) => return Err(SessionResult::Closed),
+            Err(SendTimeoutError::Timeout(_)) => return Err(SessionResult::Timeout),
+            Ok(_) => {}
+        }
+
+        let closed = match resp.await {
+            Ok(Ok(stats)) => Ok(stats),
+            Ok(Err(e)) => Err(SessionResult::Err(e)),
+            Err(_) => Err(SessionResult::NoResponse),
+        };
+
+        #[cfg(feature = "test-util")]
+        if let (Ok(_), Some(faults)) = (&closed, &self.faults) {
+            faults.on_close();
+        }
+
+        closed
+    }
+
+    /// close without a runtime, records written before, Timeout if not closed by deadline
+    pub fn close_blocking(&self, records: Vec<Vec<u8>>, timeout: Duration) -> Result<WalStats, SessionResult> {
+        let deadline = std::time::Instant::now() + timeout;
+
+        let sent = |res| match res {
+            Err(SendTimeoutError::Closed(_)) => Err(SessionResult::Closed),
+            Err(SendTimeoutError::Timeout(_)) => Err(SessionResult::Timeout),
+            Ok(_) => Ok(()),
+        };
+
+        if !records.is_empty() {
+            sent(self.sender.send_deadline(0, Request::Records(records), deadline))?;
+        }
+
+        let (ask, mut resp) = oneshot::channel();
+        sent(self.sender.send_deadline(0, Request::Close(ask), deadline))?;
+
+        loop {
+            match resp.try_recv() {
+                Ok(Ok(stats)) => return Ok(stats),
+                Ok(Err(e)) => return Err(SessionResult::Err(e)),
+                Err(oneshot::error::TryRecvError::Closed) => return Err(SessionResult::NoResponse),
+                Err(oneshot::error::TryRecvError::Empty) => {
+                    if std::time::Instant::now() >= deadline {
+                        return Err(SessionResult::Timeout);
+                    }
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }
+    }
+
+    /// round trip to disk_log worker, Err if it stopped or doesn't answer in time
+    pub async fn ping(&self) -> Result<(), SessionResult> {
+        let (ask, resp) = oneshot::channel();
+
+        match self.sender.send_timeout(0, Request::Ping(ask), TIMEOUT).await {
+            Err(SendTimeoutError::Closed(_)) => return Err(SessionResult::Closed),
+            Err(SendTimeoutError::Timeout(_)) => return Err(SessionResult::Timeout),
+            Ok(_) => {}
+        }
+
+        match tokio::time::timeout(TIMEOUT, resp).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_)) => Err(SessionResult::NoResponse),
+            Err(_) => Err(SessionResult::Timeout),
+        }
+    }
+
+    /// Cut WAL once records queued before are written, see `Checkpoint`.
+    /// caller must keep new records from being queued until it return
+    pub async fn checkpoint(&self) -> Result<Checkpoint, SessionResult> {
+        let (ask, resp) = oneshot::channel();
+
+        match self.sender.send_timeout(0, Request::Checkpoint(ask), TIMEOUT).await {
+            Err(SendTimeoutError::Closed(_)) => return Err(SessionResult::Closed),
+            Err(SendTimeoutError::Timeout(_)) => return Err(SessionResult::Timeout),
+            Ok(_) => {}
+        }
+
+        match resp.await {
+            Ok(res) => res.map_err(SessionResult::Err),
+            Err(_) => Err(SessionResult::NoResponse),
+        }
+    }
+
+    /// wait until pages sealed up to seq are acknowledged by archive hook,
+    /// at most its grace, see `ArchiveHook`
+    pub async fn archived(&self, seq: u64) -> Result<(), SessionResult> {
+        match &self.archive {
+            Some(archive) => archive.wait(seq).await,
+            None => Ok(()),
+        }
+    }
+
+    /// replace pages before `tail` by `snapshot_pages` compacted pages written by `write_snapshot`,
+    /// return pages before and after
+    pub async fn install(&self, snapshot_pages: usize, tail: usize) -> Result<(usize, usize), SessionResult> {
+        let (ask, resp) = oneshot::channel();
+
+        let req = Request::Install { snapshot_pages, tail, dst: ask };
+        match self.sender.send_timeout(0, req, TIMEOUT).await {
+            Err(SendTimeoutError::Closed(_)) => return Err(SessionResult::Closed),
+            Err(SendTimeoutError::Timeout(_)) => return Err(SessionResult::Timeout),
+            Ok(_) => {}
+        }
+
+        match resp.await {
+            Ok(res) => res.map_err(SessionResult::Err),
+            Err(_) => Err(SessionResult::NoResponse),
+        }
+    }
+
+    /// bytes of page, `StatusResult::End` past last page
+    pub async fn read_page(&self, index: usize) -> Result<Vec<u8>, SessionResult> {
+        let (ask, resp) = oneshot::channel();
+
+        match self.sender.send_timeout(0, Request::ReadPage { page_index: index, dst: ask }, TIMEOUT).await {
+            Err(SendTimeoutError::Closed(_)) => return Err(SessionResult::Closed),
+            Err(SendTimeoutError::Timeout(_)) => return Err(SessionResult::Timeout),
+            Ok(_) => {}
+        }
+
+        match resp.await {
+            Ok(res) => res.map_err(SessionResult::Err),
+            Err(_) => Err(SessionResult::NoResponse),
+        }
+    }
+
+    /// cut page at offset and remove pages after it, return pages removed
+    pub async fn truncate(&self, index: usize, offset: u64) -> Result<usize, SessionResult> {
+        let (ask, resp) = oneshot::channel();
+
+        let req = Request::Truncate { page_index: index, offset, dst: ask };
+        match self.sender.send_timeout(0, req, TIMEOUT).await {
+            Err(SendTimeoutError::Closed(_)) => return Err(SessionResult::Closed),
+            Err(SendTimeoutError::Timeout(_)) => return Err(SessionResult::Timeout),
+            Ok(_) => {}
+        }
+
+        match resp.await {
+            Ok(res) => res.map_err(SessionResult::Err),
+            Err(_) => Err(SessionResult::NoResponse),
+        }
+    }
+
     /// checkout a resource
     pub async fn get_page(&self, index: usize) -> Result<LogFile, SessionResult> {
         
@@ -499,7 +1216,7 @@ impl Session {
         
         
         // send request with timeout (5 seconds)
-        let res = self.sender.send_timeout(req, TIMEOUT).await;
+        let res = self.sender.send_timeout(0, req, TIMEOUT).await;
            
         match res {
             // Closed
@@ -0,0 +1,60 @@
+use std::convert::TryInto;
+
+
+
+// simple_wal page: first index, then per record its length, data and crc32, little-endian
+const PAGE_HEADER: usize = 8;
+const LENGTH: usize = 8;
+const CRC: usize = 4;
+
+
+/// record of a page read raw, `LogFile::open` would drop a torn record silently
+pub(crate) enum Frame<'a> {
+    Record(&'a [u8]),
+
+    // data doesn't match its crc, next frame is read after it
+    BadChecksum,
+
+    // length run past end of page, rest of page is unreadable
+    Torn,
+}
+
+
+/// frames of page bytes with their offset in page, end after a torn one
+pub(crate) struct Frames<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+pub(crate) fn frames(bytes: &[u8]) -> Frames<'_> {
+    Frames { bytes, pos: PAGE_HEADER }
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = (u64, Frame<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.pos;
+        let rest = self.bytes.get(offset..).filter(|rest| !rest.is_empty())?;
+
+        let len = rest.get(..LENGTH).map(|len| u64::from_le_bytes(len.try_into().unwrap()) as usize);
+        let frame = len.and_then(|len| rest.get(LENGTH..len.checked_add(LENGTH + CRC)?));
+
+        let frame = match frame {
+            Some(frame) => frame,
+            None => {
+                self.pos = self.bytes.len();
+                return Some((offset as u64, Frame::Torn));
+            }
+        };
+
+        self.pos += LENGTH + frame.len();
+
+        let (data, crc) = frame.split_at(frame.len() - CRC);
+        if crc32fast::hash(data) != u32::from_le_bytes(crc.try_into().unwrap()) {
+            return Some((offset as u64, Frame::BadChecksum));
+        }
+
+        Some((offset as u64, Frame::Record(data)))
+    }
+}
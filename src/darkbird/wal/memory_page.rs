@@ -7,7 +7,14 @@ use crate::RQuery;
 
 
 pub struct MemoryPage<K: Eq + PartialEq + Hash, Doc> {
-    mapper: HashMap<(&'static str, K), (Instant, Option<Doc>)>
+    mapper: HashMap<(&'static str, K), (Instant, Option<Doc>)>,
+
+    // RQuery::Clear stashed, written ahead of queries stashed after it
+    cleared: Option<Instant>,
+
+    // last RQuery::TagAdd (true) or TagRemove of key and tag, written after documents
+    // as edits are kept over writes of key. a remove of key drop those stashed before
+    tags: HashMap<(K, String), (Instant, bool)>,
 }
 
 impl<K, Doc> MemoryPage<K, Doc>  
@@ -16,10 +23,30 @@ where
 {
     
     pub fn new() -> Self {
-        MemoryPage { mapper: HashMap::new() }
+        MemoryPage { mapper: HashMap::new(), cleared: None, tags: HashMap::new() }
     }
 
     pub fn stash(&mut self, rquery: RQuery<K, Doc>)  {
+        match rquery {
+            // drop everything stashed before, clear of pages before this one is kept
+            RQuery::Clear => {
+                self.mapper.clear();
+                self.tags.clear();
+                self.cleared = Some(Instant::now());
+                return;
+            }
+            RQuery::TagAdd(key, tag) => {
+                self.tags.insert((key, tag), (Instant::now(), true));
+                return;
+            }
+            RQuery::TagRemove(key, tag) => {
+                self.tags.insert((key, tag), (Instant::now(), false));
+                return;
+            }
+            RQuery::Remove(ref key) => self.tags.retain(|(k, _), _| k != key),
+            _ => {}
+        }
+
         let (type_id, key, doc) = rquery.into_raw();
         let time = Instant::now();
         self.mapper.insert((type_id, key), (time, doc));
@@ -27,12 +54,24 @@ where
 
 
     pub fn get_page(self) -> Vec<(Instant, RQuery<K, Doc>)> {
-        let mut result = Vec::with_capacity(self.mapper.len());
+        let mut result = Vec::with_capacity(self.mapper.len() + self.tags.len() + 1);
+        if let Some(instant) = self.cleared {
+            result.push((instant, RQuery::Clear));
+        }
         for ((type_id, key), (instant, doc)) in self.mapper {
             result.push((instant, RQuery::from_raw(type_id, key, doc)));
         }
 
-        result.sort_by(|(a, _), (b, _)| a.cmp(b));
+        result.sort_by_key(|(instant, _)| *instant);
+
+        let mut tags: Vec<_> = self.tags.into_iter().collect();
+        tags.sort_by_key(|(_, (instant, _))| *instant);
+        for ((key, tag), (instant, add)) in tags {
+            match add {
+                true => result.push((instant, RQuery::TagAdd(key, tag))),
+                false => result.push((instant, RQuery::TagRemove(key, tag))),
+            }
+        }
         result
     } 
 }
\ No newline at end of file
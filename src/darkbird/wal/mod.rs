@@ -1,4 +1,11 @@
+pub mod archive;
+pub mod codec;
+pub(crate) mod frames;
 pub mod disk_log;
 pub mod page_processor;
 pub mod memory_page;
 pub mod helper;
+pub mod writer;
+
+#[cfg(all(target_os = "linux", feature = "uring"))]
+pub(crate) mod uring;
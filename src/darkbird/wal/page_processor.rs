@@ -1,11 +1,12 @@
-use std::{path::Path, fs, marker::PhantomData};
+use std::{path::Path, fs, marker::PhantomData, time::SystemTime};
 use std::hash::Hash;
 
 use serde::{Serialize, de::DeserializeOwned};
 use simple_wal::LogFile;
 
-use crate::RQuery;
+use crate::{RQuery, darkbird::{expiry::unix_ms, storage::LogRecord}};
 
+use super::codec::{Codec, WalCodec};
 use super::disk_log::DEFAULT_PAGE_SIZE;
 use super::memory_page::MemoryPage;
 
@@ -124,6 +125,10 @@ where
         // page_index
         let mut page_index = 1;
 
+        // codec of source records, named by header record of each page,
+        // sync pages are written with same codec
+        let mut codec = WalCodec::Bincode;
+
         loop {
 
             let page_pointer = total_page_size * page_index;
@@ -209,10 +214,26 @@ where
                         };
                         return Err(Recovery::Recoverable(meta))
                     }
-                    Ok(raw_qline) => {
+                    Ok(mut raw_qline) => {
+
+                        // header is copied as is
+                        if let Some(stored) = WalCodec::of_header(&raw_qline) {
+                            let res = stored.map(|stored| codec = stored)
+                                .and_then(|_| sync_page.write(&mut raw_qline).map_err(|e| e.to_string()));
+
+                            if let Err(err) = res {
+                                let meta = Metadata {
+                                    original_filename: source_page_name.to_owned(),
+                                    currepted_filename: source_name.to_owned(),
+                                    err,
+                                };
+                                return Err(Recovery::Recoverable(meta))
+                            }
+                            continue;
+                        }
 
-                        // Deserialize rquery
-                        let old_query: RQuery<OldKey, OldDoc> = match bincode::deserialize(&raw_qline) {
+                        // Deserialize record, a transaction is transformed query by query
+                        let old_record: LogRecord<OldKey, OldDoc> = match codec.decode(&raw_qline) {
                             Ok(res) => res,
                             Err(e) => {
                                 let meta = Metadata {
@@ -224,28 +245,69 @@ where
                             }
                         };
 
+                        // applied to a document of old type, which handler can't transform
+                        if matches!(old_record, LogRecord::Patch(..)) {
+                            return Err(Recovery::UnRecoverable(format!("{}: patch record, compact store before migration", source_page_name)))
+                        }
+
+                        let transaction = matches!(old_record, LogRecord::Transaction(_));
+                        let batch = matches!(old_record, LogRecord::InsertBatch(_));
+                        let expires_at = old_record.expires_at();
+
                         // transform
-                        let new_query = (&self.handler)(old_query);
+                        let new_queries: Vec<RQuery<NewKey, NewDoc>> = old_record
+                            .into_queries()
+                            .into_iter()
+                            .map(|old_query| (self.handler)(old_query))
+                            .collect();
 
 
                         if self.vacuum {
 
-                           memory_page.stash(new_query);
+                           // ttl isn't kept by a vacuum, a document already expired is dropped
+                           let expired = expires_at.is_some_and(|at| at <= unix_ms(SystemTime::now()));
+                           for new_query in new_queries {
+                               match new_query {
+                                   RQuery::Insert(key, _) if expired => memory_page.stash(RQuery::Remove(key)),
+                                   new_query => memory_page.stash(new_query),
+                               }
+                           }
 
                         } else {
 
-                            // serialize
-                            let mut bytes = bincode::serialize(&new_query).unwrap();
+                            // serialize, a transaction stay one record,
+                            // a batch too unless handler turned an insert into a remove
+                            let mut records = vec![];
+                            if transaction {
+                                records.push(codec.encode(&LogRecord::Transaction(new_queries)));
+                            } else if batch && new_queries.iter().all(|q| matches!(q, RQuery::Insert(..))) {
+                                let docs: Vec<_> = new_queries
+                                    .into_iter()
+                                    .filter_map(|q| match q {
+                                        RQuery::Insert(key, doc) => Some((key, doc)),
+                                        RQuery::Remove(_) | RQuery::Update(..) | RQuery::Patch(..) | RQuery::Clear | RQuery::TagAdd(..) | RQuery::TagRemove(..) => None,
+                                    })
+                                    .collect();
+                                records.push(codec.encode(&LogRecord::InsertBatch(docs)));
+                            } else if let (Some(at), [RQuery::Insert(key, doc)]) = (expires_at, new_queries.as_slice()) {
+                                records.push(codec.encode(&LogRecord::InsertWithExpiry(key, doc, at)));
+                            } else {
+                                for new_query in new_queries {
+                                    records.push(new_query.to_record(codec));
+                                }
+                            }
 
 
                             // write to sync
-                            if let Err(e) = sync_page.write(&mut bytes) {
-                                let meta = Metadata {
-                                    original_filename: source_page_name.to_owned(),
-                                    currepted_filename: source_name.to_owned(),
-                                    err: e.to_string(),
-                                };
-                                return Err(Recovery::Recoverable(meta))
+                            for mut bytes in records {
+                                if let Err(e) = sync_page.write(&mut bytes) {
+                                    let meta = Metadata {
+                                        original_filename: source_page_name.to_owned(),
+                                        currepted_filename: source_name.to_owned(),
+                                        err: e.to_string(),
+                                    };
+                                    return Err(Recovery::Recoverable(meta))
+                                }
                             }
                         }
                     }
@@ -256,7 +318,7 @@ where
                 for (_, rquery) in memory_page.get_page().into_iter() {
                     
                     // serialize
-                    let mut bytes = bincode::serialize(&rquery).unwrap();
+                    let mut bytes = rquery.to_record(codec);
 
                     // write to sync
                     if let Err(e) = sync_page.write(&mut bytes) {
@@ -0,0 +1,297 @@
+use io_uring::{opcode, types, IoUring};
+use std::{
+    alloc::{self, Layout},
+    fs::{File, OpenOptions},
+    io,
+    os::unix::{
+        fs::{FileExt, OpenOptionsExt},
+        io::AsRawFd,
+    },
+    ptr::NonNull,
+};
+
+
+
+// O_DIRECT offset, length and memory of a write are aligned to it,
+// covers logical blocks of 512 and 4096
+const BLOCK: usize = 4096;
+
+// records queued beyond it are written before drain end
+const FLUSH_AT: usize = 1 << 20;
+
+// largest write submitted at once
+const CHUNK: usize = 1 << 30;
+
+// reserved past end of page by preallocate, at once
+const PREALLOCATE: u64 = 4 << 20;
+
+// fill of last block after end of page under O_DIRECT, cut once written.
+// read as length of a record it run past end of page, so left by a crash it is a torn record
+const PAD: u8 = 0x7f;
+
+
+/// Current page of `WalWriter::Uring`.
+///
+/// records are queued in memory as simple_wal frames and written by `flush`,
+/// which DiskLog call once requests are drained, so a burst of records is one submission.
+/// page is locked as LogFile lock it
+pub(crate) struct UringPage {
+    ring: IoUring,
+    file: File,
+    direct_io: bool,
+    preallocate: bool,
+
+    // offset of buf in page, aligned to BLOCK under O_DIRECT
+    offset: u64,
+
+    // frames not yet written, under O_DIRECT after bytes of last block already written
+    buf: Aligned,
+
+    // end of page on disk
+    written: u64,
+
+    // end of blocks reserved by preallocate
+    reserved: u64,
+}
+
+impl UringPage {
+    pub fn open(filename: &str, direct_io: bool, preallocate: bool) -> io::Result<UringPage> {
+        let mut options = OpenOptions::new();
+        options.write(true);
+        if direct_io {
+            options.custom_flags(libc::O_DIRECT);
+        }
+        let file = options.open(filename)?;
+
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let written = file.metadata()?.len();
+        let mut buf = Aligned::new();
+
+        // last block is written again with records after it
+        let offset = if direct_io {
+            let offset = written - written % BLOCK as u64;
+            let mut tail = vec![0; (written - offset) as usize];
+            File::open(filename)?.read_exact_at(&mut tail, offset)?;
+            buf.extend(&tail);
+            offset
+        } else {
+            written
+        };
+
+        Ok(UringPage {
+            ring: IoUring::new(8)?,
+            file,
+            direct_io,
+            preallocate,
+            offset,
+            buf,
+            written,
+            reserved: written,
+        })
+    }
+
+    #[inline]
+    pub fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.buf.extend(&(bytes.len() as u64).to_le_bytes());
+        self.buf.extend(bytes);
+        self.buf.extend(&crc32fast::hash(bytes).to_le_bytes());
+
+        if self.buf.len() >= FLUSH_AT {
+            return self.flush();
+        }
+        Ok(())
+    }
+
+    /// write queued frames, dropped like a failed LogFile::write if writing fail
+    pub fn flush(&mut self) -> io::Result<()> {
+        let end = self.offset + self.buf.len() as u64;
+        if end == self.written {
+            return Ok(());
+        }
+
+        if self.preallocate && end > self.reserved {
+            self.reserve(end);
+        }
+
+        if let Err(e) = self.write_buf(end) {
+            let _ = self.file.set_len(self.written);
+            self.buf.truncate((self.written - self.offset) as usize);
+            return Err(e);
+        }
+        self.written = end;
+
+        // keep last block, appended to by next records
+        let offset = if self.direct_io { end - end % BLOCK as u64 } else { end };
+        self.buf.consume((offset - self.offset) as usize);
+        self.offset = offset;
+
+        Ok(())
+    }
+
+    fn write_buf(&mut self, end: u64) -> io::Result<()> {
+        let len = self.buf.len();
+        if self.direct_io {
+            self.buf.pad(BLOCK, PAD);
+        }
+
+        let res = self.submit();
+        self.buf.truncate(len);
+        res?;
+
+        // cut padding of last block
+        if self.direct_io && end % BLOCK as u64 != 0 {
+            self.file.set_len(end)?;
+        }
+        Ok(())
+    }
+
+    fn submit(&mut self) -> io::Result<()> {
+        let fd = types::Fd(self.file.as_raw_fd());
+        let bytes = self.buf.as_slice();
+
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let len = (bytes.len() - pos).min(CHUNK);
+            let write = opcode::Write::new(fd, bytes[pos..].as_ptr(), len as u32)
+                .offset(self.offset + pos as u64)
+                .build();
+
+            // buffer outlive the write, waited for below
+            unsafe {
+                self.ring
+                    .submission()
+                    .push(&write)
+                    .map_err(|_| io::Error::other("io_uring submission queue full"))?;
+            }
+            self.ring.submit_and_wait(1)?;
+
+            let written = match self.ring.completion().next() {
+                Some(cqe) => cqe.result(),
+                None => return Err(io::Error::other("io_uring completion missing")),
+            };
+            if written < 0 {
+                return Err(io::Error::from_raw_os_error(-written));
+            }
+            if written == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            pos += written as usize;
+        }
+        Ok(())
+    }
+
+    // size of page isn't changed, so loader and readers see its records only
+    fn reserve(&mut self, end: u64) {
+        let reserved = end + PREALLOCATE;
+        let res = unsafe {
+            libc::fallocate(
+                self.file.as_raw_fd(),
+                libc::FALLOC_FL_KEEP_SIZE,
+                self.reserved as libc::off_t,
+                (reserved - self.reserved) as libc::off_t,
+            )
+        };
+
+        // file system without fallocate, written as without preallocate
+        if res != 0 {
+            self.preallocate = false;
+            return;
+        }
+        self.reserved = reserved;
+    }
+}
+
+impl Drop for UringPage {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            eprintln!("{:?}", e);
+        }
+    }
+}
+
+
+
+// growable buffer aligned to BLOCK, O_DIRECT require it of memory written
+struct Aligned {
+    ptr: NonNull<u8>,
+    len: usize,
+    cap: usize,
+}
+
+// owns its memory like a Vec<u8>
+unsafe impl Send for Aligned {}
+
+impl Aligned {
+    fn new() -> Self {
+        let cap = FLUSH_AT + BLOCK;
+        let ptr = unsafe { alloc::alloc(Self::layout(cap)) };
+        Aligned {
+            ptr: NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(Self::layout(cap))),
+            len: 0,
+            cap,
+        }
+    }
+
+    #[inline]
+    fn layout(cap: usize) -> Layout {
+        Layout::from_size_align(cap, BLOCK).unwrap()
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        let needed = self.len + additional;
+        if needed <= self.cap {
+            return;
+        }
+
+        let cap = needed.max(self.cap * 2).next_multiple_of(BLOCK);
+        let ptr = unsafe { alloc::realloc(self.ptr.as_ptr(), Self::layout(self.cap), cap) };
+        self.ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(Self::layout(cap)));
+        self.cap = cap;
+    }
+
+    #[inline]
+    fn extend(&mut self, bytes: &[u8]) {
+        self.reserve(bytes.len());
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), self.ptr.as_ptr().add(self.len), bytes.len()) };
+        self.len += bytes.len();
+    }
+
+    /// fill up to a multiple of block
+    fn pad(&mut self, block: usize, byte: u8) {
+        let len = self.len.next_multiple_of(block);
+        self.reserve(len - self.len);
+        unsafe { std::ptr::write_bytes(self.ptr.as_ptr().add(self.len), byte, len - self.len) };
+        self.len = len;
+    }
+
+    #[inline]
+    fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// drop first n bytes
+    fn consume(&mut self, n: usize) {
+        let n = n.min(self.len);
+        unsafe { std::ptr::copy(self.ptr.as_ptr().add(n), self.ptr.as_ptr(), self.len - n) };
+        self.len -= n;
+    }
+}
+
+impl Drop for Aligned {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), Self::layout(self.cap)) };
+    }
+}
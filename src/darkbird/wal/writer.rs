@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use simple_wal::LogFile;
+use std::io;
+
+
+
+/// How DiskLog write pages, set by `Options::with_wal_writer`.
+///
+/// either way pages are written by thread of DiskLog, never by a tokio worker,
+/// and hold same bytes: a WAL written by one is opened by other
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WalWriter {
+    // a write per record through simple_wal
+    #[default]
+    Thread,
+
+    // records of a drain written by one io_uring submission (uring feature, linux).
+    // direct_io open pages with O_DIRECT, preallocate reserve blocks of page ahead of writes
+    #[cfg(all(target_os = "linux", feature = "uring"))]
+    Uring { direct_io: bool, preallocate: bool },
+}
+
+
+/// current page of DiskLog
+pub(super) enum PageWriter {
+    Log(LogFile),
+
+    #[cfg(all(target_os = "linux", feature = "uring"))]
+    Uring(Box<super::uring::UringPage>),
+}
+
+impl PageWriter {
+    /// writer of page log opened, so a new page has its header and a torn record is trimmed
+    #[cfg_attr(not(all(target_os = "linux", feature = "uring")), allow(unused_variables))]
+    pub fn new(log: LogFile, filename: &str, writer: WalWriter) -> io::Result<PageWriter> {
+        match writer {
+            WalWriter::Thread => Ok(PageWriter::Log(log)),
+
+            #[cfg(all(target_os = "linux", feature = "uring"))]
+            WalWriter::Uring { direct_io, preallocate } => {
+                // release lock of log, taken again by page
+                drop(log);
+                super::uring::UringPage::open(filename, direct_io, preallocate).map(|page| PageWriter::Uring(Box::new(page)))
+            }
+        }
+    }
+
+    #[inline]
+    pub fn write(&mut self, bytes: &mut Vec<u8>) -> io::Result<()> {
+        match self {
+            PageWriter::Log(log) => log.write(bytes),
+
+            #[cfg(all(target_os = "linux", feature = "uring"))]
+            PageWriter::Uring(page) => page.write(bytes),
+        }
+    }
+
+    /// records written before are on page once returned
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self {
+            PageWriter::Log(log) => log.flush(),
+
+            #[cfg(all(target_os = "linux", feature = "uring"))]
+            PageWriter::Uring(page) => page.flush(),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use dashmap::{mapref::{entry::Entry, one::Ref}, DashMap};
+use std::{hash::Hash, sync::Arc};
+use tokio::sync::watch;
+
+
+
+type Senders<K, Doc> = DashMap<K, Arc<watch::Sender<Option<Doc>>>>;
+
+
+/// Channels of `Storage::watch`, one per watched key.
+///
+/// a key is added by first `watch` of it, writes of keys no one watch touch nothing,
+/// and it is removed once its last receiver is dropped
+pub(crate) struct Watchers<K, Doc> {
+    senders: Arc<Senders<K, Doc>>,
+}
+
+impl<K: Eq + Hash, Doc> Watchers<K, Doc> {
+    pub fn new() -> Self {
+        Watchers { senders: Arc::new(DashMap::new()) }
+    }
+
+    /// sender of key if watched, held until write is in memory then sent to
+    #[inline]
+    pub fn sender(&self, key: &K) -> Option<Ref<'_, K, Arc<watch::Sender<Option<Doc>>>>> {
+        self.senders.get(key)
+    }
+}
+
+impl<K, Doc> Watchers<K, Doc>
+where
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+    Doc: Send + Sync + 'static,
+{
+    /// receiver of key, a new channel start with current.
+    /// current is read while key is locked, so a write held by `sender` isn't missed
+    pub fn watch(&self, key: K, current: impl FnOnce() -> Option<Doc>) -> watch::Receiver<Option<Doc>> {
+        match self.senders.entry(key.clone()) {
+            Entry::Occupied(entry) => entry.get().subscribe(),
+            Entry::Vacant(entry) => {
+                let (sender, receiver) = watch::channel(current());
+                let sender = entry.insert(Arc::new(sender)).clone();
+
+                let senders = self.senders.clone();
+                tokio::spawn(async move {
+                    loop {
+                        sender.closed().await;
+
+                        // watched again meanwhile, wait for new receivers to drop
+                        if senders.remove_if(&key, |_, sender| sender.receiver_count() == 0).is_some() {
+                            break;
+                        }
+                    }
+                });
+
+                receiver
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Stable wire representation of reporter events for non-Rust consumers.
+//!
+//! JSON codec, one object per event, `v` is `WIRE_VERSION`.
+//! fields are only added in a version, never renamed, removed or retyped,
+//! consumers must ignore unknown fields and unknown `type` values.
+//!
+//! ```text
+//! change:     {"v":1,"type":"change","op":"insert"|"update"|"patch"|"remove","key":<key>,
+//!              "before":null,"after":<doc>|null,"seq":null,"ts":null}
+//!              (patch add "patch":<merge patch>|null)
+//! lagging:    {"v":1,"type":"lagging","subscriber":<u64>,"sent":<u64>,
+//!              "occupancy":<u64>,"capacity":<u64>,"blocked_ms":<u64>}
+//! subscribed: {"v":1,"type":"subscribed"}
+//! subscriber_dropped: {"v":1,"type":"subscriber_dropped","subscriber":<u64>}
+//! timer:      {"v":1,"type":"timer","key":<key>}
+//! bulk_remove: {"v":1,"type":"bulk_remove","keys":[<key>, ...]}
+//! expired:    {"v":1,"type":"expired","key":<key>}
+//! quarantined: {"v":1,"type":"quarantined","key":<key>}
+//! clear:      {"v":1,"type":"clear"}
+//! tag:        {"v":1,"type":"tag","op":"add"|"remove","key":<key>,"tag":<string>}
+//! compacting: {"v":1,"type":"compacting","phase":"started"|"copied"|"finished",
+//!              "records":<u64>,"pages_before":<u64>,"pages_after":<u64>,"pause_ms":<u64>}
+//!              (records from copied, the rest on finished only)
+//! ```
+//!
+//! `key`, `before` and `after` are serde JSON of K and Doc, objects with sorted fields.
+//! `after` is null for remove. `before` is previous document of `Event::Removed` and
+//! `Event::Replaced` (`Options::with_previous_values`), null otherwise.
+//! `update` is a new version of a held document (`Storage::update`, or any overwrite
+//! with previous values), an `insert` may overwrite too. `patch` is an update by `Storage::patch`,
+//! `after` the document it produced and `patch` its merge patch, null for a custom patch.
+//! consumers should read an unknown `op` like `insert`.
+//! `seq` and `ts` are reserved: the store doesn't track sequence or commit time yet,
+//! they are always null in version 1 and become non-null without a version bump.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::{patch::DocPatch, router::SubscriberInfo, storage::{CompactPhase, Event, RQuery}};
+
+
+
+pub const WIRE_VERSION: u32 = 1;
+
+
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WireCodec {
+    Json,
+}
+
+
+impl<K, Doc> Event<K, Doc>
+where
+    K: Serialize,
+    Doc: Serialize,
+{
+    /// encode event by schema of `WIRE_VERSION`
+    pub fn to_wire(&self, codec: WireCodec) -> Vec<u8> {
+        match codec {
+            WireCodec::Json => serde_json::to_vec(&self.to_value()).unwrap(),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            Event::Query(RQuery::Insert(key, doc)) => change("insert", to_value(key), Value::Null, to_value(doc)),
+            Event::Query(RQuery::Remove(key)) => change("remove", to_value(key), Value::Null, Value::Null),
+            Event::Query(RQuery::Update(key, doc)) => change("update", to_value(key), Value::Null, to_value(doc)),
+            Event::Query(RQuery::Clear) => json!({ "v": WIRE_VERSION, "type": "clear" }),
+            Event::Query(RQuery::TagAdd(key, tag)) => tag_change("add", to_value(key), tag),
+            Event::Query(RQuery::TagRemove(key, tag)) => tag_change("remove", to_value(key), tag),
+            Event::Query(RQuery::Patch(key, patch, doc)) => {
+                let mut value = change("patch", to_value(key), Value::Null, to_value(doc));
+                value["patch"] = match patch {
+                    DocPatch::Merge(patch) => patch.clone(),
+                    DocPatch::Custom(_) => Value::Null,
+                };
+                value
+            }
+            Event::Replaced(key, old, doc) => change("update", to_value(key), to_value(old), to_value(doc)),
+            Event::Removed(key, old) => change("remove", to_value(key), to_value(old), Value::Null),
+            Event::Lagging(info) => lagging(info),
+            Event::Timer(key) => json!({ "v": WIRE_VERSION, "type": "timer", "key": to_value(key) }),
+            Event::BulkRemove(keys) => json!({ "v": WIRE_VERSION, "type": "bulk_remove", "keys": to_value(keys) }),
+            Event::Expired(key) => json!({ "v": WIRE_VERSION, "type": "expired", "key": to_value(key) }),
+            Event::Quarantined(key) => json!({ "v": WIRE_VERSION, "type": "quarantined", "key": to_value(key) }),
+            Event::Compacting(phase) => compacting(phase),
+            Event::Subscribed(_) => json!({ "v": WIRE_VERSION, "type": "subscribed" }),
+            Event::SubscriberDropped(id) => json!({ "v": WIRE_VERSION, "type": "subscriber_dropped", "subscriber": id.0 }),
+        }
+    }
+}
+
+
+fn change(op: &str, key: Value, before: Value, after: Value) -> Value {
+    json!({
+        "v": WIRE_VERSION,
+        "type": "change",
+        "op": op,
+        "key": key,
+        "before": before,
+        "after": after,
+        "seq": Value::Null,
+        "ts": Value::Null,
+    })
+}
+
+fn tag_change(op: &str, key: Value, tag: &str) -> Value {
+    json!({ "v": WIRE_VERSION, "type": "tag", "op": op, "key": key, "tag": tag })
+}
+
+fn lagging(info: &SubscriberInfo) -> Value {
+    json!({
+        "v": WIRE_VERSION,
+        "type": "lagging",
+        "subscriber": info.id.0,
+        "sent": info.sent,
+        "occupancy": info.occupancy,
+        "capacity": info.capacity,
+        "blocked_ms": info.blocked.as_millis() as u64,
+    })
+}
+
+fn compacting(phase: &CompactPhase) -> Value {
+    match phase {
+        CompactPhase::Started => json!({ "v": WIRE_VERSION, "type": "compacting", "phase": "started" }),
+        CompactPhase::Copied { records } => json!({
+            "v": WIRE_VERSION,
+            "type": "compacting",
+            "phase": "copied",
+            "records": records,
+        }),
+        CompactPhase::Finished(report) => json!({
+            "v": WIRE_VERSION,
+            "type": "compacting",
+            "phase": "finished",
+            "records": report.records,
+            "pages_before": report.pages_before,
+            "pages_after": report.pages_after,
+            "pause_ms": report.pause.as_millis() as u64,
+        }),
+    }
+}
+
+// through Value so maps get sorted keys, same as export
+fn to_value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
@@ -0,0 +1,140 @@
+//! Deterministic workload generators, used by `Storage::self_test`
+//! and by benchmarks so measurements stay comparable
+
+use serde::{Deserialize, Serialize};
+
+use super::document::{Document, FullText, Indexer, MaterializedView, Range, RangeField, Tags};
+
+// words of generated bodies
+const VOCABULARY: [&str; 16] = [
+    "alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel",
+    "india", "juliet", "kilo", "lima", "mike", "november", "oscar", "papa",
+];
+
+// distinct tags of generated documents
+const BUCKETS: u64 = 16;
+
+// width of zero padded score, so range order match numeric order
+pub const SCORE_WIDTH: usize = 8;
+
+
+
+/// xorshift64, same seed give same sequence on every platform
+#[derive(Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    #[inline]
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// uniform in 0..bound
+    #[inline]
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound.max(1)
+    }
+}
+
+
+
+/// generated document: tag `bucket-N`, range field `score`, full text `body`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorkloadDoc {
+    pub id: u64,
+    pub bucket: String,
+    pub score: String,
+    pub body: String,
+}
+
+impl Document for WorkloadDoc {}
+
+impl Indexer for WorkloadDoc {
+    fn extract(&self) -> Vec<String> {
+        vec![format!("doc-{}", self.id)]
+    }
+}
+
+impl Tags for WorkloadDoc {
+    fn get_tags(&self) -> Vec<String> {
+        vec![self.bucket.clone()]
+    }
+}
+
+impl Range for WorkloadDoc {
+    fn get_fields(&self) -> Vec<RangeField> {
+        vec![RangeField { name: "score".to_owned(), value: self.score.clone() }]
+    }
+}
+
+impl MaterializedView for WorkloadDoc {
+    fn filter(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FullText for WorkloadDoc {
+    fn get_content(&self) -> Option<String> {
+        Some(self.body.clone())
+    }
+}
+
+
+
+// words of vocabulary up to about `size` bytes
+fn words(rng: &mut Rng, size: usize) -> String {
+    let mut body = String::with_capacity(size + 8);
+    while body.len() < size {
+        if !body.is_empty() {
+            body.push(' ');
+        }
+        body.push_str(VOCABULARY[rng.below(VOCABULARY.len() as u64) as usize]);
+    }
+    body
+}
+
+/// text that compress like prose, `len` bytes
+#[cfg(feature = "test-util")]
+pub fn text(len: usize, seed: u64) -> String {
+    let mut out = words(&mut Rng::new(seed), len);
+    out.truncate(len);
+    out
+}
+
+/// zero padded score as stored in `WorkloadDoc::score`
+pub fn score(value: u64) -> String {
+    format!("{:0width$}", value, width = SCORE_WIDTH)
+}
+
+/// `count` documents keyed 0..count with bodies of about `size` bytes,
+/// scores uniform in 0..count
+pub fn documents(count: usize, size: usize, seed: u64) -> impl Iterator<Item = (u64, WorkloadDoc)> {
+    let mut rng = Rng::new(seed);
+
+    (0..count as u64).map(move |id| {
+        let body = words(&mut rng, size);
+        let doc = WorkloadDoc {
+            id,
+            bucket: format!("bucket-{}", rng.below(BUCKETS)),
+            score: score(rng.below(count as u64)),
+            body,
+        };
+
+        (id, doc)
+    })
+}
+
+/// `count` keys uniform over documents generated with `documents(of, ..)`
+pub fn lookup_keys(count: usize, of: usize, seed: u64) -> impl Iterator<Item = u64> {
+    let mut rng = Rng::new(seed);
+    (0..count).map(move |_| rng.below(of as u64))
+}
@@ -2,17 +2,57 @@
 
 mod darkbird;
 
+#[cfg(feature = "test-util")]
+pub use darkbird::testing;
+
+// generators of Storage::self_test, shared with benches
+#[cfg(feature = "test-util")]
+pub use darkbird::workload;
+
 pub use darkbird::{
-    storage::Storage,
+    storage::{Storage, AuditReport, AuditEntry, Structure, CheckpointReport, CloseReport, RebuildProgress, RebuildState, ExportOptions, ExportFormat, ConflictPolicy, ImportReport, PinnedDoc, BulkProgress, KeyPage, PlanReport, RemovalPlan, CompactPlan, CompactPhase, CompactReport, SelfTestProfile, SelfTestReport, PhaseReport, StorageStats},
     storage_redis,
-    wal::{helper::{backup, migration}, page_processor::{Format, Sync, PageProcessor}}, 
+    storage_bytes,
+    wal::{helper::{backup, migration}, page_processor::{Format, Sync, PageProcessor}, codec::{Codec, WalCodec, WAL_FORMAT_VERSION}, writer::WalWriter, archive::{ArchiveHook, PageSealed}}, 
     persistent_worker::{Persistent, DatabaseName, DatabaseSession, Stop},
     document,
     RQuery, 
     Event,
+    SessionResult,
+    SubscriberInfo,
+    SubscriptionId,
     Options,
+    views::ViewFn,
     StorageType,
-    schema::Schema,
+    schema::{Schema, SchemaError},
+    registry::{TypeRegistry, TypeRegistryError},
+    config::{StoreConfig, ConfigError},
+    reference::ReferenceAction,
+    patch::DocPatch,
+    query::{QueryBuilder, Order, PageOrder},
+    search::SearchQuery,
+    admin::{AdminEvent, AdminOutcome},
+    capabilities::Capabilities,
+    plugin::{WritePlugin, WriteContext, SizeLimit},
+    read_snapshot::{ReadPreference, ReadResult, ReadSource},
+    repair::{ReadRepair, Repair, RepairStats},
+    access::AccessReport,
+    retention::EventRetention,
+    recovery::{ClockSkew, ClockSkewPolicy, RecoveryMode, RecoveryReport},
+    capacity::EvictionPolicy,
+    query_cache::QueryCacheStats,
+    filter::{EventFilter, EventPredicate, SubscribeFilter, TagExpr, Change, ChangeMask},
+    clock::{Clock, ClampedClock, SystemClock},
+    compression::Compression,
+    stream::ScanStream,
+    startup::{IoBudget, OpenProgress, StoreProgress},
+    key_codec::KeyCodec,
+    latency::{LatencyReport, OpLatency, Operation},
+    metrics::{MetricEvent, MetricsObserver, MetricsSnapshot},
+    wire::{WireCodec, WIRE_VERSION},
     database::Database,
+    derive::Derivation,
+    snapshot::ConsistentView,
+    transaction::Transaction,
     async_trait
 };
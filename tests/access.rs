@@ -0,0 +1,72 @@
+mod common;
+
+use common::{ram_options, temp_dir, User};
+use darkbird::Storage;
+use std::path::Path;
+
+
+
+async fn open(dir: &Path, tracking: bool) -> Storage<String, User> {
+    let ops = ram_options(dir, "users");
+    let ops = if tracking { ops.with_access_tracking() } else { ops };
+
+    let storage = Storage::<String, User>::open(ops).await.unwrap();
+    for name in ["ann", "bob", "eve", "joe"] {
+        storage.insert(name.to_owned(), User::new(name, 30, "rome")).await.unwrap();
+    }
+    storage
+}
+
+fn read(storage: &Storage<String, User>, name: &str, times: usize) {
+    for _ in 0..times {
+        assert!(storage.lookup(&name.to_owned()).is_some());
+    }
+}
+
+fn ranked(storage: &Storage<String, User>, n: usize) -> Vec<(String, u64)> {
+    storage.top_accessed(n).top
+}
+
+
+
+// most read first, ties listed by key, removed keys left out
+#[tokio::test]
+async fn top_accessed_rank_by_reads() {
+    let dir = temp_dir("access-rank");
+    let storage = open(&dir, true).await;
+
+    read(&storage, "eve", 5);
+    read(&storage, "bob", 2);
+    read(&storage, "ann", 2);
+    read(&storage, "joe", 1);
+
+    let all = [("eve".to_owned(), 5), ("ann".to_owned(), 2), ("bob".to_owned(), 2), ("joe".to_owned(), 1)];
+    assert_eq!(ranked(&storage, 10), all);
+    assert_eq!(ranked(&storage, 3), all[..3]);
+    assert_eq!(ranked(&storage, 1), all[..1]);
+    assert!(ranked(&storage, 0).is_empty());
+    assert_eq!(storage.top_accessed(1).reads, 10);
+    assert_eq!(storage.access_count(&"eve".to_owned()), 5);
+
+    // writes aren't reads
+    storage.update(&"joe".to_owned(), |user| user.age += 1).await.unwrap();
+    storage.remove("eve".to_owned()).await.unwrap();
+    assert_eq!(ranked(&storage, 10), all[1..]);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn top_accessed_empty_without_tracking() {
+    let dir = temp_dir("access-off");
+    let storage = open(&dir, false).await;
+    read(&storage, "ann", 3);
+
+    let report = storage.top_accessed(10);
+    assert_eq!((report.top.len(), report.reads), (0, 0));
+    assert_eq!(storage.access_count(&"ann".to_owned()), 0);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
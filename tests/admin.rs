@@ -0,0 +1,102 @@
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use darkbird::{
+    AdminEvent, AdminOutcome, ConflictPolicy, ExportFormat, ExportOptions, SelfTestProfile, SessionResult, Storage,
+    Structure,
+};
+use std::sync::Arc;
+use tokio::sync::broadcast::Receiver;
+
+
+
+// events emitted since last call, notices of store conditions left out
+fn drain(events: &mut Receiver<AdminEvent>) -> Vec<AdminEvent> {
+    let mut drained = vec![];
+    while let Ok(event) = events.try_recv() {
+        if event.outcome != AdminOutcome::Notice {
+            drained.push(event);
+        }
+    }
+    drained
+}
+
+// one Started then one terminal event of operation, with same id
+fn assert_one_op(events: &mut Receiver<AdminEvent>, operation: &str, succeeded: bool) {
+    let drained = drain(events);
+    assert_eq!(drained.len(), 2, "{}: {:?}", operation, drained);
+
+    let (started, finished) = (&drained[0], &drained[1]);
+    assert_eq!((started.operation, finished.operation), (operation, operation));
+    assert_eq!(started.id, finished.id);
+    assert_eq!(started.outcome, AdminOutcome::Started);
+    assert!(started.finished.is_none() && finished.finished.is_some());
+
+    match succeeded {
+        true => assert_eq!(finished.outcome, AdminOutcome::Succeeded, "{}", operation),
+        false => assert!(matches!(finished.outcome, AdminOutcome::Failed(_)), "{}: {:?}", operation, finished.outcome),
+    }
+}
+
+#[tokio::test]
+async fn every_admin_operation_emit_one_start_and_one_end() {
+    let dir = temp_dir("admin-events");
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+    let mut events = storage.admin_events();
+
+    for (i, city) in ["rome", "oslo", "lima"].iter().enumerate() {
+        let name = format!("user{}", i);
+        storage.insert(name.clone(), User::new(&name, 20 + i as i64, city)).await.unwrap();
+    }
+    assert!(drain(&mut events).is_empty());
+
+    storage.audit(false).await;
+    assert_one_op(&mut events, "audit", true);
+
+    storage.rebuild_index(Structure::Tags).await.unwrap();
+    assert_one_op(&mut events, "rebuild_index", true);
+
+    storage.create_view("elders", Arc::new(|_: &String, user: &User| user.age >= 21)).await.unwrap();
+    assert_one_op(&mut events, "create_view", true);
+    assert!(storage.create_view("elders", Arc::new(|_: &String, _: &User| true)).await.is_err());
+    assert_one_op(&mut events, "create_view", false);
+
+    storage.rebuild_view("elders").await.unwrap();
+    assert_one_op(&mut events, "rebuild_view", true);
+    storage.drop_view("elders").await.unwrap();
+    assert_one_op(&mut events, "drop_view", true);
+    assert!(matches!(storage.drop_view("elders").await, Err(SessionResult::ViewNotFound { .. })));
+    assert_one_op(&mut events, "drop_view", false);
+
+    storage.checkpoint().await.unwrap();
+    assert_one_op(&mut events, "checkpoint", true);
+
+    storage.compact().await.unwrap();
+    assert_one_op(&mut events, "compact", true);
+
+    let opts = ExportOptions::new().with_format(ExportFormat::JsonLines);
+    let mut exported = vec![];
+    storage.export(&mut exported, &opts).await.unwrap();
+    assert_one_op(&mut events, "export", true);
+
+    // held documents keep their index keys, see `import`
+    storage.retain(|_, _| false).await.unwrap();
+    storage.import(exported.as_slice(), ExportFormat::JsonLines, ConflictPolicy::Overwrite).await.unwrap();
+    assert_one_op(&mut events, "import", true);
+    assert!(storage.import(exported.as_slice(), ExportFormat::JsonLines, ConflictPolicy::Fail).await.is_err());
+    assert_one_op(&mut events, "import", false);
+
+    storage.remove_keys(vec!["user0".to_owned()]).await.unwrap();
+    assert_one_op(&mut events, "bulk_remove", true);
+
+    storage.self_test(SelfTestProfile::quick().with_documents(50).with_lookups(50)).await.unwrap();
+    assert_one_op(&mut events, "self_test", true);
+
+    storage.clear().await.unwrap();
+    assert_one_op(&mut events, "clear", true);
+
+    storage.close().await.unwrap();
+    assert_one_op(&mut events, "close", true);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
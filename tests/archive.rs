@@ -0,0 +1,81 @@
+mod common;
+
+use common::{temp_dir, User};
+use darkbird::{ArchiveHook, Options, PageSealed, Storage, StorageType};
+use std::{collections::BTreeMap, path::Path};
+use tokio::sync::mpsc;
+
+// smallest page size, records per page
+const PAGE_SIZE: usize = 5000;
+const USERS: i64 = 12_000;
+
+
+
+fn options(dir: &Path) -> Options {
+    Options::new(dir.to_str().unwrap(), "users", PAGE_SIZE, StorageType::DiskCopies, true)
+}
+
+fn state(storage: &Storage<String, User>) -> BTreeMap<String, User> {
+    storage.iter().map(|entry| (entry.key().clone(), entry.value().clone())).collect()
+}
+
+// archive every sealed page under its restore name, then acknowledge it
+fn archiver(archive: &Path) -> (ArchiveHook, tokio::task::JoinHandle<Vec<u64>>) {
+    let restore = archive.join("users");
+    std::fs::create_dir_all(&restore).unwrap();
+
+    let (sender, mut pages) = mpsc::unbounded_channel::<PageSealed>();
+    let task = tokio::spawn(async move {
+        let mut seqs = vec![];
+        while let Some(page) = pages.recv().await {
+            let bytes = std::fs::read(&page.path).unwrap();
+            assert_eq!(crc32fast::hash(&bytes), page.checksum);
+            std::fs::write(restore.join(page.restore_name()), bytes).unwrap();
+            page.ack();
+            seqs.push(page.seq);
+        }
+        seqs
+    });
+
+    (ArchiveHook::new(sender), task)
+}
+
+#[tokio::test]
+async fn store_restore_from_archive_alone() {
+    let dir = temp_dir("archive-restore");
+    let (live, archive) = (dir.join("live"), dir.join("archive"));
+    std::fs::create_dir_all(&live).unwrap();
+
+    let (hook, task) = archiver(&archive);
+    let storage = Storage::<String, User>::open(options(&live).with_archive_hook(hook)).await.unwrap();
+
+    for i in 0..USERS {
+        let name = format!("user{}", i);
+        storage.insert(name.clone(), User::new(&name, i, "rome")).await.unwrap();
+    }
+    for i in 0..USERS / 4 {
+        storage.remove(format!("user{}", i * 3)).await.unwrap();
+    }
+    for i in 0..USERS / 8 {
+        storage.update(&format!("user{}", i * 5 + 1), |user| user.age += 100).await.unwrap();
+    }
+
+    // cut last page, so every write is in a sealed page. waits for their acknowledgement
+    storage.compact().await.unwrap();
+    let expected = state(&storage);
+    storage.close().await.unwrap();
+
+    // every page once, in order
+    let seqs = task.await.unwrap();
+    assert!(seqs.len() > 2, "{:?}", seqs);
+    assert_eq!(seqs, (1..=seqs.len() as u64).collect::<Vec<_>>());
+
+    // live store gone
+    std::fs::remove_dir_all(&live).unwrap();
+    let restored = Storage::<String, User>::open(options(&archive)).await.unwrap();
+    assert_eq!(state(&restored), expected);
+    assert_eq!(restored.len() as i64, USERS - USERS / 4);
+
+    restored.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
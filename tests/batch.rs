@@ -0,0 +1,133 @@
+mod common;
+
+use common::{disk_options, temp_dir, Order};
+use darkbird::{testing::FaultyWal, Options, Storage};
+use std::{
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const KEYS: u64 = 16;
+
+
+
+async fn open(options: Options) -> Storage<u64, Order> {
+    Storage::open(options.with_lanes(4)).await.unwrap()
+}
+
+fn order(n: u64) -> Order {
+    Order { user: format!("u{}", n % 3), item: format!("item-{}", n) }
+}
+
+fn state(storage: &Storage<u64, Order>) -> Vec<Option<String>> {
+    (0..KEYS * 2).map(|key| storage.lookup_owned(&key).map(|order| order.item)).collect()
+}
+
+async fn filled(dir: &Path, wal: FaultyWal) -> Storage<u64, Order> {
+    let storage = open(disk_options(dir, "orders").with_faulty_wal(wal)).await;
+    for key in 0..KEYS {
+        storage.insert(key, order(key)).await.unwrap();
+    }
+    storage
+}
+
+
+
+// record rejected before disk_log: nothing in memory nor on disk
+#[tokio::test]
+async fn failed_insert_batch_leave_store_untouched() {
+    let dir = temp_dir("batch-insert-enqueue");
+    let storage = filled(&dir, FaultyWal::new().fail_nth_append(KEYS + 1)).await;
+    let before = state(&storage);
+
+    let batch = (0..KEYS * 2).map(|key| (key, order(100 + key))).collect();
+    assert!(storage.insert_batch(batch).await.is_err());
+    assert_eq!(state(&storage), before);
+
+    storage.close().await.unwrap();
+    let storage = open(disk_options(&dir, "orders")).await;
+    assert_eq!(state(&storage), before);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// record written then reported failed: error returned, memory untouched
+#[tokio::test]
+async fn failed_write_of_remove_batch_leave_memory_untouched() {
+    let dir = temp_dir("batch-remove-write");
+    let storage = filled(&dir, FaultyWal::new().fail_nth_write(1)).await;
+    let before = state(&storage);
+
+    assert!(storage.remove_batch((0..KEYS).collect()).await.is_err());
+    assert_eq!(state(&storage), before);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// written in one record, replayed whole
+#[tokio::test]
+async fn batches_are_one_record_and_replay() {
+    let dir = temp_dir("batch-replay");
+    let wal = FaultyWal::new();
+    let storage = filled(&dir, wal.clone()).await;
+
+    let batch = (KEYS..KEYS * 2).map(|key| (key, order(key))).collect();
+    storage.insert_batch(batch).await.unwrap();
+    assert_eq!(wal.appends(), KEYS + 1);
+
+    storage.remove_batch((0..KEYS).step_by(2).collect()).await.unwrap();
+    assert_eq!(wal.appends(), KEYS + 2);
+
+    let memory = state(&storage);
+    storage.close().await.unwrap();
+    let storage = open(disk_options(&dir, "orders")).await;
+    assert_eq!(state(&storage), memory);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// predicate run again once keys are held, a document it now keep isn't removed
+#[tokio::test]
+async fn retain_check_predicate_again_under_lock() {
+    let dir = temp_dir("batch-retain");
+    let storage = filled(&dir, FaultyWal::new()).await;
+    let calls = AtomicUsize::new(0);
+
+    // scan drop every document, second look keep them
+    let removed = storage
+        .retain(|_, _| calls.fetch_add(1, Ordering::SeqCst) >= KEYS as usize)
+        .await
+        .unwrap();
+
+    assert_eq!(removed, 0);
+    assert_eq!(calls.load(Ordering::SeqCst), KEYS as usize * 2);
+    assert!(state(&storage)[..KEYS as usize].iter().all(Option::is_some));
+
+    let removed = storage.retain(|key, _| key % 2 == 0).await.unwrap();
+    assert_eq!(removed, KEYS as usize / 2);
+
+    let memory = state(&storage);
+    storage.close().await.unwrap();
+    let storage = open(disk_options(&dir, "orders")).await;
+    assert_eq!(state(&storage), memory);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// removal record reported failed by disk_log: retain return error, memory untouched
+#[tokio::test]
+async fn retain_report_failed_write() {
+    let dir = temp_dir("batch-retain-write");
+    let storage = filled(&dir, FaultyWal::new().fail_nth_write(1)).await;
+    let before = state(&storage);
+
+    assert!(storage.retain(|key, _| key % 2 == 0).await.is_err());
+    assert_eq!(state(&storage), before);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
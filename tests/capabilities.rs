@@ -0,0 +1,110 @@
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use darkbird::{
+    testing::FaultyWal, ArchiveHook, ClockSkewPolicy, Compression, EvictionPolicy, MetricEvent, MetricsObserver, Options, RecoveryMode,
+    Repair, Schema, SizeLimit, Storage, StorageType, WalCodec, WalWriter,
+};
+use std::time::Duration;
+
+
+
+struct Ignore;
+
+impl MetricsObserver for Ignore {
+    fn observe(&self, _: MetricEvent) {}
+}
+
+// every Options knob set to a value other than its default
+fn every_feature(dir: &std::path::Path) -> Options {
+    let (archived, _pages) = tokio::sync::mpsc::unbounded_channel();
+
+    // reporter on
+    Options::new(dir.to_str().unwrap(), "users", 1000, StorageType::DiskCopies, false)
+        .with_lag_threshold(64)
+        .with_value_compression(Compression::Lz4)
+        .with_lanes(4)
+        .with_admin_log(dir.join("admin.log").to_str().unwrap())
+        .with_timers()
+        .with_plugin(SizeLimit::<String, User>::new(4096))
+        .with_read_repair(|_: &String, _: &User| Repair::<User>::Valid)
+        .with_read_snapshot(Duration::from_millis(250))
+        .with_schema_override()
+        .with_access_tracking()
+        .with_ordered_keys()
+        .with_low_memory_replay()
+        .with_previous_values()
+        .with_event_retention(500, Duration::from_secs(30))
+        .with_wal_codec(WalCodec::Json)
+        .with_wal_writer(WalWriter::Thread)
+        .with_archive_hook(ArchiveHook::new(archived))
+        .with_recovery(RecoveryMode::SkipCorrupted)
+        .with_clock_skew(Duration::from_secs(5), ClockSkewPolicy::TrustLog)
+        .with_capacity(10_000, EvictionPolicy::EvictLru)
+        .with_query_cache(32, 1 << 20)
+        .with_metrics_observer(Ignore)
+        .with_bloom_filter(100_000, 0.01)
+        .with_disk_space_watch(1 << 20, Some(1 << 10))
+        .with_faulty_wal(FaultyWal::new())
+}
+
+#[test]
+fn every_feature_is_reported() {
+    let dir = temp_dir("capabilities-options");
+    let caps = every_feature(&dir).capabilities();
+
+    assert_eq!(caps.store, "users");
+    assert!(matches!(caps.storage_type, StorageType::DiskCopies));
+    assert!(caps.reporter);
+    assert_eq!(caps.coalesce_ms, None);
+    assert_eq!(caps.lag_threshold, Some(64));
+    assert!(matches!(caps.value_compression, Some(Compression::Lz4)));
+    assert_eq!(caps.lanes, 4);
+    assert!(caps.admin_log.unwrap().ends_with("admin.log"));
+    assert!(caps.timers);
+    assert_eq!(caps.plugins, vec!["size_limit".to_owned()]);
+    assert!(caps.read_repair);
+    assert_eq!(caps.read_snapshot_ms, Some(250));
+    assert!(caps.schema_override);
+    assert!(caps.track_access);
+    assert!(caps.ordered_keys);
+    assert!(caps.low_memory_replay);
+    assert!(caps.previous_values);
+    assert_eq!((caps.event_retention, caps.event_retention_ms), (Some(500), Some(30_000)));
+    assert_eq!(caps.wal_codec, WalCodec::Json);
+    assert_eq!(caps.wal_writer, WalWriter::Thread);
+    assert!(caps.archive_hook);
+    assert_eq!(caps.recovery, RecoveryMode::SkipCorrupted);
+    assert_eq!((caps.clock_skew_ms, caps.clock_skew_policy), (Some(5000), ClockSkewPolicy::TrustLog));
+    assert_eq!((caps.capacity, caps.eviction), (Some(10_000), EvictionPolicy::EvictLru));
+    assert_eq!((caps.query_cache, caps.query_cache_bytes), (Some(32), Some(1 << 20)));
+    assert!(caps.faulty_wal);
+    assert_eq!(caps.latency_metrics, cfg!(feature = "metrics"));
+    assert!(caps.metrics_observer);
+    assert_eq!((caps.bloom_filter, caps.bloom_false_positive_rate), (Some(100_000), Some(0.01)));
+    assert_eq!((caps.disk_space_threshold, caps.read_only_floor), (Some(1 << 20), Some(1 << 10)));
+
+    // coalescing alone, it exclude timers and transactions
+    let caps = disk_options(&dir, "users").with_coalesce(Duration::from_millis(20)).capabilities();
+    assert_eq!(caps.coalesce_ms, Some(20));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn opened_store_report_its_options() {
+    let dir = temp_dir("capabilities-open");
+    let expected = serde_json::to_value(every_feature(&dir).capabilities()).unwrap();
+
+    let storage = Storage::<String, User>::open(every_feature(&dir)).await.unwrap();
+    assert_eq!(serde_json::to_value(storage.capabilities()).unwrap(), expected);
+    storage.close().await.unwrap();
+
+    let db = Schema::new().with_datastore::<String, User>(every_feature(&dir)).await.unwrap().build();
+    let all = db.capabilities_all();
+    assert_eq!(all.len(), 1);
+    assert_eq!(serde_json::to_value(all.values().next().unwrap()).unwrap(), expected);
+
+    db.close_all().await;
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use darkbird::{testing::ManualClock, ClockSkew, ClockSkewPolicy, Storage};
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+const TTL: Duration = Duration::from_secs(600);
+const SKEW: Duration = Duration::from_secs(3600);
+const THRESHOLD: Duration = Duration::from_secs(300);
+
+
+
+// documents with ttl, written at real time so WAL pages carry it
+async fn write(dir: &Path) -> SystemTime {
+    let now = SystemTime::now();
+    let ops = disk_options(dir, "users").with_clock(Arc::new(ManualClock::new(now)));
+    let storage = Storage::<String, User>::open(ops).await.unwrap();
+    for i in 0..10 {
+        let name = format!("u{}", i);
+        storage.insert_with_ttl(name.clone(), User::new(&name, i, "rome"), TTL).await.unwrap();
+    }
+    storage.close().await.unwrap();
+    now
+}
+
+async fn open(dir: &Path, clock: SystemTime, policy: ClockSkewPolicy) -> Result<Storage<String, User>, String> {
+    let ops = disk_options(dir, "users")
+        .with_clock(Arc::new(ManualClock::new(clock)))
+        .with_clock_skew(THRESHOLD, policy);
+    Storage::open(ops).await
+}
+
+// ttl left of u0 on store clock
+fn left(storage: &Storage<String, User>) -> Duration {
+    storage.expires_at(&"u0".to_owned()).unwrap().duration_since(storage.now()).unwrap_or_default()
+}
+
+// files of store, WAL and its side files
+fn copy_store(from: &Path, to: &Path) {
+    std::fs::create_dir_all(to.join("users")).unwrap();
+    for entry in std::fs::read_dir(from.join("users")).unwrap() {
+        let entry = entry.unwrap();
+        std::fs::copy(entry.path(), to.join("users").join(entry.file_name())).unwrap();
+    }
+    for entry in std::fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_type().unwrap().is_file() {
+            std::fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+        }
+    }
+}
+
+fn close_to(left: Duration, expected: Duration) -> bool {
+    left <= expected && expected - left < Duration::from_secs(30)
+}
+
+// restored where clock is an hour late: WAL from the future
+#[tokio::test]
+async fn wal_ahead_of_clock() {
+    let dir = temp_dir("skew-ahead");
+    let written = write(&dir).await;
+    let clock = written - SKEW;
+
+    // deadlines as they are: an hour and ten minutes away
+    let storage = open(&dir, clock, ClockSkewPolicy::TrustClock).await.unwrap();
+    assert!(matches!(storage.recovery().clock_skew, Some(ClockSkew::Ahead(skew)) if skew > SKEW - THRESHOLD));
+    assert!(close_to(left(&storage), SKEW + TTL), "{:?}", left(&storage));
+    assert_eq!(storage.len(), 10);
+    storage.close().await.unwrap();
+
+    assert!(open(&dir, clock, ClockSkewPolicy::Fail).await.err().unwrap().starts_with("ClockSkew"));
+
+    // moved back by skew, ten minutes left as when written, and logged moved
+    let storage = open(&dir, clock, ClockSkewPolicy::TrustLog).await.unwrap();
+    assert!(close_to(left(&storage), TTL), "{:?}", left(&storage));
+    assert_eq!(storage.recovery().rebased, 10);
+    storage.close().await.unwrap();
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// restored where clock is an hour ahead: WAL from the past
+#[tokio::test]
+async fn wal_behind_clock() {
+    let dir = temp_dir("skew-behind");
+    let written = write(&dir).await;
+    let clock = written + SKEW;
+
+    assert!(open(&dir, clock, ClockSkewPolicy::Fail).await.err().unwrap().starts_with("ClockSkew"));
+
+    // moved forward by skew, nothing expire at open
+    let copy = dir.join("copy");
+    copy_store(&dir, &copy);
+    let storage = open(&copy, clock, ClockSkewPolicy::TrustLog).await.unwrap();
+    assert!(matches!(storage.recovery().clock_skew, Some(ClockSkew::Behind(skew)) if skew > SKEW - THRESHOLD));
+    assert_eq!(storage.len(), 10);
+    assert!(close_to(left(&storage), TTL), "{:?}", left(&storage));
+    storage.close().await.unwrap();
+
+    // deadlines as they are: every document expired an hour ago
+    let storage = open(&dir, clock, ClockSkewPolicy::TrustClock).await.unwrap();
+    assert_eq!(storage.len(), 0);
+    assert!(storage.lookup_owned(&"u0".to_owned()).is_none());
+    storage.close().await.unwrap();
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
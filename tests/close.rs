@@ -0,0 +1,123 @@
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use darkbird::{testing::FaultyWal, Schema, Storage};
+
+
+
+// windows refuse to delete a directory holding open files,
+// so removing it right after close show every handle was released
+#[tokio::test]
+async fn log_directory_removable_after_close() {
+    let dir = temp_dir("close-storage");
+
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+    for i in 0..100 {
+        storage.insert(format!("u{}", i), User::new(&format!("u{}", i), i, "paris")).await.unwrap();
+    }
+
+    let report = storage.close().await.unwrap();
+    assert_eq!(report.records, 100);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(!dir.exists());
+}
+
+#[tokio::test]
+async fn log_directory_removable_after_close_all() {
+    let dir = temp_dir("close-database");
+
+    let db = Schema::new()
+        .with_datastore::<String, User>(disk_options(&dir, "users"))
+        .await
+        .unwrap()
+        .build();
+
+    db.insert::<String, User>("a".to_owned(), User::new("a", 30, "rome")).await.unwrap();
+
+    let reports = db.close_all().await;
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].1.is_ok());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(!dir.exists());
+}
+
+#[tokio::test]
+async fn reopen_after_close_replay_log() {
+    let dir = temp_dir("close-reopen");
+
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+    storage.insert("a".to_owned(), User::new("a", 30, "rome")).await.unwrap();
+    storage.close().await.unwrap();
+
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+    assert_eq!(storage.lookup_owned(&"a".to_owned()).unwrap().age, 30);
+    storage.close().await.unwrap();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+// plain store, then one whose writes a coalescer hold for a minute, so only drop can log them
+fn stores(dir: &std::path::Path) -> Vec<(std::path::PathBuf, darkbird::Options)> {
+    let (plain, coalesced) = (dir.join("plain"), dir.join("coalesced"));
+    std::fs::create_dir_all(&plain).unwrap();
+    std::fs::create_dir_all(&coalesced).unwrap();
+
+    vec![
+        (plain.clone(), disk_options(&plain, "users")),
+        (coalesced.clone(), disk_options(&coalesced, "users").with_coalesce(std::time::Duration::from_secs(60))),
+    ]
+}
+
+async fn fill(storage: &Storage<String, User>) {
+    for i in 0..100 {
+        storage.insert(format!("u{}", i), User::new(&format!("u{}", i), i, "oslo")).await.unwrap();
+    }
+}
+
+async fn assert_replayed(dir: &std::path::Path) {
+    let storage = Storage::<String, User>::open(disk_options(dir, "users")).await.unwrap();
+    assert_eq!(storage.len(), 100);
+    assert_eq!(storage.lookup_owned(&"u99".to_owned()).unwrap().age, 99);
+    storage.close().await.unwrap();
+}
+
+// dropped outside runtime: closed before drop return
+#[test]
+fn drop_outside_runtime_log_every_write() {
+    let dir = temp_dir("close-drop-sync");
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+
+    for (store, opts) in stores(&dir) {
+        let storage = runtime.block_on(async {
+            let storage = Storage::<String, User>::open(opts).await.unwrap();
+            fill(&storage).await;
+            storage
+        });
+        drop(storage);
+
+        runtime.block_on(assert_replayed(&store));
+    }
+
+    drop(runtime);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+// dropped in a task: close is spawned, done shortly after
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn drop_inside_runtime_log_every_write() {
+    let dir = temp_dir("close-drop-async");
+
+    for (store, opts) in stores(&dir) {
+        let wal = FaultyWal::new();
+        let storage = Storage::<String, User>::open(opts.with_faulty_wal(wal.clone())).await.unwrap();
+        fill(&storage).await;
+        drop(storage);
+
+        wal.wait_closed().await;
+        assert_replayed(&store).await;
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use darkbird::{
+    testing::{self, FaultyWal, ManualClock},
+    Storage,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+const WINDOW: Duration = Duration::from_millis(100);
+
+
+
+// let flush task run after clock moved
+async fn settle() {
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+}
+
+// coalescing window run on tokio clock, paused and advanced by hand,
+// store wall clock pinned by ManualClock so nothing depend on real time
+async fn open(dir: &std::path::Path, wal: &FaultyWal) -> Storage<String, User> {
+    let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    let ops = disk_options(dir, "users")
+        .with_clock(Arc::new(clock))
+        .with_coalesce(WINDOW)
+        .with_faulty_wal(wal.clone());
+
+    Storage::<String, User>::open(ops).await.unwrap()
+}
+
+
+#[tokio::test]
+async fn writes_within_window_collapse_to_last() {
+    let dir = temp_dir("coalesce-collapse");
+    let wal = FaultyWal::new();
+    let storage = open(&dir, &wal).await;
+
+    testing::pause_clock();
+    testing::advance_clock(WINDOW).await;
+    settle().await;
+
+    storage.insert("a".to_owned(), User::new("a", 0, "rome")).await.unwrap();
+    for age in 1..10 {
+        storage.update(&"a".to_owned(), |user| user.age = age).await.unwrap();
+    }
+
+    // memory is newest right away, nothing logged before window elapsed
+    assert_eq!(storage.lookup_owned(&"a".to_owned()).unwrap().age, 9);
+    assert_eq!(wal.appends(), 0);
+
+    testing::advance_clock(WINDOW).await;
+    settle().await;
+    assert_eq!(wal.appends(), 1);
+
+    testing::resume_clock();
+    storage.close().await.unwrap();
+
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+    assert_eq!(storage.lookup_owned(&"a".to_owned()).unwrap().age, 9);
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn failed_flush_is_retried() {
+    let dir = temp_dir("coalesce-retry");
+    let wal = FaultyWal::new().fail_nth_append(1);
+    let storage = open(&dir, &wal).await;
+
+    testing::pause_clock();
+    testing::advance_clock(WINDOW).await;
+    settle().await;
+
+    storage.insert("a".to_owned(), User::new("a", 1, "rome")).await.unwrap();
+
+    testing::advance_clock(WINDOW).await;
+    settle().await;
+    assert_eq!((wal.appends(), wal.failed()), (1, 1));
+
+    // stashed back and logged on next window
+    testing::advance_clock(WINDOW).await;
+    settle().await;
+    assert_eq!((wal.appends(), wal.failed()), (2, 1));
+
+    testing::resume_clock();
+    storage.close().await.unwrap();
+
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+    assert_eq!(storage.lookup_owned(&"a".to_owned()).unwrap().age, 1);
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn newer_write_replace_failed_one() {
+    let dir = temp_dir("coalesce-newer");
+    let wal = FaultyWal::new().fail_nth_append(1);
+    let storage = open(&dir, &wal).await;
+
+    testing::pause_clock();
+    testing::advance_clock(WINDOW).await;
+    settle().await;
+
+    storage.insert("a".to_owned(), User::new("a", 1, "rome")).await.unwrap();
+    testing::advance_clock(WINDOW).await;
+    settle().await;
+    assert_eq!(wal.failed(), 1);
+
+    storage.update(&"a".to_owned(), |user| user.age = 2).await.unwrap();
+    testing::advance_clock(WINDOW).await;
+    settle().await;
+    assert_eq!(wal.appends(), 2);
+
+    testing::resume_clock();
+    storage.close().await.unwrap();
+
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+    assert_eq!(storage.lookup_owned(&"a".to_owned()).unwrap().age, 2);
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn close_report_unlogged_write() {
+    let dir = temp_dir("coalesce-close");
+    let wal = FaultyWal::new().fail_after_bytes(1);
+    let storage = open(&dir, &wal).await;
+
+    storage.insert("a".to_owned(), User::new("a", 1, "rome")).await.unwrap();
+
+    assert!(storage.close().await.is_err());
+    assert!(wal.failed() >= 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,130 @@
+#![allow(dead_code)]
+
+use darkbird::{
+    document::{Document, FieldValue, FullText, Indexer, MaterializedView, Range, Tags, TypedField},
+    Options, StorageType,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct User {
+    pub name: String,
+    pub age: i64,
+    pub city: String,
+    pub bio: String,
+}
+
+impl User {
+    pub fn new(name: &str, age: i64, city: &str) -> Self {
+        User {
+            name: name.to_owned(),
+            age,
+            city: city.to_owned(),
+            bio: format!("{} lives in {}", name, city),
+        }
+    }
+}
+
+impl Document for User {}
+
+impl Indexer for User {
+    fn extract(&self) -> Vec<String> {
+        vec![format!("name:{}", self.name)]
+    }
+}
+
+impl Tags for User {
+    fn get_tags(&self) -> Vec<String> {
+        vec![format!("city:{}", self.city)]
+    }
+}
+
+impl Range for User {
+    fn get_typed_fields(&self) -> Vec<TypedField> {
+        vec![TypedField { name: "age".to_owned(), value: FieldValue::Int(self.age) }]
+    }
+}
+
+impl MaterializedView for User {
+    fn filter(&self) -> Option<String> {
+        if self.age >= 18 {
+            Some("adults".to_owned())
+        } else {
+            None
+        }
+    }
+}
+
+impl FullText for User {
+    fn get_content(&self) -> Option<String> {
+        Some(self.bio.clone())
+    }
+}
+
+
+
+/// Order owned by a user, tagged `user:<name>`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Order {
+    pub user: String,
+    pub item: String,
+}
+
+impl Document for Order {}
+
+impl Indexer for Order {
+    fn extract(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Tags for Order {
+    fn get_tags(&self) -> Vec<String> {
+        vec![format!("user:{}", self.user)]
+    }
+}
+
+impl Range for Order {}
+
+impl MaterializedView for Order {
+    fn filter(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FullText for Order {
+    fn get_content(&self) -> Option<String> {
+        None
+    }
+}
+
+
+
+/// empty directory under system temp dir, unique per call
+pub fn temp_dir(name: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = std::env::temp_dir().join(format!(
+        "darkbird-{}-{}-{}",
+        name,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::SeqCst)
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub fn disk_options(dir: &Path, name: &str) -> Options {
+    Options::new(dir.to_str().unwrap(), name, 1000, StorageType::DiskCopies, true)
+}
+
+pub fn ram_options(dir: &Path, name: &str) -> Options {
+    Options::new(dir.to_str().unwrap(), name, 1000, StorageType::RamCopies, true)
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{disk_options, temp_dir, Order};
+use darkbird::Storage;
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+const KEYS: u64 = 2_000;
+const WRITES: u64 = 20_000;
+
+
+
+fn state(storage: &Storage<u64, Order>) -> BTreeMap<u64, Order> {
+    storage.iter().map(|entry| (*entry.key(), entry.value().clone())).collect()
+}
+
+async fn open(dir: &Path) -> Storage<u64, Order> {
+    Storage::open(disk_options(dir, "orders")).await.unwrap()
+}
+
+// inserts, updates and removes keep running while compactions cut and renumber pages,
+// reopening replay same state as memory
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn compact_during_writes_keep_every_write() {
+    let dir = temp_dir("compact-concurrent");
+    let storage = Arc::new(open(&dir).await);
+    for key in 0..KEYS {
+        storage.insert(key, Order { user: "ann".to_owned(), item: format!("item-{}", key) }).await.unwrap();
+    }
+
+    let writer = {
+        let storage = storage.clone();
+        tokio::spawn(async move {
+            for i in 0..WRITES {
+                let key = i % KEYS;
+                match i % 3 {
+                    0 => storage.insert(key, Order { user: "bob".to_owned(), item: format!("write-{}", i) }).await.unwrap(),
+                    1 => {
+                        storage.update(&key, |order| order.item.push('+')).await.unwrap();
+                    }
+                    _ => storage.remove(key).await.unwrap(),
+                }
+                if i % 100 == 0 {
+                    tokio::task::yield_now().await;
+                }
+            }
+        })
+    };
+
+    let mut compactions = 0;
+    while !writer.is_finished() {
+        let report = storage.compact().await.unwrap();
+        assert!(report.records as u64 <= KEYS);
+        compactions += 1;
+    }
+    writer.await.unwrap();
+    assert!(compactions > 1, "{} compactions", compactions);
+
+    let expected = state(&storage);
+    Arc::try_unwrap(storage).ok().unwrap().close().await.unwrap();
+
+    let storage = open(&dir).await;
+    assert_eq!(state(&storage), expected);
+
+    // and once more after a compaction of the reopened store
+    storage.compact().await.unwrap();
+    storage.close().await.unwrap();
+    let storage = open(&dir).await;
+    assert_eq!(state(&storage), expected);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::{ram_options, temp_dir, User};
+use darkbird::{Compression, Schema, SessionResult, Storage};
+use std::path::Path;
+
+
+
+async fn open(dir: &Path) -> Storage<String, User> {
+    let storage = Storage::<String, User>::open(ram_options(dir, "users").with_value_compression(Compression::Lz4))
+        .await
+        .unwrap();
+
+    storage.insert("a".to_owned(), User::new("ann", 25, "rome")).await.unwrap();
+    storage.insert("b".to_owned(), User::new("bob", 40, "oslo")).await.unwrap();
+    storage.insert("c".to_owned(), User::new("cid", 12, "rome")).await.unwrap();
+    storage
+}
+
+fn compressed<T>(res: Result<T, SessionResult>) -> bool {
+    matches!(res, Err(SessionResult::CompressedValue))
+}
+
+
+#[tokio::test]
+async fn lookup_ref_fails_with_compressed_value() {
+    let dir = temp_dir("compression-refs");
+    let storage = open(&dir).await;
+    let a = "a".to_owned();
+
+    assert!(compressed(storage.lookup_ref(&a)));
+
+    // other Ref reads keep their signatures and find no document to borrow
+    assert!(storage.lookup(&a).is_none());
+    assert!(storage.gets(vec![&a]).is_empty());
+    assert!(storage.range("age", 0, 100).is_empty());
+    assert!(storage.lookup_by_index("name:ann").is_none());
+    assert!(storage.lookup_by_tag("city:rome").is_empty());
+    assert!(storage.fetch_view("adults").is_empty());
+    assert!(storage.search("rome").is_empty());
+    assert_eq!(storage.iter().count(), 0);
+    assert!(storage.find().tag("city:rome").fetch().is_empty());
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn owned_reads_decompress() {
+    let dir = temp_dir("compression-owned");
+    let storage = open(&dir).await;
+
+    assert_eq!(storage.lookup_owned(&"a".to_owned()).unwrap().name, "ann");
+    assert_eq!(storage.lookup_by_index_owned("name:bob").unwrap().0, "b");
+
+    let mut rome: Vec<String> = storage.lookup_by_tag_owned("city:rome").into_iter().map(|(k, _)| k).collect();
+    rome.sort();
+    assert_eq!(rome, vec!["a", "c"]);
+
+    let adults = storage.range_owned("age", 18, 100).unwrap();
+    assert_eq!(adults.len(), 2);
+
+    assert_eq!(storage.fetch_view_owned("adults").len(), 2);
+    assert_eq!(storage.search_owned("oslo")[0].0, "b");
+
+    let fetched = storage.find().tag("city:rome").range("age", 18..31).fetch_owned();
+    assert_eq!(fetched.len(), 1);
+    assert_eq!(fetched[0].1.name, "ann");
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn database_ref_reads_fail_with_compressed_value() {
+    let dir = temp_dir("compression-database");
+
+    let db = Schema::new()
+        .with_datastore::<String, User>(ram_options(&dir, "users").with_value_compression(Compression::Lz4))
+        .await
+        .unwrap()
+        .build();
+
+    db.insert::<String, User>("a".to_owned(), User::new("ann", 25, "rome")).await.unwrap();
+
+    assert!(compressed(db.lookup_ref::<String, User>(&"a".to_owned())));
+    assert!(compressed(db.lookup::<String, User>(&"a".to_owned())));
+    assert!(compressed(db.gets::<String, User>(vec![&"a".to_owned()])));
+    assert!(compressed(db.range::<String, User>("age", 0, 100)));
+    assert!(compressed(db.lookup_by_tag::<String, User>("city:rome")));
+    assert!(compressed(db.search::<String, User>("rome")));
+    assert!(compressed(db.iter::<String, User>().map(|iter| iter.count())));
+    assert_eq!(db.lookup_owned::<String, User>(&"a".to_owned()).unwrap().unwrap().age, 25);
+
+    drop(db);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use common::{ram_options, temp_dir, User};
+use darkbird::Storage;
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+// concurrent callers racing on one key
+const RACERS: usize = 16;
+
+
+
+async fn open(dir: &Path) -> Arc<Storage<String, User>> {
+    let storage = Storage::<String, User>::open(ram_options(dir, "users")).await.unwrap();
+    storage.insert("ann".to_owned(), User::new("ann", 30, "rome")).await.unwrap();
+    Arc::new(storage)
+}
+
+async fn close(storage: Arc<Storage<String, User>>, dir: &Path) {
+    Arc::try_unwrap(storage).ok().expect("racers joined").close().await.unwrap();
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+
+
+#[tokio::test]
+async fn compare_and_swap_replace_only_expected() {
+    let dir = temp_dir("cas-match");
+    let storage = open(&dir).await;
+    let ann = "ann".to_owned();
+
+    let swapped = storage.compare_and_swap(ann.clone(), User::new("ann", 30, "rome"), User::new("ann", 31, "oslo")).await;
+    assert!(swapped.unwrap());
+    assert_eq!(storage.lookup_owned(&ann).unwrap().city, "oslo");
+
+    // expected is stale now
+    let swapped = storage.compare_and_swap(ann.clone(), User::new("ann", 30, "rome"), User::new("ann", 32, "pisa")).await;
+    assert!(!swapped.unwrap());
+    assert_eq!(storage.lookup_owned(&ann).unwrap().age, 31);
+
+    let swapped = storage.compare_and_swap("bob".to_owned(), User::new("bob", 20, "oslo"), User::new("bob", 21, "oslo")).await;
+    assert!(!swapped.unwrap());
+    assert!(storage.lookup_owned(&"bob".to_owned()).is_none());
+
+    close(storage, &dir).await;
+}
+
+// every racer expect the same version, exactly one swap it
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn compare_and_swap_race_has_one_winner() {
+    let dir = temp_dir("cas-race");
+    let storage = open(&dir).await;
+
+    let racers: Vec<_> = (0..RACERS).map(|i| {
+        let storage = storage.clone();
+        tokio::spawn(async move {
+            let new = User::new("ann", 100 + i as i64, "oslo");
+            storage.compare_and_swap("ann".to_owned(), User::new("ann", 30, "rome"), new).await.unwrap()
+        })
+    }).collect();
+
+    let mut winners = vec![];
+    for (i, racer) in racers.into_iter().enumerate() {
+        if racer.await.unwrap() {
+            winners.push(i);
+        }
+    }
+    assert_eq!(winners.len(), 1, "{:?}", winners);
+    assert_eq!(storage.lookup_owned(&"ann".to_owned()).unwrap().age, 100 + winners[0] as i64);
+
+    close(storage, &dir).await;
+}
+
+#[tokio::test]
+async fn insert_if_absent_keep_present_document() {
+    let dir = temp_dir("absent-present");
+    let storage = open(&dir).await;
+
+    let inserted = storage.insert_if_absent("ann".to_owned(), User::new("ann", 99, "oslo")).await;
+    assert!(!inserted.unwrap());
+    assert_eq!(storage.lookup_owned(&"ann".to_owned()).unwrap().age, 30);
+
+    let inserted = storage.insert_if_absent("bob".to_owned(), User::new("bob", 20, "oslo")).await;
+    assert!(inserted.unwrap());
+    assert_eq!(storage.lookup_owned(&"bob".to_owned()).unwrap().city, "oslo");
+    assert_eq!(storage.len(), 2);
+
+    close(storage, &dir).await;
+}
+
+// racers insert a missing key, exactly one insert and its document is kept
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn insert_if_absent_race_has_one_winner() {
+    let dir = temp_dir("absent-race");
+    let storage = open(&dir).await;
+
+    let racers: Vec<_> = (0..RACERS).map(|i| {
+        let storage = storage.clone();
+        tokio::spawn(async move { storage.insert_if_absent("bob".to_owned(), User::new("bob", i as i64, "oslo")).await.unwrap() })
+    }).collect();
+
+    let mut winners = vec![];
+    for (i, racer) in racers.into_iter().enumerate() {
+        if racer.await.unwrap() {
+            winners.push(i);
+        }
+    }
+    assert_eq!(winners.len(), 1, "{:?}", winners);
+    assert_eq!(storage.lookup_owned(&"bob".to_owned()).unwrap().age, winners[0] as i64);
+
+    close(storage, &dir).await;
+}
+
+// racers ask a missing key, default is built once and every racer get its document
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn get_or_insert_run_default_once() {
+    let dir = temp_dir("get-or-insert");
+    let storage = open(&dir).await;
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    let racers: Vec<_> = (0..RACERS).map(|i| {
+        let (storage, runs) = (storage.clone(), runs.clone());
+        tokio::spawn(async move {
+            let doc = storage.get_or_insert("bob".to_owned(), || {
+                runs.fetch_add(1, Ordering::SeqCst);
+                User::new("bob", i as i64, "oslo")
+            });
+            doc.await.unwrap().value().age
+        })
+    }).collect();
+
+    let mut ages = vec![];
+    for racer in racers {
+        ages.push(racer.await.unwrap());
+    }
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    ages.dedup();
+    assert_eq!(ages.len(), 1, "{:?}", ages);
+
+    // present key: default not run
+    let ann = storage.get_or_insert("ann".to_owned(), || unreachable!()).await.unwrap().value().clone();
+    assert_eq!(ann.age, 30);
+
+    close(storage, &dir).await;
+}
+
+#[tokio::test]
+async fn remove_if_remove_only_accepted() {
+    let dir = temp_dir("remove-if");
+    let storage = open(&dir).await;
+    let ann = "ann".to_owned();
+
+    assert!(!storage.remove_if(ann.clone(), |user| user.age > 40).await.unwrap());
+    assert!(storage.lookup_owned(&ann).is_some());
+
+    assert!(storage.remove_if(ann.clone(), |user| user.city == "rome").await.unwrap());
+    assert!(storage.lookup_owned(&ann).is_none());
+
+    // missing key: predicate never see a document
+    assert!(!storage.remove_if(ann.clone(), |_| unreachable!()).await.unwrap());
+    assert!(storage.is_empty());
+
+    close(storage, &dir).await;
+}
@@ -0,0 +1,207 @@
+mod common;
+
+use common::{temp_dir, User};
+use darkbird::{
+    ClockSkewPolicy, EvictionPolicy, Options, RecoveryMode, Schema, SchemaError, StoreConfig, WalCodec,
+};
+use serde_json::{json, Value};
+
+
+
+fn base() -> Value {
+    json!({ "path": "/tmp/darkbird", "storage_name": "users", "storage_type": "DiskCopies" })
+}
+
+fn config(fields: Value) -> StoreConfig {
+    let mut cfg = base();
+    for (field, value) in fields.as_object().unwrap() {
+        cfg[field] = value.clone();
+    }
+    serde_json::from_value(cfg).unwrap()
+}
+
+// field and reason of error building Options
+fn rejected(fields: Value) -> (&'static str, String) {
+    match Options::from_config(&config(fields)) {
+        Err(e) => (e.field, e.reason),
+        Ok(_) => panic!("config accepted"),
+    }
+}
+
+
+
+#[test]
+fn every_knob_reach_options() {
+    let cfg = config(json!({
+        "total_page_size": 500,
+        "coalesce_ms": 20,
+        "lag_threshold": 64,
+        "value_compression": "Lz4",
+        "lanes": 4,
+        "admin_log": "/tmp/darkbird/admin.log",
+        "timers": true,
+        "read_snapshot_ms": 250,
+        "schema_override": true,
+        "track_access": true,
+        "ordered_keys": true,
+        "low_memory_replay": true,
+        "previous_values": true,
+        "event_retention": 1000,
+        "event_retention_ms": 60000,
+        "wal_codec": "Json",
+        "wal_writer": "Thread",
+        "recovery": "SkipCorrupted",
+        "clock_skew_ms": 30000,
+        "clock_skew_policy": "TrustLog",
+        "capacity": 10000,
+        "eviction": "EvictLru",
+        "query_cache": 128,
+        "query_cache_bytes": 1048576,
+        "bloom_filter": 10000,
+        "bloom_false_positive_rate": 0.01,
+        "disk_space_threshold": 1073741824,
+        "read_only_floor": 104857600,
+    }));
+
+    let caps = Options::from_config(&cfg).unwrap().capabilities();
+
+    assert_eq!((caps.total_page_size, caps.coalesce_ms, caps.lag_threshold, caps.lanes), (500, Some(20), Some(64), 4));
+    assert!(caps.value_compression.is_some());
+    assert_eq!(caps.admin_log.as_deref(), Some("/tmp/darkbird/admin.log"));
+    assert!(caps.timers && caps.schema_override && caps.track_access);
+    assert!(caps.ordered_keys && caps.low_memory_replay && caps.previous_values);
+    assert_eq!(caps.read_snapshot_ms, Some(250));
+    assert_eq!((caps.event_retention, caps.event_retention_ms), (Some(1000), Some(60000)));
+    assert_eq!(caps.wal_codec, WalCodec::Json);
+    assert_eq!(caps.recovery, RecoveryMode::SkipCorrupted);
+    assert_eq!((caps.clock_skew_ms, caps.clock_skew_policy), (Some(30000), ClockSkewPolicy::TrustLog));
+    assert_eq!((caps.capacity, caps.eviction), (Some(10000), EvictionPolicy::EvictLru));
+    assert_eq!((caps.query_cache, caps.query_cache_bytes), (Some(128), Some(1048576)));
+    assert_eq!((caps.bloom_filter, caps.bloom_false_positive_rate), (Some(10000), Some(0.01)));
+    assert_eq!((caps.disk_space_threshold, caps.read_only_floor), (Some(1073741824), Some(104857600)));
+}
+
+#[test]
+fn omitted_knobs_keep_options_defaults() {
+    let from_config = Options::from_config(&config(json!({}))).unwrap().capabilities();
+    let built = Options::new("/tmp/darkbird", "users", from_config.total_page_size, darkbird::StorageType::DiskCopies, false)
+        .capabilities();
+
+    // Capabilities has no PartialEq, serialized forms compare every field
+    assert_eq!(serde_json::to_value(&from_config).unwrap(), serde_json::to_value(&built).unwrap());
+}
+
+#[test]
+fn policy_alone_default_its_partner() {
+    let caps = Options::from_config(&config(json!({ "capacity": 10, "clock_skew_ms": 1000 }))).unwrap().capabilities();
+    assert_eq!(caps.eviction, EvictionPolicy::RejectNew);
+    assert_eq!(caps.clock_skew_policy, ClockSkewPolicy::TrustClock);
+}
+
+#[test]
+fn zero_values_are_rejected_by_field() {
+    for field in [
+        "total_page_size",
+        "lanes",
+        "coalesce_ms",
+        "read_snapshot_ms",
+        "clock_skew_ms",
+        "capacity",
+        "bloom_filter",
+        "disk_space_threshold",
+    ] {
+        let mut fields = json!({ field: 0 });
+        if field == "bloom_filter" {
+            fields["bloom_false_positive_rate"] = json!(0.01);
+        }
+        assert_eq!(rejected(fields), (field, "must be greater than zero".to_owned()), "{}", field);
+    }
+
+    // pairs check both halves
+    assert_eq!(rejected(json!({ "event_retention": 0, "event_retention_ms": 10 })).0, "event_retention");
+    assert_eq!(rejected(json!({ "event_retention": 10, "event_retention_ms": 0 })).0, "event_retention_ms");
+    assert_eq!(rejected(json!({ "query_cache": 0, "query_cache_bytes": 10 })).0, "query_cache");
+    assert_eq!(rejected(json!({ "query_cache": 10, "query_cache_bytes": 0 })).0, "query_cache_bytes");
+}
+
+#[test]
+fn half_of_a_pair_is_rejected() {
+    let cases = [
+        (json!({ "event_retention": 10 }), "event_retention", "require event_retention_ms"),
+        (json!({ "event_retention_ms": 10 }), "event_retention_ms", "require event_retention"),
+        (json!({ "query_cache": 10 }), "query_cache", "require query_cache_bytes"),
+        (json!({ "query_cache_bytes": 10 }), "query_cache_bytes", "require query_cache"),
+        (json!({ "bloom_filter": 10 }), "bloom_filter", "require bloom_false_positive_rate"),
+        (json!({ "bloom_false_positive_rate": 0.01 }), "bloom_false_positive_rate", "require bloom_filter"),
+        (json!({ "eviction": "EvictOldest" }), "eviction", "require capacity"),
+        (json!({ "clock_skew_policy": "Fail" }), "clock_skew_policy", "require clock_skew_ms"),
+        (json!({ "read_only_floor": 10 }), "read_only_floor", "require disk_space_threshold"),
+    ];
+
+    for (fields, field, reason) in cases {
+        assert_eq!(rejected(fields), (field, reason.to_owned()));
+    }
+}
+
+#[test]
+fn out_of_range_values_are_rejected() {
+    for rate in [0.0, -0.1, 0.6, 1.0] {
+        let (field, reason) = rejected(json!({ "bloom_filter": 10, "bloom_false_positive_rate": rate }));
+        assert_eq!((field, reason.as_str()), ("bloom_false_positive_rate", "must be in (0, 0.5]"));
+    }
+
+    let (field, reason) = rejected(json!({ "disk_space_threshold": 100, "read_only_floor": 101 }));
+    assert_eq!((field, reason.as_str()), ("read_only_floor", "must not exceed disk_space_threshold"));
+    assert!(Options::from_config(&config(json!({ "disk_space_threshold": 100, "read_only_floor": 100 }))).is_ok());
+}
+
+#[test]
+fn reporter_knobs_need_reporter() {
+    assert_eq!(rejected(json!({ "off_reporter": true, "timers": true })).0, "timers");
+
+    let (field, reason) = rejected(json!({ "off_reporter": true, "event_retention": 10, "event_retention_ms": 10 }));
+    assert_eq!(field, "event_retention");
+    assert!(reason.contains("off_reporter"));
+}
+
+#[test]
+fn unknown_variant_fail_deserialization() {
+    let mut cfg = base();
+    cfg["recovery"] = json!("Lenient");
+    let err = serde_json::from_value::<StoreConfig>(cfg).unwrap_err();
+    assert!(err.to_string().contains("Lenient"), "{}", err);
+}
+
+#[tokio::test]
+async fn schema_open_store_from_config_or_report_field() {
+    let dir = temp_dir("config-schema");
+    let path = dir.to_str().unwrap();
+
+    let cfg: StoreConfig = serde_json::from_value(json!({
+        "path": path,
+        "storage_name": "users",
+        "storage_type": "DiskCopies",
+        "capacity": 1,
+        "eviction": "EvictOldest",
+        "wal_codec": "Json",
+    }))
+    .unwrap();
+    let db = Schema::new().with_store_config::<String, User>(&cfg).await.unwrap().build();
+
+    // capacity and eviction applied
+    db.insert::<String, User>("ann".to_owned(), User::new("ann", 30, "rome")).await.unwrap();
+    db.insert::<String, User>("bob".to_owned(), User::new("bob", 20, "oslo")).await.unwrap();
+    assert!(db.lookup_owned::<String, User>(&"ann".to_owned()).unwrap().is_none());
+    assert!(db.lookup_owned::<String, User>(&"bob".to_owned()).unwrap().is_some());
+    db.close_all().await;
+
+    let mut bad = cfg.clone();
+    bad.query_cache = Some(8);
+    match Schema::new().with_store_config::<String, User>(&bad).await {
+        Err(SchemaError::Config(e)) => assert_eq!(e.field, "query_cache"),
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(_) => panic!("config accepted"),
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::{disk_options, temp_dir, Order};
+use darkbird::{ExportOptions, Storage};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+const DOCS: u64 = 200_000;
+
+// bound of insert p99 while scans run
+const P99: Duration = Duration::from_millis(25);
+
+
+
+// an export per worker, so no worker is left idle for inserts unless scans yield
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn inserts_keep_latency_during_exports() {
+    let dir = temp_dir("coop-export");
+    let storage = Arc::new(Storage::<u64, Order>::open(disk_options(&dir, "orders")).await.unwrap());
+
+    let batch: Vec<(u64, Order)> = (0..DOCS)
+        .map(|key| (key, Order { user: format!("u{}", key % 100), item: format!("item-{}", key) }))
+        .collect();
+    storage.insert_batch(batch).await.unwrap();
+
+    let exports: Vec<_> = (0..2)
+        .map(|_| {
+            let storage = storage.clone();
+            tokio::spawn(async move { storage.export(std::io::sink(), &ExportOptions::new()).await.unwrap() })
+        })
+        .collect();
+
+    // on a worker like exports, test body isn't
+    let done = Arc::new(AtomicBool::new(false));
+    let inserts = {
+        let (storage, done) = (storage.clone(), done.clone());
+        tokio::spawn(async move {
+            let mut latencies = vec![];
+            let mut key = DOCS;
+            while !done.load(Ordering::SeqCst) {
+                let started = Instant::now();
+                storage.insert(key, Order { user: "late".to_owned(), item: key.to_string() }).await.unwrap();
+                latencies.push(started.elapsed());
+                key += 1;
+                tokio::task::yield_now().await;
+            }
+            latencies
+        })
+    };
+
+    for export in exports {
+        assert!(export.await.unwrap() as u64 >= DOCS);
+    }
+    done.store(true, Ordering::SeqCst);
+    let mut latencies = inserts.await.unwrap();
+
+    // enough inserts ran alongside exports to read a p99
+    assert!(latencies.len() >= 100, "{} inserts during exports", latencies.len());
+    latencies.sort();
+    let p99 = latencies[latencies.len() * 99 / 100];
+    assert!(p99 < P99, "insert p99 {:?} over {} inserts", p99, latencies.len());
+
+    Arc::try_unwrap(storage).ok().unwrap().close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
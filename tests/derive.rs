@@ -0,0 +1,157 @@
+mod common;
+
+use common::{disk_options, temp_dir, Order, User};
+use darkbird::{Database, Options, Schema, StorageType};
+use std::{path::Path, time::Duration};
+
+
+
+async fn open(dir: &Path) -> Database {
+    // reporter on
+    let users = Options::new(dir.to_str().unwrap(), "users", 1000, StorageType::DiskCopies, false)
+        .with_previous_values()
+        .with_event_retention(1000, Duration::from_secs(600));
+
+    Schema::new()
+        .with_datastore::<String, User>(users)
+        .await
+        .unwrap()
+        .with_datastore::<String, Order>(disk_options(dir, "orders"))
+        .await
+        .unwrap()
+        .build()
+}
+
+// adults only, keyed by name
+fn v1(name: &String, user: &User) -> Option<(String, Order)> {
+    (user.age >= 18).then(|| (format!("order:{}", name), Order { user: name.clone(), item: user.city.clone() }))
+}
+
+fn v2(name: &String, user: &User) -> Option<(String, Order)> {
+    (user.age >= 18).then(|| (format!("order:{}", name), Order { user: name.clone(), item: format!("v2 {}", user.city) }))
+}
+
+async fn insert(db: &Database, name: &str, age: i64, city: &str) {
+    db.insert::<String, User>(name.to_owned(), User::new(name, age, city)).await.unwrap();
+}
+
+fn item(db: &Database, name: &str) -> Option<String> {
+    db.lookup_owned::<String, Order>(&format!("order:{}", name)).unwrap().map(|order| order.item)
+}
+
+async fn close(db: Database) {
+    for (store, res) in db.close_all().await {
+        res.unwrap_or_else(|e| panic!("{}: {}", store, e.to_string()));
+    }
+}
+
+
+
+#[tokio::test]
+async fn backfill_then_follow_removes_and_replaces() {
+    let dir = temp_dir("derive-follow");
+    let db = open(&dir).await;
+    insert(&db, "ann", 30, "rome").await;
+    insert(&db, "bob", 12, "oslo").await;
+    insert(&db, "cid", 40, "lima").await;
+
+    let derivation = db.derive::<String, User, String, Order, _>(1, v1).await.unwrap();
+    assert_eq!(derivation.backfilled(), Some(2));
+    assert_eq!(item(&db, "ann").as_deref(), Some("rome"));
+    assert_eq!(item(&db, "bob"), None);
+
+    // updated, removed, and one turning adult
+    db.update::<String, User, _>(&"ann".to_owned(), |user| user.city = "pisa".to_owned()).await.unwrap();
+    db.remove::<String, User>("cid".to_owned()).await.unwrap();
+    db.update::<String, User, _>(&"bob".to_owned(), |user| user.age = 18).await.unwrap();
+    derivation.finish().await.unwrap();
+
+    assert_eq!(item(&db, "ann").as_deref(), Some("pisa"));
+    assert_eq!(item(&db, "cid"), None);
+    assert_eq!(item(&db, "bob").as_deref(), Some("oslo"));
+
+    close(db).await;
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn version_bump_backfill_again() {
+    let dir = temp_dir("derive-version");
+    let db = open(&dir).await;
+    insert(&db, "ann", 30, "rome").await;
+
+    db.derive::<String, User, String, Order, _>(1, v1).await.unwrap().finish().await.unwrap();
+    assert_eq!(item(&db, "ann").as_deref(), Some("rome"));
+
+    let derivation = db.derive::<String, User, String, Order, _>(2, v2).await.unwrap();
+    assert_eq!(derivation.backfilled(), Some(1));
+    derivation.finish().await.unwrap();
+    assert_eq!(item(&db, "ann").as_deref(), Some("v2 rome"));
+
+    close(db).await;
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn restart_resume_without_backfill() {
+    let dir = temp_dir("derive-restart");
+    let db = open(&dir).await;
+    insert(&db, "ann", 30, "rome").await;
+    db.derive::<String, User, String, Order, _>(1, v1).await.unwrap().finish().await.unwrap();
+    close(db).await;
+
+    let db = open(&dir).await;
+    let derivation = db.derive::<String, User, String, Order, _>(1, v1).await.unwrap();
+    assert_eq!(derivation.backfilled(), None);
+    assert_eq!(item(&db, "ann").as_deref(), Some("rome"));
+
+    // and follow as before
+    insert(&db, "bob", 20, "oslo").await;
+    derivation.finish().await.unwrap();
+    assert_eq!(item(&db, "bob").as_deref(), Some("oslo"));
+
+    close(db).await;
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn writes_while_down_are_replayed() {
+    let dir = temp_dir("derive-down");
+    let db = open(&dir).await;
+    insert(&db, "ann", 30, "rome").await;
+    db.derive::<String, User, String, Order, _>(1, v1).await.unwrap().finish().await.unwrap();
+
+    // no derivation running
+    insert(&db, "bob", 20, "oslo").await;
+    db.remove::<String, User>("ann".to_owned()).await.unwrap();
+
+    let derivation = db.derive::<String, User, String, Order, _>(1, v1).await.unwrap();
+    assert_eq!(derivation.backfilled(), None);
+    derivation.finish().await.unwrap();
+    assert_eq!(item(&db, "bob").as_deref(), Some("oslo"));
+    assert_eq!(item(&db, "ann"), None);
+
+    close(db).await;
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn writes_before_restart_backfill() {
+    let dir = temp_dir("derive-down-restart");
+    let db = open(&dir).await;
+    insert(&db, "ann", 30, "rome").await;
+    db.derive::<String, User, String, Order, _>(1, v1).await.unwrap().finish().await.unwrap();
+
+    // written after finish, not retained across reopen
+    insert(&db, "bob", 20, "oslo").await;
+    close(db).await;
+
+    let db = open(&dir).await;
+    let derivation = db.derive::<String, User, String, Order, _>(1, v1).await.unwrap();
+    assert_eq!(derivation.backfilled(), Some(2));
+    derivation.finish().await.unwrap();
+    assert_eq!(item(&db, "bob").as_deref(), Some("oslo"));
+
+    close(db).await;
+    let _ = std::fs::remove_dir_all(&dir);
+}
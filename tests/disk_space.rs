@@ -0,0 +1,91 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use darkbird::{RQuery, SessionResult, Storage};
+use std::{path::Path, time::Duration};
+
+
+
+// operations of notices in admin log, those emitted before a receiver could subscribe included
+fn notices(log: &Path) -> Vec<String> {
+    std::fs::read_to_string(log)
+        .unwrap_or_default()
+        .lines()
+        .map(|line| line.split('\t').collect::<Vec<_>>())
+        .filter(|fields| fields[3] == "notice")
+        .map(|fields| fields[2].to_owned())
+        .collect()
+}
+
+// checks of disk watch due within duration run on paused time, auto-advanced
+// from timer to timer, then real time again
+async fn pass_checks(duration: Duration) {
+    tokio::time::pause();
+    tokio::time::sleep(duration).await;
+    tokio::time::resume();
+}
+
+// a floor above any free space, as a volume filled up would be
+#[tokio::test]
+async fn store_turn_read_only_below_floor() {
+    let dir = temp_dir("disk-floor");
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+    storage.insert("ann".to_owned(), User::new("ann", 30, "rome")).await.unwrap();
+    storage.close().await.unwrap();
+
+    let log = dir.join("admin.log");
+    let ops = disk_options(&dir, "users")
+        .with_admin_log(log.to_str().unwrap())
+        .with_disk_space_watch(u64::MAX, Some(u64::MAX));
+    let storage = Storage::<String, User>::open(ops).await.unwrap();
+
+    // noticed once each, though checked again every second
+    pass_checks(Duration::from_millis(2500)).await;
+    assert_eq!(notices(&log), vec!["disk_space_low", "read_only"]);
+    assert!(storage.stats().read_only);
+
+    let res = storage.insert("bob".to_owned(), User::new("bob", 20, "oslo")).await;
+    assert!(matches!(res, Err(SessionResult::ReadOnly)), "{:?}", res.err());
+    assert!(matches!(storage.remove("ann".to_owned()).await, Err(SessionResult::ReadOnly)));
+    let batch = vec![("bob".to_owned(), User::new("bob", 20, "oslo"))];
+    assert!(matches!(storage.insert_batch(batch).await, Err(SessionResult::ReadOnly)));
+    assert!(matches!(storage.remove_batch(vec!["ann".to_owned()]).await, Err(SessionResult::ReadOnly)));
+    assert!(matches!(storage.retain(|_, _| false).await, Err(SessionResult::ReadOnly)));
+    assert!(matches!(storage.clear().await, Err(SessionResult::ReadOnly)));
+    let queries = vec![RQuery::Remove("ann".to_owned())];
+    assert!(matches!(storage.transaction(queries).await, Err(SessionResult::ReadOnly)));
+
+    // reads served, memory untouched
+    assert_eq!(storage.len(), 1);
+    assert!(storage.lookup_owned(&"ann".to_owned()).is_some());
+    assert!(storage.lookup_owned(&"bob".to_owned()).is_none());
+    storage.close().await.unwrap();
+
+    // WAL intact
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+    assert_eq!(storage.len(), 1);
+    assert!(!storage.stats().read_only);
+    storage.close().await.unwrap();
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn store_writable_above_floor() {
+    let dir = temp_dir("disk-room");
+    let log = dir.join("admin.log");
+    let ops = disk_options(&dir, "users")
+        .with_admin_log(log.to_str().unwrap())
+        .with_disk_space_watch(1, Some(1));
+    let storage = Storage::<String, User>::open(ops).await.unwrap();
+
+    pass_checks(Duration::from_millis(1500)).await;
+    assert!(notices(&log).is_empty());
+    storage.insert("ann".to_owned(), User::new("ann", 30, "rome")).await.unwrap();
+    assert!(!storage.stats().read_only);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use common::{ram_options, temp_dir};
+use darkbird::{
+    document::{Document, FullText, Indexer, MaterializedView, Range, Tags},
+    ExportFormat, ExportOptions, Storage,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const PROFILES: u64 = 200;
+
+
+
+/// document with a map field, iterated in a different order by every HashMap
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Profile {
+    name: String,
+    scores: HashMap<String, u64>,
+}
+
+impl Document for Profile {}
+
+impl Indexer for Profile {
+    fn extract(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Tags for Profile {
+    fn get_tags(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Range for Profile {}
+
+impl MaterializedView for Profile {
+    fn filter(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FullText for Profile {
+    fn get_content(&self) -> Option<String> {
+        None
+    }
+}
+
+
+
+fn profile(id: u64) -> Profile {
+    let scores = (0..8).map(|game| (format!("game{}", game), id * game)).collect();
+    Profile { name: format!("player{}", id), scores }
+}
+
+// same documents, inserted in order of keys
+async fn filled(dir: &std::path::Path, name: &str, keys: impl Iterator<Item = u64>) -> Storage<u64, Profile> {
+    let storage = Storage::open(ram_options(dir, name)).await.unwrap();
+    for key in keys {
+        storage.insert(key, profile(key)).await.unwrap();
+    }
+    storage
+}
+
+async fn export(storage: &Storage<u64, Profile>, opts: &ExportOptions) -> Vec<u8> {
+    let mut out = vec![];
+    assert_eq!(storage.export(&mut out, opts).await.unwrap(), PROFILES as usize);
+    out
+}
+
+#[tokio::test]
+async fn ordered_exports_are_byte_identical() {
+    let dir = temp_dir("export-ordered");
+    let forward = filled(&dir, "forward", 0..PROFILES).await;
+    let backward = filled(&dir, "backward", (0..PROFILES).rev()).await;
+
+    // small runs, so keys are spilled and merged
+    let spill = dir.join("spill");
+    std::fs::create_dir_all(&spill).unwrap();
+    let ordered = ExportOptions::new().with_ordered(true).with_run_size(16).with_spill_dir(spill.to_str().unwrap());
+
+    for format in [ExportFormat::JsonLines, ExportFormat::JsonArray] {
+        let opts = ordered.clone().with_format(format);
+        let first = export(&forward, &opts).await;
+        assert_eq!(export(&forward, &opts).await, first, "{:?}", format);
+
+        // other store, other map iteration order
+        assert_eq!(export(&backward, &opts).await, first, "{:?}", format);
+    }
+
+    let opts = ordered.clone().with_format(ExportFormat::Bincode);
+    assert_eq!(export(&forward, &opts).await, export(&forward, &opts).await);
+
+    // keys in order, map fields with sorted keys
+    let lines = export(&forward, &ordered).await;
+    let records: Vec<serde_json::Value> = lines.split(|b| *b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect();
+    let keys: Vec<u64> = records.iter().map(|record| record["key"].as_u64().unwrap()).collect();
+    assert_eq!(keys, (0..PROFILES).collect::<Vec<_>>());
+
+    let line = std::str::from_utf8(lines.split(|b| *b == b'\n').next().unwrap()).unwrap();
+    let games: Vec<usize> = (0..8).map(|game| line.find(&format!("\"game{}\"", game)).unwrap()).collect();
+    assert!(games.windows(2).all(|pair| pair[0] < pair[1]), "{}", line);
+
+    // spilled runs are removed
+    assert_eq!(std::fs::read_dir(&spill).unwrap().count(), 0);
+
+    forward.close().await.unwrap();
+    backward.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
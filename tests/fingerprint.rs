@@ -0,0 +1,100 @@
+mod common;
+
+use common::{disk_options, temp_dir, Order, User};
+use darkbird::{
+    document::{Document, FullText, Indexer, MaterializedView, Range, Tags},
+    Storage,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+
+
+/// `Order` renamed, same shape, so its WAL decode as is
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Purchase {
+    user: String,
+    item: String,
+}
+
+impl Document for Purchase {}
+
+impl Indexer for Purchase {
+    fn extract(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Tags for Purchase {
+    fn get_tags(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Range for Purchase {}
+
+impl MaterializedView for Purchase {
+    fn filter(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FullText for Purchase {
+    fn get_content(&self) -> Option<String> {
+        None
+    }
+}
+
+
+
+async fn orders(dir: &Path) {
+    let storage = Storage::<String, Order>::open(disk_options(dir, "orders")).await.unwrap();
+    storage.insert("o1".to_owned(), Order { user: "ann".to_owned(), item: "pen".to_owned() }).await.unwrap();
+    storage.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn mismatched_types_are_rejected_and_wal_kept() {
+    let dir = temp_dir("fingerprint-mismatch");
+    orders(&dir).await;
+    let wal = std::fs::read_dir(dir.join("orders")).unwrap().count();
+
+    let err = Storage::<String, User>::open(disk_options(&dir, "orders")).await.err().unwrap();
+    assert!(err.starts_with("SchemaMismatch"), "{}", err);
+    assert!(err.contains("Order") && err.contains("User"), "{}", err);
+
+    let err = Storage::<u64, Order>::open(disk_options(&dir, "orders")).await.err().unwrap();
+    assert!(err.starts_with("SchemaMismatch"), "{}", err);
+
+    // same types open as before
+    assert_eq!(std::fs::read_dir(dir.join("orders")).unwrap().count(), wal);
+    let storage = Storage::<String, Order>::open(disk_options(&dir, "orders")).await.unwrap();
+    assert_eq!(storage.lookup_owned(&"o1".to_owned()).unwrap().item, "pen");
+    storage.close().await.unwrap();
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn override_record_new_types() {
+    let dir = temp_dir("fingerprint-override");
+    orders(&dir).await;
+
+    // a failed replay doesn't record types
+    assert!(Storage::<String, User>::open(disk_options(&dir, "orders").with_schema_override()).await.is_err());
+    let err = Storage::<String, Purchase>::open(disk_options(&dir, "orders")).await.err().unwrap();
+    assert!(err.contains("Order"), "{}", err);
+
+    let storage = Storage::<String, Purchase>::open(disk_options(&dir, "orders").with_schema_override()).await.unwrap();
+    assert_eq!(storage.lookup_owned(&"o1".to_owned()).unwrap().item, "pen");
+    storage.close().await.unwrap();
+
+    // new types are the recorded ones
+    let storage = Storage::<String, Purchase>::open(disk_options(&dir, "orders")).await.unwrap();
+    assert_eq!(storage.len(), 1);
+    storage.close().await.unwrap();
+    let err = Storage::<String, Order>::open(disk_options(&dir, "orders")).await.err().unwrap();
+    assert!(err.starts_with("SchemaMismatch"), "{}", err);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
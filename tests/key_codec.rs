@@ -0,0 +1,122 @@
+mod common;
+
+use common::{ram_options, temp_dir, Order};
+use darkbird::{Compression, KeyCodec, SessionResult, Storage};
+use std::fmt::Debug;
+
+
+
+// every key decode back to itself, and byte order of encodings equal Ord of keys
+fn check<K: KeyCodec + Ord + Debug + Clone>(mut keys: Vec<K>) {
+    for key in keys.iter() {
+        assert_eq!(K::decode(&key.encode()).as_ref(), Some(key), "round trip of {:?}", key);
+    }
+
+    keys.sort();
+    let mut encoded: Vec<(Vec<u8>, K)> = keys.iter().map(|key| (key.encode(), key.clone())).collect();
+    encoded.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(encoded.into_iter().map(|(_, key)| key).collect::<Vec<_>>(), keys);
+}
+
+#[test]
+fn integers_round_trip_in_order() {
+    check(vec![0u8, 1, 127, 128, 255]);
+    check(vec![0u64, 1, 255, 256, u32::MAX as u64, u64::MAX - 1, u64::MAX]);
+    check(vec![i8::MIN, -1, 0, 1, i8::MAX]);
+    check(vec![i32::MIN, -65536, -256, -1, 0, 1, 256, i32::MAX]);
+    check(vec![i64::MIN, i64::MIN + 1, -1, 0, 1, i64::MAX]);
+    check(vec![i128::MIN, -1, 0, u64::MAX as i128 + 1, i128::MAX]);
+}
+
+#[test]
+fn strings_round_trip_in_order() {
+    check(vec![
+        String::new(),
+        "\0".to_owned(),
+        "\0\0".to_owned(),
+        "\0a".to_owned(),
+        "a".to_owned(),
+        "a\0".to_owned(),
+        "a\0b".to_owned(),
+        "ab".to_owned(),
+        "b".to_owned(),
+        "\u{ff}".to_owned(),
+        "żółw".to_owned(),
+    ]);
+}
+
+#[test]
+fn tuples_order_component_by_component() {
+    // a shorter first string sort before, whatever follow it
+    check(vec![
+        ("a".to_owned(), 9u64),
+        ("a".to_owned(), 10),
+        ("a\0".to_owned(), 0),
+        ("ab".to_owned(), 0),
+        ("b".to_owned(), 0),
+    ]);
+    check(vec![(-1i32, 2u8, "x".to_owned()), (-1, 2, "y".to_owned()), (0, 0, String::new()), (0, 1, String::new())]);
+}
+
+#[test]
+fn malformed_bytes_decode_to_none() {
+    assert_eq!(u64::decode(&[0, 1, 2]), None);
+    assert_eq!(u64::decode(&[0; 9]), None);
+    assert_eq!(String::decode(b"abc"), None);
+    assert_eq!(String::decode(&[b'a', 0x00, 0x01]), None);
+    assert_eq!(String::decode(&[0xC3, 0x00, 0x00]), None);
+    assert_eq!(<(u8, String)>::decode(&[1]), None);
+}
+
+#[tokio::test]
+async fn scans_follow_encoded_order() {
+    let dir = temp_dir("key-codec");
+    let storage = Storage::<(u64, String), Order>::open(ram_options(&dir, "sessions")).await.unwrap();
+
+    for user in [7u64, 300, 8] {
+        for session in ["b", "a", "c"] {
+            let order = Order { user: user.to_string(), item: session.to_owned() };
+            storage.insert((user, session.to_owned()), order).await.unwrap();
+        }
+    }
+
+    let keys = |docs: Vec<dashmap::mapref::one::Ref<'_, (u64, String), Order>>| {
+        docs.iter().map(|doc| doc.key().clone()).collect::<Vec<_>>()
+    };
+
+    let sessions = keys(storage.scan_prefix(&7u64.encode()).unwrap());
+    assert_eq!(sessions, vec![(7, "a".to_owned()), (7, "b".to_owned()), (7, "c".to_owned())]);
+
+    let range = keys(storage.key_range(&(7, "b".to_owned()), &(300, "b".to_owned())).unwrap());
+    let expected: Vec<(u64, String)> = vec![(7, "b"), (7, "c"), (8, "a"), (8, "b"), (8, "c"), (300, "a")]
+        .into_iter()
+        .map(|(user, session)| (user, session.to_owned()))
+        .collect();
+    assert_eq!(range, expected);
+
+    // ordered once, then kept by writes
+    storage.remove((7, "a".to_owned())).await.unwrap();
+    storage.insert((7, "0".to_owned()), Order { user: "7".to_owned(), item: "0".to_owned() }).await.unwrap();
+    let sessions = keys(storage.scan_prefix(&7u64.encode()).unwrap());
+    assert_eq!(sessions, vec![(7, "0".to_owned()), (7, "b".to_owned()), (7, "c".to_owned())]);
+
+    // empty and reversed range
+    assert!(storage.key_range(&(8, "a".to_owned()), &(8, "a".to_owned())).unwrap().is_empty());
+    assert!(storage.key_range(&(300, "a".to_owned()), &(7, "a".to_owned())).unwrap().is_empty());
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn scans_fail_with_compressed_value() {
+    let dir = temp_dir("key-codec-compressed");
+    let storage = Storage::<(u64, String), Order>::open(ram_options(&dir, "sessions").with_value_compression(Compression::Lz4)).await.unwrap();
+    storage.insert((7, "a".to_owned()), Order { user: "7".to_owned(), item: "a".to_owned() }).await.unwrap();
+
+    assert!(matches!(storage.scan_prefix(&7u64.encode()), Err(SessionResult::CompressedValue)));
+    assert!(matches!(storage.key_range(&(0, String::new()), &(9, String::new())), Err(SessionResult::CompressedValue)));
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::{disk_options, temp_dir, Order};
+use darkbird::{RQuery, Storage};
+use std::{path::Path, sync::Arc};
+
+const KEYS: u64 = 32;
+const ROUNDS: u64 = 20;
+
+
+
+async fn open(dir: &Path) -> Arc<Storage<u64, Order>> {
+    Arc::new(Storage::open(disk_options(dir, "orders").with_lanes(4)).await.unwrap())
+}
+
+fn order(round: u64, n: u64) -> Order {
+    Order { user: format!("u{}", n % 3), item: format!("{}-{}", round, n) }
+}
+
+fn state(storage: &Storage<u64, Order>) -> Vec<Option<String>> {
+    (0..KEYS).map(|key| storage.lookup_owned(&key).map(|order| order.item)).collect()
+}
+
+
+
+// single writes queue up on every lane while a transaction and a clear, each logged on one lane,
+// are written: replay must end where memory did
+#[tokio::test]
+async fn records_of_many_keys_replay_in_memory_order_across_lanes() {
+    let dir = temp_dir("lanes-replay");
+    let storage = open(&dir).await;
+
+    for round in 0..ROUNDS {
+        let mut writes = vec![];
+        for n in 0..500 {
+            let storage = storage.clone();
+            writes.push(tokio::spawn(async move { storage.insert(n % KEYS, order(round, n)).await.unwrap() }));
+        }
+        for write in writes {
+            write.await.unwrap();
+        }
+
+        // every key of store, over all lanes
+        let queries = (0..KEYS)
+            .map(|key| match (key + round) % 4 {
+                0 => RQuery::Remove(key),
+                _ => RQuery::Insert(key, order(round, 1000 + key)),
+            })
+            .collect();
+        storage.transaction(queries).await.unwrap();
+
+        if round % 5 == 2 {
+            for n in 0..200 {
+                storage.insert(n % KEYS, order(round, 2000 + n)).await.unwrap();
+            }
+            storage.clear().await.unwrap();
+        }
+    }
+
+    let memory = state(&storage);
+    Arc::try_unwrap(storage).ok().unwrap().close().await.unwrap();
+
+    let storage = open(&dir).await;
+    assert_eq!(state(&storage), memory);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// updates of a key run while transactions write it: each hold the key from log to memory,
+// so no update read a document a transaction replaced before its record was written
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn updates_racing_transactions_replay_in_memory_order() {
+    let dir = temp_dir("lanes-updates");
+    let mut storage = open(&dir).await;
+
+    for key in 0..KEYS {
+        storage.insert(key, order(0, key)).await.unwrap();
+    }
+
+    // a transaction overwrite every key, so replay is compared after each round
+    for round in 0..ROUNDS {
+        let mut writes = vec![];
+        for n in 0..200 {
+            let storage = storage.clone();
+            writes.push(tokio::spawn(async move {
+                storage.update(&(n % KEYS), |order| order.item.push_str(&format!("+{}", n))).await.unwrap();
+            }));
+        }
+
+        let queries = (0..KEYS).map(|key| RQuery::Insert(key, order(round, key))).collect();
+        storage.transaction(queries).await.unwrap();
+
+        for write in writes {
+            write.await.unwrap();
+        }
+
+        let memory = state(&storage);
+        Arc::try_unwrap(storage).ok().unwrap().close().await.unwrap();
+
+        storage = open(&dir).await;
+        assert_eq!(state(&storage), memory, "round {}", round);
+    }
+
+    Arc::try_unwrap(storage).ok().unwrap().close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use bytes::Bytes;
+use common::{disk_options, temp_dir, Order, User};
+use darkbird::{storage_bytes::{BytesStorage, Extractors}, testing::ManualClock, Schema, SessionResult};
+use std::{sync::Arc, time::{Duration, SystemTime}};
+
+
+
+#[tokio::test]
+async fn database_len_after_inserts_and_removes() {
+    let dir = temp_dir("len-database");
+    let clock = Arc::new(ManualClock::new(SystemTime::now()));
+    let db = Schema::new().with_datastore::<String, User>(disk_options(&dir, "users").with_clock(clock.clone())).await.unwrap().build();
+    assert_eq!((db.len::<String, User>().unwrap(), db.is_empty::<String, User>().unwrap()), (0, true));
+
+    for i in 0..10 {
+        let name = format!("u{}", i);
+        db.insert::<String, User>(name.clone(), User::new(&name, i, "rome")).await.unwrap();
+    }
+    for i in 0..3 {
+        db.remove::<String, User>(format!("u{}", i)).await.unwrap();
+    }
+
+    // absent key and updated one count nothing
+    db.remove::<String, User>("nobody".to_owned()).await.unwrap();
+    db.update::<String, User, _>(&"u5".to_owned(), |user| user.age += 1).await.unwrap();
+    assert_eq!((db.len::<String, User>().unwrap(), db.is_empty::<String, User>().unwrap()), (7, false));
+
+    // document past its ttl no longer counted
+    db.insert_with_ttl::<String, User>("brief".to_owned(), User::new("brief", 1, "oslo"), Duration::from_millis(20)).await.unwrap();
+    assert_eq!(db.len::<String, User>().unwrap(), 8);
+    clock.advance(Duration::from_millis(21));
+    assert_eq!(db.len::<String, User>().unwrap(), 7);
+
+    for i in 3..10 {
+        db.remove::<String, User>(format!("u{}", i)).await.unwrap();
+    }
+    assert_eq!((db.len::<String, User>().unwrap(), db.is_empty::<String, User>().unwrap()), (0, true));
+
+    assert!(matches!(db.len::<u64, Order>(), Err(SessionResult::DataStoreNotFound)));
+    assert!(matches!(db.is_empty::<u64, Order>(), Err(SessionResult::DataStoreNotFound)));
+
+    db.close_all().await;
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn bytes_storage_len_after_inserts_and_removes() {
+    let dir = temp_dir("len-bytes");
+    let storage = BytesStorage::<u64>::open(disk_options(&dir, "blobs"), Extractors::new()).await.unwrap();
+    assert!(storage.is_empty());
+
+    for key in 0..10 {
+        storage.insert(key, Bytes::from(vec![key as u8; 64])).await.unwrap();
+    }
+    storage.insert(0, Bytes::from_static(b"replaced")).await.unwrap();
+    for key in 0..4 {
+        storage.remove(key * 2).await.unwrap();
+    }
+    storage.remove(100).await.unwrap();
+    assert_eq!((storage.len(), storage.is_empty()), (6, false));
+
+    for key in [1, 3, 5, 7, 8, 9] {
+        storage.remove(key).await.unwrap();
+    }
+    assert_eq!((storage.len(), storage.is_empty()), (0, true));
+
+    drop(storage);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::{ram_options, temp_dir};
+use darkbird::{
+    document::{Document, FullText, Indexer, MaterializedView, Range, Tags},
+    Storage,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+
+
+/// contact indexed by each of its emails
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Contact {
+    emails: Vec<String>,
+}
+
+impl Document for Contact {}
+
+impl Indexer for Contact {
+    fn extract(&self) -> Vec<String> {
+        self.emails.iter().map(|email| format!("email:{}", email)).collect()
+    }
+}
+
+impl Tags for Contact {
+    fn get_tags(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Range for Contact {}
+
+impl MaterializedView for Contact {
+    fn filter(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FullText for Contact {
+    fn get_content(&self) -> Option<String> {
+        None
+    }
+}
+
+async fn open(dir: &Path) -> Storage<u64, Contact> {
+    let storage = Storage::<u64, Contact>::open(ram_options(dir, "contacts")).await.unwrap();
+    let contacts = [(1, vec!["zoe@work", "ann@home"]), (2, vec!["bob@work", "bob@home"]), (3, vec!["eve@home"])];
+    for (key, emails) in contacts {
+        let emails = emails.into_iter().map(str::to_owned).collect();
+        storage.insert(key, Contact { emails }).await.unwrap();
+    }
+    storage
+}
+
+fn keys(storage: &Storage<u64, Contact>, prefix: &str) -> Vec<u64> {
+    storage.lookup_by_index_prefix(prefix).iter().map(|rf| *rf.key()).collect()
+}
+
+
+
+// in order of first index key matching, a document matching by several keys once
+#[tokio::test]
+async fn index_prefix_in_index_key_order_each_once() {
+    let dir = temp_dir("lookup-index-prefix");
+    let storage = open(&dir).await;
+
+    assert_eq!(keys(&storage, "email:bob@"), [2]);
+    assert_eq!(keys(&storage, "email:"), [1, 2, 3]);
+    assert_eq!(keys(&storage, "email:e"), [3]);
+    assert_eq!(keys(&storage, "email:z"), [1]);
+    assert!(keys(&storage, "email:x").is_empty());
+    assert!(keys(&storage, "phone:").is_empty());
+
+    // removed document leaves prefix
+    storage.remove(2).await.unwrap();
+    assert!(keys(&storage, "email:bob").is_empty());
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn empty_index_prefix_match_every_indexed_document() {
+    let dir = temp_dir("lookup-index-prefix-empty");
+    let storage = open(&dir).await;
+    storage.insert(4, Contact { emails: vec![] }).await.unwrap();
+
+    // 4 has no index key
+    assert_eq!(keys(&storage, ""), [1, 2, 3]);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use bytes::Bytes;
+use darkbird::{
+    storage_bytes::{BytesStorage, Extractors},
+    MetricEvent, MetricsObserver, Order, PageOrder, Storage,
+};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+
+
+#[derive(Clone, Default)]
+struct Count(Arc<AtomicU64>);
+
+impl MetricsObserver for Count {
+    fn observe(&self, _event: MetricEvent) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+
+
+// every read path bump its counter once, a scripted run give exact counts
+#[tokio::test]
+async fn scripted_reads_give_exact_counts() {
+    let dir = temp_dir("metrics-script");
+    let observed = Count::default();
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users").with_metrics_observer(observed.clone()))
+        .await
+        .unwrap();
+
+    for (name, age, city) in [("ann", 30, "rome"), ("bob", 15, "paris"), ("eve", 40, "rome")] {
+        storage.insert(name.to_owned(), User::new(name, age, city)).await.unwrap();
+    }
+    let (ann, zed) = ("ann".to_owned(), "zed".to_owned());
+    let page = PageOrder::Key(Order::Asc);
+
+    // lookups
+    assert!(storage.lookup(&ann).is_some());
+    assert!(storage.lookup(&zed).is_none());
+    assert!(storage.lookup_ref(&ann).unwrap().is_some());
+    assert!(storage.lookup_owned(&"bob".to_owned()).is_some());
+    assert!(storage.lookup_owned(&zed).is_none());
+    assert!(storage.lookup_pinned(&"eve".to_owned()).is_some());
+    assert_eq!(storage.gets(vec![&ann, &zed]).len(), 1);
+    assert!(storage.try_lookup(&ann).unwrap().is_some());
+
+    // index
+    assert!(storage.lookup_by_index("name:ann").is_some());
+    assert!(storage.lookup_by_index("name:zed").is_none());
+    assert!(storage.lookup_by_index_owned("name:bob").is_some());
+
+    // tags
+    assert_eq!(storage.lookup_by_tag("city:rome").len(), 2);
+    assert!(storage.lookup_by_tag("city:oslo").is_empty());
+    assert_eq!(storage.lookup_by_tag_owned("city:paris").len(), 1);
+    assert_eq!(storage.lookup_by_tag_page("city:rome", 0, 1, &page).len(), 1);
+
+    // range
+    assert_eq!(storage.range("age", 18, 50).len(), 2);
+    assert!(storage.range("age", 100, 200).is_empty());
+    assert_eq!(storage.range_owned("age", 0, 20).unwrap().len(), 1);
+    assert_eq!(storage.range_page("age", 0, 100, 0, 10, &page).unwrap().len(), 3);
+
+    // views
+    assert_eq!(storage.fetch_view("adults").len(), 2);
+    assert!(storage.fetch_view("seniors").is_empty());
+    assert_eq!(storage.fetch_view_owned("adults").len(), 2);
+    assert_eq!(storage.fetch_view_page("adults", 0, 1, &page).len(), 1);
+
+    // search
+    assert_eq!(storage.search("rome").len(), 2);
+    assert!(storage.search("tokyo").is_empty());
+    assert_eq!(storage.search_owned("paris").len(), 1);
+    assert_eq!(storage.search_page("rome", 0, 10, &page).len(), 2);
+
+    let metrics = storage.metrics();
+    assert_eq!((metrics.lookup_hits, metrics.lookup_misses), (6, 3));
+    assert_eq!((metrics.index_hits, metrics.index_misses), (2, 1));
+    assert_eq!((metrics.tag_hits, metrics.tag_misses), (3, 1));
+    assert_eq!((metrics.range_hits, metrics.range_misses), (3, 1));
+    assert_eq!((metrics.view_hits, metrics.view_misses), (3, 1));
+    assert_eq!((metrics.search_hits, metrics.search_misses), (3, 1));
+
+    // one enqueue per insert, reads enqueue nothing
+    assert_eq!(metrics.wal_enqueues, 3);
+
+    assert_eq!(observed.0.load(Ordering::SeqCst), 9 + 3 + 4 + 4 + 4 + 4 + 3);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// reads made by writes and scans don't count as lookups
+#[tokio::test]
+async fn internal_reads_are_not_counted() {
+    let dir = temp_dir("metrics-internal");
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+
+    storage.insert("ann".to_owned(), User::new("ann", 30, "rome")).await.unwrap();
+    storage.update(&"ann".to_owned(), |user| user.age += 1).await.unwrap();
+    let _ = storage.find().tag("city:rome").fetch_owned();
+
+    let metrics = storage.metrics();
+    assert_eq!((metrics.lookup_hits, metrics.lookup_misses), (0, 0));
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn bytes_store_reads_are_counted() {
+    let dir = temp_dir("metrics-bytes");
+    let extractors = Extractors::new()
+        .with_index(|doc| vec![format!("first:{}", doc[0])])
+        .with_tags(|doc| vec![format!("len:{}", doc.len())])
+        .with_view(|doc| (doc.len() > 2).then(|| "long".to_owned()));
+    let storage = BytesStorage::<u64>::open(disk_options(&dir, "blobs"), extractors).await.unwrap();
+
+    storage.insert(1, Bytes::from_static(b"abc")).await.unwrap();
+    storage.insert(2, Bytes::from_static(b"xy")).await.unwrap();
+
+    assert!(storage.lookup(&1).is_some());
+    assert!(storage.lookup(&3).is_none());
+    assert!(storage.lookup_pinned(&2).is_some());
+    assert!(storage.lookup_by_index(&format!("first:{}", b'a')).is_some());
+    assert!(storage.lookup_by_index("first:0").is_none());
+    assert_eq!(storage.lookup_by_tag("len:2").len(), 1);
+    assert!(storage.lookup_by_tag("len:9").is_empty());
+    assert_eq!(storage.fetch_view("long").len(), 1);
+
+    let metrics = storage.metrics();
+    assert_eq!((metrics.lookup_hits, metrics.lookup_misses), (2, 1));
+    assert_eq!((metrics.index_hits, metrics.index_misses), (1, 1));
+    assert_eq!((metrics.tag_hits, metrics.tag_misses), (1, 1));
+    assert_eq!((metrics.view_hits, metrics.view_misses), (1, 0));
+    assert_eq!(metrics.wal_enqueues, 2);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
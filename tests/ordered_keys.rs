@@ -0,0 +1,80 @@
+mod common;
+
+use common::{ram_options, temp_dir, User};
+use darkbird::Storage;
+use std::path::Path;
+
+
+
+// same documents with and without ordered keys, inserted out of order
+async fn open(dir: &Path, ordered: bool) -> Storage<String, User> {
+    let ops = ram_options(dir, "users");
+    let ops = if ordered { ops.with_ordered_keys() } else { ops };
+
+    let storage = Storage::<String, User>::open(ops).await.unwrap();
+    for name in ["bob", "ann", "annie", "anna", "carl", "an", "zed"] {
+        storage.insert(name.to_owned(), User::new(name, 30, "rome")).await.unwrap();
+    }
+    storage
+}
+
+fn prefixed(storage: &Storage<String, User>, prefix: &str) -> Vec<String> {
+    storage.iter_prefix(prefix).iter().map(|rf| rf.key().clone()).collect()
+}
+
+
+
+#[tokio::test]
+async fn iter_prefix_in_key_order_either_way() {
+    for ordered in [false, true] {
+        let dir = temp_dir("iter-prefix");
+        let storage = open(&dir, ordered).await;
+
+        assert_eq!(prefixed(&storage, "an"), ["an", "ann", "anna", "annie"], "ordered {}", ordered);
+        assert_eq!(prefixed(&storage, "ann"), ["ann", "anna", "annie"], "ordered {}", ordered);
+        assert_eq!(prefixed(&storage, ""), ["an", "ann", "anna", "annie", "bob", "carl", "zed"], "ordered {}", ordered);
+        assert!(prefixed(&storage, "x").is_empty(), "ordered {}", ordered);
+        assert!(prefixed(&storage, "annex").is_empty(), "ordered {}", ordered);
+
+        storage.remove("anna".to_owned()).await.unwrap();
+        storage.insert("anka".to_owned(), User::new("anka", 30, "rome")).await.unwrap();
+        assert_eq!(prefixed(&storage, "an"), ["an", "anka", "ann", "annie"], "ordered {}", ordered);
+
+        storage.close().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
+
+// pages follow cursor over removals of cursor key and of keys ahead, and over new keys
+#[tokio::test]
+async fn iter_page_cursor_across_removals_either_way() {
+    for ordered in [false, true] {
+        let dir = temp_dir("iter-page");
+        let storage = open(&dir, ordered).await;
+        let page = |cursor: Option<&str>, size| {
+            let (docs, next) = storage.iter_page(cursor.map(str::to_owned), size);
+            (docs.iter().map(|rf| rf.key().clone()).collect::<Vec<_>>(), next)
+        };
+
+        let (keys, cursor) = page(None, 3);
+        assert_eq!((keys, cursor.as_deref()), (vec!["an".to_owned(), "ann".to_owned(), "anna".to_owned()], Some("anna")));
+        assert_eq!(page(Some("anna"), 0), (vec![], Some("anna".to_owned())));
+
+        // cursor key and next one gone, a key added behind and one ahead
+        storage.remove("anna".to_owned()).await.unwrap();
+        storage.remove("annie".to_owned()).await.unwrap();
+        storage.insert("amy".to_owned(), User::new("amy", 30, "rome")).await.unwrap();
+        storage.insert("cat".to_owned(), User::new("cat", 30, "rome")).await.unwrap();
+
+        let (keys, cursor) = page(Some("anna"), 3);
+        assert_eq!((keys, cursor.as_deref()), (vec!["bob".to_owned(), "carl".to_owned(), "cat".to_owned()], Some("cat")), "ordered {}", ordered);
+        let (keys, cursor) = page(Some("cat"), 3);
+        assert_eq!((keys, cursor), (vec!["zed".to_owned()], None), "ordered {}", ordered);
+
+        // exactly a page left: no cursor after it
+        assert_eq!(page(Some("carl"), 2), (vec!["cat".to_owned(), "zed".to_owned()], None), "ordered {}", ordered);
+
+        storage.close().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use darkbird::{DocPatch, SessionResult, Storage};
+use serde_json::json;
+use std::path::Path;
+
+
+
+async fn open(dir: &Path) -> Storage<String, User> {
+    Storage::<String, User>::open(disk_options(dir, "users")).await.unwrap()
+}
+
+// index and tag entries follow document, old ones are gone
+fn assert_moved(storage: &Storage<String, User>) {
+    assert!(storage.lookup_by_index("name:ann").is_none());
+    assert_eq!(storage.lookup_by_index("name:anna").unwrap().key(), "ann");
+    assert!(storage.lookup_by_tag("city:rome").is_empty());
+    assert_eq!(storage.lookup_by_tag("city:oslo").len(), 1);
+}
+
+
+
+#[tokio::test]
+async fn patch_move_index_and_tags_and_survive_replay() {
+    let dir = temp_dir("patch-indexed");
+    let storage = open(&dir).await;
+    let ann = "ann".to_owned();
+    storage.insert(ann.clone(), User::new("ann", 30, "rome")).await.unwrap();
+
+    let patched = storage.patch(&ann, DocPatch::merge(json!({ "name": "anna", "city": "oslo" }))).await;
+    assert!(patched.unwrap());
+    assert_eq!(storage.lookup_owned(&ann).unwrap().age, 30);
+    assert_moved(&storage);
+    storage.close().await.unwrap();
+
+    // WAL replay apply logged patch on top of inserted version
+    let storage = open(&dir).await;
+    assert_eq!(storage.lookup_owned(&ann).unwrap().name, "anna");
+    assert_moved(&storage);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn patch_of_missing_key_or_bad_patch_change_nothing() {
+    let dir = temp_dir("patch-missing");
+    let storage = open(&dir).await;
+    storage.insert("ann".to_owned(), User::new("ann", 30, "rome")).await.unwrap();
+
+    let patched = storage.patch(&"bob".to_owned(), DocPatch::merge(json!({ "age": 1 }))).await;
+    assert!(!patched.unwrap());
+    assert!(storage.lookup_owned(&"bob".to_owned()).is_none());
+
+    // age is no string, patched document doesn't deserialize
+    let err = storage.patch(&"ann".to_owned(), DocPatch::merge(json!({ "age": "old" }))).await.err().unwrap();
+    assert!(matches!(&err, SessionResult::Err(_)) && format!("{:?}", err).contains("patch:"), "{:?}", err);
+    assert_eq!(storage.lookup_owned(&"ann".to_owned()).unwrap(), User::new("ann", 30, "rome"));
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use bytes::Bytes;
+use common::{disk_options, temp_dir, User};
+use darkbird::{storage_bytes::{BytesStorage, Extractors}, Schema, Storage};
+
+
+
+#[tokio::test]
+async fn pinned_doc_outlive_storage() {
+    let dir = temp_dir("pinned-storage");
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+    storage.insert("ann".to_owned(), User::new("ann", 30, "rome")).await.unwrap();
+
+    let pinned = storage.lookup_pinned(&"ann".to_owned()).unwrap();
+    let (address, clone) = (pinned.as_ptr(), pinned.clone());
+    drop(storage);
+
+    assert_eq!(pinned.city, "rome");
+    assert_eq!(clone.as_ptr(), address);
+    drop(pinned);
+    assert_eq!(clone.into_arc().name, "ann");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn pinned_doc_outlive_database() {
+    let dir = temp_dir("pinned-database");
+    let db = Schema::new()
+        .with_datastore::<String, User>(disk_options(&dir, "users"))
+        .await
+        .unwrap()
+        .build();
+    db.insert::<String, User>("ann".to_owned(), User::new("ann", 30, "rome")).await.unwrap();
+
+    let pinned = db.lookup_pinned::<String, User>(&"ann".to_owned()).unwrap().unwrap();
+    drop(db);
+    assert_eq!(pinned.age, 30);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn pinned_bytes_outlive_bytes_storage() {
+    let dir = temp_dir("pinned-bytes");
+    let storage = BytesStorage::<u64>::open(disk_options(&dir, "blobs"), Extractors::new()).await.unwrap();
+    storage.insert(1, Bytes::from_static(b"payload")).await.unwrap();
+
+    let pinned = storage.lookup_pinned(&1).unwrap();
+    drop(storage);
+    assert_eq!(pinned.as_bytes(), b"payload");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
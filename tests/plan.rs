@@ -0,0 +1,84 @@
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use darkbird::{Storage, StorageStats};
+use std::collections::BTreeMap;
+
+const USERS: usize = 100;
+
+
+
+async fn open(dir: &std::path::Path) -> Storage<String, User> {
+    let storage = Storage::open(disk_options(dir, "users")).await.unwrap();
+    for i in 0..USERS {
+        let name = format!("user{}", i);
+        storage.insert(name.clone(), User::new(&name, i as i64, "rome")).await.unwrap();
+    }
+    storage
+}
+
+fn state(storage: &Storage<String, User>) -> (BTreeMap<String, User>, StorageStats) {
+    let docs = storage.iter().map(|entry| (entry.key().clone(), entry.value().clone())).collect();
+    (docs, storage.stats())
+}
+
+#[tokio::test]
+async fn removal_dry_run_match_execution() {
+    let dir = temp_dir("plan-removal");
+    let storage = open(&dir).await;
+
+    let before = state(&storage);
+    let plan = storage.plan_remove_where(|_, user: &User| user.age % 2 == 0);
+    let report = plan.dry_run();
+    assert_eq!((report.operation, report.documents, report.rejected), ("remove_where", USERS / 2, 0));
+    assert_eq!(report.sample.len(), 16);
+    assert!(report.bytes > 0);
+    assert_eq!(state(&storage), before);
+
+    let progress = plan.execute().await.unwrap();
+    assert_eq!((progress.removed, progress.rejected), (report.documents, report.rejected));
+    assert_eq!(progress.reclaimed, report.bytes);
+    assert_eq!(storage.len(), USERS - report.documents);
+    assert!(report.sample.iter().all(|key| storage.lookup_owned(key).is_none()));
+
+    // user1 and user10 to user19
+    let before = state(&storage);
+    let plan = storage.plan_remove_prefix(b"user1");
+    let report = plan.dry_run();
+    assert_eq!((report.operation, report.documents), ("remove_prefix", 6));
+    assert_eq!(state(&storage), before);
+
+    let progress = plan.execute().await.unwrap();
+    assert_eq!((progress.removed, progress.reclaimed), (report.documents, report.bytes));
+    assert_eq!(storage.len(), USERS / 2 - 6);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn compaction_dry_run_match_execution() {
+    let dir = temp_dir("plan-compact");
+    let storage = open(&dir).await;
+    for _ in 0..5 {
+        for i in 0..USERS {
+            storage.update(&format!("user{}", i), |user| user.age += 1).await.unwrap();
+        }
+    }
+    storage.close().await.unwrap();
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+
+    let before = state(&storage);
+    let plan = storage.plan_compact();
+    let report = plan.dry_run().unwrap();
+    assert_eq!((report.operation, report.documents), ("compact", USERS));
+    assert_eq!(state(&storage), before);
+
+    let compacted = plan.execute().await.unwrap();
+    assert_eq!(compacted.records, report.documents);
+    let freed = before.1.wal_size_bytes - storage.stats().wal_size_bytes;
+    assert_eq!(freed, report.bytes, "estimated {} freed {}", report.bytes, freed);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,299 @@
+mod common;
+
+use common::{ram_options, temp_dir, User};
+use darkbird::{Order, Storage};
+use std::ops::Bound;
+
+
+
+const USERS: [(&str, i64, &str); 8] = [
+    ("ann", 30, "rome"),
+    ("bob", 15, "paris"),
+    ("cid", 22, "rome"),
+    ("dan", 45, "oslo"),
+    ("eve", 18, "rome"),
+    ("fay", 30, "paris"),
+    ("gus", 60, "rome"),
+    ("hal", 17, "oslo"),
+];
+
+async fn open(name: &str) -> (Storage<String, User>, std::path::PathBuf) {
+    let dir = temp_dir(name);
+    let storage = Storage::<String, User>::open(ram_options(&dir, "users").with_query_cache(64, 1 << 20))
+        .await
+        .unwrap();
+    for (name, age, city) in USERS {
+        storage.insert(name.to_owned(), User::new(name, age, city)).await.unwrap();
+    }
+    (storage, dir)
+}
+
+fn names(keys: &[&str]) -> Vec<String> {
+    keys.iter().map(|key| key.to_string()).collect()
+}
+
+
+
+#[tokio::test]
+async fn tag_and_inclusive_range() {
+    let (storage, dir) = open("query-tag-range").await;
+
+    let keys = storage.find().tag("city:rome").range("age", 18..=30).keys();
+    assert_eq!(keys, names(&["ann", "cid", "eve"]));
+
+    // same query, filters in other order
+    let keys = storage.find().range("age", 18..=30).tag("city:rome").keys();
+    assert_eq!(keys, names(&["ann", "cid", "eve"]));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn range_bounds() {
+    let (storage, dir) = open("query-bounds").await;
+
+    assert_eq!(storage.find().range("age", 18..30).keys(), names(&["cid", "eve"]));
+    assert_eq!(storage.find().range("age", ..18).keys(), names(&["bob", "hal"]));
+    assert_eq!(storage.find().range("age", 45..).keys(), names(&["dan", "gus"]));
+    assert_eq!(storage.find().range::<i64>("age", ..).count(), USERS.len());
+    assert_eq!(
+        storage.find().range("age", (Bound::Excluded(18), Bound::Included(30))).keys(),
+        names(&["ann", "cid", "fay"])
+    );
+
+    // empty and reversed ranges match nothing
+    assert!(storage.find().range("age", 30..30).keys().is_empty());
+    assert!(storage.find().range("age", (Bound::Included(30), Bound::Included(18))).keys().is_empty());
+
+    // bounds must have type of field
+    assert!(storage.find().range("age", "a".."z").keys().is_empty());
+    assert!(storage.find().tag("city:rome").range("age", "a"..).keys().is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn index_eq_with_tag() {
+    let (storage, dir) = open("query-index").await;
+
+    assert_eq!(storage.find().index_eq("name", "ann").keys(), names(&["ann"]));
+    assert_eq!(storage.find().index_eq("name", "ann").tag("city:rome").keys(), names(&["ann"]));
+    assert!(storage.find().index_eq("name", "ann").tag("city:paris").keys().is_empty());
+    assert!(storage.find().index_eq("name", "zed").keys().is_empty());
+
+    // index_eq is index with key field:value
+    assert_eq!(storage.find().index("name:bob").keys(), storage.find().index_eq("name", "bob").keys());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn text_with_range_and_tag() {
+    let (storage, dir) = open("query-text").await;
+
+    // any word of text
+    assert_eq!(storage.find().text("paris oslo").keys(), names(&["bob", "dan", "fay", "hal"]));
+    assert_eq!(storage.find().text("paris oslo").range("age", 18..).keys(), names(&["dan", "fay"]));
+    assert_eq!(storage.find().text("oslo").tag("city:rome").keys(), Vec::<String>::new());
+    assert_eq!(storage.find().text("ann paris").tag("city:rome").keys(), names(&["ann"]));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn every_operator_together() {
+    let (storage, dir) = open("query-all").await;
+
+    let query = || storage.find().tag("city:rome").index_eq("name", "cid").range("age", 20..25).text("rome");
+    assert_eq!(query().keys(), names(&["cid"]));
+    assert_eq!(query().count(), 1);
+
+    // one failing filter empties result
+    assert!(query().range("age", 23..).keys().is_empty());
+    assert!(query().text("oslo").keys().is_empty());
+    assert!(query().tag("city:oslo").keys().is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn no_filter_match_every_document() {
+    let (storage, dir) = open("query-empty").await;
+
+    let mut all = names(&USERS.map(|(name, ..)| name));
+    all.sort();
+    assert_eq!(storage.find().keys(), all);
+    assert_eq!(storage.find().count(), USERS.len());
+    assert!(storage.find().tag("city:tokyo").keys().is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn order_by_offset_limit() {
+    let (storage, dir) = open("query-order").await;
+
+    // ties ordered by key
+    let keys = storage.find().range("age", 18..).order_by("age", Order::Desc).keys();
+    assert_eq!(keys, names(&["gus", "dan", "ann", "fay", "cid", "eve"]));
+
+    let keys = storage.find().range("age", 18..).order_by("age", Order::Asc).offset(1).limit(3).keys();
+    assert_eq!(keys, names(&["cid", "ann", "fay"]));
+
+    let keys = storage.find().tag("city:paris").order_by("age", Order::Asc).keys();
+    assert_eq!(keys, names(&["bob", "fay"]));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn fetch_and_fetch_owned_agree() {
+    let (storage, dir) = open("query-fetch").await;
+
+    let query = || storage.find().tag("city:rome").range("age", ..40);
+    let fetched: Vec<String> = query().fetch().iter().map(|rf| rf.key().clone()).collect();
+    let owned: Vec<String> = query().fetch_owned().into_iter().map(|(key, doc)| {
+        assert_eq!(key, doc.name);
+        key
+    }).collect();
+
+    assert_eq!(fetched, owned);
+    assert_eq!(owned, query().keys());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn cursor_in_key_order() {
+    let (storage, dir) = open("query-cursor").await;
+
+    let page = |after: Option<&str>| {
+        let query = storage.find().tag("city:rome").limit(2);
+        match after {
+            Some(cursor) => query.after(cursor.to_owned()).keys(),
+            None => query.keys(),
+        }
+    };
+
+    assert_eq!(page(None), names(&["ann", "cid"]));
+    assert_eq!(page(Some("cid")), names(&["eve", "gus"]));
+    assert!(page(Some("gus")).is_empty());
+
+    // cursor needn't match: keys after it
+    assert_eq!(page(Some("bob")), names(&["cid", "eve"]));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn cursor_with_order_by() {
+    let (storage, dir) = open("query-cursor-order").await;
+
+    let page = |after: Option<String>| {
+        let query = storage.find().range("age", 18..).order_by("age", Order::Asc).limit(2);
+        match after {
+            Some(cursor) => query.after(cursor).keys(),
+            None => query.keys(),
+        }
+    };
+
+    let first = page(None);
+    assert_eq!(first, names(&["eve", "cid"]));
+    let second = page(first.last().cloned());
+    assert_eq!(second, names(&["ann", "fay"]));
+    let third = page(second.last().cloned());
+    assert_eq!(third, names(&["dan", "gus"]));
+    assert!(page(third.last().cloned()).is_empty());
+
+    // cursor not matching continue from its place, found by its document: fay is 30, after ann
+    let query = storage.find().tag("city:rome").order_by("age", Order::Asc).after("fay".to_owned());
+    assert_eq!(query.keys(), names(&["gus"]));
+
+    // cursor moved out of filter since last page
+    storage.update(&"cid".to_owned(), |user| user.city = "oslo".to_owned()).await.unwrap();
+    let query = storage.find().tag("city:rome").order_by("age", Order::Asc).after("cid".to_owned());
+    assert_eq!(query.keys(), names(&["ann", "gus"]));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn cached_query_keyed_by_cursor() {
+    let (storage, dir) = open("query-cached").await;
+    let ttl = std::time::Duration::from_secs(60);
+
+    let first = storage.find().tag("city:rome").limit(2).cached(ttl).keys();
+    let second = storage.find().tag("city:rome").limit(2).after("cid".to_owned()).cached(ttl).keys();
+
+    assert_eq!(first, names(&["ann", "cid"]));
+    assert_eq!(second, names(&["eve", "gus"]));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// documents moved in and out of cached results: each cached answer is the one computed afresh
+#[tokio::test]
+async fn cached_query_never_stale_after_writes() {
+    let (storage, dir) = open("query-cached-writes").await;
+    let ttl = std::time::Duration::from_secs(60);
+
+    let cached = |storage: &Storage<String, User>| {
+        let tag = storage.find().tag("city:rome").cached(ttl).keys();
+        let range = storage.find().range("age", 18..=30).order_by("age", Order::Desc).cached(ttl).keys();
+        let text = storage.find().text("paris").cached(ttl).keys();
+        (tag, range, text)
+    };
+    let fresh = |storage: &Storage<String, User>| {
+        let tag = storage.find().tag("city:rome").keys();
+        let range = storage.find().range("age", 18..=30).order_by("age", Order::Desc).keys();
+        let text = storage.find().text("paris").keys();
+        (tag, range, text)
+    };
+    assert_eq!(cached(&storage), fresh(&storage));
+
+    // out of tag and text, into range
+    storage.update(&"bob".to_owned(), |user| *user = User::new("bob", 19, "oslo")).await.unwrap();
+    assert_eq!(cached(&storage), fresh(&storage));
+
+    // into tag, out of range
+    storage.update(&"fay".to_owned(), |user| *user = User::new("fay", 31, "rome")).await.unwrap();
+    assert_eq!(cached(&storage), fresh(&storage));
+
+    // new member of all three, then removed
+    storage.insert("ivy".to_owned(), User::new("ivy", 25, "rome")).await.unwrap();
+    storage.update(&"ivy".to_owned(), |user| user.bio.push_str(" near paris")).await.unwrap();
+    assert_eq!(cached(&storage), fresh(&storage));
+    storage.remove("ivy".to_owned()).await.unwrap();
+    assert_eq!(cached(&storage), fresh(&storage));
+
+    let stats = storage.query_cache_stats();
+    assert!(stats.invalidations > 0, "{:?}", stats);
+
+    // write to a tag none of them read keep entries
+    storage.update(&"dan".to_owned(), |user| user.city = "lima".to_owned()).await.unwrap();
+    assert_eq!(cached(&storage), fresh(&storage));
+    assert_eq!(storage.query_cache_stats().hits, stats.hits + 3);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// an entry older than ttl of a call is computed again
+#[tokio::test(start_paused = true)]
+async fn cached_query_recomputed_past_ttl() {
+    let (storage, dir) = open("query-cached-ttl").await;
+    let ttl = std::time::Duration::from_millis(100);
+
+    storage.find().tag("city:oslo").cached(ttl).keys();
+    storage.find().tag("city:oslo").cached(ttl).keys();
+    assert_eq!((storage.query_cache_stats().hits, storage.query_cache_stats().misses), (1, 1));
+
+    tokio::time::advance(std::time::Duration::from_millis(150)).await;
+    assert_eq!(storage.find().tag("city:oslo").cached(ttl).keys(), names(&["dan", "hal"]));
+    assert_eq!((storage.query_cache_stats().hits, storage.query_cache_stats().misses), (1, 2));
+
+    // a longer ttl still accept it
+    storage.find().tag("city:oslo").cached(ttl * 10).keys();
+    assert_eq!(storage.query_cache_stats().hits, 2);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
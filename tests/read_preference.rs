@@ -0,0 +1,108 @@
+mod common;
+
+use common::{ram_options, temp_dir, User};
+use darkbird::{ReadPreference, ReadSource, Storage};
+use std::{path::Path, time::Duration};
+
+
+
+// snapshot refreshed only on demand within a test
+async fn open(dir: &Path) -> Storage<String, User> {
+    let storage = Storage::<String, User>::open(ram_options(dir, "users").with_read_snapshot(Duration::from_secs(3600)))
+        .await
+        .unwrap();
+
+    storage.insert("ann".to_owned(), User::new("ann", 30, "rome")).await.unwrap();
+    storage
+}
+
+async fn insert_bob(storage: &Storage<String, User>) {
+    storage.insert("bob".to_owned(), User::new("bob", 20, "oslo")).await.unwrap();
+}
+
+
+
+#[tokio::test]
+async fn fresh_reads_live_store() {
+    let dir = temp_dir("read-fresh");
+    let storage = open(&dir).await;
+    storage.refresh_snapshot().await;
+    insert_bob(&storage).await;
+
+    let read = storage.lookup_with(&"bob".to_owned(), ReadPreference::Fresh).await;
+    assert_eq!(read.source, ReadSource::Live);
+    assert_eq!((read.taken_at, read.age), (None, Duration::ZERO));
+    assert_eq!(read.value.unwrap().city, "oslo");
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn snapshot_reads_frozen_copy_until_refreshed() {
+    let dir = temp_dir("read-snapshot");
+    let storage = open(&dir).await;
+
+    // first Snapshot read take the snapshot
+    let read = storage.lookup_with(&"ann".to_owned(), ReadPreference::Snapshot).await;
+    assert_eq!(read.source, ReadSource::Snapshot);
+    assert!(read.taken_at.is_some());
+    assert!(read.value.is_some());
+
+    insert_bob(&storage).await;
+    let read = storage.lookup_with(&"bob".to_owned(), ReadPreference::Snapshot).await;
+    assert_eq!(read.source, ReadSource::Snapshot);
+    assert!(read.value.is_none());
+    assert_eq!(storage.iter_with(ReadPreference::Snapshot).await.value.len(), 1);
+
+    storage.refresh_snapshot().await;
+    let read = storage.lookup_with(&"bob".to_owned(), ReadPreference::Snapshot).await;
+    assert_eq!(read.value.unwrap().city, "oslo");
+    assert_eq!(storage.iter_with(ReadPreference::Snapshot).await.value.len(), 2);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(start_paused = true)]
+async fn max_staleness_fall_back_to_live_once_exceeded() {
+    let dir = temp_dir("read-staleness");
+    let storage = open(&dir).await;
+    storage.refresh_snapshot().await;
+    insert_bob(&storage).await;
+
+    let bob = "bob".to_owned();
+    let read = storage.lookup_with(&bob, ReadPreference::SnapshotMaxStaleness(Duration::from_secs(10))).await;
+    assert_eq!(read.source, ReadSource::Snapshot);
+    assert!(read.value.is_none());
+
+    tokio::time::advance(Duration::from_secs(11)).await;
+    let read = storage.lookup_with(&bob, ReadPreference::SnapshotMaxStaleness(Duration::from_secs(10))).await;
+    assert_eq!(read.source, ReadSource::Live);
+    assert!(read.value.is_some());
+
+    // a looser bound still accept it
+    let read = storage.lookup_with(&bob, ReadPreference::SnapshotMaxStaleness(Duration::from_secs(60))).await;
+    assert_eq!(read.source, ReadSource::Snapshot);
+    assert_eq!(read.age, Duration::from_secs(11));
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn snapshot_without_read_snapshot_reads_live_store() {
+    let dir = temp_dir("read-no-snapshot");
+    let storage = Storage::<String, User>::open(ram_options(&dir, "users")).await.unwrap();
+    insert_bob(&storage).await;
+
+    for pref in [ReadPreference::Snapshot, ReadPreference::SnapshotMaxStaleness(Duration::from_secs(1))] {
+        let read = storage.lookup_with(&"bob".to_owned(), pref).await;
+        assert_eq!(read.source, ReadSource::Live, "{:?}", pref);
+        assert!(read.value.is_some());
+    }
+    assert_eq!(storage.snapshot_age(), None);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
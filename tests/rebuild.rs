@@ -0,0 +1,70 @@
+mod common;
+
+use common::{ram_options, temp_dir, User};
+use darkbird::{RebuildState, Storage, Structure};
+use std::{future::Future, path::Path, pin::pin, task::Poll};
+
+// a few rebuild batches
+const USERS: usize = 3500;
+
+
+
+async fn open(dir: &Path) -> Storage<String, User> {
+    let storage = Storage::<String, User>::open(ram_options(dir, "users")).await.unwrap();
+    for i in 0..USERS {
+        let name = format!("user{:04}", i);
+        storage.insert(name.clone(), User::new(&name, i as i64, if i % 2 == 0 { "rome" } else { "oslo" })).await.unwrap();
+    }
+    storage
+}
+
+// rebuild polled until a batch is in shadow, then dropped
+async fn interrupt(storage: &Storage<String, User>, structure: Structure) {
+    let mut rebuild = pin!(storage.rebuild_index(structure));
+    loop {
+        let polled = std::future::poll_fn(|cx| Poll::Ready(rebuild.as_mut().poll(cx))).await;
+        assert!(polled.is_pending(), "rebuild of {:?} finished before interrupted", structure);
+
+        let progress = storage.rebuild_progress().unwrap();
+        if progress.scanned > 0 {
+            assert!(progress.scanned < progress.total);
+            break;
+        }
+        tokio::task::yield_now().await;
+    }
+}
+
+fn ages(storage: &Storage<String, User>, from: i64, to: i64) -> usize {
+    storage.range("age", from, to).len()
+}
+
+
+
+// an interrupted rebuild leave old index serving, a later one start over and swap
+#[tokio::test]
+async fn interrupted_rebuild_start_over() {
+    let dir = temp_dir("rebuild-interrupt");
+    let storage = open(&dir).await;
+
+    for structure in [Structure::Range, Structure::Text] {
+        interrupt(&storage, structure).await;
+        assert_eq!(storage.rebuild_progress().unwrap().state, RebuildState::Aborted);
+
+        // old index still answer and still follow writes
+        assert_eq!(ages(&storage, 0, 100), 100);
+        assert_eq!(storage.search("rome").len(), USERS / 2);
+        storage.insert("late".to_owned(), User::new("late", 50, "pisa")).await.unwrap();
+        assert_eq!((ages(&storage, 0, 100), storage.search("pisa").len()), (101, 1));
+
+        let progress = storage.rebuild_index(structure).await.unwrap();
+        assert_eq!(progress.state, RebuildState::Done);
+        assert_eq!(progress.scanned, progress.total);
+        assert_eq!((ages(&storage, 0, 100), storage.search("pisa").len()), (101, 1));
+        assert_eq!(storage.search("rome").len(), USERS / 2);
+
+        storage.remove("late".to_owned()).await.unwrap();
+    }
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use common::{disk_options, temp_dir, Order};
+use darkbird::{RecoveryMode, Storage, WAL_FORMAT_VERSION};
+use std::{fs::OpenOptions, path::Path};
+
+const MODES: [RecoveryMode; 3] = [RecoveryMode::Strict, RecoveryMode::SkipCorrupted, RecoveryMode::TruncateAtCorruption];
+
+// records per page, smallest page size, first of them the page header
+const PAGE: u64 = 5000;
+
+
+
+async fn write(dir: &Path, orders: u64) {
+    std::fs::create_dir_all(dir).unwrap();
+    let storage = Storage::<u64, Order>::open(disk_options(dir, "orders")).await.unwrap();
+    for key in 0..orders {
+        storage.insert(key, Order { user: "ann".to_owned(), item: key.to_string() }).await.unwrap();
+    }
+    storage.close().await.unwrap();
+}
+
+async fn open(dir: &Path, mode: RecoveryMode) -> Result<Storage<u64, Order>, String> {
+    Storage::open(disk_options(dir, "orders").with_recovery(mode)).await
+}
+
+// copy of WAL of store in from, its page cut by bytes
+fn cut(from: &Path, to: &Path, page: u64, bytes: u64) {
+    let (wal, copy) = (from.join("orders"), to.join("orders"));
+    std::fs::create_dir_all(&copy).unwrap();
+    for entry in std::fs::read_dir(&wal).unwrap() {
+        let entry = entry.unwrap();
+        std::fs::copy(entry.path(), copy.join(entry.file_name())).unwrap();
+    }
+    let _ = std::fs::copy(from.join("orders.schema"), to.join("orders.schema"));
+
+    let file = OpenOptions::new().write(true).open(copy.join(format!("page-{}.LOG", page * PAGE))).unwrap();
+    let len = file.metadata().unwrap().len();
+    file.set_len(len - bytes).unwrap();
+}
+
+// last bytes of WAL lost to a crash mid append: LogFile::open trim the torn record
+// off last page before loader read it, so every mode open the same way
+#[tokio::test]
+async fn torn_tail_is_dropped_in_every_mode() {
+    let dir = temp_dir("recovery-tail");
+    write(&dir.join("source"), 20).await;
+
+    for (i, mode) in MODES.into_iter().enumerate() {
+        let copy = dir.join(format!("copy{}", i));
+        cut(&dir.join("source"), &copy, 1, 5);
+
+        let storage = open(&copy, mode).await.unwrap();
+        let report = storage.recovery();
+        assert_eq!(storage.len(), 19, "{:?}", mode);
+        assert!(report.torn_tail > 0, "{:?}", report);
+        assert_eq!((report.skipped, report.truncated_at, report.pages_removed), (0, None, 0), "{:?}", mode);
+
+        // appends follow last whole record
+        storage.insert(19, Order { user: "bob".to_owned(), item: "pen".to_owned() }).await.unwrap();
+        storage.close().await.unwrap();
+        let storage = open(&copy, RecoveryMode::Strict).await.unwrap();
+        assert_eq!((storage.len(), storage.recovery().torn_tail), (20, 0));
+        storage.close().await.unwrap();
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// last record of a sealed page cut: corruption, each mode as specified
+#[tokio::test]
+async fn cut_sealed_page_per_mode() {
+    let dir = temp_dir("recovery-sealed");
+    let orders = PAGE + 100;
+    write(&dir.join("source"), orders).await;
+
+    cut(&dir.join("source"), &dir.join("strict"), 1, 5);
+    let err = open(&dir.join("strict"), RecoveryMode::Strict).await.err().unwrap();
+    assert!(err.contains("page 1 offset"), "{}", err);
+
+    cut(&dir.join("source"), &dir.join("skip"), 1, 5);
+    let storage = open(&dir.join("skip"), RecoveryMode::SkipCorrupted).await.unwrap();
+    assert_eq!(storage.len() as u64, orders - 1);
+    assert!(storage.lookup_owned(&(PAGE - 2)).is_none());
+    let report = storage.recovery();
+    assert_eq!((report.recovered, report.skipped, report.truncated_at), (orders - 1, 1, None));
+    storage.close().await.unwrap();
+
+    cut(&dir.join("source"), &dir.join("truncate"), 1, 5);
+    let storage = open(&dir.join("truncate"), RecoveryMode::TruncateAtCorruption).await.unwrap();
+    assert_eq!(storage.len() as u64, PAGE - 2);
+    let report = storage.recovery();
+    assert_eq!((report.skipped, report.pages_removed), (0, 1));
+    assert_eq!(report.truncated_at.map(|(page, _)| page), Some(1));
+    storage.close().await.unwrap();
+
+    // truncated WAL is whole again
+    let storage = open(&dir.join("truncate"), RecoveryMode::Strict).await.unwrap();
+    assert_eq!(storage.len() as u64, PAGE - 2);
+    storage.close().await.unwrap();
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// header record of first page rewritten from a format version to another, its crc with it
+fn set_format_version(dir: &Path, from: u32, to: u32) {
+    let page = dir.join("orders").join(format!("page-{}.LOG", PAGE));
+    let mut bytes = std::fs::read(&page).unwrap();
+
+    let current = format!("darkbird-wal-v{}:bincode", from);
+    let other = format!("darkbird-wal-v{}:bincode", to);
+    assert_eq!(current.len(), other.len());
+
+    let at = bytes.windows(current.len()).position(|w| w == current.as_bytes()).unwrap();
+    let end = at + other.len();
+    bytes[at..end].copy_from_slice(other.as_bytes());
+    bytes[end..end + 4].copy_from_slice(&crc32fast::hash(other.as_bytes()).to_le_bytes());
+    std::fs::write(&page, bytes).unwrap();
+}
+
+// a page format this release doesn't read fail open in every mode, naming its version
+#[tokio::test]
+async fn unknown_page_format_version_fail_open() {
+    let dir = temp_dir("recovery-format-version");
+    write(&dir, 10).await;
+
+    set_format_version(&dir, WAL_FORMAT_VERSION, 9);
+    for mode in MODES {
+        let err = open(&dir, mode).await.err().unwrap();
+        assert!(err.contains("format version 9 is newer"), "{:?}: {}", mode, err);
+    }
+
+    set_format_version(&dir, 9, 1);
+    let err = open(&dir, RecoveryMode::Strict).await.err().unwrap();
+    assert!(err.contains("unsupported WAL page format version 1"), "{}", err);
+
+    set_format_version(&dir, 1, WAL_FORMAT_VERSION);
+    let storage = open(&dir, RecoveryMode::Strict).await.unwrap();
+    assert_eq!(storage.len(), 10);
+    storage.close().await.unwrap();
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
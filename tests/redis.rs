@@ -0,0 +1,124 @@
+use darkbird::{storage_redis::RedisStorage, SessionResult};
+use std::time::Duration;
+
+
+
+// every page of scan, following cursors from first
+fn scan_all(storage: &RedisStorage<u64, String>, count: usize) -> Vec<Vec<u64>> {
+    let mut pages = vec![];
+    let mut cursor = None;
+    loop {
+        let (keys, next) = storage.scan(cursor, count);
+        pages.push(keys);
+        match next {
+            Some(next) => cursor = Some(next),
+            None => return pages,
+        }
+    }
+}
+
+#[tokio::test]
+async fn scan_pages_keys_in_order() {
+    let storage = RedisStorage::<u64, String>::new();
+    storage.mset((0..10).rev().map(|i| (i, i.to_string(), None)).collect());
+
+    assert_eq!(scan_all(&storage, 4), vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9]]);
+    assert_eq!(scan_all(&storage, 5), vec![vec![0, 1, 2, 3, 4], vec![5, 6, 7, 8, 9]]);
+
+    // a cursor deleted between calls still resume after it
+    let (_, next) = storage.scan(None, 3);
+    assert_eq!(next, Some(2));
+    storage.del(&2);
+    assert_eq!(storage.scan(next, 3), (vec![3, 4, 5], Some(5)));
+
+    assert_eq!(storage.scan(Some(9), 3), (vec![], None));
+    assert_eq!(storage.scan(Some(4), 0), (vec![], Some(4)));
+}
+
+#[tokio::test]
+async fn scan_follow_every_write() {
+    let storage = RedisStorage::<u64, String>::new();
+    storage.set(1, "a".to_owned(), None);
+    assert!(storage.set_nx(3, "c".to_owned(), None));
+    assert!(!storage.set_nx(3, "c".to_owned(), None));
+    storage.mset(vec![(2, "b".to_owned(), None), (1, "a".to_owned(), None)]);
+    assert_eq!(scan_all(&storage, 10), vec![vec![1, 2, 3]]);
+
+    storage.del(&1);
+    storage.del(&7);
+    assert_eq!(scan_all(&storage, 10), vec![vec![2, 3]]);
+
+    let counters = RedisStorage::<u64, i64>::new();
+    counters.incr(5, 1, None).unwrap();
+    counters.decr(4, 1, None).unwrap();
+    counters.incr(5, 1, None).unwrap();
+    assert_eq!(counters.scan(None, 10), (vec![4, 5], None));
+}
+
+#[tokio::test(start_paused = true)]
+async fn expired_keys_leave_scan() {
+    let storage = RedisStorage::<u64, String>::new();
+    storage.mset((0..6).map(|i| (i, i.to_string(), None)).collect());
+    storage.set(1, "1".to_owned(), Some(Duration::from_millis(20)));
+    assert!(storage.expire(&4, Duration::from_millis(20)));
+
+    tokio::time::advance(Duration::from_millis(40)).await;
+    assert_eq!(scan_all(&storage, 2), vec![vec![0, 2], vec![3, 5]]);
+
+    // once purged, a key set again is scanned
+    tokio::time::advance(Duration::from_millis(20)).await;
+    storage.set(4, "4".to_owned(), None);
+    assert_eq!(scan_all(&storage, 10), vec![vec![0, 2, 3, 4, 5]]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn deleted_key_set_again_outlive_old_expire() {
+    let storage = RedisStorage::<u64, String>::new();
+    storage.set(1, "old".to_owned(), Some(Duration::from_millis(20)));
+    storage.del(&1);
+    storage.set(1, "new".to_owned(), None);
+
+    tokio::time::advance(Duration::from_millis(60)).await;
+    assert_eq!(storage.get(&1).as_deref().map(String::as_str), Some("new"));
+    assert_eq!(storage.scan(None, 10), (vec![1], None));
+}
+
+#[tokio::test]
+async fn incr_create_absent_key_at_zero() {
+    let counters = RedisStorage::<u64, i64>::new();
+    assert_eq!(counters.incr(1, 5, None).unwrap(), 5);
+    assert_eq!(counters.decr(2, 3, None).unwrap(), -3);
+    assert_eq!(counters.incr(1, 2, None).unwrap(), 7);
+
+    // zero minus one is out of u8, key isn't created
+    let unsigned = RedisStorage::<u64, u8>::new();
+    assert!(matches!(unsigned.decr(1, 1, None), Err(SessionResult::Overflow)));
+    assert!(unsigned.get(&1).is_none());
+}
+
+#[tokio::test]
+async fn incr_overflow_leave_value_and_ttl() {
+    let counters = RedisStorage::<u64, i8>::new();
+    counters.set(1, 120, Some(Duration::from_secs(60)));
+
+    assert!(matches!(counters.incr(1, 10, None), Err(SessionResult::Overflow)));
+    assert!(matches!(counters.decr(1, i64::MIN, None), Err(SessionResult::Overflow)));
+    assert_eq!(*counters.get(&1).unwrap(), 120);
+    assert!(counters.ttl(&1).unwrap().is_some());
+}
+
+// thread blocked past expire, so purge task didn't run: entry is still held but dead
+#[tokio::test]
+async fn incr_of_expired_key_start_at_zero() {
+    let counters = RedisStorage::<u64, i64>::new();
+    counters.set(1, 10, Some(Duration::from_millis(20)));
+
+    std::thread::sleep(Duration::from_millis(40));
+    assert_eq!(counters.incr(1, 1, None).unwrap(), 1);
+    assert_eq!(counters.ttl(&1).unwrap(), None);
+
+    // old expire doesn't purge new value, time frozen from here
+    tokio::time::pause();
+    tokio::time::advance(Duration::from_millis(40)).await;
+    assert_eq!(*counters.get(&1).unwrap(), 1);
+}
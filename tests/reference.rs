@@ -0,0 +1,181 @@
+mod common;
+
+use common::{disk_options, temp_dir, Order, User};
+use darkbird::{
+    document::{Document, FullText, Indexer, MaterializedView, Range, Tags},
+    testing::FaultyWal,
+    Database, Options, ReferenceAction, Schema, SessionResult,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+
+
+/// Line of an order, tagged `order:<id>`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Line {
+    order: u64,
+    sku: String,
+}
+
+impl Document for Line {}
+
+impl Indexer for Line {
+    fn extract(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Tags for Line {
+    fn get_tags(&self) -> Vec<String> {
+        vec![format!("order:{}", self.order)]
+    }
+}
+
+impl Range for Line {}
+
+impl MaterializedView for Line {
+    fn filter(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FullText for Line {
+    fn get_content(&self) -> Option<String> {
+        None
+    }
+}
+
+fn user_tag(name: &String) -> String {
+    format!("user:{}", name)
+}
+
+fn order_tag(id: &u64) -> String {
+    format!("order:{}", id)
+}
+
+
+
+// users <- orders <- lines, lines opened with lines_ops
+async fn open(dir: &Path, lines_action: ReferenceAction, lines_ops: Options) -> Database {
+    let db = Schema::new()
+        .with_datastore::<String, User>(disk_options(dir, "users"))
+        .await
+        .unwrap()
+        .with_datastore::<u64, Order>(disk_options(dir, "orders"))
+        .await
+        .unwrap()
+        .with_datastore::<u64, Line>(lines_ops)
+        .await
+        .unwrap()
+        .with_reference::<String, User, u64, Order>("orders", user_tag, ReferenceAction::Cascade)
+        .with_reference::<u64, Order, u64, Line>("lines", order_tag, lines_action)
+        .build();
+
+    for name in ["ann", "bob"] {
+        db.insert::<String, User>(name.to_owned(), User::new(name, 30, "rome")).await.unwrap();
+    }
+
+    // orders 1, 2 of ann, 3 of bob, two lines each
+    for (id, user) in [(1, "ann"), (2, "ann"), (3, "bob")] {
+        let order = Order { user: user.to_owned(), item: format!("item {}", id) };
+        db.insert::<u64, Order>(id, order).await.unwrap();
+        for n in 0..2 {
+            let line = Line { order: id, sku: format!("sku {}", n) };
+            db.insert::<u64, Line>(id * 10 + n, line).await.unwrap();
+        }
+    }
+
+    db
+}
+
+fn counts(db: &Database) -> (usize, usize, usize) {
+    (
+        db.len::<String, User>().unwrap(),
+        db.len::<u64, Order>().unwrap(),
+        db.len::<u64, Line>().unwrap(),
+    )
+}
+
+
+
+#[tokio::test]
+async fn cascade_remove_dependents_of_dependents() {
+    let dir = temp_dir("reference-cascade");
+    let db = open(&dir, ReferenceAction::Cascade, disk_options(&dir, "lines")).await;
+
+    db.remove_checked::<String, User>("ann".to_owned()).await.unwrap();
+
+    assert_eq!(counts(&db), (1, 1, 2));
+    assert!(db.lookup_owned::<u64, Order>(&3).unwrap().is_some());
+    assert!(db.lookup_owned::<u64, Line>(&30).unwrap().is_some());
+    assert!(db.lookup_owned::<u64, Line>(&10).unwrap().is_none());
+
+    drop(db);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn restrict_of_cascaded_dependent_refuse_remove() {
+    let dir = temp_dir("reference-restrict");
+    let db = open(&dir, ReferenceAction::Restrict, disk_options(&dir, "lines")).await;
+
+    let res = db.remove_checked::<String, User>("ann".to_owned()).await;
+    match res {
+        Err(SessionResult::ReferencedBy { store, count }) => {
+            assert_eq!(store, "lines");
+            assert_eq!(count, 2);
+        }
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+
+    // nothing removed, not even orders cascaded before lines were checked
+    assert_eq!(counts(&db), (2, 3, 6));
+
+    // without lines, orders cascade
+    for id in [10, 11, 20, 21] {
+        db.remove::<u64, Line>(id).await.unwrap();
+    }
+    db.remove_checked::<String, User>("ann".to_owned()).await.unwrap();
+    assert_eq!(counts(&db), (1, 1, 2));
+
+    drop(db);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn failed_log_of_dependent_remove_nothing() {
+    let dir = temp_dir("reference-atomic");
+
+    // 6 lines inserted by open, transaction is append 7
+    let wal = FaultyWal::new().fail_nth_append(7);
+    let db = open(&dir, ReferenceAction::Cascade, disk_options(&dir, "lines").with_faulty_wal(wal.clone())).await;
+
+    assert!(db.remove_checked::<String, User>("ann".to_owned()).await.is_err());
+    assert_eq!(wal.failed(), 1);
+
+    // user, orders and lines all left as they were
+    assert_eq!(counts(&db), (2, 3, 6));
+    assert!(db.lookup_owned::<String, User>(&"ann".to_owned()).unwrap().is_some());
+    assert!(db.lookup_owned::<u64, Order>(&1).unwrap().is_some());
+
+    // next append succeed
+    db.remove_checked::<String, User>("ann".to_owned()).await.unwrap();
+    assert_eq!(counts(&db), (1, 1, 2));
+
+    drop(db);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn remove_checked_without_dependents() {
+    let dir = temp_dir("reference-plain");
+    let db = open(&dir, ReferenceAction::Cascade, disk_options(&dir, "lines")).await;
+
+    db.remove::<u64, Order>(3).await.unwrap();
+    db.remove_checked::<String, User>("bob".to_owned()).await.unwrap();
+    assert_eq!(counts(&db), (1, 2, 6));
+
+    drop(db);
+    let _ = std::fs::remove_dir_all(&dir);
+}
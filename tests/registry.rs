@@ -0,0 +1,192 @@
+mod common;
+
+use common::{ram_options, temp_dir, User};
+use darkbird::{
+    document::{Document, FullText, Indexer, MaterializedView, Range, Tags},
+    Database, Schema, SessionResult, TypeRegistry, TypeRegistryError,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::BTreeMap, path::Path};
+
+
+
+/// document json can't encode: keys of map aren't strings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Grid {
+    cells: BTreeMap<(u8, u8), u8>,
+}
+
+impl Document for Grid {}
+
+impl Indexer for Grid {
+    fn extract(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Tags for Grid {
+    fn get_tags(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl Range for Grid {}
+
+impl MaterializedView for Grid {
+    fn filter(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FullText for Grid {
+    fn get_content(&self) -> Option<String> {
+        None
+    }
+}
+
+
+
+async fn open(dir: &Path) -> (Database, TypeRegistry) {
+    let db = Schema::new()
+        .with_datastore::<String, User>(ram_options(dir, "users"))
+        .await
+        .unwrap()
+        .with_datastore::<u32, Grid>(ram_options(dir, "grids"))
+        .await
+        .unwrap()
+        .build();
+
+    let mut registry = TypeRegistry::new();
+    registry.register::<String, User>("users").unwrap().register::<u32, Grid>("grids").unwrap();
+    (db, registry)
+}
+
+fn ann() -> serde_json::Value {
+    json!({ "name": "ann", "age": 30, "city": "rome", "bio": "ann lives in rome" })
+}
+
+
+
+#[test]
+fn register_list_names_in_order() {
+    let mut registry = TypeRegistry::new();
+    registry.register::<u32, Grid>("grids").unwrap();
+    registry.register::<String, User>("users").unwrap();
+    assert_eq!(registry.names(), vec!["grids", "users"]);
+}
+
+#[test]
+fn duplicate_registration_is_rejected() {
+    let mut registry = TypeRegistry::new();
+    registry.register::<String, User>("users").unwrap();
+
+    // same name refused whatever the types
+    match registry.register::<u32, Grid>("users") {
+        Err(TypeRegistryError::AlreadyRegistered(name)) => assert_eq!(name, "users"),
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+    assert_eq!(registry.names(), vec!["users"]);
+}
+
+#[tokio::test]
+async fn unknown_name() {
+    let dir = temp_dir("registry-unknown");
+    let (db, registry) = open(&dir).await;
+
+    let res = registry.lookup_json(&db, "people", &json!("ann"));
+    assert!(matches!(res, Err(TypeRegistryError::UnknownStore(name)) if name == "people"));
+    assert!(matches!(registry.len_of(&db, "people"), Err(TypeRegistryError::UnknownStore(_))));
+    assert!(matches!(
+        registry.insert_json(&db, "people", &json!("ann"), &ann()).await,
+        Err(TypeRegistryError::UnknownStore(_))
+    ));
+    assert!(matches!(registry.remove_json(&db, "people", &json!("ann")).await, Err(TypeRegistryError::UnknownStore(_))));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// registered but not opened in database
+#[tokio::test]
+async fn registered_store_missing_from_database() {
+    let db = Schema::new().build();
+    let mut registry = TypeRegistry::new();
+    registry.register::<String, User>("users").unwrap();
+
+    let res = registry.len_of(&db, "users");
+    assert!(matches!(res, Err(TypeRegistryError::Session(SessionResult::DataStoreNotFound))));
+}
+
+#[tokio::test]
+async fn malformed_key() {
+    let dir = temp_dir("registry-key");
+    let (db, registry) = open(&dir).await;
+
+    // users are keyed by string, grids by u32
+    assert!(matches!(registry.insert_json(&db, "users", &json!(7), &ann()).await, Err(TypeRegistryError::MalformedKey(_))));
+    assert!(matches!(registry.lookup_json(&db, "grids", &json!("one")), Err(TypeRegistryError::MalformedKey(_))));
+    assert!(matches!(registry.remove_json(&db, "grids", &json!(-1)).await, Err(TypeRegistryError::MalformedKey(_))));
+    assert_eq!(registry.len_of(&db, "users").unwrap(), 0);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn malformed_doc() {
+    let dir = temp_dir("registry-doc");
+    let (db, registry) = open(&dir).await;
+
+    let missing_field = json!({ "name": "ann", "age": 30 });
+    let wrong_type = json!({ "name": "ann", "age": "thirty", "city": "rome", "bio": "" });
+
+    for doc in [missing_field, wrong_type, json!([1, 2])] {
+        match registry.insert_json(&db, "users", &json!("ann"), &doc).await {
+            Err(TypeRegistryError::MalformedDoc(reason)) => assert!(!reason.is_empty()),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+    assert_eq!(registry.len_of(&db, "users").unwrap(), 0);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn insert_lookup_remove_round_trip() {
+    let dir = temp_dir("registry-round-trip");
+    let (db, registry) = open(&dir).await;
+
+    registry.insert_json(&db, "users", &json!("ann"), &ann()).await.unwrap();
+    assert_eq!(registry.len_of(&db, "users").unwrap(), 1);
+
+    // same json back, and typed document through Database
+    assert_eq!(registry.lookup_json(&db, "users", &json!("ann")).unwrap(), Some(ann()));
+    let typed = db.lookup_owned::<String, User>(&"ann".to_owned()).unwrap().unwrap();
+    assert_eq!(typed, User::new("ann", 30, "rome"));
+
+    assert_eq!(registry.lookup_json(&db, "users", &json!("bob")).unwrap(), None);
+
+    registry.remove_json(&db, "users", &json!("ann")).await.unwrap();
+    assert_eq!(registry.lookup_json(&db, "users", &json!("ann")).unwrap(), None);
+    assert_eq!(registry.len_of(&db, "users").unwrap(), 0);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn document_json_cant_encode() {
+    let dir = temp_dir("registry-encode");
+    let (db, registry) = open(&dir).await;
+
+    let grid = Grid { cells: BTreeMap::from([((0, 1), 5)]) };
+    db.insert::<u32, Grid>(1, grid).await.unwrap();
+
+    match registry.lookup_json(&db, "grids", &json!(1)) {
+        Err(TypeRegistryError::Encode(reason)) => assert!(reason.contains("key must be a string"), "{}", reason),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    // store itself is fine
+    assert_eq!(registry.len_of(&db, "grids").unwrap(), 1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
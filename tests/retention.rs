@@ -0,0 +1,93 @@
+mod common;
+
+use common::{temp_dir, Order};
+use darkbird::{testing::ManualClock, Event, Options, RQuery, SessionResult, Storage, StorageType};
+use std::{sync::Arc, time::{Duration, SystemTime}};
+use tokio::sync::mpsc::{self, Receiver};
+
+
+
+async fn open(dir: &std::path::Path, max_events: usize, max_age: Duration) -> Storage<u64, Order> {
+    open_with_clock(dir, max_events, max_age, Arc::new(ManualClock::new(SystemTime::now()))).await
+}
+
+async fn open_with_clock(dir: &std::path::Path, max_events: usize, max_age: Duration, clock: Arc<ManualClock>) -> Storage<u64, Order> {
+    // reporter on
+    let ops = Options::new(dir.to_str().unwrap(), "orders", 1000, StorageType::RamCopies, false)
+        .with_event_retention(max_events, max_age)
+        .with_clock(clock);
+    Storage::open(ops).await.unwrap()
+}
+
+async fn insert(storage: &Storage<u64, Order>, keys: std::ops::Range<u64>) {
+    for key in keys {
+        storage.insert(key, Order { user: "ann".to_owned(), item: key.to_string() }).await.unwrap();
+    }
+}
+
+// sequences of replay, drained once router has nothing left to send
+async fn resume(storage: &Storage<u64, Order>, last_seen: u64) -> Result<Vec<u64>, SessionResult> {
+    let (sender, mut receiver) = mpsc::channel(1024);
+    storage.subscribe_from_seq(sender, last_seen).await?;
+    Ok(drain(&mut receiver).await)
+}
+
+async fn drain(receiver: &mut Receiver<(u64, Event<u64, Order>)>) -> Vec<u64> {
+    let mut seqs = vec![];
+    while let Ok(Some((seq, event))) = tokio::time::timeout(Duration::from_millis(50), receiver.recv()).await {
+        assert!(matches!(event, Event::Query(RQuery::Insert(..))));
+        seqs.push(seq);
+    }
+    seqs
+}
+
+#[tokio::test]
+async fn resume_at_exact_boundary() {
+    let dir = temp_dir("retention-boundary");
+    let storage = open(&dir, 10, Duration::from_secs(60)).await;
+    insert(&storage, 0..25).await;
+
+    // 16 to 25 kept: oldest kept follow last_seen, or is last_seen itself
+    assert_eq!(resume(&storage, 15).await.unwrap(), (16..=25).collect::<Vec<_>>());
+    assert_eq!(resume(&storage, 24).await.unwrap(), vec![25]);
+    assert!(matches!(resume(&storage, 14).await, Err(SessionResult::GapTooLarge { oldest_available: 16 })));
+
+    // up to date, then live events only
+    let (sender, mut receiver) = mpsc::channel(16);
+    storage.subscribe_from_seq(sender, 25).await.unwrap();
+    assert!(drain(&mut receiver).await.is_empty());
+    insert(&storage, 25..27).await;
+    assert_eq!(drain(&mut receiver).await, vec![26, 27]);
+
+    // ahead of buffer
+    assert!(matches!(resume(&storage, 28).await, Err(SessionResult::GapTooLarge { oldest_available: 18 })));
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn oldest_events_are_evicted_under_pressure() {
+    let dir = temp_dir("retention-pressure");
+    let storage = open(&dir, 100, Duration::from_secs(60)).await;
+    insert(&storage, 0..5_000).await;
+
+    // bounded by max_events however many were dispatched
+    assert_eq!(resume(&storage, 4_900).await.unwrap().len(), 100);
+    assert!(matches!(resume(&storage, 0).await, Err(SessionResult::GapTooLarge { oldest_available: 4_901 })));
+    storage.close().await.unwrap();
+
+    // and by max_age of store clock, with no dispatch since
+    let clock = Arc::new(ManualClock::new(SystemTime::now()));
+    let storage = open_with_clock(&dir, 100, Duration::from_secs(60), clock.clone()).await;
+    insert(&storage, 0..10).await;
+    assert_eq!(resume(&storage, 0).await.unwrap().len(), 10);
+    clock.advance(Duration::from_secs(60));
+    assert_eq!(resume(&storage, 0).await.unwrap().len(), 10);
+    clock.advance(Duration::from_secs(1));
+    assert!(matches!(resume(&storage, 0).await, Err(SessionResult::GapTooLarge { oldest_available: 11 })));
+    assert_eq!(resume(&storage, 10).await.unwrap(), Vec::<u64>::new());
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use darkbird::{Schema, SchemaError};
+
+
+
+// a second store of same type used to replace the first in AnyMap, orphaning its WAL session
+#[tokio::test]
+async fn duplicate_store_is_rejected_before_open() {
+    let dir = temp_dir("schema-duplicate");
+
+    let schema = Schema::new()
+        .with_datastore::<String, User>(disk_options(&dir, "users"))
+        .await
+        .unwrap();
+
+    let res = schema
+        .with_datastore::<String, User>(disk_options(&dir, "people"))
+        .await;
+
+    match res {
+        Err(SchemaError::DuplicateStore(name)) => assert!(name.contains("Storage")),
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(_) => panic!("duplicate store accepted"),
+    }
+
+    // the duplicate never opened, no WAL session and no log directory for it
+    assert!(dir.join("users").exists());
+    assert!(!dir.join("people").exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn duplicate_pending_store_is_rejected() {
+    let dir = temp_dir("schema-pending");
+
+    let res = Schema::new()
+        .add_datastore::<String, User>(disk_options(&dir, "users"))
+        .unwrap()
+        .add_datastore::<String, User>(disk_options(&dir, "people"));
+
+    assert!(matches!(res, Err(SchemaError::DuplicateStore(_))));
+    assert!(!dir.join("people").exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn open_pings_every_store() {
+    let dir = temp_dir("schema-open");
+
+    let db = Schema::new()
+        .with_datastore::<String, User>(disk_options(&dir, "users"))
+        .await
+        .unwrap()
+        .open()
+        .await
+        .unwrap();
+
+    db.insert::<String, User>("a".to_owned(), User::new("a", 20, "paris")).await.unwrap();
+    assert!(db.lookup::<String, User>(&"a".to_owned()).unwrap().is_some());
+
+    drop(db);
+    let _ = std::fs::remove_dir_all(&dir);
+}
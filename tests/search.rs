@@ -0,0 +1,107 @@
+mod common;
+
+use common::{ram_options, temp_dir, User};
+use darkbird::{Schema, SearchQuery, Storage};
+use std::{collections::HashSet, sync::Arc};
+use tokio::sync::mpsc;
+
+
+
+#[tokio::test]
+async fn database_search_async_stream_matches() {
+    let dir = temp_dir("search-db");
+    let db = Schema::new()
+        .with_datastore::<String, User>(ram_options(&dir, "users"))
+        .await
+        .unwrap()
+        .build();
+
+    for (name, city) in [("ann", "rome"), ("bob", "paris"), ("eve", "rome"), ("joe", "oslo")] {
+        db.insert::<String, User>(name.to_owned(), User::new(name, 30, city)).await.unwrap();
+    }
+
+    // capacity 1: documents must be received while postings are walked
+    let (sender, mut receiver) = mpsc::channel(1);
+    let receive = async {
+        let mut names = vec![];
+        while let Some((key, doc)) = receiver.recv().await {
+            let doc: User = doc;
+            assert_eq!(key, doc.name);
+            names.push(key);
+        }
+        names
+    };
+
+    // "rome" and "ann" both match ann, sent once
+    let (sent, names) = tokio::join!(db.search_async::<String, User>("rome ann", sender), receive);
+
+    assert_eq!(sent.unwrap(), 2);
+    assert_eq!(names.len(), 2);
+    assert_eq!(names.into_iter().collect::<HashSet<_>>(), HashSet::from(["ann".to_owned(), "eve".to_owned()]));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn search_async_stop_when_receiver_dropped() {
+    let dir = temp_dir("search-drop");
+    let storage = Arc::new(Storage::<String, User>::open(ram_options(&dir, "users")).await.unwrap());
+
+    for i in 0..50 {
+        let name = format!("user{}", i);
+        storage.insert(name.clone(), User::new(&name, 30, "rome")).await.unwrap();
+    }
+
+    let (sender, mut receiver) = mpsc::channel(1);
+    let handle = storage.search_async("rome", sender);
+
+    assert!(receiver.recv().await.is_some());
+    drop(receiver);
+
+    let sent = handle.await.unwrap();
+    assert!(sent < 50, "sent {}", sent);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn database_search_async_unknown_store() {
+    let db = Schema::new().build();
+    let (sender, _receiver) = mpsc::channel::<(String, User)>(1);
+    assert!(db.search_async::<String, User>("rome", sender).await.is_err());
+}
+
+// keys matching query, sorted
+fn matching(storage: &Storage<String, User>, query: &str) -> Vec<String> {
+    let query: SearchQuery = query.parse().unwrap();
+    let mut keys: Vec<_> = storage.search_query(&query).iter().map(|rf| rf.key().clone()).collect();
+    keys.sort();
+    keys
+}
+
+// words of an overwritten or removed version no longer match, a key back with other words
+// match only those
+#[tokio::test]
+async fn search_query_follow_insert_overwrite_remove() {
+    let dir = temp_dir("search-query-writes");
+    let storage = Storage::<String, User>::open(ram_options(&dir, "users")).await.unwrap();
+
+    storage.insert("ann".to_owned(), User::new("ann", 30, "rome")).await.unwrap();
+    storage.insert("bob".to_owned(), User::new("bob", 30, "rome")).await.unwrap();
+    assert_eq!(matching(&storage, "rome AND (ann OR bob)"), ["ann", "bob"]);
+
+    storage.update(&"ann".to_owned(), |user| *user = User::new("ann", 30, "oslo")).await.unwrap();
+    assert_eq!(matching(&storage, "rome"), ["bob"]);
+    assert_eq!(matching(&storage, "ann AND oslo"), ["ann"]);
+    assert!(matching(&storage, "ann AND rome").is_empty());
+
+    storage.remove("bob".to_owned()).await.unwrap();
+    assert!(matching(&storage, "bob OR rome").is_empty());
+
+    storage.insert("bob".to_owned(), User::new("bob", 30, "pisa")).await.unwrap();
+    assert_eq!(matching(&storage, "pisa OR oslo"), ["ann", "bob"]);
+    assert!(matching(&storage, "rome").is_empty());
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
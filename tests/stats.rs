@@ -0,0 +1,47 @@
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use darkbird::{Schema, Storage};
+
+const N: usize = 100;
+const M: usize = 30;
+
+
+
+fn user(i: usize) -> (String, User) {
+    let name = format!("user{}", i);
+    let user = User::new(&name, 20 + i as i64, ["rome", "oslo", "lima"][i % 3]);
+    (name, user)
+}
+
+#[tokio::test]
+async fn doc_count_is_inserts_minus_removes() {
+    let dir = temp_dir("stats-count");
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+    assert_eq!(storage.stats().doc_count, 0);
+
+    for i in 0..N {
+        let (name, user) = user(i);
+        storage.insert(name, user).await.unwrap();
+    }
+    for i in 0..M {
+        storage.remove(format!("user{}", i * 3)).await.unwrap();
+    }
+
+    // removing an absent key counts nothing
+    storage.remove("nobody".to_owned()).await.unwrap();
+
+    let stats = storage.stats();
+    assert_eq!(stats.doc_count, N - M);
+    assert_eq!(stats.doc_count, storage.iter().count());
+    assert_eq!(stats.tag_count, 3);
+    assert!(stats.wal_pages >= 1 && stats.wal_size_bytes > 0, "{:?}", stats);
+    storage.close().await.unwrap();
+
+    // replayed store, through Database
+    let db = Schema::new().with_datastore::<String, User>(disk_options(&dir, "users")).await.unwrap().build();
+    assert_eq!(db.stats::<String, User>().unwrap().doc_count, N - M);
+
+    db.close_all().await;
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use common::{ram_options, temp_dir, User};
+use darkbird::{ScanStream, Storage};
+use futures_core::Stream;
+use std::{future::poll_fn, path::Path, pin::Pin, time::Duration};
+
+// more than a refill of ScanStream
+const USERS: usize = 600;
+
+
+
+async fn open(dir: &Path) -> Storage<String, User> {
+    let storage = Storage::<String, User>::open(ram_options(dir, "users")).await.unwrap();
+    for i in 0..USERS {
+        let (name, city) = (format!("user{:04}", i), if i % 3 == 0 { "rome" } else { "oslo" });
+        storage.insert(name.clone(), User::new(&name, i as i64, city)).await.unwrap();
+    }
+    storage
+}
+
+async fn next(stream: &mut ScanStream<'_, String, User>) -> Option<(String, User)> {
+    poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+}
+
+async fn keys(mut stream: ScanStream<'_, String, User>) -> Vec<String> {
+    let mut keys = vec![];
+    while let Some((key, doc)) = next(&mut stream).await {
+        assert_eq!(key, doc.name);
+        keys.push(key);
+    }
+    keys
+}
+
+fn sorted(mut keys: Vec<String>) -> Vec<String> {
+    keys.sort();
+    keys
+}
+
+
+
+// streams yield what matching lookups return, range in key order
+#[tokio::test]
+async fn streams_match_lookups() {
+    let dir = temp_dir("stream-match");
+    let storage = open(&dir).await;
+
+    let range = keys(storage.range_stream("age", 100, 500)).await;
+    assert_eq!(range.len(), 400);
+    assert!(range.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(range, sorted(storage.range("age", 100, 500).iter().map(|rf| rf.key().clone()).collect()));
+
+    let tagged = sorted(keys(storage.tag_stream("city:rome")).await);
+    assert_eq!(tagged, sorted(storage.lookup_by_tag("city:rome").iter().map(|rf| rf.key().clone()).collect()));
+    assert_eq!(tagged.len(), USERS.div_ceil(3));
+
+    let found = sorted(keys(storage.search_stream("rome")).await);
+    assert_eq!(found, tagged);
+
+    let adults = keys(storage.view_stream("adults")).await;
+    assert_eq!(adults.len(), USERS - 18);
+
+    assert!(keys(storage.tag_stream("city:none")).await.is_empty());
+    assert!(keys(storage.range_stream("age", "a", "z")).await.is_empty());
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// documents removed after match are skipped, writes go on while a stream is held,
+// dropping it early release nothing writes wait on
+#[tokio::test]
+async fn stream_skip_removed_and_drop_early() {
+    let dir = temp_dir("stream-drop");
+    let storage = open(&dir).await;
+
+    let mut stream = storage.range_stream("age", 0, USERS as i64);
+    assert_eq!(next(&mut stream).await.unwrap().0, "user0000");
+
+    // past first refill
+    let last = format!("user{:04}", USERS - 1);
+    let write = async {
+        storage.remove(last.clone()).await.unwrap();
+        storage.update(&"user0001".to_owned(), |user| user.city = "pisa".to_owned()).await.unwrap();
+    };
+    tokio::time::timeout(Duration::from_secs(5), write).await.unwrap();
+
+    let rest = keys(stream).await;
+    assert_eq!(rest.len(), USERS - 2);
+    assert!(!rest.contains(&last));
+
+    let mut stream = storage.tag_stream("city:oslo");
+    assert!(next(&mut stream).await.is_some());
+    drop(stream);
+    tokio::time::timeout(Duration::from_secs(5), storage.remove("user0002".to_owned())).await.unwrap().unwrap();
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
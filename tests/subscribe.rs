@@ -0,0 +1,108 @@
+mod common;
+
+use common::{temp_dir, Order};
+use darkbird::{ChangeMask, Event, EventFilter, KeyCodec, Options, RQuery, Storage, StorageType, TagExpr};
+use std::{path::Path, time::Duration};
+use tokio::sync::mpsc::{self, Receiver};
+
+
+
+async fn open(dir: &Path) -> Storage<u64, Order> {
+    // reporter on
+    let ops = Options::new(dir.to_str().unwrap(), "orders", 1000, StorageType::RamCopies, false);
+    Storage::open(ops).await.unwrap()
+}
+
+fn order(user: &str) -> Order {
+    Order { user: user.to_owned(), item: "pen".to_owned() }
+}
+
+// next document change received, skipping events filters always let through
+async fn next_change(receiver: &mut Receiver<Event<u64, Order>>) -> Event<u64, Order> {
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap();
+        if matches!(event, Event::Query(_) | Event::Removed(..) | Event::Replaced(..) | Event::Expired(..)) {
+            return event;
+        }
+    }
+}
+
+fn inserted_key(event: Event<u64, Order>) -> u64 {
+    match event {
+        Event::Query(RQuery::Insert(key, _)) => key,
+        _ => panic!("not an insert"),
+    }
+}
+
+
+
+// router send in dispatch order, so a match received right after non-matching
+// writes proves those were skipped
+#[tokio::test]
+async fn subscribe_matching_skip_non_matching_changes() {
+    let dir = temp_dir("subscribe-matching");
+    let storage = open(&dir).await;
+
+    let (sender, mut by_tag) = mpsc::channel(64);
+    storage.subscribe_matching(sender, EventFilter::Tags(TagExpr::tag("user:ann"))).await.unwrap();
+    // keys below 256 only, big-endian encoding start with seven zero bytes
+    assert_eq!(1u64.encode()[..7], [0; 7]);
+    let (sender, mut by_prefix) = mpsc::channel(64);
+    storage.subscribe_matching(sender, EventFilter::KeyPrefix(vec![0; 7])).await.unwrap();
+    let (sender, mut removes) = mpsc::channel(64);
+    storage.subscribe_matching(sender, EventFilter::Changes(ChangeMask::REMOVE)).await.unwrap();
+
+    storage.insert(1 << 8, order("bob")).await.unwrap();
+    storage.insert(1 << 9, order("eve")).await.unwrap();
+    storage.insert(1, order("ann")).await.unwrap();
+    storage.remove(1 << 8).await.unwrap();
+
+    assert_eq!(inserted_key(next_change(&mut by_tag).await), 1);
+    assert_eq!(inserted_key(next_change(&mut by_prefix).await), 1);
+    assert!(matches!(next_change(&mut removes).await, Event::Query(RQuery::Remove(key)) if key == 1 << 8));
+
+    // removed document of bob doesn't carry tag of ann
+    storage.insert(2, order("ann")).await.unwrap();
+    assert_eq!(inserted_key(next_change(&mut by_tag).await), 2);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// others are told of an unsubscribed subscriber, which gets nothing after it
+#[tokio::test]
+async fn unsubscribe_alert_others_and_stop_delivery() {
+    let dir = temp_dir("subscribe-unsubscribe");
+    let storage = open(&dir).await;
+
+    let (sender, mut watcher) = mpsc::channel(64);
+    storage.subscribe(sender).await.unwrap();
+    let (sender, mut leaving) = mpsc::channel(64);
+    let id = storage.subscribe(sender).await.unwrap();
+
+    storage.insert(1, order("ann")).await.unwrap();
+    assert_eq!(inserted_key(next_change(&mut leaving).await), 1);
+
+    assert!(storage.unsubscribe(id).await.unwrap());
+    assert!(!storage.unsubscribe(id).await.unwrap());
+    storage.insert(2, order("ann")).await.unwrap();
+
+    let mut dropped = None;
+    while let Some(event) = watcher.recv().await {
+        match event {
+            Event::SubscriberDropped(gone) => dropped = Some(gone),
+            Event::Query(RQuery::Insert(2, _)) => break,
+            _ => {}
+        }
+    }
+    assert_eq!(dropped, Some(id));
+
+    // router let go of its sender, channel end once drained
+    while let Some(event) = leaving.recv().await {
+        assert!(!matches!(event, Event::Query(RQuery::Insert(2, _))));
+    }
+    assert_eq!(storage.subscriber_report().await.unwrap().len(), 1);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{temp_dir, Order};
+use darkbird::{Event, Options, RQuery, Storage, StorageType};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, Receiver, UnboundedReceiver};
+
+const WRITES: u64 = 20_000;
+const RUNS: usize = 5;
+
+
+
+async fn open(dir: &std::path::Path, name: &str) -> Storage<u64, Order> {
+    // reporter on
+    let ops = Options::new(dir.to_str().unwrap(), name, 1000, StorageType::RamCopies, false);
+    Storage::open(ops).await.unwrap()
+}
+
+// subscriber reading as fast as router send, inserts forwarded to returned receiver
+async fn live(storage: &Storage<u64, Order>) -> (UnboundedReceiver<()>, UnboundedReceiver<()>) {
+    let (sender, mut receiver) = mpsc::channel(1024);
+    storage.subscribe(sender).await.unwrap();
+
+    let (inserted, inserts) = mpsc::unbounded_channel();
+    let (dropped, drops) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(event) = receiver.recv().await {
+            let _ = match event {
+                Event::Query(RQuery::Insert(..)) => inserted.send(()),
+                Event::SubscriberDropped(_) => dropped.send(()),
+                _ => Ok(()),
+            };
+        }
+    });
+    (inserts, drops)
+}
+
+// writes until the live subscriber received every one of them
+async fn run(storage: &Storage<u64, Order>, inserts: &mut UnboundedReceiver<()>) -> Duration {
+    let started = Instant::now();
+    for key in 0..WRITES {
+        storage.insert(key, Order { user: "ann".to_owned(), item: key.to_string() }).await.unwrap();
+    }
+    for _ in 0..WRITES {
+        inserts.recv().await.unwrap();
+    }
+    started.elapsed()
+}
+
+async fn median(storage: &Storage<u64, Order>, inserts: &mut UnboundedReceiver<()>) -> Duration {
+    run(storage, inserts).await;
+
+    let mut runs = vec![];
+    for _ in 0..RUNS {
+        runs.push(run(storage, inserts).await);
+    }
+    runs.sort();
+    runs[RUNS / 2]
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn dropped_receivers_dont_slow_writes() {
+    let dir = temp_dir("subscribers-dropped");
+
+    let baseline = open(&dir, "baseline").await;
+    let (mut inserts, _) = live(&baseline).await;
+    let single = median(&baseline, &mut inserts).await;
+    baseline.close().await.unwrap();
+
+    let storage = open(&dir, "crowded").await;
+    let (mut inserts, mut drops) = live(&storage).await;
+    let mut dropped: Vec<Receiver<Event<u64, Order>>> = vec![];
+    for _ in 0..99 {
+        let (sender, receiver) = mpsc::channel(1024);
+        storage.subscribe(sender).await.unwrap();
+        dropped.push(receiver);
+    }
+    drop(dropped);
+
+    // first write find them closed, remaining subscriber is told of each
+    storage.insert(WRITES, Order { user: "bob".to_owned(), item: "pen".to_owned() }).await.unwrap();
+    for _ in 0..99 {
+        drops.recv().await.unwrap();
+    }
+    inserts.recv().await.unwrap();
+    assert_eq!(storage.subscriber_report().await.unwrap().len(), 1);
+
+    let pruned = median(&storage, &mut inserts).await;
+    assert!(
+        pruned.as_secs_f64() <= single.as_secs_f64() * 1.05,
+        "one subscriber {:?}, after dropping 99 of 100 {:?}",
+        single,
+        pruned
+    );
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use darkbird::{SessionResult, Storage};
+use std::path::Path;
+
+
+
+async fn open(dir: &Path) -> Storage<String, User> {
+    Storage::<String, User>::open(disk_options(dir, "users")).await.unwrap()
+}
+
+// keys of documents, sorted with duplicates kept
+fn keys(refs: Vec<dashmap::mapref::one::Ref<'_, String, User>>) -> Vec<String> {
+    let mut keys: Vec<_> = refs.iter().map(|rf| rf.key().clone()).collect();
+    keys.sort();
+    keys
+}
+
+fn tagged(storage: &Storage<String, User>, tag: &str) -> Vec<String> {
+    keys(storage.lookup_by_tag(tag))
+}
+
+fn tags_of(storage: &Storage<String, User>, key: &str) -> Vec<String> {
+    let mut tags = storage.tags_of(&key.to_owned()).unwrap();
+    tags.sort();
+    tags
+}
+
+
+
+// added and removed tags hold over updates of document and WAL replay
+#[tokio::test]
+async fn tag_edits_survive_updates_and_replay() {
+    let dir = temp_dir("tags-edits");
+    let storage = open(&dir).await;
+    let ann = "ann".to_owned();
+    storage.insert(ann.clone(), User::new("ann", 30, "rome")).await.unwrap();
+    storage.insert("bob".to_owned(), User::new("bob", 20, "rome")).await.unwrap();
+
+    storage.add_tag(&ann, "vip").await.unwrap();
+    storage.remove_tag(&ann, "city:rome").await.unwrap();
+    assert_eq!(tagged(&storage, "vip"), ["ann"]);
+    assert_eq!(tagged(&storage, "city:rome"), ["bob"]);
+
+    storage.update(&ann, |user| user.age += 1).await.unwrap();
+    assert_eq!(tags_of(&storage, "ann"), ["vip"]);
+    storage.close().await.unwrap();
+
+    let storage = open(&dir).await;
+    assert_eq!(tags_of(&storage, "ann"), ["vip"]);
+    assert_eq!(tagged(&storage, "city:rome"), ["bob"]);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn tag_edits_of_missing_key_and_emptied_tag() {
+    let dir = temp_dir("tags-missing");
+    let storage = open(&dir).await;
+    let ann = "ann".to_owned();
+    storage.insert(ann.clone(), User::new("ann", 30, "rome")).await.unwrap();
+
+    for result in [storage.add_tag(&"bob".to_owned(), "vip").await, storage.remove_tag(&"bob".to_owned(), "vip").await] {
+        assert!(matches!(result, Err(SessionResult::KeyNotFound)), "{:?}", result);
+    }
+    assert!(storage.tags_of(&"bob".to_owned()).is_none());
+
+    // a tag left without documents is dropped
+    storage.add_tag(&ann, "vip").await.unwrap();
+    storage.remove_tag(&ann, "vip").await.unwrap();
+    assert!(storage.iter_tags().all(|tag| tag.key() != "vip"));
+
+    // dropped with document
+    storage.add_tag(&ann, "vip").await.unwrap();
+    storage.remove(ann.clone()).await.unwrap();
+    assert!(tagged(&storage, "vip").is_empty());
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// all is intersection and any union of tag sets, each document once
+#[tokio::test]
+async fn tags_all_and_any_are_set_operations() {
+    let dir = temp_dir("tags-all-any");
+    let storage = open(&dir).await;
+    for (name, city) in [("ann", "rome"), ("bob", "rome"), ("eve", "oslo"), ("joe", "pisa")] {
+        storage.insert(name.to_owned(), User::new(name, 30, city)).await.unwrap();
+    }
+    for name in ["ann", "eve"] {
+        storage.add_tag(&name.to_owned(), "vip").await.unwrap();
+    }
+
+    assert_eq!(keys(storage.lookup_by_tags_all(&["city:rome", "vip"])), ["ann"]);
+    assert_eq!(keys(storage.lookup_by_tags_all(&["vip", "vip"])), ["ann", "eve"]);
+    assert!(storage.lookup_by_tags_all(&["city:rome", "unknown"]).is_empty());
+    assert!(storage.lookup_by_tags_all(&[]).is_empty());
+
+    assert_eq!(keys(storage.lookup_by_tags_any(&["city:rome", "vip"])), ["ann", "bob", "eve"]);
+    assert_eq!(keys(storage.lookup_by_tags_any(&["vip", "unknown", "vip"])), ["ann", "eve"]);
+    assert!(storage.lookup_by_tags_any(&[]).is_empty());
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
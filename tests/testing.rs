@@ -0,0 +1,134 @@
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use darkbird::{
+    testing::{FaultyWal, ManualClock, SlowSubscriber},
+    ClampedClock, Clock, Event, Options, SessionResult, Storage, StorageType,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+
+
+#[tokio::test]
+async fn faulty_wal_fail_nth_append() {
+    let dir = temp_dir("faulty-nth");
+    let wal = FaultyWal::new().fail_nth_append(2);
+
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users").with_faulty_wal(wal.clone()))
+        .await
+        .unwrap();
+
+    storage.insert("a".to_owned(), User::new("a", 20, "rome")).await.unwrap();
+    let res = storage.insert("b".to_owned(), User::new("b", 21, "rome")).await;
+    storage.insert("c".to_owned(), User::new("c", 22, "rome")).await.unwrap();
+
+    assert!(matches!(res, Err(SessionResult::Err(_))));
+    assert!(storage.lookup_owned(&"b".to_owned()).is_none());
+    assert_eq!(storage.len(), 2);
+
+    assert_eq!(wal.appends(), 3);
+    assert_eq!(wal.failed(), 1);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn faulty_wal_fail_after_bytes() {
+    let dir = temp_dir("faulty-bytes");
+    let wal = FaultyWal::new().fail_after_bytes(1);
+
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users").with_faulty_wal(wal.clone()))
+        .await
+        .unwrap();
+
+    assert!(storage.insert("a".to_owned(), User::new("a", 20, "rome")).await.is_err());
+    assert!(storage.is_empty());
+    assert_eq!(wal.bytes(), 0);
+    assert_eq!(wal.failed(), 1);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn faulty_wal_latency_delay_append() {
+    let dir = temp_dir("faulty-latency");
+    let wal = FaultyWal::new().with_latency(Duration::from_millis(30));
+
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users").with_faulty_wal(wal.clone()))
+        .await
+        .unwrap();
+
+    let started = Instant::now();
+    storage.insert("a".to_owned(), User::new("a", 20, "rome")).await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(30));
+    assert!(wal.bytes() > 0);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn slow_subscriber_receive_every_event() {
+    let dir = temp_dir("slow-subscriber");
+    let ops = Options::new(dir.to_str().unwrap(), "users", 1000, StorageType::RamCopies, false);
+    let storage = Storage::<String, User>::open(ops).await.unwrap();
+
+    let (sender, subscriber) = SlowSubscriber::spawn::<Event<String, User>>(1, Duration::from_millis(5));
+    storage.subscribe(sender).await.unwrap();
+
+    for i in 0..5 {
+        storage.insert(format!("u{}", i), User::new(&format!("u{}", i), i, "rome")).await.unwrap();
+    }
+
+    // inserts only, Subscribed go to others
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while subscriber.received() < 5 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert_eq!(subscriber.received(), 5);
+
+    storage.close().await.unwrap();
+    assert_eq!(subscriber.closed().await, 5);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn manual_clock_drive_store_clock() {
+    let dir = temp_dir("manual-clock");
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let manual = ManualClock::new(start);
+
+    let ops = disk_options(&dir, "users").with_clock(Arc::new(manual.clone()));
+    let storage = Storage::<String, User>::open(ops).await.unwrap();
+
+    assert_eq!(storage.now(), start);
+    manual.advance(Duration::from_secs(60));
+    assert_eq!(storage.now(), start + Duration::from_secs(60));
+
+    manual.set(start);
+    assert_eq!(storage.now(), start);
+
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn manual_clock_step_back_is_clamped() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let manual = ManualClock::new(start);
+    let clamped = ClampedClock::new(Arc::new(manual.clone()));
+
+    let before = clamped.now();
+    manual.step_back(Duration::from_secs(30));
+
+    assert!(clamped.now() >= before);
+    assert_eq!(clamped.backward_jumps(), 1);
+
+    manual.advance(Duration::from_secs(90));
+    assert!(clamped.now() >= start + Duration::from_secs(60));
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::{temp_dir, Order};
+use darkbird::{testing::ManualClock, Clock, Event, Options, Storage, StorageType};
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::sync::mpsc::{self, Receiver};
+
+
+
+async fn open(dir: &Path, clock: &Arc<ManualClock>) -> (Storage<String, Order>, Receiver<Event<String, Order>>) {
+    let ops = Options::new(dir.to_str().unwrap(), "jobs", 1000, StorageType::DiskCopies, false)
+        .with_timers()
+        .with_clock(clock.clone());
+    let storage = Storage::open(ops).await.unwrap();
+
+    let (sender, receiver) = mpsc::channel(64);
+    storage.subscribe(sender).await.unwrap();
+    (storage, receiver)
+}
+
+// keys of timers fired within window
+async fn fired(receiver: &mut Receiver<Event<String, Order>>, window: Duration) -> Vec<String> {
+    let mut keys = vec![];
+    let until = tokio::time::Instant::now() + window;
+    while let Ok(Some(event)) = tokio::time::timeout_at(until, receiver.recv()).await {
+        if let Event::Timer(key) = event {
+            keys.push(key);
+        }
+    }
+    keys.sort();
+    keys
+}
+
+// files of store as a crash would leave them
+fn copy_dir(from: &Path, to: &Path) {
+    std::fs::create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        match entry.file_type().unwrap().is_dir() {
+            true => copy_dir(&entry.path(), &target),
+            false => {
+                std::fs::copy(entry.path(), target).unwrap();
+            }
+        }
+    }
+}
+
+// before crash: done fired, again fired then re-armed, later pending, all due while down.
+// each due timer fire once after restart, done never again
+#[tokio::test]
+async fn fired_timers_dont_fire_again_after_crash() {
+    let dir = temp_dir("timer-crash");
+    let live = dir.join("live");
+    std::fs::create_dir_all(&live).unwrap();
+    let clock = Arc::new(ManualClock::new(SystemTime::now()));
+    let (storage, mut receiver) = open(&live, &clock).await;
+
+    let now = clock.now();
+    storage.notify_at("done".to_owned(), now).await.unwrap();
+    storage.notify_at("again".to_owned(), now).await.unwrap();
+    storage.notify_at("later".to_owned(), now + Duration::from_millis(200)).await.unwrap();
+    assert_eq!(fired(&mut receiver, Duration::from_millis(100)).await, vec!["again", "done"]);
+
+    storage.notify_at("again".to_owned(), now + Duration::from_millis(200)).await.unwrap();
+
+    // timer WAL closed with no record of its own, so its files are those of a crash
+    // once Fired and Arm records are flushed
+    storage.close().await.unwrap();
+    let crashed = dir.join("crashed");
+    copy_dir(&live, &crashed);
+
+    clock.advance(Duration::from_millis(200));
+    let (storage, mut receiver) = open(&crashed, &clock).await;
+    assert_eq!(fired(&mut receiver, Duration::from_millis(300)).await, vec!["again", "later"]);
+    storage.close().await.unwrap();
+
+    let (storage, mut receiver) = open(&crashed, &clock).await;
+    assert!(fired(&mut receiver, Duration::from_millis(300)).await.is_empty());
+    storage.close().await.unwrap();
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use common::{disk_options, temp_dir, Order, User};
+use darkbird::{testing::FaultyWal, Database, Options, Schema, SessionResult};
+use std::path::Path;
+
+
+
+async fn open(dir: &Path, orders: Options) -> Database {
+    Schema::new()
+        .with_datastore::<String, User>(disk_options(dir, "users"))
+        .await
+        .unwrap()
+        .with_datastore::<u64, Order>(orders)
+        .await
+        .unwrap()
+        .build()
+}
+
+fn state(db: &Database) -> (Option<i64>, bool, bool) {
+    let ann = db.lookup_owned::<String, User>(&"ann".to_owned()).unwrap().map(|user| user.age);
+    let bob = db.lookup_owned::<String, User>(&"bob".to_owned()).unwrap().is_some();
+    let order = db.lookup_owned::<u64, Order>(&1).unwrap().is_some();
+    (ann, bob, order)
+}
+
+async fn buy(db: &Database) -> Result<(), SessionResult> {
+    db.cross_transaction(async |tx| {
+        tx.update::<String, User, _>("ann".to_owned(), |user| user.age += 1);
+        tx.insert::<String, User>("bob".to_owned(), User::new("bob", 20, "oslo"));
+        tx.insert::<u64, Order>(1, Order { user: "bob".to_owned(), item: "pen".to_owned() });
+        Ok(())
+    })
+    .await
+}
+
+
+
+// orders record is queued, written, then reported failed by disk_log:
+// users logged before it and orders itself are logged back
+#[tokio::test]
+async fn failed_write_after_enqueue_roll_back_every_store() {
+    let dir = temp_dir("transaction-write");
+    let wal = FaultyWal::new().fail_nth_write(1);
+    let db = open(&dir, disk_options(&dir, "orders").with_faulty_wal(wal.clone())).await;
+
+    db.insert::<String, User>("ann".to_owned(), User::new("ann", 30, "rome")).await.unwrap();
+
+    assert!(matches!(buy(&db).await, Err(SessionResult::Err(_))));
+    assert_eq!(wal.failed(), 1);
+    assert_eq!(state(&db), (Some(30), false, false));
+
+    // both records reached disk, replay undo them
+    db.close_all().await;
+    let db = open(&dir, disk_options(&dir, "orders")).await;
+    assert_eq!(state(&db), (Some(30), false, false));
+
+    // and a committed transaction replay whole
+    buy(&db).await.unwrap();
+    assert_eq!(state(&db), (Some(31), true, true));
+    db.close_all().await;
+    let db = open(&dir, disk_options(&dir, "orders")).await;
+    assert_eq!(state(&db), (Some(31), true, true));
+
+    db.close_all().await;
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn failed_enqueue_write_nothing() {
+    let dir = temp_dir("transaction-enqueue");
+    let wal = FaultyWal::new().fail_nth_append(1);
+    let db = open(&dir, disk_options(&dir, "orders").with_faulty_wal(wal.clone())).await;
+
+    db.insert::<String, User>("ann".to_owned(), User::new("ann", 30, "rome")).await.unwrap();
+
+    assert!(buy(&db).await.is_err());
+    assert_eq!(state(&db), (Some(30), false, false));
+
+    db.close_all().await;
+    let db = open(&dir, disk_options(&dir, "orders")).await;
+    assert_eq!(state(&db), (Some(30), false, false));
+
+    db.close_all().await;
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// reads interleaved with transactions that write a user and its order together,
+// or remove both: every view see both or neither
+#[tokio::test]
+async fn consistent_read_never_see_half_a_transaction() {
+    let dir = temp_dir("transaction-consistent");
+    let db = open(&dir, disk_options(&dir, "orders")).await;
+    let done = std::cell::Cell::new(false);
+
+    let writer = async {
+        for id in 0..200u64 {
+            let name = format!("user{}", id % 10);
+            match id % 20 < 10 {
+                true => db.cross_transaction(async |tx| {
+                    tx.insert::<String, User>(name.clone(), User::new(&name, 20, "oslo"));
+                    tx.insert::<u64, Order>(id % 10, Order { user: name.clone(), item: "pen".to_owned() });
+                    Ok(())
+                }).await.unwrap(),
+                false => db.cross_transaction(async |tx| {
+                    tx.remove::<String, User>(name.clone());
+                    tx.remove::<u64, Order>(id % 10);
+                    Ok(())
+                }).await.unwrap(),
+            }
+        }
+        done.set(true);
+    };
+
+    let reader = async {
+        let mut reads = 0;
+        while !done.get() {
+            let (users, orders) = db.consistent_read(|view| {
+                let users = view.documents::<String, User>().unwrap();
+                let orders = view.documents::<u64, Order>().unwrap();
+                for (_, order) in orders.iter() {
+                    assert!(view.lookup::<String, User>(&order.user).unwrap().is_some(), "{:?}", order);
+                }
+                assert!(view.pause() < std::time::Duration::from_millis(250));
+                (users.len(), orders.len())
+            }).await.unwrap();
+            assert_eq!(users, orders);
+            reads += 1;
+            tokio::task::yield_now().await;
+        }
+        reads
+    };
+
+    let ((), reads) = tokio::join!(writer, reader);
+    assert!(reads > 10, "{} reads", reads);
+
+    db.close_all().await;
+    let _ = std::fs::remove_dir_all(&dir);
+}
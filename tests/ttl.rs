@@ -0,0 +1,97 @@
+mod common;
+
+use common::{disk_options, temp_dir, User};
+use darkbird::{
+    testing::{FaultyWal, ManualClock},
+    Event, Options, RQuery, Storage, StorageType,
+};
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::sync::mpsc;
+
+const TTL: Duration = Duration::from_secs(60);
+
+async fn open(dir: &Path, clock: &ManualClock) -> Storage<String, User> {
+    // reporter on
+    let ops = Options::new(dir.to_str().unwrap(), "users", 1000, StorageType::DiskCopies, false)
+        .with_clock(Arc::new(clock.clone()));
+    Storage::open(ops).await.unwrap()
+}
+
+
+
+// key written again while expire_due wait for its lock: new version isn't expired
+#[tokio::test]
+async fn write_between_due_and_remove_is_kept() {
+    let dir = temp_dir("ttl-reinsert");
+    let clock = ManualClock::new(SystemTime::now());
+    let wal = FaultyWal::new().with_latency(Duration::from_millis(50));
+    let ops = disk_options(&dir, "users").with_clock(Arc::new(clock.clone())).with_faulty_wal(wal);
+    let storage = Storage::<String, User>::open(ops).await.unwrap();
+
+    let key = "ann".to_owned();
+    storage.insert_with_ttl(key.clone(), User::new("ann", 1, "rome"), TTL).await.unwrap();
+
+    // update hold lock of key across its WAL append, deadline pass meanwhile
+    let (updated, expired) = tokio::join!(
+        storage.update(&key, |user| user.age = 2),
+        async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            clock.advance(TTL * 2);
+            storage.expire_due().await
+        }
+    );
+    assert!(updated.unwrap());
+    assert_eq!(expired, 0);
+    assert_eq!(storage.lookup_owned(&key).unwrap().age, 2);
+    assert!(storage.expires_at(&key).is_none());
+    storage.close().await.unwrap();
+
+    // no remove logged after update
+    let storage = Storage::<String, User>::open(disk_options(&dir, "users")).await.unwrap();
+    assert_eq!(storage.lookup_owned(&key).unwrap().age, 2);
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// nothing written after deadline: expiry service log removal and tell subscribers
+#[tokio::test]
+async fn expiry_fire_without_writes() {
+    let dir = temp_dir("ttl-idle");
+    let written = SystemTime::now();
+    let clock = ManualClock::new(written);
+    let storage = open(&dir, &clock).await;
+
+    let (sender, mut events) = mpsc::channel(16);
+    storage.subscribe(sender).await.unwrap();
+
+    let key = "ann".to_owned();
+    storage.insert_with_ttl(key.clone(), User::new("ann", 1, "rome"), TTL).await.unwrap();
+    clock.advance(TTL * 2);
+
+    let mut removed = false;
+    let expired = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(event) = events.recv().await {
+            match event {
+                Event::Query(RQuery::Remove(k)) if k == key => removed = true,
+                Event::Expired(k) if k == key => return true,
+                _ => {}
+            }
+        }
+        false
+    });
+    assert!(expired.await.unwrap());
+    assert!(removed);
+    assert!(storage.lookup_owned(&key).is_none());
+    assert_eq!(storage.len(), 0);
+    storage.close().await.unwrap();
+
+    // removal is on WAL: gone even on a clock before its deadline
+    let storage = open(&dir, &ManualClock::new(written)).await;
+    assert!(storage.lookup_owned(&key).is_none());
+    storage.close().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use common::{disk_options, temp_dir};
+use darkbird::{
+    document::{Document, FullText, Indexer, MaterializedView, Range, Tags},
+    Storage, Structure,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+
+
+/// three variants sharing `user` and `at`, two sharing `item`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum Activity {
+    Login { user: String, at: u64 },
+    Purchase { user: String, at: u64, item: String },
+    Refund { user: String, at: u64, item: String },
+}
+
+impl Document for Activity {
+    const VARIANTS: &'static [&'static str] = &["Login", "Purchase", "Refund"];
+
+    fn variant(&self) -> Option<&'static str> {
+        match self {
+            Activity::Login { .. } => Some("Login"),
+            Activity::Purchase { .. } => Some("Purchase"),
+            Activity::Refund { .. } => Some("Refund"),
+        }
+    }
+}
+
+impl Indexer for Activity {
+    fn extract(&self) -> Vec<String> {
+        match self {
+            Activity::Login { user, at } => vec![format!("login:{}:{}", user, at)],
+            Activity::Purchase { item, at, .. } => vec![format!("purchase:{}:{}", item, at)],
+            Activity::Refund { .. } => vec![],
+        }
+    }
+}
+
+impl Tags for Activity {
+    fn get_tags(&self) -> Vec<String> {
+        match self {
+            Activity::Login { user, .. } => vec![format!("user:{}", user)],
+            Activity::Purchase { user, item, .. } | Activity::Refund { user, item, .. } => {
+                vec![format!("user:{}", user), format!("item:{}", item)]
+            }
+        }
+    }
+}
+
+impl Range for Activity {}
+
+impl MaterializedView for Activity {
+    fn filter(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FullText for Activity {
+    fn get_content(&self) -> Option<String> {
+        None
+    }
+}
+
+
+
+async fn open(dir: &Path) -> Result<Storage<u64, Activity>, String> {
+    Storage::open(disk_options(dir, "activity")).await
+}
+
+fn counts(storage: &Storage<u64, Activity>) -> Vec<(&'static str, usize)> {
+    storage.count_by_variant()
+}
+
+fn keys_of(storage: &Storage<u64, Activity>, variant: &str) -> Vec<u64> {
+    let mut keys: Vec<u64> = storage.lookup_by_variant(variant).iter().map(|entry| *entry.key()).collect();
+    keys.sort();
+    keys
+}
+
+#[tokio::test]
+async fn enum_documents_are_grouped_by_variant() {
+    let dir = temp_dir("variant-groups");
+    let storage = open(&dir).await.unwrap();
+
+    let ann = || "ann".to_owned();
+    storage.insert(1, Activity::Login { user: ann(), at: 10 }).await.unwrap();
+    storage.insert(2, Activity::Purchase { user: ann(), at: 11, item: "pen".to_owned() }).await.unwrap();
+    storage.insert(3, Activity::Purchase { user: "bob".to_owned(), at: 12, item: "ink".to_owned() }).await.unwrap();
+    storage.insert(4, Activity::Login { user: "bob".to_owned(), at: 13 }).await.unwrap();
+    assert_eq!(counts(&storage), vec![("Login", 2), ("Purchase", 2), ("Refund", 0)]);
+
+    // same key and fields, other variant: leaves group of old one
+    storage.insert(2, Activity::Refund { user: ann(), at: 11, item: "pen".to_owned() }).await.unwrap();
+    storage.remove(4).await.unwrap();
+    assert_eq!(counts(&storage), vec![("Login", 1), ("Purchase", 1), ("Refund", 1)]);
+    assert_eq!(keys_of(&storage, "Purchase"), vec![3]);
+    assert_eq!(keys_of(&storage, "Refund"), vec![2]);
+    assert!(keys_of(&storage, "Logout").is_empty());
+
+    // per variant indexes and overlapping tags
+    assert!(storage.lookup_by_index("login:ann:10").is_some());
+    assert!(storage.lookup_by_index("purchase:ink:12").is_some());
+    assert_eq!(storage.lookup_by_tag("user:ann").len(), 2);
+    assert_eq!(storage.lookup_by_tag("item:pen").len(), 1);
+
+    let report = storage.audit(false).await;
+    // variant groups are tag entries, index keys of replaced Purchase stay as with any overwrite
+    let mut entries = report.missing.iter().chain(report.stale.iter()).chain(report.orphaned.iter());
+    assert!(entries.all(|entry| !matches!(entry.structure, Structure::Tags)), "{:?}", report.stale);
+
+    storage.close().await.unwrap();
+    let storage = open(&dir).await.unwrap();
+    assert_eq!(counts(&storage), vec![("Login", 1), ("Purchase", 1), ("Refund", 1)]);
+    assert_eq!(keys_of(&storage, "Login"), vec![1]);
+    storage.close().await.unwrap();
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn renamed_variant_fail_open() {
+    let dir = temp_dir("variant-rename");
+    let storage = open(&dir).await.unwrap();
+    storage.insert(1, Activity::Refund { user: "ann".to_owned(), at: 1, item: "pen".to_owned() }).await.unwrap();
+    storage.close().await.unwrap();
+
+    // as recorded by a build where Refund was named Return
+    let schema = dir.join("activity.schema");
+    let recorded = std::fs::read_to_string(&schema).unwrap().replace("\"Refund\"", "\"Return\"");
+    std::fs::write(&schema, recorded).unwrap();
+
+    let err = open(&dir).await.err().unwrap();
+    assert!(err.starts_with("SchemaMismatch") && err.contains("Return"), "{}", err);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use common::User;
+use darkbird::{
+    CompactPhase, CompactReport, DocPatch, Event, RQuery, SubscriberInfo, SubscriptionId, WireCodec, WIRE_VERSION,
+};
+use serde_json::{json, Value};
+use std::{path::PathBuf, time::Duration};
+use tokio::sync::mpsc;
+
+
+
+// golden encodings in tests/fixtures/wire, one file per event.
+// a changed fixture is a change of wire schema: only fields may be added, see wire module.
+// WIRE_BLESS=1 cargo test --test wire rewrite them
+fn events() -> Vec<(&'static str, Event<String, User>)> {
+    let ann = || User::new("ann", 30, "rome");
+    let older = User::new("ann", 29, "rome");
+    let (sender, _) = mpsc::channel(1);
+
+    vec![
+        ("insert", Event::Query(RQuery::Insert("ann".to_owned(), ann()))),
+        ("update", Event::Query(RQuery::Update("ann".to_owned(), ann()))),
+        ("patch_merge", Event::Query(RQuery::Patch("ann".to_owned(), DocPatch::merge(json!({ "age": 30 })), ann()))),
+        ("patch_custom", Event::Query(RQuery::Patch("ann".to_owned(), DocPatch::custom(vec![1, 2]), ann()))),
+        ("remove", Event::Query(RQuery::Remove("ann".to_owned()))),
+        ("replaced", Event::Replaced("ann".to_owned(), older.clone(), ann())),
+        ("removed", Event::Removed("ann".to_owned(), older)),
+        ("clear", Event::Query(RQuery::Clear)),
+        ("tag_add", Event::Query(RQuery::TagAdd("ann".to_owned(), "vip".to_owned()))),
+        ("tag_remove", Event::Query(RQuery::TagRemove("ann".to_owned(), "vip".to_owned()))),
+        (
+            "lagging",
+            Event::Lagging(SubscriberInfo {
+                id: SubscriptionId(3),
+                sent: 120,
+                occupancy: 60,
+                capacity: 64,
+                last_send: None,
+                blocked: Duration::from_millis(250),
+                skipped: 7,
+                filter: None,
+                predicate: None,
+            }),
+        ),
+        ("subscribed", Event::Subscribed(sender)),
+        ("subscriber_dropped", Event::SubscriberDropped(SubscriptionId(3))),
+        ("timer", Event::Timer("ann".to_owned())),
+        ("bulk_remove", Event::BulkRemove(vec!["ann".to_owned(), "bob".to_owned()])),
+        ("expired", Event::Expired("ann".to_owned())),
+        ("quarantined", Event::Quarantined("ann".to_owned())),
+        ("compacting_started", Event::Compacting(CompactPhase::Started)),
+        ("compacting_copied", Event::Compacting(CompactPhase::Copied { records: 42 })),
+        (
+            "compacting_finished",
+            Event::Compacting(CompactPhase::Finished(CompactReport {
+                records: 42,
+                pages_before: 9,
+                pages_after: 2,
+                pause: Duration::from_millis(15),
+                duration: Duration::from_millis(80),
+            })),
+        ),
+    ]
+}
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/wire").join(format!("{}.json", name))
+}
+
+
+
+#[test]
+fn encoding_match_golden_fixtures() {
+    let bless = std::env::var_os("WIRE_BLESS").is_some();
+
+    for (name, event) in events() {
+        let wire = event.to_wire(WireCodec::Json);
+        let encoded: Value = serde_json::from_slice(&wire).unwrap();
+
+        if bless {
+            let pretty = serde_json::to_string_pretty(&encoded).unwrap();
+            std::fs::write(fixture(name), pretty + "\n").unwrap();
+            continue;
+        }
+
+        let golden = std::fs::read(fixture(name)).unwrap_or_else(|e| panic!("fixture {}: {}", name, e));
+        let golden: Value = serde_json::from_slice(&golden).unwrap();
+        assert_eq!(encoded, golden, "event {}", name);
+
+        // fields sorted, compact golden is byte for byte the encoding
+        assert_eq!(serde_json::to_vec(&golden).unwrap(), wire, "event {}", name);
+    }
+}
+
+#[test]
+fn fixtures_decode_to_events_they_encode() {
+    for (name, event) in events() {
+        let golden: Value = serde_json::from_slice(&std::fs::read(fixture(name)).unwrap()).unwrap();
+        assert_eq!(golden["v"], json!(WIRE_VERSION), "event {}", name);
+
+        match event {
+            Event::Query(RQuery::Insert(key, doc) | RQuery::Update(key, doc) | RQuery::Patch(key, _, doc)) => {
+                assert_eq!(golden["type"], "change");
+                assert_eq!(serde_json::from_value::<String>(golden["key"].clone()).unwrap(), key);
+                assert_eq!(serde_json::from_value::<User>(golden["after"].clone()).unwrap(), doc);
+                assert!(golden["before"].is_null() && golden["seq"].is_null() && golden["ts"].is_null());
+            }
+            Event::Replaced(key, old, doc) => {
+                assert_eq!(golden["op"], "update");
+                assert_eq!(serde_json::from_value::<String>(golden["key"].clone()).unwrap(), key);
+                assert_eq!(serde_json::from_value::<User>(golden["before"].clone()).unwrap(), old);
+                assert_eq!(serde_json::from_value::<User>(golden["after"].clone()).unwrap(), doc);
+            }
+            Event::Removed(key, old) => {
+                assert_eq!(golden["op"], "remove");
+                assert_eq!(serde_json::from_value::<String>(golden["key"].clone()).unwrap(), key);
+                assert_eq!(serde_json::from_value::<User>(golden["before"].clone()).unwrap(), old);
+                assert!(golden["after"].is_null());
+            }
+            Event::Query(RQuery::Remove(key)) => {
+                assert_eq!(golden["op"], "remove");
+                assert_eq!(serde_json::from_value::<String>(golden["key"].clone()).unwrap(), key);
+                assert!(golden["before"].is_null() && golden["after"].is_null());
+            }
+            Event::BulkRemove(keys) => {
+                assert_eq!(serde_json::from_value::<Vec<String>>(golden["keys"].clone()).unwrap(), keys);
+            }
+            Event::Lagging(info) => {
+                assert_eq!(golden["subscriber"], json!(info.id.0));
+                assert_eq!(golden["blocked_ms"], json!(info.blocked.as_millis() as u64));
+            }
+            _ => assert!(golden["type"].is_string(), "event {}", name),
+        }
+    }
+
+    // merge patch carried as json, custom patch opaque
+    let merge: Value = serde_json::from_slice(&std::fs::read(fixture("patch_merge")).unwrap()).unwrap();
+    assert_eq!(merge["patch"], json!({ "age": 30 }));
+    let custom: Value = serde_json::from_slice(&std::fs::read(fixture("patch_custom")).unwrap()).unwrap();
+    assert!(custom["patch"].is_null());
+}